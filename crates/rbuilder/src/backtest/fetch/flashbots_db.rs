@@ -108,6 +108,9 @@ impl RelayDB {
                         min_timestamp: min_timestamp.map(|ts| ts.try_into().unwrap_or_default()),
                         max_timestamp: None,
                         replacement_nonce: replacement_uuid.and(Some(0)),
+                        private: false,
+                        max_block_position: None,
+                        top_of_block: false,
                     };
 
                     let sim_value = coinbase_diff.zip(total_gas_used).and_then(|(cb, gas)| {
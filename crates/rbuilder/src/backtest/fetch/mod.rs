@@ -18,6 +18,7 @@ use alloy_rpc_types::{Block, BlockId, BlockNumberOrTag};
 use eyre::WrapErr;
 use flashbots_db::RelayDB;
 use futures::TryStreamExt;
+use revm::primitives::BlobExcessGasAndPrice;
 use sqlx::PgPool;
 use std::{
     collections::HashMap,
@@ -105,10 +106,14 @@ impl HistoricalDataFetcher {
     fn filter_orders_by_base_fee(
         &self,
         block_base_fee: u128,
+        blob_base_fee: Option<u128>,
         orders: &mut Vec<OrdersWithTimestamp>,
     ) {
         orders.retain(|order| {
-            if !order.order.can_execute_with_block_base_fee(block_base_fee) {
+            if !order
+                .order
+                .can_execute_with_block_base_fee(block_base_fee, blob_base_fee)
+            {
                 trace!("Order base fee too low, order: {:?}", order.order.id());
                 false
             } else {
@@ -221,7 +226,10 @@ impl HistoricalDataFetcher {
         info!("Fetched orders, unfiltered: {}", orders.len());
 
         let base_fee_per_gas = onchain_block.header.base_fee_per_gas.unwrap_or_default();
-        self.filter_orders_by_base_fee(base_fee_per_gas, &mut orders);
+        let blob_base_fee = onchain_block.header.excess_blob_gas.map(|excess_blob_gas| {
+            BlobExcessGasAndPrice::new(excess_blob_gas as u64).blob_gasprice
+        });
+        self.filter_orders_by_base_fee(base_fee_per_gas, blob_base_fee, &mut orders);
         info!("Filtered orders by base fee, left: {}", orders.len());
 
         let mut available_orders = self.filter_order_by_nonces(orders, block_number).await?;
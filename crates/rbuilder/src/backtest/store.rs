@@ -494,6 +494,9 @@ mod test {
                     min_timestamp: None,
                     max_timestamp: Some(100),
                     replacement_nonce: Some(0),
+                    private: true,
+                    max_block_position: None,
+                    top_of_block: false,
                 }),
                 sim_value: Some(SimValue {
                     coinbase_profit: U256::from(42u64),
@@ -544,6 +547,7 @@ mod test {
 
         let read_block_data = storage.read_block_data(12).await.unwrap();
         assert_eq!(read_block_data, block_data);
+        assert!(read_block_data.available_orders[1].order.metadata().private);
 
         let blocks = storage.get_blocks().await.unwrap();
         assert_eq!(blocks, vec![12]);
@@ -5,7 +5,8 @@ use clap::Parser;
 use itertools::Itertools;
 use rbuilder::{
     building::{
-        sim::simulate_all_orders_with_sim_tree, BlockBuildingContext, BlockState, PartialBlock,
+        sim::simulate_all_orders_with_sim_tree, BlockBuildingContext, BlockState, ParentSource,
+        PartialBlock,
     },
     live_builder::{base_config::load_config_toml_and_env, cli::LiveBuilderConfig, config::Config},
     primitives::{MempoolTx, Order, TransactionSignedEcRecoveredWithBlobs},
@@ -53,8 +54,8 @@ async fn main() -> eyre::Result<()> {
 
     let cfg_env = default_cfg_env(&chain, block_data.timestamp);
 
-    let ctx = BlockBuildingContext {
-        block_env: BlockEnv {
+    let ctx = BlockBuildingContext::new(
+        BlockEnv {
             number: U256::from(block_data.number),
             coinbase: signer.address,
             timestamp: U256::from(block_data.timestamp),
@@ -64,8 +65,8 @@ async fn main() -> eyre::Result<()> {
             prevrandao: Some(block_data.difficulty.into()),
             blob_excess_gas_and_price: block_data.excess_blob_gas.map(BlobExcessGasAndPrice::new),
         },
-        initialized_cfg: cfg_env,
-        attributes: EthPayloadBuilderAttributes {
+        cfg_env,
+        EthPayloadBuilderAttributes {
             id: PayloadId::new([0u8; 8]),
             parent: block_data.parent_hash,
             timestamp: block_data.timestamp,
@@ -74,13 +75,14 @@ async fn main() -> eyre::Result<()> {
             withdrawals: block_data.withdrawals.clone().unwrap_or_default(),
             parent_beacon_block_root: block_data.parent_beacon_block_root,
         },
-        chain_spec: chain.clone(),
-        builder_signer: Some(signer),
-        extra_data: Vec::new(),
-        blocklist: Default::default(),
-        excess_blob_gas: block_data.excess_blob_gas,
-        spec_id: SpecId::LATEST,
-    };
+        chain.clone(),
+        Some(signer),
+        Default::default(),
+        Vec::new(),
+        block_data.excess_blob_gas,
+        SpecId::LATEST,
+        ParentSource::Historical,
+    );
 
     // Get the landed orders (all Order::Tx) from the block
     let orders = block_data
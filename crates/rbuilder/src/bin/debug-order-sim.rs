@@ -130,6 +130,7 @@ pub async fn main() -> eyre::Result<()> {
             None,
             Vec::new(),
             None,
+            time::OffsetDateTime::now_utc(),
         );
 
         let block_cancel = CancellationToken::new();
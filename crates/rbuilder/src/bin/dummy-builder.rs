@@ -71,8 +71,11 @@ async fn main() -> eyre::Result<()> {
         cls: vec![Client::default()],
         relays: vec![relay],
         watchdog_timeout: Duration::from_secs(10000),
+        max_parent_age: None,
         error_storage_path: DEFAULT_ERROR_STORAGE_PATH.parse().unwrap(),
         simulation_threads: 1,
+        simulation_overload_filter: None,
+        simulation_priority_lane: None,
         order_input_config: OrderInputConfig::new(
             false,
             true,
@@ -93,6 +96,16 @@ async fn main() -> eyre::Result<()> {
         coinbase_signer: Signer::random(),
         extra_data: Vec::new(),
         blocklist: Default::default(),
+        builder_identity: Default::default(),
+        token_valuation: Default::default(),
+        builder_tag_schedule: None,
+        relay_identities: Default::default(),
+        gas_estimator: None,
+        pending_accounts: None,
+        pending_candidates: Default::default(),
+        canary: None,
+        kill_switch: None,
+        sbundle_mergeabe_signers: None,
         global_cancellation: cancel.clone(),
         bidding_service,
         extra_rpc: RpcModule::new(()),
@@ -216,6 +229,7 @@ impl DummyBuildingAlgorithm {
         if !should_finalize {
             return Ok(None);
         }
+        let coinbase_payment_tx_hash = partial_block.proposer_payout_tx_hash;
         let finalized_block = partial_block.finalize(
             state,
             ctx,
@@ -228,6 +242,8 @@ impl DummyBuildingAlgorithm {
             sealed_block: finalized_block.sealed_block,
             txs_blobs_sidecars: finalized_block.txs_blob_sidecars,
             builder_name: BUILDER_NAME.to_string(),
+            coinbase_payment_tx_hash,
+            root_hash_skipped: false,
         }))
     }
 }
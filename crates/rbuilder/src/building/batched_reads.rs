@@ -0,0 +1,327 @@
+//! Batched state reads for orders that tell us in advance what they are going to touch.
+//! Without this, commit_tx hits the state provider (MDBX) with many small sequential lookups (account, then each slot,
+//! then code) spread along the execution. When an order carries an access list (the tx's own EIP-2930 list or the read
+//! set of its simulation) we resolve all the listed accounts/slots in a single sorted pass before executing it filling
+//! the CachedReads so the EVM lookups are served from memory.
+//! The pass is a [`BatchStateReader`]: [`PlainStateBatchReader`] resolves the whole list with one db read tx and sorted
+//! cursor seeks on the plain state tables instead of a provider lookup per key.
+use crate::{building::evm_inspector::SlotKey, primitives::SimulatedOrder};
+use ahash::HashMap;
+use alloy_primitives::{Address, B256, U256};
+use reth::providers::{BlockHashReader, BlockNumReader, ProviderFactory};
+use reth_db::{
+    cursor::{DbCursorRO, DbDupCursorRO},
+    database::Database,
+    tables,
+    transaction::DbTx,
+};
+use reth_interfaces::provider::ProviderError;
+use revm::{
+    primitives::{AccountInfo, Bytecode, KECCAK_EMPTY},
+    DatabaseRef,
+};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Accounts and slots to load for an order, sorted so consecutive lookups hit neighbouring keys on the db.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OrderAccessList {
+    pub accounts: BTreeMap<Address, BTreeSet<U256>>,
+}
+
+impl OrderAccessList {
+    /// None if the order carries no access list (no EIP-2930 list on its txs and no simulation read set).
+    /// extra_accounts (eg: coinbase) are added to the list of orders with access list.
+    pub fn from_order(order: &SimulatedOrder, extra_accounts: &[Address]) -> Option<Self> {
+        let mut res = Self::default();
+        let mut has_access_list = false;
+        for (tx, _) in order.order.list_txs() {
+            if let Some(access_list) = tx.tx.access_list() {
+                for item in access_list.0.iter() {
+                    has_access_list = true;
+                    res.add_slots(
+                        item.address,
+                        item.storage_keys
+                            .iter()
+                            .map(|key| U256::from_be_bytes(key.0)),
+                    );
+                }
+            }
+            res.add_slots(tx.signer(), []);
+            if let Some(to) = tx.tx.to() {
+                res.add_slots(to, []);
+            }
+        }
+        if let Some(used_state_trace) = &order.used_state_trace {
+            for SlotKey { address, key } in used_state_trace.read_set.keys() {
+                has_access_list = true;
+                res.add_slots(*address, [U256::from_be_bytes(key.0)]);
+            }
+        }
+        if !has_access_list {
+            return None;
+        }
+        for account in extra_accounts {
+            res.add_slots(*account, []);
+        }
+        Some(res)
+    }
+
+    fn add_slots(&mut self, address: Address, slots: impl IntoIterator<Item = U256>) {
+        self.accounts.entry(address).or_default().extend(slots);
+    }
+}
+
+/// State of all the accounts (with code) and slots of an [`OrderAccessList`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchedState {
+    pub accounts: HashMap<Address, Option<AccountInfo>>,
+    pub storage: HashMap<(Address, U256), U256>,
+    pub contracts: HashMap<B256, Bytecode>,
+}
+
+/// Resolves a whole [`OrderAccessList`] in a single provider access.
+pub trait BatchStateReader {
+    /// None if the reader can't serve the state the block is built on (the caller falls back to plain lookups).
+    fn read_batch(
+        &self,
+        access_list: &OrderAccessList,
+    ) -> Result<Option<BatchedState>, ProviderError>;
+}
+
+/// Reads the plain state tables (state after the best block) with one read tx, seeking the sorted accounts and
+/// slots with a cursor per table.
+/// Only serves blocks built on top of parent while it's still the best block of the db.
+#[derive(Debug, Clone)]
+pub struct PlainStateBatchReader<DB> {
+    provider_factory: ProviderFactory<DB>,
+    parent: B256,
+}
+
+impl<DB> PlainStateBatchReader<DB> {
+    pub fn new(provider_factory: ProviderFactory<DB>, parent: B256) -> Self {
+        Self {
+            provider_factory,
+            parent,
+        }
+    }
+}
+
+impl<DB: Database> BatchStateReader for PlainStateBatchReader<DB> {
+    fn read_batch(
+        &self,
+        access_list: &OrderAccessList,
+    ) -> Result<Option<BatchedState>, ProviderError> {
+        let provider = self.provider_factory.provider()?;
+        // same check history_by_block_hash does to use the latest (plain) state
+        let best_block = provider.best_block_number()?;
+        if provider.block_hash(best_block)? != Some(self.parent) {
+            return Ok(None);
+        }
+        let tx = provider.tx_ref();
+        let mut accounts_cursor = tx.cursor_read::<tables::PlainAccountState>()?;
+        let mut storage_cursor = tx.cursor_dup_read::<tables::PlainStorageState>()?;
+        let mut res = BatchedState::default();
+        for (address, slots) in &access_list.accounts {
+            let account = accounts_cursor
+                .seek_exact(*address)?
+                .map(|(_, account)| AccountInfo {
+                    balance: account.balance,
+                    nonce: account.nonce,
+                    code_hash: account.bytecode_hash.unwrap_or(KECCAK_EMPTY),
+                    code: None,
+                });
+            if let Some(account) = &account {
+                if account.code_hash != KECCAK_EMPTY {
+                    let code = tx.get::<tables::Bytecodes>(account.code_hash)?;
+                    res.contracts
+                        .insert(account.code_hash, code.unwrap_or_default().0);
+                }
+            }
+            res.accounts.insert(*address, account);
+            for slot in slots {
+                let key = B256::from(*slot);
+                let value = match storage_cursor.seek_by_key_subkey(*address, key)? {
+                    Some(entry) if entry.key == key => entry.value,
+                    _ => U256::ZERO,
+                };
+                res.storage.insert((*address, *slot), value);
+            }
+        }
+        Ok(Some(res))
+    }
+}
+
+/// Serves the lookups from a [`BatchedState`], the ones not in it go to db.
+#[derive(Debug)]
+pub struct BatchedStateDBRef<'a, DB> {
+    batch: &'a BatchedState,
+    db: DB,
+}
+
+impl<'a, DB> BatchedStateDBRef<'a, DB> {
+    pub fn new(batch: &'a BatchedState, db: DB) -> Self {
+        Self { batch, db }
+    }
+}
+
+impl<'a, DB: DatabaseRef<Error = ProviderError>> DatabaseRef for BatchedStateDBRef<'a, DB> {
+    type Error = ProviderError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        match self.batch.accounts.get(&address) {
+            Some(account) => Ok(account.clone()),
+            None => self.db.basic_ref(address),
+        }
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        match self.batch.contracts.get(&code_hash) {
+            Some(code) => Ok(code.clone()),
+            None => self.db.code_by_hash_ref(code_hash),
+        }
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        match self.batch.storage.get(&(address, index)) {
+            Some(value) => Ok(*value),
+            None => self.db.storage_ref(address, index),
+        }
+    }
+
+    fn block_hash_ref(&self, number: U256) -> Result<B256, Self::Error> {
+        self.db.block_hash_ref(number)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::{
+            evm_inspector::UsedStateTrace,
+            testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
+            BlockState, ExecutionResult, PartialBlock,
+        },
+        primitives::{MempoolTx, Order, TransactionSignedEcRecoveredWithBlobs},
+    };
+    use alloy_primitives::B256;
+    use revm::db::BundleState;
+
+    const SLOT: u64 = 5;
+
+    struct CommitResult {
+        result: ExecutionResult,
+        bundle_state: BundleState,
+        provider_reads: usize,
+        batched_provider_reads: usize,
+    }
+
+    /// batch_parent: Some -> reads batched with a PlainStateBatchReader for this parent.
+    fn commit(
+        test_chain: &TestChainState,
+        order: &SimulatedOrder,
+        batch_parent: Option<B256>,
+    ) -> eyre::Result<CommitResult> {
+        let state_provider = test_chain.provider_factory().latest()?;
+        let batch_reader = batch_parent.map(|parent| {
+            PlainStateBatchReader::new(test_chain.provider_factory().clone(), parent)
+        });
+        let mut state = BlockState::new(&state_provider).with_batch_reader(
+            batch_reader
+                .as_ref()
+                .map(|reader| reader as &dyn BatchStateReader),
+        );
+        let mut partial_block = PartialBlock::new(true, None);
+        let result = partial_block
+            .commit_order(order, test_chain.block_building_context(), &mut state)?
+            .map_err(|err| eyre::eyre!("Order failed: {}", err))?;
+        let provider_reads = state.provider_reads();
+        let batched_provider_reads = state.batched_provider_reads();
+        Ok(CommitResult {
+            result,
+            bundle_state: state.into_parts().1,
+            provider_reads,
+            batched_provider_reads,
+        })
+    }
+
+    fn increment_order(
+        test_chain: &TestChainState,
+        with_read_set: bool,
+    ) -> eyre::Result<SimulatedOrder> {
+        let tx = test_chain.sign_tx(TxArgs::new_increment_value(NamedAddr::User(0), 0, SLOT, 0))?;
+        let used_state_trace = with_read_set.then(|| {
+            let mut trace = UsedStateTrace::default();
+            trace.read_set.insert(
+                SlotKey {
+                    address: test_chain.named_address(NamedAddr::MevTest).unwrap(),
+                    key: B256::from(U256::from(SLOT)),
+                },
+                B256::ZERO,
+            );
+            trace
+        });
+        Ok(SimulatedOrder {
+            order: Order::Tx(MempoolTx::new(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            )),
+            sim_value: Default::default(),
+            prev_order: None,
+            used_state_trace,
+        })
+    }
+
+    #[test]
+    fn test_batched_reads_same_result() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let order = increment_order(&test_chain, true)?;
+
+        let plain = commit(&test_chain, &order, None)?;
+        let batched = commit(&test_chain, &order, Some(test_chain.parent_hash()))?;
+        assert_eq!(plain.result, batched.result);
+        assert_eq!(plain.bundle_state, batched.bundle_state);
+
+        assert_eq!(plain.batched_provider_reads, 0);
+        assert_eq!(batched.batched_provider_reads, 1);
+        assert!(
+            batched.provider_reads + batched.batched_provider_reads < plain.provider_reads,
+            "total reads, batched: {} + {} plain: {}",
+            batched.provider_reads,
+            batched.batched_provider_reads,
+            plain.provider_reads
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_batch_reader_not_on_best_block_falls_back() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let order = increment_order(&test_chain, true)?;
+        let reader = PlainStateBatchReader::new(test_chain.provider_factory().clone(), B256::ZERO);
+        assert_eq!(
+            reader.read_batch(&OrderAccessList::from_order(&order, &[]).unwrap())?,
+            None
+        );
+
+        let plain = commit(&test_chain, &order, None)?;
+        let fallback = commit(&test_chain, &order, Some(B256::ZERO))?;
+        assert_eq!(plain.result, fallback.result);
+        assert_eq!(plain.bundle_state, fallback.bundle_state);
+        assert_eq!(fallback.provider_reads, plain.provider_reads);
+        Ok(())
+    }
+
+    #[test]
+    fn test_no_access_list_keeps_current_path() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let order = increment_order(&test_chain, false)?;
+        assert_eq!(OrderAccessList::from_order(&order, &[]), None);
+
+        let plain = commit(&test_chain, &order, None)?;
+        let batched = commit(&test_chain, &order, Some(test_chain.parent_hash()))?;
+        assert_eq!(plain.result, batched.result);
+        assert_eq!(batched.batched_provider_reads, 0);
+        assert_eq!(batched.provider_reads, plain.provider_reads);
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+//! Limits the number of orders a single block building iteration considers.
+//! With huge order pools trying every order on every iteration is too slow, but always trying only the best K would starve the rest.
+//! Every iteration considers the best orders (head, half of the limit) and uses the rest of the limit on the remainder (tail).
+//! The cursor remembers which tail orders were already considered on the current round so the next iterations continue with
+//! the orders below them. A round ends when all the ready orders were considered, then the tail starts again from the top.
+//! Orders not considered yet on the round (eg: new arrivals) are not skipped so a new high priority order preempts the cursor.
+use ahash::HashSet;
+
+use crate::primitives::{OrderId, SimulatedOrder};
+
+use super::BlockOrders;
+
+/// How many orders were considered vs how many were ready to be considered on an iteration.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConsiderationStats {
+    pub considered: usize,
+    pub available: usize,
+}
+
+/// Usage: call start_iteration before each iteration and then use pop_order instead of BlockOrders::pop_order.
+#[derive(Debug, Default)]
+pub struct OrderConsiderationCursor {
+    /// None -> no limit, every iteration considers all the orders.
+    max_orders_per_iteration: Option<usize>,
+    /// Tail orders considered on previous iterations of the current round.
+    round_considered: HashSet<OrderId>,
+    /// Head orders of the current iteration.
+    iteration_head: HashSet<OrderId>,
+    /// Tail orders considered on the current iteration. Reinserted orders (eg: failed_order_retries) are counted once.
+    iteration_considered: HashSet<OrderId>,
+    stats: ConsiderationStats,
+}
+
+impl OrderConsiderationCursor {
+    pub fn new(max_orders_per_iteration: Option<usize>) -> Self {
+        Self {
+            max_orders_per_iteration,
+            ..Default::default()
+        }
+    }
+
+    /// (head, tail) orders considered on each iteration.
+    fn limits(max_orders: usize) -> (usize, usize) {
+        let head = max_orders / 2;
+        (head, max_orders - head)
+    }
+
+    pub fn start_iteration(&mut self, block_orders: &BlockOrders) {
+        let ready_orders: HashSet<OrderId> = block_orders.ready_order_ids().into_iter().collect();
+        self.iteration_head.clear();
+        if let Some(max_orders) = self.max_orders_per_iteration {
+            self.round_considered
+                .extend(self.iteration_considered.drain());
+            // forget orders that are gone so the round can end
+            self.round_considered.retain(|id| ready_orders.contains(id));
+            // what is left fits in the head
+            let (head, _) = Self::limits(max_orders);
+            if ready_orders
+                .len()
+                .saturating_sub(self.round_considered.len())
+                <= head
+            {
+                self.round_considered.clear();
+            }
+        } else {
+            self.iteration_considered.clear();
+        }
+        self.stats = ConsiderationStats {
+            considered: 0,
+            available: ready_orders.len() - self.round_considered.len(),
+        };
+    }
+
+    /// Next order to consider, None when we run out of orders or reached max_orders_per_iteration.
+    pub fn pop_order(&mut self, block_orders: &mut BlockOrders) -> Option<SimulatedOrder> {
+        let Some(max_orders) = self.max_orders_per_iteration else {
+            let order = block_orders.pop_order()?;
+            if self.iteration_considered.insert(order.id()) {
+                self.stats.considered += 1;
+            }
+            return Some(order);
+        };
+        let (head, tail) = Self::limits(max_orders);
+        loop {
+            let order = block_orders.pop_order()?;
+            let id = order.id();
+            if self.iteration_head.contains(&id) || self.iteration_considered.contains(&id) {
+                return Some(order);
+            }
+            // orders come best first so the head is always the best ready orders
+            if self.iteration_head.len() < head {
+                self.iteration_head.insert(id);
+                self.stats.considered += 1;
+                return Some(order);
+            }
+            if self.round_considered.contains(&id) {
+                continue;
+            }
+            if self.iteration_considered.len() >= tail {
+                return None;
+            }
+            self.iteration_considered.insert(id);
+            self.stats.considered += 1;
+            return Some(order);
+        }
+    }
+
+    /// Stats for the current (or last) iteration.
+    pub fn stats(&self) -> ConsiderationStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::building::{block_orders::TestDataGenerator, Sorting};
+
+    const POOL_SIZE: usize = 10_000;
+    const MAX_ORDERS_PER_ITERATION: usize = 1_000;
+
+    /// Independent single tx orders (one account each), order i has profit i + 1.
+    fn create_orders(data_gen: &mut TestDataGenerator, count: usize) -> Vec<SimulatedOrder> {
+        (0..count)
+            .map(|profit| {
+                let nonce = data_gen.create_account_nonce(0);
+                let order = data_gen.base.create_tx_order(nonce);
+                data_gen.create_sim_order(order, profit as u64 + 1, profit as u64 + 1)
+            })
+            .collect()
+    }
+
+    fn block_orders(orders: &[SimulatedOrder]) -> BlockOrders {
+        let mut block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        for order in orders {
+            block_orders.add_order(order.clone());
+        }
+        block_orders
+    }
+
+    /// Runs one iteration over a copy of block_orders without executing anything.
+    fn run_iteration(
+        cursor: &mut OrderConsiderationCursor,
+        block_orders: &BlockOrders,
+    ) -> Vec<OrderId> {
+        let mut block_orders = block_orders.clone();
+        cursor.start_iteration(&block_orders);
+        let mut considered = Vec::new();
+        while let Some(order) = cursor.pop_order(&mut block_orders) {
+            considered.push(order.id());
+        }
+        considered
+    }
+
+    #[test]
+    fn test_full_coverage_without_starvation() {
+        let mut data_gen = TestDataGenerator::default();
+        let orders = create_orders(&mut data_gen, POOL_SIZE);
+        let pool = block_orders(&orders);
+        let mut cursor = OrderConsiderationCursor::new(Some(MAX_ORDERS_PER_ITERATION));
+
+        let head = MAX_ORDERS_PER_ITERATION / 2;
+        let best: Vec<OrderId> = orders.iter().rev().take(head).map(|o| o.id()).collect();
+        let mut all_considered: HashSet<OrderId> = best.iter().copied().collect();
+        for _ in 0..(POOL_SIZE - head) / head {
+            let considered = run_iteration(&mut cursor, &pool);
+            assert_eq!(considered.len(), MAX_ORDERS_PER_ITERATION);
+            assert_eq!(cursor.stats().considered, MAX_ORDERS_PER_ITERATION);
+            // the best orders are considered on every iteration
+            assert_eq!(considered[..head], best[..]);
+            for id in &considered[head..] {
+                assert!(all_considered.insert(*id), "tail order considered twice");
+            }
+        }
+        // the tail (least profitable orders) got its turn
+        for order in &orders {
+            assert!(all_considered.contains(&order.id()));
+        }
+
+        // round finished, the tail starts again below the head
+        let considered = run_iteration(&mut cursor, &pool);
+        assert_eq!(cursor.stats().available, POOL_SIZE);
+        assert_eq!(considered[..head], best[..]);
+        assert_eq!(considered[head], orders[POOL_SIZE - 1 - head].id());
+    }
+
+    #[test]
+    fn test_new_orders_preempt_cursor() {
+        let mut data_gen = TestDataGenerator::default();
+        let orders = create_orders(&mut data_gen, 30);
+        let mut pool = block_orders(&orders);
+        let mut cursor = OrderConsiderationCursor::new(Some(10));
+
+        let considered = run_iteration(&mut cursor, &pool);
+        assert_eq!(considered.len(), 10);
+        assert_eq!(cursor.stats().available, 30);
+
+        let nonce = data_gen.create_account_nonce(0);
+        let best_order = data_gen.base.create_tx_order(nonce);
+        let best_order = data_gen.create_sim_order(best_order, 1_000_000, 1_000_000);
+        pool.add_order(best_order.clone());
+
+        let considered = run_iteration(&mut cursor, &pool);
+        assert_eq!(considered.len(), 10);
+        assert_eq!(considered[0], best_order.id());
+        assert_eq!(considered[4], orders[26].id());
+        // pushed out of the head, first on the tail
+        assert_eq!(considered[5], orders[25].id());
+        // cursor continues where the last iteration stopped
+        assert_eq!(considered[6], orders[19].id());
+        assert_eq!(
+            cursor.stats(),
+            ConsiderationStats {
+                considered: 10,
+                available: 26
+            }
+        );
+    }
+
+    #[test]
+    fn test_no_limit() {
+        let mut data_gen = TestDataGenerator::default();
+        let orders = create_orders(&mut data_gen, 100);
+        let pool = block_orders(&orders);
+        let mut cursor = OrderConsiderationCursor::new(None);
+        for _ in 0..2 {
+            assert_eq!(run_iteration(&mut cursor, &pool).len(), 100);
+            assert_eq!(
+                cursor.stats(),
+                ConsiderationStats {
+                    considered: 100,
+                    available: 100
+                }
+            );
+        }
+    }
+}
@@ -1,3 +1,4 @@
+mod consideration_cursor;
 pub mod multi_share_bundle_merger;
 mod prioritized_order_store;
 mod share_bundle_merger;
@@ -19,6 +20,7 @@ use multi_share_bundle_merger::MultiShareBundleMerger;
 use reth::{primitives::Address, providers::StateProviderBox};
 use reth_interfaces::provider::ProviderResult;
 
+pub use consideration_cursor::{ConsiderationStats, OrderConsiderationCursor};
 use prioritized_order_store::PrioritizedOrderStore;
 pub use test_data_generator::TestDataGenerator;
 
@@ -176,6 +178,10 @@ impl BlockOrders {
     pub fn get_all_orders(&self) -> Vec<SimulatedOrder> {
         self.prioritized_order_store.borrow().get_all_orders()
     }
+
+    pub fn ready_order_ids(&self) -> Vec<OrderId> {
+        self.prioritized_order_store.borrow().ready_order_ids()
+    }
 }
 impl SimulatedOrderSink for BlockOrders {
     fn insert_order(&mut self, order: SimulatedOrder) {
@@ -218,7 +224,7 @@ pub fn block_orders_from_sim_orders(
 
 #[cfg(test)]
 mod test {
-    use crate::primitives::BundledTxInfo;
+    use crate::primitives::{BundledTxInfo, Order};
 
     use super::*;
     /// Helper struct for common BlockOrders test operations
@@ -387,6 +393,30 @@ mod test {
         context.assert_pop_none();
     }
 
+    #[test]
+    /// Profitable position constrained bundles go before more profitable orders
+    fn test_block_orders_position_constrained_first() {
+        let (nonce_1, nonce_2, mut context) = TestContext::new_2_accounts(0, 0);
+        let tx_order = context.create_add_tx_order(&nonce_1, 5);
+        let mut bundle = context.data_gen.base.create_bundle_multi_tx(
+            0,
+            &[BundledTxInfo {
+                nonce: nonce_2.clone(),
+                optional: false,
+            }],
+            None,
+        );
+        bundle.max_block_position = Some(0);
+        let top_of_block_order = context
+            .data_gen
+            .create_sim_order(Order::Bundle(bundle), 1, 1);
+        context.order_pool.add_order(top_of_block_order.clone());
+
+        context.assert_pop_order(&top_of_block_order);
+        context.assert_pop_order(&tx_order);
+        context.assert_pop_none();
+    }
+
     #[test]
     // Execute a bundle with an optional tx that fails for invalid nonce
     fn test_block_orders_optional_nonce() {
@@ -14,6 +14,9 @@ use super::SimulatedOrderSink;
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct OrderPriority {
     pub order_id: OrderId,
+    /// Profitable orders with a block position constraint (eg: top of block bundles) go before any other order
+    /// since they can only be included early in the block.
+    pub position_constrained: bool,
     pub priority: u128,
 }
 
@@ -25,8 +28,9 @@ impl PartialOrd for OrderPriority {
 
 impl Ord for OrderPriority {
     fn cmp(&self, other: &Self) -> Ordering {
-        self.priority
-            .cmp(&other.priority)
+        self.position_constrained
+            .cmp(&other.position_constrained)
+            .then_with(|| self.priority.cmp(&other.priority))
             .then_with(|| self.order_id.cmp(&other.order_id))
     }
 }
@@ -170,6 +174,11 @@ impl PrioritizedOrderStore {
     pub fn get_all_orders(&self) -> Vec<SimulatedOrder> {
         self.orders.values().cloned().collect()
     }
+
+    /// Ids of the orders pop_order can give us right now (excludes the ones waiting for nonces).
+    pub fn ready_order_ids(&self) -> Vec<OrderId> {
+        self.main_queue.iter().map(|(id, _)| *id).collect()
+    }
 }
 
 impl SimulatedOrderSink for PrioritizedOrderStore {
@@ -201,13 +210,16 @@ impl SimulatedOrderSink for PrioritizedOrderStore {
             }
         }
         if pending_nonces.is_empty() {
+            let priority = self
+                .priority
+                .sorting_value(&sim_order.sim_value)
+                .to::<u128>();
             self.main_queue.push(
                 sim_order.id(),
                 OrderPriority {
-                    priority: self
-                        .priority
-                        .sorting_value(&sim_order.sim_value)
-                        .to::<u128>(),
+                    position_constrained: priority > 0
+                        && sim_order.order.max_block_position().is_some(),
+                    priority,
                     order_id: sim_order.id(),
                 },
             );
@@ -0,0 +1,153 @@
+//! Splits the build iterations of a slot between several candidate configs by the profit they recently yielded.
+//! Each candidate keeps an exponential moving average (PROFIT_EWMA_ALPHA) of the bid value of its builds (0 for builds
+//! that produced no block), it lives as long as the allocator so it spans slots.
+//! Iterations are handed out with a smooth weighted round robin so the split is deterministic, every candidate gets at
+//! least min_share of them so a poor candidate can recover and candidates without history are explored first.
+use alloy_primitives::U256;
+
+pub const PROFIT_EWMA_ALPHA: f64 = 0.2;
+
+#[derive(Debug, Clone, Default)]
+struct CandidateBudget {
+    /// EWMA of the profit in wei, None until the first build.
+    profit_estimate: Option<f64>,
+    /// Smooth weighted round robin credit.
+    current_weight: f64,
+    iterations: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuildBudgetAllocator {
+    candidates: Vec<CandidateBudget>,
+    min_share: f64,
+}
+
+impl BuildBudgetAllocator {
+    /// min_share is clamped to [0, 1 / candidate_count].
+    pub fn new(candidate_count: usize, min_share: f64) -> Self {
+        let max_min_share = 1.0 / candidate_count.max(1) as f64;
+        Self {
+            candidates: vec![CandidateBudget::default(); candidate_count],
+            min_share: min_share.clamp(0.0, max_min_share),
+        }
+    }
+
+    pub fn candidate_count(&self) -> usize {
+        self.candidates.len()
+    }
+
+    pub fn record_profit(&mut self, candidate: usize, profit: U256) {
+        let profit = u128::try_from(profit).unwrap_or(u128::MAX) as f64;
+        let candidate = &mut self.candidates[candidate];
+        candidate.profit_estimate = Some(match candidate.profit_estimate {
+            Some(estimate) => estimate + PROFIT_EWMA_ALPHA * (profit - estimate),
+            None => profit,
+        });
+    }
+
+    pub fn profit_estimate(&self, candidate: usize) -> Option<f64> {
+        self.candidates[candidate].profit_estimate
+    }
+
+    /// Iterations handed to the candidate by next_candidate.
+    pub fn iterations(&self, candidate: usize) -> u64 {
+        self.candidates[candidate].iterations
+    }
+
+    /// Share of the iterations each candidate should get, adds up to 1.
+    pub fn shares(&self) -> Vec<f64> {
+        let count = self.candidates.len();
+        // candidates without history are as promising as the best one
+        let best_estimate = self
+            .candidates
+            .iter()
+            .filter_map(|candidate| candidate.profit_estimate)
+            .fold(0.0, f64::max);
+        let weights: Vec<f64> = self
+            .candidates
+            .iter()
+            .map(|candidate| candidate.profit_estimate.unwrap_or(best_estimate))
+            .collect();
+        let total: f64 = weights.iter().sum();
+        let weighted_share = 1.0 - self.min_share * count as f64;
+        weights
+            .iter()
+            .map(|weight| {
+                let share = if total > 0.0 {
+                    weight / total
+                } else {
+                    1.0 / count as f64
+                };
+                self.min_share + weighted_share * share
+            })
+            .collect()
+    }
+
+    /// Candidate that should get the next iteration.
+    pub fn next_candidate(&mut self) -> usize {
+        let shares = self.shares();
+        for (candidate, share) in self.candidates.iter_mut().zip(shares) {
+            candidate.current_weight += share;
+        }
+        let next = self
+            .candidates
+            .iter()
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.current_weight.total_cmp(&b.current_weight))
+            .map(|(i, _)| i)
+            .unwrap_or_default();
+        // shares add up to 1
+        self.candidates[next].current_weight -= 1.0;
+        self.candidates[next].iterations += 1;
+        next
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_profitable_candidate_gets_more_iterations() {
+        let mut allocator = BuildBudgetAllocator::new(2, 0.1);
+        // history from previous slots
+        for _ in 0..10 {
+            allocator.record_profit(0, U256::from(9_000_000));
+            allocator.record_profit(1, U256::from(1_000_000));
+        }
+        for _ in 0..100 {
+            allocator.next_candidate();
+        }
+        let (good, poor) = (allocator.iterations(0), allocator.iterations(1));
+        assert_eq!(good + poor, 100);
+        assert!(good > poor);
+        // 0.1 + 0.8 * 0.9 = 0.82
+        assert!((81..=83).contains(&good));
+        // the floor keeps the poor one alive
+        assert!(poor >= 10);
+    }
+
+    #[test]
+    fn test_unknown_candidates_are_explored() {
+        let mut allocator = BuildBudgetAllocator::new(3, 0.0);
+        assert_eq!(allocator.shares(), vec![1.0 / 3.0; 3]);
+        allocator.record_profit(0, U256::from(100));
+        // 1 and 2 count as good as 0 until they build
+        let shares = allocator.shares();
+        assert!(shares.iter().all(|share| (share - 1.0 / 3.0).abs() < 1e-9));
+        for _ in 0..3 {
+            allocator.next_candidate();
+        }
+        assert_eq!(
+            (0..3).map(|i| allocator.iterations(i)).collect::<Vec<_>>(),
+            vec![1, 1, 1]
+        );
+
+        allocator.record_profit(1, U256::ZERO);
+        allocator.record_profit(2, U256::ZERO);
+        assert_eq!(allocator.shares(), vec![1.0, 0.0, 0.0]);
+        // estimates move with the new profits
+        allocator.record_profit(1, U256::from(100));
+        assert_eq!(allocator.profit_estimate(1), Some(20.0));
+    }
+}
@@ -1,10 +1,12 @@
 //! builders is a subprocess that builds a block
+pub mod build_budget;
 pub mod ordering_builder;
 
 use crate::{
     building::{
-        tracers::SimulationTracer, BlockBuildingContext, BlockOrders, BlockState, BuiltBlockTrace,
-        InsertPayoutTxErr, PartialBlock, SimulatedOrderSink, Sorting,
+        pending_accounts::PendingCandidates, tracers::SimulationTracer,
+        value_curve::SlotValueCurve, AssembledBlock, BlockBuildingContext, BlockOrders, BlockState,
+        BuiltBlockTrace, InsertPayoutTxErr, PartialBlock, SimulatedOrderSink, Sorting,
     },
     live_builder::{
         bidding::{SealInstruction, SlotBidder},
@@ -12,12 +14,15 @@ use crate::{
         simulation::SimulatedOrderCommand,
     },
     primitives::{AccountNonce, OrderId, SimulatedOrder},
+    telemetry::{
+        add_slot_sink_blocks, inc_best_block_supersessions_capped, observe_sink_blocks_per_slot,
+    },
     utils::NonceCache,
 };
-use ahash::HashSet;
-use alloy_primitives::{Address, B256, U256};
+use ahash::{HashMap, HashSet};
+use alloy_primitives::{Address, Bytes, B256, U256};
 use reth::{
-    primitives::{BlobTransactionSidecar, SealedBlock},
+    primitives::{BlobTransactionSidecar, SealedBlock, SealedHeader},
     providers::ProviderFactory,
     tasks::pool::BlockingTaskPool,
 };
@@ -39,43 +44,278 @@ pub struct Block {
     /// Sidecars for the txs included in SealedBlock
     pub txs_blobs_sidecars: Vec<Arc<BlobTransactionSidecar>>,
     pub builder_name: String,
+    /// Hash of the tx paying the bid to the proposer (only when the builder is the coinbase, eg: coinbase_payment).
+    pub coinbase_payment_tx_hash: Option<B256>,
+    /// Built with RootHashMode::SkipRootHash, the state root is invalid and the block must not be submitted.
+    pub root_hash_skipped: bool,
 }
 
-/// Contains the best block so far.
-/// Building updates via compare_and_update while relay submitter polls via take_best_block
+impl Block {
+    /// Legacy RLP encoding of the block (header, txs, ommers, withdrawals) as returned by debug_getRawBlock.
+    /// Blob sidecars are not part of it.
+    pub fn rlp_encoded(&self) -> Bytes {
+        alloy_rlp::encode(self.sealed_block.clone().unseal()).into()
+    }
+}
+
+/// Block we built with the header sealed but the payload not built yet.
+/// Enough for header only bids (eg: optimistic v2 relays), call seal to get the full Block.
 #[derive(Debug, Clone)]
-pub struct BestBlockCell {
-    val: Arc<Mutex<Option<Block>>>,
+pub struct LazyBlock {
+    pub trace: BuiltBlockTrace,
+    pub assembled_block: AssembledBlock,
+    pub builder_name: String,
+    pub coinbase_payment_tx_hash: Option<B256>,
+    /// See [`Block::root_hash_skipped`].
+    pub root_hash_skipped: bool,
 }
 
-impl Default for BestBlockCell {
-    fn default() -> Self {
-        Self {
-            val: Arc::new(Mutex::new(None)),
+impl LazyBlock {
+    pub fn sealed_header(&self) -> &SealedHeader {
+        &self.assembled_block.sealed_header
+    }
+
+    pub fn seal(self) -> Block {
+        let (sealed_block, txs_blobs_sidecars) = self.assembled_block.seal_payload();
+        Block {
+            trace: self.trace,
+            sealed_block,
+            txs_blobs_sidecars,
+            builder_name: self.builder_name,
+            coinbase_payment_tx_hash: self.coinbase_payment_tx_hash,
+            root_hash_skipped: self.root_hash_skipped,
         }
     }
 }
 
+/// Which blocks supersede the best one of a [`BestBlockCell`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BestBlockFilter {
+    /// A block must beat the best bid so far by more than this (ties never supersede).
+    pub min_improvement: U256,
+    /// Once this many blocks superseded the first one of the slot the rest are dropped.
+    pub max_supersessions: Option<usize>,
+}
+
+/// What a [`BestBlockCell`] did with a block it got.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NewBlockOutcome {
+    /// Became the best block.
+    Best,
+    /// Did not beat the best bid (see [`BestBlockFilter::min_improvement`]).
+    NotBetter,
+    /// Better but dropped by [`BestBlockFilter::max_supersessions`].
+    Capped,
+    /// First block of the slot bidding 0.
+    ZeroValue,
+}
+
+impl NewBlockOutcome {
+    pub const ALL: [NewBlockOutcome; 4] = [
+        NewBlockOutcome::Best,
+        NewBlockOutcome::NotBetter,
+        NewBlockOutcome::Capped,
+        NewBlockOutcome::ZeroValue,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NewBlockOutcome::Best => "best",
+            NewBlockOutcome::NotBetter => "not_better",
+            NewBlockOutcome::Capped => "capped",
+            NewBlockOutcome::ZeroValue => "zero_value",
+        }
+    }
+}
+
+/// Builders alternating (A, B, A...) this many times on a slot get a warning.
+const OSCILLATION_WARN_ALTERNATIONS: usize = 8;
+
+/// Best block as the builder sent it.
+#[derive(Debug)]
+enum BestBlock {
+    Sealed(Block),
+    /// Sealed on take_best_block so blocks superseded before being taken never build their payload.
+    Lazy(LazyBlock),
+}
+
+impl BestBlock {
+    fn trace(&self) -> &BuiltBlockTrace {
+        match self {
+            BestBlock::Sealed(block) => &block.trace,
+            BestBlock::Lazy(block) => &block.trace,
+        }
+    }
+
+    fn builder_name(&self) -> &String {
+        match self {
+            BestBlock::Sealed(block) => &block.builder_name,
+            BestBlock::Lazy(block) => &block.builder_name,
+        }
+    }
+
+    fn number(&self) -> u64 {
+        match self {
+            BestBlock::Sealed(block) => block.sealed_block.number,
+            BestBlock::Lazy(block) => block.sealed_header().number,
+        }
+    }
+
+    fn seal(self) -> Block {
+        match self {
+            BestBlock::Sealed(block) => block,
+            BestBlock::Lazy(block) => block.seal(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BestBlockCellState {
+    block: Option<BestBlock>,
+    /// Best bid accepted on the slot, kept after take_best_block so a block already taken can't be superseded by
+    /// an equal one.
+    best_value: Option<U256>,
+    supersessions: usize,
+    /// Builders of the last two accepted blocks, latest first.
+    last_builders: [Option<String>; 2],
+    alternations: usize,
+    /// Blocks received (new_block/compare_and_update calls) by outcome.
+    new_blocks: HashMap<NewBlockOutcome, usize>,
+}
+
+/// Contains the best block so far.
+/// Building updates via compare_and_update while relay submitter polls via take_best_block
+#[derive(Debug, Clone, Default)]
+pub struct BestBlockCell {
+    state: Arc<Mutex<BestBlockCellState>>,
+    filter: BestBlockFilter,
+    /// Every accepted block is recorded here.
+    value_curve: Option<SlotValueCurve>,
+}
+
 impl BlockBuildingSink for BestBlockCell {
     fn new_block(&self, block: Block) {
         self.compare_and_update(block);
     }
+
+    fn new_lazy_block(&self, block: LazyBlock) {
+        self.compare_and_update_lazy(block);
+    }
 }
 
 impl BestBlockCell {
-    pub fn compare_and_update(&self, block: Block) {
-        let mut best_block = self.val.lock().unwrap();
-        let old_value = best_block
-            .as_ref()
-            .map(|b| b.trace.bid_value)
-            .unwrap_or_default();
-        if block.trace.bid_value > old_value {
-            *best_block = Some(block);
+    /// Returns true if block became the best one.
+    pub fn compare_and_update(&self, block: Block) -> bool {
+        self.compare_and_update_best(BestBlock::Sealed(block))
+    }
+
+    /// Like compare_and_update but the payload is only sealed if the block is taken (see [`Self::take_best_block`]).
+    pub fn compare_and_update_lazy(&self, block: LazyBlock) -> bool {
+        self.compare_and_update_best(BestBlock::Lazy(block))
+    }
+
+    fn compare_and_update_best(&self, block: BestBlock) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let outcome = self.update(&mut state, block);
+        *state.new_blocks.entry(outcome).or_default() += 1;
+        outcome == NewBlockOutcome::Best
+    }
+
+    fn update(&self, state: &mut BestBlockCellState, block: BestBlock) -> NewBlockOutcome {
+        let bid_value = block.trace().bid_value;
+        match state.best_value {
+            Some(best_value) => {
+                if bid_value <= best_value.saturating_add(self.filter.min_improvement) {
+                    return NewBlockOutcome::NotBetter;
+                }
+                if self
+                    .filter
+                    .max_supersessions
+                    .is_some_and(|max_supersessions| state.supersessions >= max_supersessions)
+                {
+                    inc_best_block_supersessions_capped();
+                    return NewBlockOutcome::Capped;
+                }
+                state.supersessions += 1;
+            }
+            None => {
+                if bid_value.is_zero() {
+                    return NewBlockOutcome::ZeroValue;
+                }
+            }
+        }
+
+        let alternating = state.last_builders[1].as_ref() == Some(block.builder_name())
+            && state.last_builders[0].as_ref() != Some(block.builder_name());
+        if alternating {
+            state.alternations += 1;
+            if state.alternations == OSCILLATION_WARN_ALTERNATIONS {
+                warn!(
+                    block = block.number(),
+                    builder_name = block.builder_name(),
+                    other_builder_name = ?state.last_builders[0],
+                    supersessions = state.supersessions,
+                    "Builders keep superseding each other",
+                );
+            }
         }
+        let last_builder = state.last_builders[0].take();
+        state.last_builders = [Some(block.builder_name().clone()), last_builder];
+
+        if let Some(value_curve) = &self.value_curve {
+            value_curve.record(bid_value, block.builder_name());
+        }
+        state.best_value = Some(bid_value);
+        state.block = Some(block);
+        NewBlockOutcome::Best
+    }
+
+    pub fn with_filter(self, filter: BestBlockFilter) -> Self {
+        Self { filter, ..self }
+    }
+
+    /// Blocks that superseded the first one.
+    pub fn supersessions(&self) -> usize {
+        self.state.lock().unwrap().supersessions
+    }
+
+    /// Blocks received with the given outcome.
+    pub fn new_blocks(&self, outcome: NewBlockOutcome) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .new_blocks
+            .get(&outcome)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Adds the blocks received on the slot to the slot sink blocks metrics, call once at the end of the slot.
+    pub fn report_new_blocks(&self) {
+        let mut total = 0;
+        for outcome in NewBlockOutcome::ALL {
+            let blocks = self.new_blocks(outcome);
+            add_slot_sink_blocks(outcome.as_str(), blocks);
+            total += blocks;
+        }
+        observe_sink_blocks_per_slot(total);
     }
 
+    pub fn with_value_curve(self, value_curve: SlotValueCurve) -> Self {
+        Self {
+            value_curve: Some(value_curve),
+            ..self
+        }
+    }
+
+    pub fn value_curve(&self) -> Option<&SlotValueCurve> {
+        self.value_curve.as_ref()
+    }
+
+    /// Lazy blocks get their payload sealed here, outside the lock.
     pub fn take_best_block(&self) -> Option<Block> {
-        self.val.lock().unwrap().take()
+        let block = self.state.lock().unwrap().block.take();
+        block.map(BestBlock::seal)
     }
 }
 
@@ -90,6 +330,8 @@ pub struct LiveBuilderInput<DB: Database, SinkType: BlockBuildingSink> {
     pub slot_bidder: Arc<dyn SlotBidder>,
     pub cancel: CancellationToken,
     pub sbundle_mergeabe_signers: Vec<Address>,
+    /// Finalized blocks are offered here (see [`PendingCandidates`]).
+    pub pending_candidates: Arc<PendingCandidates>,
 }
 
 /// Struct that helps reading new orders/cancelations
@@ -238,6 +480,8 @@ pub fn finalize_block_execution(
     bidder: &dyn SlotBidder,
     fee_recipient_balance_diff: U256,
 ) -> Result<bool, InsertPayoutTxErr> {
+    partial_block.pay_deferred_refunds(ctx, state)?;
+    partial_block.verify_refunds(ctx)?;
     let (bid_value, true_value) = if let Some(payout_tx_gas) = payout_tx_gas {
         let available_value = partial_block.get_proposer_payout_tx_value(payout_tx_gas, ctx)?;
         let value = match bidder.seal_instruction(available_value, ctx.timestamp()) {
@@ -261,6 +505,12 @@ pub fn finalize_block_execution(
 /// Output of the BlockBuildingAlgorithm
 pub trait BlockBuildingSink: std::fmt::Debug + Clone + Send + Sync {
     fn new_block(&self, block: Block);
+
+    /// Seals right away, sinks that can decide with just the header and bid (eg: [`BestBlockCell`] drops the blocks
+    /// superseded before being taken) override this to seal the payload on demand.
+    fn new_lazy_block(&self, block: LazyBlock) {
+        self.new_block(block.seal());
+    }
 }
 
 #[derive(Debug)]
@@ -273,6 +523,10 @@ pub struct BlockBuildingAlgorithmInput<DB: Database, SinkType: BlockBuildingSink
     /// Needed to add the pay to validator tx (the bid!)
     pub slot_bidder: Arc<dyn SlotBidder>,
     pub cancel: CancellationToken,
+    /// If Some, replaces the sbundle mergeable signers the algorithm was created with (only for this build).
+    pub sbundle_mergeabe_signers: Option<Vec<Address>>,
+    /// Shared by all the builders, see [`LiveBuilderInput::pending_candidates`].
+    pub pending_candidates: Arc<PendingCandidates>,
 }
 
 /// Algorithm to build blocks
@@ -307,3 +561,92 @@ pub struct BacktestSimulateBlockInput<'a, DB> {
     pub provider_factory: ProviderFactory<DB>,
     pub cached_reads: Option<CachedReads>,
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        telemetry::{SINK_BLOCKS_PER_SLOT, SLOT_SINK_BLOCKS},
+        test_utils::mock_block,
+    };
+
+    fn block(bid_value: u64, builder_name: &str) -> Block {
+        mock_block(1, U256::from(bid_value), builder_name)
+    }
+
+    #[test]
+    fn test_equal_blocks_do_not_supersede() {
+        let cell = BestBlockCell::default();
+        assert!(!cell.compare_and_update(block(0, "a")));
+        assert!(cell.compare_and_update(block(10, "a")));
+        // two builders producing the same value over and over
+        for _ in 0..10 {
+            assert!(!cell.compare_and_update(block(10, "b")));
+            assert!(!cell.compare_and_update(block(10, "a")));
+        }
+        let best = cell.take_best_block().unwrap();
+        assert_eq!(best.builder_name, "a");
+        assert_eq!(cell.supersessions(), 0);
+
+        // a taken (submitted) block still can't be superseded by an equal one
+        assert!(!cell.compare_and_update(block(10, "b")));
+        assert!(cell.take_best_block().is_none());
+        assert!(cell.compare_and_update(block(11, "b")));
+        assert_eq!(cell.supersessions(), 1);
+    }
+
+    #[test]
+    fn test_best_block_filter() {
+        let cell = BestBlockCell::default().with_filter(BestBlockFilter {
+            min_improvement: U256::from(5),
+            max_supersessions: Some(2),
+        });
+        assert!(cell.compare_and_update(block(10, "a")));
+        assert!(!cell.compare_and_update(block(15, "b")));
+        assert!(cell.compare_and_update(block(16, "b")));
+        assert!(cell.compare_and_update(block(30, "a")));
+        // capped
+        assert!(!cell.compare_and_update(block(100, "b")));
+        assert_eq!(cell.supersessions(), 2);
+        assert_eq!(
+            cell.take_best_block().unwrap().trace.bid_value,
+            U256::from(30)
+        );
+    }
+
+    #[test]
+    fn test_slot_sink_block_metrics() {
+        let sink = BestBlockCell::default().with_filter(BestBlockFilter {
+            min_improvement: U256::ZERO,
+            max_supersessions: Some(1),
+        });
+        for bid_value in [0, 10, 5, 10, 20, 30] {
+            sink.new_block(block(bid_value, "a"));
+        }
+        let expected = [
+            (NewBlockOutcome::Best, 2),
+            (NewBlockOutcome::NotBetter, 2),
+            (NewBlockOutcome::Capped, 1),
+            (NewBlockOutcome::ZeroValue, 1),
+        ];
+        for (outcome, blocks) in expected {
+            assert_eq!(sink.new_blocks(outcome), blocks);
+        }
+
+        let counter = |outcome: NewBlockOutcome| {
+            SLOT_SINK_BLOCKS
+                .with_label_values(&[outcome.as_str()])
+                .get()
+        };
+        let before: Vec<_> = NewBlockOutcome::ALL.into_iter().map(counter).collect();
+        let slots_before = SINK_BLOCKS_PER_SLOT.get_sample_count();
+        let blocks_before = SINK_BLOCKS_PER_SLOT.get_sample_sum();
+        sink.report_new_blocks();
+        // expected is in NewBlockOutcome::ALL order
+        for ((outcome, blocks), before) in expected.into_iter().zip(before) {
+            assert_eq!(counter(outcome) - before, blocks as u64);
+        }
+        assert_eq!(SINK_BLOCKS_PER_SLOT.get_sample_count() - slots_before, 1);
+        assert_eq!(SINK_BLOCKS_PER_SLOT.get_sample_sum() - blocks_before, 6.0);
+    }
+}
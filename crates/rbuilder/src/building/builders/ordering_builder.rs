@@ -7,38 +7,59 @@
 //! For some more details see [`OrderingBuilderConfig`]
 use crate::{
     building::{
+        batched_reads::{BatchStateReader, PlainStateBatchReader},
         block_orders_from_sim_orders,
         builders::{LiveBuilderInput, OrderIntakeConsumer},
-        estimate_payout_gas_limit, BlockBuildingContext, BlockOrders, BlockState, BuiltBlockTrace,
-        ExecutionError, PartialBlock, Sorting,
+        estimate_payout_gas_limit,
+        pending_accounts::PendingCandidates,
+        profit_divergence::{ProfitDivergenceReport, PROFIT_DIVERGENCES},
+        provider_io::ProviderIoBudgetExceeded,
+        verify_header_basefee, BlockBuildingContext, BlockOrders, BlockState, BuiltBlockTrace,
+        CoinbaseMode, ConsiderationStats, ExecutionError, LogSummaryMode, OrderConsiderationCursor,
+        PartialBlock, PayoutMode, RefundMode, Sorting,
+    },
+    primitives::{
+        AccountNonce, MempoolTx, Order, OrderId, SimValue, SimulatedOrder,
+        TransactionSignedEcRecoveredWithBlobs,
     },
-    primitives::{AccountNonce, OrderId},
     telemetry,
-    utils::is_provider_factory_health_error,
+    utils::{
+        is_provider_factory_health_error,
+        rate_limited_log::{rate_limited, report_discriminant},
+        DurationWindow,
+    },
 };
 use ahash::{HashMap, HashSet};
-use alloy_primitives::{utils::format_ether, Address};
-use reth::providers::{BlockNumReader, ProviderFactory};
+use alloy_primitives::{utils::format_ether, Address, Bytes, B256, U256};
+use cpu_time::ThreadTime;
+use reth::{
+    primitives::{constants::eip4844::DATA_GAS_PER_BLOB, Withdrawal},
+    providers::{BlockNumReader, HeaderProvider, ProviderFactory},
+};
 use reth_db::database::Database;
+use reth_interfaces::provider::ProviderError;
 use reth_provider::StateProvider;
 
 use crate::{
-    building::tracers::GasUsedSimulationTracer, live_builder::bidding::SlotBidder,
-    roothash::RootHashMode, utils::check_provider_factory_health,
+    building::tracers::GasUsedSimulationTracer,
+    live_builder::bidding::{FillPassAction, FillPassInfo, SlotBidder},
+    roothash::RootHashMode,
+    utils::check_provider_factory_health,
 };
 use reth::tasks::pool::BlockingTaskPool;
 use reth_payload_builder::database::CachedReads;
 use serde::Deserialize;
 use std::{
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info_span, trace, warn};
 
 use super::{
-    finalize_block_execution, BacktestSimulateBlockInput, Block, BlockBuildingAlgorithm,
-    BlockBuildingAlgorithmInput, BlockBuildingSink,
+    build_budget::BuildBudgetAllocator, finalize_block_execution, BacktestSimulateBlockInput,
+    Block, BlockBuildingAlgorithm, BlockBuildingAlgorithmInput, BlockBuildingSink, LazyBlock,
 };
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -58,43 +79,298 @@ pub struct OrderingBuilderConfig {
     /// This mode saves gas on the payout tx from builder to validator but disables mev-share and profit taking.
     #[serde(default)]
     pub coinbase_payment: bool,
+    /// false: the ctx coinbase (our builder signer) is always kept, even when coinbase_payment (or the caller of build_block)
+    /// asks for the suggested_fee_recipient as coinbase. The proposer is then always paid via the payout tx.
+    #[serde(default = "default_use_suggested_fee_recipient_as_coinbase")]
+    pub use_suggested_fee_recipient_as_coinbase: bool,
+    /// How the blocks with our builder signer as coinbase pay the proposer.
+    #[serde(default)]
+    pub payout_mode: PayoutMode,
     /// Amount of time allocated for EVM execution while building block.
     #[serde(default)]
     pub build_duration_deadline_ms: Option<u64>,
+    /// If set the block timestamp is parent_timestamp + timestamp_offset_s instead of the slot timestamp.
+    /// Only meant for testing time dependent behavior, must be > 0 so timestamps keep increasing.
+    #[serde(default)]
+    pub timestamp_offset_s: Option<u64>,
+    /// Reject txs that would create a new account (recipient does not exist or contract creation).
+    #[serde(default)]
+    pub reject_new_account_recipients: bool,
+    /// Signed tx (network encoding, as in eth_sendRawTransaction) committed at the top of every block before any other order.
+    /// eg: a sequencer tx that must open every block.
+    #[serde(default)]
+    pub anchor_tx: Option<Bytes>,
+    /// By default if anchor_tx fails the block is not built, with this we build the block without it.
+    #[serde(default)]
+    pub allow_anchor_tx_failure: bool,
+    /// Logs of the included orders kept on the block trace, see [`LogSummaryMode`].
+    #[serde(default)]
+    pub log_summary: LogSummaryMode,
+    #[serde(default)]
+    pub parent_not_found: ParentNotFoundAction,
+    /// Max number of orders considered on each block building iteration. Half of them are always the best orders, the
+    /// other half continues on each iteration with the orders that were not considered (see [`OrderConsiderationCursor`]).
+    /// None -> all orders are considered on every iteration.
+    #[serde(default)]
+    pub max_orders_per_iteration: Option<usize>,
+    /// Don't finalize (skip root hash etc) blocks where the fill loop committed no order, whatever the coinbase mode.
+    /// Such block would only contain our payout tx (if any) and the anchor_tx (if any).
+    #[serde(default)]
+    pub skip_finalize_on_no_commits: bool,
+    /// If anchor_tx carries blobs and its signer can't pay for it (blob fee at the current blob basefee included)
+    /// we build the block without it instead of failing on the commit.
+    #[serde(default)]
+    pub skip_underfunded_blob_anchor_tx: bool,
+    /// How sbundle refunds are paid, see [`RefundMode`].
+    #[serde(default)]
+    pub refund_mode: RefundMode,
+    /// If set, every built block is held this long before being sent to the sink (eg: to bid closer to a target time in the slot).
+    /// A block still waiting when the slot is cancelled is dropped.
+    #[serde(default)]
+    pub submit_delay_ms: Option<u64>,
+    /// Basis points (100 = 1%) of the reads served by the cached reads shared between iterations that are verified against the db.
+    /// On any divergence the block is dropped and the shared cache is disabled for the rest of the slot.
+    #[serde(default)]
+    pub verify_cached_reads_bps: u32,
+    /// Export time_to_first_commit (build start -> first successful commit) on every build.
+    #[serde(default)]
+    pub time_to_first_commit_metric: bool,
+    /// Export build_cpu_time, cpu time of the building thread during the fill (wall time minus waiting on db/locks).
+    #[serde(default)]
+    pub build_cpu_time_metric: bool,
+    /// Don't include mempool txs that revert, by default they are included if they pay enough for the gas they burn.
+    #[serde(default)]
+    pub exclude_reverting_txs: bool,
+    /// Load the state of orders with access lists (EIP-2930 or simulation read set) in a single pass before executing them.
+    #[serde(default)]
+    pub batched_reads: bool,
+    /// Skip the block (before filling) when the builder signer balance can't pay a plain transfer (TRANSFER_GAS) at the block basefee.
+    /// The profit of the block is not taken into account, with this the signer account must be kept funded.
+    /// Only for CoinbaseMode::BuilderSigner, with SuggestedFeeRecipient the signer sends no txs.
+    #[serde(default)]
+    pub skip_unaffordable_basefee: bool,
+    /// Re-execute every committed tx alone against its pre-commit state and fail the block on any difference
+    /// (see [`BlockState::with_commit_verification`]). Expensive, for tests only.
+    #[serde(default)]
+    pub verify_commits: bool,
+    /// Abort the fill with [`ProviderIoBudgetExceeded`] when opening the state plus the state provider lookups of the
+    /// build take longer than this (cached reads hits don't count).
+    #[serde(default)]
+    pub max_provider_io_time_ms: Option<u64>,
+    /// Reject txs whose logs take more than this many bytes (see [`crate::building::logs_size`]).
+    #[serde(default)]
+    pub max_tx_logs_size: Option<usize>,
+    /// Check the finalized header basefee against the one computed from the parent header
+    /// (see [`crate::building::verify_header_basefee`]), mismatches are logged and counted (header_basefee_mismatches).
+    #[serde(default)]
+    pub verify_header_basefee: bool,
+    /// If set replaces the withdrawals of the slot (see [`BlockBuildingContext::modify_withdrawals`]).
+    /// Only meant for testing withdrawal processing, the blocks won't be valid for the CL.
+    #[serde(default)]
+    pub withdrawals: Option<Vec<Withdrawal>>,
+}
+
+/// What to do when the slot parent block is not in our db (eg: we are too far behind or it was pruned).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParentNotFoundAction {
+    /// Cancel the slot (stops all the builders of the slot).
+    #[default]
+    SkipSlot,
+    /// Keep retrying every PARENT_NOT_FOUND_RETRY_INTERVAL until the slot is cancelled in case our node catches up.
+    WaitAndRetry,
+}
+
+const PARENT_NOT_FOUND_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+/// How often we check for cancellation while holding a block for submit_delay_ms.
+const SUBMIT_DELAY_POLL_INTERVAL: Duration = Duration::from_millis(5);
+/// Gas of a plain eth transfer, see [`OrderingBuilderConfig::skip_unaffordable_basefee`].
+const TRANSFER_GAS: u64 = 21_000;
+
+fn default_use_suggested_fee_recipient_as_coinbase() -> bool {
+    true
+}
+
+/// Same values as deserializing a config with only the required fields set to their defaults.
+impl Default for OrderingBuilderConfig {
+    fn default() -> Self {
+        Self {
+            discard_txs: Default::default(),
+            sorting: Default::default(),
+            failed_order_retries: Default::default(),
+            drop_failed_orders: Default::default(),
+            coinbase_payment: Default::default(),
+            use_suggested_fee_recipient_as_coinbase:
+                default_use_suggested_fee_recipient_as_coinbase(),
+            payout_mode: Default::default(),
+            build_duration_deadline_ms: Default::default(),
+            timestamp_offset_s: Default::default(),
+            reject_new_account_recipients: Default::default(),
+            anchor_tx: Default::default(),
+            allow_anchor_tx_failure: Default::default(),
+            log_summary: Default::default(),
+            parent_not_found: Default::default(),
+            max_orders_per_iteration: Default::default(),
+            skip_finalize_on_no_commits: Default::default(),
+            skip_underfunded_blob_anchor_tx: Default::default(),
+            refund_mode: Default::default(),
+            submit_delay_ms: Default::default(),
+            verify_cached_reads_bps: Default::default(),
+            time_to_first_commit_metric: Default::default(),
+            build_cpu_time_metric: Default::default(),
+            exclude_reverting_txs: Default::default(),
+            batched_reads: Default::default(),
+            skip_unaffordable_basefee: Default::default(),
+            verify_commits: Default::default(),
+            max_provider_io_time_ms: Default::default(),
+            max_tx_logs_size: Default::default(),
+            verify_header_basefee: Default::default(),
+            withdrawals: Default::default(),
+        }
+    }
+}
+
+/// history_by_block_hash fails with BlockHashNotFound when the parent is not in the db.
+fn is_parent_not_found_error(err: &eyre::Report, parent: B256) -> bool {
+    matches!(
+        err.downcast_ref::<ProviderError>(),
+        Some(ProviderError::BlockHashNotFound(hash)) if *hash == parent
+    )
 }
 
 impl OrderingBuilderConfig {
     pub fn build_duration_deadline(&self) -> Option<Duration> {
         self.build_duration_deadline_ms.map(Duration::from_millis)
     }
+
+    pub fn submit_delay(&self) -> Option<Duration> {
+        self.submit_delay_ms.map(Duration::from_millis)
+    }
+
+    /// Mode of the first build of the slot (see coinbase_payment).
+    pub fn coinbase_mode(&self) -> CoinbaseMode {
+        if self.coinbase_payment {
+            CoinbaseMode::SuggestedFeeRecipient
+        } else {
+            self.builder_signer_coinbase_mode()
+        }
+    }
+
+    pub fn builder_signer_coinbase_mode(&self) -> CoinbaseMode {
+        CoinbaseMode::BuilderSigner {
+            payout: self.payout_mode,
+        }
+    }
+
+    /// Block timestamp to use given the parent timestamp, None means use the ctx timestamp.
+    pub fn block_timestamp(&self, parent_timestamp: u64) -> eyre::Result<Option<u64>> {
+        match self.timestamp_offset_s {
+            Some(0) => eyre::bail!("timestamp_offset_s must be > 0, block timestamp must be bigger than the parent timestamp"),
+            Some(offset) => Ok(Some(parent_timestamp.checked_add(offset).ok_or_else(|| {
+                eyre::eyre!("timestamp_offset_s overflows block timestamp")
+            })?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn anchor_tx(&self) -> eyre::Result<Option<TransactionSignedEcRecoveredWithBlobs>> {
+        self.anchor_tx
+            .clone()
+            .map(|raw_tx| {
+                TransactionSignedEcRecoveredWithBlobs::decode_enveloped_with_real_blobs(raw_tx)
+                    .map_err(|err| eyre::eyre!("Invalid anchor_tx: {:?}", err))
+            })
+            .transpose()
+    }
 }
 
 pub fn run_ordering_builder<DB: Database + Clone + 'static, SinkType: BlockBuildingSink>(
     input: LiveBuilderInput<DB, SinkType>,
     config: &OrderingBuilderConfig,
 ) {
+    let builder_name = input.builder_name.clone();
+    run_candidates_loop(input, vec![(builder_name, config.clone())], SingleCandidate);
+}
+
+/// Chooses the candidate that builds on each iteration of [`run_candidates_loop`].
+trait CandidatePicker {
+    fn next_candidate(&mut self) -> usize;
+    /// profit: bid value of the block the candidate built on its iteration, 0 if it built none.
+    fn record_profit(&mut self, candidate: usize, profit: U256);
+}
+
+/// Plain ordering builder, a single candidate builds every iteration.
+struct SingleCandidate;
+
+impl CandidatePicker for SingleCandidate {
+    fn next_candidate(&mut self) -> usize {
+        0
+    }
+
+    fn record_profit(&mut self, _candidate: usize, _profit: U256) {}
+}
+
+impl CandidatePicker for &Mutex<BuildBudgetAllocator> {
+    fn next_candidate(&mut self) -> usize {
+        self.lock().unwrap().next_candidate()
+    }
+
+    fn record_profit(&mut self, candidate: usize, profit: U256) {
+        self.lock().unwrap().record_profit(candidate, profit);
+    }
+}
+
+/// Build loop of the ordering builders: consumes the orders and on every iteration the candidate chosen by picker builds
+/// (and submits) a block with all the orders so far.
+/// candidates: (builder_name, config), they share the orders so sorting and parent_not_found are taken from the first one.
+fn run_candidates_loop<DB: Database + Clone + 'static, SinkType: BlockBuildingSink>(
+    input: LiveBuilderInput<DB, SinkType>,
+    candidates: Vec<(String, OrderingBuilderConfig)>,
+    mut picker: impl CandidatePicker,
+) {
+    let Some((_, first_candidate)) = candidates.first() else {
+        return;
+    };
     let block_number = input.ctx.block_env.number.to::<u64>();
+    let parent = input.ctx.attributes.parent;
+    telemetry::inc_build_attempts(&input.builder_name);
     //
     let mut order_intake_consumer = OrderIntakeConsumer::new(
         input.provider_factory.clone(),
         input.input,
-        input.ctx.attributes.parent,
-        config.sorting,
+        parent,
+        first_candidate.sorting,
         &input.sbundle_mergeabe_signers,
     );
 
-    let mut builder = OrderingBuilderContext::new(
-        input.provider_factory.clone(),
-        input.slot_bidder,
-        input.root_hash_task_pool,
-        input.builder_name,
-        input.ctx,
-        config.clone(),
-    );
+    // (builder, coinbase_mode) for each candidate
+    let mut builders: Vec<_> = candidates
+        .iter()
+        .map(|(builder_name, config)| {
+            let builder = OrderingBuilderContext::new(
+                input.provider_factory.clone(),
+                input.slot_bidder.clone(),
+                input.root_hash_task_pool.clone(),
+                builder_name.clone(),
+                input.ctx.clone(),
+                config.clone(),
+            )
+            .with_pending_candidates(input.pending_candidates.clone());
+            (builder, config.coinbase_mode())
+        })
+        .collect();
 
     // this is a hack to mark used orders until built block trace is implemented as a sane thing
     let mut removed_orders = Vec::new();
-    let mut use_suggested_fee_recipient_as_coinbase = config.coinbase_payment;
+    // returns true if we should stop building
+    let handle_parent_not_found = || {
+        on_parent_not_found(
+            first_candidate.parent_not_found,
+            block_number,
+            parent,
+            &input.cancel,
+        )
+    };
     'building: loop {
         if input.cancel.is_cancelled() {
             break 'building;
@@ -107,24 +383,63 @@ pub fn run_ordering_builder<DB: Database + Clone + 'static, SinkType: BlockBuild
                 }
             }
             Err(err) => {
-                error!(?err, "Error consuming next order batch");
+                if is_parent_not_found_error(&err, parent) {
+                    if handle_parent_not_found() {
+                        break 'building;
+                    }
+                } else if let Some(repeated_in_last_minute) = rate_limited(
+                    "ordering_builder.consume_next_batch",
+                    report_discriminant(&err),
+                ) {
+                    error!(
+                        ?err,
+                        repeated_in_last_minute, "Error consuming next order batch"
+                    );
+                }
                 continue;
             }
         }
 
         let orders = order_intake_consumer.current_block_orders();
-        match builder.build_block(orders, use_suggested_fee_recipient_as_coinbase) {
+        let candidate = picker.next_candidate();
+        let config = &candidates[candidate].1;
+        let (builder, coinbase_mode) = &mut builders[candidate];
+        // None -> the build says nothing about the candidate (eg: parent not found)
+        let profit = match builder.build_lazy_block(orders, *coinbase_mode) {
             Ok(Some(block)) => {
                 if block.trace.got_no_signer_error {
-                    use_suggested_fee_recipient_as_coinbase = false;
+                    *coinbase_mode = config.builder_signer_coinbase_mode();
+                }
+                let bid_value = block.trace.bid_value;
+                if !submit_lazy_block(&input.sink, block, config.submit_delay(), &input.cancel) {
+                    break 'building;
+                }
+                if builder.finalize_requested() {
+                    debug!(block_number, "Slot bidder requested early finalization");
+                    break 'building;
                 }
-                input.sink.new_block(block);
+                Some(bid_value)
+            }
+            Ok(None) => {
+                // eg: the fill pass asked to finalize but the block was skipped (no commits)
+                if builder.finalize_requested() {
+                    debug!(
+                        block_number,
+                        "Slot bidder requested early finalization, no block built"
+                    );
+                    break 'building;
+                }
+                Some(U256::ZERO)
             }
-            Ok(None) => {}
             Err(err) => {
                 // @Types
                 let err_str = err.to_string();
-                if err_str.contains("failed to initialize consistent view") {
+                if is_parent_not_found_error(&err, parent) {
+                    if handle_parent_not_found() {
+                        break 'building;
+                    }
+                    None
+                } else if err_str.contains("failed to initialize consistent view") {
                     let last_block_number = input
                         .provider_factory
                         .last_block_number()
@@ -135,15 +450,26 @@ pub fn run_ordering_builder<DB: Database + Clone + 'static, SinkType: BlockBuild
                     );
                     input.cancel.cancel();
                     break 'building;
-                } else if !err_str.contains("Profit too low") {
-                    if is_provider_factory_health_error(&err) {
-                        error!(?err, "Cancelling building due to provider factory error");
-                        break 'building;
-                    } else {
-                        warn!(?err, "Error filling orders");
+                } else {
+                    if !err_str.contains("Profit too low") {
+                        if is_provider_factory_health_error(&err) {
+                            error!(?err, "Cancelling building due to provider factory error");
+                            break 'building;
+                        } else if let Some(repeated_in_last_minute) =
+                            rate_limited("ordering_builder.fill_orders", report_discriminant(&err))
+                        {
+                            warn!(
+                                ?err,
+                                candidate, repeated_in_last_minute, "Error filling orders"
+                            );
+                        }
                     }
+                    Some(U256::ZERO)
                 }
             }
+        };
+        if let Some(profit) = profit {
+            picker.record_profit(candidate, profit);
         }
         if config.drop_failed_orders {
             let mut removed = order_intake_consumer.remove_orders(builder.failed_orders.drain());
@@ -152,11 +478,61 @@ pub fn run_ordering_builder<DB: Database + Clone + 'static, SinkType: BlockBuild
     }
 }
 
+/// Returns true if the builder should stop building.
+fn on_parent_not_found(
+    action: ParentNotFoundAction,
+    block_number: u64,
+    parent: B256,
+    cancel: &CancellationToken,
+) -> bool {
+    match action {
+        ParentNotFoundAction::SkipSlot => {
+            warn!(
+                block_number,
+                ?parent,
+                "Parent block not found, cancelling slot"
+            );
+            cancel.cancel();
+            true
+        }
+        ParentNotFoundAction::WaitAndRetry => {
+            debug!(block_number, ?parent, "Parent block not found, waiting");
+            std::thread::sleep(PARENT_NOT_FOUND_RETRY_INTERVAL);
+            false
+        }
+    }
+}
+
+/// Sends the block to the sink after waiting submit_delay.
+/// Returns false if cancel was triggered while waiting, the block is not sent in that case.
+fn submit_lazy_block<SinkType: BlockBuildingSink>(
+    sink: &SinkType,
+    block: LazyBlock,
+    submit_delay: Option<Duration>,
+    cancel: &CancellationToken,
+) -> bool {
+    if let Some(submit_delay) = submit_delay {
+        let deadline = Instant::now() + submit_delay;
+        loop {
+            if cancel.is_cancelled() {
+                return false;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            std::thread::sleep(SUBMIT_DELAY_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+    sink.new_lazy_block(block);
+    true
+}
+
 pub fn backtest_simulate_block<DB: Database + Clone + 'static>(
     ordering_config: OrderingBuilderConfig,
     input: BacktestSimulateBlockInput<'_, DB>,
 ) -> eyre::Result<(Block, CachedReads)> {
-    let use_suggested_fee_recipient_as_coinbase = ordering_config.coinbase_payment;
+    let coinbase_mode = ordering_config.coinbase_mode();
     let state_provider = input
         .provider_factory
         .history_by_block_number(input.ctx.block_env.number.to::<u64>() - 1)?;
@@ -177,11 +553,22 @@ pub fn backtest_simulate_block<DB: Database + Clone + 'static>(
     .with_skip_root_hash()
     .with_cached_reads(input.cached_reads.unwrap_or_default());
     let block = builder
-        .build_block(block_orders, use_suggested_fee_recipient_as_coinbase)?
+        .build_block(block_orders, coinbase_mode)?
         .ok_or_else(|| eyre::eyre!("No block built"))?;
     Ok((block, builder.take_cached_reads().unwrap_or_default()))
 }
 
+/// Number of most recent build times used for [`OrderingBuilderContext::build_time_percentiles`].
+pub const BUILD_TIME_SAMPLES: usize = 100;
+
+/// Percentiles of the recent build times (fill + finalize) of a builder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BuildTimePercentiles {
+    pub p50: Duration,
+    pub p95: Duration,
+    pub p99: Duration,
+}
+
 #[derive(Debug)]
 pub struct OrderingBuilderContext<DB> {
     provider_factory: ProviderFactory<DB>,
@@ -191,13 +578,29 @@ pub struct OrderingBuilderContext<DB> {
     config: OrderingBuilderConfig,
     root_hash_mode: RootHashMode,
     slot_bidder: Arc<dyn SlotBidder>,
+    /// Absolute time after which building a block is pointless (eg: slot deadline).
+    build_deadline: Option<Instant>,
+    /// Every finalized block is offered here.
+    pending_candidates: Arc<PendingCandidates>,
 
     // caches
     cached_reads: Option<CachedReads>,
+    /// Set when we detect that cached_reads diverged from the db, from then on every block starts with an empty cache.
+    cached_reads_disabled: bool,
 
     // scratchpad
     failed_orders: HashSet<OrderId>,
     order_attempts: HashMap<OrderId, usize>,
+    /// Profit each order was first ranked with on the current fill (see [`crate::building::profit_divergence`]).
+    ranked_profits: HashMap<OrderId, U256>,
+    /// Persists across build_block calls so iterations continue where the last one stopped.
+    consideration_cursor: OrderConsiderationCursor,
+    /// Last BUILD_TIME_SAMPLES build times of built blocks.
+    build_times: DurationWindow,
+    /// Fill passes done, see [`FillPassInfo::iteration`].
+    fill_passes: usize,
+    /// Set when the slot bidder answered FillPassAction::FinalizeNow.
+    finalize_requested: bool,
 }
 
 impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
@@ -214,12 +617,20 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
             root_hash_task_pool,
             builder_name,
             ctx,
-            config,
             root_hash_mode: RootHashMode::CorrectRoot,
             slot_bidder,
+            build_deadline: None,
+            pending_candidates: Default::default(),
             cached_reads: None,
+            cached_reads_disabled: false,
             failed_orders: HashSet::default(),
             order_attempts: HashMap::default(),
+            ranked_profits: HashMap::default(),
+            consideration_cursor: OrderConsiderationCursor::new(config.max_orders_per_iteration),
+            build_times: DurationWindow::new(BUILD_TIME_SAMPLES),
+            fill_passes: 0,
+            finalize_requested: false,
+            config,
         }
     }
 
@@ -231,6 +642,22 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
         }
     }
 
+    /// build_block will not start (or continue filling) after this instant.
+    pub fn with_build_deadline(self, build_deadline: Instant) -> Self {
+        Self {
+            build_deadline: Some(build_deadline),
+            ..self
+        }
+    }
+
+    /// Defaults to disabled candidates nobody reads.
+    pub fn with_pending_candidates(self, pending_candidates: Arc<PendingCandidates>) -> Self {
+        Self {
+            pending_candidates,
+            ..self
+        }
+    }
+
     pub fn with_cached_reads(self, cached_reads: CachedReads) -> Self {
         Self {
             cached_reads: Some(cached_reads),
@@ -242,21 +669,92 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
         self.cached_reads.take()
     }
 
-    /// use_suggested_fee_recipient_as_coinbase: all the mev profit goes directly to the slot suggested_fee_recipient so we avoid the payout tx.
+    /// Puts back the cache of a fill pass that ended without a block so the next pass still starts warm.
+    fn keep_cached_reads(&mut self, state: BlockState) {
+        if state.cached_reads_divergences() > 0 {
+            self.cached_reads_disabled = true;
+        }
+        if !self.cached_reads_disabled {
+            self.cached_reads = Some(state.into_parts().0);
+        }
+    }
+
+    /// Orders considered vs available on the last build_block.
+    pub fn consideration_stats(&self) -> ConsiderationStats {
+        self.consideration_cursor.stats()
+    }
+
+    /// True if the slot bidder asked to stop building after the last block (see [`FillPassAction::FinalizeNow`]).
+    pub fn finalize_requested(&self) -> bool {
+        self.finalize_requested
+    }
+
+    /// None until we build the first block.
+    pub fn build_time_percentiles(&self) -> Option<BuildTimePercentiles> {
+        Some(BuildTimePercentiles {
+            p50: self.build_times.percentile(0.5)?,
+            p95: self.build_times.percentile(0.95)?,
+            p99: self.build_times.percentile(0.99)?,
+        })
+    }
+
+    fn record_build_time(&mut self, build_time: Duration) {
+        self.build_times.push(build_time);
+        if let Some(percentiles) = self.build_time_percentiles() {
+            telemetry::set_build_time_percentiles(
+                &self.builder_name,
+                percentiles.p50,
+                percentiles.p95,
+                percentiles.p99,
+            );
+        }
+    }
+
+    /// CoinbaseMode::SuggestedFeeRecipient: all the mev profit goes directly to the slot suggested_fee_recipient so we avoid the payout tx.
     ///     This mode disables mev-share orders since the builder has to receive the mev profit to give some portion back to the mev-share user.
-    /// !use_suggested_fee_recipient_as_coinbase: all the mev profit goes to the builder and at the end of the block we pay to the suggested_fee_recipient.
+    ///     Falls back to BuilderSigner if the config (use_suggested_fee_recipient_as_coinbase) or the slot bidder don't allow it.
+    /// CoinbaseMode::BuilderSigner: all the mev profit goes to the builder and at the end of the block we pay to the suggested_fee_recipient.
+    /// The mode used ends up on the trace (BuiltBlockTrace::coinbase_mode).
     pub fn build_block(
         &mut self,
-        mut block_orders: BlockOrders,
-        use_suggested_fee_recipient_as_coinbase: bool,
+        block_orders: BlockOrders,
+        coinbase_mode: CoinbaseMode,
     ) -> eyre::Result<Option<Block>> {
-        let use_suggested_fee_recipient_as_coinbase = use_suggested_fee_recipient_as_coinbase
-            && self.slot_bidder.is_pay_to_coinbase_allowed();
+        Ok(self
+            .build_lazy_block(block_orders, coinbase_mode)?
+            .map(LazyBlock::seal))
+    }
+
+    /// Like build_block but the payload is not sealed (see [`LazyBlock`]).
+    pub fn build_lazy_block(
+        &mut self,
+        mut block_orders: BlockOrders,
+        coinbase_mode: CoinbaseMode,
+    ) -> eyre::Result<Option<LazyBlock>> {
+        let coinbase_mode = match coinbase_mode {
+            CoinbaseMode::SuggestedFeeRecipient
+                if !self.config.use_suggested_fee_recipient_as_coinbase
+                    || !self.slot_bidder.is_pay_to_coinbase_allowed() =>
+            {
+                self.config.builder_signer_coinbase_mode()
+            }
+            coinbase_mode => coinbase_mode,
+        };
 
         let build_attempt_id: u32 = rand::random();
         let span = info_span!("build_run", build_attempt_id);
         let _guard = span.enter();
 
+        if let Some(build_deadline) = self.build_deadline {
+            if build_deadline <= Instant::now() {
+                trace!(
+                    builder_name = self.builder_name,
+                    "No time left to build block, skipping"
+                );
+                return Ok(None);
+            }
+        }
+
         check_provider_factory_health(self.ctx.block(), &self.provider_factory)?;
 
         let build_start = Instant::now();
@@ -264,60 +762,206 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
 
         // Create a new ctx to remove builder_signer if necessary
         let mut new_ctx = self.ctx.clone();
-        if use_suggested_fee_recipient_as_coinbase {
-            new_ctx.modify_use_suggested_fee_recipient_as_coinbase();
+        new_ctx.modify_coinbase_mode(coinbase_mode)?;
+        if self.config.reject_new_account_recipients {
+            new_ctx.modify_reject_new_account_recipients(true);
+        }
+        new_ctx.modify_refund_mode(self.config.refund_mode);
+        if self.config.exclude_reverting_txs {
+            new_ctx.modify_exclude_reverting_txs(true);
+        }
+        new_ctx.modify_max_tx_logs_size(self.config.max_tx_logs_size);
+        if let Some(withdrawals) = &self.config.withdrawals {
+            new_ctx.modify_withdrawals(withdrawals.clone());
+        }
+        if self.config.timestamp_offset_s.is_some() {
+            let parent_timestamp = self
+                .provider_factory
+                .header(&new_ctx.attributes.parent)?
+                .ok_or(ProviderError::BlockHashNotFound(new_ctx.attributes.parent))?
+                .timestamp;
+            if let Some(timestamp) = self.config.block_timestamp(parent_timestamp)? {
+                new_ctx.modify_timestamp(timestamp);
+            }
         }
         let ctx = &new_ctx;
 
         self.failed_orders.clear();
         self.order_attempts.clear();
+        self.ranked_profits.clear();
 
         // @Maybe an issue - we have 2 db txs here (one for hash and one for finalize)
+        let state_provider_open_start = Instant::now();
         let state_provider = self
             .provider_factory
             .history_by_block_hash(ctx.attributes.parent)?;
+        let state_provider_open_time = state_provider_open_start.elapsed();
+        let batch_reader = self.config.batched_reads.then(|| {
+            PlainStateBatchReader::new(self.provider_factory.clone(), ctx.attributes.parent)
+        });
+        let provider_io_budget = self
+            .config
+            .max_provider_io_time_ms
+            .map(Duration::from_millis);
+        let check_provider_io_budget =
+            |state: &BlockState| -> Result<(), ProviderIoBudgetExceeded> {
+                match provider_io_budget {
+                    Some(budget) => {
+                        let spent = state_provider_open_time + state.provider_io().time();
+                        if spent > budget {
+                            return Err(ProviderIoBudgetExceeded { spent, budget });
+                        }
+                        Ok(())
+                    }
+                    None => Ok(()),
+                }
+            };
         let fee_recipient_balance_before = state_provider
             .account_balance(ctx.attributes.suggested_fee_recipient)?
             .unwrap_or_default();
+        // checked on the provider before the cached reads are moved into the block state
+        if self.config.skip_unaffordable_basefee
+            && matches!(coinbase_mode, CoinbaseMode::BuilderSigner { .. })
+        {
+            if let Some(builder_signer) = &ctx.builder_signer {
+                let signer_balance = state_provider
+                    .account_balance(builder_signer.address)?
+                    .unwrap_or_default();
+                let min_fee = U256::from(TRANSFER_GAS).saturating_mul(ctx.block_env.basefee);
+                if signer_balance < min_fee {
+                    trace!(
+                        builder_name = self.builder_name,
+                        ?signer_balance,
+                        ?min_fee,
+                        "Builder signer can't afford a transfer at the current basefee, skipping block"
+                    );
+                    return Ok(None);
+                }
+            }
+        }
+        let fill_cpu_start = self.config.build_cpu_time_metric.then(ThreadTime::now);
         let (mut built_block_trace, state, partial_block) = {
             let mut partial_block =
                 PartialBlock::new(self.config.discard_txs, self.config.sorting.into())
                     .with_tracer(GasUsedSimulationTracer::default());
             let mut state = BlockState::new(&state_provider)
-                .with_cached_reads(self.cached_reads.take().unwrap_or_default());
+                .with_cached_reads(self.cached_reads.take().unwrap_or_default())
+                .with_cached_reads_verification(self.config.verify_cached_reads_bps)
+                .with_batch_reader(
+                    batch_reader
+                        .as_ref()
+                        .map(|reader| reader as &dyn BatchStateReader),
+                )
+                .with_commit_verification(self.config.verify_commits);
             partial_block.pre_block_call(ctx, &mut state)?;
-            let mut built_block_trace = BuiltBlockTrace::new();
+            let mut built_block_trace = BuiltBlockTrace::new()
+                .with_log_summary_mode(self.config.log_summary)
+                .with_builder_identity(ctx.builder_identity.clone());
+            built_block_trace.state_provider_open_time = state_provider_open_time;
+            built_block_trace.coinbase_mode = ctx.coinbase_mode();
+            check_provider_io_budget(&state)?;
 
             let mut order_attempts: HashMap<OrderId, usize> = HashMap::default();
+            let mut commit_attempts = 0;
+            let mut commit_successes = 0;
 
-            let payout_tx_gas = if use_suggested_fee_recipient_as_coinbase {
-                None
-            } else {
-                let payout_tx_gas = estimate_payout_gas_limit(
-                    ctx.attributes.suggested_fee_recipient,
-                    ctx,
-                    &mut state,
-                    0,
-                )?;
-                partial_block.reserve_gas(payout_tx_gas);
-                Some(payout_tx_gas)
+            let payout_tx_gas = match coinbase_mode {
+                CoinbaseMode::SuggestedFeeRecipient => None,
+                CoinbaseMode::BuilderSigner { payout } => {
+                    let payout_tx_gas = match payout {
+                        PayoutMode::EstimatedGasLimit => estimate_payout_gas_limit(
+                            ctx.attributes.suggested_fee_recipient,
+                            ctx,
+                            &mut state,
+                            0,
+                        )?,
+                        PayoutMode::FixedGasLimit(gas_limit) => gas_limit,
+                    };
+                    partial_block.reserve_gas(payout_tx_gas);
+                    Some(payout_tx_gas)
+                }
+            };
+
+            let anchor_tx = match self.config.anchor_tx()? {
+                Some(anchor_tx)
+                    if self.config.skip_underfunded_blob_anchor_tx
+                        && !can_pay_blob_tx(&anchor_tx, ctx, &mut state)? =>
+                {
+                    warn!(
+                        tx_hash = ?anchor_tx.hash(),
+                        signer = ?anchor_tx.signer(),
+                        "Blob anchor tx signer can't pay for it, building block without it"
+                    );
+                    None
+                }
+                anchor_tx => anchor_tx,
             };
+            if let Some(anchor_tx) = anchor_tx {
+                let anchor_order = SimulatedOrder {
+                    order: Order::Tx(MempoolTx::new(anchor_tx)),
+                    sim_value: SimValue::default(),
+                    prev_order: None,
+                    used_state_trace: None,
+                };
+                match partial_block.commit_order(&anchor_order, ctx, &mut state)? {
+                    Ok(res) => {
+                        let nonces_updated: Vec<_> = res
+                            .nonces_updated
+                            .iter()
+                            .map(|(account, nonce)| AccountNonce {
+                                account: *account,
+                                nonce: *nonce,
+                            })
+                            .collect();
+                        block_orders.update_onchain_nonces(&nonces_updated);
+                        built_block_trace.add_included_order(res);
+                    }
+                    Err(err) if self.config.allow_anchor_tx_failure => {
+                        warn!(?err, "Anchor tx failed, building block without it");
+                    }
+                    Err(err) => {
+                        return Err(eyre::eyre!("Anchor tx failed: {:?}", err));
+                    }
+                }
+            }
 
+            self.consideration_cursor.start_iteration(&block_orders);
+            // Profitable position constrained bundles pop first (see OrderPriority) so they get the positions right
+            // after the anchor tx, once the block is past their max_block_position they fail with BlockPositionTooHigh.
             // @Perf when gas left is too low we should break.
-            while let Some(sim_order) = block_orders.pop_order() {
+            while let Some(sim_order) = self.consideration_cursor.pop_order(&mut block_orders) {
                 if let Some(deadline) = self.config.build_duration_deadline() {
                     if build_start.elapsed() > deadline {
                         break;
                     }
                 }
+                if let Some(build_deadline) = self.build_deadline {
+                    if Instant::now() > build_deadline {
+                        break;
+                    }
+                }
 
+                self.ranked_profits
+                    .entry(sim_order.id())
+                    .or_insert(sim_order.sim_value.coinbase_profit);
                 let start_time = Instant::now();
                 let commit_result = partial_block.commit_order(&sim_order, ctx, &mut state)?;
                 let order_commit_time = start_time.elapsed();
+                check_provider_io_budget(&state)?;
                 let mut gas_used = 0;
                 let mut execution_error = None;
                 let mut reinserted = false;
                 let success = commit_result.is_ok();
+                commit_attempts += 1;
+                if success {
+                    commit_successes += 1;
+                    if commit_successes == 1 && self.config.time_to_first_commit_metric {
+                        telemetry::add_time_to_first_commit(
+                            &self.builder_name,
+                            build_start.elapsed(),
+                        );
+                    }
+                }
                 match commit_result {
                     Ok(res) => {
                         gas_used = res.gas_used;
@@ -362,6 +1006,74 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
                     "Executed order"
                 );
             }
+            built_block_trace.commit_attempts = commit_attempts;
+            let consideration_stats = self.consideration_cursor.stats();
+            trace!(
+                builder_name = self.builder_name,
+                considered = consideration_stats.considered,
+                available = consideration_stats.available,
+                "Considered orders"
+            );
+            telemetry::set_orders_considered(
+                &self.builder_name,
+                consideration_stats.considered,
+                consideration_stats.available,
+            );
+            telemetry::add_state_provider_reads(
+                &self.builder_name,
+                state.provider_reads(),
+                state.batched_provider_reads(),
+            );
+            built_block_trace.provider_io = state.provider_io();
+            telemetry::add_provider_io(
+                &self.builder_name,
+                state_provider_open_time,
+                &built_block_trace.provider_io,
+            );
+            if commit_attempts != 0 {
+                telemetry::set_commit_success_ratio(
+                    &self.builder_name,
+                    commit_successes as f64 / commit_attempts as f64,
+                );
+            }
+
+            let fill_pass_info = FillPassInfo {
+                remaining_gas: ctx
+                    .block_env
+                    .gas_limit
+                    .to::<u64>()
+                    .saturating_sub(partial_block.gas_used + partial_block.gas_reserved),
+                true_block_value: partial_block.coinbase_profit,
+                iteration: self.fill_passes,
+                time_into_slot: OffsetDateTime::now_utc() - ctx.timestamp(),
+            };
+            self.fill_passes += 1;
+            match self.slot_bidder.on_fill_pass(&fill_pass_info) {
+                FillPassAction::ContinueFilling => {}
+                FillPassAction::FinalizeNow => {
+                    self.finalize_requested = true;
+                }
+                FillPassAction::SkipSubmission => {
+                    trace!(
+                        builder_name = self.builder_name,
+                        iteration = fill_pass_info.iteration,
+                        "Slot bidder skipped block submission"
+                    );
+                    self.keep_cached_reads(state);
+                    return Ok(None);
+                }
+            }
+
+            if self.config.skip_finalize_on_no_commits && commit_successes == 0 {
+                trace!(
+                    block = ctx.block_env.number.to::<u64>(),
+                    builder_name = self.builder_name,
+                    commit_attempts,
+                    "No orders committed, skipped block finalization",
+                );
+                self.keep_cached_reads(state);
+                return Ok(None);
+            }
 
             let fee_recipient_balance_after = state_provider
                 .account_balance(ctx.attributes.suggested_fee_recipient)?
@@ -385,9 +1097,10 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
                 trace!(
                     block = ctx.block_env.number.to::<u64>(),
                     builder_name = self.builder_name,
-                    use_suggested_fee_recipient_as_coinbase,
+                    ?coinbase_mode,
                     "Skipped block finalization",
                 );
+                self.keep_cached_reads(state);
                 return Ok(None);
             }
 
@@ -396,13 +1109,35 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
         };
 
         let build_time = build_start.elapsed();
+        if let Some(fill_cpu_start) = fill_cpu_start {
+            telemetry::add_build_cpu_time(&self.builder_name, fill_cpu_start.elapsed());
+        }
 
         built_block_trace.fill_time = build_time;
 
         let start = Instant::now();
 
+        let cached_reads_divergences = state.cached_reads_divergences();
+        if cached_reads_divergences > 0 {
+            telemetry::add_cached_reads_divergences(&self.builder_name, cached_reads_divergences);
+            error!(
+                block = ctx.block_env.number.to::<u64>(),
+                builder_name = self.builder_name,
+                cached_reads_divergences,
+                "Cached reads diverged from db, disabling shared cached reads for the slot",
+            );
+            self.cached_reads_disabled = true;
+            eyre::bail!("Cached reads diverged from db, block dropped");
+        }
+
+        // taken before assemble consumes the state, offered only if the block is finalized
+        let pending_accounts = self
+            .pending_candidates
+            .changed_accounts(state.bundle_state());
+
         let sim_gas_used = partial_block.tracer.used_gas;
-        let finalized_block = partial_block.finalize(
+        let coinbase_payment_tx_hash = partial_block.proposer_payout_tx_hash;
+        let assembled = partial_block.assemble(
             state,
             ctx,
             self.provider_factory.clone(),
@@ -410,16 +1145,45 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
             self.root_hash_task_pool.clone(),
         )?;
         built_block_trace.update_orders_timestamps_after_block_sealed(orders_closed_at);
+        if let Some(pending_accounts) = pending_accounts {
+            self.pending_candidates.offer(
+                ctx,
+                &self.builder_name,
+                built_block_trace.bid_value,
+                pending_accounts,
+            );
+        }
 
-        self.cached_reads = Some(finalized_block.cached_reads);
+        if !self.cached_reads_disabled {
+            self.cached_reads = Some(assembled.cached_reads);
+        }
+        let assembled_block = assembled.assembled_block;
+        if self.config.verify_header_basefee {
+            let parent = self
+                .provider_factory
+                .header(&ctx.attributes.parent)?
+                .ok_or(ProviderError::BlockHashNotFound(ctx.attributes.parent))?;
+            if let Err(err) =
+                verify_header_basefee(&assembled_block.sealed_header, &parent, &ctx.chain_spec)
+            {
+                telemetry::inc_header_basefee_mismatches(&self.builder_name);
+                error!(
+                    block = ctx.block_env.number.to::<u64>(),
+                    builder_name = self.builder_name,
+                    ?err,
+                    "Finalized header basefee mismatch",
+                );
+            }
+        }
 
         let finalize_time = start.elapsed();
 
         built_block_trace.finalize_time = finalize_time;
+        self.record_build_time(build_time + finalize_time);
 
-        let txs = finalized_block.sealed_block.body.len();
-        let gas_used = finalized_block.sealed_block.gas_used;
-        let blobs = finalized_block.txs_blob_sidecars.len();
+        let txs = assembled_block.tx_count();
+        let gas_used = assembled_block.sealed_header.gas_used;
+        let blobs = assembled_block.blob_sidecar_count();
 
         telemetry::add_built_block_metrics(
             build_time,
@@ -431,6 +1195,13 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
             &self.builder_name,
             ctx.timestamp(),
         );
+        telemetry::set_block_bid_value(&self.builder_name, built_block_trace.bid_value);
+        PROFIT_DIVERGENCES.record(ProfitDivergenceReport::new(
+            &self.builder_name,
+            ctx.block(),
+            &self.ranked_profits,
+            &built_block_trace,
+        ));
 
         trace!(
             block = ctx.block_env.number.to::<u64>(),
@@ -442,19 +1213,37 @@ impl<DB: Database + Clone + 'static> OrderingBuilderContext<DB> {
             blobs,
             gas_used,
             sim_gas_used,
-            use_suggested_fee_recipient_as_coinbase,
+            ?coinbase_mode,
             "Built block",
         );
 
-        Ok(Some(Block {
+        Ok(Some(LazyBlock {
             trace: built_block_trace,
-            sealed_block: finalized_block.sealed_block,
-            txs_blobs_sidecars: finalized_block.txs_blob_sidecars,
+            assembled_block,
             builder_name: self.builder_name.clone(),
+            coinbase_payment_tx_hash,
+            root_hash_skipped: matches!(self.root_hash_mode, RootHashMode::SkipRootHash),
         }))
     }
 }
 
+/// true if the tx has no blobs or its signer balance covers value + gas_limit * max_fee_per_gas + blob gas * blob basefee.
+fn can_pay_blob_tx(
+    tx: &TransactionSignedEcRecoveredWithBlobs,
+    ctx: &BlockBuildingContext,
+    state: &mut BlockState,
+) -> Result<bool, ProviderError> {
+    let blob_count = match tx.tx.blob_versioned_hashes() {
+        Some(hashes) if !hashes.is_empty() => hashes.len() as u64,
+        _ => return Ok(true),
+    };
+    let blob_gas_price = ctx.block_env.get_blob_gasprice().unwrap_or_default();
+    let blob_fee = U256::from(blob_count * DATA_GAS_PER_BLOB) * U256::from(blob_gas_price);
+    let gas_fee = U256::from(tx.tx.gas_limit()) * U256::from(tx.tx.max_fee_per_gas());
+    let required = tx.tx.value() + gas_fee + blob_fee;
+    Ok(state.balance(tx.signer())? >= required)
+}
+
 #[derive(Debug)]
 pub struct OrderingBuildingAlgorithm {
     root_hash_task_pool: BlockingTaskPool,
@@ -477,6 +1266,11 @@ impl OrderingBuildingAlgorithm {
             name,
         }
     }
+
+    /// Signers used for a build given the optional per build override.
+    fn sbundle_mergeabe_signers(&self, build_override: Option<Vec<Address>>) -> Vec<Address> {
+        build_override.unwrap_or_else(|| self.sbundle_mergeabe_signers.clone())
+    }
 }
 
 impl<DB: Database + Clone + 'static, SinkType: BlockBuildingSink>
@@ -496,8 +1290,2295 @@ impl<DB: Database + Clone + 'static, SinkType: BlockBuildingSink>
             builder_name: self.name.clone(),
             slot_bidder: input.slot_bidder,
             cancel: input.cancel,
-            sbundle_mergeabe_signers: self.sbundle_mergeabe_signers.clone(),
+            sbundle_mergeabe_signers: self.sbundle_mergeabe_signers(input.sbundle_mergeabe_signers),
+            pending_candidates: input.pending_candidates,
         };
         run_ordering_builder(live_input, &self.config);
     }
 }
+
+/// Several [`OrderingBuilderConfig`]s (candidates) sharing the build iterations of a single builder, candidates that
+/// recently built more valuable blocks get more iterations (see [`BuildBudgetAllocator`]).
+/// The candidates share the orders so they must all use the same sorting, parent_not_found is taken from the first one.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct WeightedOrderingBuilderConfig {
+    pub candidates: Vec<OrderingBuilderConfig>,
+    /// Minimum % of the iterations every candidate gets so a candidate that did badly can recover.
+    #[serde(default = "default_min_iteration_share_percent")]
+    pub min_iteration_share_percent: u64,
+}
+
+fn default_min_iteration_share_percent() -> u64 {
+    10
+}
+
+impl WeightedOrderingBuilderConfig {
+    pub fn new_allocator(&self) -> BuildBudgetAllocator {
+        BuildBudgetAllocator::new(
+            self.candidates.len(),
+            self.min_iteration_share_percent as f64 / 100.0,
+        )
+    }
+
+    /// builder_name of the blocks built by a candidate.
+    pub fn candidate_name(builder_name: &str, candidate: usize) -> String {
+        format!("{}-{}", builder_name, candidate)
+    }
+}
+
+pub fn run_weighted_ordering_builder<
+    DB: Database + Clone + 'static,
+    SinkType: BlockBuildingSink,
+>(
+    input: LiveBuilderInput<DB, SinkType>,
+    config: &WeightedOrderingBuilderConfig,
+    allocator: &Mutex<BuildBudgetAllocator>,
+) {
+    let candidates = config
+        .candidates
+        .iter()
+        .enumerate()
+        .map(|(i, candidate)| {
+            (
+                WeightedOrderingBuilderConfig::candidate_name(&input.builder_name, i),
+                candidate.clone(),
+            )
+        })
+        .collect();
+    run_candidates_loop(input, candidates, allocator);
+}
+
+/// There is no history to split the iterations in a backtest so every candidate builds once and the best block is kept.
+pub fn backtest_simulate_weighted_block<DB: Database + Clone + 'static>(
+    config: WeightedOrderingBuilderConfig,
+    input: BacktestSimulateBlockInput<'_, DB>,
+) -> eyre::Result<(Block, CachedReads)> {
+    let mut cached_reads = input.cached_reads;
+    let mut best_block: Option<Block> = None;
+    for (i, candidate) in config.candidates.into_iter().enumerate() {
+        let (block, candidate_cached_reads) = backtest_simulate_block(
+            candidate,
+            BacktestSimulateBlockInput {
+                ctx: input.ctx.clone(),
+                builder_name: WeightedOrderingBuilderConfig::candidate_name(&input.builder_name, i),
+                sbundle_mergeabe_signers: input.sbundle_mergeabe_signers.clone(),
+                sim_orders: input.sim_orders,
+                provider_factory: input.provider_factory.clone(),
+                cached_reads,
+            },
+        )?;
+        cached_reads = Some(candidate_cached_reads);
+        if best_block
+            .as_ref()
+            .map_or(true, |best| block.trace.bid_value > best.trace.bid_value)
+        {
+            best_block = Some(block);
+        }
+    }
+    let best_block = best_block.ok_or_else(|| eyre::eyre!("No candidates"))?;
+    Ok((best_block, cached_reads.unwrap_or_default()))
+}
+
+#[derive(Debug)]
+pub struct WeightedOrderingBuildingAlgorithm {
+    root_hash_task_pool: BlockingTaskPool,
+    sbundle_mergeabe_signers: Vec<Address>,
+    config: WeightedOrderingBuilderConfig,
+    name: String,
+    /// Shared by all the slots so the profit history spans them.
+    allocator: Mutex<BuildBudgetAllocator>,
+}
+
+impl WeightedOrderingBuildingAlgorithm {
+    pub fn new(
+        root_hash_task_pool: BlockingTaskPool,
+        sbundle_mergeabe_signers: Vec<Address>,
+        config: WeightedOrderingBuilderConfig,
+        name: String,
+    ) -> Self {
+        Self {
+            root_hash_task_pool,
+            sbundle_mergeabe_signers,
+            allocator: Mutex::new(config.new_allocator()),
+            config,
+            name,
+        }
+    }
+
+    /// Iterations each candidate got so far.
+    pub fn candidate_iterations(&self) -> Vec<u64> {
+        let allocator = self.allocator.lock().unwrap();
+        (0..allocator.candidate_count())
+            .map(|candidate| allocator.iterations(candidate))
+            .collect()
+    }
+}
+
+impl<DB: Database + Clone + 'static, SinkType: BlockBuildingSink>
+    BlockBuildingAlgorithm<DB, SinkType> for WeightedOrderingBuildingAlgorithm
+{
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn build_blocks(&self, input: BlockBuildingAlgorithmInput<DB, SinkType>) {
+        let live_input = LiveBuilderInput {
+            provider_factory: input.provider_factory,
+            root_hash_task_pool: self.root_hash_task_pool.clone(),
+            ctx: input.ctx.clone(),
+            input: input.input,
+            sink: input.sink,
+            builder_name: self.name.clone(),
+            slot_bidder: input.slot_bidder,
+            cancel: input.cancel,
+            sbundle_mergeabe_signers: input
+                .sbundle_mergeabe_signers
+                .unwrap_or_else(|| self.sbundle_mergeabe_signers.clone()),
+            pending_candidates: input.pending_candidates,
+        };
+        run_weighted_ordering_builder(live_input, &self.config, &self.allocator);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::building::{
+        sim::simulate_all_orders_with_sim_tree,
+        testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
+        token_valuation::{TokenPriceConfig, TokenPriceSource, TokenValuation, MAINNET_WETH},
+        BuilderIdentity, InsertPayoutTxErr, RefundLedgerError,
+    };
+    use crate::live_builder::{bidding::SealInstruction, simulation::SimulatedOrderCommand};
+    use crate::primitives::{order_builder::OrderBuilder, Refund, RefundConfig, TxRevertBehavior};
+    use crate::test_utils::{AlwaysDeclineBidder, FailingSink, RecordingSink, StaticBidder};
+    use crate::utils::Signer;
+    use alloy_rlp::Decodable;
+    use reth::primitives::{
+        proofs, BlobTransaction, BlobTransactionSidecar, PooledTransactionsElement, Transaction,
+        TransactionSignedEcRecovered, TxEip4844,
+    };
+    use reth_db::{test_utils::TempDatabase, DatabaseEnv};
+
+    const FEE_RECIPIENT: CoinbaseMode = CoinbaseMode::SuggestedFeeRecipient;
+    const BUILDER_SIGNER: CoinbaseMode = CoinbaseMode::BuilderSigner {
+        payout: PayoutMode::EstimatedGasLimit,
+    };
+
+    fn test_config() -> OrderingBuilderConfig {
+        OrderingBuilderConfig {
+            discard_txs: true,
+            sorting: Sorting::MaxProfit,
+            failed_order_retries: 1,
+            drop_failed_orders: true,
+            ..Default::default()
+        }
+    }
+
+    fn tx_order(test_chain: &TestChainState, args: TxArgs) -> eyre::Result<Order> {
+        let tx = test_chain.sign_tx(args)?;
+        Ok(Order::Tx(MempoolTx::new(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+        )))
+    }
+
+    type TestDB = Arc<TempDatabase<DatabaseEnv>>;
+
+    /// Builder on ctx (a slot of test_chain) with the root hash skipped.
+    /// builder_name labels the metrics of the builds.
+    fn test_builder_on(
+        test_chain: &TestChainState,
+        ctx: BlockBuildingContext,
+        slot_bidder: Arc<dyn SlotBidder>,
+        builder_name: &str,
+        config: OrderingBuilderConfig,
+    ) -> eyre::Result<OrderingBuilderContext<TestDB>> {
+        Ok(OrderingBuilderContext::new(
+            test_chain.provider_factory().clone(),
+            slot_bidder,
+            BlockingTaskPool::build()?,
+            builder_name.to_string(),
+            ctx,
+            config,
+        )
+        .with_skip_root_hash())
+    }
+
+    /// test_builder_on the slot of test_chain.
+    fn test_builder(
+        test_chain: &TestChainState,
+        builder_name: &str,
+        config: OrderingBuilderConfig,
+    ) -> eyre::Result<OrderingBuilderContext<TestDB>> {
+        test_builder_on(
+            test_chain,
+            test_chain.block_building_context().clone(),
+            Arc::new(StaticBidder::full_value()),
+            builder_name,
+            config,
+        )
+    }
+
+    /// Sorted like test_config.
+    fn to_block_orders(sim_orders: Vec<SimulatedOrder>) -> BlockOrders {
+        let mut block_orders = BlockOrders::new(test_config().sorting, vec![], &[]);
+        for order in sim_orders {
+            block_orders.add_order(order);
+        }
+        block_orders
+    }
+
+    /// Simulates orders on the slot of test_chain, the failed ones are dropped.
+    fn sim_block_orders(
+        test_chain: &TestChainState,
+        orders: &[Order],
+    ) -> eyre::Result<BlockOrders> {
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            orders,
+            false,
+        )?;
+        Ok(to_block_orders(sim_orders))
+    }
+
+    /// Simulates orders on the slot of test_chain and builds a block with them (root hash skipped).
+    fn build_block_with(
+        test_chain: &TestChainState,
+        orders: &[Order],
+        config: OrderingBuilderConfig,
+        builder_name: &str,
+        coinbase_mode: CoinbaseMode,
+    ) -> eyre::Result<Option<Block>> {
+        let block_orders = sim_block_orders(test_chain, orders)?;
+        test_builder(test_chain, builder_name, config)?.build_block(block_orders, coinbase_mode)
+    }
+
+    /// User(0) paying 0.001 ETH to the coinbase.
+    fn coinbase_payment_order(test_chain: &TestChainState) -> eyre::Result<Order> {
+        tx_order(
+            test_chain,
+            TxArgs::new_send_to_coinbase(NamedAddr::User(0), 0, 1_000_000_000_000_000)
+                .max_fee_per_gas(10 * GWEI),
+        )
+    }
+
+    #[test]
+    fn test_build_deadline_in_the_past_skips_build() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let mut builder =
+            test_builder(&test_chain, "test", test_config())?.with_build_deadline(Instant::now());
+
+        let block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        assert!(builder.build_block(block_orders, BUILDER_SIGNER)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_header_basefee() -> eyre::Result<()> {
+        let mut test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        // the genesis parent has no basefee, build on a parent header that has one
+        test_chain.advance_block()?;
+        let builder_name = "test_verify_header_basefee";
+        let mismatches = || {
+            telemetry::HEADER_BASEFEE_MISMATCHES
+                .with_label_values(&[builder_name])
+                .get()
+        };
+        let build = |ctx: BlockBuildingContext| -> eyre::Result<()> {
+            let mut builder = test_builder_on(
+                &test_chain,
+                ctx,
+                Arc::new(StaticBidder::full_value()),
+                builder_name,
+                OrderingBuilderConfig {
+                    verify_header_basefee: true,
+                    ..test_config()
+                },
+            )?;
+            let block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+            builder
+                .build_block(block_orders, FEE_RECIPIENT)?
+                .expect("block should be built");
+            Ok(())
+        };
+
+        build(test_chain.block_building_context().clone())?;
+        assert_eq!(mismatches(), 0);
+
+        // executing with a basefee that does not follow from the parent gives an invalid header
+        let mut ctx = test_chain.block_building_context().clone();
+        ctx.block_env.basefee += U256::from(1);
+        build(ctx)?;
+        assert_eq!(mismatches(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_timestamp_offset() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        let parent_timestamp = test_chain
+            .provider_factory()
+            .header(&test_chain.block_building_context().attributes.parent)?
+            .unwrap()
+            .timestamp;
+        let mut builder = test_builder(
+            &test_chain,
+            "test",
+            OrderingBuilderConfig {
+                timestamp_offset_s: Some(7),
+                ..test_config()
+            },
+        )?;
+
+        let block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        let block = builder
+            .build_block(block_orders, FEE_RECIPIENT)?
+            .expect("block should be built");
+        assert_eq!(block.sealed_block.timestamp, parent_timestamp + 7);
+        Ok(())
+    }
+
+    #[test]
+    fn test_default_config_matches_deserialized() -> eyre::Result<()> {
+        let deserialized: OrderingBuilderConfig = serde_json::from_value(serde_json::json!({
+            "discard_txs": false,
+            "sorting": "mev-gas-price",
+            "failed_order_retries": 0,
+            "drop_failed_orders": false,
+        }))?;
+        assert_eq!(deserialized, OrderingBuilderConfig::default());
+        assert!(OrderingBuilderConfig::default().use_suggested_fee_recipient_as_coinbase);
+        Ok(())
+    }
+
+    #[test]
+    fn test_zero_timestamp_offset_is_rejected() {
+        let config = OrderingBuilderConfig {
+            timestamp_offset_s: Some(0),
+            ..test_config()
+        };
+        assert!(config.block_timestamp(100).is_err());
+        assert_eq!(test_config().block_timestamp(100).unwrap(), None);
+    }
+
+    #[test]
+    fn test_sbundle_mergeabe_signers_override() {
+        let configured = vec![Address::repeat_byte(1)];
+        let algo = OrderingBuildingAlgorithm::new(
+            BlockingTaskPool::build().unwrap(),
+            configured.clone(),
+            test_config(),
+            "test".to_string(),
+        );
+        assert_eq!(algo.sbundle_mergeabe_signers(None), configured);
+
+        let build_override = vec![Address::repeat_byte(2), Address::repeat_byte(3)];
+        assert_eq!(
+            algo.sbundle_mergeabe_signers(Some(build_override.clone())),
+            build_override
+        );
+        // override disables merging completely
+        assert!(algo.sbundle_mergeabe_signers(Some(Vec::new())).is_empty());
+    }
+
+    #[test]
+    fn test_build_attempts_metric() -> eyre::Result<()> {
+        let builder_name = "test_build_attempts_metric";
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let run = || -> eyre::Result<()> {
+            let (_sender, receiver) = tokio::sync::broadcast::channel(10);
+            let cancel = tokio_util::sync::CancellationToken::new();
+            // cancelled before starting, the builder must still count the attempt
+            cancel.cancel();
+            let input = LiveBuilderInput {
+                provider_factory: test_chain.provider_factory().clone(),
+                root_hash_task_pool: BlockingTaskPool::build()?,
+                ctx: test_chain.block_building_context().clone(),
+                input: receiver,
+                sink: crate::building::builders::BestBlockCell::default(),
+                builder_name: builder_name.to_string(),
+                slot_bidder: Arc::new(StaticBidder::full_value()),
+                cancel,
+                sbundle_mergeabe_signers: vec![],
+                pending_candidates: Default::default(),
+            };
+            run_ordering_builder(input, &test_config());
+            Ok(())
+        };
+        let attempts = || {
+            telemetry::BUILD_ATTEMPTS
+                .with_label_values(&[builder_name])
+                .get()
+        };
+
+        let before = attempts();
+        run()?;
+        assert_eq!(attempts(), before + 1);
+        run()?;
+        assert_eq!(attempts(), before + 2);
+        Ok(())
+    }
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn build_with_anchor_tx(
+        anchor_nonce: u64,
+        allow_anchor_tx_failure: bool,
+    ) -> eyre::Result<(Option<Block>, TransactionSignedEcRecovered)> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        let anchor_tx = test_chain.sign_tx(
+            TxArgs::new(NamedAddr::User(0), anchor_nonce)
+                .to(NamedAddr::User(1))
+                .max_fee_per_gas(10 * GWEI),
+        )?;
+        let block = build_with_raw_anchor_tx(
+            &test_chain,
+            anchor_tx.envelope_encoded(),
+            OrderingBuilderConfig {
+                allow_anchor_tx_failure,
+                ..test_config()
+            },
+        )?;
+        Ok((block, anchor_tx))
+    }
+
+    /// Builds a block with a single mempool tx from User(2) and anchor_tx set on config
+    fn build_with_raw_anchor_tx(
+        test_chain: &TestChainState,
+        anchor_tx: Bytes,
+        config: OrderingBuilderConfig,
+    ) -> eyre::Result<Option<Block>> {
+        // pays more than the anchor so it would go first without the anchor
+        let mempool_tx = tx_order(
+            test_chain,
+            TxArgs::new(NamedAddr::User(2), 0)
+                .to(NamedAddr::User(1))
+                .max_fee_per_gas(10 * GWEI)
+                .max_priority_fee(5 * GWEI),
+        )?;
+        build_block_with(
+            test_chain,
+            &[mempool_tx],
+            OrderingBuilderConfig {
+                anchor_tx: Some(anchor_tx),
+                ..config
+            },
+            "test",
+            FEE_RECIPIENT,
+        )
+    }
+
+    #[test]
+    fn test_anchor_tx_is_first() -> eyre::Result<()> {
+        let (block, anchor_tx) = build_with_anchor_tx(0, false)?;
+        let block = block.expect("block should be built");
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert_eq!(block.sealed_block.body[0].hash(), anchor_tx.hash());
+        Ok(())
+    }
+
+    /// User(1) bundle paying a low priority fee, it sorts below coinbase_payment_order.
+    fn position_constrained_bundle(
+        test_chain: &TestChainState,
+        max_block_position: Option<usize>,
+    ) -> eyre::Result<(Order, B256)> {
+        let tx = test_chain.sign_tx(
+            TxArgs::new(NamedAddr::User(1), 0)
+                .to(NamedAddr::Dummy)
+                .max_fee_per_gas(10 * GWEI)
+                .max_priority_fee(GWEI),
+        )?;
+        let tx_hash = tx.hash();
+        let mut order_builder = OrderBuilder::None;
+        order_builder.start_bundle_builder(1);
+        order_builder.add_tx(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            TxRevertBehavior::NotAllowed,
+        );
+        order_builder.set_bundle_max_block_position(max_block_position);
+        Ok((order_builder.build_order(), tx_hash))
+    }
+
+    #[test]
+    fn test_top_of_block_bundle_goes_first() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let build = |max_block_position: Option<usize>| -> eyre::Result<(Block, B256)> {
+            let (bundle, bundle_tx_hash) =
+                position_constrained_bundle(&test_chain, max_block_position)?;
+            let block = build_block_with(
+                &test_chain,
+                &[coinbase_payment_order(&test_chain)?, bundle],
+                test_config(),
+                "test_top_of_block_bundle_goes_first",
+                FEE_RECIPIENT,
+            )?
+            .expect("block should be built");
+            Ok((block, bundle_tx_hash))
+        };
+
+        // without the constraint the bundle sorts after the coinbase payment
+        let (block, bundle_tx_hash) = build(None)?;
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert_eq!(block.sealed_block.body[1].hash(), bundle_tx_hash);
+
+        // the top of block bundle is considered first even if it sorts lower
+        let (block, bundle_tx_hash) = build(Some(0))?;
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert_eq!(block.sealed_block.body[0].hash(), bundle_tx_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn test_anchor_tx_takes_a_block_position() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let anchor_tx = test_chain.sign_tx(
+            TxArgs::new(NamedAddr::User(2), 0)
+                .to(NamedAddr::User(3))
+                .max_fee_per_gas(10 * GWEI),
+        )?;
+        let build = |max_block_position: Option<usize>| -> eyre::Result<(Block, B256)> {
+            let (bundle, bundle_tx_hash) =
+                position_constrained_bundle(&test_chain, max_block_position)?;
+            let block = build_block_with(
+                &test_chain,
+                &[bundle],
+                OrderingBuilderConfig {
+                    anchor_tx: Some(anchor_tx.envelope_encoded()),
+                    ..test_config()
+                },
+                "test_anchor_tx_takes_a_block_position",
+                FEE_RECIPIENT,
+            )?
+            .expect("block should be built");
+            Ok((block, bundle_tx_hash))
+        };
+
+        // anchor tx is at position 0 so a top of block bundle is excluded
+        let (block, _) = build(Some(0))?;
+        assert_eq!(block.sealed_block.body.len(), 1);
+        assert_eq!(block.sealed_block.body[0].hash(), anchor_tx.hash());
+
+        // position 1 is right after the anchor tx
+        let (block, bundle_tx_hash) = build(Some(1))?;
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert_eq!(block.sealed_block.body[1].hash(), bundle_tx_hash);
+        Ok(())
+    }
+
+    #[test]
+    fn test_failed_anchor_tx() -> eyre::Result<()> {
+        // bad nonce
+        assert!(build_with_anchor_tx(5, false).is_err());
+
+        let (block, anchor_tx) = build_with_anchor_tx(5, true)?;
+        let block = block.expect("block should be built");
+        assert_eq!(block.sealed_block.body.len(), 1);
+        assert_ne!(block.sealed_block.body[0].hash(), anchor_tx.hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_underfunded_blob_anchor_tx_is_skipped() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        // fresh account, no balance at all
+        let signer = Signer::random();
+        let blob_tx = signer.sign_tx(Transaction::Eip4844(TxEip4844 {
+            chain_id: test_chain.block_building_context().chain_spec.chain.id(),
+            gas_limit: 21_000,
+            max_fee_per_gas: 10 * GWEI,
+            max_fee_per_blob_gas: GWEI,
+            blob_versioned_hashes: vec![B256::repeat_byte(0x01)],
+            ..Default::default()
+        }))?;
+        let blob_tx = BlobTransaction::try_from_signed(
+            blob_tx.into_signed(),
+            BlobTransactionSidecar::default(),
+        )
+        .map_err(|_| eyre::eyre!("not a blob tx"))?;
+        let raw_blob_tx = PooledTransactionsElement::BlobTransaction(blob_tx).envelope_encoded();
+        let anchor = TransactionSignedEcRecoveredWithBlobs::decode_enveloped_with_real_blobs(
+            raw_blob_tx.clone(),
+        )?;
+        {
+            let state_provider = test_chain.provider_factory().latest()?;
+            let mut state = BlockState::new(&state_provider);
+            assert!(!can_pay_blob_tx(
+                &anchor,
+                test_chain.block_building_context(),
+                &mut state
+            )?);
+        }
+
+        // without the check the anchor tx fails and we don't build
+        assert!(build_with_raw_anchor_tx(&test_chain, raw_blob_tx.clone(), test_config()).is_err());
+
+        let block = build_with_raw_anchor_tx(
+            &test_chain,
+            raw_blob_tx,
+            OrderingBuilderConfig {
+                skip_underfunded_blob_anchor_tx: true,
+                ..test_config()
+            },
+        )?
+        .expect("block should be built");
+        // regular fill still happens
+        assert_eq!(block.sealed_block.body.len(), 1);
+        assert_ne!(block.sealed_block.body[0].hash(), anchor.hash());
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_success_ratio_metric() -> eyre::Result<()> {
+        let builder_name = "test_commit_success_ratio_metric";
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        // both bundles increment the same slot expecting its current value so they simulate fine alone
+        // but the second one reverts after the first one lands
+        let orders = [0, 1]
+            .into_iter()
+            .map(|user| {
+                let tx = test_chain.sign_tx(
+                    TxArgs::new_increment_value(NamedAddr::User(user), 0, 0, 0)
+                        .max_fee_per_gas(10 * GWEI)
+                        .max_priority_fee((user as u128 + 1) * GWEI),
+                )?;
+                let mut order_builder = OrderBuilder::None;
+                order_builder.start_bundle_builder(1);
+                order_builder.add_tx(
+                    TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+                    TxRevertBehavior::NotAllowed,
+                );
+                Ok(order_builder.build_order())
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            &orders,
+            false,
+        )?;
+        assert_eq!(sim_orders.len(), 2);
+
+        let block = test_builder(&test_chain, builder_name, test_config())?
+            .build_block(to_block_orders(sim_orders), FEE_RECIPIENT)?
+            .expect("block should be built");
+        assert_eq!(block.trace.included_orders.len(), 1);
+        assert_eq!(
+            telemetry::COMMIT_SUCCESS_RATIO
+                .with_label_values(&[builder_name])
+                .get(),
+            0.5
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_orders_per_iteration() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        let orders = (0..3)
+            .map(|user| {
+                let tx = test_chain.sign_tx(
+                    TxArgs::new(NamedAddr::User(user), 0)
+                        .to(NamedAddr::User(4))
+                        .max_fee_per_gas(10 * GWEI)
+                        .max_priority_fee((user as u128 + 1) * GWEI),
+                )?;
+                let mut order_builder = OrderBuilder::None;
+                order_builder.start_mempool_tx_builder();
+                order_builder.add_tx(
+                    TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+                    TxRevertBehavior::AllowedIncluded,
+                );
+                Ok(order_builder.build_order())
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let block_orders = sim_block_orders(&test_chain, &orders)?;
+
+        let mut builder = test_builder(
+            &test_chain,
+            "test_max_orders_per_iteration",
+            OrderingBuilderConfig {
+                max_orders_per_iteration: Some(2),
+                ..test_config()
+            },
+        )?;
+        let block = builder
+            .build_block(block_orders.clone(), FEE_RECIPIENT)?
+            .expect("block should be built");
+        assert_eq!(block.trace.included_orders.len(), 2);
+        assert_eq!(
+            builder.consideration_stats(),
+            ConsiderationStats {
+                considered: 2,
+                available: 3
+            }
+        );
+
+        // next iteration keeps the best order and gets the one left out
+        let block = builder
+            .build_block(block_orders, FEE_RECIPIENT)?
+            .expect("block should be built");
+        let included: Vec<_> = block
+            .trace
+            .included_orders
+            .iter()
+            .map(|included| included.order.id())
+            .collect();
+        assert_eq!(included, vec![orders[2].id(), orders[0].id()]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_configured_withdrawals() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let withdrawals = vec![Withdrawal {
+            index: 7,
+            validator_index: 11,
+            address: test_chain.named_address(NamedAddr::User(3))?,
+            amount: 32_000_000_000,
+        }];
+        let build = |withdrawals: Option<Vec<Withdrawal>>| -> eyre::Result<Block> {
+            let mut builder = OrderingBuilderContext::new(
+                test_chain.provider_factory().clone(),
+                Arc::new(StaticBidder::full_value()),
+                BlockingTaskPool::build()?,
+                "test_configured_withdrawals".to_string(),
+                test_chain.block_building_context().clone(),
+                OrderingBuilderConfig {
+                    withdrawals,
+                    ..test_config()
+                },
+            );
+            Ok(builder
+                .build_block(
+                    BlockOrders::new(Sorting::MaxProfit, vec![], &[]),
+                    BUILDER_SIGNER,
+                )?
+                .expect("block should be built"))
+        };
+
+        let block = build(Some(withdrawals.clone()))?;
+        assert_eq!(
+            block.sealed_block.withdrawals.as_deref(),
+            Some(&withdrawals)
+        );
+        assert_eq!(
+            block.sealed_block.withdrawals_root,
+            Some(proofs::calculate_withdrawals_root(&withdrawals))
+        );
+        // the withdrawal is credited
+        let block_without = build(None)?;
+        assert_ne!(
+            block.sealed_block.state_root,
+            block_without.sealed_block.state_root
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_attempts() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        // User(1) tx reverts, it's fine for the simulation but exclude_reverting_txs makes it fail on the build
+        let orders = [
+            TxArgs::new(NamedAddr::User(0), 0)
+                .to(NamedAddr::User(4))
+                .max_priority_fee(2 * GWEI),
+            TxArgs::new_revert(NamedAddr::User(1), 0).max_priority_fee(GWEI),
+            TxArgs::new(NamedAddr::User(2), 0)
+                .to(NamedAddr::User(4))
+                .max_priority_fee(3 * GWEI),
+        ]
+        .into_iter()
+        .map(|tx_args| {
+            let tx = test_chain.sign_tx(tx_args.max_fee_per_gas(10 * GWEI))?;
+            let mut order_builder = OrderBuilder::None;
+            order_builder.start_mempool_tx_builder();
+            order_builder.add_tx(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+                TxRevertBehavior::AllowedIncluded,
+            );
+            Ok(order_builder.build_order())
+        })
+        .collect::<eyre::Result<Vec<_>>>()?;
+        let block_orders = sim_block_orders(&test_chain, &orders)?;
+
+        let block = test_builder(
+            &test_chain,
+            "test_commit_attempts",
+            OrderingBuilderConfig {
+                exclude_reverting_txs: true,
+                ..test_config()
+            },
+        )?
+        .build_block(block_orders.clone(), FEE_RECIPIENT)?
+        .expect("block should be built");
+        assert_eq!(block.trace.included_orders.len(), 2);
+        assert_eq!(block.trace.commit_attempts, 3);
+
+        // the fill stops after the first order
+        let block = test_builder(
+            &test_chain,
+            "test_commit_attempts",
+            OrderingBuilderConfig {
+                exclude_reverting_txs: true,
+                max_orders_per_iteration: Some(1),
+                ..test_config()
+            },
+        )?
+        .build_block(block_orders, FEE_RECIPIENT)?
+        .expect("block should be built");
+        assert_eq!(block.trace.included_orders.len(), 1);
+        assert_eq!(block.trace.commit_attempts, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_wrong_chain_id_orders_fail() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .use_suggested_fee_recipient_as_coinbase(true),
+        )?;
+        let chain_id = test_chain.block_building_context().chain_spec.chain.id();
+        let mut block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        let mut wrong_chain_id_orders = HashSet::default();
+        // half of the txs are signed for another chain
+        for user in 0..4 {
+            let mut args = TxArgs::new(NamedAddr::User(user), 0)
+                .to(NamedAddr::User(4))
+                .max_fee_per_gas(10 * GWEI)
+                .max_priority_fee(GWEI);
+            if user % 2 == 1 {
+                args = args.chain_id(chain_id + 1);
+            }
+            let tx = test_chain.sign_tx(args)?;
+            let order = Order::Tx(MempoolTx::new(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            ));
+            if user % 2 == 1 {
+                wrong_chain_id_orders.insert(order.id());
+            }
+            // simulation would filter them, we want to see the fill loop rejecting them
+            block_orders.add_order(SimulatedOrder {
+                order,
+                sim_value: Default::default(),
+                prev_order: None,
+                used_state_trace: None,
+            });
+        }
+
+        let mut builder = test_builder(
+            &test_chain,
+            "test_wrong_chain_id_orders_fail",
+            test_config(),
+        )?;
+        let block = builder
+            .build_block(block_orders, FEE_RECIPIENT)?
+            .expect("block should be built");
+        assert_eq!(block.trace.included_orders.len(), 2);
+        assert!(block
+            .trace
+            .included_orders
+            .iter()
+            .all(|res| !wrong_chain_id_orders.contains(&res.order.id())));
+        assert_eq!(builder.failed_orders, wrong_chain_id_orders);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_time_percentiles() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let mut builder = test_builder(&test_chain, "test_build_time_percentiles", test_config())?;
+        assert_eq!(builder.build_time_percentiles(), None);
+        // old samples must be forgotten
+        for _ in 0..BUILD_TIME_SAMPLES {
+            builder.record_build_time(Duration::from_secs(10));
+        }
+        for ms in 1..=BUILD_TIME_SAMPLES as u64 {
+            builder.record_build_time(Duration::from_millis(ms));
+        }
+        assert_eq!(
+            builder.build_time_percentiles(),
+            Some(BuildTimePercentiles {
+                p50: Duration::from_millis(50),
+                p95: Duration::from_millis(95),
+                p99: Duration::from_millis(99),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_finalize_on_no_commits() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let build = |orders: &[Order], coinbase_mode: CoinbaseMode| {
+            build_block_with(
+                &test_chain,
+                orders,
+                OrderingBuilderConfig {
+                    skip_finalize_on_no_commits: true,
+                    ..test_config()
+                },
+                "test_skip_finalize_on_no_commits",
+                coinbase_mode,
+            )
+        };
+        assert!(build(&[], BUILDER_SIGNER)?.is_none());
+        // same with direct payment to the fee recipient, the block would be empty
+        assert!(build(&[], FEE_RECIPIENT)?.is_none());
+
+        let order = coinbase_payment_order(&test_chain)?;
+        assert!(build(&[order.clone()], BUILDER_SIGNER)?.is_some());
+        assert!(build(&[order], FEE_RECIPIENT)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_skipped_block_keeps_cached_reads() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let mut builder = test_builder(
+            &test_chain,
+            "test_skipped_block_keeps_cached_reads",
+            OrderingBuilderConfig {
+                skip_finalize_on_no_commits: true,
+                ..test_config()
+            },
+        )?;
+        assert!(builder
+            .build_block(sim_block_orders(&test_chain, &[])?, BUILDER_SIGNER)?
+            .is_none());
+        // the pre block call and payout estimation reads survive the skipped block
+        assert!(builder.take_cached_reads().is_some());
+        Ok(())
+    }
+
+    /// Cancels the slot after taking max_blocks blocks.
+    #[derive(Debug, Clone)]
+    struct CancelAfterSink {
+        blocks: RecordingSink,
+        max_blocks: usize,
+        cancel: CancellationToken,
+    }
+
+    impl BlockBuildingSink for CancelAfterSink {
+        fn new_block(&self, block: Block) {
+            self.blocks.new_block(block);
+            if self.blocks.len() >= self.max_blocks {
+                self.cancel.cancel();
+            }
+        }
+    }
+
+    #[test]
+    fn test_weighted_builder_shares_iterations() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        // pays a fresh account, the second candidate rejects it so it never builds a block
+        let tx = test_chain.sign_tx(
+            TxArgs::new(NamedAddr::User(0), 0)
+                .to(NamedAddr::Dummy)
+                .max_fee_per_gas(10 * GWEI)
+                .max_priority_fee(GWEI),
+        )?;
+        let order = Order::Tx(MempoolTx::new(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+        ));
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            &[order],
+            false,
+        )?;
+        let candidate = OrderingBuilderConfig {
+            coinbase_payment: true,
+            skip_finalize_on_no_commits: true,
+            ..test_config()
+        };
+        let algorithm = WeightedOrderingBuildingAlgorithm::new(
+            BlockingTaskPool::build()?,
+            vec![],
+            WeightedOrderingBuilderConfig {
+                candidates: vec![
+                    candidate.clone(),
+                    OrderingBuilderConfig {
+                        reject_new_account_recipients: true,
+                        ..candidate
+                    },
+                ],
+                min_iteration_share_percent: 10,
+            },
+            "weighted".to_string(),
+        );
+
+        // kept alive, a closed channel ends the slot
+        let (sender, receiver) = tokio::sync::broadcast::channel(10);
+        for sim_order in sim_orders {
+            sender.send(SimulatedOrderCommand::Simulation(sim_order))?;
+        }
+        let cancel = CancellationToken::new();
+        let max_blocks = 40;
+        let sink = CancelAfterSink {
+            blocks: RecordingSink::default(),
+            max_blocks,
+            cancel: cancel.clone(),
+        };
+        algorithm.build_blocks(BlockBuildingAlgorithmInput {
+            provider_factory: test_chain.provider_factory().clone(),
+            ctx: test_chain.block_building_context().clone(),
+            input: receiver,
+            sink: sink.clone(),
+            slot_bidder: Arc::new(StaticBidder::full_value()),
+            cancel,
+            sbundle_mergeabe_signers: None,
+            pending_candidates: Default::default(),
+        });
+
+        let blocks = sink.blocks.blocks();
+        assert_eq!(blocks.len(), max_blocks);
+        assert!(blocks
+            .iter()
+            .all(|recorded| recorded.block.builder_name == "weighted-0"));
+        let iterations = algorithm.candidate_iterations();
+        assert_eq!(iterations[0], max_blocks as u64);
+        // ~10% (min share) of the iterations, none of them built a block
+        assert!((2..=8).contains(&iterations[1]), "{:?}", iterations);
+        Ok(())
+    }
+
+    /// Answers on_fill_pass with the scripted actions (ContinueFilling after the script ends) and records the calls.
+    #[derive(Debug)]
+    struct ScriptedBidder {
+        actions: Vec<FillPassAction>,
+        calls: std::sync::Mutex<Vec<FillPassInfo>>,
+    }
+
+    impl SlotBidder for ScriptedBidder {
+        fn is_pay_to_coinbase_allowed(&self) -> bool {
+            true
+        }
+
+        fn seal_instruction(
+            &self,
+            unsealed_block_profit: U256,
+            _slot_timestamp: OffsetDateTime,
+        ) -> SealInstruction {
+            SealInstruction::Value(unsealed_block_profit)
+        }
+
+        fn best_bid_value(&self) -> Option<U256> {
+            None
+        }
+
+        fn on_fill_pass(&self, info: &FillPassInfo) -> FillPassAction {
+            let action = self
+                .actions
+                .get(info.iteration)
+                .copied()
+                .unwrap_or_default();
+            self.calls.lock().unwrap().push(info.clone());
+            action
+        }
+    }
+
+    #[test]
+    fn test_slot_bidder_fill_pass_actions() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+
+        let bidder = Arc::new(ScriptedBidder {
+            actions: vec![
+                FillPassAction::ContinueFilling,
+                FillPassAction::SkipSubmission,
+                FillPassAction::FinalizeNow,
+            ],
+            calls: Default::default(),
+        });
+        let mut builder = test_builder_on(
+            &test_chain,
+            test_chain.block_building_context().clone(),
+            bidder.clone(),
+            "test_slot_bidder_fill_pass_actions",
+            test_config(),
+        )?;
+
+        assert!(builder
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .is_some());
+        assert!(!builder.finalize_requested());
+        assert!(builder
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .is_none());
+        assert!(!builder.finalize_requested());
+        assert!(builder.build_block(block_orders, BUILDER_SIGNER)?.is_some());
+        assert!(builder.finalize_requested());
+
+        let calls = bidder.calls.lock().unwrap();
+        assert_eq!(
+            calls.iter().map(|c| c.iteration).collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+        let gas_limit = test_chain
+            .block_building_context()
+            .block_env
+            .gas_limit
+            .to::<u64>();
+        for call in calls.iter() {
+            assert!(call.remaining_gas > 0 && call.remaining_gas < gas_limit);
+            assert!(call.true_block_value > U256::ZERO);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_finalize_now_stops_loop_without_block() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let (_sender, receiver) = tokio::sync::broadcast::channel(10);
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let bidder = Arc::new(ScriptedBidder {
+            actions: vec![FillPassAction::FinalizeNow],
+            calls: Default::default(),
+        });
+        let sink = RecordingSink::default();
+        let input = LiveBuilderInput {
+            provider_factory: test_chain.provider_factory().clone(),
+            root_hash_task_pool: BlockingTaskPool::build()?,
+            ctx: test_chain.block_building_context().clone(),
+            input: receiver,
+            sink: sink.clone(),
+            builder_name: "test_finalize_now_stops_loop_without_block".to_string(),
+            slot_bidder: bidder.clone(),
+            cancel: cancel.clone(),
+            sbundle_mergeabe_signers: vec![],
+            pending_candidates: Default::default(),
+        };
+        // only stops the loop if FinalizeNow is ignored
+        let watchdog_cancel = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_secs(5));
+            watchdog_cancel.cancel();
+        });
+        // no orders so the block is skipped after the fill pass
+        run_ordering_builder(
+            input,
+            &OrderingBuilderConfig {
+                skip_finalize_on_no_commits: true,
+                ..test_config()
+            },
+        );
+        assert!(!cancel.is_cancelled());
+        assert!(sink.is_empty());
+        assert_eq!(bidder.calls.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_always_decline_bidder_skips_block() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+        let build = |coinbase_mode: CoinbaseMode| {
+            test_builder_on(
+                &test_chain,
+                test_chain.block_building_context().clone(),
+                Arc::new(AlwaysDeclineBidder),
+                "test_always_decline_bidder_skips_block",
+                test_config(),
+            )?
+            .build_block(block_orders.clone(), coinbase_mode)
+        };
+        // the bidder is asked for the payout value
+        assert!(build(BUILDER_SIGNER)?.is_none());
+        // no payout tx, nothing to ask
+        assert!(build(FEE_RECIPIENT)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_failing_sink_does_not_stop_building() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            &[coinbase_payment_order(&test_chain)?],
+            false,
+        )?;
+        // kept alive, a closed channel ends the slot
+        let (sender, receiver) = tokio::sync::broadcast::channel(10);
+        for sim_order in sim_orders {
+            sender.send(SimulatedOrderCommand::Simulation(sim_order))?;
+        }
+        let cancel = CancellationToken::new();
+        let sink = FailingSink::new(1);
+        let input = LiveBuilderInput {
+            provider_factory: test_chain.provider_factory().clone(),
+            root_hash_task_pool: BlockingTaskPool::build()?,
+            ctx: test_chain.block_building_context().clone(),
+            input: receiver,
+            sink: sink.clone(),
+            builder_name: "test_failing_sink_does_not_stop_building".to_string(),
+            slot_bidder: Arc::new(StaticBidder::full_value()),
+            cancel: cancel.clone(),
+            sbundle_mergeabe_signers: vec![],
+            pending_candidates: Default::default(),
+        };
+        let canceller = {
+            let (sink, cancel) = (sink.clone(), cancel.clone());
+            std::thread::spawn(move || {
+                let start = Instant::now();
+                while sink.failed() < 2 && start.elapsed() < Duration::from_secs(5) {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                cancel.cancel();
+            })
+        };
+        run_ordering_builder(input, &test_config());
+        canceller.join().unwrap();
+        // the builder kept going after the first block was dropped
+        assert!(sink.failed() >= 2, "failed: {}", sink.failed());
+        assert_eq!(sink.recorded().len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_identity_on_built_block() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let builder_identity = BuilderIdentity {
+            version: "v1.2.3".to_string(),
+            instance_id: "instance-7".to_string(),
+            region: "eu-west".to_string(),
+        };
+        let mut ctx = test_chain.block_building_context().clone();
+        ctx.modify_builder_identity(builder_identity.clone());
+        let block = test_builder_on(
+            &test_chain,
+            ctx,
+            Arc::new(StaticBidder::full_value()),
+            "test_builder_identity_on_built_block",
+            test_config(),
+        )?
+        .build_block(
+            BlockOrders::new(Sorting::MaxProfit, vec![], &[]),
+            FEE_RECIPIENT,
+        )?
+        .unwrap();
+        assert_eq!(block.trace.builder_identity, builder_identity);
+        assert_eq!(block.builder_name, "test_builder_identity_on_built_block");
+        Ok(())
+    }
+
+    #[test]
+    fn test_root_hash_skipped_flag() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let new_builder = || -> eyre::Result<_> {
+            Ok(OrderingBuilderContext::new(
+                test_chain.provider_factory().clone(),
+                Arc::new(StaticBidder::full_value()),
+                BlockingTaskPool::build()?,
+                "test_root_hash_skipped_flag".to_string(),
+                test_chain.block_building_context().clone(),
+                test_config(),
+            ))
+        };
+        let empty_orders = || BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        let skipped = new_builder()?
+            .with_skip_root_hash()
+            .build_block(empty_orders(), FEE_RECIPIENT)?
+            .unwrap();
+        assert!(skipped.root_hash_skipped);
+        let correct = new_builder()?
+            .build_block(empty_orders(), FEE_RECIPIENT)?
+            .unwrap();
+        assert!(!correct.root_hash_skipped);
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_unaffordable_basefee() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+        let build = |ctx: BlockBuildingContext,
+                     coinbase_mode: CoinbaseMode|
+         -> eyre::Result<Option<Block>> {
+            test_builder_on(
+                &test_chain,
+                ctx,
+                Arc::new(StaticBidder::full_value()),
+                "test_skip_unaffordable_basefee",
+                OrderingBuilderConfig {
+                    skip_unaffordable_basefee: true,
+                    ..test_config()
+                },
+            )?
+            .build_block(block_orders.clone(), coinbase_mode)
+        };
+        assert!(build(test_chain.block_building_context().clone(), BUILDER_SIGNER)?.is_some());
+
+        // builder signer has 1 ETH, a transfer at 1M gwei costs 21 ETH
+        let mut ctx = test_chain.block_building_context().clone();
+        ctx.block_env.basefee = U256::from(1_000_000 * GWEI);
+        assert!(build(ctx.clone(), BUILDER_SIGNER)?.is_none());
+
+        // the skipped iteration leaves the warm cache in place
+        let mut builder = test_builder_on(
+            &test_chain,
+            ctx.clone(),
+            Arc::new(StaticBidder::full_value()),
+            "test_skip_unaffordable_basefee",
+            OrderingBuilderConfig {
+                skip_unaffordable_basefee: true,
+                ..test_config()
+            },
+        )?
+        .with_cached_reads(CachedReads::default());
+        assert!(builder
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .is_none());
+        assert!(builder.take_cached_reads().is_some());
+
+        // the signer pays nothing when the fee recipient is the coinbase
+        assert!(build(ctx, FEE_RECIPIENT)?.is_some());
+        Ok(())
+    }
+
+    #[test]
+    fn test_coinbase_payment_tx_hash() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let orders = [coinbase_payment_order(&test_chain)?];
+        let build = |coinbase_mode: CoinbaseMode| -> eyre::Result<Block> {
+            Ok(build_block_with(
+                &test_chain,
+                &orders,
+                test_config(),
+                "test_coinbase_payment_tx_hash",
+                coinbase_mode,
+            )?
+            .unwrap())
+        };
+
+        // we are the coinbase and pay the proposer with the last tx
+        let block = build(BUILDER_SIGNER)?;
+        let payment_tx = block.sealed_block.body.last().unwrap();
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert_eq!(block.coinbase_payment_tx_hash, Some(payment_tx.hash()));
+
+        // fee recipient is the coinbase, no payment tx
+        let block = build(FEE_RECIPIENT)?;
+        assert_eq!(block.sealed_block.body.len(), 1);
+        assert_eq!(block.coinbase_payment_tx_hash, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_use_suggested_fee_recipient_as_coinbase_flag() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let orders = [coinbase_payment_order(&test_chain)?];
+        let ctx = test_chain.block_building_context();
+        let build = |use_suggested_fee_recipient_as_coinbase: bool| -> eyre::Result<Block> {
+            let config = OrderingBuilderConfig {
+                use_suggested_fee_recipient_as_coinbase,
+                ..test_config()
+            };
+            // always asking for the fee recipient as coinbase, the flag decides
+            Ok(build_block_with(
+                &test_chain,
+                &orders,
+                config,
+                "test_use_suggested_fee_recipient_as_coinbase_flag",
+                FEE_RECIPIENT,
+            )?
+            .unwrap())
+        };
+        assert_ne!(
+            ctx.block_env.coinbase,
+            ctx.attributes.suggested_fee_recipient
+        );
+
+        let block = build(true)?;
+        assert_eq!(
+            block.sealed_block.header.beneficiary,
+            ctx.attributes.suggested_fee_recipient
+        );
+        assert_eq!(block.coinbase_payment_tx_hash, None);
+        assert_eq!(block.trace.coinbase_mode, FEE_RECIPIENT);
+
+        // original coinbase is kept and the proposer gets the payout tx
+        let block = build(false)?;
+        assert_eq!(
+            block.sealed_block.header.beneficiary,
+            ctx.block_env.coinbase
+        );
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert!(block.coinbase_payment_tx_hash.is_some());
+        // the trace has the mode actually used, not the requested one
+        assert_eq!(block.trace.coinbase_mode, BUILDER_SIGNER);
+        Ok(())
+    }
+
+    #[test]
+    fn test_payout_mode() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let orders = [coinbase_payment_order(&test_chain)?];
+        let ctx = test_chain.block_building_context();
+        let build = |coinbase_mode: CoinbaseMode| -> eyre::Result<Block> {
+            Ok(build_block_with(
+                &test_chain,
+                &orders,
+                test_config(),
+                "test_payout_mode",
+                coinbase_mode,
+            )?
+            .unwrap())
+        };
+
+        // the fee recipient is an EOA so the estimation gives a plain transfer
+        for (payout, gas_limit) in [
+            (PayoutMode::EstimatedGasLimit, 21_000),
+            (PayoutMode::FixedGasLimit(30_000), 30_000),
+        ] {
+            let coinbase_mode = CoinbaseMode::BuilderSigner { payout };
+            let block = build(coinbase_mode)?;
+            assert_eq!(
+                block.sealed_block.header.beneficiary,
+                ctx.block_env.coinbase
+            );
+            assert_eq!(block.trace.coinbase_mode, coinbase_mode);
+            let payout_tx = block.sealed_block.body.last().unwrap();
+            assert_eq!(block.coinbase_payment_tx_hash, Some(payout_tx.hash()));
+            assert_eq!(payout_tx.gas_limit(), gas_limit);
+        }
+
+        let block = build(FEE_RECIPIENT)?;
+        assert_eq!(
+            block.sealed_block.header.beneficiary,
+            ctx.attributes.suggested_fee_recipient
+        );
+        assert_eq!(block.trace.coinbase_mode, FEE_RECIPIENT);
+        assert_eq!(block.sealed_block.body.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unpaid_aggregated_refund_fails_finalization() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .refund_mode(RefundMode::Aggregated),
+        )?;
+        let refund_sink = test_chain.named_address(NamedAddr::RefundSink)?;
+        let sign = |args: TxArgs| -> eyre::Result<TransactionSignedEcRecoveredWithBlobs> {
+            Ok(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(test_chain.sign_tx(args)?)
+                    .unwrap(),
+            )
+        };
+        // 90% of the coinbase payment is refunded to the sink at the end of the block
+        let mut order_builder = OrderBuilder::None;
+        order_builder.start_share_bundle_builder(1, 1);
+        order_builder.start_inner_bundle(false);
+        order_builder.add_tx(
+            sign(TxArgs::new(NamedAddr::User(0), 0).to(NamedAddr::User(1)))?,
+            TxRevertBehavior::NotAllowed,
+        );
+        order_builder.set_inner_bundle_refund_config(vec![RefundConfig {
+            address: refund_sink,
+            percent: 100,
+        }]);
+        order_builder.finish_inner_bundle();
+        order_builder.add_tx(
+            sign(TxArgs::new_send_to_coinbase(
+                NamedAddr::User(1),
+                0,
+                1_000_000_000_000_000,
+            ))?,
+            TxRevertBehavior::NotAllowed,
+        );
+        order_builder.set_inner_bundle_refund(vec![Refund {
+            body_idx: 0,
+            percent: 90,
+        }]);
+        let sbundle = order_builder.build_order();
+        // lower profit than the sbundle so it lands after it and before the refund tx
+        let close_sink = Order::Tx(MempoolTx::new(sign(TxArgs::new_close_refund_sink(
+            NamedAddr::User(2),
+            0,
+        ))?));
+
+        let ctx = test_chain.block_building_context();
+        let build = |orders: &[Order]| -> eyre::Result<Option<Block>> {
+            let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+                test_chain.provider_factory().clone(),
+                ctx,
+                orders,
+                false,
+            )?;
+            assert_eq!(sim_orders.len(), orders.len());
+            test_builder(
+                &test_chain,
+                "test_unpaid_aggregated_refund_fails_finalization",
+                OrderingBuilderConfig {
+                    refund_mode: RefundMode::Aggregated,
+                    ..test_config()
+                },
+            )?
+            .build_block(to_block_orders(sim_orders), BUILDER_SIGNER)
+        };
+
+        // sbundle txs, refund tx, payout tx
+        let block = build(&[sbundle.clone()])?.expect("block");
+        let body = &block.sealed_block.body;
+        assert_eq!(body.len(), 4);
+        assert_eq!(body[2].to(), Some(refund_sink));
+
+        // the sink is closed after the sbundle so the refund can't be paid
+        let err = build(&[sbundle, close_sink]).unwrap_err();
+        match err.downcast_ref::<InsertPayoutTxErr>() {
+            Some(InsertPayoutTxErr::RefundLedger(RefundLedgerError::UnmetObligation {
+                recipient,
+                paid,
+                ..
+            })) => {
+                assert_eq!(*recipient, refund_sink);
+                assert_eq!(*paid, U256::ZERO);
+            }
+            _ => panic!("unexpected error: {err:?}"),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_token_profit_is_scored_but_not_bid() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let token = test_chain.named_address(NamedAddr::MockToken)?;
+        let token_transfer = |to: Address| -> eyre::Result<Order> {
+            let tx = test_chain.sign_tx(
+                TxArgs::new_mock_token_transfer(
+                    NamedAddr::User(0),
+                    0,
+                    to,
+                    U256::from(2_000_000_000_000_000_000u128),
+                )
+                .max_fee_per_gas(10 * GWEI)
+                .max_priority_fee(GWEI),
+            )?;
+            Ok(Order::Tx(MempoolTx::new(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            )))
+        };
+        let order = token_transfer(test_chain.named_address(NamedAddr::Builder)?)?;
+        // 0.5 ETH per token
+        let mut ctx = test_chain.block_building_context().clone();
+        ctx.modify_token_valuation(TokenValuation::new(vec![TokenPriceConfig {
+            token,
+            source: TokenPriceSource::Static {
+                price_wei: U256::from(500_000_000_000_000_000u64),
+                decimals: 18,
+            },
+        }])?);
+
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            &ctx,
+            &[order.clone()],
+            false,
+        )?;
+        let sim_value = sim_orders[0].sim_value.clone();
+        let token_profit = U256::from(1_000_000_000_000_000_000u64);
+        assert_eq!(sim_value.token_profit, token_profit);
+        assert!(sim_value.coinbase_profit > U256::ZERO);
+        assert!(sim_value.coinbase_profit < token_profit);
+        assert_eq!(
+            Sorting::MaxProfit.sorting_value(&sim_value),
+            sim_value.coinbase_profit + token_profit
+        );
+        assert_eq!(
+            sim_value.mev_gas_price,
+            (sim_value.coinbase_profit + token_profit) / U256::from(sim_value.gas_used)
+        );
+
+        let block = test_builder_on(
+            &test_chain,
+            ctx.clone(),
+            Arc::new(StaticBidder::full_value()),
+            "test_token_profit_is_scored_but_not_bid",
+            test_config(),
+        )?
+        .build_block(to_block_orders(sim_orders), BUILDER_SIGNER)?
+        .expect("block");
+        // tx + payout tx, the payout only has the eth profit
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert!(block.trace.bid_value < sim_value.coinbase_profit);
+
+        // the coinbase is the proposer, its tokens are not our profit
+        let mut fee_recipient_ctx = ctx;
+        fee_recipient_ctx.modify_coinbase_mode(FEE_RECIPIENT)?;
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            &fee_recipient_ctx,
+            &[token_transfer(fee_recipient_ctx.block_env.coinbase)?],
+            false,
+        )?;
+        assert_eq!(sim_orders[0].sim_value.token_profit, U256::ZERO);
+
+        // not configured, not valued
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            &[order],
+            false,
+        )?;
+        assert_eq!(sim_orders[0].sim_value.token_profit, U256::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_twap_token_profit() -> eyre::Result<()> {
+        let timestamp = 10_000;
+        let test_chain = TestChainState::new(BlockArgs::default().number(1).timestamp(timestamp))?;
+        let token = test_chain.named_address(NamedAddr::MockToken)?;
+        let builder = test_chain.named_address(NamedAddr::Builder)?;
+
+        // UniV3 token/WETH pool (token is token0): 500s at tick -7000 then 100s at the current tick -6532,
+        // the 600s twap tick is -6922
+        let pool = Address::repeat_byte(0x15);
+        let int_bits = |value: i64, bits: usize| U256::from((value as u64) & ((1u64 << bits) - 1));
+        let observation = |observation_timestamp: u64, tick_cumulative: i64| {
+            U256::from(observation_timestamp)
+                | (int_bits(tick_cumulative, 56) << 32)
+                | (U256::from(1) << 248)
+        };
+        // tick, observationIndex = 1, observationCardinality = 2
+        let slot0 = (int_bits(-6532, 24) << 160) | (U256::from(1) << 184) | (U256::from(2) << 200);
+        test_chain.set_storage(
+            pool,
+            &[
+                (U256::from(0), slot0),
+                (U256::from(8), observation(timestamp - 600, 0)),
+                (U256::from(9), observation(timestamp - 100, -7000 * 500)),
+            ],
+        )?;
+
+        let amount = 2_000_000_000_000_000_000u128;
+        let tx = test_chain.sign_tx(
+            TxArgs::new_mock_token_transfer(NamedAddr::User(0), 0, builder, U256::from(amount))
+                .max_fee_per_gas(10 * GWEI)
+                .max_priority_fee(GWEI),
+        )?;
+        let order = Order::Tx(MempoolTx::new(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+        ));
+        let mut ctx = test_chain.block_building_context().clone();
+        ctx.modify_token_valuation(TokenValuation::new(vec![TokenPriceConfig {
+            token,
+            source: TokenPriceSource::UniV3Twap {
+                pool,
+                weth: MAINNET_WETH,
+                twap_secs: 600,
+            },
+        }])?);
+
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            &ctx,
+            &[order],
+            false,
+        )?;
+        let sim_value = sim_orders[0].sim_value.clone();
+        let token_profit: f64 = sim_value.token_profit.to_string().parse()?;
+        let expected = amount as f64 * 1.0001f64.powi(-6922);
+        assert!(((token_profit - expected) / expected).abs() < 1e-9);
+
+        let block = test_builder_on(
+            &test_chain,
+            ctx,
+            Arc::new(StaticBidder::full_value()),
+            "test_twap_token_profit",
+            test_config(),
+        )?
+        .build_block(to_block_orders(sim_orders), BUILDER_SIGNER)?
+        .expect("block");
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert!(block.trace.bid_value < sim_value.coinbase_profit);
+        Ok(())
+    }
+
+    #[test]
+    fn test_bid_value_metric_matches_trace() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let builder_name = "test_bid_value_metric_matches_trace";
+        let block = build_block_with(
+            &test_chain,
+            &[coinbase_payment_order(&test_chain)?],
+            test_config(),
+            builder_name,
+            FEE_RECIPIENT,
+        )?
+        .expect("block");
+
+        let bid_value: f64 = format_ether(block.trace.bid_value).parse()?;
+        assert!(bid_value > 0.0);
+        let metric = telemetry::BLOCK_BID_VALUE
+            .with_label_values(&[builder_name])
+            .get();
+        assert!((metric - bid_value).abs() <= bid_value * 1e-6);
+        Ok(())
+    }
+
+    /// A bundle that loses half its profit to a conflicting tx earlier in the block is reported with the profit it
+    /// was ranked with, not the in place value of its retry.
+    #[test]
+    fn test_profit_divergence_report() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let value = 100_000_000_000_000_000u64;
+        let sign = |from: usize, value: u64| {
+            test_chain.sign_tx(TxArgs::new_send_to_coinbase(
+                NamedAddr::User(from),
+                0,
+                value,
+            ))
+        };
+        let conflicting_tx = Order::Tx(MempoolTx::new(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(sign(1, 3 * value)?).unwrap(),
+        ));
+        let mut order_builder = OrderBuilder::None;
+        order_builder.start_bundle_builder(1);
+        order_builder.add_tx(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(sign(0, value)?).unwrap(),
+            TxRevertBehavior::NotAllowed,
+        );
+        // same nonce as conflicting_tx, skipped on the block
+        order_builder.add_tx(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(sign(1, value)?).unwrap(),
+            TxRevertBehavior::AllowedIncluded,
+        );
+        let bundle = order_builder.build_order();
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            &[conflicting_tx.clone(), bundle.clone()],
+            false,
+        )?;
+        let simulated_profit = sim_orders
+            .iter()
+            .find(|order| order.id() == bundle.id())
+            .expect("bundle sim")
+            .sim_value
+            .coinbase_profit;
+        let builder_name = "test_profit_divergence_report";
+        let block = test_builder(&test_chain, builder_name, test_config())?
+            .build_block(to_block_orders(sim_orders), FEE_RECIPIENT)?
+            .expect("block");
+        assert_eq!(block.trace.included_orders.len(), 2);
+
+        let report = PROFIT_DIVERGENCES
+            .reports()
+            .into_iter()
+            .find(|report| report.builder_name == builder_name)
+            .expect("report");
+        assert_eq!(report.block, 1);
+        assert_eq!(report.orders, 2);
+        let top = &report.top_diverging_orders[0];
+        assert_eq!(top.order_id, bundle.id());
+        assert_eq!(top.simulated_profit, simulated_profit);
+        // only the send to coinbase (and fee) of the first tx is realized
+        assert!(top.realized_profit > simulated_profit * U256::from(4) / U256::from(10));
+        assert!(top.realized_profit < simulated_profit * U256::from(6) / U256::from(10));
+        let lost = simulated_profit - top.realized_profit;
+        assert_eq!(top.signed_error_wei, -(lost.to::<u128>() as f64));
+        assert_eq!(report.top_diverging_orders[1].order_id, conflicting_tx.id());
+        assert_eq!(report.top_diverging_orders[1].signed_error_wei, 0.0);
+        assert_eq!(report.mean_signed_error_wei, top.signed_error_wei / 2.0);
+        assert_eq!(report.p95_abs_error_wei, -top.signed_error_wei);
+
+        let metric = telemetry::LAST_BLOCK_ORDER_PROFIT_DIVERGENCE
+            .with_label_values(&[builder_name, "p95_abs_error"])
+            .get();
+        let lost_eth: f64 = format_ether(lost).parse()?;
+        assert!((metric - lost_eth).abs() <= lost_eth * 1e-6);
+        Ok(())
+    }
+
+    /// Simulation must run the EIP-4788 system call like the final block does.
+    #[test]
+    fn test_beacon_root_read_sim_matches_block() -> eyre::Result<()> {
+        let root = B256::repeat_byte(0x47);
+        // post Cancun on mainnet
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .timestamp(1_710_338_135 + 120)
+                .parent_beacon_block_root(root),
+        )?;
+        let ctx = test_chain.block_building_context();
+        assert_eq!(ctx.parent_beacon_block_root(), Some(root));
+        let order = |nonce, root| -> eyre::Result<Order> {
+            let tx = test_chain.sign_tx(
+                TxArgs::new_check_beacon_root(NamedAddr::User(0), nonce, root)
+                    .max_fee_per_gas(10 * GWEI)
+                    .max_priority_fee(GWEI),
+            )?;
+            Ok(Order::Tx(MempoolTx::new(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            )))
+        };
+        let (sim_orders, sim_errors) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            ctx,
+            &[order(0, root)?],
+            false,
+        )?;
+        assert!(sim_errors.is_empty(), "{:?}", sim_errors);
+        assert_eq!(sim_orders.len(), 1);
+        let sim_gas_used = sim_orders[0].sim_value.gas_used;
+
+        // the checker really checks
+        let (wrong_root_sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            ctx,
+            &[order(0, B256::repeat_byte(0x48))?],
+            false,
+        )?;
+        assert!(wrong_root_sim_orders.is_empty());
+
+        let block = test_builder(
+            &test_chain,
+            "test_beacon_root_read_sim_matches_block",
+            test_config(),
+        )?
+        .build_block(to_block_orders(sim_orders), FEE_RECIPIENT)?
+        .expect("block");
+        assert_eq!(block.trace.included_orders.len(), 1);
+        let executed = &block.trace.included_orders[0];
+        assert!(executed.receipts[0].success);
+        assert_eq!(executed.gas_used, sim_gas_used);
+        assert_eq!(block.sealed_block.parent_beacon_block_root, Some(root));
+        Ok(())
+    }
+
+    #[test]
+    fn test_lazy_block_header_matches_sealed_block() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+        let new_builder = || test_builder(&test_chain, "test_lazy_block", test_config());
+
+        let lazy_block = new_builder()?
+            .build_lazy_block(block_orders.clone(), BUILDER_SIGNER)?
+            .expect("block");
+        let header = lazy_block.sealed_header().clone();
+        let bid_value = lazy_block.trace.bid_value;
+        let block = lazy_block.seal();
+        assert_eq!(block.sealed_block.body.len(), 2);
+        assert_eq!(block.trace.bid_value, bid_value);
+
+        // relay submit sink, sealed when the submission job takes it
+        let best_block = crate::building::builders::BestBlockCell::default();
+        best_block.new_lazy_block(
+            new_builder()?
+                .build_lazy_block(block_orders.clone(), BUILDER_SIGNER)?
+                .expect("block"),
+        );
+        let taken_block = best_block.take_best_block().expect("best block");
+        assert_eq!(taken_block.trace.bid_value, bid_value);
+
+        let full_block = new_builder()?
+            .build_block(block_orders, BUILDER_SIGNER)?
+            .expect("block");
+        for sealed_header in [
+            &block.sealed_block.header,
+            &taken_block.sealed_block.header,
+            &full_block.sealed_block.header,
+        ] {
+            assert_eq!(sealed_header.hash(), header.hash());
+            assert_eq!(
+                alloy_rlp::encode(sealed_header.header()),
+                alloy_rlp::encode(header.header())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_rlp_roundtrip() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block = build_block_with(
+            &test_chain,
+            &[coinbase_payment_order(&test_chain)?],
+            test_config(),
+            "test_block_rlp_roundtrip",
+            BUILDER_SIGNER,
+        )?
+        .expect("block");
+        // user tx + payment tx
+        assert_eq!(block.sealed_block.body.len(), 2);
+
+        let encoded = block.rlp_encoded();
+        let decoded = reth::primitives::Block::decode(&mut encoded.as_ref())?.seal_slow();
+        assert_eq!(decoded, block.sealed_block);
+        assert_eq!(decoded.hash(), block.sealed_block.hash());
+        Ok(())
+    }
+
+    fn missing_parent_ctx(test_chain: &TestChainState) -> BlockBuildingContext {
+        let mut ctx = test_chain.block_building_context().clone();
+        ctx.attributes.parent = B256::repeat_byte(0x11);
+        ctx
+    }
+
+    #[test]
+    fn test_build_block_missing_parent() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let ctx = missing_parent_ctx(&test_chain);
+        let parent = ctx.attributes.parent;
+        let mut builder = test_builder_on(
+            &test_chain,
+            ctx,
+            Arc::new(StaticBidder::full_value()),
+            "test",
+            test_config(),
+        )?;
+
+        let block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        let err = builder
+            .build_block(block_orders, BUILDER_SIGNER)
+            .unwrap_err();
+        assert!(is_parent_not_found_error(&err, parent));
+        assert!(!is_parent_not_found_error(&eyre::eyre!("other"), parent));
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_parent_skips_slot() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let (_sender, receiver) = tokio::sync::broadcast::channel(10);
+        let cancel = tokio_util::sync::CancellationToken::new();
+        let input = LiveBuilderInput {
+            provider_factory: test_chain.provider_factory().clone(),
+            root_hash_task_pool: BlockingTaskPool::build()?,
+            ctx: missing_parent_ctx(&test_chain),
+            input: receiver,
+            sink: crate::building::builders::BestBlockCell::default(),
+            builder_name: "test".to_string(),
+            slot_bidder: Arc::new(StaticBidder::full_value()),
+            cancel: cancel.clone(),
+            sbundle_mergeabe_signers: vec![],
+            pending_candidates: Default::default(),
+        };
+        // returns only because the slot gets cancelled
+        run_ordering_builder(
+            input,
+            &OrderingBuilderConfig {
+                parent_not_found: ParentNotFoundAction::SkipSlot,
+                ..test_config()
+            },
+        );
+        assert!(cancel.is_cancelled());
+        Ok(())
+    }
+
+    #[test]
+    fn test_submit_delay() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let build_empty_block = || -> eyre::Result<LazyBlock> {
+            Ok(
+                test_builder(&test_chain, "test_submit_delay", test_config())?
+                    .build_lazy_block(
+                        BlockOrders::new(Sorting::MaxProfit, vec![], &[]),
+                        FEE_RECIPIENT,
+                    )?
+                    .expect("block"),
+            )
+        };
+        let delay = Duration::from_millis(100);
+
+        let sink = RecordingSink::default();
+        let cancel = CancellationToken::new();
+        let start = Instant::now();
+        assert!(submit_lazy_block(
+            &sink,
+            build_empty_block()?,
+            Some(delay),
+            &cancel
+        ));
+        let blocks = sink.blocks();
+        assert_eq!(blocks.len(), 1);
+        assert!(blocks[0].received_at - start >= delay);
+
+        // no delay
+        let start = Instant::now();
+        assert!(submit_lazy_block(
+            &sink,
+            build_empty_block()?,
+            None,
+            &cancel
+        ));
+        assert!(sink.blocks()[1].received_at - start < delay);
+
+        // cancelled while waiting
+        let sink = RecordingSink::default();
+        let cancel = CancellationToken::new();
+        let canceller = {
+            let cancel = cancel.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(20));
+                cancel.cancel();
+            })
+        };
+        let start = Instant::now();
+        assert!(!submit_lazy_block(
+            &sink,
+            build_empty_block()?,
+            Some(Duration::from_secs(10)),
+            &cancel
+        ));
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert!(sink.is_empty());
+        canceller.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_poisoned_cached_reads_are_detected() -> eyre::Result<()> {
+        let builder_name = "test_poisoned_cached_reads_are_detected";
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+        let new_builder = || {
+            test_builder(
+                &test_chain,
+                builder_name,
+                OrderingBuilderConfig {
+                    verify_cached_reads_bps: 10_000,
+                    ..test_config()
+                },
+            )
+        };
+        let divergences = || {
+            telemetry::CACHED_READS_DIVERGENCES
+                .with_label_values(&[builder_name])
+                .get()
+        };
+
+        // valid cache is kept between builds
+        let before = divergences();
+        let mut builder = new_builder()?;
+        assert!(builder
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .is_some());
+        assert!(builder
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .is_some());
+        assert_eq!(divergences(), before);
+        assert!(builder.take_cached_reads().is_some());
+
+        // stale balance for the order signer
+        let mut poisoned_cached_reads = CachedReads::default();
+        poisoned_cached_reads.insert_account(
+            test_chain.named_address(NamedAddr::User(0))?,
+            revm::primitives::AccountInfo::from_balance(U256::from(1)),
+            Default::default(),
+        );
+        let mut builder = new_builder()?.with_cached_reads(poisoned_cached_reads);
+        let err = builder
+            .build_block(block_orders.clone(), BUILDER_SIGNER)
+            .unwrap_err();
+        assert!(err.to_string().contains("Cached reads diverged"));
+        assert!(divergences() > before);
+
+        // the rest of the slot builds without shared cache
+        let block = builder
+            .build_block(block_orders, BUILDER_SIGNER)?
+            .expect("block");
+        assert_eq!(block.trace.included_orders.len(), 1);
+        assert!(builder.take_cached_reads().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_commit_verification() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+        let new_builder = |verify_commits: bool| {
+            test_builder(
+                &test_chain,
+                "test_commit_verification",
+                OrderingBuilderConfig {
+                    verify_commits,
+                    ..test_config()
+                },
+            )
+        };
+
+        // order + payout tx verified
+        let block = new_builder(true)?
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .expect("block");
+        assert_eq!(block.trace.included_orders.len(), 1);
+
+        // injected discrepancy: a stale (but big enough) balance for the order signer changes the state the commit writes
+        let poisoned_cached_reads = || {
+            let mut cached_reads = CachedReads::default();
+            cached_reads.insert_account(
+                test_chain.named_address(NamedAddr::User(0)).unwrap(),
+                revm::primitives::AccountInfo::from_balance(U256::from(10).pow(U256::from(30))),
+                Default::default(),
+            );
+            cached_reads
+        };
+        assert!(new_builder(false)?
+            .with_cached_reads(poisoned_cached_reads())
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .is_some());
+        let err = new_builder(true)?
+            .with_cached_reads(poisoned_cached_reads())
+            .build_block(block_orders, BUILDER_SIGNER)
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("Commit verification failed"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_provider_io_budget() -> eyre::Result<()> {
+        let builder_name = "test_provider_io_budget";
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let block_orders = sim_block_orders(&test_chain, &[coinbase_payment_order(&test_chain)?])?;
+        let new_builder = |max_provider_io_time_ms: Option<u64>| {
+            test_builder(
+                &test_chain,
+                builder_name,
+                OrderingBuilderConfig {
+                    max_provider_io_time_ms,
+                    ..test_config()
+                },
+            )
+        };
+        let account_calls = || {
+            telemetry::PROVIDER_IO_CALLS
+                .with_label_values(&[builder_name, "account"])
+                .get()
+        };
+
+        let before = account_calls();
+        let block = new_builder(Some(60_000))?
+            .build_block(block_orders.clone(), BUILDER_SIGNER)?
+            .expect("block");
+        let provider_io = block.trace.provider_io;
+        assert!(provider_io.account.calls > 0);
+        assert!(provider_io.time() > Duration::ZERO);
+        assert!(block.trace.state_provider_open_time > Duration::ZERO);
+        assert_eq!(account_calls() - before, provider_io.account.calls);
+
+        let err = new_builder(Some(0))?
+            .build_block(block_orders, BUILDER_SIGNER)
+            .unwrap_err();
+        let err = err
+            .downcast_ref::<ProviderIoBudgetExceeded>()
+            .expect("typed error");
+        assert_eq!(err.budget, Duration::ZERO);
+        assert!(err.spent > Duration::ZERO);
+        Ok(())
+    }
+
+    #[test]
+    fn test_time_to_first_commit_metric() -> eyre::Result<()> {
+        let builder_name = "test_time_to_first_commit_metric";
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let orders = [0, 1]
+            .into_iter()
+            .map(|user| {
+                tx_order(
+                    &test_chain,
+                    TxArgs::new_send_to_coinbase(NamedAddr::User(user), 0, 1_000_000_000_000_000)
+                        .max_fee_per_gas(10 * GWEI),
+                )
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let build = |orders: &[Order], time_to_first_commit_metric: bool| -> eyre::Result<()> {
+            build_block_with(
+                &test_chain,
+                orders,
+                OrderingBuilderConfig {
+                    time_to_first_commit_metric,
+                    ..test_config()
+                },
+                builder_name,
+                BUILDER_SIGNER,
+            )?;
+            Ok(())
+        };
+        let samples = || {
+            telemetry::TIME_TO_FIRST_COMMIT
+                .with_label_values(&[builder_name])
+                .get_sample_count()
+        };
+
+        let before = samples();
+        // once per build even with several commits
+        build(&orders, true)?;
+        assert_eq!(samples(), before + 1);
+        // nothing committed
+        build(&[], true)?;
+        assert_eq!(samples(), before + 1);
+        // disabled
+        build(&orders, false)?;
+        assert_eq!(samples(), before + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_build_cpu_time_metric() -> eyre::Result<()> {
+        let builder_name = "test_build_cpu_time_metric";
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        // enough evm work for the thread clock to move
+        let mut orders = Vec::new();
+        for user in 0..5 {
+            for nonce in 0..4 {
+                orders.push(tx_order(
+                    &test_chain,
+                    TxArgs::new_increment_value(NamedAddr::User(user), nonce, user as u64, nonce)
+                        .max_fee_per_gas(10 * GWEI),
+                )?);
+            }
+        }
+        let metric = || telemetry::BUILD_CPU_TIME.with_label_values(&[builder_name]);
+        let (samples_before, sum_before) = (metric().get_sample_count(), metric().get_sample_sum());
+        build_block_with(
+            &test_chain,
+            &orders,
+            OrderingBuilderConfig {
+                build_cpu_time_metric: true,
+                ..test_config()
+            },
+            builder_name,
+            FEE_RECIPIENT,
+        )?
+        .expect("block");
+        assert_eq!(metric().get_sample_count(), samples_before + 1);
+        assert!(metric().get_sample_sum() > sum_before);
+        Ok(())
+    }
+}
@@ -1,7 +1,8 @@
-use super::{BundleErr, ExecutionError, ExecutionResult, OrderErr};
+use super::{provider_io::ProviderIoStats, BundleErr, ExecutionError, ExecutionResult, OrderErr};
 use crate::primitives::{Order, OrderReplacementKey};
 use ahash::{HashMap, HashSet};
-use alloy_primitives::{Address, U256};
+use alloy_primitives::{Address, Bytes, B256, U256};
+use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use time::OffsetDateTime;
 
@@ -15,12 +16,139 @@ pub struct BuiltBlockTrace {
     pub bid_value: U256,
     /// True block value (coinbase balance delta) excluding the cost of the payout to validator
     pub true_bid_value: U256,
-    /// Some bundle failed with BundleErr::NoSigner, we might want to switch to !use_suggested_fee_recipient_as_coinbase
+    /// Some bundle failed with BundleErr::NoSigner, we might want to switch to CoinbaseMode::BuilderSigner
     pub got_no_signer_error: bool,
+    /// Coinbase the block was built with, tells how the proposer is paid.
+    pub coinbase_mode: CoinbaseMode,
     pub orders_closed_at: OffsetDateTime,
     pub orders_sealed_at: OffsetDateTime,
     pub fill_time: Duration,
     pub finalize_time: Duration,
+    /// history_by_block_hash call that opened the state for the build.
+    pub state_provider_open_time: Duration,
+    /// State provider lookups of the fill (cached reads misses only).
+    pub provider_io: ProviderIoStats,
+    /// Used to fill [`ExecutionResult::log_summary`] on add_included_order.
+    pub log_summary_mode: LogSummaryMode,
+    /// Instance that built the block.
+    pub builder_identity: BuilderIdentity,
+    /// Orders the fill loop tried to commit (failed ones and retries included), included_orders are the successful ones.
+    /// Can be lower than the available orders if the fill was cut (deadline, max_orders_per_iteration).
+    pub commit_attempts: usize,
+}
+
+/// Coinbase of a block and so how the proposer gets paid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoinbaseMode {
+    /// The slot suggested_fee_recipient is the coinbase and gets the block value directly, no payout tx.
+    /// Disables the orders that need us to pay (eg: mev-share refunds) since the builder gets nothing.
+    SuggestedFeeRecipient,
+    /// The builder signer is the coinbase and pays the proposer with a payout tx at the end of the block.
+    BuilderSigner { payout: PayoutMode },
+}
+
+impl Default for CoinbaseMode {
+    fn default() -> Self {
+        CoinbaseMode::BuilderSigner {
+            payout: PayoutMode::default(),
+        }
+    }
+}
+
+/// How a CoinbaseMode::BuilderSigner block pays the proposer.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayoutMode {
+    /// Payout tx with the gas limit estimated at the start of every block (see [`super::estimate_payout_gas_limit`]).
+    #[default]
+    EstimatedGasLimit,
+    /// Payout tx with this gas limit, saves the estimation when the cost of paying the fee recipients is known.
+    /// If it's not enough the payout tx fails and no block is built.
+    FixedGasLimit(u64),
+}
+
+/// Identifies the builder instance on multi-instance deployments (builder_name only identifies the algorithm).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BuilderIdentity {
+    pub version: String,
+    pub instance_id: String,
+    pub region: String,
+}
+
+/// How much of the logs emitted by the included orders we keep on the trace (eg: to generate MEV-Share hints about landed backruns).
+/// Defaults to TopicsOnly since a trace is kept for every built block and log data can be big.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogSummaryMode {
+    None,
+    #[default]
+    TopicsOnly,
+    Full,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LogSummary {
+    pub address: Address,
+    pub topics: Vec<B256>,
+    /// Only for LogSummaryMode::Full.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<Bytes>,
+}
+
+/// Receipt status and logs of the txs of an included order (same order as [`ExecutionResult::txs`]).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderLogSummary {
+    pub tx_success: Vec<bool>,
+    /// Logs for each tx, empty if redacted.
+    pub tx_logs: Vec<Vec<LogSummary>>,
+    /// Logs were dropped because the order (or one of its original orders) is private (see [`crate::primitives::Metadata::private`]).
+    pub redacted: bool,
+}
+
+impl OrderLogSummary {
+    /// None for LogSummaryMode::None.
+    pub fn new(execution_result: &ExecutionResult, mode: LogSummaryMode) -> Option<Self> {
+        if mode == LogSummaryMode::None {
+            return None;
+        }
+        let redacted = execution_result
+            .order
+            .original_orders()
+            .iter()
+            .any(|order| order.metadata().private);
+        let tx_success = execution_result
+            .receipts
+            .iter()
+            .map(|receipt| receipt.success)
+            .collect();
+        let tx_logs = if redacted {
+            Vec::new()
+        } else {
+            execution_result
+                .receipts
+                .iter()
+                .map(|receipt| {
+                    receipt
+                        .logs
+                        .iter()
+                        .map(|log| LogSummary {
+                            address: log.address,
+                            topics: log.data.topics().to_vec(),
+                            data: (mode == LogSummaryMode::Full).then(|| log.data.data.clone()),
+                        })
+                        .collect()
+                })
+                .collect()
+        };
+        Some(Self {
+            tx_success,
+            tx_logs,
+            redacted,
+        })
+    }
 }
 
 impl Default for BuiltBlockTrace {
@@ -52,9 +180,39 @@ impl BuiltBlockTrace {
             orders_sealed_at: OffsetDateTime::now_utc(),
             fill_time: Duration::from_secs(0),
             finalize_time: Duration::from_secs(0),
+            state_provider_open_time: Duration::from_secs(0),
+            provider_io: ProviderIoStats::default(),
+            log_summary_mode: LogSummaryMode::default(),
+            commit_attempts: 0,
+            coinbase_mode: CoinbaseMode::default(),
+            builder_identity: BuilderIdentity::default(),
+        }
+    }
+
+    pub fn with_builder_identity(self, builder_identity: BuilderIdentity) -> Self {
+        Self {
+            builder_identity,
+            ..self
+        }
+    }
+
+    pub fn with_log_summary_mode(self, log_summary_mode: LogSummaryMode) -> Self {
+        Self {
+            log_summary_mode,
+            ..self
         }
     }
 
+    /// Some included order (or one of its original orders) is private (see [`crate::primitives::Metadata::private`]).
+    pub fn has_private_orders(&self) -> bool {
+        self.included_orders.iter().any(|res| {
+            res.order
+                .original_orders()
+                .iter()
+                .any(|order| order.metadata().private)
+        })
+    }
+
     /// Should be called after block is sealed
     /// Sets:
     /// orders_sealed_at to the current time
@@ -68,7 +226,9 @@ impl BuiltBlockTrace {
     }
 
     /// Call after a commit_order ok
-    pub fn add_included_order(&mut self, execution_result: ExecutionResult) {
+    pub fn add_included_order(&mut self, mut execution_result: ExecutionResult) {
+        execution_result.log_summary =
+            OrderLogSummary::new(&execution_result, self.log_summary_mode);
         self.included_orders.push(execution_result);
     }
 
@@ -0,0 +1,122 @@
+//! Sampled verification of the reads served by [`reth_payload_builder::database::CachedReads`].
+//! CachedReads is shared between block building iterations of a slot, a bug there could make us build on top of stale values
+//! and produce invalid blocks without noticing. Reads reaching the cache are always parent block state so for a sample of them
+//! we re-read the value from the state provider and count the divergences.
+use alloy_primitives::{Address, B256, U256};
+use reth_interfaces::provider::ProviderError;
+use revm::{
+    primitives::{AccountInfo, Bytecode},
+    DatabaseRef,
+};
+use std::cell::Cell;
+use tracing::error;
+
+/// Rates are expressed in basis points.
+pub const VERIFY_CACHED_READS_MAX_BPS: u32 = 10_000;
+
+/// Wraps the cached db (CachedReads over the state provider) and, for sample_rate_bps of the reads, compares against provider.
+#[derive(Debug)]
+pub struct VerifyCachedReadsDBRef<'a, CachedDB, ProviderDB> {
+    cached: CachedDB,
+    provider: ProviderDB,
+    sample_rate_bps: u32,
+    divergences: &'a Cell<usize>,
+}
+
+impl<'a, CachedDB, ProviderDB> VerifyCachedReadsDBRef<'a, CachedDB, ProviderDB> {
+    pub fn new(
+        cached: CachedDB,
+        provider: ProviderDB,
+        sample_rate_bps: u32,
+        divergences: &'a Cell<usize>,
+    ) -> Self {
+        Self {
+            cached,
+            provider,
+            sample_rate_bps,
+            divergences,
+        }
+    }
+
+    fn sample(&self) -> bool {
+        self.sample_rate_bps != 0
+            && (self.sample_rate_bps >= VERIFY_CACHED_READS_MAX_BPS
+                || rand::random::<u32>() % VERIFY_CACHED_READS_MAX_BPS < self.sample_rate_bps)
+    }
+
+    fn add_divergence(&self) {
+        self.divergences.set(self.divergences.get() + 1);
+    }
+}
+
+fn same_account(cached: &Option<AccountInfo>, fresh: &Option<AccountInfo>) -> bool {
+    match (cached, fresh) {
+        (Some(cached), Some(fresh)) => {
+            cached.balance == fresh.balance
+                && cached.nonce == fresh.nonce
+                && cached.code_hash == fresh.code_hash
+        }
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl<'a, CachedDB, ProviderDB> DatabaseRef for VerifyCachedReadsDBRef<'a, CachedDB, ProviderDB>
+where
+    CachedDB: DatabaseRef<Error = ProviderError>,
+    ProviderDB: DatabaseRef<Error = ProviderError>,
+{
+    type Error = ProviderError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        let cached = self.cached.basic_ref(address)?;
+        if self.sample() {
+            let fresh = self.provider.basic_ref(address)?;
+            if !same_account(&cached, &fresh) {
+                error!(?address, ?cached, ?fresh, "Cached account diverged from db");
+                self.add_divergence();
+            }
+        }
+        Ok(cached)
+    }
+
+    /// Code is addressed by its hash so it can't get stale.
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.cached.code_by_hash_ref(code_hash)
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        let cached = self.cached.storage_ref(address, index)?;
+        if self.sample() {
+            let fresh = self.provider.storage_ref(address, index)?;
+            if cached != fresh {
+                error!(
+                    ?address,
+                    ?index,
+                    ?cached,
+                    ?fresh,
+                    "Cached storage diverged from db"
+                );
+                self.add_divergence();
+            }
+        }
+        Ok(cached)
+    }
+
+    fn block_hash_ref(&self, number: U256) -> Result<B256, Self::Error> {
+        let cached = self.cached.block_hash_ref(number)?;
+        if self.sample() {
+            let fresh = self.provider.block_hash_ref(number)?;
+            if cached != fresh {
+                error!(
+                    ?number,
+                    ?cached,
+                    ?fresh,
+                    "Cached block hash diverged from db"
+                );
+                self.add_divergence();
+            }
+        }
+        Ok(cached)
+    }
+}
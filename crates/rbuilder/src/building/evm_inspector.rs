@@ -6,6 +6,9 @@ use revm::{
     Database, EvmContext, Inspector,
 };
 
+/// BLOCKHASH only resolves the last 256 blocks, older (or future) blocks give 0.
+pub const BLOCK_HASH_HISTORY: u64 = 256;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct SlotKey {
     pub address: Address,
@@ -97,6 +100,8 @@ where
 pub struct RBuilderEVMInspector<'a> {
     access_list_inspector: AccessListInspector,
     used_state_inspector: Option<UsedStateEVMInspector<'a>>,
+    /// First BLOCKHASH read of a block older than BLOCK_HASH_HISTORY.
+    unavailable_block_hash_read: Option<U256>,
 }
 
 impl<'a> RBuilderEVMInspector<'a> {
@@ -121,9 +126,15 @@ impl<'a> RBuilderEVMInspector<'a> {
         Self {
             access_list_inspector,
             used_state_inspector,
+            unavailable_block_hash_read: None,
         }
     }
 
+    /// Some(block number) if the tx asked for the hash of a block older than BLOCK_HASH_HISTORY (it got 0).
+    pub fn unavailable_block_hash_read(&self) -> Option<U256> {
+        self.unavailable_block_hash_read
+    }
+
     pub fn into_access_list(self) -> reth::rpc::types::AccessList {
         self.access_list_inspector.into_access_list()
     }
@@ -139,5 +150,15 @@ where
         if let Some(used_state_inspector) = &mut self.used_state_inspector {
             used_state_inspector.step(interp, data);
         }
+        if interp.current_opcode() == opcode::BLOCKHASH
+            && self.unavailable_block_hash_read.is_none()
+        {
+            if let Ok(number) = interp.stack().peek(0) {
+                let current = data.env.block.number;
+                if number < current && current - number > U256::from(BLOCK_HASH_HISTORY) {
+                    self.unavailable_block_hash_read = Some(number);
+                }
+            }
+        }
     }
 }
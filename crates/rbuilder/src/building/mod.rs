@@ -1,15 +1,23 @@
+pub mod batched_reads;
 pub mod block_orders;
 pub mod builders;
 pub mod built_block_trace;
+pub mod cached_reads_verifier;
 #[cfg(test)]
 pub mod conflict;
 pub mod evm_inspector;
 pub mod fmt;
 pub mod order_commit;
 pub mod payout_tx;
+pub mod pending_accounts;
+pub mod profit_divergence;
+pub mod provider_io;
+pub mod refund_ledger;
 pub mod sim;
 pub mod testing;
+pub mod token_valuation;
 pub mod tracers;
+pub mod value_curve;
 pub use block_orders::BlockOrders;
 
 use crate::{
@@ -18,14 +26,16 @@ use crate::{
     utils::{a2r_withdrawal, calc_gas_limit, timestamp_as_u64, Signer},
 };
 use ahash::HashSet;
+use alloy_primitives::{address, hex};
 use jsonrpsee::core::Serialize;
 use reth::{
     payload::PayloadId,
     primitives::{
         constants::BEACON_NONCE, eip4844::calculate_excess_blob_gas, proofs,
         revm::config::revm_spec, revm_primitives::InvalidTransaction, Address,
-        BlobTransactionSidecar, Block, ChainSpec, Head, Header, Receipt, Receipts, SealedBlock,
-        Withdrawals, B256, EMPTY_OMMER_ROOT_HASH, U256,
+        BlobTransactionSidecar, Block, BlockBody, ChainSpec, Head, Header, Receipt, Receipts,
+        SealedBlock, SealedHeader, TransactionSigned, Withdrawal, Withdrawals, B256,
+        EMPTY_OMMER_ROOT_HASH, U256,
     },
     providers::{BundleStateWithReceipts, ProviderFactory},
     rpc::types::beacon::events::PayloadAttributesEvent,
@@ -38,15 +48,18 @@ use reth_interfaces::provider::ProviderError;
 use reth_node_api::PayloadBuilderAttributes;
 use reth_payload_builder::{database::CachedReads, EthPayloadBuilderAttributes};
 use revm::{
-    db::states::bundle_state::BundleRetention::{self, PlainState},
+    db::{
+        states::bundle_state::BundleRetention::{self, PlainState},
+        BundleState,
+    },
     primitives::{BlobExcessGasAndPrice, BlockEnv, CfgEnvWithHandlerCfg, SpecId},
 };
 use serde::Deserialize;
-use std::{hash::Hash, str::FromStr, sync::Arc};
+use std::{hash::Hash, str::FromStr, sync::Arc, time::Duration};
 use thiserror::Error;
 use time::OffsetDateTime;
 
-use self::tracers::SimulationTracer;
+use self::{token_valuation::TokenValuation, tracers::SimulationTracer};
 use crate::{backtest::BlockData, roothash::RootHashMode, utils::default_cfg_env};
 pub use block_orders::*;
 pub use built_block_trace::*;
@@ -54,6 +67,7 @@ pub use built_block_trace::*;
 pub use conflict::*;
 pub use order_commit::*;
 pub use payout_tx::*;
+pub use refund_ledger::{RefundLedger, RefundLedgerError, RefundMode};
 pub use sim::simulate_order;
 
 #[derive(Debug, Clone)]
@@ -67,17 +81,82 @@ pub struct BlockBuildingContext {
     /// None: coinbase = attributes.suggested_fee_recipient. No payoffs allowed.
     /// Some(signer): coinbase = signer.
     pub builder_signer: Option<Signer>,
+    /// How we pay the proposer when builder_signer is the coinbase.
+    pub payout_mode: PayoutMode,
     pub blocklist: HashSet<Address>,
     pub extra_data: Vec<u8>,
     /// Excess blob gas calculated from the parent block header
     pub excess_blob_gas: Option<u64>,
     /// Version of the EVM that we are going to use
     pub spec_id: SpecId,
+    /// If true txs to non existing accounts (or creating contracts) are rejected to avoid state growth.
+    /// Txs signed by builder_signer (payouts) are not affected.
+    pub reject_new_account_recipients: bool,
+    pub refund_mode: RefundMode,
+    /// If true mempool txs that revert (status 0) are not included even if they could pay for their gas.
+    pub exclude_reverting_txs: bool,
+    /// If set txs whose logs take more than this many bytes (see [`logs_size`]) are rejected, some relays refuse
+    /// blocks with oversized receipts.
+    pub max_tx_logs_size: Option<usize>,
+    /// Copied to the trace of the blocks built with this context.
+    pub builder_identity: BuilderIdentity,
+    /// Values the tokens paid to the coinbase for the order score (see [`SimValue::token_profit`]).
+    pub token_valuation: TokenValuation,
+    pub parent_source: ParentSource,
+}
+
+/// Where the parent of the block we build comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParentSource {
+    /// Live slot: payload attributes from the CL (received at received_at) and the parent header from our node.
+    PayloadAttributes {
+        parent_timestamp: u64,
+        received_at: OffsetDateTime,
+    },
+    /// Rebuilding a block from the past (backtest, replays, benchmarks), the parent age means nothing.
+    Historical,
 }
 
 impl BlockBuildingContext {
+    /// The options not taken here (payout_mode, refund_mode, builder_identity...) start with their defaults, change them
+    /// with the modify_* methods.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        block_env: BlockEnv,
+        initialized_cfg: CfgEnvWithHandlerCfg,
+        attributes: EthPayloadBuilderAttributes,
+        chain_spec: Arc<ChainSpec>,
+        builder_signer: Option<Signer>,
+        blocklist: HashSet<Address>,
+        extra_data: Vec<u8>,
+        excess_blob_gas: Option<u64>,
+        spec_id: SpecId,
+        parent_source: ParentSource,
+    ) -> BlockBuildingContext {
+        BlockBuildingContext {
+            block_env,
+            initialized_cfg,
+            attributes,
+            chain_spec,
+            builder_signer,
+            payout_mode: PayoutMode::default(),
+            blocklist,
+            extra_data,
+            excess_blob_gas,
+            spec_id,
+            reject_new_account_recipients: false,
+            refund_mode: RefundMode::default(),
+            exclude_reverting_txs: false,
+            max_tx_logs_size: None,
+            builder_identity: BuilderIdentity::default(),
+            token_valuation: TokenValuation::default(),
+            parent_source,
+        }
+    }
+
     #[allow(clippy::too_many_arguments)]
     /// spec_id None: we use the proper SpecId for the block timestamp.
+    /// received_at: when we got the attributes from the CL (see parent_age).
     pub fn from_attributes(
         attributes: PayloadAttributesEvent,
         parent: &Header,
@@ -87,6 +166,7 @@ impl BlockBuildingContext {
         prefer_gas_limit: Option<u64>,
         extra_data: Vec<u8>,
         spec_id: Option<SpecId>,
+        received_at: OffsetDateTime,
     ) -> BlockBuildingContext {
         let attributes = EthPayloadBuilderAttributes::try_new(
             attributes.data.parent_block_hash,
@@ -128,17 +208,83 @@ impl BlockBuildingContext {
             );
             revm_spec(&chain_spec, head)
         });
-        BlockBuildingContext {
+        BlockBuildingContext::new(
             block_env,
             initialized_cfg,
             attributes,
             chain_spec,
-            builder_signer: Some(signer),
+            Some(signer),
             blocklist,
             extra_data,
             excess_blob_gas,
             spec_id,
-        }
+            ParentSource::PayloadAttributes {
+                parent_timestamp: parent.timestamp,
+                received_at,
+            },
+        )
+    }
+
+    /// Context to re-execute a block from our db exactly as it landed: coinbase = beneficiary and no builder_signer
+    /// (no payouts).
+    /// spec_id None: we use the SpecId for the block (same caveat around forks as from_block_data).
+    pub fn from_landed_block(
+        block: &Block,
+        chain_spec: Arc<ChainSpec>,
+        spec_id: Option<SpecId>,
+    ) -> BlockBuildingContext {
+        let header = &block.header;
+        let blob_excess_gas_and_price =
+            if chain_spec.is_cancun_active_at_timestamp(header.timestamp) {
+                Some(BlobExcessGasAndPrice::new(
+                    header.excess_blob_gas.unwrap_or_default(),
+                ))
+            } else {
+                None
+            };
+        let block_env = BlockEnv {
+            number: U256::from(header.number),
+            coinbase: header.beneficiary,
+            timestamp: U256::from(header.timestamp),
+            difficulty: header.difficulty,
+            prevrandao: Some(header.mix_hash),
+            basefee: U256::from(header.base_fee_per_gas.unwrap_or_default()),
+            gas_limit: U256::from(header.gas_limit),
+            blob_excess_gas_and_price,
+        };
+        let attributes = EthPayloadBuilderAttributes {
+            id: PayloadId::new([0u8; 8]),
+            parent: header.parent_hash,
+            timestamp: header.timestamp,
+            suggested_fee_recipient: header.beneficiary,
+            prev_randao: header.mix_hash,
+            withdrawals: block.withdrawals.clone().unwrap_or_default(),
+            parent_beacon_block_root: header.parent_beacon_block_root,
+        };
+        let spec_id = spec_id.unwrap_or_else(|| {
+            revm_spec(
+                &chain_spec,
+                Head::new(
+                    header.number,
+                    header.parent_hash,
+                    header.difficulty,
+                    U256::ZERO,
+                    header.timestamp,
+                ),
+            )
+        });
+        BlockBuildingContext::new(
+            block_env,
+            default_cfg_env(&chain_spec, header.timestamp),
+            attributes,
+            chain_spec,
+            None,
+            HashSet::default(),
+            header.extra_data.to_vec(),
+            header.excess_blob_gas,
+            spec_id,
+            ParentSource::Historical,
+        )
     }
 
     /// `from_block_data` is used to create `BlockBuildingContext` from onchain block for backtest purposes
@@ -221,36 +367,160 @@ impl BlockBuildingContext {
                 ),
             )
         });
-        BlockBuildingContext {
+        BlockBuildingContext::new(
             block_env,
-            initialized_cfg: cfg,
+            cfg,
             attributes,
             chain_spec,
-            builder_signer: Some(builder_signer),
+            Some(builder_signer),
             blocklist,
-            extra_data: Vec::new(),
-            excess_blob_gas: block_data
+            Vec::new(),
+            block_data
                 .onchain_block
                 .header
                 .excess_blob_gas
                 .map(|b| b as u64),
             spec_id,
-        }
+            ParentSource::Historical,
+        )
     }
 
-    pub fn modify_use_suggested_fee_recipient_as_coinbase(&mut self) {
+    fn modify_use_suggested_fee_recipient_as_coinbase(&mut self) {
         self.builder_signer = None;
         self.block_env.coinbase = self.attributes.suggested_fee_recipient;
     }
 
+    /// Slot contexts (from_attributes) start as CoinbaseMode::BuilderSigner, call on a copy of the slot ctx for every
+    /// build so a builder can use both modes on the same slot.
+    /// BuilderSigner sets the coinbase back to the signer, it fails on a ctx already switched to SuggestedFeeRecipient
+    /// since the signer is gone.
+    pub fn modify_coinbase_mode(
+        &mut self,
+        coinbase_mode: CoinbaseMode,
+    ) -> Result<(), NoBuilderSignerErr> {
+        match coinbase_mode {
+            CoinbaseMode::SuggestedFeeRecipient => {
+                self.modify_use_suggested_fee_recipient_as_coinbase();
+            }
+            CoinbaseMode::BuilderSigner { payout } => {
+                let builder_signer = self.builder_signer.as_ref().ok_or(NoBuilderSignerErr)?;
+                self.block_env.coinbase = builder_signer.address;
+                self.payout_mode = payout;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn coinbase_mode(&self) -> CoinbaseMode {
+        if self.builder_signer.is_some() {
+            CoinbaseMode::BuilderSigner {
+                payout: self.payout_mode,
+            }
+        } else {
+            CoinbaseMode::SuggestedFeeRecipient
+        }
+    }
+
+    pub fn modify_reject_new_account_recipients(&mut self, reject: bool) {
+        self.reject_new_account_recipients = reject;
+    }
+
+    pub fn modify_refund_mode(&mut self, refund_mode: RefundMode) {
+        self.refund_mode = refund_mode;
+    }
+
+    pub fn modify_exclude_reverting_txs(&mut self, exclude: bool) {
+        self.exclude_reverting_txs = exclude;
+    }
+
+    pub fn modify_max_tx_logs_size(&mut self, max_tx_logs_size: Option<usize>) {
+        self.max_tx_logs_size = max_tx_logs_size;
+    }
+
+    pub fn modify_builder_identity(&mut self, builder_identity: BuilderIdentity) {
+        self.builder_identity = builder_identity;
+    }
+
+    pub fn modify_token_valuation(&mut self, token_valuation: TokenValuation) {
+        self.token_valuation = token_valuation;
+    }
+
+    /// Addresses whose token income counts for the order score.
+    /// CoinbaseMode::SuggestedFeeRecipient: none, the coinbase is the proposer so its income is not ours.
+    pub fn token_profit_recipients(&self) -> Vec<Address> {
+        match self.coinbase_mode() {
+            // coinbase = builder signer
+            CoinbaseMode::BuilderSigner { .. } => vec![self.block_env.coinbase],
+            CoinbaseMode::SuggestedFeeRecipient => Vec::new(),
+        }
+    }
+
+    /// Overrides the withdrawals from the payload attributes, they are processed when the block is assembled.
+    /// The block won't match what the CL expects, only for testing.
+    pub fn modify_withdrawals(&mut self, withdrawals: Vec<Withdrawal>) {
+        self.attributes.withdrawals = Withdrawals::new(withdrawals);
+    }
+
+    /// Overrides the block timestamp (used on the evm block env and on the sealed header).
+    pub fn modify_timestamp(&mut self, timestamp: u64) {
+        self.attributes.timestamp = timestamp;
+        self.block_env.timestamp = U256::from(timestamp);
+    }
+
     pub fn timestamp(&self) -> OffsetDateTime {
         OffsetDateTime::from_unix_timestamp(self.attributes.timestamp as i64)
             .expect("Payload attributes timestamp")
     }
 
+    /// Time between the parent block timestamp and our receipt of the attributes, usually less than a slot.
+    /// Way more means the CL is asking us to build on a stale head (eg: CL hiccups) and the block will probably be
+    /// rejected as not building on the canonical head.
+    /// None for ParentSource::Historical or a parent timestamp out of range (garbage parent header, we can't tell its age).
+    pub fn parent_age(&self) -> Option<Duration> {
+        match self.parent_source {
+            ParentSource::PayloadAttributes {
+                parent_timestamp,
+                received_at,
+            } => {
+                let parent_time = i64::try_from(parent_timestamp)
+                    .ok()
+                    .and_then(|timestamp| OffsetDateTime::from_unix_timestamp(timestamp).ok())?;
+                Some((received_at - parent_time).try_into().unwrap_or_default())
+            }
+            ParentSource::Historical => None,
+        }
+    }
+
+    pub fn parent_beacon_block_root(&self) -> Option<B256> {
+        self.attributes.parent_beacon_block_root
+    }
+
     pub fn block(&self) -> u64 {
         self.block_env.number.to()
     }
+
+    /// Read only copy of the execution environment so tooling can check what a block was built on.
+    pub fn env_snapshot(&self) -> BlockEnvSnapshot {
+        BlockEnvSnapshot {
+            coinbase: self.block_env.coinbase,
+            base_fee: self.block_env.basefee.to(),
+            gas_limit: self.block_env.gas_limit.to(),
+            number: self.block(),
+            timestamp: self.block_env.timestamp.to(),
+            chain_id: self.chain_spec.chain.id(),
+        }
+    }
+}
+
+/// See [`BlockBuildingContext::env_snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BlockEnvSnapshot {
+    pub coinbase: Address,
+    pub base_fee: u64,
+    pub gas_limit: u64,
+    pub number: u64,
+    pub timestamp: u64,
+    pub chain_id: u64,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -261,11 +531,12 @@ pub struct BlockBuildingConfig {
     pub remove_failed: bool,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Sorting {
     /// Sorts the SimulatedOrders by its effective gas price. This not only includes the explicit gas price set in the tx but also the direct coinbase payments
     /// so we compute it as (coinbase balance delta after executing the order) / (gas used)
+    #[default]
     MevGasPrice,
     /// Sorts the SimulatedOrders by its absolute profit which is computed as the coinbase balance delta after executing the order
     MaxProfit,
@@ -275,7 +546,7 @@ impl Sorting {
     pub fn sorting_value(&self, sim_value: &SimValue) -> U256 {
         match self {
             Sorting::MevGasPrice => sim_value.mev_gas_price,
-            Sorting::MaxProfit => sim_value.coinbase_profit,
+            Sorting::MaxProfit => sim_value.score_profit(),
         }
     }
 }
@@ -310,6 +581,10 @@ pub struct PartialBlock<Tracer: SimulationTracer> {
     pub coinbase_profit: U256,
     pub executed_tx: Vec<TransactionSignedEcRecoveredWithBlobs>,
     pub receipts: Vec<Receipt>,
+    /// Refunds promised by the included sbundles.
+    pub refund_ledger: RefundLedger,
+    /// Set by insert_proposer_payout_tx.
+    pub proposer_payout_tx_hash: Option<B256>,
     pub tracer: Tracer,
 }
 
@@ -326,6 +601,8 @@ pub struct ExecutionResult {
     pub receipts: Vec<Receipt>,
     pub nonces_updated: Vec<(Address, u64)>,
     pub paid_kickbacks: Vec<(Address, U256)>,
+    /// Filled by [`BuiltBlockTrace::add_included_order`].
+    pub log_summary: Option<OrderLogSummary>,
 }
 
 #[derive(Error, Debug)]
@@ -342,6 +619,10 @@ pub enum InsertPayoutTxErr {
     TxErr(#[from] TransactionErr),
     #[error("Payout without signer")]
     NoSigner,
+    #[error("Failed to estimate refund gas: {0}")]
+    EstimateRefundGas(#[from] EstimatePayoutGasErr),
+    #[error("Refund ledger: {0}")]
+    RefundLedger(#[from] RefundLedgerError),
 }
 
 #[derive(Error, Debug)]
@@ -390,6 +671,74 @@ pub struct FinalizeResult {
     pub txs_blob_sidecars: Vec<Arc<BlobTransactionSidecar>>,
 }
 
+pub struct AssembleResult {
+    pub assembled_block: AssembledBlock,
+    pub cached_reads: CachedReads,
+}
+
+/// Block with all the roots computed and the header sealed but without the payload.
+/// Enough to bid (header only), call seal_payload to get the full block.
+#[derive(Debug, Clone)]
+pub struct AssembledBlock {
+    pub sealed_header: SealedHeader,
+    transactions: Vec<TransactionSigned>,
+    withdrawals: Option<Withdrawals>,
+    // sidecars for all txs in transactions
+    txs_blob_sidecars: Vec<Arc<BlobTransactionSidecar>>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("CoinbaseMode::BuilderSigner on a ctx without builder_signer")]
+pub struct NoBuilderSignerErr;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("Header basefee {header_basefee:?} does not match the one computed from the parent {expected:?}")]
+pub struct BasefeeMismatch {
+    pub header_basefee: Option<u64>,
+    pub expected: Option<u64>,
+}
+
+/// The header basefee must be calc_next_block_base_fee of the parent, any other basefee is an invalid block.
+/// Catches a wrong basefee in the ctx (eg: built from a wrong parent) and bugs in the finalize path.
+pub fn verify_header_basefee(
+    header: &Header,
+    parent: &Header,
+    chain_spec: &ChainSpec,
+) -> Result<(), BasefeeMismatch> {
+    let expected = parent.next_block_base_fee(chain_spec.base_fee_params(header.timestamp));
+    if header.base_fee_per_gas.is_some() && header.base_fee_per_gas == expected {
+        Ok(())
+    } else {
+        Err(BasefeeMismatch {
+            header_basefee: header.base_fee_per_gas,
+            expected,
+        })
+    }
+}
+
+impl AssembledBlock {
+    pub fn tx_count(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn blob_sidecar_count(&self) -> usize {
+        self.txs_blob_sidecars.len()
+    }
+
+    /// Returns (sealed_block, txs_blob_sidecars). The header is not recomputed.
+    pub fn seal_payload(self) -> (SealedBlock, Vec<Arc<BlobTransactionSidecar>>) {
+        let sealed_block = SealedBlock::new(
+            self.sealed_header,
+            BlockBody {
+                transactions: self.transactions,
+                ommers: vec![],
+                withdrawals: self.withdrawals,
+            },
+        );
+        (sealed_block, self.txs_blob_sidecars)
+    }
+}
+
 impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
     pub fn with_tracer<NewTracer: SimulationTracer>(
         self,
@@ -404,6 +753,8 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
             coinbase_profit: self.coinbase_profit,
             executed_tx: self.executed_tx,
             receipts: self.receipts,
+            refund_ledger: self.refund_ledger,
+            proposer_payout_tx_hash: self.proposer_payout_tx_hash,
             tracer,
         }
     }
@@ -429,13 +780,27 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
             ))));
         }
 
+        if let Some(max_block_position) = order.order.max_block_position() {
+            let position = self.executed_tx.len();
+            if position > max_block_position {
+                return Ok(Err(ExecutionError::OrderError(OrderErr::Bundle(
+                    BundleErr::BlockPositionTooHigh {
+                        position,
+                        max_block_position,
+                    },
+                ))));
+            }
+        }
+
+        state.prefetch_order(order, ctx)?;
+
         let mut fork = PartialBlockFork::new(state).with_tracer(&mut self.tracer);
         let rollback = fork.rollback_point();
         let exec_result = fork.commit_order(
             &order.order,
             ctx,
             self.gas_used,
-            self.gas_reserved,
+            self.gas_reserved + self.refund_ledger.reserved_gas(),
             self.blob_gas_used,
             self.discard_txs,
         )?;
@@ -450,8 +815,19 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
             ok_result.coinbase_profit,
             ok_result.gas_used,
             ok_result.blob_gas_used,
-            ok_result.paid_kickbacks.clone(),
-        );
+            ok_result
+                .paid_kickbacks
+                .iter()
+                .cloned()
+                .chain(
+                    ok_result
+                        .deferred_refunds
+                        .iter()
+                        .map(|(to, payout)| (*to, payout.tx_value)),
+                )
+                .collect(),
+        )
+        .with_token_profit(ok_result.token_profit);
         if let Some(enforce_sorting) = self.enforce_sorting {
             match enforce_inplace_sim_result(enforce_sorting, &order.sim_value, &inplace_sim_result)
             {
@@ -468,6 +844,18 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
         self.coinbase_profit += ok_result.coinbase_profit;
         self.executed_tx.extend(ok_result.txs.clone());
         self.receipts.extend(ok_result.receipts.clone());
+        for (to, value) in &ok_result.paid_kickbacks {
+            self.refund_ledger.add_paid(order.id(), *to, *value);
+        }
+        for (to, payout) in &ok_result.deferred_refunds {
+            self.refund_ledger.add_deferred(
+                order.id(),
+                *to,
+                payout.tx_value,
+                payout.gas_limit,
+                payout.total_refundable_value,
+            );
+        }
         Ok(Ok(ExecutionResult {
             coinbase_profit: ok_result.coinbase_profit,
             inplace_sim: inplace_sim_result,
@@ -478,9 +866,83 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
             receipts: ok_result.receipts,
             nonces_updated: ok_result.nonces_updated,
             paid_kickbacks: ok_result.paid_kickbacks,
+            log_summary: None,
         }))
     }
 
+    /// RefundMode::Aggregated: inserts a refund tx per recipient for the refunds accumulated during the fill.
+    /// Failed refund txs (or recipients that can't take a transfer anymore) are not included, verify_refunds will report them.
+    /// The gas is estimated again at this point of the block so it can be over what the orders reserved, the extra cost
+    /// is paid from the block profit.
+    pub fn pay_deferred_refunds(
+        &mut self,
+        ctx: &BlockBuildingContext,
+        state: &mut BlockState,
+    ) -> Result<(), InsertPayoutTxErr> {
+        for pending in self.refund_ledger.take_pending() {
+            let builder_signer = ctx
+                .builder_signer
+                .as_ref()
+                .ok_or(InsertPayoutTxErr::NoSigner)?;
+            let gas_limit =
+                match estimate_payout_gas_limit(pending.recipient, ctx, state, self.gas_used) {
+                    Ok(gas_limit) => gas_limit,
+                    Err(EstimatePayoutGasErr::FailedToEstimate) => continue,
+                    Err(err) => return Err(err.into()),
+                };
+            let nonce = state
+                .nonce(builder_signer.address)
+                .map_err(CriticalCommitOrderError::Reth)?;
+            let tx = create_payout_tx(
+                ctx.chain_spec.as_ref(),
+                ctx.block_env.basefee,
+                builder_signer,
+                nonce,
+                pending.recipient,
+                gas_limit,
+                pending.value.to(),
+            )?;
+            // payout tx has no blobs so it's safe to unwrap
+            let tx = TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap();
+            let coinbase_balance_before = state
+                .balance(ctx.block_env.coinbase)
+                .map_err(CriticalCommitOrderError::Reth)?;
+            let mut fork = PartialBlockFork::new(state).with_tracer(&mut self.tracer);
+            let ok_result = match fork.commit_tx(
+                &tx,
+                ctx,
+                self.gas_used,
+                self.gas_reserved,
+                self.blob_gas_used,
+            )? {
+                Ok(ok_result) => ok_result,
+                Err(_) => continue,
+            };
+            let coinbase_balance_after = state
+                .balance(ctx.block_env.coinbase)
+                .map_err(CriticalCommitOrderError::Reth)?;
+            // orders were charged value + gas_limit * basefee for each refund, give back what we saved
+            // or take what the re-estimated gas cost on top of it
+            let cost = coinbase_balance_before.saturating_sub(coinbase_balance_after);
+            self.coinbase_profit =
+                (self.coinbase_profit + pending.reserved_cost).saturating_sub(cost);
+            self.gas_used += ok_result.gas_used;
+            self.blob_gas_used += ok_result.blob_gas_used;
+            self.executed_tx.push(ok_result.tx);
+            self.receipts.push(ok_result.receipt);
+        }
+        Ok(())
+    }
+
+    /// Checks that every refund obligation of the included orders was paid.
+    pub fn verify_refunds(&self, ctx: &BlockBuildingContext) -> Result<(), RefundLedgerError> {
+        self.refund_ledger.verify(
+            &self.executed_tx,
+            &self.receipts,
+            ctx.builder_signer.as_ref().map(|s| s.address),
+        )
+    }
+
     /// Gets the block profit excluding the expected payout base gas that  we'll pay.
     pub fn get_proposer_payout_tx_value(
         &self,
@@ -529,35 +991,55 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
 
         self.gas_used += ok_result.gas_used;
         self.blob_gas_used += ok_result.blob_gas_used;
+        self.proposer_payout_tx_hash = Some(ok_result.tx.hash());
         self.executed_tx.push(ok_result.tx);
         self.receipts.push(ok_result.receipt);
 
         Ok(())
     }
 
+    /// assemble + seal_payload.
     #[allow(clippy::too_many_arguments)]
     pub fn finalize<DB: reth_db::database::Database + Clone + 'static>(
         self,
-        mut state: BlockState,
+        state: BlockState,
         ctx: &BlockBuildingContext,
         provider_factory: ProviderFactory<DB>,
         root_hash_mode: RootHashMode,
         root_hash_task_pool: BlockingTaskPool,
     ) -> eyre::Result<FinalizeResult> {
-        let (withdrawals_root, withdrawals) = {
-            let mut db = state.new_db_ref();
-            let WithdrawalsOutcome {
-                withdrawals_root,
-                withdrawals,
-            } = commit_withdrawals(
-                db.as_mut(),
-                &ctx.chain_spec,
-                ctx.attributes.timestamp,
-                ctx.attributes.withdrawals.clone(),
-            )?;
-            db.as_mut().merge_transitions(PlainState);
-            (withdrawals_root, withdrawals)
-        };
+        let AssembleResult {
+            assembled_block,
+            cached_reads,
+        } = self.assemble(
+            state,
+            ctx,
+            provider_factory,
+            root_hash_mode,
+            root_hash_task_pool,
+        )?;
+        let (sealed_block, txs_blob_sidecars) = assembled_block.seal_payload();
+        Ok(FinalizeResult {
+            sealed_block,
+            cached_reads,
+            txs_blob_sidecars,
+        })
+    }
+
+    /// Post block processing (withdrawals) and all the roots -> sealed header.
+    /// The payload is built later by AssembledBlock::seal_payload.
+    pub fn assemble<DB: reth_db::database::Database + Clone + 'static>(
+        self,
+        mut state: BlockState,
+        ctx: &BlockBuildingContext,
+        provider_factory: ProviderFactory<DB>,
+        root_hash_mode: RootHashMode,
+        root_hash_task_pool: BlockingTaskPool,
+    ) -> eyre::Result<AssembleResult> {
+        let WithdrawalsOutcome {
+            withdrawals_root,
+            withdrawals,
+        } = apply_withdrawals(&mut state, ctx)?;
 
         let (cached_reads, bundle) = state.into_parts();
 
@@ -641,17 +1123,14 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
             excess_blob_gas,
         };
 
-        let block = Block {
-            header,
-            body: self.executed_tx.into_iter().map(|t| t.tx.into()).collect(),
-            ommers: vec![],
-            withdrawals,
-        };
-
-        Ok(FinalizeResult {
-            sealed_block: block.seal_slow(),
+        Ok(AssembleResult {
+            assembled_block: AssembledBlock {
+                sealed_header: header.seal_slow(),
+                transactions: self.executed_tx.into_iter().map(|t| t.tx.into()).collect(),
+                withdrawals,
+                txs_blob_sidecars,
+            },
             cached_reads,
-            txs_blob_sidecars,
         })
     }
 
@@ -660,17 +1139,59 @@ impl<Tracer: SimulationTracer> PartialBlock<Tracer> {
         ctx: &BlockBuildingContext,
         state: &mut BlockState,
     ) -> eyre::Result<()> {
-        let mut db = state.new_db_ref();
-        pre_block_beacon_root_contract_call(
-            db.as_mut(),
-            &ctx.chain_spec,
-            ctx.block_env.number.to(),
-            &ctx.initialized_cfg,
-            &ctx.block_env,
-            &ctx.attributes,
-        )?;
-        db.as_mut().merge_transitions(BundleRetention::Reverts);
-        Ok(())
+        pre_block_call(ctx, state)
+    }
+}
+
+/// EIP-4788 beacon roots contract, called by pre_block_call.
+pub const BEACON_ROOTS_ADDRESS: Address = address!("000F3df6D732807Ef1319fB7B8bB8522d0Beac02");
+
+/// EIP-4788 beacon roots contract runtime code (as deployed on mainnet).
+pub const BEACON_ROOTS_CODE: [u8; 97] = hex!("3373fffffffffffffffffffffffffffffffffffffffe14604d57602036146024575f5ffd5b5f35801560495762001fff810690815414603c575f5ffd5b62001fff01545f5260205ff35b5f5ffd5b62001fff42064281555f359062001fff015500");
+
+/// EIP-4788 beacon root system call (no-op before Cancun).
+/// Every execution of the block (building, simulation, gas estimation) must start with it or the orders reading the
+/// beacon roots contract see a different state than the final block.
+pub fn pre_block_call(ctx: &BlockBuildingContext, state: &mut BlockState) -> eyre::Result<()> {
+    let mut db = state.new_db_ref();
+    pre_block_beacon_root_contract_call(
+        db.as_mut(),
+        &ctx.chain_spec,
+        ctx.block_env.number.to(),
+        &ctx.initialized_cfg,
+        &ctx.block_env,
+        &ctx.attributes,
+    )?;
+    db.as_mut().merge_transitions(BundleRetention::Reverts);
+    Ok(())
+}
+
+/// Snapshot of a PartialBlock and its BlockState, see [`PartialBlock::checkpoint`].
+#[derive(Debug, Clone)]
+pub struct PartialBlockCheckpoint<Tracer: SimulationTracer> {
+    partial_block: PartialBlock<Tracer>,
+    bundle_state: BundleState,
+}
+
+impl<Tracer: SimulationTracer + Clone> PartialBlock<Tracer> {
+    /// Snapshots the block built so far so we can try different fills on top of it and later go back with [`PartialBlock::rollback_to`].
+    pub fn checkpoint(&self, state: &BlockState) -> PartialBlockCheckpoint<Tracer> {
+        PartialBlockCheckpoint {
+            partial_block: self.clone(),
+            bundle_state: state.bundle_state().clone(),
+        }
+    }
+
+    /// Discards everything committed after checkpoint was taken.
+    /// The cached reads of state are kept since they only contain parent block state.
+    /// A checkpoint can be used for any number of rollbacks.
+    pub fn rollback_to(
+        &mut self,
+        checkpoint: &PartialBlockCheckpoint<Tracer>,
+        state: &mut BlockState,
+    ) {
+        *self = checkpoint.partial_block.clone();
+        state.set_bundle_state(checkpoint.bundle_state.clone());
     }
 }
 
@@ -685,6 +1206,8 @@ impl PartialBlock<()> {
             coinbase_profit: U256::ZERO,
             executed_tx: Vec::new(),
             receipts: Vec::new(),
+            refund_ledger: RefundLedger::default(),
+            proposer_payout_tx_hash: None,
             tracer: (),
         }
     }
@@ -724,9 +1247,186 @@ fn enforce_inplace_sim_result(
     }
 }
 
+/// Credits the ctx withdrawals (post Shanghai) to the state.
+fn apply_withdrawals(
+    state: &mut BlockState,
+    ctx: &BlockBuildingContext,
+) -> eyre::Result<WithdrawalsOutcome> {
+    let mut db = state.new_db_ref();
+    let outcome = commit_withdrawals(
+        db.as_mut(),
+        &ctx.chain_spec,
+        ctx.attributes.timestamp,
+        ctx.attributes.withdrawals.clone(),
+    )?;
+    db.as_mut().merge_transitions(PlainState);
+    Ok(outcome)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::building::testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState};
+    use reth::providers::HeaderProvider;
+
+    #[test]
+    fn test_apply_withdrawals() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let mut ctx = test_chain.block_building_context().clone();
+        let recipient = test_chain.named_address(NamedAddr::User(0))?;
+        let fresh_account = Address::repeat_byte(0x42);
+        ctx.modify_withdrawals(vec![
+            Withdrawal {
+                index: 0,
+                validator_index: 1,
+                address: recipient,
+                amount: 1_000,
+            },
+            Withdrawal {
+                index: 1,
+                validator_index: 2,
+                address: fresh_account,
+                amount: 2_000,
+            },
+        ]);
+        let state_provider = test_chain.provider_factory().latest()?;
+        let mut state = BlockState::new(&state_provider);
+        let recipient_balance = state.balance(recipient)?;
+
+        let outcome = apply_withdrawals(&mut state, &ctx)?;
+        assert_eq!(
+            outcome.withdrawals,
+            Some(ctx.attributes.withdrawals.clone())
+        );
+        assert_eq!(
+            outcome.withdrawals_root,
+            Some(proofs::calculate_withdrawals_root(
+                &ctx.attributes.withdrawals
+            ))
+        );
+        // amounts are in gwei
+        assert_eq!(
+            state.balance(recipient)?,
+            recipient_balance + U256::from(1_000_000_000_000u64)
+        );
+        assert_eq!(
+            state.balance(fresh_account)?,
+            U256::from(2_000_000_000_000u64)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_header_basefee() -> eyre::Result<()> {
+        let mut test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        // the genesis parent has no basefee, build on a parent header that has one
+        test_chain.advance_block()?;
+        let ctx = test_chain.block_building_context();
+        let parent = test_chain
+            .provider_factory()
+            .header(&ctx.attributes.parent)?
+            .expect("parent header");
+        // the test parent is exactly at the gas target so the basefee does not move
+        let basefee = parent.base_fee_per_gas.expect("parent basefee");
+        assert_eq!(ctx.block_env.basefee, U256::from(basefee));
+        let header = |base_fee_per_gas| Header {
+            base_fee_per_gas,
+            timestamp: ctx.attributes.timestamp,
+            ..Default::default()
+        };
+        assert_eq!(
+            verify_header_basefee(&header(Some(basefee)), &parent, &ctx.chain_spec),
+            Ok(())
+        );
+        assert_eq!(
+            verify_header_basefee(&header(Some(basefee + 1)), &parent, &ctx.chain_spec),
+            Err(BasefeeMismatch {
+                header_basefee: Some(basefee + 1),
+                expected: Some(basefee),
+            })
+        );
+        assert!(verify_header_basefee(&header(None), &parent, &ctx.chain_spec).is_err());
+
+        // a parent with gas_used over the target raises the basefee
+        let full_parent = Header {
+            gas_used: parent.gas_limit,
+            base_fee_per_gas: Some(1_000_000_000),
+            ..parent.clone()
+        };
+        assert_eq!(
+            verify_header_basefee(&header(Some(1_125_000_000)), &full_parent, &ctx.chain_spec),
+            Ok(())
+        );
+        assert!(
+            verify_header_basefee(&header(Some(1_000_000_000)), &full_parent, &ctx.chain_spec)
+                .is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_modify_coinbase_mode() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let slot_ctx = test_chain.block_building_context();
+        let signer = slot_ctx.builder_signer.clone().expect("builder signer");
+        assert_eq!(slot_ctx.coinbase_mode(), CoinbaseMode::default());
+
+        let builder_signer = CoinbaseMode::BuilderSigner {
+            payout: PayoutMode::FixedGasLimit(50_000),
+        };
+        let mut ctx = slot_ctx.clone();
+        ctx.modify_coinbase_mode(builder_signer)?;
+        assert_eq!(ctx.block_env.coinbase, signer.address);
+        assert_eq!(ctx.coinbase_mode(), builder_signer);
+
+        ctx.modify_coinbase_mode(CoinbaseMode::SuggestedFeeRecipient)?;
+        assert_eq!(
+            ctx.block_env.coinbase,
+            ctx.attributes.suggested_fee_recipient
+        );
+        assert!(ctx.builder_signer.is_none());
+        assert_eq!(ctx.coinbase_mode(), CoinbaseMode::SuggestedFeeRecipient);
+
+        // the signer is gone, the ctx is left as it was
+        assert_eq!(
+            ctx.modify_coinbase_mode(builder_signer),
+            Err(NoBuilderSignerErr)
+        );
+        assert_eq!(
+            ctx.block_env.coinbase,
+            ctx.attributes.suggested_fee_recipient
+        );
+        assert_eq!(ctx.coinbase_mode(), CoinbaseMode::SuggestedFeeRecipient);
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_snapshot() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(11).timestamp(1000))?;
+        let ctx = test_chain.block_building_context();
+        let snapshot = ctx.env_snapshot();
+        assert_eq!(
+            snapshot,
+            BlockEnvSnapshot {
+                coinbase: ctx.block_env.coinbase,
+                base_fee: ctx.block_env.basefee.to(),
+                gas_limit: ctx.block_env.gas_limit.to(),
+                number: 11,
+                timestamp: 1000,
+                chain_id: ctx.chain_spec.chain.id(),
+            }
+        );
+        assert_eq!(
+            snapshot.coinbase,
+            ctx.builder_signer.as_ref().unwrap().address
+        );
+        assert_eq!(snapshot.chain_id, ctx.initialized_cfg.cfg_env.chain_id);
+
+        let json = serde_json::to_value(snapshot)?;
+        assert_eq!(json["number"], 11);
+        assert_eq!(json["timestamp"], 1000);
+        Ok(())
+    }
 
     #[test]
     fn test_enforce_inplace_sim_result_max_profit() {
@@ -1,11 +1,13 @@
 use super::{
     create_payout_tx, tracers::SimulationTracer, BlockBuildingContext, EstimatePayoutGasErr,
+    RefundMode,
 };
 use crate::{
     building::estimate_payout_gas_limit,
     primitives::{
+        static_limits::{check_tx_static_limits, StaticLimitError},
         Bundle, Order, OrderId, RefundConfig, ShareBundle, ShareBundleBody, ShareBundleInner,
-        TransactionSignedEcRecoveredWithBlobs,
+        SimulatedOrder, TransactionSignedEcRecoveredWithBlobs,
     },
     utils::get_percent,
 };
@@ -26,19 +28,42 @@ use reth_payload_builder::database::CachedReads;
 use revm::{
     db::{states::bundle_state::BundleRetention, BundleState},
     inspector_handle_register,
-    primitives::{db::WrapDatabaseRef, EVMError, Env, ExecutionResult, InvalidTransaction},
-    Database, DatabaseCommit, State,
+    primitives::{
+        db::WrapDatabaseRef, EVMError, Env, ExecutionResult, InvalidTransaction, Log,
+        ResultAndState,
+    },
+    Database, DatabaseCommit, DatabaseRef, State,
 };
 
-use crate::building::evm_inspector::{RBuilderEVMInspector, UsedStateTrace};
-use std::collections::HashMap;
+use crate::building::{
+    batched_reads::{BatchStateReader, BatchedStateDBRef, OrderAccessList},
+    cached_reads_verifier::VerifyCachedReadsDBRef,
+    evm_inspector::{RBuilderEVMInspector, UsedStateTrace, BLOCK_HASH_HISTORY},
+    provider_io::{InstrumentedDBRef, ProviderIoRecorder, ProviderIoStats},
+};
+use std::{
+    cell::Cell,
+    collections::{BTreeMap, HashMap},
+};
 use thiserror::Error;
+use tracing::warn;
 
 #[derive(Clone)]
 pub struct BlockState<'a> {
     provider: &'a StateProviderBox,
     cached_reads: CachedReads,
     bundle_state: Option<BundleState>,
+    /// See [`VerifyCachedReadsDBRef`], 0 disables the verification.
+    verify_cached_reads_bps: u32,
+    cached_reads_divergences: Cell<usize>,
+    /// See [`BlockState::prefetch_order`], None disables batched reads.
+    batch_reader: Option<&'a dyn BatchStateReader>,
+    /// Lookups that reached the provider from new_db_ref.
+    provider_io: ProviderIoRecorder,
+    /// Lookups that reached the provider from prefetch_order.
+    batched_provider_io: ProviderIoRecorder,
+    /// See [`BlockState::with_commit_verification`].
+    verify_commits: bool,
 }
 
 impl<'a> BlockState<'a> {
@@ -47,9 +72,95 @@ impl<'a> BlockState<'a> {
             provider,
             cached_reads: CachedReads::default(),
             bundle_state: Some(BundleState::default()),
+            verify_cached_reads_bps: 0,
+            cached_reads_divergences: Cell::new(0),
+            batch_reader: None,
+            provider_io: ProviderIoRecorder::default(),
+            batched_provider_io: ProviderIoRecorder::default(),
+            verify_commits: false,
         }
     }
 
+    pub fn with_batch_reader(mut self, batch_reader: Option<&'a dyn BatchStateReader>) -> Self {
+        self.batch_reader = batch_reader;
+        self
+    }
+
+    /// Provider lookups done while executing (cached reads misses).
+    pub fn provider_reads(&self) -> usize {
+        self.provider_io.stats().calls() as usize
+    }
+
+    /// Provider accesses done by prefetch_order: one per batch read plus the lookups the batches didn't serve.
+    pub fn batched_provider_reads(&self) -> usize {
+        self.batched_provider_io.stats().calls() as usize
+    }
+
+    /// Calls and time per method of all the provider lookups (execution + prefetch_order).
+    pub fn provider_io(&self) -> ProviderIoStats {
+        self.provider_io.stats() + self.batched_provider_io.stats()
+    }
+
+    /// If there is a batch reader and the order has an access list (see [`OrderAccessList::from_order`]) loads all
+    /// its accounts (with code) and slots into the cached reads with a single batch read so the execution does not go
+    /// to the provider.
+    pub fn prefetch_order(
+        &mut self,
+        order: &SimulatedOrder,
+        ctx: &BlockBuildingContext,
+    ) -> Result<(), ProviderError> {
+        let Some(batch_reader) = self.batch_reader else {
+            return Ok(());
+        };
+        let Some(access_list) = OrderAccessList::from_order(order, &[ctx.block_env.coinbase])
+        else {
+            return Ok(());
+        };
+        let Some(batch) = self
+            .batched_provider_io
+            .record_batch(|| batch_reader.read_batch(&access_list))?
+        else {
+            return Ok(());
+        };
+        let db = self.cached_reads.as_db(BatchedStateDBRef::new(
+            &batch,
+            InstrumentedDBRef::new(
+                StateProviderDatabase::new(self.provider),
+                &self.batched_provider_io,
+            ),
+        ));
+        for (address, slots) in access_list.accounts {
+            if let Some(info) = db.basic_ref(address)? {
+                if info.code_hash != KECCAK_EMPTY {
+                    db.code_by_hash_ref(info.code_hash)?;
+                }
+            }
+            for slot in slots {
+                db.storage_ref(address, slot)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// sample_rate_bps of the reads served by the cached reads are checked against the provider.
+    pub fn with_cached_reads_verification(mut self, sample_rate_bps: u32) -> Self {
+        self.verify_cached_reads_bps = sample_rate_bps;
+        self
+    }
+
+    /// Every committed tx is executed again alone on top of the state it was committed on (no cached reads, no inspector)
+    /// and the commit fails with [`CriticalCommitOrderError::CommitVerification`] if the result or the state changes differ.
+    /// Doubles the execution work, meant for tests.
+    pub fn with_commit_verification(mut self, verify_commits: bool) -> Self {
+        self.verify_commits = verify_commits;
+        self
+    }
+
+    /// Number of sampled cached reads that did not match the provider.
+    pub fn cached_reads_divergences(&self) -> usize {
+        self.cached_reads_divergences.get()
+    }
+
     pub fn with_cached_reads(mut self, cached_reads: CachedReads) -> Self {
         self.cached_reads = cached_reads;
         self
@@ -60,13 +171,27 @@ impl<'a> BlockState<'a> {
         self
     }
 
+    pub fn bundle_state(&self) -> &BundleState {
+        self.bundle_state.as_ref().unwrap()
+    }
+
+    pub fn set_bundle_state(&mut self, bundle_state: BundleState) {
+        self.bundle_state = Some(bundle_state);
+    }
+
     pub fn into_parts(self) -> (CachedReads, BundleState) {
         (self.cached_reads, self.bundle_state.unwrap())
     }
 
     pub fn new_db_ref(&mut self) -> BlockStateDBRef<impl Database<Error = ProviderError> + '_> {
-        let state_provider = StateProviderDatabase::new(self.provider);
-        let cachedb = WrapDatabaseRef(self.cached_reads.as_db(state_provider));
+        let state_provider =
+            InstrumentedDBRef::new(StateProviderDatabase::new(self.provider), &self.provider_io);
+        let cachedb = WrapDatabaseRef(VerifyCachedReadsDBRef::new(
+            self.cached_reads.as_db(state_provider),
+            StateProviderDatabase::new(self.provider),
+            self.verify_cached_reads_bps,
+            &self.cached_reads_divergences,
+        ));
         let bundle_state = self.bundle_state.take().unwrap();
         let db = State::builder()
             .with_database(cachedb)
@@ -94,6 +219,11 @@ impl<'a> BlockState<'a> {
             .unwrap_or_default())
     }
 
+    pub fn storage(&mut self, address: Address, index: U256) -> Result<U256, ProviderError> {
+        let mut db = self.new_db_ref();
+        db.as_mut().storage(address, index)
+    }
+
     pub fn code_hash(&mut self, address: Address) -> Result<B256, ProviderError> {
         let mut db = self.new_db_ref();
         Ok(db
@@ -184,6 +314,23 @@ pub enum TransactionErr {
     GasLeft,
     #[error("Blob Gas left is too low")]
     BlobGasLeft,
+    #[error("Tx would create a new account")]
+    NewAccountRecipient,
+    #[error("Tx reverted")]
+    Reverted,
+    #[error("Static limit: {0}")]
+    StaticLimit(StaticLimitError),
+    #[error("Logs size {size} over {max} bytes")]
+    LogsTooLarge { size: usize, max: usize },
+}
+
+/// Bytes taken by the logs of a receipt: address, topics and data of each log.
+pub fn logs_size(logs: &[Log]) -> usize {
+    logs.iter()
+        .map(|log| {
+            Address::len_bytes() + log.data.topics().len() * B256::len_bytes() + log.data.data.len()
+        })
+        .sum()
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -197,6 +344,8 @@ pub struct BundleOk {
     pub nonces_updated: Vec<(Address, u64)>,
     pub receipts: Vec<Receipt>,
     pub paid_kickbacks: Vec<(Address, U256)>,
+    /// RefundMode::Aggregated: refunds promised but not paid by the sbundle txs (see [`crate::building::RefundLedger`]).
+    pub deferred_refunds: Vec<(Address, ReservedPayout)>,
     /// Only for sbundles we accumulate ShareBundleInner::original_order_id that executed ok.
     /// Its original use is for only one level or orders with original_order_id but if nesting happens the parent order original_order_id goes before its children (pre-order DFS)
     /// Fully dropped orders (TxRevertBehavior::AllowedExcluded allows it!) are not included.
@@ -245,6 +394,13 @@ pub enum BundleErr {
     IncorrectTimestamp { min: u64, max: u64, block: u64 },
     #[error("Mev-share without signer")]
     NoSigner,
+    #[error(
+        "Block position too high, position: {position}, max_block_position: {max_block_position}"
+    )]
+    BlockPositionTooHigh {
+        position: usize,
+        max_block_position: usize,
+    },
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -261,7 +417,11 @@ pub struct OrderOk {
     pub nonces_updated: Vec<(Address, u64)>,
     pub receipts: Vec<Receipt>,
     pub paid_kickbacks: Vec<(Address, U256)>,
+    /// See [`BundleOk::deferred_refunds`].
+    pub deferred_refunds: Vec<(Address, ReservedPayout)>,
     pub used_state_trace: Option<UsedStateTrace>,
+    /// See [`crate::primitives::SimValue::token_profit`].
+    pub token_profit: U256,
 }
 
 #[derive(Error, Debug, Eq, PartialEq)]
@@ -296,7 +456,8 @@ pub struct ShareBundleCommitResult {
     pub bundle_ok: BundleOk,
     pub coinbase_diff_before_payouts: U256,
     pub total_payouts_promissed: U256,
-    pub payouts_promissed: HashMap<Address, ReservedPayout>,
+    /// Sorted by recipient so the refund txs always come out in the same order.
+    pub payouts_promissed: BTreeMap<Address, ReservedPayout>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -305,6 +466,45 @@ pub enum CriticalCommitOrderError {
     Reth(#[from] ProviderError),
     #[error("EVM error: {0}")]
     EVM(#[from] EVMError<ProviderError>),
+    #[error(
+        "Commit verification failed, tx: {tx_hash:?}, gas used: {committed_gas_used} re-executed: {reexecuted_gas_used}"
+    )]
+    CommitVerification {
+        tx_hash: B256,
+        committed_gas_used: u64,
+        reexecuted_gas_used: u64,
+    },
+    #[error("Pre block call failed: {0}")]
+    PreBlockCall(String),
+}
+
+/// See [`BlockState::with_commit_verification`].
+fn verify_commit(
+    provider: &StateProviderBox,
+    pre_commit_state: BundleState,
+    env: Env,
+    ctx: &BlockBuildingContext,
+    tx_hash: B256,
+    committed: &ResultAndState,
+) -> Result<(), CriticalCommitOrderError> {
+    let mut db = State::builder()
+        .with_database(WrapDatabaseRef(StateProviderDatabase::new(provider)))
+        .with_bundle_prestate(pre_commit_state)
+        .build();
+    let mut evm = revm::Evm::builder()
+        .with_spec_id(ctx.spec_id)
+        .with_env(Box::new(env))
+        .with_db(&mut db)
+        .build();
+    let reexecuted = evm.transact()?;
+    if reexecuted.result != committed.result || reexecuted.state != committed.state {
+        return Err(CriticalCommitOrderError::CommitVerification {
+            tx_hash,
+            committed_gas_used: committed.result.gas_used(),
+            reexecuted_gas_used: reexecuted.result.gas_used(),
+        });
+    }
+    Ok(())
 }
 
 impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer> {
@@ -368,12 +568,23 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
         gas_reserved: u64,
         mut cumulative_blob_gas_used: u64,
     ) -> Result<Result<TransactionOk, TransactionErr>, CriticalCommitOrderError> {
+        // cheaper than an evm execution and gives a better error
+        if let Err(err) =
+            check_tx_static_limits(tx_with_blobs, &ctx.chain_spec, ctx.attributes.timestamp)
+        {
+            return Ok(Err(TransactionErr::StaticLimit(err)));
+        }
+
         // Use blobs.len() instead of checking for tx type just in case in the future some other new txs have blobs
         let blob_gas_used = tx_with_blobs.blobs_sidecar.blobs.len() as u64 * DATA_GAS_PER_BLOB;
         if cumulative_blob_gas_used + blob_gas_used > MAX_DATA_GAS_PER_BLOCK {
             return Ok(Err(TransactionErr::BlobGasLeft));
         }
 
+        let commit_verification = self
+            .state
+            .verify_commits
+            .then(|| (self.state.provider, self.state.bundle_state().clone()));
         let mut db = self.state.new_db_ref();
         let tx = &tx_with_blobs.tx;
         if ctx.blocklist.contains(&tx.signer())
@@ -385,6 +596,47 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
             return Ok(Err(TransactionErr::Blocklist));
         }
 
+        // revm would also catch this but we don't want to waste an evm execution on a tx replayed from another chain
+        if tx
+            .chain_id()
+            .is_some_and(|chain_id| chain_id != ctx.initialized_cfg.cfg_env.chain_id)
+        {
+            return Ok(Err(TransactionErr::InvalidTransaction(
+                InvalidTransaction::InvalidChainId,
+            )));
+        }
+        // same for blob txs that can't pay the blob basefee of the block
+        if let (Some(max_fee_per_blob_gas), Some(blob_gasprice)) =
+            (tx.max_fee_per_blob_gas(), ctx.block_env.get_blob_gasprice())
+        {
+            if max_fee_per_blob_gas < blob_gasprice {
+                return Ok(Err(TransactionErr::InvalidTransaction(
+                    InvalidTransaction::BlobGasPriceGreaterThanMax,
+                )));
+            }
+        }
+
+        // payouts are signed by us and must be allowed to pay fresh accounts
+        let is_builder_tx = ctx
+            .builder_signer
+            .as_ref()
+            .map(|signer| signer.address == tx.signer())
+            .unwrap_or(false);
+        if ctx.reject_new_account_recipients && !is_builder_tx {
+            let recipient_exists = match tx.to() {
+                Some(to) => db
+                    .as_mut()
+                    .basic(to)?
+                    .map(|acc| !acc.is_empty())
+                    .unwrap_or(false),
+                // contract creation
+                None => false,
+            };
+            if !recipient_exists {
+                return Ok(Err(TransactionErr::NewAccountRecipient));
+            }
+        }
+
         match ctx
             .block_env
             .gas_limit
@@ -403,6 +655,8 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
             block: ctx.block_env.clone(),
             tx: tx_env_with_recovered(tx),
         };
+        let commit_verification = commit_verification
+            .map(|(provider, pre_commit_state)| (provider, pre_commit_state, env.clone()));
 
         let used_state_tracer = self.tracer.as_mut().and_then(|t| t.get_used_state_tracer());
         let mut rbuilder_inspector = RBuilderEVMInspector::new(tx, used_state_tracer);
@@ -427,6 +681,15 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
         };
         let mut db_context = evm.into_context();
         let db = &mut db_context.evm.db;
+        if let Some(number) = rbuilder_inspector.unavailable_block_hash_read() {
+            warn!(
+                tx_hash = ?tx.hash(),
+                block = ?ctx.block_env.number,
+                requested_block = ?number,
+                "Tx read a block hash older than the last {} blocks, got 0",
+                BLOCK_HASH_HISTORY
+            );
+        }
         let access_list = rbuilder_inspector.into_access_list();
         if let Some(tracer) = &mut self.tracer {
             tracer.gas_used(res.result.gas_used());
@@ -437,6 +700,16 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
         {
             return Ok(Err(TransactionErr::Blocklist));
         }
+        if let Some(max) = ctx.max_tx_logs_size {
+            // nothing was committed yet, dropping res is the rollback
+            let size = logs_size(res.result.logs());
+            if size > max {
+                return Ok(Err(TransactionErr::LogsTooLarge { size, max }));
+            }
+        }
+        if let Some((provider, pre_commit_state, env)) = commit_verification {
+            verify_commit(provider, pre_commit_state, env, ctx, tx.hash(), &res)?;
+        }
         db.commit(res.state);
         db.merge_transitions(BundleRetention::Reverts);
         self.rollbacks += 1;
@@ -528,6 +801,7 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
             nonces_updated: Vec::new(),
             receipts: Vec::new(),
             paid_kickbacks: Vec::new(),
+            deferred_refunds: Vec::new(),
             original_order_ids: Vec::new(),
         };
         for tx_with_blobs in &bundle.txs {
@@ -626,6 +900,14 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
 
         let mut insert = res.bundle_ok;
 
+        if ctx.refund_mode == RefundMode::Aggregated {
+            if !res.payouts_promissed.is_empty() && ctx.builder_signer.is_none() {
+                return Ok(Err(BundleErr::NoSigner));
+            }
+            insert.deferred_refunds.extend(res.payouts_promissed);
+            return Ok(Ok(insert));
+        }
+
         // now pay all kickbacks
         for (
             to,
@@ -737,6 +1019,7 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
             nonces_updated: Vec::new(),
             receipts: Vec::new(),
             paid_kickbacks: Vec::new(),
+            deferred_refunds: Vec::new(),
             original_order_ids: Vec::new(),
         };
         let coinbase_balance_before = self.state.balance(ctx.block_env.coinbase)?;
@@ -886,7 +1169,7 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
         }
 
         // calculate gas limits
-        let mut payouts_promised = HashMap::new();
+        let mut payouts_promised = BTreeMap::new();
         for (to, refundable_value) in inner_payouts.drain() {
             let gas_limit =
                 match estimate_payout_gas_limit(to, ctx, self.state, insert.cumulative_gas_used) {
@@ -940,6 +1223,23 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
             .map(|t| t.clone())
     }
 
+    /// See [`crate::building::token_valuation`].
+    fn token_profit(
+        &mut self,
+        receipts: &[Receipt],
+        ctx: &BlockBuildingContext,
+    ) -> Result<U256, ProviderError> {
+        if ctx.token_valuation.is_empty() {
+            return Ok(U256::ZERO);
+        }
+        ctx.token_valuation.value_transfers(
+            receipts,
+            &ctx.token_profit_recipients(),
+            self.state,
+            ctx.block_env.timestamp.to(),
+        )
+    }
+
     pub fn commit_order(
         &mut self,
         order: &Order,
@@ -982,6 +1282,10 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                 )?;
                 match res {
                     Ok(ok) => {
+                        if ctx.exclude_reverting_txs && !ok.receipt.success {
+                            // commit_order rollbacks the tx
+                            return Ok(Err(TransactionErr::Reverted.into()));
+                        }
                         let coinbase_balance_after = self.state.balance(ctx.block_env.coinbase)?;
                         let coinbase_profit = match coinbase_profit(
                             coinbase_balance_before,
@@ -992,6 +1296,8 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                                 return Ok(Err(err));
                             }
                         };
+                        let receipts = vec![ok.receipt];
+                        let token_profit = self.token_profit(&receipts, ctx)?;
                         Ok(Ok(OrderOk {
                             coinbase_profit,
                             gas_used: ok.gas_used,
@@ -1000,10 +1306,12 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                             cumulative_blob_gas_used: ok.cumulative_blob_gas_used,
                             txs: vec![ok.tx],
                             nonces_updated: vec![ok.nonce_updated],
-                            receipts: vec![ok.receipt],
+                            receipts,
                             paid_kickbacks: Vec::new(),
+                            deferred_refunds: Vec::new(),
                             used_state_trace: self.get_used_state_trace(),
                             original_order_ids: Vec::new(),
+                            token_profit,
                         }))
                     }
                     Err(err) => Ok(Err(err.into())),
@@ -1030,6 +1338,8 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                                 return Ok(Err(err));
                             }
                         };
+                        let receipts = ok.receipts;
+                        let token_profit = self.token_profit(&receipts, ctx)?;
                         Ok(Ok(OrderOk {
                             coinbase_profit,
                             gas_used: ok.gas_used,
@@ -1038,10 +1348,12 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                             cumulative_blob_gas_used: ok.cumulative_blob_gas_used,
                             txs: ok.txs,
                             nonces_updated: ok.nonces_updated,
-                            receipts: ok.receipts,
+                            receipts,
                             paid_kickbacks: ok.paid_kickbacks,
+                            deferred_refunds: ok.deferred_refunds,
                             used_state_trace: self.get_used_state_trace(),
                             original_order_ids: ok.original_order_ids,
+                            token_profit,
                         }))
                     }
                     Err(err) => Ok(Err(err.into())),
@@ -1068,6 +1380,23 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                                 return Ok(Err(err));
                             }
                         };
+                        // deferred refunds are paid later from the coinbase balance
+                        let deferred_refunds_cost = ok
+                            .deferred_refunds
+                            .iter()
+                            .map(|(_, payout)| payout.total_refundable_value)
+                            .sum::<U256>();
+                        let coinbase_profit =
+                            match coinbase_profit.checked_sub(deferred_refunds_cost) {
+                                Some(profit) => profit,
+                                None => {
+                                    return Ok(Err(OrderErr::NegativeProfit(
+                                        deferred_refunds_cost - coinbase_profit,
+                                    )));
+                                }
+                            };
+                        let receipts = ok.receipts;
+                        let token_profit = self.token_profit(&receipts, ctx)?;
                         Ok(Ok(OrderOk {
                             coinbase_profit,
                             gas_used: ok.gas_used,
@@ -1076,10 +1405,12 @@ impl<'a, 'b, 'c, Tracer: SimulationTracer> PartialBlockFork<'a, 'b, 'c, Tracer>
                             cumulative_blob_gas_used: ok.cumulative_blob_gas_used,
                             txs: ok.txs,
                             nonces_updated: ok.nonces_updated,
-                            receipts: ok.receipts,
+                            receipts,
                             paid_kickbacks: ok.paid_kickbacks,
+                            deferred_refunds: ok.deferred_refunds,
                             used_state_trace: self.get_used_state_trace(),
                             original_order_ids: ok.original_order_ids,
+                            token_profit,
                         }))
                     }
                     Err(err) => Ok(Err(err.into())),
@@ -0,0 +1,133 @@
+//! Accounts (nonce, balance) changed by the best block candidate of the slot so searchers can ask what their
+//! accounts will look like in the block we are building (see rbuilder_getPendingAccount).
+//! Builders offer every block they finalize, the one with the best bid for the parent is kept.
+//! Only the accounts are kept (no storage), withdrawals (applied after the txs) are not included.
+use crate::building::BlockBuildingContext;
+use ahash::HashMap;
+use alloy_primitives::{Address, B256, U256};
+use revm::db::BundleState;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PendingAccount {
+    pub nonce: u64,
+    pub balance: U256,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingCandidate {
+    pub parent_hash: B256,
+    pub block_number: u64,
+    /// Number of candidates offered for the parent when this one was taken (1 for the first one).
+    pub iteration: u64,
+    pub builder_name: String,
+    pub bid_value: U256,
+    /// Accounts changed by the block, destroyed accounts are default.
+    accounts: HashMap<Address, PendingAccount>,
+}
+
+impl PendingCandidate {
+    /// None if the block didn't change the account.
+    pub fn account(&self, address: &Address) -> Option<PendingAccount> {
+        self.accounts.get(address).copied()
+    }
+}
+
+#[derive(Debug, Default)]
+struct PendingCandidatesInner {
+    parent_hash: B256,
+    offered: u64,
+    best: Option<Arc<PendingCandidate>>,
+}
+
+/// Shared by the builders of the process (see [`crate::building::builders::LiveBuilderInput::pending_candidates`]),
+/// disabled unless someone reads them.
+#[derive(Debug, Default)]
+pub struct PendingCandidates {
+    enabled: AtomicBool,
+    inner: Mutex<PendingCandidatesInner>,
+}
+
+impl PendingCandidates {
+    /// Until called offer does nothing.
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Accounts of the filled block to offer once it's finalized, None if disabled.
+    pub fn changed_accounts(
+        &self,
+        bundle_state: &BundleState,
+    ) -> Option<HashMap<Address, PendingAccount>> {
+        if !self.is_enabled() {
+            return None;
+        }
+        Some(
+            bundle_state
+                .state
+                .iter()
+                .map(|(address, account)| {
+                    let account = account
+                        .info
+                        .as_ref()
+                        .map(|info| PendingAccount {
+                            nonce: info.nonce,
+                            balance: info.balance,
+                        })
+                        .unwrap_or_default();
+                    (*address, account)
+                })
+                .collect(),
+        )
+    }
+
+    /// accounts: from [`PendingCandidates::changed_accounts`]. A new parent discards the candidates of the previous one.
+    pub fn offer(
+        &self,
+        ctx: &BlockBuildingContext,
+        builder_name: &str,
+        bid_value: U256,
+        accounts: HashMap<Address, PendingAccount>,
+    ) {
+        let parent_hash = ctx.attributes.parent;
+        let mut inner = self.inner.lock().unwrap();
+        if inner.parent_hash != parent_hash {
+            *inner = PendingCandidatesInner {
+                parent_hash,
+                ..Default::default()
+            };
+        }
+        inner.offered += 1;
+        if inner
+            .best
+            .as_ref()
+            .is_some_and(|best| best.bid_value >= bid_value)
+        {
+            return;
+        }
+        inner.best = Some(Arc::new(PendingCandidate {
+            parent_hash,
+            block_number: ctx.block(),
+            iteration: inner.offered,
+            builder_name: builder_name.to_string(),
+            bid_value,
+            accounts,
+        }));
+    }
+
+    /// Best candidate built on top of parent_hash.
+    pub fn best(&self, parent_hash: B256) -> Option<Arc<PendingCandidate>> {
+        let inner = self.inner.lock().unwrap();
+        if inner.parent_hash != parent_hash {
+            return None;
+        }
+        inner.best.clone()
+    }
+}
@@ -0,0 +1,136 @@
+//! Simulated vs realized profit of the orders included in a block.
+//! Orders are ranked by the profit of their (top of block) simulation but realize the coinbase delta of their commit
+//! and earlier orders of the block can change it (eg: a conflicting tx takes part of the opportunity).
+//! Large systematic divergence means our ordering is mispricing orders.
+//! The builders keep the profit each order was first ranked with during the fill (retries re-rank orders with their
+//! in place value so the SimulatedOrder of the commit is not enough) and after every finalized block the report of the
+//! builder goes to the metrics and replaces its previous one in [`PROFIT_DIVERGENCES`] (served on /debug/profit_divergence).
+use super::BuiltBlockTrace;
+use crate::{primitives::OrderId, telemetry::set_last_block_order_profit_divergence};
+use ahash::HashMap;
+use alloy_primitives::U256;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+
+/// Orders with the biggest absolute error kept on the report.
+pub const TOP_DIVERGING_ORDERS: usize = 10;
+
+lazy_static! {
+    /// Process wide last report of each builder.
+    pub static ref PROFIT_DIVERGENCES: Arc<ProfitDivergences> = Arc::new(ProfitDivergences::default());
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderProfitDivergence {
+    pub order_id: OrderId,
+    /// Profit the order was ranked with.
+    pub simulated_profit: U256,
+    /// Coinbase delta of its commit.
+    pub realized_profit: U256,
+    /// realized_profit - simulated_profit.
+    pub signed_error_wei: f64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProfitDivergenceReport {
+    pub builder_name: String,
+    pub block: u64,
+    /// Included orders we have a ranking profit for.
+    pub orders: usize,
+    pub mean_signed_error_wei: f64,
+    pub p95_abs_error_wei: f64,
+    /// Biggest absolute error first.
+    pub top_diverging_orders: Vec<OrderProfitDivergence>,
+}
+
+impl ProfitDivergenceReport {
+    /// ranked_profits: profit each order was first ranked with on the fill of the block.
+    /// Included orders without ranking profit (eg: anchor tx) are ignored.
+    pub fn new(
+        builder_name: &str,
+        block: u64,
+        ranked_profits: &HashMap<OrderId, U256>,
+        trace: &BuiltBlockTrace,
+    ) -> Self {
+        let mut divergences: Vec<_> = trace
+            .included_orders
+            .iter()
+            .filter_map(|included| {
+                let order_id = included.order.id();
+                let simulated_profit = *ranked_profits.get(&order_id)?;
+                let realized_profit = included.coinbase_profit;
+                Some(OrderProfitDivergence {
+                    order_id,
+                    simulated_profit,
+                    realized_profit,
+                    signed_error_wei: to_f64(realized_profit) - to_f64(simulated_profit),
+                })
+            })
+            .collect();
+        let orders = divergences.len();
+        let mean_signed_error_wei = if orders == 0 {
+            0.0
+        } else {
+            divergences
+                .iter()
+                .map(|divergence| divergence.signed_error_wei)
+                .sum::<f64>()
+                / orders as f64
+        };
+        divergences.sort_by(|a, b| {
+            b.signed_error_wei
+                .abs()
+                .total_cmp(&a.signed_error_wei.abs())
+        });
+        // nearest rank on the sorted (desc) errors
+        let p95_abs_error_wei = if orders == 0 {
+            0.0
+        } else {
+            let rank = ((orders as f64 * 0.95).ceil() as usize).clamp(1, orders);
+            divergences[orders - rank].signed_error_wei.abs()
+        };
+        divergences.truncate(TOP_DIVERGING_ORDERS);
+        Self {
+            builder_name: builder_name.to_string(),
+            block,
+            orders,
+            mean_signed_error_wei,
+            p95_abs_error_wei,
+            top_diverging_orders: divergences,
+        }
+    }
+}
+
+fn to_f64(value: U256) -> f64 {
+    u128::try_from(value).unwrap_or(u128::MAX) as f64
+}
+
+#[derive(Debug, Default)]
+pub struct ProfitDivergences {
+    reports: Mutex<HashMap<String, ProfitDivergenceReport>>,
+}
+
+impl ProfitDivergences {
+    /// Exports the report metrics and keeps it as the last one of its builder.
+    pub fn record(&self, report: ProfitDivergenceReport) {
+        set_last_block_order_profit_divergence(
+            &report.builder_name,
+            report.mean_signed_error_wei,
+            report.p95_abs_error_wei,
+        );
+        self.reports
+            .lock()
+            .unwrap()
+            .insert(report.builder_name.clone(), report);
+    }
+
+    /// Sorted by builder name.
+    pub fn reports(&self) -> Vec<ProfitDivergenceReport> {
+        let mut reports: Vec<_> = self.reports.lock().unwrap().values().cloned().collect();
+        reports.sort_by(|a, b| a.builder_name.cmp(&b.builder_name));
+        reports
+    }
+}
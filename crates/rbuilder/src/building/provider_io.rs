@@ -0,0 +1,256 @@
+//! Accounting of the lookups that reach the state provider (MDBX) while building a block, per method.
+//! Only the lookups missing the cached reads get here and each one is a db access, so timing every call costs
+//! nothing noticeable compared with the lookup itself.
+use alloy_primitives::{Address, B256, U256};
+use reth_interfaces::provider::ProviderError;
+use revm::{
+    primitives::{AccountInfo, Bytecode},
+    DatabaseRef,
+};
+use std::{
+    cell::Cell,
+    ops::Add,
+    time::{Duration, Instant},
+};
+use thiserror::Error;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProviderMethodStats {
+    pub calls: u64,
+    pub time: Duration,
+}
+
+impl Add for ProviderMethodStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            calls: self.calls + other.calls,
+            time: self.time + other.time,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProviderIoStats {
+    pub account: ProviderMethodStats,
+    pub storage: ProviderMethodStats,
+    pub bytecode: ProviderMethodStats,
+    pub block_hash: ProviderMethodStats,
+    /// See [`crate::building::batched_reads::BatchStateReader`].
+    pub batch: ProviderMethodStats,
+}
+
+impl ProviderIoStats {
+    pub fn calls(&self) -> u64 {
+        self.methods().iter().map(|(_, stats)| stats.calls).sum()
+    }
+
+    pub fn time(&self) -> Duration {
+        self.methods().iter().map(|(_, stats)| stats.time).sum()
+    }
+
+    /// (method label, stats)
+    pub fn methods(&self) -> [(&'static str, ProviderMethodStats); 5] {
+        [
+            ("account", self.account),
+            ("storage", self.storage),
+            ("bytecode", self.bytecode),
+            ("block_hash", self.block_hash),
+            ("batch", self.batch),
+        ]
+    }
+}
+
+impl Add for ProviderIoStats {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            account: self.account + other.account,
+            storage: self.storage + other.storage,
+            bytecode: self.bytecode + other.bytecode,
+            block_hash: self.block_hash + other.block_hash,
+            batch: self.batch + other.batch,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ProviderMethod {
+    Account,
+    Storage,
+    Bytecode,
+    BlockHash,
+    Batch,
+}
+
+/// Accumulates the stats of all the [`InstrumentedDBRef`] created over it.
+#[derive(Debug, Default)]
+pub struct ProviderIoRecorder {
+    stats: Cell<ProviderIoStats>,
+}
+
+impl ProviderIoRecorder {
+    pub fn stats(&self) -> ProviderIoStats {
+        self.stats.get()
+    }
+
+    /// Records f as a single batch call.
+    pub fn record_batch<T>(&self, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let res = f();
+        self.record(ProviderMethod::Batch, start.elapsed());
+        res
+    }
+
+    fn record(&self, method: ProviderMethod, time: Duration) {
+        let mut stats = self.stats.get();
+        let method_stats = match method {
+            ProviderMethod::Account => &mut stats.account,
+            ProviderMethod::Storage => &mut stats.storage,
+            ProviderMethod::Bytecode => &mut stats.bytecode,
+            ProviderMethod::BlockHash => &mut stats.block_hash,
+            ProviderMethod::Batch => &mut stats.batch,
+        };
+        method_stats.calls += 1;
+        method_stats.time += time;
+        self.stats.set(stats);
+    }
+}
+
+/// Records calls and time of every lookup on db.
+#[derive(Debug)]
+pub struct InstrumentedDBRef<'a, DB> {
+    db: DB,
+    recorder: &'a ProviderIoRecorder,
+}
+
+impl<'a, DB> InstrumentedDBRef<'a, DB> {
+    pub fn new(db: DB, recorder: &'a ProviderIoRecorder) -> Self {
+        Self { db, recorder }
+    }
+
+    fn timed<T>(&self, method: ProviderMethod, f: impl FnOnce(&DB) -> T) -> T {
+        let start = Instant::now();
+        let res = f(&self.db);
+        self.recorder.record(method, start.elapsed());
+        res
+    }
+}
+
+impl<'a, DB: DatabaseRef<Error = ProviderError>> DatabaseRef for InstrumentedDBRef<'a, DB> {
+    type Error = ProviderError;
+
+    fn basic_ref(&self, address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+        self.timed(ProviderMethod::Account, |db| db.basic_ref(address))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.timed(ProviderMethod::Bytecode, |db| {
+            db.code_by_hash_ref(code_hash)
+        })
+    }
+
+    fn storage_ref(&self, address: Address, index: U256) -> Result<U256, Self::Error> {
+        self.timed(ProviderMethod::Storage, |db| db.storage_ref(address, index))
+    }
+
+    fn block_hash_ref(&self, number: U256) -> Result<B256, Self::Error> {
+        self.timed(ProviderMethod::BlockHash, |db| db.block_hash_ref(number))
+    }
+}
+
+/// The node is too slow serving state, building further is pointless.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error(
+    "State provider io took {spent:?}, over the budget of {budget:?} (node might be unhealthy)"
+)]
+pub struct ProviderIoBudgetExceeded {
+    pub spent: Duration,
+    pub budget: Duration,
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::thread::sleep;
+
+    /// Counts its own calls, storage lookups are slow.
+    #[derive(Default)]
+    struct FakeProvider {
+        calls: Cell<[u64; 4]>,
+    }
+
+    impl FakeProvider {
+        fn inc(&self, index: usize) {
+            let mut calls = self.calls.get();
+            calls[index] += 1;
+            self.calls.set(calls);
+        }
+    }
+
+    impl DatabaseRef for FakeProvider {
+        type Error = ProviderError;
+
+        fn basic_ref(&self, _address: Address) -> Result<Option<AccountInfo>, Self::Error> {
+            self.inc(0);
+            Ok(None)
+        }
+
+        fn code_by_hash_ref(&self, _code_hash: B256) -> Result<Bytecode, Self::Error> {
+            self.inc(2);
+            Ok(Bytecode::default())
+        }
+
+        fn storage_ref(&self, _address: Address, _index: U256) -> Result<U256, Self::Error> {
+            self.inc(1);
+            sleep(Duration::from_millis(2));
+            Ok(U256::ZERO)
+        }
+
+        fn block_hash_ref(&self, _number: U256) -> Result<B256, Self::Error> {
+            self.inc(3);
+            Err(ProviderError::BlockHashNotFound(B256::ZERO))
+        }
+    }
+
+    #[test]
+    fn test_recorded_calls_match_provider_calls() {
+        let provider = FakeProvider::default();
+        let recorder = ProviderIoRecorder::default();
+        // several short lived dbs over the same recorder like BlockState::new_db_ref does
+        for _ in 0..2 {
+            let db = InstrumentedDBRef::new(&provider, &recorder);
+            for i in 0..3 {
+                db.basic_ref(Address::repeat_byte(i)).unwrap();
+            }
+            db.storage_ref(Address::ZERO, U256::from(1)).unwrap();
+            db.code_by_hash_ref(B256::ZERO).unwrap();
+            // errors are recorded too
+            assert!(db.block_hash_ref(U256::from(1)).is_err());
+        }
+
+        let stats = recorder.stats();
+        let [account, storage, bytecode, block_hash] = provider.calls.get();
+        assert_eq!(stats.account.calls, account);
+        assert_eq!(stats.storage.calls, storage);
+        assert_eq!(stats.bytecode.calls, bytecode);
+        assert_eq!(stats.block_hash.calls, block_hash);
+        assert_eq!((account, storage, bytecode, block_hash), (6, 2, 2, 2));
+        assert_eq!(stats.calls(), 12);
+
+        assert!(stats.storage.time >= Duration::from_millis(4));
+        assert!(stats.time() >= stats.storage.time);
+        assert_eq!((stats + stats).calls(), 24);
+
+        // a batch is a single call whatever it reads
+        recorder.record_batch(|| {
+            let db = InstrumentedDBRef::new(&provider, &ProviderIoRecorder::default());
+            db.basic_ref(Address::ZERO).unwrap();
+            db.basic_ref(Address::repeat_byte(1)).unwrap();
+        });
+        assert_eq!(recorder.stats().batch.calls, 1);
+        assert_eq!(recorder.stats().calls(), 13);
+    }
+}
@@ -0,0 +1,315 @@
+//! Keeps track of the refunds we promised to the users of the MEV-Share bundles included in a block.
+//! Refunds are paid by builder_signer so something executed later in the block (eg: an order moving funds out of the
+//! builder account) could leave us unable to pay deferred refunds. Before sealing the block we check that every
+//! obligation got its transfer.
+use alloy_primitives::{Address, U256};
+use reth::primitives::Receipt;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::primitives::{OrderId, TransactionSignedEcRecoveredWithBlobs};
+
+/// How the refund txs of the sbundles are generated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RefundMode {
+    /// A refund tx per recipient is inserted right after each sbundle.
+    #[default]
+    PerBundle,
+    /// Refunds are accumulated during the fill and paid at the end of the block with a single tx per recipient (saves gas).
+    Aggregated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefundObligation {
+    pub order_id: OrderId,
+    pub recipient: Address,
+    /// Value of the refund tx (gas already discounted).
+    pub value: U256,
+}
+
+/// Aggregated refund for a recipient not paid yet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PendingRefund {
+    pub recipient: Address,
+    pub value: U256,
+    /// Biggest gas limit estimated for the deferred refunds, we keep it reserved until the refund is paid.
+    pub gas_limit: u64,
+    /// What the refunds were charged to the orders (value + gas_limit * basefee for each one).
+    pub reserved_cost: U256,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RefundLedgerError {
+    #[error("Unmet refund obligation, to: {recipient:?}, owed: {owed}, paid: {paid}, orders: {order_ids:?}")]
+    UnmetObligation {
+        recipient: Address,
+        owed: U256,
+        paid: U256,
+        order_ids: Vec<OrderId>,
+    },
+    #[error("Refund obligations without builder signer")]
+    NoSigner,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RefundLedger {
+    obligations: Vec<RefundObligation>,
+    pending: Vec<PendingRefund>,
+}
+
+impl RefundLedger {
+    /// Refund already paid by a tx inside the order (RefundMode::PerBundle).
+    pub fn add_paid(&mut self, order_id: OrderId, recipient: Address, value: U256) {
+        self.obligations.push(RefundObligation {
+            order_id,
+            recipient,
+            value,
+        });
+    }
+
+    /// Refund to be paid at the end of the block (RefundMode::Aggregated).
+    pub fn add_deferred(
+        &mut self,
+        order_id: OrderId,
+        recipient: Address,
+        value: U256,
+        gas_limit: u64,
+        reserved_cost: U256,
+    ) {
+        self.add_paid(order_id, recipient, value);
+        match self.pending.iter_mut().find(|p| p.recipient == recipient) {
+            Some(pending) => {
+                pending.value += value;
+                pending.gas_limit = pending.gas_limit.max(gas_limit);
+                pending.reserved_cost += reserved_cost;
+            }
+            None => self.pending.push(PendingRefund {
+                recipient,
+                value,
+                gas_limit,
+                reserved_cost,
+            }),
+        }
+    }
+
+    pub fn obligations(&self) -> &[RefundObligation] {
+        &self.obligations
+    }
+
+    /// Gas needed by the refunds not paid yet.
+    pub fn reserved_gas(&self) -> u64 {
+        self.pending.iter().map(|p| p.gas_limit).sum()
+    }
+
+    pub fn take_pending(&mut self) -> Vec<PendingRefund> {
+        std::mem::take(&mut self.pending)
+    }
+
+    /// Every recipient must have received (successful txs from refund_signer) at least the sum of its obligations.
+    pub fn verify(
+        &self,
+        txs: &[TransactionSignedEcRecoveredWithBlobs],
+        receipts: &[Receipt],
+        refund_signer: Option<Address>,
+    ) -> Result<(), RefundLedgerError> {
+        if self.obligations.is_empty() {
+            return Ok(());
+        }
+        let refund_signer = refund_signer.ok_or(RefundLedgerError::NoSigner)?;
+        let mut recipients: Vec<Address> = self.obligations.iter().map(|o| o.recipient).collect();
+        recipients.sort();
+        recipients.dedup();
+        for recipient in recipients {
+            let owed = self
+                .obligations
+                .iter()
+                .filter(|o| o.recipient == recipient)
+                .map(|o| o.value)
+                .sum::<U256>();
+            let paid = txs
+                .iter()
+                .zip(receipts)
+                .filter(|(tx, receipt)| {
+                    receipt.success && tx.signer() == refund_signer && tx.tx.to() == Some(recipient)
+                })
+                .map(|(tx, _)| tx.tx.value())
+                .sum::<U256>();
+            if paid < owed {
+                return Err(RefundLedgerError::UnmetObligation {
+                    recipient,
+                    owed,
+                    paid,
+                    order_ids: self
+                        .obligations
+                        .iter()
+                        .filter(|o| o.recipient == recipient)
+                        .map(|o| o.order_id)
+                        .collect(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_primitives::B256;
+    use reth::primitives::{
+        Transaction, TransactionKind, TransactionSigned, TransactionSignedEcRecovered, TxLegacy,
+        TxType,
+    };
+
+    const SIGNER: Address = Address::repeat_byte(0x01);
+    const ALICE: Address = Address::repeat_byte(0x02);
+    const BOB: Address = Address::repeat_byte(0x03);
+
+    fn order_id(i: u8) -> OrderId {
+        OrderId::Tx(B256::repeat_byte(i))
+    }
+
+    fn transfer(
+        from: Address,
+        to: Address,
+        value: u64,
+        success: bool,
+    ) -> (TransactionSignedEcRecoveredWithBlobs, Receipt) {
+        let tx = TransactionSignedEcRecovered::from_signed_transaction(
+            TransactionSigned {
+                transaction: Transaction::Legacy(TxLegacy {
+                    to: TransactionKind::Call(to),
+                    value: U256::from(value),
+                    ..TxLegacy::default()
+                }),
+                ..Default::default()
+            },
+            from,
+        );
+        let receipt = Receipt {
+            tx_type: TxType::Legacy,
+            success,
+            cumulative_gas_used: 0,
+            logs: vec![],
+        };
+        (
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            receipt,
+        )
+    }
+
+    fn verify(
+        ledger: &RefundLedger,
+        transfers: Vec<(TransactionSignedEcRecoveredWithBlobs, Receipt)>,
+    ) -> Result<(), RefundLedgerError> {
+        let (txs, receipts): (Vec<_>, Vec<_>) = transfers.into_iter().unzip();
+        ledger.verify(&txs, &receipts, Some(SIGNER))
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let mut ledger = RefundLedger::default();
+        ledger.add_deferred(order_id(1), ALICE, U256::from(10), 21_000, U256::from(30));
+        ledger.add_deferred(order_id(2), BOB, U256::from(20), 21_000, U256::from(40));
+        ledger.add_deferred(order_id(3), ALICE, U256::from(30), 30_000, U256::from(50));
+        assert_eq!(ledger.obligations().len(), 3);
+        assert_eq!(ledger.reserved_gas(), 51_000);
+        assert_eq!(
+            ledger.take_pending(),
+            vec![
+                PendingRefund {
+                    recipient: ALICE,
+                    value: U256::from(40),
+                    gas_limit: 30_000,
+                    reserved_cost: U256::from(80),
+                },
+                PendingRefund {
+                    recipient: BOB,
+                    value: U256::from(20),
+                    gas_limit: 21_000,
+                    reserved_cost: U256::from(40),
+                },
+            ]
+        );
+        assert_eq!(ledger.reserved_gas(), 0);
+        // obligations are kept for verify
+        assert_eq!(ledger.obligations().len(), 3);
+    }
+
+    #[test]
+    fn test_verify() {
+        let mut ledger = RefundLedger::default();
+        ledger.add_paid(order_id(1), ALICE, U256::from(10));
+        ledger.add_paid(order_id(2), BOB, U256::from(20));
+        ledger.add_paid(order_id(3), ALICE, U256::from(30));
+
+        // per bundle
+        assert_eq!(
+            verify(
+                &ledger,
+                vec![
+                    transfer(SIGNER, ALICE, 10, true),
+                    transfer(SIGNER, BOB, 20, true),
+                    transfer(SIGNER, ALICE, 30, true),
+                ]
+            ),
+            Ok(())
+        );
+        // aggregated
+        assert_eq!(
+            verify(
+                &ledger,
+                vec![
+                    transfer(SIGNER, ALICE, 40, true),
+                    transfer(SIGNER, BOB, 20, true)
+                ]
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_verify_underpaid() {
+        let mut ledger = RefundLedger::default();
+        ledger.add_paid(order_id(1), ALICE, U256::from(10));
+        ledger.add_paid(order_id(2), BOB, U256::from(20));
+        ledger.add_paid(order_id(3), ALICE, U256::from(30));
+
+        let alice_underpaid = Err(RefundLedgerError::UnmetObligation {
+            recipient: ALICE,
+            owed: U256::from(40),
+            paid: U256::from(10),
+            order_ids: vec![order_id(1), order_id(3)],
+        });
+        // aggregated refund failed (eg: builder account drained by a later order)
+        assert_eq!(
+            verify(
+                &ledger,
+                vec![
+                    transfer(SIGNER, ALICE, 10, true),
+                    transfer(SIGNER, BOB, 20, true),
+                    transfer(SIGNER, ALICE, 30, false),
+                ]
+            ),
+            alice_underpaid
+        );
+        // transfers from someone else don't count
+        assert_eq!(
+            verify(
+                &ledger,
+                vec![
+                    transfer(SIGNER, ALICE, 10, true),
+                    transfer(SIGNER, BOB, 20, true),
+                    transfer(BOB, ALICE, 30, true),
+                ]
+            ),
+            alice_underpaid
+        );
+        assert_eq!(
+            ledger.verify(&[], &[], None),
+            Err(RefundLedgerError::NoSigner)
+        );
+        assert_eq!(RefundLedger::default().verify(&[], &[], None), Ok(()));
+    }
+}
@@ -3,7 +3,7 @@ use super::{
     OrderErr, PartialBlockFork,
 };
 use crate::{
-    building::{BlockBuildingContext, BlockState, CriticalCommitOrderError},
+    building::{pre_block_call, BlockBuildingContext, BlockState, CriticalCommitOrderError},
     primitives::{Order, OrderId, SimValue, SimulatedOrder},
     utils::{NonceCache, NonceCacheRef},
 };
@@ -324,7 +324,10 @@ pub fn simulate_all_orders_with_sim_tree<DB: Database + Clone>(
 
     let mut sim_errors = Vec::new();
     let state_for_sim = factory.history_by_block_hash(ctx.attributes.parent)?;
-    let mut cache_reads = Some(CachedReads::default());
+    let mut block_state = BlockState::new(&state_for_sim).with_cached_reads(CachedReads::default());
+    // The block starts with the beacon root system call, orders must see its effects.
+    pre_block_call(ctx, &mut block_state)
+        .map_err(|err| CriticalCommitOrderError::PreBlockCall(err.to_string()))?;
     loop {
         // mix new orders into the sim_tree
         if randomize_insertion && !orders.is_empty() {
@@ -345,16 +348,12 @@ pub fn simulate_all_orders_with_sim_tree<DB: Database + Clone>(
         let mut sim_results = Vec::new();
         for sim_task in sim_tasks {
             let start_time = Instant::now();
-            let mut block_state = BlockState::new(&state_for_sim)
-                .with_cached_reads(cache_reads.take().unwrap_or_default());
             let sim_result = simulate_order(
                 sim_task.parents.clone(),
                 sim_task.order.clone(),
                 ctx,
                 &mut block_state,
             )?;
-            let (new_cache_reads, _) = block_state.into_parts();
-            cache_reads = Some(new_cache_reads);
             match sim_result.result {
                 OrderSimResult::Failed(err) => {
                     trace!(
@@ -395,6 +394,8 @@ pub fn simulate_all_orders_with_sim_tree<DB: Database + Clone>(
 }
 
 /// Prepares context (fork + tracer) and calls simulate_order_using_fork
+/// state must start with the block pre block call (see [`pre_block_call`]), call it once when creating the state since
+/// the order changes are rolled back after the simulation.
 pub fn simulate_order(
     parent_orders: Vec<Order>,
     order: Order,
@@ -452,7 +453,8 @@ pub fn simulate_order_using_fork<Tracer: SimulationTracer>(
                 res.gas_used,
                 res.blob_gas_used,
                 res.paid_kickbacks,
-            );
+            )
+            .with_token_profit(res.token_profit);
             let new_nonces = res.nonces_updated.into_iter().collect::<Vec<_>>();
             Ok(OrderSimResult::Success(
                 SimulatedOrder {
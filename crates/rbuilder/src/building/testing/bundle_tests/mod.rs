@@ -2,13 +2,18 @@ pub mod setup;
 
 use alloy_primitives::{Address, U256};
 use itertools::Itertools;
-use reth_primitives::B256;
+use reth_primitives::{constants::eip4844::DATA_GAS_PER_BLOB, B256};
+use revm::primitives::MAX_INITCODE_SIZE;
 use std::collections::HashSet;
 use uuid::Uuid;
 
 use crate::{
-    building::{testing::bundle_tests::setup::NonceValue, BuiltBlockTrace, BundleErr, OrderErr},
+    building::{
+        testing::bundle_tests::setup::NonceValue, BuiltBlockTrace, BundleErr, LogSummary,
+        LogSummaryMode, OrderErr, RefundMode,
+    },
     primitives::{
+        static_limits::{MAX_BLOBS_PER_TX, MAX_TX_SIZE},
         Bundle, BundleReplacementData, BundleReplacementKey, Order, OrderId, Refund, RefundConfig,
         TxRevertBehavior,
     },
@@ -17,7 +22,7 @@ use crate::{
 
 use self::setup::TestSetup;
 
-use super::test_chain_state::{BlockArgs, NamedAddr};
+use super::test_chain_state::{BlockArgs, NamedAddr, LOG_EMITTER_TOPIC};
 
 pub const CURR_NONCE: NonceValue = NonceValue::Relative(0);
 
@@ -74,6 +79,268 @@ fn test_blocklist() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_reject_new_account_recipients() -> eyre::Result<()> {
+    let mut test_setup =
+        TestSetup::gen_test_setup(BlockArgs::default().reject_new_account_recipients(true))?;
+
+    // Dummy has no balance, nonce or code
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx(
+        NamedAddr::User(0),
+        NamedAddr::Dummy,
+        1,
+        TxRevertBehavior::NotAllowed,
+    )?;
+    test_setup.commit_order_err("new account");
+
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx_0_1_no_rev()?;
+    test_setup.commit_order_ok();
+
+    // same transfer is fine without the flag
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default())?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx(
+        NamedAddr::User(0),
+        NamedAddr::Dummy,
+        1,
+        TxRevertBehavior::NotAllowed,
+    )?;
+    test_setup.commit_order_ok();
+    Ok(())
+}
+
+#[test]
+fn test_exclude_reverting_txs() -> eyre::Result<()> {
+    let mut test_setup =
+        TestSetup::gen_test_setup(BlockArgs::default().exclude_reverting_txs(true))?;
+
+    // reverting tx consumes gas but it's rolled back
+    test_setup.begin_mempool_tx_order();
+    test_setup.add_revert(NamedAddr::User(0), TxRevertBehavior::AllowedIncluded)?;
+    test_setup.commit_order_err("reverted");
+    assert!(test_setup.block_txs().is_empty());
+    assert_eq!(test_setup.partial_block().gas_used, 0);
+
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx_0_1_no_rev()?;
+    test_setup.commit_order_ok();
+    assert_eq!(test_setup.block_txs().len(), 1);
+
+    // same tx is included without the flag
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default())?;
+    test_setup.begin_mempool_tx_order();
+    test_setup.add_revert(NamedAddr::User(0), TxRevertBehavior::AllowedIncluded)?;
+    let res = test_setup.commit_order_ok();
+    assert!(!res.receipts[0].success);
+    assert_eq!(test_setup.block_txs().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_max_tx_logs_size() -> eyre::Result<()> {
+    // LogEmitter emits a single LOG1 without data: 20 (address) + 32 (topic) bytes
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().max_tx_logs_size(51))?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_emit_log_tx(NamedAddr::User(0))?;
+    test_setup.commit_order_err("Logs size 52 over 51");
+    assert!(test_setup.block_txs().is_empty());
+    assert_eq!(test_setup.partial_block().gas_used, 0);
+
+    // txs without logs are not affected
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx_0_1_no_rev()?;
+    test_setup.commit_order_ok();
+    assert_eq!(test_setup.block_txs().len(), 1);
+
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().max_tx_logs_size(52))?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_emit_log_tx(NamedAddr::User(0))?;
+    test_setup.commit_order_ok();
+    assert_eq!(test_setup.block_txs().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_wrong_chain_id() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default())?;
+    let chain_id = test_setup.chain_id();
+
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx_0_1_chain_id(chain_id + 1)?;
+    test_setup.commit_order_err("InvalidChainId");
+
+    // same tx for the right chain
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx_0_1_chain_id(chain_id)?;
+    test_setup.commit_order_ok();
+    Ok(())
+}
+
+// mainnet activations
+const SHANGHAI_TIMESTAMP: u64 = 1_681_338_455;
+const CANCUN_TIMESTAMP: u64 = 1_710_338_135;
+
+#[test]
+fn test_initcode_size_limit() -> eyre::Result<()> {
+    let mut test_setup =
+        TestSetup::gen_test_setup(BlockArgs::default().timestamp(SHANGHAI_TIMESTAMP))?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_create_tx(NamedAddr::User(0), MAX_INITCODE_SIZE + 1)?;
+    test_setup.commit_order_err("Static limit: Initcode size");
+
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_create_tx(NamedAddr::User(0), MAX_INITCODE_SIZE)?;
+    test_setup.commit_order_ok();
+
+    // before Shanghai the check is off, it's up to the evm
+    let mut test_setup =
+        TestSetup::gen_test_setup(BlockArgs::default().timestamp(SHANGHAI_TIMESTAMP - 1))?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_create_tx(NamedAddr::User(0), MAX_INITCODE_SIZE + 1)?;
+    test_setup.commit_order_err("CreateInitCodeSizeLimit");
+    Ok(())
+}
+
+#[test]
+fn test_blobs_per_tx_limit() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(
+        BlockArgs::default()
+            .timestamp(CANCUN_TIMESTAMP)
+            .parent_beacon_block_root(B256::repeat_byte(1)),
+    )?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_fake_blob_tx(NamedAddr::User(0), MAX_BLOBS_PER_TX + 1)?;
+    test_setup.commit_order_err("Static limit: 7 blobs");
+
+    // before Cancun the check is off, the blob gas check catches it
+    let mut test_setup =
+        TestSetup::gen_test_setup(BlockArgs::default().timestamp(CANCUN_TIMESTAMP - 1))?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_fake_blob_tx(NamedAddr::User(0), MAX_BLOBS_PER_TX + 1)?;
+    test_setup.commit_order_err("Blob Gas left");
+    Ok(())
+}
+
+#[test]
+fn test_blob_tx_fees() -> eyre::Result<()> {
+    // parent has no excess blob gas so the blob basefee is the minimum (1 wei)
+    let mut test_setup = TestSetup::gen_test_setup(
+        BlockArgs::default()
+            .timestamp(CANCUN_TIMESTAMP)
+            .parent_beacon_block_root(B256::repeat_byte(1)),
+    )?;
+    let tip = 1_000;
+
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_blob_tx(NamedAddr::User(0), tip, 0)?;
+    test_setup.commit_order_err("BlobGasPriceGreaterThanMax");
+    assert!(test_setup.block_txs().is_empty());
+
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_blob_tx(NamedAddr::User(0), tip, 1)?;
+    let res = test_setup.commit_order_ok();
+    assert_eq!(test_setup.partial_block().blob_gas_used, DATA_GAS_PER_BLOB);
+    // the blob fee (DATA_GAS_PER_BLOB * 1 wei) is burned, only the tip is ours
+    assert_eq!(res.coinbase_profit, U256::from(21_000 * tip));
+    Ok(())
+}
+
+#[test]
+fn test_tx_size_limit() -> eyre::Result<()> {
+    // txpool policy checked at ingestion only, valid for consensus so commit takes it
+    for timestamp in [SHANGHAI_TIMESTAMP - 1, CANCUN_TIMESTAMP] {
+        let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().timestamp(timestamp))?;
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_large_tx(NamedAddr::User(0), MAX_TX_SIZE)?;
+        test_setup.commit_order_ok();
+    }
+    Ok(())
+}
+
+#[test]
+fn test_prev_randao_and_block_hash() -> eyre::Result<()> {
+    for parity in [0, 1] {
+        let prev_randao = B256::with_last_byte(0x10 + parity as u8);
+        let mut test_setup =
+            TestSetup::gen_test_setup(BlockArgs::default().number(1).prev_randao(prev_randao))?;
+        let parent_hash = test_setup.parent_hash();
+
+        // PREVRANDAO comes from the payload attributes, BLOCKHASH(parent) from the state provider
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_check_block_data_tx(NamedAddr::User(0), 0, parent_hash, parity)?;
+        test_setup.commit_order_ok();
+
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_check_block_data_tx(NamedAddr::User(0), 0, parent_hash, 1 - parity)?;
+        test_setup.commit_order_err("transaction reverted");
+
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_check_block_data_tx(NamedAddr::User(0), 0, B256::ZERO, parity)?;
+        test_setup.commit_order_err("transaction reverted");
+
+        // current block hash is not available
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_check_block_data_tx(NamedAddr::User(0), 1, B256::ZERO, parity)?;
+        test_setup.commit_order_ok();
+    }
+
+    // blocks older than 256 blocks resolve to 0
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(300))?;
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_check_block_data_tx(NamedAddr::User(0), 0, B256::ZERO, 0)?;
+    test_setup.commit_order_ok();
+    Ok(())
+}
+
+#[test]
+fn test_included_order_log_summary() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(11))?;
+    let log_emitter = test_setup.named_address(NamedAddr::LogEmitter)?;
+
+    test_setup.begin_bundle_order(11);
+    test_setup.add_emit_log_tx(NamedAddr::User(0))?;
+    let public_res = test_setup.commit_order_ok();
+
+    let mut trace = BuiltBlockTrace::new();
+    trace.add_included_order(public_res.clone());
+    let summary = trace.included_orders[0].log_summary.clone().unwrap();
+    assert!(!summary.redacted);
+    assert_eq!(summary.tx_success, vec![true]);
+    assert_eq!(
+        summary.tx_logs,
+        vec![vec![LogSummary {
+            address: log_emitter,
+            topics: vec![LOG_EMITTER_TOPIC],
+            data: None,
+        }]]
+    );
+
+    let mut trace = BuiltBlockTrace::new().with_log_summary_mode(LogSummaryMode::Full);
+    trace.add_included_order(public_res.clone());
+    let summary = trace.included_orders[0].log_summary.clone().unwrap();
+    assert_eq!(summary.tx_logs[0][0].data, Some(Default::default()));
+
+    let mut trace = BuiltBlockTrace::new().with_log_summary_mode(LogSummaryMode::None);
+    trace.add_included_order(public_res);
+    assert_eq!(trace.included_orders[0].log_summary, None);
+
+    test_setup.begin_bundle_order(11);
+    test_setup.add_emit_log_tx(NamedAddr::User(1))?;
+    let mut private_res = test_setup.commit_order_ok();
+    if let Order::Bundle(bundle) = &mut private_res.order {
+        bundle.metadata.private = true;
+    }
+    let mut trace = BuiltBlockTrace::new();
+    trace.add_included_order(private_res);
+    let summary = trace.included_orders[0].log_summary.clone().unwrap();
+    assert!(summary.redacted);
+    assert_eq!(summary.tx_success, vec![true]);
+    assert!(summary.tx_logs.is_empty());
+    Ok(())
+}
+
 #[test]
 fn test_target_block() -> eyre::Result<()> {
     {
@@ -259,6 +526,33 @@ fn bundle_revert_tests(
     Ok(())
 }
 
+#[test]
+fn test_bundle_max_block_position() -> eyre::Result<()> {
+    {
+        // top of block bundle considered first lands at position 0
+        let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(11))?;
+        test_setup.begin_bundle_order(11);
+        test_setup.set_bundle_max_block_position(Some(0));
+        let tx_hash = test_setup.add_dummy_tx_0_1_no_rev()?;
+        let res = test_setup.commit_order_ok();
+        assert_eq!(res.txs.len(), 1);
+        assert_eq!(res.txs[0].hash(), tx_hash);
+
+        // block already has 1 tx, top of block bundle is excluded
+        test_setup.begin_bundle_order(11);
+        test_setup.set_bundle_max_block_position(Some(0));
+        test_setup.add_dummy_tx_0_1_no_rev()?;
+        test_setup.commit_order_err("block position too high");
+
+        // position 1 is still allowed
+        test_setup.begin_bundle_order(11);
+        test_setup.set_bundle_max_block_position(Some(1));
+        test_setup.add_dummy_tx_0_1_no_rev()?;
+        test_setup.commit_order_ok();
+    }
+    Ok(())
+}
+
 #[test]
 fn test_bundle_revert() -> eyre::Result<()> {
     let target_block = 11;
@@ -459,6 +753,91 @@ fn test_mev_share_failed_refunds() -> eyre::Result<()> {
     Ok(())
 }
 
+/// 3 sbundles, refunds: User(0) twice, User(3) once (90_000 - 21_000 each)
+fn commit_refund_sbundles(test_setup: &mut TestSetup) -> eyre::Result<()> {
+    for _ in 0..2 {
+        test_setup.begin_share_bundle_order(11, 11);
+        test_setup.add_dummy_tx_0_1_no_rev()?;
+        test_setup.add_send_to_coinbase_tx(NamedAddr::User(1), 100_000)?;
+        test_setup.set_inner_bundle_refund(vec![Refund {
+            body_idx: 0,
+            percent: 90,
+        }]);
+        test_setup.commit_order_ok();
+    }
+    test_setup.begin_share_bundle_order(11, 11);
+    test_setup.start_inner_bundle(false);
+    test_setup.add_dummy_tx_0_1_no_rev()?;
+    test_setup.set_inner_bundle_refund_config(vec![RefundConfig {
+        address: test_setup.named_address(NamedAddr::User(3))?,
+        percent: 100,
+    }]);
+    test_setup.finish_inner_bundle();
+    test_setup.add_send_to_coinbase_tx(NamedAddr::User(1), 100_000)?;
+    test_setup.set_inner_bundle_refund(vec![Refund {
+        body_idx: 0,
+        percent: 90,
+    }]);
+    test_setup.commit_order_ok();
+    Ok(())
+}
+
+#[test]
+fn test_refund_ledger_per_bundle() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(11))?;
+    commit_refund_sbundles(&mut test_setup)?;
+    assert_eq!(test_setup.refund_ledger().obligations().len(), 3);
+    // 2 txs + 1 refund tx per sbundle
+    assert_eq!(test_setup.block_txs().len(), 9);
+
+    test_setup.pay_and_verify_refunds()?.unwrap();
+    assert_eq!(test_setup.block_txs().len(), 9);
+    Ok(())
+}
+
+#[test]
+fn test_refund_ledger_aggregated() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(
+        BlockArgs::default()
+            .number(11)
+            .refund_mode(RefundMode::Aggregated),
+    )?;
+    commit_refund_sbundles(&mut test_setup)?;
+    assert_eq!(test_setup.refund_ledger().obligations().len(), 3);
+    // refunds not paid yet
+    assert_eq!(test_setup.block_txs().len(), 6);
+
+    test_setup.pay_and_verify_refunds()?.unwrap();
+    let refund_txs: Vec<_> = test_setup.block_txs()[6..]
+        .iter()
+        .map(|tx| (tx.tx.to().unwrap(), tx.tx.value()))
+        .collect();
+    assert_eq!(
+        refund_txs,
+        vec![
+            (
+                test_setup.named_address(NamedAddr::User(0))?,
+                U256::from(2 * (90_000 - 21_000))
+            ),
+            (
+                test_setup.named_address(NamedAddr::User(3))?,
+                U256::from(90_000 - 21_000)
+            ),
+        ]
+    );
+
+    // deferred refunds still count against the bundle profit
+    test_setup.begin_share_bundle_order(11, 11);
+    test_setup.add_dummy_tx_0_1_no_rev()?;
+    test_setup.add_send_to_coinbase_tx(NamedAddr::User(1), 42_000)?;
+    test_setup.set_inner_bundle_refund(vec![Refund {
+        body_idx: 0,
+        percent: 101,
+    }]);
+    test_setup.commit_order_err("Negative profit");
+    Ok(())
+}
+
 #[test]
 fn test_bundle_consistency_check() -> eyre::Result<()> {
     let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(11))?;
@@ -770,3 +1149,86 @@ fn test_original_order_id() -> eyre::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_build_n_blocks() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(11))?;
+
+    let mut nonces_before = Vec::new();
+    let blocks = test_setup.build_n_blocks(3, |test_setup| {
+        nonces_before.push(test_setup.current_nonce(NamedAddr::User(0))?);
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_dummy_tx_0_1_no_rev()?;
+        Ok(vec![test_setup.build_order()])
+    })?;
+
+    assert_eq!(
+        blocks.iter().map(|b| b.block_number).collect::<Vec<_>>(),
+        vec![11, 12, 13]
+    );
+    for block in &blocks {
+        assert_eq!(block.tx_count, 1);
+        assert_eq!(block.gas_used, BASE_TX_GAS);
+    }
+    assert_eq!(nonces_before, vec![0, 1, 2]);
+    assert_eq!(test_setup.current_nonce(NamedAddr::User(0))?, 3);
+
+    // every block builds on top of the previous one
+    for pair in blocks.windows(2) {
+        let parent = test_setup
+            .header(pair[1].parent_hash)?
+            .expect("parent header in db");
+        assert_eq!(parent.number, pair[0].block_number);
+        assert_eq!(parent.parent_hash, pair[0].parent_hash);
+    }
+    Ok(())
+}
+
+#[test]
+fn test_checkpoint_rollback() -> eyre::Result<()> {
+    let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default())?;
+
+    // common prefix
+    test_setup.begin_bundle_order(test_setup.block_number());
+    test_setup.add_dummy_tx_0_1_no_rev()?;
+    test_setup.commit_order_ok();
+
+    let checkpoint = test_setup.checkpoint()?;
+    let txs_before = test_setup.block_txs().to_vec();
+    let receipts_before = test_setup.partial_block().receipts.clone();
+    let gas_used_before = test_setup.partial_block().gas_used;
+    let coinbase_profit_before = test_setup.partial_block().coinbase_profit;
+    let bundle_state_before = test_setup.bundle_state();
+
+    // each tail uses the same nonces so it only commits if the rollback restored the state
+    for tail_value in [1, 2] {
+        test_setup.begin_bundle_order(test_setup.block_number());
+        test_setup.add_dummy_tx(
+            NamedAddr::User(1),
+            NamedAddr::User(2),
+            tail_value,
+            TxRevertBehavior::NotAllowed,
+        )?;
+        test_setup.commit_order_ok();
+        test_setup.begin_mempool_tx_order();
+        test_setup.add_send_to_coinbase_tx(NamedAddr::User(2), 1000 * tail_value)?;
+        test_setup.commit_order_ok();
+        assert_eq!(test_setup.block_txs().len(), 3);
+        assert_eq!(test_setup.current_nonce(NamedAddr::User(1))?, 1);
+        assert_ne!(test_setup.bundle_state(), bundle_state_before);
+
+        test_setup.rollback_to(&checkpoint)?;
+        assert_eq!(test_setup.block_txs(), txs_before.as_slice());
+        assert_eq!(test_setup.partial_block().receipts, receipts_before);
+        assert_eq!(test_setup.partial_block().gas_used, gas_used_before);
+        assert_eq!(
+            test_setup.partial_block().coinbase_profit,
+            coinbase_profit_before
+        );
+        assert_eq!(test_setup.bundle_state(), bundle_state_before);
+        assert_eq!(test_setup.current_nonce(NamedAddr::User(0))?, 1);
+        assert_eq!(test_setup.current_nonce(NamedAddr::User(1))?, 0);
+        assert_eq!(test_setup.current_nonce(NamedAddr::User(2))?, 0);
+    }
+    Ok(())
+}
@@ -4,17 +4,33 @@
 //! test setup is used to build orders and commit them
 use crate::{
     building::{
+        block_orders_from_sim_orders,
+        builders::ordering_builder::{OrderingBuilderConfig, OrderingBuilderContext},
+        sim::simulate_all_orders_with_sim_tree,
         testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
-        BlockState, ExecutionError, ExecutionResult, OrderErr, PartialBlock,
+        BlockState, CoinbaseMode, ExecutionError, ExecutionResult, InsertPayoutTxErr, OrderErr,
+        PartialBlock, PartialBlockCheckpoint, PartialBlockFork, RefundLedger,
     },
     primitives::{
-        order_builder::OrderBuilder, BundleReplacementData, OrderId, Refund, RefundConfig,
+        order_builder::OrderBuilder, BundleReplacementData, Order, OrderId, Refund, RefundConfig,
         SimulatedOrder, TransactionSignedEcRecoveredWithBlobs, TxRevertBehavior,
     },
+    test_utils::StaticBidder,
+};
+use alloy_primitives::{Address, TxHash, B256};
+use eyre::eyre;
+use reth::{
+    primitives::{
+        kzg::{Blob, BYTES_PER_BLOB},
+        BlobTransactionSidecar, Header, SealedBlock, Transaction, TransactionKind,
+        TransactionSigned, TransactionSignedEcRecovered, TxEip4844,
+    },
+    providers::HeaderProvider,
+    tasks::pool::BlockingTaskPool,
 };
-use alloy_primitives::{Address, TxHash};
 use reth_payload_builder::database::CachedReads;
 use revm::db::BundleState;
+use std::sync::Arc;
 
 pub enum NonceValue {
     /// Fixed value
@@ -23,6 +39,15 @@ pub enum NonceValue {
     Relative(u64),
 }
 
+/// Summary of a block built by [`TestSetup::build_n_blocks`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SequentialBlock {
+    pub block_number: u64,
+    pub parent_hash: B256,
+    pub gas_used: u64,
+    pub tx_count: usize,
+}
+
 #[derive(Debug)]
 pub struct TestSetup {
     partial_block: PartialBlock<()>,
@@ -46,6 +71,134 @@ impl TestSetup {
     pub fn named_address(&self, named_addr: NamedAddr) -> eyre::Result<Address> {
         self.test_chain.named_address(named_addr)
     }
+
+    pub fn block_number(&self) -> u64 {
+        self.test_chain
+            .block_building_context()
+            .block_env
+            .number
+            .to()
+    }
+
+    pub fn parent_hash(&self) -> B256 {
+        self.test_chain.parent_hash()
+    }
+
+    pub fn header(&self, hash: B256) -> eyre::Result<Option<Header>> {
+        Ok(self.test_chain.provider_factory().header(&hash)?)
+    }
+
+    pub fn chain_id(&self) -> u64 {
+        self.test_chain
+            .block_building_context()
+            .initialized_cfg
+            .cfg_env
+            .chain_id
+    }
+
+    /// Builds n sequential blocks with the ordering builder (build_block), each one on top of the state left by the previous one.
+    /// Every block is landed (its changes written to the db) before moving to the next one and the builder cached reads
+    /// are carried forward (updated with the landed changes) so nonces and balances advance.
+    /// orders_for_block is called once per block to create its orders (eg: with build_order).
+    pub fn build_n_blocks(
+        &mut self,
+        n: usize,
+        mut orders_for_block: impl FnMut(&mut Self) -> eyre::Result<Vec<Order>>,
+    ) -> eyre::Result<Vec<SequentialBlock>> {
+        self.bundle_state = None;
+        let mut blocks = Vec::with_capacity(n);
+        for i in 0..n {
+            if i != 0 {
+                self.test_chain.advance_block()?;
+            }
+            let orders = orders_for_block(self)?;
+            let provider_factory = self.test_chain.provider_factory().clone();
+            let ctx = self.test_chain.block_building_context().clone();
+            let (sim_orders, _) =
+                simulate_all_orders_with_sim_tree(provider_factory.clone(), &ctx, &orders, false)?;
+            let config = OrderingBuilderConfig::default();
+            let block_orders = block_orders_from_sim_orders(
+                &sim_orders,
+                config.sorting,
+                &provider_factory.history_by_block_hash(ctx.attributes.parent)?,
+                &[],
+            )?;
+            let mut builder = OrderingBuilderContext::new(
+                provider_factory,
+                Arc::new(StaticBidder::full_value()),
+                BlockingTaskPool::build()?,
+                "build_n_blocks".to_string(),
+                ctx,
+                config,
+            )
+            .with_skip_root_hash()
+            .with_cached_reads(self.cached_reads.take().unwrap_or_default());
+            let block = builder
+                .build_block(block_orders, CoinbaseMode::SuggestedFeeRecipient)?
+                .ok_or_else(|| eyre!("Block {} not built", self.block_number()))?;
+            self.cached_reads = builder.take_cached_reads();
+            self.land_block(&block.sealed_block)?;
+            blocks.push(SequentialBlock {
+                block_number: block.sealed_block.number,
+                parent_hash: block.sealed_block.parent_hash,
+                gas_used: block.sealed_block.gas_used,
+                tx_count: block.sealed_block.body.len(),
+            });
+        }
+        Ok(blocks)
+    }
+
+    /// Executes block on top of the current parent state and writes its changes to the db.
+    /// The carried cached reads are updated with the changes so they stay valid for the next block.
+    fn land_block(&mut self, block: &SealedBlock) -> eyre::Result<()> {
+        let ctx = self.test_chain.block_building_context();
+        let state_provider = self.test_chain.provider_factory().latest()?;
+        let mut state = BlockState::new(&state_provider);
+        let mut partial_block = PartialBlock::new(true, None);
+        partial_block.pre_block_call(ctx, &mut state)?;
+        {
+            let mut fork = PartialBlockFork::new(&mut state);
+            for tx in &block.body {
+                let tx = TransactionSignedEcRecoveredWithBlobs {
+                    tx: tx
+                        .clone()
+                        .try_ecrecovered()
+                        .ok_or_else(|| eyre!("Failed to recover tx {:?}", tx.hash()))?,
+                    blobs_sidecar: Default::default(),
+                    metadata: Default::default(),
+                };
+                let ok = fork
+                    .commit_tx(
+                        &tx,
+                        ctx,
+                        partial_block.gas_used,
+                        0,
+                        partial_block.blob_gas_used,
+                    )?
+                    .map_err(|err| eyre!("Landed tx {:?} failed: {:?}", tx.hash(), err))?;
+                partial_block.gas_used = ok.cumulative_gas_used;
+                partial_block.blob_gas_used = ok.cumulative_blob_gas_used;
+            }
+        }
+        let (_, bundle) = state.into_parts();
+        self.test_chain.commit_state(&bundle)?;
+        if let Some(cached_reads) = &mut self.cached_reads {
+            for (address, account) in bundle.state() {
+                if let Some(info) = &account.info {
+                    cached_reads.insert_account(
+                        *address,
+                        info.clone(),
+                        account
+                            .storage
+                            .iter()
+                            .map(|(slot, value)| (*slot, value.present_value))
+                            .collect(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
     // Build order methods
 
     pub fn begin_mempool_tx_order(&mut self) {
@@ -73,6 +226,11 @@ impl TestSetup {
             .set_bundle_replacement_data(replacement_data);
     }
 
+    pub fn set_bundle_max_block_position(&mut self, max_block_position: Option<usize>) {
+        self.order_builder
+            .set_bundle_max_block_position(max_block_position);
+    }
+
     // Share bundle methods
 
     pub fn start_inner_bundle(&mut self, can_skip: bool) {
@@ -147,6 +305,96 @@ impl TestSetup {
         Ok(tx_hash)
     }
 
+    /// Contract creation with initcode_size bytes of (never executed) initcode
+    pub fn add_create_tx(&mut self, from: NamedAddr, initcode_size: usize) -> eyre::Result<TxHash> {
+        self.add_tx(
+            TxArgs::new_create(from, self.current_nonce(from)?, vec![0; initcode_size])
+                .gas_limit(10_000_000),
+            TxRevertBehavior::NotAllowed,
+        )
+    }
+
+    /// Call to user 1 with input_size bytes of calldata
+    pub fn add_large_tx(&mut self, from: NamedAddr, input_size: usize) -> eyre::Result<TxHash> {
+        self.add_tx(
+            TxArgs::new(from, self.current_nonce(from)?)
+                .to(NamedAddr::User(1))
+                .input(vec![1; input_size])
+                .gas_limit(10_000_000),
+            TxRevertBehavior::NotAllowed,
+        )
+    }
+
+    /// Unsigned blob tx with blob_count (zeroed) blobs, never meant to execute
+    pub fn add_fake_blob_tx(&mut self, from: NamedAddr, blob_count: usize) -> eyre::Result<TxHash> {
+        let tx = TxEip4844 {
+            chain_id: self.chain_id(),
+            nonce: self.current_nonce(from)?,
+            blob_versioned_hashes: vec![B256::ZERO; blob_count],
+            ..Default::default()
+        };
+        self.add_unsigned_blob_tx(from, tx)
+    }
+
+    /// Unsigned (the evm only needs the sender) single blob tx to NamedAddr::User(1) paying max_priority_fee tip
+    pub fn add_blob_tx(
+        &mut self,
+        from: NamedAddr,
+        max_priority_fee: u128,
+        max_fee_per_blob_gas: u128,
+    ) -> eyre::Result<TxHash> {
+        let tx = TxEip4844 {
+            chain_id: self.chain_id(),
+            nonce: self.current_nonce(from)?,
+            gas_limit: 21_000,
+            max_fee_per_gas: max_priority_fee + 1_000_000_000,
+            max_priority_fee_per_gas: max_priority_fee,
+            to: TransactionKind::Call(self.named_address(NamedAddr::User(1))?),
+            // version 0x01
+            blob_versioned_hashes: vec![B256::repeat_byte(0x01)],
+            max_fee_per_blob_gas,
+            ..Default::default()
+        };
+        self.add_unsigned_blob_tx(from, tx)
+    }
+
+    /// The sidecar has as many (zeroed) blobs as tx.blob_versioned_hashes
+    fn add_unsigned_blob_tx(&mut self, from: NamedAddr, tx: TxEip4844) -> eyre::Result<TxHash> {
+        let blob_count = tx.blob_versioned_hashes.len();
+        let tx = TransactionSignedEcRecovered::from_signed_transaction(
+            TransactionSigned::from_transaction_and_signature(
+                Transaction::Eip4844(tx),
+                Default::default(),
+            ),
+            self.named_address(from)?,
+        );
+        let tx_hash = tx.hash;
+        let sidecar = BlobTransactionSidecar {
+            blobs: vec![Blob::from([0u8; BYTES_PER_BLOB]); blob_count],
+            ..Default::default()
+        };
+        self.order_builder.add_tx(
+            TransactionSignedEcRecoveredWithBlobs {
+                tx,
+                blobs_sidecar: Arc::new(sidecar),
+                metadata: Default::default(),
+            },
+            TxRevertBehavior::NotAllowed,
+        );
+        Ok(tx_hash)
+    }
+
+    /// Like add_dummy_tx_0_1_no_rev but signed for chain_id
+    pub fn add_dummy_tx_0_1_chain_id(&mut self, chain_id: u64) -> eyre::Result<TxHash> {
+        let from = NamedAddr::User(0);
+        self.add_tx(
+            TxArgs::new(from, self.current_nonce(from)?)
+                .to(NamedAddr::User(1))
+                .chain_id(chain_id),
+            TxRevertBehavior::NotAllowed,
+        )
+    }
+
     pub fn add_send_to_coinbase_tx(&mut self, from: NamedAddr, value: u64) -> eyre::Result<TxHash> {
         self.add_tx(
             TxArgs::new_send_to_coinbase(from, self.current_nonce(from)?, value),
@@ -165,6 +413,34 @@ impl TestSetup {
         )
     }
 
+    /// Calls NamedAddr::LogEmitter, the receipt gets a log with LOG_EMITTER_TOPIC
+    pub fn add_emit_log_tx(&mut self, from: NamedAddr) -> eyre::Result<TxHash> {
+        self.add_tx(
+            TxArgs::new(from, self.current_nonce(from)?).to(NamedAddr::LogEmitter),
+            TxRevertBehavior::NotAllowed,
+        )
+    }
+
+    /// Calls NamedAddr::BlockDataChecker, reverts unless BLOCKHASH(number) == block_hash and PREVRANDAO & 1 == prev_randao_parity
+    pub fn add_check_block_data_tx(
+        &mut self,
+        from: NamedAddr,
+        number: u64,
+        block_hash: B256,
+        prev_randao_parity: u64,
+    ) -> eyre::Result<TxHash> {
+        self.add_tx(
+            TxArgs::new_check_block_data(
+                from,
+                self.current_nonce(from)?,
+                number,
+                block_hash,
+                prev_randao_parity,
+            ),
+            TxRevertBehavior::NotAllowed,
+        )
+    }
+
     /// This transaction will send value to `to` address through the intermediary contract
     pub fn add_mev_test_send_to_tx(
         &mut self,
@@ -206,13 +482,21 @@ impl TestSetup {
         )
     }
     fn try_commit_order(&mut self) -> eyre::Result<Result<ExecutionResult, ExecutionError>> {
+        let order = self.build_order();
+        self.try_commit_given_order(order)
+    }
+
+    fn try_commit_given_order(
+        &mut self,
+        order: Order,
+    ) -> eyre::Result<Result<ExecutionResult, ExecutionError>> {
         let state_provider = self.test_chain.provider_factory().latest()?;
         let mut block_state = BlockState::new(&state_provider)
             .with_bundle_state(self.bundle_state.take().unwrap_or_default())
             .with_cached_reads(self.cached_reads.take().unwrap_or_default());
 
         let sim_order = SimulatedOrder {
-            order: self.order_builder.build_order(),
+            order,
             sim_value: Default::default(),
             prev_order: Default::default(),
             used_state_trace: Default::default(),
@@ -231,11 +515,83 @@ impl TestSetup {
         Ok(result)
     }
 
+    /// End of block refund processing (as finalize_block_execution does before the payout tx).
+    pub fn pay_and_verify_refunds(&mut self) -> eyre::Result<Result<(), InsertPayoutTxErr>> {
+        let state_provider = self.test_chain.provider_factory().latest()?;
+        let mut block_state = BlockState::new(&state_provider)
+            .with_bundle_state(self.bundle_state.take().unwrap_or_default())
+            .with_cached_reads(self.cached_reads.take().unwrap_or_default());
+
+        let ctx = self.test_chain.block_building_context();
+        let result = self
+            .partial_block
+            .pay_deferred_refunds(ctx, &mut block_state)
+            .and_then(|_| Ok(self.partial_block.verify_refunds(ctx)?));
+
+        let (cached_reads, bundle_state) = block_state.into_parts();
+        self.cached_reads = Some(cached_reads);
+        self.bundle_state = Some(bundle_state);
+
+        Ok(result)
+    }
+
+    pub fn checkpoint(&self) -> eyre::Result<PartialBlockCheckpoint<()>> {
+        let state_provider = self.test_chain.provider_factory().latest()?;
+        let block_state = BlockState::new(&state_provider)
+            .with_bundle_state(self.bundle_state.clone().unwrap_or_default());
+        Ok(self.partial_block.checkpoint(&block_state))
+    }
+
+    pub fn rollback_to(&mut self, checkpoint: &PartialBlockCheckpoint<()>) -> eyre::Result<()> {
+        let state_provider = self.test_chain.provider_factory().latest()?;
+        let mut block_state = BlockState::new(&state_provider)
+            .with_bundle_state(self.bundle_state.take().unwrap_or_default())
+            .with_cached_reads(self.cached_reads.take().unwrap_or_default());
+
+        self.partial_block.rollback_to(checkpoint, &mut block_state);
+
+        let (cached_reads, bundle_state) = block_state.into_parts();
+        self.cached_reads = Some(cached_reads);
+        self.bundle_state = Some(bundle_state);
+        Ok(())
+    }
+
+    /// Bundle state of the committed orders.
+    pub fn bundle_state(&self) -> BundleState {
+        self.bundle_state.clone().unwrap_or_default()
+    }
+
+    pub fn partial_block(&self) -> &PartialBlock<()> {
+        &self.partial_block
+    }
+
+    /// Txs committed so far
+    pub fn block_txs(&self) -> &[TransactionSignedEcRecoveredWithBlobs] {
+        &self.partial_block.executed_tx
+    }
+
+    pub fn refund_ledger(&self) -> &RefundLedger {
+        &self.partial_block.refund_ledger
+    }
+
     pub fn commit_order_ok(&mut self) -> ExecutionResult {
         let res = self.try_commit_order().expect("Failed to commit order");
         res.expect("Order commit failed")
     }
 
+    /// Finishes the order being built without committing it (eg: to send it through its submission format).
+    pub fn build_order(&mut self) -> Order {
+        self.order_builder.build_order()
+    }
+
+    /// Same as commit_order_ok for an order built elsewhere.
+    pub fn commit_given_order_ok(&mut self, order: Order) -> ExecutionResult {
+        let res = self
+            .try_commit_given_order(order)
+            .expect("Failed to commit order");
+        res.expect("Order commit failed")
+    }
+
     pub fn commit_order_err(&mut self, expected_error: &str) {
         let res = self.try_commit_order().expect("Failed to commit order");
         match res {
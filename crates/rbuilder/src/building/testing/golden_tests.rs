@@ -0,0 +1,251 @@
+//! Golden tests for the ordering builder.
+//! Each scenario builds a block from a fixed seed (see [`BlockArgs::seed`]), fixed block args and a fixed order set
+//! and compares the sealed block hash with the one stored on GOLDEN_FILE.
+//! A scenario missing from GOLDEN_FILE or building a different block fails. New scenarios and intentional behavior
+//! changes are recorded running the tests with RBUILDER_UPDATE_GOLDEN=1, commit the updated file.
+//! The fill path is deterministic as long as equal priority orders are always broken by OrderId (see OrderPriority).
+use crate::{
+    building::{
+        builders::ordering_builder::{OrderingBuilderConfig, OrderingBuilderContext},
+        sim::simulate_all_orders_with_sim_tree,
+        testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
+        BlockOrders, CoinbaseMode, Sorting,
+    },
+    primitives::{
+        order_builder::OrderBuilder, Order, TransactionSignedEcRecoveredWithBlobs, TxRevertBehavior,
+    },
+    test_utils::StaticBidder,
+};
+use alloy_primitives::B256;
+use reth::tasks::pool::BlockingTaskPool;
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::PathBuf,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+const GOLDEN_FILE: &str = "src/building/testing/golden_block_hashes.txt";
+const UPDATE_GOLDEN_ENV: &str = "RBUILDER_UPDATE_GOLDEN";
+const SEED: u64 = 0x5eed;
+const BLOCK_NUMBER: u64 = 1;
+const BLOCK_TIMESTAMP: u64 = 1_000;
+const GWEI: u128 = 1_000_000_000;
+
+/// Tests run in parallel and all of them share GOLDEN_FILE.
+static GOLDEN_FILE_LOCK: Mutex<()> = Mutex::new(());
+
+fn golden_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join(GOLDEN_FILE)
+}
+
+/// Header of GOLDEN_FILE, kept on updates.
+const GOLDEN_FILE_HEADER: &str =
+    "# scenario_name block_hash, see golden_tests.rs. Updated with RBUILDER_UPDATE_GOLDEN=1\n";
+
+/// "scenario_name hash" lines, # starts a comment line.
+fn read_golden_hashes() -> BTreeMap<String, B256> {
+    let Ok(data) = fs::read_to_string(golden_path()) else {
+        return BTreeMap::new();
+    };
+    data.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (name, hash) = line
+                .split_once(' ')
+                .unwrap_or_else(|| panic!("Bad golden line: {}", line));
+            (
+                name.to_string(),
+                B256::from_str(hash.trim()).expect("Bad golden hash"),
+            )
+        })
+        .collect()
+}
+
+fn write_golden_hash(scenario: &str, hash: B256) {
+    let mut hashes = read_golden_hashes();
+    hashes.insert(scenario.to_string(), hash);
+    let data: String = std::iter::once(GOLDEN_FILE_HEADER.to_string())
+        .chain(
+            hashes
+                .iter()
+                .map(|(name, hash)| format!("{} {:?}\n", name, hash)),
+        )
+        .collect();
+    fs::write(golden_path(), data).expect("Failed to write golden file");
+}
+
+fn assert_golden_hash(scenario: &str, hash: B256) {
+    let _guard = GOLDEN_FILE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if std::env::var(UPDATE_GOLDEN_ENV).is_ok() {
+        write_golden_hash(scenario, hash);
+        return;
+    }
+    match read_golden_hashes().get(scenario) {
+        Some(golden) => assert_eq!(
+            *golden, hash,
+            "Block for scenario {} changed, if this is intended run with {}=1",
+            scenario, UPDATE_GOLDEN_ENV
+        ),
+        None => panic!(
+            "Scenario {} not found on {}, record it running with {}=1",
+            scenario, GOLDEN_FILE, UPDATE_GOLDEN_ENV
+        ),
+    }
+}
+
+fn test_config() -> OrderingBuilderConfig {
+    OrderingBuilderConfig {
+        discard_txs: true,
+        sorting: Sorting::MaxProfit,
+        failed_order_retries: 1,
+        drop_failed_orders: true,
+        ..Default::default()
+    }
+}
+
+fn mempool_tx(test_chain: &TestChainState, args: TxArgs) -> eyre::Result<Order> {
+    let tx = test_chain.sign_tx(args)?;
+    let mut order_builder = OrderBuilder::None;
+    order_builder.start_mempool_tx_builder();
+    order_builder.add_tx(
+        TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+        TxRevertBehavior::AllowedIncluded,
+    );
+    Ok(order_builder.build_order())
+}
+
+/// Simulates the orders and builds a block, returns the sealed block hash.
+fn build_block_hash(
+    orders: impl FnOnce(&TestChainState) -> eyre::Result<Vec<Order>>,
+    coinbase_mode: CoinbaseMode,
+) -> eyre::Result<B256> {
+    let test_chain = TestChainState::new(
+        BlockArgs::default()
+            .number(BLOCK_NUMBER)
+            .timestamp(BLOCK_TIMESTAMP)
+            .seed(SEED),
+    )?;
+    let orders = orders(&test_chain)?;
+    let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+        test_chain.provider_factory().clone(),
+        test_chain.block_building_context(),
+        &orders,
+        false,
+    )?;
+    let mut block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+    for order in sim_orders {
+        block_orders.add_order(order);
+    }
+
+    let mut builder = OrderingBuilderContext::new(
+        test_chain.provider_factory().clone(),
+        Arc::new(StaticBidder::full_value()),
+        BlockingTaskPool::build()?,
+        "golden".to_string(),
+        test_chain.block_building_context().clone(),
+        test_config(),
+    )
+    .with_skip_root_hash();
+    let block = builder
+        .build_block(block_orders, coinbase_mode)?
+        .expect("block should be built");
+    Ok(block.sealed_block.hash())
+}
+
+fn check_scenario(
+    scenario: &str,
+    orders: impl Fn(&TestChainState) -> eyre::Result<Vec<Order>>,
+    coinbase_mode: CoinbaseMode,
+) -> eyre::Result<()> {
+    let hash = build_block_hash(&orders, coinbase_mode)?;
+    // same inputs in the same process must give the same block before comparing across commits
+    assert_eq!(hash, build_block_hash(&orders, coinbase_mode)?);
+    assert_golden_hash(scenario, hash);
+    Ok(())
+}
+
+#[test]
+fn test_golden_empty_block() -> eyre::Result<()> {
+    check_scenario(
+        "empty_block",
+        |_| Ok(Vec::new()),
+        CoinbaseMode::SuggestedFeeRecipient,
+    )
+}
+
+#[test]
+fn test_golden_mempool_txs() -> eyre::Result<()> {
+    check_scenario(
+        "mempool_txs",
+        |test_chain| {
+            (0..4)
+                .map(|user| {
+                    mempool_tx(
+                        test_chain,
+                        TxArgs::new(NamedAddr::User(user), 0)
+                            .to(NamedAddr::User(4))
+                            .value(1_000)
+                            .max_fee_per_gas(10 * GWEI)
+                            .max_priority_fee((user as u128 + 1) * GWEI),
+                    )
+                })
+                .collect()
+        },
+        CoinbaseMode::default(),
+    )
+}
+
+#[test]
+fn test_golden_bundle_and_competing_txs() -> eyre::Result<()> {
+    check_scenario(
+        "bundle_and_competing_txs",
+        |test_chain| {
+            let mut order_builder = OrderBuilder::None;
+            order_builder.start_bundle_builder(BLOCK_NUMBER);
+            order_builder.add_tx(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(
+                    test_chain.sign_tx(
+                        TxArgs::new_send_to_coinbase(NamedAddr::User(0), 0, 1_000_000)
+                            .max_fee_per_gas(10 * GWEI),
+                    )?,
+                )
+                .unwrap(),
+                TxRevertBehavior::NotAllowed,
+            );
+            order_builder.add_tx(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(
+                    test_chain.sign_tx(
+                        TxArgs::new(NamedAddr::User(1), 0)
+                            .to(NamedAddr::User(2))
+                            .max_fee_per_gas(10 * GWEI),
+                    )?,
+                )
+                .unwrap(),
+                TxRevertBehavior::AllowedExcluded,
+            );
+            let bundle = order_builder.build_order();
+
+            Ok(vec![
+                bundle,
+                // same nonce as the bundle first tx
+                mempool_tx(
+                    test_chain,
+                    TxArgs::new(NamedAddr::User(0), 0)
+                        .to(NamedAddr::User(3))
+                        .max_fee_per_gas(10 * GWEI)
+                        .max_priority_fee(GWEI),
+                )?,
+                mempool_tx(
+                    test_chain,
+                    TxArgs::new(NamedAddr::User(3), 0)
+                        .to(NamedAddr::User(4))
+                        .max_fee_per_gas(10 * GWEI)
+                        .max_priority_fee(2 * GWEI),
+                )?,
+            ])
+        },
+        CoinbaseMode::default(),
+    )
+}
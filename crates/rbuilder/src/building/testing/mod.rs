@@ -1,3 +1,5 @@
 #[cfg(test)]
 pub mod bundle_tests;
+#[cfg(test)]
+mod golden_tests;
 pub mod test_chain_state;
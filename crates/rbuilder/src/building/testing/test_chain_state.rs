@@ -1,12 +1,14 @@
 use ahash::HashSet;
 use alloy_primitives::{keccak256, utils::parse_ether, Address, BlockHash, Bytes, B256, U256};
 use lazy_static::lazy_static;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use reth::{
     primitives::{
-        Account, BlockBody, Bytecode, ChainSpec, Header, SealedBlock, TransactionKind,
-        TransactionSignedEcRecovered, TxEip1559, MAINNET,
+        stage::{StageCheckpoint, StageId},
+        Account, BlockBody, Bytecode, ChainSpec, Header, SealedBlock, StorageEntry,
+        TransactionKind, TransactionSignedEcRecovered, TxEip1559, MAINNET,
     },
-    providers::{test_utils::create_test_provider_factory, ProviderFactory},
+    providers::{test_utils::create_test_provider_factory, ProviderFactory, StageCheckpointWriter},
     rpc::types::{
         beacon::events::{PayloadAttributesData, PayloadAttributesEvent},
         engine::PayloadAttributes,
@@ -14,12 +16,26 @@ use reth::{
     },
 };
 use reth_db::{
-    cursor::DbCursorRW, tables, test_utils::TempDatabase, transaction::DbTxMut, DatabaseEnv,
+    cursor::{DbCursorRO, DbCursorRW, DbDupCursorRO},
+    tables,
+    test_utils::TempDatabase,
+    transaction::{DbTx, DbTxMut},
+    DatabaseEnv,
+};
+use revm::{
+    db::BundleState,
+    primitives::{SpecId, KECCAK_EMPTY},
 };
-use revm::primitives::SpecId;
 use std::sync::Arc;
+use time::OffsetDateTime;
 
-use crate::{building::BlockBuildingContext, utils::Signer};
+use crate::{
+    building::{
+        token_valuation::ERC20_TRANSFER_TOPIC, BlockBuildingContext, CoinbaseMode, RefundMode,
+        BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE,
+    },
+    utils::Signer,
+};
 
 #[derive(Debug, Clone, Copy)]
 pub enum NamedAddr {
@@ -30,13 +46,129 @@ pub enum NamedAddr {
     User(usize),
     // Dummy address with no money and no code to receive empty txs
     Dummy,
+    /// Contract that emits a single log with LOG_EMITTER_TOPIC on every call
+    LogEmitter,
+    /// Contract that reverts unless the block data matches the calldata (see block_data_checker_code)
+    BlockDataChecker,
+    /// Fake ERC-20 that only emits the Transfer log (see mock_token_code)
+    MockToken,
+    /// Contract that reverts unless the EIP-4788 root for the block timestamp matches the calldata (see beacon_root_checker_code)
+    BeaconRootChecker,
+    /// Contract that takes plain transfers until it's closed by any call with calldata (see refund_sink_code)
+    RefundSink,
+}
+
+pub const LOG_EMITTER_TOPIC: B256 = B256::new([0x42; 32]);
+/// Fixed (not taken from the rng) so seeded chains keep generating the same keys.
+const LOG_EMITTER_ADDRESS: Address = Address::new([0x10; 20]);
+
+/// PUSH32 LOG_EMITTER_TOPIC, PUSH1 0 (size), PUSH1 0 (offset), LOG1, STOP
+fn log_emitter_code() -> Bytes {
+    let mut code = vec![0x7f];
+    code.extend_from_slice(LOG_EMITTER_TOPIC.as_slice());
+    code.extend_from_slice(&[0x60, 0x00, 0x60, 0x00, 0xa1, 0x00]);
+    code.into()
 }
 
-#[derive(Debug, Default)]
+const BLOCK_DATA_CHECKER_ADDRESS: Address = Address::new([0x11; 20]);
+
+/// Calldata: block number, expected block hash, expected PREVRANDAO parity (32 bytes each).
+/// Reverts unless BLOCKHASH(number) == expected hash && PREVRANDAO & 1 == expected parity.
+fn block_data_checker_code() -> Bytes {
+    let code: &[u8] = &[
+        0x60, 0x40, 0x35, // PUSH1 0x40, CALLDATALOAD (expected parity)
+        0x44, 0x60, 0x01, 0x16, // PREVRANDAO, PUSH1 1, AND
+        0x14, // EQ
+        0x60, 0x20, 0x35, // PUSH1 0x20, CALLDATALOAD (expected hash)
+        0x60, 0x00, 0x35, 0x40, // PUSH1 0, CALLDATALOAD (number), BLOCKHASH
+        0x14, // EQ
+        0x16, // AND
+        0x60, 0x18, 0x57, // PUSH1 0x18, JUMPI
+        0x60, 0x00, 0x80, 0xfd, // PUSH1 0, DUP1, REVERT
+        0x5b, 0x00, // JUMPDEST, STOP
+    ];
+    Bytes::copy_from_slice(code)
+}
+
+pub const MOCK_TOKEN_ADDRESS: Address = Address::new([0x12; 20]);
+
+/// Calldata: to, amount (32 bytes each).
+/// Emits Transfer(CALLER, to, amount) without keeping any balance.
+fn mock_token_code() -> Bytes {
+    let mut code = vec![
+        0x60, 0x20, 0x35, 0x60, 0x00,
+        0x52, // PUSH1 0x20, CALLDATALOAD, PUSH1 0, MSTORE (data = amount)
+        0x60, 0x00, 0x35, // PUSH1 0, CALLDATALOAD (topic2 = to)
+        0x33, // CALLER (topic1 = from)
+        0x7f, // PUSH32 (topic0)
+    ];
+    code.extend_from_slice(ERC20_TRANSFER_TOPIC.as_slice());
+    code.extend_from_slice(&[
+        0x60, 0x20, 0x60, 0x00, 0xa3, // PUSH1 0x20 (size), PUSH1 0 (offset), LOG3
+        0x00, // STOP
+    ]);
+    code.into()
+}
+
+const BEACON_ROOT_CHECKER_ADDRESS: Address = Address::new([0x13; 20]);
+
+/// Calldata: expected parent beacon block root (32 bytes).
+/// Reverts unless BEACON_ROOTS_ADDRESS returns the expected root for TIMESTAMP.
+fn beacon_root_checker_code() -> Bytes {
+    let mut code = vec![
+        0x42, 0x5f, 0x52, // TIMESTAMP, PUSH0, MSTORE (args = timestamp)
+        0x60, 0x20, 0x60, 0x20, // PUSH1 0x20 (ret size), PUSH1 0x20 (ret offset)
+        0x60, 0x20, 0x5f, // PUSH1 0x20 (args size), PUSH0 (args offset)
+        0x73, // PUSH20
+    ];
+    code.extend_from_slice(BEACON_ROOTS_ADDRESS.as_slice());
+    code.extend_from_slice(&[
+        0x5a, 0xfa, // GAS, STATICCALL
+        0x60, 0x20, 0x51, // PUSH1 0x20, MLOAD (returned root)
+        0x5f, 0x35, // PUSH0, CALLDATALOAD (expected root)
+        0x14, // EQ
+        0x16, // AND (call succeeded)
+        0x60, 0x2e, 0x57, // PUSH1 0x2e, JUMPI
+        0x5f, 0x80, 0xfd, // PUSH0, DUP1, REVERT
+        0x5b, 0x00, // JUMPDEST, STOP
+    ]);
+    code.into()
+}
+
+const REFUND_SINK_ADDRESS: Address = Address::new([0x14; 20]);
+
+/// Calls with calldata set slot 0 (closed), plain transfers revert once it's set.
+fn refund_sink_code() -> Bytes {
+    let code: &[u8] = &[
+        0x36, 0x15, // CALLDATASIZE, ISZERO
+        0x60, 0x0b, 0x57, // PUSH1 0x0b, JUMPI
+        0x60, 0x01, 0x60, 0x00, 0x55, 0x00, // PUSH1 1, PUSH1 0, SSTORE, STOP
+        0x5b, // JUMPDEST
+        0x60, 0x00, 0x54, // PUSH1 0, SLOAD
+        0x60, 0x13, 0x57, // PUSH1 0x13, JUMPI
+        0x00, // STOP
+        0x5b, 0x60, 0x00, 0x80, 0xfd, // JUMPDEST, PUSH1 0, DUP1, REVERT
+    ];
+    Bytes::copy_from_slice(code)
+}
+
+#[derive(Debug, Default, Clone)]
 pub struct BlockArgs {
     pub number: u64,
     pub timestamp: u64,
     pub use_suggested_fee_recipient_as_coinbase: bool,
+    pub reject_new_account_recipients: bool,
+    /// If Some all the generated keys/addresses come from this seed so built blocks are reproducible.
+    pub seed: Option<u64>,
+    pub prev_randao: B256,
+    pub refund_mode: RefundMode,
+    pub exclude_reverting_txs: bool,
+    pub max_tx_logs_size: Option<usize>,
+    /// If Some the block is a Cancun block (timestamp must be post Cancun on mainnet) with this parent beacon block root.
+    pub parent_beacon_block_root: Option<B256>,
+    /// If Some the parent timestamp, timestamp - 12 if None.
+    /// The attributes are always received 4s after the parent timestamp of the None case (see parent_age).
+    pub parent_timestamp: Option<u64>,
 }
 
 impl BlockArgs {
@@ -48,6 +180,13 @@ impl BlockArgs {
         Self { timestamp, ..self }
     }
 
+    pub fn parent_timestamp(self, parent_timestamp: u64) -> Self {
+        Self {
+            parent_timestamp: Some(parent_timestamp),
+            ..self
+        }
+    }
+
     pub fn use_suggested_fee_recipient_as_coinbase(
         self,
         use_suggested_fee_recipient_as_coinbase: bool,
@@ -57,6 +196,55 @@ impl BlockArgs {
             ..self
         }
     }
+
+    pub fn reject_new_account_recipients(self, reject_new_account_recipients: bool) -> Self {
+        Self {
+            reject_new_account_recipients,
+            ..self
+        }
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn refund_mode(self, refund_mode: RefundMode) -> Self {
+        Self {
+            refund_mode,
+            ..self
+        }
+    }
+
+    pub fn exclude_reverting_txs(self, exclude_reverting_txs: bool) -> Self {
+        Self {
+            exclude_reverting_txs,
+            ..self
+        }
+    }
+
+    pub fn max_tx_logs_size(self, max_tx_logs_size: usize) -> Self {
+        Self {
+            max_tx_logs_size: Some(max_tx_logs_size),
+            ..self
+        }
+    }
+
+    pub fn prev_randao(self, prev_randao: B256) -> Self {
+        Self {
+            prev_randao,
+            ..self
+        }
+    }
+
+    pub fn parent_beacon_block_root(self, parent_beacon_block_root: B256) -> Self {
+        Self {
+            parent_beacon_block_root: Some(parent_beacon_block_root),
+            ..self
+        }
+    }
 }
 
 /// Provides a fully working fake blockchain state with several pre-created accounts and contracts for testing
@@ -70,25 +258,47 @@ pub struct TestChainState {
     blocklisted_address: Signer, //NamedAddr::BlockedAddress
     chain_spec: Arc<ChainSpec>,
     provider_factory: ProviderFactory<Arc<TempDatabase<DatabaseEnv>>>,
+    block_args: BlockArgs,
+    parent_hash: BlockHash,
     block_building_context: BlockBuildingContext,
 }
 impl TestChainState {
     pub fn new(block_args: BlockArgs) -> eyre::Result<Self> {
-        let blocklisted_address = Signer::random();
-        let builder = Signer::random();
-        let fee_recipient = Signer::random();
+        let mut rng = match block_args.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        let mut random_signer = || {
+            Signer::try_from_secret(B256::from(rng.gen::<[u8; 32]>()))
+                .expect("failed to create random signer")
+        };
+        let blocklisted_address = random_signer();
+        let builder = random_signer();
+        let fee_recipient = random_signer();
         let chain_spec = MAINNET.clone();
         let test_accounts = vec![
-            Signer::random(),
-            Signer::random(),
-            Signer::random(),
-            Signer::random(),
-            Signer::random(),
+            random_signer(),
+            random_signer(),
+            random_signer(),
+            random_signer(),
+            random_signer(),
         ];
-        let mev_test_address = Address::random();
-        let dummy_test_address = Address::random();
+        let mev_test_address = Address::from(rng.gen::<[u8; 20]>());
+        let dummy_test_address = Address::from(rng.gen::<[u8; 20]>());
         let test_contracts = TestContracts::load();
         let (mev_test_hash, mev_test_code) = test_contracts.mev_test();
+        let log_emitter_code = log_emitter_code();
+        let log_emitter_hash = keccak256(&log_emitter_code);
+        let block_data_checker_code = block_data_checker_code();
+        let block_data_checker_hash = keccak256(&block_data_checker_code);
+        let mock_token_code = mock_token_code();
+        let mock_token_hash = keccak256(&mock_token_code);
+        let beacon_roots_code = Bytes::from_static(&BEACON_ROOTS_CODE);
+        let beacon_roots_hash = keccak256(&beacon_roots_code);
+        let beacon_root_checker_code = beacon_root_checker_code();
+        let beacon_root_checker_hash = keccak256(&beacon_root_checker_code);
+        let refund_sink_code = refund_sink_code();
+        let refund_sink_hash = keccak256(&refund_sink_code);
         let genesis_header = chain_spec.sealed_genesis_header();
         let provider_factory = create_test_provider_factory();
         {
@@ -132,6 +342,54 @@ impl TestChainState {
                         bytecode_hash: Some(mev_test_hash),
                     },
                 )?;
+                cursor.upsert(
+                    LOG_EMITTER_ADDRESS,
+                    Account {
+                        nonce: 0,
+                        balance: U256::ZERO,
+                        bytecode_hash: Some(log_emitter_hash),
+                    },
+                )?;
+                cursor.upsert(
+                    BLOCK_DATA_CHECKER_ADDRESS,
+                    Account {
+                        nonce: 0,
+                        balance: U256::ZERO,
+                        bytecode_hash: Some(block_data_checker_hash),
+                    },
+                )?;
+                cursor.upsert(
+                    MOCK_TOKEN_ADDRESS,
+                    Account {
+                        nonce: 0,
+                        balance: U256::ZERO,
+                        bytecode_hash: Some(mock_token_hash),
+                    },
+                )?;
+                cursor.upsert(
+                    BEACON_ROOTS_ADDRESS,
+                    Account {
+                        nonce: 0,
+                        balance: U256::ZERO,
+                        bytecode_hash: Some(beacon_roots_hash),
+                    },
+                )?;
+                cursor.upsert(
+                    BEACON_ROOT_CHECKER_ADDRESS,
+                    Account {
+                        nonce: 0,
+                        balance: U256::ZERO,
+                        bytecode_hash: Some(beacon_root_checker_hash),
+                    },
+                )?;
+                cursor.upsert(
+                    REFUND_SINK_ADDRESS,
+                    Account {
+                        nonce: 0,
+                        balance: U256::ZERO,
+                        bytecode_hash: Some(refund_sink_hash),
+                    },
+                )?;
             }
             {
                 let mut cursor = provider
@@ -139,11 +397,23 @@ impl TestChainState {
                     .cursor_write::<tables::Bytecodes>()
                     .unwrap();
                 cursor.upsert(mev_test_hash, Bytecode::new_raw(mev_test_code))?;
+                cursor.upsert(log_emitter_hash, Bytecode::new_raw(log_emitter_code))?;
+                cursor.upsert(
+                    block_data_checker_hash,
+                    Bytecode::new_raw(block_data_checker_code),
+                )?;
+                cursor.upsert(mock_token_hash, Bytecode::new_raw(mock_token_code))?;
+                cursor.upsert(beacon_roots_hash, Bytecode::new_raw(beacon_roots_code))?;
+                cursor.upsert(
+                    beacon_root_checker_hash,
+                    Bytecode::new_raw(beacon_root_checker_code),
+                )?;
+                cursor.upsert(refund_sink_hash, Bytecode::new_raw(refund_sink_code))?;
             }
             provider.commit()?;
         }
         let ctx = TestBlockContextBuilder::new(
-            block_args,
+            block_args.clone(),
             builder.clone(),
             fee_recipient.address,
             chain_spec.clone(),
@@ -161,21 +431,73 @@ impl TestChainState {
             mev_test_address,
             dummy_test_address,
             provider_factory,
+            block_args,
+            parent_hash: genesis_header.hash(),
             block_building_context: ctx,
         })
     }
 
+    /// Moves the block building context to the next block (number + 1, timestamp + 12).
+    /// A header for the current block is inserted in the db and becomes the new parent so the
+    /// blocks are chained by parent_hash. It's also marked as our last synced block so the
+    /// parent state is the plain state.
+    /// State is not modified, callers must carry forward the state changes of the previous block
+    /// (see [`TestChainState::commit_state`]).
+    pub fn advance_block(&mut self) -> eyre::Result<()> {
+        self.block_args = BlockArgs {
+            number: self.block_args.number + 1,
+            timestamp: self.block_args.timestamp + 12,
+            parent_timestamp: Some(self.block_args.timestamp),
+            ..self.block_args.clone()
+        };
+        let parent_header = TestBlockContextBuilder::new(
+            self.block_args.clone(),
+            self.builder.clone(),
+            self.fee_recipient.address,
+            self.chain_spec.clone(),
+            self.blocklisted_address.address,
+            self.parent_hash,
+        )
+        .parent_header()
+        .seal_slow();
+        let provider = self.provider_factory.provider_rw()?;
+        provider.insert_historical_block(
+            SealedBlock::new(parent_header.clone(), BlockBody::default())
+                .try_seal_with_senders()
+                .unwrap(),
+            None,
+        )?;
+        provider
+            .save_stage_checkpoint(StageId::Finish, StageCheckpoint::new(parent_header.number))?;
+        provider.commit()?;
+        self.parent_hash = parent_header.hash();
+        self.block_building_context = TestBlockContextBuilder::new(
+            self.block_args.clone(),
+            self.builder.clone(),
+            self.fee_recipient.address,
+            self.chain_spec.clone(),
+            self.blocklisted_address.address,
+            self.parent_hash,
+        )
+        .build();
+        Ok(())
+    }
+
     // returns signed transaction
     pub fn sign_tx(&self, args: TxArgs) -> eyre::Result<TransactionSignedEcRecovered> {
         let tx = TxEip1559 {
-            chain_id: self.chain_spec.chain.id(),
+            chain_id: args.chain_id.unwrap_or_else(|| self.chain_spec.chain.id()),
             nonce: args.nonce,
             gas_limit: args.gas_limit,
             max_fee_per_gas: args.max_fee_per_gas,
             max_priority_fee_per_gas: args.max_priority_fee,
-            to: TransactionKind::Call(
-                self.named_address(args.to.ok_or_else(|| eyre::eyre!("missing to address"))?)?,
-            ),
+            to: if args.create {
+                TransactionKind::Create
+            } else {
+                TransactionKind::Call(
+                    self.named_address(args.to.ok_or_else(|| eyre::eyre!("missing to address"))?)?,
+                )
+            },
             value: U256::from(args.value),
             access_list: Default::default(),
             input: args.input.into(),
@@ -188,6 +510,11 @@ impl TestChainState {
             NamedAddr::Builder => self.builder.address,
             NamedAddr::MevTest => self.mev_test_address,
             NamedAddr::Dummy => self.dummy_test_address,
+            NamedAddr::LogEmitter => LOG_EMITTER_ADDRESS,
+            NamedAddr::BlockDataChecker => BLOCK_DATA_CHECKER_ADDRESS,
+            NamedAddr::MockToken => MOCK_TOKEN_ADDRESS,
+            NamedAddr::BeaconRootChecker => BEACON_ROOT_CHECKER_ADDRESS,
+            NamedAddr::RefundSink => REFUND_SINK_ADDRESS,
             NamedAddr::BlockedAddress => self.blocklisted_address.address,
             NamedAddr::FeeRecipient => self.fee_recipient.address,
             NamedAddr::User(idx) => {
@@ -203,7 +530,12 @@ impl TestChainState {
         Ok(match named_addr {
             NamedAddr::Builder => &self.builder,
             NamedAddr::MevTest => &self.builder,
-            NamedAddr::Dummy => &self.builder, //Fake
+            NamedAddr::Dummy => &self.builder,             //Fake
+            NamedAddr::LogEmitter => &self.builder,        //Fake
+            NamedAddr::BlockDataChecker => &self.builder,  //Fake
+            NamedAddr::MockToken => &self.builder,         //Fake
+            NamedAddr::BeaconRootChecker => &self.builder, //Fake
+            NamedAddr::RefundSink => &self.builder,        //Fake
             NamedAddr::BlockedAddress => &self.blocklisted_address,
             NamedAddr::FeeRecipient => &self.fee_recipient,
             NamedAddr::User(idx) => self
@@ -212,6 +544,10 @@ impl TestChainState {
                 .ok_or_else(|| eyre::eyre!("invalid user index"))?,
         })
     }
+    pub fn parent_hash(&self) -> BlockHash {
+        self.parent_hash
+    }
+
     pub fn block_building_context(&self) -> &BlockBuildingContext {
         &self.block_building_context
     }
@@ -219,6 +555,106 @@ impl TestChainState {
     pub fn provider_factory(&self) -> &ProviderFactory<Arc<TempDatabase<DatabaseEnv>>> {
         &self.provider_factory
     }
+
+    /// Writes storage slots of address in the parent state (eg: to fake a pool the block reads).
+    /// The account is created (nonce 1, no code) if it doesn't exist.
+    pub fn set_storage(&self, address: Address, slots: &[(U256, U256)]) -> eyre::Result<()> {
+        let provider = self.provider_factory.provider_rw()?;
+        if provider
+            .tx_ref()
+            .get::<tables::PlainAccountState>(address)?
+            .is_none()
+        {
+            provider
+                .tx_ref()
+                .cursor_write::<tables::PlainAccountState>()?
+                .upsert(
+                    address,
+                    Account {
+                        nonce: 1,
+                        balance: U256::ZERO,
+                        bytecode_hash: None,
+                    },
+                )?;
+        }
+        let mut cursor = provider
+            .tx_ref()
+            .cursor_write::<tables::PlainStorageState>()?;
+        for (slot, value) in slots {
+            cursor.upsert(
+                address,
+                StorageEntry {
+                    key: B256::from(*slot),
+                    value: *value,
+                },
+            )?;
+        }
+        drop(cursor);
+        provider.commit()?;
+        Ok(())
+    }
+
+    /// Writes the changes of an executed block (eg: from BlockState::into_parts) to the plain state
+    /// so the next block (see advance_block) builds on top of them. Storage of self destructed accounts is not wiped.
+    pub fn commit_state(&self, bundle: &BundleState) -> eyre::Result<()> {
+        let provider = self.provider_factory.provider_rw()?;
+        {
+            let mut accounts = provider
+                .tx_ref()
+                .cursor_write::<tables::PlainAccountState>()?;
+            let mut storage = provider
+                .tx_ref()
+                .cursor_dup_write::<tables::PlainStorageState>()?;
+            for (address, account) in bundle.state() {
+                if account.is_info_changed() {
+                    match &account.info {
+                        Some(info) => accounts.upsert(
+                            *address,
+                            Account {
+                                nonce: info.nonce,
+                                balance: info.balance,
+                                bytecode_hash: (info.code_hash != KECCAK_EMPTY)
+                                    .then_some(info.code_hash),
+                            },
+                        )?,
+                        None => {
+                            if accounts.seek_exact(*address)?.is_some() {
+                                accounts.delete_current()?;
+                            }
+                        }
+                    }
+                }
+                for (slot, value) in &account.storage {
+                    if !value.is_changed() {
+                        continue;
+                    }
+                    let key = B256::from(*slot);
+                    // dup sorted, upsert would add a second entry for the slot
+                    if storage
+                        .seek_by_key_subkey(*address, key)?
+                        .is_some_and(|entry| entry.key == key)
+                    {
+                        storage.delete_current()?;
+                    }
+                    if value.present_value != U256::ZERO {
+                        storage.upsert(
+                            *address,
+                            StorageEntry {
+                                key,
+                                value: value.present_value,
+                            },
+                        )?;
+                    }
+                }
+            }
+            let mut bytecodes = provider.tx_ref().cursor_write::<tables::Bytecodes>()?;
+            for (hash, code) in &bundle.contracts {
+                bytecodes.upsert(*hash, Bytecode(code.clone()))?;
+            }
+        }
+        provider.commit()?;
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -238,6 +674,12 @@ struct TestBlockContextBuilder {
     blocklist: HashSet<Address>,
     prefer_gas_limit: Option<u64>,
     use_suggested_fee_recipient_as_coinbase: bool,
+    reject_new_account_recipients: bool,
+    prev_randao: B256,
+    refund_mode: RefundMode,
+    exclude_reverting_txs: bool,
+    max_tx_logs_size: Option<usize>,
+    parent_beacon_block_root: Option<B256>,
 }
 
 impl TestBlockContextBuilder {
@@ -251,7 +693,9 @@ impl TestBlockContextBuilder {
     ) -> Self {
         TestBlockContextBuilder {
             parent_gas_limit: 30_000_000,
-            parent_timestamp: block_args.timestamp.checked_sub(12).unwrap_or_default(),
+            parent_timestamp: block_args
+                .parent_timestamp
+                .unwrap_or_else(|| block_args.timestamp.checked_sub(12).unwrap_or_default()),
             block_number: block_args.number,
             parent_base_fee_per_gas: 1,
             builder_signer,
@@ -266,13 +710,64 @@ impl TestBlockContextBuilder {
             prefer_gas_limit: None,
             use_suggested_fee_recipient_as_coinbase: block_args
                 .use_suggested_fee_recipient_as_coinbase,
+            reject_new_account_recipients: block_args.reject_new_account_recipients,
+            prev_randao: block_args.prev_randao,
+            refund_mode: block_args.refund_mode,
+            exclude_reverting_txs: block_args.exclude_reverting_txs,
+            max_tx_logs_size: block_args.max_tx_logs_size,
+            parent_beacon_block_root: block_args.parent_beacon_block_root,
+        }
+    }
+
+    fn cancun(&self) -> bool {
+        self.parent_beacon_block_root.is_some()
+    }
+
+    fn parent_header(&self) -> Header {
+        // parent is a Cancun block too
+        let (blob_gas_used, excess_blob_gas) = if self.cancun() {
+            (Some(0), Some(0))
+        } else {
+            (None, None)
+        };
+        Header {
+            parent_hash: self.parent_hash,
+            ommers_hash: Default::default(),
+            beneficiary: Default::default(),
+            state_root: Default::default(),
+            transactions_root: Default::default(),
+            receipts_root: Default::default(),
+            withdrawals_root: None,
+            logs_bloom: Default::default(),
+            difficulty: Default::default(),
+            number: self.block_number.checked_sub(1).unwrap_or_default(),
+            gas_limit: self.parent_gas_limit,
+            gas_used: self.parent_gas_used,
+            timestamp: self.parent_timestamp,
+            mix_hash: Default::default(),
+            nonce: 0,
+            base_fee_per_gas: Some(self.parent_base_fee_per_gas),
+            blob_gas_used,
+            excess_blob_gas,
+            parent_beacon_block_root: None,
+            extra_data: Default::default(),
         }
     }
 
     fn build(self) -> BlockBuildingContext {
+        let cancun = self.cancun();
+        let spec_id = if cancun {
+            SpecId::CANCUN
+        } else {
+            SpecId::SHANGHAI
+        };
         let mut res = BlockBuildingContext::from_attributes(
             PayloadAttributesEvent {
-                version: self.payload_attributes_version,
+                version: if cancun {
+                    "deneb".to_string()
+                } else {
+                    self.payload_attributes_version
+                },
                 data: PayloadAttributesData {
                     proposal_slot: 1,
                     parent_block_root: Default::default(),
@@ -281,45 +776,31 @@ impl TestBlockContextBuilder {
                     proposer_index: 0,
                     payload_attributes: PayloadAttributes {
                         timestamp: self.slot_timestamp,
-                        prev_randao: Default::default(),
+                        prev_randao: self.prev_randao,
                         suggested_fee_recipient: self.suggested_fee_recipient,
                         withdrawals: self.withdrawals,
-                        parent_beacon_block_root: None,
+                        parent_beacon_block_root: self.parent_beacon_block_root,
                     },
                 },
             },
-            &Header {
-                parent_hash: self.parent_hash,
-                ommers_hash: Default::default(),
-                beneficiary: Default::default(),
-                state_root: Default::default(),
-                transactions_root: Default::default(),
-                receipts_root: Default::default(),
-                withdrawals_root: None,
-                logs_bloom: Default::default(),
-                difficulty: Default::default(),
-                number: self.block_number.checked_sub(1).unwrap_or_default(),
-                gas_limit: self.parent_gas_limit,
-                gas_used: self.parent_gas_used,
-                timestamp: self.parent_timestamp,
-                mix_hash: Default::default(),
-                nonce: 0,
-                base_fee_per_gas: Some(self.parent_base_fee_per_gas),
-                blob_gas_used: None,
-                excess_blob_gas: None,
-                parent_beacon_block_root: None,
-                extra_data: Default::default(),
-            },
+            &self.parent_header(),
             self.builder_signer.clone(),
             self.chain_spec,
             self.blocklist,
             self.prefer_gas_limit,
             vec![],
-            Some(SpecId::SHANGHAI),
+            Some(spec_id),
+            OffsetDateTime::from_unix_timestamp(self.slot_timestamp as i64 - 8)
+                .expect("received_at"),
         );
         if self.use_suggested_fee_recipient_as_coinbase {
-            res.modify_use_suggested_fee_recipient_as_coinbase();
+            res.modify_coinbase_mode(CoinbaseMode::SuggestedFeeRecipient)
+                .expect("SuggestedFeeRecipient never fails");
         }
+        res.modify_reject_new_account_recipients(self.reject_new_account_recipients);
+        res.modify_refund_mode(self.refund_mode);
+        res.modify_exclude_reverting_txs(self.exclude_reverting_txs);
+        res.modify_max_tx_logs_size(self.max_tx_logs_size);
         res
     }
 }
@@ -334,6 +815,10 @@ pub struct TxArgs {
     max_priority_fee: u128,
     gas_limit: u64,
     input: Vec<u8>,
+    /// None -> chain_spec chain id
+    chain_id: Option<u64>,
+    /// Contract creation, to is ignored.
+    create: bool,
 }
 
 impl TxArgs {
@@ -347,6 +832,16 @@ impl TxArgs {
             max_priority_fee: 0,
             gas_limit: 100_000,
             input: Vec::new(),
+            chain_id: None,
+            create: false,
+        }
+    }
+
+    /// Contract creation with initcode as input.
+    pub fn new_create(from: NamedAddr, nonce: u64, initcode: Vec<u8>) -> Self {
+        Self {
+            create: true,
+            ..Self::new(from, nonce).input(initcode)
         }
     }
 
@@ -376,6 +871,51 @@ impl TxArgs {
             .value(value)
     }
 
+    /// Calls NamedAddr::BlockDataChecker, reverts unless BLOCKHASH(number) == block_hash and PREVRANDAO & 1 == prev_randao_parity
+    pub fn new_check_block_data(
+        from: NamedAddr,
+        nonce: u64,
+        number: u64,
+        block_hash: B256,
+        prev_randao_parity: u64,
+    ) -> Self {
+        Self::new(from, nonce)
+            .to(NamedAddr::BlockDataChecker)
+            .input(
+                [
+                    U256::from(number).to_be_bytes_vec(),
+                    block_hash.to_vec(),
+                    U256::from(prev_randao_parity).to_be_bytes_vec(),
+                ]
+                .concat(),
+            )
+    }
+
+    /// Calls NamedAddr::BeaconRootChecker, reverts unless the EIP-4788 root for the block timestamp is root
+    pub fn new_check_beacon_root(from: NamedAddr, nonce: u64, root: B256) -> Self {
+        Self::new(from, nonce)
+            .to(NamedAddr::BeaconRootChecker)
+            .input(root.to_vec())
+    }
+
+    /// Closes NamedAddr::RefundSink, transfers to it revert after this tx
+    pub fn new_close_refund_sink(from: NamedAddr, nonce: u64) -> Self {
+        Self::new(from, nonce)
+            .to(NamedAddr::RefundSink)
+            .input(vec![0x01])
+    }
+
+    /// Calls NamedAddr::MockToken, the receipt gets a Transfer(from, to, amount) log
+    pub fn new_mock_token_transfer(from: NamedAddr, nonce: u64, to: Address, amount: U256) -> Self {
+        Self::new(from, nonce).to(NamedAddr::MockToken).input(
+            [
+                B256::left_padding_from(to.as_slice()).to_vec(),
+                amount.to_be_bytes_vec(),
+            ]
+            .concat(),
+        )
+    }
+
     /// This transaction send value to value_to via a contract
     pub fn new_send_to(from: NamedAddr, nonce: u64, value: u64, value_to: Address) -> Self {
         Self::new(from, nonce)
@@ -427,6 +967,14 @@ impl TxArgs {
     pub fn gas_limit(self, gas_limit: u64) -> Self {
         Self { gas_limit, ..self }
     }
+
+    /// Signs for another chain, used to test replay protection.
+    pub fn chain_id(self, chain_id: u64) -> Self {
+        Self {
+            chain_id: Some(chain_id),
+            ..self
+        }
+    }
 }
 
 static TEST_CONTRACTS: &str = include_str!("./contracts.json");
@@ -0,0 +1,369 @@
+//! Optional valuation of the ERC-20s an order sends to our coinbase (eg: a bundle swapping its payment into the coinbase).
+//! Without it those payments count as 0 and we underbid for the orders paying that way.
+//! The ETH equivalent only goes to the order score ([`crate::primitives::SimValue::token_profit`]), never to the coinbase
+//! profit, so it's never bid: the proposer must be paid in ETH.
+use crate::building::BlockState;
+use alloy_primitives::{address, b256, Address, B256, U256};
+use reth::primitives::Receipt;
+use reth_interfaces::provider::ProviderError;
+use serde::Deserialize;
+use std::{collections::HashMap, sync::Arc};
+use thiserror::Error;
+
+/// keccak256("Transfer(address,address,uint256)")
+pub const ERC20_TRANSFER_TOPIC: B256 =
+    b256!("ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef");
+pub const MAINNET_WETH: Address = address!("C02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2");
+
+/// UniswapV3Pool storage layout.
+const UNIV3_SLOT0_SLOT: u64 = 0;
+const UNIV3_OBSERVATIONS_SLOT: u64 = 8;
+/// Max observations read looking for the start of the twap window, if we don't get there the window is shorter.
+const MAX_OBSERVATIONS_READ: u16 = 64;
+
+fn default_weth() -> Address {
+    MAINNET_WETH
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum TokenPriceSource {
+    /// value = amount * price_wei / 10^decimals
+    Static {
+        /// Wei for a whole token.
+        price_wei: U256,
+        decimals: u8,
+    },
+    /// Time weighted average price of a UniV3 token/WETH pool, read from the pool observations on the same state we
+    /// simulate on.
+    UniV3Twap {
+        pool: Address,
+        #[serde(default = "default_weth")]
+        weth: Address,
+        twap_secs: u32,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct TokenPriceConfig {
+    pub token: Address,
+    #[serde(flatten)]
+    pub source: TokenPriceSource,
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum TokenValuationError {
+    #[error("Token {0:?} configured more than once")]
+    DuplicateToken(Address),
+    #[error("Token {0:?}: twap_secs must be > 0")]
+    ZeroTwapWindow(Address),
+}
+
+/// Cheap to clone, empty (the default) values everything at 0.
+#[derive(Debug, Clone, Default)]
+pub struct TokenValuation {
+    tokens: Arc<HashMap<Address, TokenPriceSource>>,
+}
+
+impl TokenValuation {
+    pub fn new(configs: Vec<TokenPriceConfig>) -> Result<Self, TokenValuationError> {
+        let mut tokens = HashMap::new();
+        for config in configs {
+            if let TokenPriceSource::UniV3Twap { twap_secs: 0, .. } = config.source {
+                return Err(TokenValuationError::ZeroTwapWindow(config.token));
+            }
+            if tokens.insert(config.token, config.source).is_some() {
+                return Err(TokenValuationError::DuplicateToken(config.token));
+            }
+        }
+        Ok(Self {
+            tokens: Arc::new(tokens),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    /// ETH equivalent of the transfers of the configured tokens to any of recipients in the receipts logs.
+    /// state is used to read the twap pools so it should be the state after executing the receipts txs.
+    /// Pools without usable observations value their token at 0.
+    pub fn value_transfers(
+        &self,
+        receipts: &[Receipt],
+        recipients: &[Address],
+        state: &mut BlockState,
+        timestamp: u64,
+    ) -> Result<U256, ProviderError> {
+        let mut value = U256::ZERO;
+        for log in receipts.iter().flat_map(|receipt| receipt.logs.iter()) {
+            let Some(source) = self.tokens.get(&log.address) else {
+                continue;
+            };
+            let topics = log.data.topics();
+            if topics.len() != 3 || topics[0] != ERC20_TRANSFER_TOPIC || log.data.data.len() != 32 {
+                continue;
+            }
+            if !recipients.contains(&Address::from_word(topics[2])) {
+                continue;
+            }
+            let amount = U256::from_be_slice(&log.data.data);
+            let token_value = match source {
+                TokenPriceSource::Static {
+                    price_wei,
+                    decimals,
+                } => amount.saturating_mul(*price_wei) / U256::from(10).pow(U256::from(*decimals)),
+                TokenPriceSource::UniV3Twap {
+                    pool,
+                    weth,
+                    twap_secs,
+                } => match univ3_twap_tick(state, *pool, *twap_secs, timestamp)? {
+                    // price is token1/token0 and token0 is the smaller address
+                    Some(tick) if log.address < *weth => mul_tick_price(amount, tick),
+                    Some(tick) => mul_tick_price(amount, -tick),
+                    None => U256::ZERO,
+                },
+            };
+            value = value.saturating_add(token_value);
+        }
+        Ok(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Slot0 {
+    tick: i32,
+    observation_index: u16,
+    observation_cardinality: u16,
+}
+
+/// sqrtPriceX96 uint160 | tick int24 | observationIndex uint16 | observationCardinality uint16 | ...
+fn decode_slot0(word: U256) -> Slot0 {
+    let bits = |offset: usize, len: usize| -> u64 {
+        ((word >> offset) & ((U256::from(1) << len) - U256::from(1))).to::<u64>()
+    };
+    Slot0 {
+        tick: sign_extend(bits(160, 24), 24) as i32,
+        observation_index: bits(184, 16) as u16,
+        observation_cardinality: bits(200, 16) as u16,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Observation {
+    timestamp: u32,
+    tick_cumulative: i64,
+    initialized: bool,
+}
+
+/// blockTimestamp uint32 | tickCumulative int56 | secondsPerLiquidityCumulativeX128 uint160 | initialized bool
+fn decode_observation(word: U256) -> Observation {
+    let low = word.as_limbs()[0];
+    Observation {
+        timestamp: low as u32,
+        tick_cumulative: sign_extend(low >> 32, 56),
+        initialized: word.bit(248),
+    }
+}
+
+fn sign_extend(value: u64, bits: u32) -> i64 {
+    let shift = 64 - bits;
+    ((value << shift) as i64) >> shift
+}
+
+/// Average tick over the last twap_secs (from the pool observations + the current tick as the pool observe() does).
+fn univ3_twap_tick(
+    state: &mut BlockState,
+    pool: Address,
+    twap_secs: u32,
+    timestamp: u64,
+) -> Result<Option<i32>, ProviderError> {
+    let slot0 = decode_slot0(state.storage(pool, U256::from(UNIV3_SLOT0_SLOT))?);
+    if slot0.observation_cardinality == 0 {
+        return Ok(None);
+    }
+    let observation = |state: &mut BlockState, index: u16| -> Result<Observation, ProviderError> {
+        Ok(decode_observation(state.storage(
+            pool,
+            U256::from(UNIV3_OBSERVATIONS_SLOT + index as u64),
+        )?))
+    };
+    let newest = observation(state, slot0.observation_index)?;
+    if !newest.initialized || timestamp < newest.timestamp as u64 {
+        return Ok(None);
+    }
+    let now_tick_cumulative =
+        newest.tick_cumulative + slot0.tick as i64 * (timestamp - newest.timestamp as u64) as i64;
+    let window_start = timestamp.saturating_sub(twap_secs as u64);
+
+    let mut start = None;
+    for back in 1..slot0.observation_cardinality.min(MAX_OBSERVATIONS_READ) {
+        let index = ((slot0.observation_index as u32 + slot0.observation_cardinality as u32
+            - back as u32)
+            % slot0.observation_cardinality as u32) as u16;
+        let obs = observation(state, index)?;
+        if !obs.initialized || obs.timestamp > newest.timestamp {
+            // not written yet or newer than newest (ring buffer wrapped)
+            break;
+        }
+        start = Some(obs);
+        if obs.timestamp as u64 <= window_start {
+            break;
+        }
+    }
+    let start = match start {
+        Some(start) => start,
+        // only one observation, use the current tick since it
+        None => newest,
+    };
+    let elapsed = timestamp - start.timestamp as u64;
+    if elapsed == 0 {
+        return Ok(Some(slot0.tick));
+    }
+    Ok(Some(
+        (now_tick_cumulative - start.tick_cumulative).div_euclid(elapsed as i64) as i32,
+    ))
+}
+
+/// amount * 1.0001^tick, saturates.
+fn mul_tick_price(amount: U256, tick: i32) -> U256 {
+    let log2_price = tick as f64 * 1.0001f64.log2();
+    let int_part = log2_price.floor();
+    // [1, 2) as Q63
+    let fract = U256::from((2f64.powf(log2_price - int_part) * (1u64 << 63) as f64) as u128);
+    let value = amount.saturating_mul(fract) >> 63;
+    let shift = int_part as i64;
+    if shift >= 0 {
+        if value.leading_zeros() < shift as usize {
+            U256::MAX
+        } else {
+            value << shift as usize
+        }
+    } else {
+        value >> (-shift) as usize
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode_univ3_storage() {
+        // sqrtPriceX96 = 1, tick = -2, observationIndex = 3, observationCardinality = 10
+        let slot0 = U256::from(1)
+            | (U256::from(0xfffffeu64) << 160)
+            | (U256::from(3) << 184)
+            | (U256::from(10) << 200);
+        assert_eq!(
+            decode_slot0(slot0),
+            Slot0 {
+                tick: -2,
+                observation_index: 3,
+                observation_cardinality: 10
+            }
+        );
+
+        // tickCumulative = -100
+        let tick_cumulative = ((-100i64) as u64) & ((1u64 << 56) - 1);
+        let observation = U256::from(1234u64)
+            | (U256::from(tick_cumulative) << 32)
+            | (U256::from(77) << 88)
+            | (U256::from(1) << 248);
+        assert_eq!(
+            decode_observation(observation),
+            Observation {
+                timestamp: 1234,
+                tick_cumulative: -100,
+                initialized: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_mul_tick_price() {
+        let amount = U256::from(10).pow(U256::from(18));
+        assert_eq!(mul_tick_price(amount, 0), amount);
+        let close = |value: U256, expected: f64| {
+            let value: f64 = value.to_string().parse().unwrap();
+            ((value - expected) / expected).abs() < 1e-9
+        };
+        assert!(close(mul_tick_price(amount, 1), 1.0001e18));
+        assert!(close(mul_tick_price(amount, -1), 1e18 / 1.0001));
+        // ~ 1 WETH = 2000 USDC (6 decimals) if USDC is token0
+        let usdc_amount = U256::from(2_000_000_000u64);
+        assert!(close(
+            mul_tick_price(usdc_amount, 200311),
+            2_000_000_000f64 * 1.0001f64.powi(200311)
+        ));
+        assert_eq!(mul_tick_price(U256::MAX, 887272), U256::MAX);
+    }
+
+    #[test]
+    fn test_invalid_configs() {
+        let token = Address::repeat_byte(1);
+        let static_price = TokenPriceSource::Static {
+            price_wei: U256::from(1),
+            decimals: 0,
+        };
+        assert_eq!(
+            TokenValuation::new(vec![
+                TokenPriceConfig {
+                    token,
+                    source: static_price.clone()
+                },
+                TokenPriceConfig {
+                    token,
+                    source: static_price
+                },
+            ])
+            .unwrap_err(),
+            TokenValuationError::DuplicateToken(token)
+        );
+        assert_eq!(
+            TokenValuation::new(vec![TokenPriceConfig {
+                token,
+                source: TokenPriceSource::UniV3Twap {
+                    pool: Address::repeat_byte(2),
+                    weth: MAINNET_WETH,
+                    twap_secs: 0
+                }
+            }])
+            .unwrap_err(),
+            TokenValuationError::ZeroTwapWindow(token)
+        );
+    }
+
+    #[test]
+    fn test_parse_config() {
+        let configs: HashMap<String, Vec<TokenPriceConfig>> = toml::from_str(
+            r#"
+            tokens = [
+                { token = "0x1111111111111111111111111111111111111111", source = "static", price_wei = "500000000000000000", decimals = 18 },
+                { token = "0x2222222222222222222222222222222222222222", source = "uni_v3_twap", pool = "0x3333333333333333333333333333333333333333", twap_secs = 600 },
+            ]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            configs["tokens"],
+            vec![
+                TokenPriceConfig {
+                    token: Address::repeat_byte(0x11),
+                    source: TokenPriceSource::Static {
+                        price_wei: U256::from(500_000_000_000_000_000u64),
+                        decimals: 18
+                    }
+                },
+                TokenPriceConfig {
+                    token: Address::repeat_byte(0x22),
+                    source: TokenPriceSource::UniV3Twap {
+                        pool: Address::repeat_byte(0x33),
+                        weth: MAINNET_WETH,
+                        twap_secs: 600
+                    }
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,233 @@
+//! Block value curve of a slot: how our best bid grows over the slot, to tune the building/submission timings.
+//! Every time the sink of the slot accepts a better block a point is added with the ms elapsed since the sink was
+//! created (that is, since we got the payload attributes).
+//! Points are capped to MAX_VALUE_CURVE_POINTS, once full the last one is replaced so the final value is always there.
+//! When the slot ends its summary goes to the metrics and the curve is kept in [`VALUE_CURVES`] with the ones of the
+//! last FINISHED_VALUE_CURVES slots (served on /debug/value_curve).
+use crate::telemetry::set_value_curve_summary;
+use alloy_primitives::U256;
+use lazy_static::lazy_static;
+use serde::Serialize;
+use serde_with::{serde_as, TimestampMilliSeconds};
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use time::OffsetDateTime;
+
+pub const MAX_VALUE_CURVE_POINTS: usize = 256;
+pub const FINISHED_VALUE_CURVES: usize = 16;
+
+lazy_static! {
+    /// Process wide curves of the last finished slots.
+    pub static ref VALUE_CURVES: Arc<ValueCurveHistory> = Arc::new(ValueCurveHistory::default());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueCurvePoint {
+    pub elapsed_ms: u64,
+    /// Best bid value so far.
+    pub bid_value: U256,
+    pub builder_name: String,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValueCurve {
+    pub slot: u64,
+    pub block: u64,
+    #[serde_as(as = "TimestampMilliSeconds<i64>")]
+    pub started_at: OffsetDateTime,
+    pub points: Vec<ValueCurvePoint>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ValueCurveSummary {
+    pub value_at_4s: Option<U256>,
+    pub value_at_8s: Option<U256>,
+    pub value_at_11s: Option<U256>,
+    pub last_improvement_ms: Option<u64>,
+}
+
+impl ValueCurve {
+    pub fn new(slot: u64, block: u64, started_at: OffsetDateTime) -> Self {
+        Self {
+            slot,
+            block,
+            started_at,
+            points: Vec::new(),
+        }
+    }
+
+    /// Adds a point if bid_value is better than the best so far, returns true if it did.
+    pub fn record(&mut self, at: OffsetDateTime, bid_value: U256, builder_name: &str) -> bool {
+        if self.best_value().is_some_and(|best| best >= bid_value) {
+            return false;
+        }
+        let point = ValueCurvePoint {
+            elapsed_ms: u64::try_from((at - self.started_at).whole_milliseconds()).unwrap_or(0),
+            bid_value,
+            builder_name: builder_name.to_string(),
+        };
+        if self.points.len() >= MAX_VALUE_CURVE_POINTS {
+            self.points.pop();
+        }
+        self.points.push(point);
+        true
+    }
+
+    pub fn best_value(&self) -> Option<U256> {
+        self.points.last().map(|point| point.bid_value)
+    }
+
+    /// Best value we had elapsed_ms after the start, None if we had nothing yet.
+    pub fn value_at(&self, elapsed_ms: u64) -> Option<U256> {
+        self.points
+            .iter()
+            .take_while(|point| point.elapsed_ms <= elapsed_ms)
+            .last()
+            .map(|point| point.bid_value)
+    }
+
+    pub fn summary(&self) -> ValueCurveSummary {
+        ValueCurveSummary {
+            value_at_4s: self.value_at(4_000),
+            value_at_8s: self.value_at(8_000),
+            value_at_11s: self.value_at(11_000),
+            last_improvement_ms: self.points.last().map(|point| point.elapsed_ms),
+        }
+    }
+}
+
+/// ValueCurve shared by the sink of the slot (see [`crate::building::builders::BestBlockCell::with_value_curve`]).
+#[derive(Debug, Clone)]
+pub struct SlotValueCurve {
+    curve: Arc<Mutex<ValueCurve>>,
+}
+
+impl SlotValueCurve {
+    /// Starts now.
+    pub fn new(slot: u64, block: u64) -> Self {
+        Self {
+            curve: Arc::new(Mutex::new(ValueCurve::new(
+                slot,
+                block,
+                OffsetDateTime::now_utc(),
+            ))),
+        }
+    }
+
+    pub fn record(&self, bid_value: U256, builder_name: &str) -> bool {
+        self.curve
+            .lock()
+            .unwrap()
+            .record(OffsetDateTime::now_utc(), bid_value, builder_name)
+    }
+
+    pub fn curve(&self) -> ValueCurve {
+        self.curve.lock().unwrap().clone()
+    }
+
+    /// Called when the slot ends: exports the summary and keeps the curve in history.
+    pub fn finish(&self, history: &ValueCurveHistory) {
+        let curve = self.curve();
+        set_value_curve_summary(&curve.summary());
+        history.push(curve);
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ValueCurveHistory {
+    curves: Mutex<VecDeque<ValueCurve>>,
+}
+
+impl ValueCurveHistory {
+    pub fn push(&self, curve: ValueCurve) {
+        let mut curves = self.curves.lock().unwrap();
+        if curves.len() >= FINISHED_VALUE_CURVES {
+            curves.pop_front();
+        }
+        curves.push_back(curve);
+    }
+
+    /// Oldest first.
+    pub fn curves(&self) -> Vec<ValueCurve> {
+        self.curves.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use time::Duration;
+
+    #[test]
+    fn test_value_curve() {
+        let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let mut curve = ValueCurve::new(10, 100, start);
+        assert_eq!(curve.summary(), ValueCurveSummary::default());
+
+        let at = |ms| start + Duration::milliseconds(ms);
+        assert!(curve.record(at(1_500), U256::from(10), "a"));
+        // not an improvement
+        assert!(!curve.record(at(2_000), U256::from(10), "b"));
+        assert!(curve.record(at(6_000), U256::from(20), "b"));
+        assert!(!curve.record(at(7_000), U256::from(15), "a"));
+        assert!(curve.record(at(9_250), U256::from(30), "a"));
+
+        assert_eq!(
+            curve
+                .points
+                .iter()
+                .map(|point| (
+                    point.elapsed_ms,
+                    point.bid_value,
+                    point.builder_name.as_str()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                (1_500, U256::from(10), "a"),
+                (6_000, U256::from(20), "b"),
+                (9_250, U256::from(30), "a"),
+            ]
+        );
+        assert_eq!(curve.value_at(1_000), None);
+        assert_eq!(
+            curve.summary(),
+            ValueCurveSummary {
+                value_at_4s: Some(U256::from(10)),
+                value_at_8s: Some(U256::from(20)),
+                value_at_11s: Some(U256::from(30)),
+                last_improvement_ms: Some(9_250),
+            }
+        );
+    }
+
+    #[test]
+    fn test_value_curve_cap() {
+        let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let mut curve = ValueCurve::new(10, 100, start);
+        for i in 0..MAX_VALUE_CURVE_POINTS + 10 {
+            curve.record(
+                start + Duration::milliseconds(i as i64),
+                U256::from(i + 1),
+                "a",
+            );
+        }
+        assert_eq!(curve.points.len(), MAX_VALUE_CURVE_POINTS);
+        assert_eq!(
+            curve.best_value(),
+            Some(U256::from(MAX_VALUE_CURVE_POINTS + 10))
+        );
+
+        let history = ValueCurveHistory::default();
+        for slot in 0..FINISHED_VALUE_CURVES as u64 + 1 {
+            history.push(ValueCurve::new(slot, slot, start));
+        }
+        let curves = history.curves();
+        assert_eq!(curves.len(), FINISHED_VALUE_CURVES);
+        assert_eq!(curves[0].slot, 1);
+    }
+}
@@ -1,5 +1,5 @@
 use crate::{
-    building::BuiltBlockTrace,
+    building::{BuiltBlockTrace, OrderLogSummary},
     mev_boost::SubmitBlockRequest,
     primitives::{
         serialize::{RawBundle, RawShareBundle},
@@ -29,6 +29,8 @@ pub struct BlocksProcessorClient {
 struct UsedSbundle {
     bundle: RawShareBundle,
     success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logs: Option<OrderLogSummary>,
 }
 
 #[serde_as]
@@ -44,6 +46,8 @@ struct UsedBundle {
     #[serde_as(as = "DisplayFromStr")]
     total_gas_used: u64,
     original_bundle: RawBundle,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    logs: Option<OrderLogSummary>,
 }
 
 /// Header used by block_consumeBuiltBlockV2. Since docs are not up to date I copied RbuilderHeader from block-processor/ports/models.go (commit b341b35)
@@ -92,24 +96,7 @@ impl BlocksProcessorClient {
             .orders_sealed_at
             .format(&well_known::Iso8601::DEFAULT)?;
 
-        let committed_bundles = built_block_trace
-            .included_orders
-            .iter()
-            .filter_map(|res| {
-                if let Order::Bundle(bundle) = &res.order {
-                    Some(UsedBundle {
-                        mev_gas_price: res.inplace_sim.mev_gas_price,
-                        total_eth: res.inplace_sim.coinbase_profit,
-                        eth_send_to_coinbase: U256::ZERO,
-                        total_gas_used: res.inplace_sim.gas_used,
-                        original_bundle: RawBundle::encode_no_blobs(bundle.clone()),
-                    })
-                } else {
-                    None
-                }
-            })
-            .collect::<Vec<_>>();
-
+        let committed_bundles = Self::get_committed_bundles(built_block_trace);
         let used_share_bundles = Self::get_used_sbundles(built_block_trace);
 
         let params = (
@@ -176,6 +163,28 @@ impl BlocksProcessorClient {
         Ok(())
     }
 
+    /// Bundles are stored so private ones (see [`crate::primitives::Metadata::private`]) are redacted.
+    fn get_committed_bundles(built_block_trace: &BuiltBlockTrace) -> Vec<UsedBundle> {
+        built_block_trace
+            .included_orders
+            .iter()
+            .filter_map(|res| {
+                if let Order::Bundle(bundle) = &res.order {
+                    Some(UsedBundle {
+                        mev_gas_price: res.inplace_sim.mev_gas_price,
+                        total_eth: res.inplace_sim.coinbase_profit,
+                        eth_send_to_coinbase: U256::ZERO,
+                        total_gas_used: res.inplace_sim.gas_used,
+                        original_bundle: RawBundle::encode_redacted(bundle.clone()),
+                        logs: res.log_summary.clone(),
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
     /// Gets the UsedSbundle carefully considering virtual orders formed by other original orders.
     fn get_used_sbundles(built_block_trace: &BuiltBlockTrace) -> Vec<UsedSbundle> {
         built_block_trace
@@ -183,6 +192,12 @@ impl BlocksProcessorClient {
             .iter()
             .flat_map(|exec_result| {
                 if let Order::ShareBundle(sbundle) = &exec_result.order {
+                    // the summary covers the whole execution so we can only attach it to non merged sbundles
+                    let logs = if sbundle.is_merged_order() {
+                        None
+                    } else {
+                        exec_result.log_summary.clone()
+                    };
                     // don't like having special cases (merged vs not merged), can we improve this?
                     let filtered_sbundles = if sbundle.is_merged_order() {
                         // We include only original orders that are contained in original_order_ids.
@@ -212,8 +227,9 @@ impl BlocksProcessorClient {
                     filtered_sbundles
                         .into_iter()
                         .map(|sbundle| UsedSbundle {
-                            bundle: RawShareBundle::encode_no_blobs(sbundle.clone()),
+                            bundle: RawShareBundle::encode_redacted(sbundle.clone()),
                             success: true,
+                            logs: logs.clone(),
                         })
                         .collect()
                 } else {
@@ -223,3 +239,100 @@ impl BlocksProcessorClient {
             .collect::<Vec<_>>()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::testing::{
+            bundle_tests::setup::TestSetup,
+            test_chain_state::{BlockArgs, NamedAddr},
+        },
+        live_builder::order_input::mark_private_signers_orders,
+        primitives::{
+            serialize::{RawOrder, RawShareBundleMetadatada, TxEncoding},
+            TxRevertBehavior,
+        },
+    };
+    use alloy_primitives::hex;
+    use std::collections::HashSet;
+
+    fn raw_txs(order: &Order) -> Vec<String> {
+        order
+            .list_txs()
+            .iter()
+            .map(|(tx, _)| hex::encode(tx.envelope_encoded_no_blobs()))
+            .collect()
+    }
+
+    /// Private orders go through the submission format, get included and then we look for their txs on everything we log/store.
+    #[test]
+    fn test_private_orders_never_reach_artifacts() -> eyre::Result<()> {
+        let mut test_setup = TestSetup::gen_test_setup(BlockArgs::default().number(11))?;
+
+        // private on the request
+        test_setup.begin_bundle_order(11);
+        test_setup.add_large_tx(NamedAddr::User(0), 100)?;
+        let RawOrder::Bundle(mut raw_bundle) = RawOrder::from(test_setup.build_order()) else {
+            unreachable!()
+        };
+        raw_bundle.private = true;
+        let bundle = Order::Bundle(raw_bundle.decode(TxEncoding::NoBlobData)?);
+
+        test_setup.begin_share_bundle_order(11, 11);
+        test_setup.add_large_tx(NamedAddr::User(1), 100)?;
+        let RawOrder::ShareBundle(mut raw_sbundle) = RawOrder::from(test_setup.build_order())
+        else {
+            unreachable!()
+        };
+        raw_sbundle.metadata = Some(RawShareBundleMetadatada {
+            signer: None,
+            replacement_nonce: None,
+            cancelled: false,
+            private: true,
+        });
+        let sbundle = Order::ShareBundle(raw_sbundle.decode_new_bundle(TxEncoding::NoBlobData)?);
+
+        // private because of the signer
+        test_setup.begin_mempool_tx_order();
+        test_setup.add_null_tx(NamedAddr::User(2), TxRevertBehavior::AllowedIncluded)?;
+        let mut tx = test_setup.build_order();
+        assert!(!tx.metadata().private);
+        let private_order_signers = HashSet::from([test_setup.named_address(NamedAddr::User(2))?]);
+        mark_private_signers_orders(&mut tx, &private_order_signers);
+
+        let orders = [bundle, sbundle, tx];
+        let mut trace = BuiltBlockTrace::new();
+        for order in &orders {
+            assert!(order.metadata().private);
+            trace.add_included_order(test_setup.commit_given_order_ok(order.clone()));
+        }
+        assert_eq!(test_setup.block_txs().len(), 3);
+        assert!(trace.has_private_orders());
+
+        let artifacts = [
+            format!("{:?}", orders),
+            format!("{:?}", trace),
+            format!("{:?}", test_setup.block_txs()),
+            serde_json::to_string(&BlocksProcessorClient::get_committed_bundles(&trace))?,
+            serde_json::to_string(&BlocksProcessorClient::get_used_sbundles(&trace))?,
+            serde_json::to_string(
+                &orders
+                    .iter()
+                    .cloned()
+                    .map(RawOrder::encode_redacted)
+                    .collect::<Vec<_>>(),
+            )?,
+        ];
+        let raw_txs: Vec<_> = orders.iter().flat_map(raw_txs).collect();
+        assert_eq!(raw_txs.len(), 3);
+        for raw_tx in &raw_txs {
+            for artifact in &artifacts {
+                assert!(!artifact.contains(raw_tx), "{}", artifact);
+            }
+        }
+        // the lossless encoding (eg: the one used to sim and submit) still has them
+        assert!(serde_json::to_string(&RawOrder::from(orders[0].clone()))?.contains(&raw_txs[0]));
+        Ok(())
+    }
+}
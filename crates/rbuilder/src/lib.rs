@@ -7,5 +7,7 @@ pub mod mev_boost;
 pub mod primitives;
 pub mod roothash;
 pub mod telemetry;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 pub mod utils;
 pub mod validation_api_client;
@@ -2,22 +2,50 @@
 //!
 use crate::{
     beacon_api_client::Client,
+    building::{
+        builders::BestBlockFilter,
+        pending_accounts::PendingCandidates,
+        token_valuation::{TokenPriceConfig, TokenValuation},
+        BuilderIdentity,
+    },
     flashbots::BlocksProcessorClient,
     live_builder::{
-        bidding::DummyBiddingService,
-        building::{relay_submit::RelaySubmitSinkFactory, SubmissionConfig},
-        order_input::OrderInputConfig,
+        bidding::{
+            bid_floor::{BidFloorBiddingService, BidFloorConfig},
+            BiddingService, DummyBiddingService,
+        },
+        building::{
+            builder_tag_experiment::{
+                BuilderTag, BuilderTagExperiment, BuilderTagMappingLog, BuilderTagSchedule,
+            },
+            relay_identities::{RelayIdentities, DEFAULT_IDENTITY, OPTIMISTIC_IDENTITY},
+            relay_submit::RelaySubmitSinkFactory,
+            SubmissionConfig,
+        },
+        canary::CanaryConfig,
+        config_profile::{ConfigProfile, EffectiveConfig},
+        config_validation::ConfigValidationReport,
+        kill_switch::KillSwitchConfig,
+        order_input::{
+            estimate_gas::{EstimateGasConfig, GasEstimator},
+            http_middleware::{DEFAULT_MAX_BATCH_SIZE, DEFAULT_MAX_BODY_SIZE},
+            pending_account::PendingAccountService,
+            OrderInputConfig,
+        },
+        simulation::{overload_filter::OverloadFilterConfig, priority_lane::SimPriorityLaneConfig},
         LiveBuilder,
     },
     mev_boost::BLSBlockSigner,
     primitives::mev_boost::MevBoostRelay,
     telemetry::{setup_reloadable_tracing_subscriber, LoggerConfig},
-    utils::{http_provider, BoxedProvider, ProviderFactoryReopener, Signer},
+    utils::{
+        build_info::rbuilder_version, http_provider, BoxedProvider, ProviderFactoryReopener, Signer,
+    },
     validation_api_client::ValidationAPIClient,
 };
-use ahash::HashSet;
+use ahash::{HashMap, HashSet};
 use alloy_chains::ChainKind;
-use alloy_primitives::{utils::parse_ether, Address, FixedBytes, B256};
+use alloy_primitives::{address, utils::parse_ether, Address, FixedBytes, B256, U256};
 use ethereum_consensus::{
     builder::compute_builder_domain, crypto::SecretKey, primitives::Version,
     state_transition::Context as ContextEth,
@@ -50,6 +78,16 @@ use url::Url;
 /// From experience (Vitaly's) all generated blocks before slot_time-8sec end loosing (due to last moment orders?)
 const DEFAULT_SLOT_DELTA_TO_START_SUBMITS: time::Duration = time::Duration::milliseconds(-8000);
 
+/// Addresses of the well-known Hardhat/Anvil default private keys.
+/// Their secrets are public so anything paid to them can be taken by anyone.
+const KNOWN_TEST_KEY_ADDRESSES: [Address; 5] = [
+    address!("f39Fd6e51aad88F6F4ce6aB8827279cffFb92266"),
+    address!("70997970C51812dc3A010C7d01b50e0d17dc79C8"),
+    address!("3C44CdDdB6a900fa2b585dd299e03d12FA4293BC"),
+    address!("90F79bf6EB2c4f870365E785982E1f101E93b906"),
+    address!("15d34AAf54267DB7D7c367839AAf71A00a2C6A65"),
+];
+
 /// Base config to be used by all builders.
 /// It allows us to create a base LiveBuilder with no algorithms or custom bidding.
 /// The final configuration should usually include one of this and use it to create the base LiveBuilder to then upgrade it as needed.
@@ -57,6 +95,9 @@ const DEFAULT_SLOT_DELTA_TO_START_SUBMITS: time::Duration = time::Duration::mill
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[serde(default, deny_unknown_fields)]
 pub struct BaseConfig {
+    /// Preset of defaults for a chain (see [`crate::live_builder::config_profile`]), fields set on the file override it.
+    pub profile: Option<ConfigProfile>,
+
     pub telemetry_port: u16,
     pub telemetry_ip: Option<String>,
     pub log_json: bool,
@@ -75,9 +116,18 @@ pub struct BaseConfig {
     pub cl_node_url: Vec<String>,
     pub jsonrpc_server_port: u16,
     pub jsonrpc_server_ip: Option<String>,
+    /// Max time eth_sendBundle waits for a fast simulation of the bundle when the caller asks for feedback.
+    pub bundle_feedback_sim_budget_ms: u64,
+    /// Max order input request body in bytes, applies to the compressed and the decompressed (gzip/zstd) body.
+    pub jsonrpc_max_request_body_size: usize,
+    /// Max elements of a JSON-RPC batch on the order input server.
+    pub jsonrpc_max_batch_size: usize,
 
     pub ignore_cancellable_orders: bool,
     pub ignore_blobs: bool,
+    /// Orders signed by these addresses (bundle signing address or mempool tx signer) are always private
+    /// (see [`crate::primitives::Metadata::private`]), the rest can ask for it on the request.
+    pub private_order_signers: Vec<Address>,
 
     pub chain: String,
     pub reth_datadir: Option<PathBuf>,
@@ -86,6 +136,10 @@ pub struct BaseConfig {
 
     pub blocklist_file_path: Option<PathBuf>,
     pub extra_data: String,
+    /// Recorded on the trace of every built block, version defaults to the git commit of the build.
+    pub builder_identity: BuilderIdentity,
+    /// Tokens valued (in ETH) when paid to our coinbase, only for the order score, never bid.
+    pub token_prices: Vec<TokenPriceConfig>,
 
     // Relay Submission configuration
     pub relays: Vec<RelayConfig>,
@@ -96,6 +150,8 @@ pub struct BaseConfig {
     relay_secret_key: EnvOrInplaceValue,
     /// Secret key that will be used to sign optimistic submissions to the relay.
     optimistic_relay_secret_key: EnvOrInplaceValue,
+    /// Named keys that relays can use instead of relay_secret_key (see [`RelayConfig::signing_identity`]).
+    pub signing_identities: Vec<SigningIdentityConfig>,
     /// When enabled builer will make optimistic submissions to optimistic relays
     /// influenced by `optimistic_max_bid_value_eth` and `optimistic_prevalidate_optimistic_blocks`
     pub optimistic_enabled: bool,
@@ -110,10 +166,29 @@ pub struct BaseConfig {
 
     /// Number of threads used for incoming order simulation
     pub simulation_threads: usize,
+    /// If set heavy orders are shed when simulation is overloaded, see [`crate::live_builder::simulation::overload_filter::OverloadFilter`]
+    pub simulation_overload_filter: Option<OverloadFilterConfig>,
+    /// If set orders that can only land on the next block are simulated first, see [`crate::live_builder::simulation::priority_lane`]
+    pub simulation_priority_lane: Option<SimPriorityLaneConfig>,
 
     pub root_hash_task_pool_threads: usize,
 
+    /// If set the rbuilder_estimateGas rpc is served (see [`crate::live_builder::order_input::estimate_gas`]).
+    pub estimate_gas: Option<EstimateGasConfig>,
+    /// If true the rbuilder_getPendingAccount rpc is served (see [`crate::live_builder::order_input::pending_account`]).
+    pub pending_account_rpc: bool,
+    /// If set a landed block is rebuilt every canary.interval_mins and diffed with the chain (see [`crate::live_builder::canary`]).
+    pub canary: Option<CanaryConfig>,
+    /// File/webhook to stop all the submissions (see [`crate::live_builder::kill_switch`]).
+    pub kill_switch: Option<KillSwitchConfig>,
+    /// If set blocks below a percentile of the recent winning bids of the slot fee recipient are not submitted
+    /// (see [`crate::live_builder::bidding::bid_floor`]).
+    pub bid_floor: Option<BidFloorConfig>,
+
     pub watchdog_timeout_sec: u64,
+    /// Slots whose parent is older than this when we get the attributes are skipped (see
+    /// [`crate::building::BlockBuildingContext::parent_age`]), 0 (default) disables the check. Chains with longer slots need higher values.
+    pub max_parent_age_secs: u64,
 
     /// List of `builders` to be used for live building
     pub live_builders: Vec<String>,
@@ -129,6 +204,22 @@ pub struct BaseConfig {
 
     // See [`SubmissionConfig`]
     slot_delta_to_start_submits_ms: Option<i64>,
+    /// See [`SubmissionConfig::fast_relay_window`]
+    pub fast_relay_window_ms: Option<u64>,
+    /// See [`SubmissionConfig::max_gas_used_percent`]
+    pub max_gas_used_percent: Option<u64>,
+    /// See [`BestBlockFilter::min_improvement`]
+    pub best_block_min_improvement_wei: u64,
+    /// See [`BestBlockFilter::max_supersessions`]
+    pub max_best_block_supersessions: Option<usize>,
+    /// See [`SubmissionConfig::slot_block_metrics`]
+    pub slot_block_metrics: bool,
+
+    /// Allows well-known test keys (eg: Anvil defaults) as signers on non mainnet chains without warnings.
+    /// Mainnet always refuses them.
+    pub allow_known_test_keys: bool,
+
+    pub experiments: ExperimentsConfig,
 }
 
 lazy_static! {
@@ -151,9 +242,19 @@ fn parse_ip(ip: &Option<String>) -> Ipv4Addr {
 /// - relay_secret_key
 /// - optimistic_relay_secret_key
 /// - backtest_fetch_mempool_data_dir
+///
+/// If the file has a `profile` its defaults are applied below the file values.
 pub fn load_config_toml_and_env<T: serde::de::DeserializeOwned>(
     path: impl AsRef<Path>,
 ) -> eyre::Result<T> {
+    let config: T = load_effective_config(path)?
+        .deserialize()
+        .context("Config file parsing")?;
+    Ok(config)
+}
+
+/// Config file merged with its profile, also says which one set each value.
+pub fn load_effective_config(path: impl AsRef<Path>) -> eyre::Result<EffectiveConfig> {
     let data = read_to_string(path.as_ref()).with_context(|| {
         eyre!(
             "Config file read error: {:?}",
@@ -161,8 +262,8 @@ pub fn load_config_toml_and_env<T: serde::de::DeserializeOwned>(
         )
     })?;
 
-    let config: T = toml::from_str(&data).context("Config file parsing")?;
-    Ok(config)
+    let file = toml::from_str(&data).context("Config file parsing")?;
+    EffectiveConfig::from_file_table(file)
 }
 
 impl BaseConfig {
@@ -189,6 +290,11 @@ impl BaseConfig {
     ) -> eyre::Result<
         super::LiveBuilder<Arc<DatabaseEnv>, super::building::relay_submit::RelaySubmitSinkFactory>,
     > {
+        self.validate_signer_keys()?;
+        let builder_tag_schedule = self.builder_tag_schedule()?;
+        if builder_tag_schedule.is_some() {
+            warn!("Builder tag experiment enabled, extra_data and bid pubkey change every slot");
+        }
         let submission_config = self.submission_config()?;
         info!(
             "Builder mev boost normal relay pubkey: {:?}",
@@ -208,14 +314,27 @@ impl BaseConfig {
         let provider_factory = self.provider_factory()?;
 
         let relays = self.relays()?;
-        let sink_factory = RelaySubmitSinkFactory::new(self.submission_config()?, relays.clone());
+        let relay_identities = submission_config.relay_identities.clone();
+        let sink_factory = RelaySubmitSinkFactory::new(submission_config, relays.clone());
+        let bidding_service: Box<dyn BiddingService> = match &self.bid_floor {
+            Some(bid_floor) => Box::new(BidFloorBiddingService::new(
+                Box::new(DummyBiddingService {}),
+                bid_floor.clone(),
+                relays.clone(),
+            )?),
+            None => Box::new(DummyBiddingService {}),
+        };
 
+        let pending_candidates = Arc::new(PendingCandidates::default());
         Ok(LiveBuilder::<Arc<DatabaseEnv>, RelaySubmitSinkFactory> {
             cls: self.beacon_clients()?,
             relays,
             watchdog_timeout: self.watchdog_timeout(),
+            max_parent_age: self.max_parent_age(),
             error_storage_path: self.error_storage_path.clone(),
             simulation_threads: self.simulation_threads,
+            simulation_overload_filter: self.simulation_overload_filter.clone(),
+            simulation_priority_lane: self.simulation_priority_lane.clone(),
             order_input_config: OrderInputConfig::from_config(self),
 
             chain_chain_spec: self.chain_spec()?,
@@ -224,10 +343,26 @@ impl BaseConfig {
             coinbase_signer: self.coinbase_signer()?,
             extra_data: self.extra_data()?,
             blocklist: self.blocklist()?,
+            builder_identity: self.builder_identity(),
+            token_valuation: self.token_valuation()?,
+            builder_tag_schedule,
+            relay_identities,
+            gas_estimator: self
+                .estimate_gas
+                .as_ref()
+                .map(GasEstimator::from_config)
+                .transpose()?,
+            pending_accounts: self
+                .pending_account_rpc
+                .then(|| PendingAccountService::new(pending_candidates.clone())),
+            pending_candidates,
+            canary: self.canary.clone(),
+            kill_switch: self.kill_switch.clone(),
+            sbundle_mergeabe_signers: self.sbundle_mergeabe_signers.clone(),
 
             global_cancellation: cancellation_token,
 
-            bidding_service: Box::new(DummyBiddingService {}),
+            bidding_service,
             extra_rpc: RpcModule::new(()),
             sink_factory,
             builders: Vec::new(),
@@ -328,8 +463,7 @@ impl BaseConfig {
     pub fn bls_signer(&self) -> eyre::Result<BLSBlockSigner> {
         let chain_spec = self.chain_spec()?;
         let signing_domain = get_signing_domain(chain_spec.chain, self.beacon_clients()?)?;
-        let secret_key = self.relay_secret_key.value()?;
-        let secret_key = SecretKey::try_from(secret_key)
+        let secret_key = parse_bls_secret_key(&self.relay_secret_key)
             .map_err(|e| eyre::eyre!("Failed to parse relay key: {:?}", e.to_string()))?;
 
         BLSBlockSigner::new(secret_key, signing_domain)
@@ -338,18 +472,106 @@ impl BaseConfig {
     pub fn bls_optimistic_signer(&self) -> eyre::Result<BLSBlockSigner> {
         let chain_spec = self.chain_spec()?;
         let signing_domain = get_signing_domain(chain_spec.chain, self.beacon_clients()?)?;
-        let secret_key = self.optimistic_relay_secret_key.value()?;
-        let secret_key = SecretKey::try_from(secret_key).map_err(|e| {
+        let secret_key = parse_bls_secret_key(&self.optimistic_relay_secret_key).map_err(|e| {
             eyre::eyre!("Failed to parse optimistic relay key: {:?}", e.to_string())
         })?;
 
         BLSBlockSigner::new(secret_key, signing_domain)
     }
 
+    /// Signers for signing_identities and the initial relay assignments from relays.
+    pub fn relay_identities(&self) -> eyre::Result<RelayIdentities> {
+        let mut signers = HashMap::default();
+        if !self.signing_identities.is_empty() {
+            let chain_spec = self.chain_spec()?;
+            let signing_domain = get_signing_domain(chain_spec.chain, self.beacon_clients()?)?;
+            for identity in &self.signing_identities {
+                let secret_key = parse_bls_secret_key(&identity.relay_secret_key).map_err(|e| {
+                    eyre::eyre!(
+                        "Failed to parse key for signing identity {}: {:?}",
+                        identity.name,
+                        e.to_string()
+                    )
+                })?;
+                signers.insert(
+                    identity.name.clone(),
+                    BLSBlockSigner::new(secret_key, signing_domain)?,
+                );
+            }
+        }
+        let assignments = self
+            .relays
+            .iter()
+            .filter_map(|relay| {
+                relay
+                    .signing_identity
+                    .clone()
+                    .map(|identity| (relay.name.clone(), identity))
+            })
+            .collect();
+        let payout_signers = self
+            .signing_identities
+            .iter()
+            .filter_map(|identity| {
+                identity.payout_secret_key.as_ref().map(|key| {
+                    Ok((
+                        identity.name.clone(),
+                        coinbase_signer_from_secret_key(&key.value()?)?,
+                    ))
+                })
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(RelayIdentities::new(signers, assignments)?.with_payout_signers(payout_signers)?)
+    }
+
     pub fn coinbase_signer(&self) -> eyre::Result<Signer> {
         coinbase_signer_from_secret_key(&self.coinbase_secret_key.value()?)
     }
 
+    /// Every key of the config signing txs as (config path, address).
+    /// Keys that don't parse are left out, validate reports them.
+    pub fn signer_addresses(&self) -> Vec<(String, Address)> {
+        let mut signers = Vec::new();
+        if let Ok(signer) = self.coinbase_signer() {
+            signers.push(("coinbase_secret_key".to_string(), signer.address));
+        }
+        for (i, identity) in self.signing_identities.iter().enumerate() {
+            if let Some(Ok(signer)) = identity.payout_secret_key.as_ref().map(|key| {
+                key.value()
+                    .and_then(|key| coinbase_signer_from_secret_key(&key))
+            }) {
+                signers.push((
+                    format!("signing_identities[{}].payout_secret_key", i),
+                    signer.address,
+                ));
+            }
+        }
+        if let Some(builder_tag) = &self.experiments.builder_tag {
+            for (i, tag) in builder_tag.tags.iter().enumerate() {
+                if let Ok(signer) = tag
+                    .coinbase_secret_key
+                    .value()
+                    .and_then(|key| coinbase_signer_from_secret_key(&key))
+                {
+                    signers.push((
+                        format!("experiments.builder_tag.tags[{}].coinbase_secret_key", i),
+                        signer.address,
+                    ));
+                }
+            }
+        }
+        signers
+    }
+
+    /// Checks that none of the configured signers is a well-known test key.
+    pub fn validate_signer_keys(&self) -> eyre::Result<()> {
+        let chain = self.chain_spec()?.chain;
+        for (key_name, address) in self.signer_addresses() {
+            check_known_test_key(&key_name, address, chain, self.allow_known_test_keys)?;
+        }
+        Ok(())
+    }
+
     pub fn extra_data(&self) -> eyre::Result<Vec<u8>> {
         let extra_data = self.extra_data.clone().into_bytes();
         if extra_data.len() > 32 {
@@ -358,6 +580,67 @@ impl BaseConfig {
         Ok(extra_data)
     }
 
+    pub fn builder_identity(&self) -> BuilderIdentity {
+        let mut builder_identity = self.builder_identity.clone();
+        if builder_identity.version.is_empty() {
+            builder_identity.version = rbuilder_version().git_commit;
+        }
+        builder_identity
+    }
+
+    pub fn token_valuation(&self) -> eyre::Result<TokenValuation> {
+        Ok(TokenValuation::new(self.token_prices.clone())?)
+    }
+
+    /// None if the builder tag experiment is not configured.
+    /// Fails if it's configured but experiments are not enabled.
+    pub fn builder_tag_schedule(&self) -> eyre::Result<Option<BuilderTagSchedule>> {
+        let config = match &self.experiments.builder_tag {
+            Some(config) => config,
+            None => return Ok(None),
+        };
+        if !self.experiments.enabled {
+            eyre::bail!("experiments.builder_tag is configured but experiments.enabled is false");
+        }
+        let tags = config
+            .tags
+            .iter()
+            .map(|tag| {
+                Ok(BuilderTag {
+                    extra_data: tag.extra_data.clone().into_bytes(),
+                    signing_identity: tag.signing_identity.clone(),
+                    coinbase_signer: coinbase_signer_from_secret_key(
+                        &tag.coinbase_secret_key.value()?,
+                    )?,
+                })
+            })
+            .collect::<eyre::Result<_>>()?;
+        Ok(Some(BuilderTagSchedule::new(
+            config.seed.value()?.into_bytes(),
+            tags,
+        )?))
+    }
+
+    pub fn builder_tag_experiment(&self) -> eyre::Result<Option<BuilderTagExperiment>> {
+        let schedule = match self.builder_tag_schedule()? {
+            Some(schedule) => schedule,
+            None => return Ok(None),
+        };
+        let mapping_log_path = &self
+            .experiments
+            .builder_tag
+            .as_ref()
+            .unwrap()
+            .mapping_log_path;
+        let mapping_log = BuilderTagMappingLog::open(mapping_log_path).with_context(|| {
+            eyre!(
+                "Builder tag mapping log open error: {:?}",
+                mapping_log_path.to_string_lossy()
+            )
+        })?;
+        Ok(Some(BuilderTagExperiment::new(schedule, mapping_log)))
+    }
+
     pub fn blocklist(&self) -> eyre::Result<HashSet<Address>> {
         if let Some(path) = &self.blocklist_file_path {
             let blocklist_file = read_to_string(path).context("blocklist file")?;
@@ -386,6 +669,10 @@ impl BaseConfig {
         Duration::from_secs(self.watchdog_timeout_sec)
     }
 
+    pub fn max_parent_age(&self) -> Option<Duration> {
+        (self.max_parent_age_secs != 0).then(|| Duration::from_secs(self.max_parent_age_secs))
+    }
+
     pub fn submission_config(&self) -> eyre::Result<SubmissionConfig> {
         if (self.dry_run || self.optimistic_prevalidate_optimistic_blocks)
             && self.dry_run_validation_url.is_empty()
@@ -419,6 +706,22 @@ impl BaseConfig {
             }
         };
 
+        let relay_identities = self.relay_identities()?;
+        let builder_tag_experiment = self.builder_tag_experiment()?;
+        if let Some(experiment) = &builder_tag_experiment {
+            if let Some(tag) = experiment
+                .schedule
+                .tags()
+                .iter()
+                .find(|tag| !relay_identities.contains(&tag.signing_identity))
+            {
+                eyre::bail!(
+                    "Unknown signing identity in builder tag experiment: {}",
+                    tag.signing_identity
+                );
+            }
+        }
+
         Ok(SubmissionConfig {
             chain_spec: self.chain_spec()?,
             signer: self.bls_signer()?,
@@ -435,6 +738,15 @@ impl BaseConfig {
                 None
             },
             slot_delta_to_start_submits: self.slot_delta_to_start_submits(),
+            fast_relay_window: self.fast_relay_window_ms.map(Duration::from_millis),
+            max_gas_used_percent: self.max_gas_used_percent,
+            relay_identities,
+            builder_tag_experiment,
+            best_block_filter: BestBlockFilter {
+                min_improvement: U256::from(self.best_block_min_improvement_wei),
+                max_supersessions: self.max_best_block_supersessions,
+            },
+            slot_block_metrics: self.slot_block_metrics,
         })
     }
 
@@ -445,6 +757,291 @@ impl BaseConfig {
         Ok(path_expanded.parse()?)
     }
 
+    /// Checks the constraints between fields that serde can't check, collecting all the problems found.
+    /// Does not open any db or connect to anything.
+    pub fn validate(&self) -> ConfigValidationReport {
+        let mut report = ConfigValidationReport::default();
+
+        report.check("chain", self.chain_spec().map(|_| ()));
+        if self.profile == Some(ConfigProfile::Devnet)
+            && matches!(self.chain_spec(), Ok(chain_spec) if chain_spec.chain == Chain::mainnet())
+        {
+            report.add("profile", "devnet profile can't be used on mainnet");
+        }
+        report.check("extra_data", self.extra_data().map(|_| ()));
+        report.check("coinbase_secret_key", self.coinbase_signer().map(|_| ()));
+        if let Ok(chain_spec) = self.chain_spec() {
+            for (key_name, address) in self.signer_addresses() {
+                report.check(
+                    key_name.clone(),
+                    check_known_test_key(
+                        &key_name,
+                        address,
+                        chain_spec.chain,
+                        self.allow_known_test_keys,
+                    ),
+                );
+            }
+        }
+        report.check(
+            "relay_secret_key",
+            parse_bls_secret_key(&self.relay_secret_key).map(|_| ()),
+        );
+
+        if self.reth_datadir.is_none() {
+            if self.reth_db_path.is_none() {
+                report.add(
+                    "reth_db_path",
+                    "either reth_db_path or reth_datadir must be set",
+                );
+            }
+            if self.reth_static_files_path.is_none() {
+                report.add(
+                    "reth_static_files_path",
+                    "either reth_static_files_path or reth_datadir must be set",
+                );
+            }
+        }
+
+        if self.cl_node_url.is_empty() {
+            report.add("cl_node_url", "at least one consensus node url is needed");
+        }
+        for (i, url) in self.cl_node_url.iter().enumerate() {
+            report.check(
+                format!("cl_node_url[{}]", i),
+                Url::parse(url).map(|_| ()).map_err(Into::into),
+            );
+        }
+
+        if self.dry_run && self.dry_run_validation_url.is_empty() {
+            report.add(
+                "dry_run_validation_url",
+                "dry_run is enabled but no validation urls provided",
+            );
+        }
+        if self.optimistic_prevalidate_optimistic_blocks && self.dry_run_validation_url.is_empty() {
+            report.add(
+                "dry_run_validation_url",
+                "optimistic_prevalidate_optimistic_blocks is enabled but no validation urls provided",
+            );
+        }
+
+        report.check(
+            "optimistic_max_bid_value_eth",
+            parse_ether(&self.optimistic_max_bid_value_eth)
+                .map(|_| ())
+                .map_err(Into::into),
+        );
+        if self.optimistic_enabled {
+            if let Err(err) = parse_bls_secret_key(&self.optimistic_relay_secret_key) {
+                report.add(
+                    "optimistic_relay_secret_key",
+                    format!(
+                        "optimistic_enabled requires a valid optimistic relay key: {}",
+                        err
+                    ),
+                );
+            }
+        }
+
+        report.check("token_prices", self.token_valuation().map(|_| ()));
+
+        let mut identity_names: HashMap<&str, usize> = HashMap::default();
+        for (i, identity) in self.signing_identities.iter().enumerate() {
+            if identity.name == DEFAULT_IDENTITY || identity.name == OPTIMISTIC_IDENTITY {
+                report.add(
+                    format!("signing_identities[{}].name", i),
+                    format!("{:?} is reserved", identity.name),
+                );
+            }
+            if let Some(first) = identity_names.insert(&identity.name, i) {
+                report.add(
+                    format!("signing_identities[{}].name", i),
+                    format!(
+                        "duplicated signing identity name {:?}, already used by signing_identities[{}]",
+                        identity.name, first
+                    ),
+                );
+            }
+            report.check(
+                format!("signing_identities[{}].relay_secret_key", i),
+                parse_bls_secret_key(&identity.relay_secret_key).map(|_| ()),
+            );
+            if let Some(payout_secret_key) = &identity.payout_secret_key {
+                report.check(
+                    format!("signing_identities[{}].payout_secret_key", i),
+                    payout_secret_key
+                        .value()
+                        .and_then(|key| coinbase_signer_from_secret_key(&key))
+                        .map(|_| ()),
+                );
+            }
+        }
+
+        if let Some(builder_tag) = &self.experiments.builder_tag {
+            if !self.experiments.enabled {
+                report.add(
+                    "experiments.enabled",
+                    "experiments.builder_tag is configured but experiments are not enabled",
+                );
+            }
+            if builder_tag.tags.is_empty() {
+                report.add("experiments.builder_tag.tags", "at least one tag is needed");
+            }
+            for (i, tag) in builder_tag.tags.iter().enumerate() {
+                if tag.extra_data.len() > 32 {
+                    report.add(
+                        format!("experiments.builder_tag.tags[{}].extra_data", i),
+                        "Extra data is too long",
+                    );
+                }
+                if !identity_names.contains_key(tag.signing_identity.as_str()) {
+                    report.add(
+                        format!("experiments.builder_tag.tags[{}].signing_identity", i),
+                        format!("unknown signing identity {:?}", tag.signing_identity),
+                    );
+                }
+                report.check(
+                    format!("experiments.builder_tag.tags[{}].coinbase_secret_key", i),
+                    tag.coinbase_secret_key
+                        .value()
+                        .and_then(|key| coinbase_signer_from_secret_key(&key))
+                        .map(|_| ()),
+                );
+            }
+        }
+
+        let mut relay_names: HashMap<&str, usize> = HashMap::default();
+        for (i, relay) in self.relays.iter().enumerate() {
+            if let Some(first) = relay_names.insert(&relay.name, i) {
+                report.add(
+                    format!("relays[{}].name", i),
+                    format!(
+                        "duplicated relay name {:?}, already used by relays[{}]",
+                        relay.name, first
+                    ),
+                );
+            }
+            report.check(
+                format!("relays[{}].url", i),
+                relay.url.parse::<Url>().map(|_| ()).map_err(Into::into),
+            );
+            if relay.optimistic && !self.optimistic_enabled {
+                report.add(
+                    format!("relays[{}].optimistic", i),
+                    "relay is optimistic but optimistic_enabled is false",
+                );
+            }
+            if let Some(identity) = &relay.signing_identity {
+                if !identity_names.contains_key(identity.as_str()) {
+                    report.add(
+                        format!("relays[{}].signing_identity", i),
+                        format!("unknown signing identity {:?}", identity),
+                    );
+                }
+            }
+            if relay.interval_between_submissions_ms == Some(0) {
+                report.add(
+                    format!("relays[{}].interval_between_submissions_ms", i),
+                    "must be > 0",
+                );
+            }
+        }
+        if self.fast_relay_window_ms == Some(0) {
+            report.add(
+                "fast_relay_window_ms",
+                "must be > 0, remove it to always submit to all relays",
+            );
+        }
+        if let Some(percent) = self.max_gas_used_percent {
+            if percent == 0 || percent > 100 {
+                report.add("max_gas_used_percent", "must be in [1, 100]");
+            }
+        }
+
+        if self.telemetry_port == self.jsonrpc_server_port
+            && self.telemetry_ip() == self.jsonrpc_server_ip()
+        {
+            report.add(
+                "telemetry_port",
+                format!(
+                    "same address as the json rpc server ({}:{})",
+                    self.telemetry_ip(),
+                    self.telemetry_port
+                ),
+            );
+        }
+
+        if self.simulation_threads == 0 {
+            report.add("simulation_threads", "must be > 0");
+        }
+        if let Some(filter) = &self.simulation_overload_filter {
+            if filter.sim_queue_len == 0 {
+                report.add(
+                    "simulation_overload_filter.sim_queue_len",
+                    "must be > 0, 0 would shed orders all the time",
+                );
+            }
+        }
+        if let Some(priority_lane) = &self.simulation_priority_lane {
+            if !(1..100).contains(&priority_lane.normal_min_share_percent) {
+                report.add(
+                    "simulation_priority_lane.normal_min_share_percent",
+                    "must be in [1, 99]",
+                );
+            }
+        }
+        if self.root_hash_task_pool_threads == 0 {
+            report.add("root_hash_task_pool_threads", "must be > 0");
+        }
+        if let Some(estimate_gas) = &self.estimate_gas {
+            if estimate_gas.threads == 0 {
+                report.add("estimate_gas.threads", "must be > 0");
+            }
+            if estimate_gas.max_requests_per_sec == Some(0) {
+                report.add(
+                    "estimate_gas.max_requests_per_sec",
+                    "must be > 0, remove it for no limit",
+                );
+            }
+        }
+        if self.jsonrpc_max_request_body_size == 0 {
+            report.add("jsonrpc_max_request_body_size", "must be > 0");
+        }
+        if self.jsonrpc_max_batch_size == 0 {
+            report.add("jsonrpc_max_batch_size", "must be > 0");
+        }
+        if let Some(canary) = &self.canary {
+            if canary.interval_mins == 0 {
+                report.add("canary.interval_mins", "must be > 0");
+            }
+        }
+        if let Some(bid_floor) = &self.bid_floor {
+            if bid_floor.percentile > 100 {
+                report.add("bid_floor.percentile", "must be <= 100");
+            }
+            if bid_floor.min_samples > bid_floor.max_samples {
+                report.add(
+                    "bid_floor.min_samples",
+                    "must be <= bid_floor.max_samples, the floor would never be set",
+                );
+            }
+        }
+        if let Some(kill_switch) = &self.kill_switch {
+            if kill_switch.poll_interval_ms == 0 {
+                report.add("kill_switch.poll_interval_ms", "must be > 0");
+            }
+            if kill_switch.file.is_none() && kill_switch.webhook_url.is_none() {
+                report.add(
+                    "kill_switch",
+                    "needs file or webhook_url, remove it to only use the rpc",
+                );
+            }
+        }
+
+        report
+    }
+
     pub fn slot_delta_to_start_submits(&self) -> time::Duration {
         self.slot_delta_to_start_submits_ms
             .map(time::Duration::milliseconds)
@@ -452,8 +1049,13 @@ impl BaseConfig {
     }
 }
 
-/// Load value from env variable or use inplace value
+fn parse_bls_secret_key(value: &EnvOrInplaceValue) -> eyre::Result<SecretKey> {
+    SecretKey::try_from(value.value()?).map_err(|e| eyre::eyre!("{}", e.to_string()))
+}
+
+/// Load value from env variable, file or use inplace value
 /// To load value from env use the following syntax `env:ENV_VARIABLE_NAME`
+/// To load value from a file use the following syntax `file:PATH` (surrounding whitespace is trimmed)
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct EnvOrInplaceValue(String);
 
@@ -463,6 +1065,12 @@ impl EnvOrInplaceValue {
         if value.starts_with("env:") {
             let var_name = value.trim_start_matches("env:");
             var(var_name).context(format!("Env variable: {} not set", var_name))
+        } else if value.starts_with("file:") {
+            let path = value.trim_start_matches("file:");
+            Ok(read_to_string(path)
+                .context(format!("File: {} not readable", path))?
+                .trim()
+                .to_string())
         } else {
             Ok(value.to_string())
         }
@@ -496,6 +1104,51 @@ pub struct RelayConfig {
     pub api_token_header: Option<EnvOrInplaceValue>,
     #[serde(default)]
     pub interval_between_submissions_ms: Option<u64>,
+    /// Name of the signing identity (from signing_identities) used for the normal submissions, None -> relay_secret_key.
+    /// Can be changed at runtime from the telemetry server (see [`crate::live_builder::building::relay_identities`]).
+    #[serde(default)]
+    pub signing_identity: Option<String>,
+}
+
+/// Named BLS key to sign relay submissions.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct SigningIdentityConfig {
+    pub name: String,
+    pub relay_secret_key: EnvOrInplaceValue,
+    /// Pays the proposer on the slots where all the relays use this identity, None -> coinbase_secret_key.
+    /// See [`crate::live_builder::building::relay_identities`].
+    #[serde(default)]
+    pub payout_secret_key: Option<EnvOrInplaceValue>,
+}
+
+/// Research experiments, nothing here runs unless enabled is set.
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExperimentsConfig {
+    pub enabled: bool,
+    /// See [`crate::live_builder::building::builder_tag_experiment`].
+    pub builder_tag: Option<BuilderTagExperimentConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BuilderTagExperimentConfig {
+    /// Keep it private, anyone knowing it can link the tags used.
+    pub seed: EnvOrInplaceValue,
+    /// Local json lines file where the accepted submissions and their tags are appended.
+    pub mapping_log_path: PathBuf,
+    pub tags: Vec<BuilderTagConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct BuilderTagConfig {
+    pub extra_data: String,
+    /// One of signing_identities.
+    pub signing_identity: String,
+    /// Coinbase of the slots using this tag, each tag needs its own.
+    pub coinbase_secret_key: EnvOrInplaceValue,
 }
 
 pub const DEFAULT_ERROR_STORAGE_PATH: &str = "/tmp/rbuilder-error.sqlite";
@@ -503,10 +1156,13 @@ pub const DEFAULT_CL_NODE_URL: &str = "http://127.0.0.1:3500";
 pub const DEFAULT_EL_NODE_IPC_PATH: &str = "/tmp/reth.ipc";
 pub const DEFAULT_INCOMING_BUNDLES_PORT: u16 = 8645;
 pub const DEFAULT_RETH_DB_PATH: &str = "/mnt/data/reth";
+/// Disabled, skipping a slot for a stale parent is an operator decision (a reasonable mainnet value is a few slots, eg: 36).
+pub const DEFAULT_MAX_PARENT_AGE_SECS: u64 = 0;
 
 impl Default for BaseConfig {
     fn default() -> Self {
         Self {
+            profile: None,
             telemetry_port: 6069,
             telemetry_ip: None,
             log_json: false,
@@ -516,6 +1172,7 @@ impl Default for BaseConfig {
             coinbase_secret_key: "".into(),
             relay_secret_key: "".into(),
             optimistic_relay_secret_key: "".into(),
+            signing_identities: vec![],
             optimistic_enabled: false,
             optimistic_max_bid_value_eth: "0.0".to_string(),
             flashbots_db: None,
@@ -524,19 +1181,31 @@ impl Default for BaseConfig {
             cl_node_url: vec!["http://127.0.0.1:3500".to_string()],
             jsonrpc_server_port: DEFAULT_INCOMING_BUNDLES_PORT,
             jsonrpc_server_ip: None,
+            bundle_feedback_sim_budget_ms: 50,
+            jsonrpc_max_request_body_size: DEFAULT_MAX_BODY_SIZE,
+            jsonrpc_max_batch_size: DEFAULT_MAX_BATCH_SIZE,
             ignore_cancellable_orders: true,
             ignore_blobs: false,
+            private_order_signers: vec![],
             chain: "mainnet".to_string(),
             reth_datadir: Some(DEFAULT_RETH_DB_PATH.parse().unwrap()),
             reth_db_path: None,
             reth_static_files_path: None,
             blocklist_file_path: None,
             extra_data: "extra_data_change_me".to_string(),
+            builder_identity: BuilderIdentity::default(),
+            token_prices: vec![],
             relays: vec![],
             dry_run: false,
             dry_run_validation_url: vec![],
             root_hash_task_pool_threads: 1,
+            estimate_gas: None,
+            pending_account_rpc: false,
+            canary: None,
+            kill_switch: None,
+            bid_floor: None,
             watchdog_timeout_sec: 60 * 3,
+            max_parent_age_secs: DEFAULT_MAX_PARENT_AGE_SECS,
             backtest_fetch_mempool_data_dir: "/mnt/data/mempool".into(),
             backtest_fetch_eth_rpc_url: "http://127.0.0.1:8545".to_string(),
             backtest_fetch_eth_rpc_parallel: 1,
@@ -546,8 +1215,17 @@ impl Default for BaseConfig {
             live_builders: vec!["mgp-ordering".to_string(), "mp-ordering".to_string()],
             optimistic_prevalidate_optimistic_blocks: false,
             simulation_threads: 1,
+            simulation_overload_filter: None,
+            simulation_priority_lane: None,
             sbundle_mergeabe_signers: None,
             slot_delta_to_start_submits_ms: None,
+            fast_relay_window_ms: None,
+            max_gas_used_percent: None,
+            best_block_min_improvement_wei: 0,
+            max_best_block_supersessions: None,
+            slot_block_metrics: false,
+            allow_known_test_keys: false,
+            experiments: ExperimentsConfig::default(),
         }
     }
 }
@@ -601,6 +1279,32 @@ pub fn coinbase_signer_from_secret_key(secret_key: &str) -> eyre::Result<Signer>
     Ok(Signer::try_from_secret(secret_key)?)
 }
 
+/// Refuses well-known test keys on mainnet and warns about them elsewhere unless allow_known_test_keys is set.
+fn check_known_test_key(
+    key_name: &str,
+    address: Address,
+    chain: Chain,
+    allow_known_test_keys: bool,
+) -> eyre::Result<()> {
+    if !KNOWN_TEST_KEY_ADDRESSES.contains(&address) {
+        return Ok(());
+    }
+    if chain == Chain::mainnet() {
+        eyre::bail!(
+            "{} is a well-known test key ({:?}), refusing to use it on mainnet",
+            key_name,
+            address
+        );
+    }
+    if !allow_known_test_keys {
+        warn!(
+            "{} is a well-known test key ({:?}), anyone can spend its funds. Set allow_known_test_keys if this is intended",
+            key_name, address
+        );
+    }
+    Ok(())
+}
+
 fn get_signing_domain(chain: Chain, beacon_clients: Vec<Client>) -> eyre::Result<B256> {
     let cl_context = match chain.kind() {
         ChainKind::Named(NamedChain::Mainnet) => ContextEth::for_mainnet(),
@@ -660,6 +1364,33 @@ mod test {
         assert_eq!(config, config_default);
     }
 
+    #[test]
+    fn test_known_test_key_refused_on_mainnet() {
+        let anvil_address = KNOWN_TEST_KEY_ADDRESSES[0];
+        assert!(check_known_test_key("key", anvil_address, Chain::mainnet(), false).is_err());
+        assert!(check_known_test_key("key", anvil_address, Chain::mainnet(), true).is_err());
+        assert!(check_known_test_key("key", Address::ZERO, Chain::mainnet(), false).is_ok());
+    }
+
+    #[test]
+    fn test_known_test_key_devnet_override() {
+        let anvil_address = KNOWN_TEST_KEY_ADDRESSES[0];
+        let devnet = Chain::from_id(12345);
+        assert!(check_known_test_key("key", anvil_address, devnet, false).is_ok());
+        assert!(check_known_test_key("key", anvil_address, devnet, true).is_ok());
+
+        let config = BaseConfig {
+            coinbase_secret_key:
+                "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80".into(),
+            chain: "mainnet".to_string(),
+            allow_known_test_keys: true,
+            ..Default::default()
+        };
+        assert_eq!(config.coinbase_signer().unwrap().address, anvil_address);
+        assert!(config.validate_signer_keys().is_err());
+        assert!(config.validate().has_error_at("coinbase_secret_key"));
+    }
+
     #[test]
     fn test_signing_domain_known_chains() {
         let cases = [
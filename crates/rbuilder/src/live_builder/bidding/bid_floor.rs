@@ -0,0 +1,451 @@
+//! Some proposers (eg: staking pools) keep getting high bids, bidding below what they usually get only wastes submissions.
+//! [`WinningBidStats`] keeps the recent winning bids (relay data API proposer_payload_delivered) of every fee recipient and
+//! [`BidFloorBiddingService`] wraps the slot bidders so they don't finalize blocks below a percentile of them.
+//! Fee recipients with less than min_samples winning bids use the stats of all the fee recipients.
+//! The stats are saved to stats_path on every new winning bid so they survive restarts.
+use super::{BiddingService, FillPassAction, FillPassInfo, SealInstruction, SlotBidder};
+use crate::primitives::mev_boost::MevBoostRelay;
+use ahash::HashMap;
+use alloy_primitives::{Address, U256};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+use tracing::{trace, warn};
+
+/// When building for slot N the payload of N - 1 may not be delivered yet, N - BACKFILL_SLOT_LAG is.
+const BACKFILL_SLOT_LAG: u64 = 2;
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct BidFloorConfig {
+    /// Winning bids kept per fee recipient (and for the global stats).
+    pub max_samples: usize,
+    /// Percentile (0-100) of the recent winning bids used as floor.
+    pub percentile: u64,
+    /// Min winning bids of a fee recipient to use its stats, the global stats need as many to give a floor.
+    pub min_samples: usize,
+    pub stats_path: Option<PathBuf>,
+}
+
+impl Default for BidFloorConfig {
+    fn default() -> Self {
+        Self {
+            max_samples: 100,
+            percentile: 10,
+            min_samples: 10,
+            stats_path: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WinningBid {
+    pub slot: u64,
+    pub value: U256,
+}
+
+/// Rolling window of the last max_samples winning bids per fee recipient and globally.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WinningBidStats {
+    max_samples: usize,
+    by_fee_recipient: HashMap<Address, VecDeque<WinningBid>>,
+    global: VecDeque<WinningBid>,
+}
+
+impl WinningBidStats {
+    pub fn new(max_samples: usize) -> Self {
+        Self {
+            max_samples,
+            ..Default::default()
+        }
+    }
+
+    /// Missing file -> empty stats.
+    pub fn load(path: &Path, max_samples: usize) -> eyre::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::new(max_samples));
+        }
+        let mut stats: Self = serde_json::from_str(&fs::read_to_string(path)?)?;
+        stats.max_samples = max_samples;
+        for bids in stats
+            .by_fee_recipient
+            .values_mut()
+            .chain(std::iter::once(&mut stats.global))
+        {
+            truncate_front(bids, max_samples);
+        }
+        Ok(stats)
+    }
+
+    pub fn save(&self, path: &Path) -> eyre::Result<()> {
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Returns false if the slot was already recorded.
+    pub fn record(&mut self, fee_recipient: Address, bid: WinningBid) -> bool {
+        if self.global.iter().any(|recorded| recorded.slot == bid.slot) {
+            return false;
+        }
+        for bids in [
+            self.by_fee_recipient.entry(fee_recipient).or_default(),
+            &mut self.global,
+        ] {
+            bids.push_back(bid);
+            truncate_front(bids, self.max_samples);
+        }
+        true
+    }
+
+    pub fn samples(&self, fee_recipient: Address) -> usize {
+        self.by_fee_recipient
+            .get(&fee_recipient)
+            .map_or(0, |bids| bids.len())
+    }
+
+    /// percentile of the winning bids of fee_recipient (global ones if it has less than min_samples).
+    /// None if the global stats don't have min_samples either.
+    pub fn floor(
+        &self,
+        fee_recipient: Address,
+        percentile: u64,
+        min_samples: usize,
+    ) -> Option<U256> {
+        let min_samples = min_samples.max(1);
+        let bids = match self.by_fee_recipient.get(&fee_recipient) {
+            Some(bids) if bids.len() >= min_samples => bids,
+            _ if self.global.len() >= min_samples => &self.global,
+            _ => return None,
+        };
+        let mut values: Vec<U256> = bids.iter().map(|bid| bid.value).collect();
+        values.sort();
+        let index = (values.len() - 1) * percentile.min(100) as usize / 100;
+        Some(values[index])
+    }
+}
+
+fn truncate_front(bids: &mut VecDeque<WinningBid>, max_samples: usize) {
+    while bids.len() > max_samples {
+        bids.pop_front();
+    }
+}
+
+/// Records the payload delivered for slot by the first relay that has it.
+pub async fn backfill_slot(
+    stats: &Mutex<WinningBidStats>,
+    stats_path: Option<&Path>,
+    relays: &[MevBoostRelay],
+    slot: u64,
+) {
+    for relay in relays {
+        match relay.client.proposer_payload_delivered_slot(slot).await {
+            Ok(Some(payload)) => {
+                let mut stats = stats.lock().unwrap();
+                let bid = WinningBid {
+                    slot,
+                    value: payload.value,
+                };
+                if stats.record(payload.proposer_fee_recipient, bid) {
+                    if let Some(path) = stats_path {
+                        if let Err(err) = stats.save(path) {
+                            warn!(?err, "Failed to save winning bid stats");
+                        }
+                    }
+                }
+                return;
+            }
+            Ok(None) => {}
+            Err(err) => warn!(?err, slot, relay = %relay.id, "Failed to get delivered payload"),
+        }
+    }
+}
+
+/// Wraps inner declining the blocks that can't bid floor.
+#[derive(Debug)]
+pub struct BidFloorSlotBidder {
+    inner: Arc<dyn SlotBidder>,
+    floor: Option<U256>,
+}
+
+impl BidFloorSlotBidder {
+    pub fn new(inner: Arc<dyn SlotBidder>, floor: Option<U256>) -> Self {
+        Self { inner, floor }
+    }
+
+    pub fn floor(&self) -> Option<U256> {
+        self.floor
+    }
+
+    fn below_floor(&self, value: U256) -> bool {
+        self.floor.map_or(false, |floor| value < floor)
+    }
+}
+
+impl SlotBidder for BidFloorSlotBidder {
+    fn is_pay_to_coinbase_allowed(&self) -> bool {
+        self.inner.is_pay_to_coinbase_allowed()
+    }
+
+    fn seal_instruction(
+        &self,
+        unsealed_block_profit: U256,
+        slot_timestamp: time::OffsetDateTime,
+    ) -> SealInstruction {
+        match self
+            .inner
+            .seal_instruction(unsealed_block_profit, slot_timestamp)
+        {
+            SealInstruction::Value(value) if self.below_floor(value) => SealInstruction::Skip,
+            instruction => instruction,
+        }
+    }
+
+    fn best_bid_value(&self) -> Option<U256> {
+        self.inner.best_bid_value()
+    }
+
+    /// true_block_value is the most we could bid, no need to finalize if it's under the floor.
+    fn on_fill_pass(&self, info: &FillPassInfo) -> FillPassAction {
+        if self.below_floor(info.true_block_value) {
+            trace!(
+                true_block_value = ?info.true_block_value,
+                floor = ?self.floor,
+                "Block value under the bid floor, skipping"
+            );
+            return FillPassAction::SkipSubmission;
+        }
+        self.inner.on_fill_pass(info)
+    }
+}
+
+/// Wraps the slot bidders of inner with [`BidFloorSlotBidder`]s and backfills the stats from the relays
+/// as the slots go by (if called from a tokio runtime).
+#[derive(Debug)]
+pub struct BidFloorBiddingService {
+    inner: Box<dyn BiddingService>,
+    config: BidFloorConfig,
+    stats: Arc<Mutex<WinningBidStats>>,
+    relays: Vec<MevBoostRelay>,
+}
+
+impl BidFloorBiddingService {
+    /// Loads the stats from config.stats_path.
+    pub fn new(
+        inner: Box<dyn BiddingService>,
+        config: BidFloorConfig,
+        relays: Vec<MevBoostRelay>,
+    ) -> eyre::Result<Self> {
+        let stats = match &config.stats_path {
+            Some(path) => WinningBidStats::load(path, config.max_samples)?,
+            None => WinningBidStats::new(config.max_samples),
+        };
+        Ok(Self::with_stats(inner, config, relays, stats))
+    }
+
+    pub fn with_stats(
+        inner: Box<dyn BiddingService>,
+        config: BidFloorConfig,
+        relays: Vec<MevBoostRelay>,
+        stats: WinningBidStats,
+    ) -> Self {
+        Self {
+            inner,
+            config,
+            stats: Arc::new(Mutex::new(stats)),
+            relays,
+        }
+    }
+
+    pub fn stats(&self) -> Arc<Mutex<WinningBidStats>> {
+        self.stats.clone()
+    }
+
+    fn spawn_backfill(&self, slot: u64) {
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        if self.relays.is_empty() {
+            return;
+        }
+        let stats = self.stats.clone();
+        let stats_path = self.config.stats_path.clone();
+        let relays = self.relays.clone();
+        runtime.spawn(async move {
+            backfill_slot(&stats, stats_path.as_deref(), &relays, slot).await;
+        });
+    }
+}
+
+impl BiddingService for BidFloorBiddingService {
+    fn create_slot_bidder(
+        &mut self,
+        block: u64,
+        slot: u64,
+        slot_end_timestamp: u64,
+        fee_recipient: Address,
+    ) -> Arc<dyn SlotBidder> {
+        self.spawn_backfill(slot.saturating_sub(BACKFILL_SLOT_LAG));
+        let floor = self.stats.lock().unwrap().floor(
+            fee_recipient,
+            self.config.percentile,
+            self.config.min_samples,
+        );
+        let inner = self
+            .inner
+            .create_slot_bidder(block, slot, slot_end_timestamp, fee_recipient);
+        Arc::new(BidFloorSlotBidder::new(inner, floor))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::live_builder::bidding::DummyBiddingService;
+    use tempfile::TempDir;
+
+    const POOL: Address = Address::repeat_byte(0x01);
+    const SOLO: Address = Address::repeat_byte(0x02);
+
+    fn fill_info(true_block_value: u64) -> FillPassInfo {
+        FillPassInfo {
+            remaining_gas: 0,
+            true_block_value: U256::from(true_block_value),
+            iteration: 0,
+            time_into_slot: time::Duration::ZERO,
+        }
+    }
+
+    /// POOL won slots 0..10 with 100..1000, SOLO won slots 10..15 with 1..5.
+    fn seeded_stats() -> WinningBidStats {
+        let mut stats = WinningBidStats::new(100);
+        for slot in 0..10 {
+            let value = U256::from((slot + 1) * 100);
+            assert!(stats.record(POOL, WinningBid { slot, value }));
+        }
+        for slot in 10..15 {
+            let value = U256::from(slot - 9);
+            assert!(stats.record(SOLO, WinningBid { slot, value }));
+        }
+        stats
+    }
+
+    #[test]
+    fn test_floor() {
+        let mut stats = seeded_stats();
+        assert!(!stats.record(
+            SOLO,
+            WinningBid {
+                slot: 0,
+                value: U256::from(1)
+            }
+        ));
+        assert_eq!(stats.floor(POOL, 50, 10), Some(U256::from(500)));
+        assert_eq!(stats.floor(POOL, 0, 10), Some(U256::from(100)));
+        assert_eq!(stats.floor(POOL, 100, 10), Some(U256::from(1000)));
+        // SOLO and unknown recipients fall back to the 15 global bids: 1..5, 100..1000
+        assert_eq!(stats.floor(SOLO, 50, 10), Some(U256::from(300)));
+        assert_eq!(stats.floor(Address::ZERO, 50, 10), Some(U256::from(300)));
+        assert_eq!(stats.floor(SOLO, 50, 5), Some(U256::from(3)));
+        assert_eq!(stats.floor(SOLO, 50, 20), None);
+
+        // window
+        let mut stats = WinningBidStats::new(2);
+        for slot in 0..3 {
+            stats.record(
+                POOL,
+                WinningBid {
+                    slot,
+                    value: U256::from(slot),
+                },
+            );
+        }
+        assert_eq!(stats.samples(POOL), 2);
+        assert_eq!(stats.floor(POOL, 0, 1), Some(U256::from(1)));
+    }
+
+    #[test]
+    fn test_bidder_declines_below_floor() {
+        let mut service = BidFloorBiddingService::with_stats(
+            Box::new(DummyBiddingService {}),
+            BidFloorConfig::default(),
+            Vec::new(),
+            seeded_stats(),
+        );
+        // floor 100
+        let bidder = service.create_slot_bidder(1, 20, 0, POOL);
+        let slot_timestamp = time::OffsetDateTime::now_utc();
+        assert_eq!(
+            bidder.on_fill_pass(&fill_info(99)),
+            FillPassAction::SkipSubmission
+        );
+        assert_eq!(
+            bidder.seal_instruction(U256::from(99), slot_timestamp),
+            SealInstruction::Skip
+        );
+        assert_eq!(
+            bidder.on_fill_pass(&fill_info(100)),
+            FillPassAction::ContinueFilling
+        );
+        assert_eq!(
+            bidder.seal_instruction(U256::from(150), slot_timestamp),
+            SealInstruction::Value(U256::from(150))
+        );
+
+        // global bids 1..5, 100..1000 -> floor 2
+        let bidder = service.create_slot_bidder(1, 20, 0, SOLO);
+        assert_eq!(
+            bidder.on_fill_pass(&fill_info(1)),
+            FillPassAction::SkipSubmission
+        );
+        assert_eq!(
+            bidder.on_fill_pass(&fill_info(2)),
+            FillPassAction::ContinueFilling
+        );
+
+        // not enough history, no floor
+        let mut service = BidFloorBiddingService::with_stats(
+            Box::new(DummyBiddingService {}),
+            BidFloorConfig::default(),
+            Vec::new(),
+            WinningBidStats::new(100),
+        );
+        let bidder = service.create_slot_bidder(1, 20, 0, POOL);
+        assert_eq!(
+            bidder.on_fill_pass(&fill_info(1)),
+            FillPassAction::ContinueFilling
+        );
+    }
+
+    #[test]
+    fn test_stats_persist() -> eyre::Result<()> {
+        let tempdir = TempDir::with_prefix_in("rbuilder-", "/tmp")?;
+        let path = tempdir.path().join("bid_floor.json");
+        assert_eq!(
+            WinningBidStats::load(&path, 100)?,
+            WinningBidStats::new(100)
+        );
+
+        let stats = seeded_stats();
+        stats.save(&path)?;
+        assert_eq!(WinningBidStats::load(&path, 100)?, stats);
+
+        let service = BidFloorBiddingService::new(
+            Box::new(DummyBiddingService {}),
+            BidFloorConfig {
+                max_samples: 5,
+                stats_path: Some(path),
+                ..Default::default()
+            },
+            Vec::new(),
+        )?;
+        let stats = service.stats();
+        let stats = stats.lock().unwrap();
+        assert_eq!(stats.samples(POOL), 5);
+        assert_eq!(stats.floor(POOL, 0, 5), Some(U256::from(600)));
+        Ok(())
+    }
+}
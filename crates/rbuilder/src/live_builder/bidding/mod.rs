@@ -1,6 +1,8 @@
+pub mod bid_floor;
+
 use std::sync::Arc;
 
-use alloy_primitives::U256;
+use alloy_primitives::{Address, U256};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum SealInstruction {
@@ -10,6 +12,31 @@ pub enum SealInstruction {
     Value(U256),
 }
 
+/// State of the block after a builder finished filling it (before finalizing).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillPassInfo {
+    /// Block gas limit - gas used - gas reserved (eg: payout tx).
+    pub remaining_gas: u64,
+    /// Coinbase profit before paying the proposer.
+    pub true_block_value: U256,
+    /// Number of fill passes of the builder on this slot before this one.
+    pub iteration: usize,
+    /// now - slot timestamp, negative before the slot starts.
+    pub time_into_slot: time::Duration,
+}
+
+/// What the builder should do with the block it just filled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FillPassAction {
+    /// Finalize and submit the block as usual and keep building new blocks.
+    #[default]
+    ContinueFilling,
+    /// Finalize and submit the block and stop building for this slot.
+    FinalizeNow,
+    /// Drop the block without finalizing it and keep building new blocks.
+    SkipSubmission,
+}
+
 /// Slot bidder is used by builder to decide what value should be put into the last tx.
 /// It is created for each block / slot.
 pub trait SlotBidder: Send + Sync + std::fmt::Debug {
@@ -25,6 +52,11 @@ pub trait SlotBidder: Send + Sync + std::fmt::Debug {
 
     /// Returns best bid value available on the relays.
     fn best_bid_value(&self) -> Option<U256>;
+
+    /// Called by the builders after each fill pass.
+    fn on_fill_pass(&self, _info: &FillPassInfo) -> FillPassAction {
+        FillPassAction::ContinueFilling
+    }
 }
 
 impl SlotBidder for () {
@@ -51,6 +83,7 @@ pub trait BiddingService: std::fmt::Debug {
         block: u64,
         slot: u64,
         slot_end_timestamp: u64,
+        fee_recipient: Address,
     ) -> Arc<dyn SlotBidder>;
 }
 
@@ -58,7 +91,7 @@ pub trait BiddingService: std::fmt::Debug {
 #[derive(Debug)]
 pub struct DummyBiddingService {}
 impl BiddingService for DummyBiddingService {
-    fn create_slot_bidder(&mut self, _: u64, _: u64, _: u64) -> Arc<dyn SlotBidder> {
+    fn create_slot_bidder(&mut self, _: u64, _: u64, _: u64, _: Address) -> Arc<dyn SlotBidder> {
         Arc::new(())
     }
 }
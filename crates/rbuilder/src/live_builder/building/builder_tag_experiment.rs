@@ -0,0 +1,214 @@
+//! Experiment to measure if relays/validators treat our blocks differently depending on the builder tag.
+//! Each slot gets a tag (extra_data + signing identity used for the bid pubkey + coinbase signer) from a configured pool.
+//! Every tag has its own coinbase, a shared one would link all the tags on chain.
+//! The tag of a slot is a keyed hash of the slot so the schedule is reproducible by us but can't be inferred from outside
+//! without the seed. What was actually sent is appended to a local log (one json line per accepted submission) so results
+//! can be joined later.
+//! Only available if enabled in the `experiments` config block.
+use crate::{
+    live_builder::building::relay_identities::{
+        RelayIdentities, RelayIdentityError, RelayIdentitySnapshot,
+    },
+    mev_boost::SubmitBlockRequest,
+    utils::Signer,
+};
+use alloy_primitives::{keccak256, Address, Bytes, B256, U256};
+use alloy_rpc_types_beacon::BlsPublicKey;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, Write},
+    path::Path,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum BuilderTagExperimentError {
+    #[error("No builder tags configured")]
+    NoTags,
+    #[error("Builder tag extra_data is too long: {0} bytes")]
+    ExtraDataTooLong(usize),
+    #[error("Coinbase {0:?} is used by more than one builder tag")]
+    SharedCoinbase(Address),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BuilderTag {
+    pub extra_data: Vec<u8>,
+    /// Name of one of the signing identities (see [`RelayIdentities`]).
+    pub signing_identity: String,
+    /// Coinbase (and payout tx signer) of the blocks of the slots with this tag.
+    pub coinbase_signer: Signer,
+}
+
+#[derive(Debug, Clone)]
+pub struct BuilderTagSchedule {
+    seed: Vec<u8>,
+    tags: Vec<BuilderTag>,
+}
+
+impl BuilderTagSchedule {
+    pub fn new(seed: Vec<u8>, tags: Vec<BuilderTag>) -> Result<Self, BuilderTagExperimentError> {
+        if tags.is_empty() {
+            return Err(BuilderTagExperimentError::NoTags);
+        }
+        if let Some(tag) = tags.iter().find(|tag| tag.extra_data.len() > 32) {
+            return Err(BuilderTagExperimentError::ExtraDataTooLong(
+                tag.extra_data.len(),
+            ));
+        }
+        for (i, tag) in tags.iter().enumerate() {
+            let coinbase = tag.coinbase_signer.address;
+            if tags[..i]
+                .iter()
+                .any(|other| other.coinbase_signer.address == coinbase)
+            {
+                return Err(BuilderTagExperimentError::SharedCoinbase(coinbase));
+            }
+        }
+        Ok(Self { seed, tags })
+    }
+
+    pub fn tags(&self) -> &[BuilderTag] {
+        &self.tags
+    }
+
+    /// Depends only on seed, slot and the configured tags.
+    pub fn tag_for_slot(&self, slot: u64) -> &BuilderTag {
+        let mut data = self.seed.clone();
+        data.extend_from_slice(&slot.to_be_bytes());
+        let hash = U256::from_be_bytes(keccak256(data).0);
+        let index = hash % U256::from(self.tags.len());
+        &self.tags[index.to::<usize>()]
+    }
+}
+
+/// Line of the mapping log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuilderTagMappingEntry {
+    pub slot: u64,
+    pub block_hash: B256,
+    pub relay: String,
+    pub signing_identity: String,
+    pub builder_pubkey: BlsPublicKey,
+    pub extra_data: Bytes,
+    pub coinbase: Address,
+}
+
+impl BuilderTagMappingEntry {
+    pub fn new(submission: &SubmitBlockRequest, relay: &str, signing_identity: &str) -> Self {
+        let bid_trace = submission.bid_trace();
+        Self {
+            slot: bid_trace.slot,
+            block_hash: bid_trace.block_hash,
+            relay: relay.to_string(),
+            signing_identity: signing_identity.to_string(),
+            builder_pubkey: bid_trace.builder_pubkey,
+            extra_data: submission.extra_data(),
+            coinbase: submission.fee_recipient(),
+        }
+    }
+}
+
+/// Append only json lines file.
+#[derive(Debug)]
+pub struct BuilderTagMappingLog {
+    file: Mutex<File>,
+}
+
+impl BuilderTagMappingLog {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn record(&self, entry: &BuilderTagMappingEntry) -> io::Result<()> {
+        let mut line = serde_json::to_vec(entry)?;
+        line.push(b'\n');
+        self.file.lock().unwrap().write_all(&line)
+    }
+}
+
+/// Cheap to clone, all the clones share the mapping log.
+#[derive(Debug, Clone)]
+pub struct BuilderTagExperiment {
+    pub schedule: BuilderTagSchedule,
+    pub mapping_log: Arc<BuilderTagMappingLog>,
+}
+
+impl BuilderTagExperiment {
+    pub fn new(schedule: BuilderTagSchedule, mapping_log: BuilderTagMappingLog) -> Self {
+        Self {
+            schedule,
+            mapping_log: Arc::new(mapping_log),
+        }
+    }
+
+    /// All the relays get the bids of the slot signed by the identity of the slot tag.
+    pub fn relay_identities_for_slot(
+        &self,
+        relay_identities: &RelayIdentities,
+        slot: u64,
+    ) -> Result<RelayIdentitySnapshot, RelayIdentityError> {
+        relay_identities.snapshot_forcing(&self.schedule.tag_for_slot(slot).signing_identity)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+
+    pub(crate) fn tag(extra_data: &str, signing_identity: &str, coinbase_key: u8) -> BuilderTag {
+        BuilderTag {
+            extra_data: extra_data.as_bytes().to_vec(),
+            signing_identity: signing_identity.to_string(),
+            coinbase_signer: Signer::try_from_secret(B256::repeat_byte(coinbase_key)).unwrap(),
+        }
+    }
+
+    pub(crate) fn schedule(seed: &str) -> BuilderTagSchedule {
+        BuilderTagSchedule::new(
+            seed.as_bytes().to_vec(),
+            vec![
+                tag("tag_a", "key1", 1),
+                tag("tag_b", "key2", 2),
+                tag("tag_c", "key3", 3),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn slot_tags(schedule: &BuilderTagSchedule) -> Vec<BuilderTag> {
+        (0..1000)
+            .map(|slot| schedule.tag_for_slot(slot).clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_schedule_determinism() {
+        let tags = slot_tags(&schedule("seed"));
+        assert_eq!(tags, slot_tags(&schedule("seed")));
+        assert_ne!(tags, slot_tags(&schedule("other seed")));
+        for expected in schedule("seed").tags() {
+            assert!(tags.contains(expected), "{:?} never scheduled", expected);
+        }
+
+        assert_eq!(
+            BuilderTagSchedule::new(vec![], vec![]).unwrap_err(),
+            BuilderTagExperimentError::NoTags
+        );
+        assert_eq!(
+            BuilderTagSchedule::new(vec![], vec![tag(&"x".repeat(33), "key1", 1)]).unwrap_err(),
+            BuilderTagExperimentError::ExtraDataTooLong(33)
+        );
+        let shared = tag("tag_b", "key2", 1);
+        assert_eq!(
+            BuilderTagSchedule::new(vec![], vec![tag("tag_a", "key1", 1), shared.clone()])
+                .unwrap_err(),
+            BuilderTagExperimentError::SharedCoinbase(shared.coinbase_signer.address)
+        );
+    }
+}
@@ -1,4 +1,9 @@
+pub mod builder_tag_experiment;
+pub mod relay_identities;
+pub mod relay_latency;
 pub mod relay_submit;
+pub mod submission_dedup;
+pub mod submission_slo;
 
 use std::{sync::Arc, time::Duration};
 
@@ -7,11 +12,13 @@ pub use relay_submit::SubmissionConfig;
 use crate::{
     building::{
         builders::{BlockBuildingAlgorithm, BlockBuildingAlgorithmInput, BuilderSinkFactory},
+        pending_accounts::PendingCandidates,
         BlockBuildingContext,
     },
     live_builder::{payload_events::MevBoostSlotData, simulation::SlotOrderSimResults},
     utils::ProviderFactoryReopener,
 };
+use alloy_primitives::Address;
 use reth_db::database::Database;
 use tokio::sync::{broadcast, mpsc};
 use tokio_util::sync::CancellationToken;
@@ -34,6 +41,9 @@ pub struct BlockBuildingPool<DB, BuilderSinkFactoryType: BuilderSinkFactory> {
     bidding_service: Box<dyn BiddingService>,
     orderpool_subscriber: order_input::OrderPoolSubscriber,
     order_simulation_pool: OrderSimulationPool<DB>,
+    /// Passed to every build (see [`BlockBuildingAlgorithmInput::sbundle_mergeabe_signers`]).
+    sbundle_mergeabe_signers: Option<Vec<Address>>,
+    pending_candidates: Arc<PendingCandidates>,
 }
 
 impl<DB: Database + Clone + 'static, BuilderSinkFactoryType: BuilderSinkFactory>
@@ -48,6 +58,8 @@ where
         bidding_service: Box<dyn BiddingService>,
         orderpool_subscriber: order_input::OrderPoolSubscriber,
         order_simulation_pool: OrderSimulationPool<DB>,
+        sbundle_mergeabe_signers: Option<Vec<Address>>,
+        pending_candidates: Arc<PendingCandidates>,
     ) -> Self {
         BlockBuildingPool {
             provider_factory,
@@ -56,6 +68,8 @@ where
             bidding_service,
             orderpool_subscriber,
             order_simulation_pool,
+            sbundle_mergeabe_signers,
+            pending_candidates,
         }
     }
 
@@ -110,6 +124,7 @@ where
             slot_data.block(),
             slot_data.slot(),
             slot_data.timestamp().unix_timestamp() as u64,
+            slot_data.fee_recipient(),
         );
 
         let builder_sink =
@@ -139,6 +154,8 @@ where
                 sink: builder_sink.clone(),
                 slot_bidder: slot_bidder.clone(),
                 cancel: cancel.clone(),
+                sbundle_mergeabe_signers: self.sbundle_mergeabe_signers.clone(),
+                pending_candidates: self.pending_candidates.clone(),
             };
             let builder = builder.clone();
             tokio::task::spawn_blocking(move || {
@@ -0,0 +1,438 @@
+//! Named BLS keys (signing identities) used to sign the normal (non optimistic) bids sent to specific relays.
+//! Relays without identity use the default relay_secret_key.
+//! Assignments can be changed at runtime, each slot submission job takes a [`RelayIdentitySnapshot`] when it starts so a
+//! change is effective from the next slot.
+//! The running builder registers its identities in [`RELAY_IDENTITIES`] so they can be switched from the telemetry server:
+//! /debug/relay_identities reports the assignments and /debug/relay_identities/assign?relay=...&identity=... changes
+//! one (no identity goes back to the default one).
+//!
+//! An identity can also have its own payout signer (the coinbase paying the proposer). Blocks are built once for all the
+//! relays so it's only used on the slots where every relay uses that identity, otherwise coinbase_secret_key pays.
+use crate::{mev_boost::BLSBlockSigner, primitives::mev_boost::MevBoostRelay, utils::Signer};
+use ahash::HashMap;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tracing::info;
+use warp::{http::StatusCode, Filter, Rejection, Reply};
+
+lazy_static! {
+    /// Identities of the running builder, None until it starts.
+    pub static ref RELAY_IDENTITIES: Arc<Mutex<Option<RelayIdentities>>> = Default::default();
+}
+
+/// Identity name used for relays with no identity assigned (signed with relay_secret_key).
+pub const DEFAULT_IDENTITY: &str = "default";
+/// Identity name used for optimistic submissions (signed with optimistic_relay_secret_key).
+pub const OPTIMISTIC_IDENTITY: &str = "optimistic";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum RelayIdentityError {
+    #[error("Unknown signing identity: {0}")]
+    UnknownIdentity(String),
+}
+
+/// Cheap to clone, all the clones share the assignments.
+#[derive(Debug, Clone, Default)]
+pub struct RelayIdentities {
+    signers: Arc<HashMap<String, BLSBlockSigner>>,
+    /// Identities with their own payout signer.
+    payout_signers: Arc<HashMap<String, Signer>>,
+    /// relay -> identity name
+    assignments: Arc<Mutex<HashMap<String, String>>>,
+}
+
+impl RelayIdentities {
+    pub fn new(
+        signers: HashMap<String, BLSBlockSigner>,
+        assignments: HashMap<String, String>,
+    ) -> Result<Self, RelayIdentityError> {
+        if let Some(identity) = assignments
+            .values()
+            .find(|identity| !signers.contains_key(*identity))
+        {
+            return Err(RelayIdentityError::UnknownIdentity(identity.clone()));
+        }
+        Ok(Self {
+            signers: Arc::new(signers),
+            payout_signers: Default::default(),
+            assignments: Arc::new(Mutex::new(assignments)),
+        })
+    }
+
+    pub fn with_payout_signers(
+        self,
+        payout_signers: HashMap<String, Signer>,
+    ) -> Result<Self, RelayIdentityError> {
+        if let Some(identity) = payout_signers
+            .keys()
+            .find(|identity| !self.signers.contains_key(*identity))
+        {
+            return Err(RelayIdentityError::UnknownIdentity(identity.clone()));
+        }
+        Ok(Self {
+            payout_signers: Arc::new(payout_signers),
+            ..self
+        })
+    }
+
+    /// Changes the identity used for relay, None goes back to the default one.
+    /// Effective from the next slot.
+    pub fn assign(&self, relay: &str, identity: Option<&str>) -> Result<(), RelayIdentityError> {
+        let mut assignments = self.assignments.lock().unwrap();
+        match identity {
+            Some(identity) => {
+                if !self.signers.contains_key(identity) {
+                    return Err(RelayIdentityError::UnknownIdentity(identity.to_string()));
+                }
+                assignments.insert(relay.to_string(), identity.to_string());
+            }
+            None => {
+                assignments.remove(relay);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, identity: &str) -> bool {
+        self.signers.contains_key(identity)
+    }
+
+    /// Known identities and the relays assigned to them (relays without identity are not listed).
+    pub fn status(&self) -> RelayIdentitiesStatus {
+        let mut identities: Vec<String> = self.signers.keys().cloned().collect();
+        identities.sort();
+        RelayIdentitiesStatus {
+            identities,
+            assignments: self
+                .assignments
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(relay, identity)| (relay.clone(), identity.clone()))
+                .collect(),
+        }
+    }
+
+    /// Payout signer for a block going to all the relays with the current assignments.
+    /// None (use the default coinbase) unless all of them use identities with the same payout signer.
+    pub fn payout_signer(&self, relays: &[MevBoostRelay]) -> Option<Signer> {
+        let snapshot = self.snapshot();
+        let mut payout_signer: Option<&Signer> = None;
+        for relay in relays {
+            let signer = self.payout_signers.get(snapshot.identity(&relay.id))?;
+            match payout_signer {
+                Some(payout_signer) if payout_signer != signer => return None,
+                _ => payout_signer = Some(signer),
+            }
+        }
+        payout_signer.cloned()
+    }
+
+    pub fn snapshot(&self) -> RelayIdentitySnapshot {
+        let relay_identities = self.assignments.lock().unwrap().clone();
+        let signers = relay_identities
+            .values()
+            .map(|identity| (identity.clone(), self.signers[identity].clone()))
+            .collect();
+        RelayIdentitySnapshot {
+            relay_identities,
+            forced_identity: None,
+            signers,
+        }
+    }
+
+    /// Snapshot where every relay uses identity, ignoring the assignments.
+    pub fn snapshot_forcing(
+        &self,
+        identity: &str,
+    ) -> Result<RelayIdentitySnapshot, RelayIdentityError> {
+        let signer = self
+            .signers
+            .get(identity)
+            .ok_or_else(|| RelayIdentityError::UnknownIdentity(identity.to_string()))?;
+        Ok(RelayIdentitySnapshot {
+            relay_identities: HashMap::default(),
+            forced_identity: Some(identity.to_string()),
+            signers: [(identity.to_string(), signer.clone())]
+                .into_iter()
+                .collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelayIdentitiesStatus {
+    pub identities: Vec<String>,
+    /// relay -> identity name
+    pub assignments: BTreeMap<String, String>,
+}
+
+/// Assignments at the beginning of a slot.
+#[derive(Debug, Clone, Default)]
+pub struct RelayIdentitySnapshot {
+    relay_identities: HashMap<String, String>,
+    /// If set used for all the relays (see [`RelayIdentities::snapshot_forcing`]).
+    forced_identity: Option<String>,
+    /// Only the identities assigned to some relay.
+    signers: HashMap<String, BLSBlockSigner>,
+}
+
+impl RelayIdentitySnapshot {
+    pub fn identity(&self, relay: &str) -> &str {
+        if let Some(identity) = &self.forced_identity {
+            return identity;
+        }
+        self.relay_identities
+            .get(relay)
+            .map(|identity| identity.as_str())
+            .unwrap_or(DEFAULT_IDENTITY)
+    }
+
+    /// Assigned identities (DEFAULT_IDENTITY not included) and their signers.
+    pub fn signers(&self) -> impl Iterator<Item = (&str, &BLSBlockSigner)> {
+        self.signers
+            .iter()
+            .map(|(identity, signer)| (identity.as_str(), signer))
+    }
+
+    /// Splits relays by identity keeping the relative order of the relays.
+    pub fn group_relays(&self, relays: &[MevBoostRelay]) -> Vec<(String, Vec<MevBoostRelay>)> {
+        let mut groups: Vec<(String, Vec<MevBoostRelay>)> = Vec::new();
+        for relay in relays {
+            let identity = self.identity(&relay.id);
+            match groups.iter_mut().find(|(name, _)| name == identity) {
+                Some((_, group)) => group.push(relay.clone()),
+                None => groups.push((identity.to_string(), vec![relay.clone()])),
+            }
+        }
+        groups
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct AssignQuery {
+    relay: String,
+    identity: Option<String>,
+}
+
+/// /debug/relay_identities and /debug/relay_identities/assign?relay=...&identity=...
+pub fn relay_identity_routes(
+    relay_identities: Arc<Mutex<Option<RelayIdentities>>>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let with_identities = warp::any().map(move || relay_identities.lock().unwrap().clone());
+    let status_route = warp::path!("debug" / "relay_identities")
+        .and(with_identities.clone())
+        .map(
+            |relay_identities: Option<RelayIdentities>| match relay_identities {
+                Some(relay_identities) => warp::reply::with_status(
+                    warp::reply::json(&relay_identities.status()),
+                    StatusCode::OK,
+                ),
+                None => not_running(),
+            },
+        );
+    let assign_route = warp::path!("debug" / "relay_identities" / "assign")
+        .and(warp::query::<AssignQuery>())
+        .and(with_identities)
+        .map(
+            |query: AssignQuery, relay_identities: Option<RelayIdentities>| {
+                let relay_identities = match relay_identities {
+                    Some(relay_identities) => relay_identities,
+                    None => return not_running(),
+                };
+                match relay_identities.assign(&query.relay, query.identity.as_deref()) {
+                    Ok(()) => {
+                        info!(
+                            relay = query.relay,
+                            identity = ?query.identity,
+                            "Relay signing identity changed, effective next slot"
+                        );
+                        warp::reply::with_status(
+                            warp::reply::json(&relay_identities.status()),
+                            StatusCode::OK,
+                        )
+                    }
+                    Err(err) => warp::reply::with_status(
+                        warp::reply::json(&err.to_string()),
+                        StatusCode::BAD_REQUEST,
+                    ),
+                }
+            },
+        );
+    status_route.or(assign_route)
+}
+
+fn not_running() -> warp::reply::WithStatus<warp::reply::Json> {
+    warp::reply::with_status(
+        warp::reply::json(&"No builder running"),
+        StatusCode::SERVICE_UNAVAILABLE,
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use alloy_primitives::B256;
+    use ethereum_consensus::crypto::SecretKey;
+
+    fn signer(key_byte: u8) -> BLSBlockSigner {
+        let sec = SecretKey::try_from([key_byte; 32].as_slice()).unwrap();
+        BLSBlockSigner::new(sec, Default::default()).unwrap()
+    }
+
+    fn payout_signer(key_byte: u8) -> Signer {
+        Signer::try_from_secret(B256::repeat_byte(key_byte)).unwrap()
+    }
+
+    fn relay(id: &str) -> MevBoostRelay {
+        MevBoostRelay::try_from_name_or_url(
+            id,
+            "http://127.0.0.1:1",
+            0,
+            false,
+            false,
+            false,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn identities() -> RelayIdentities {
+        RelayIdentities::new(
+            [
+                ("key1".to_string(), signer(1)),
+                ("key2".to_string(), signer(2)),
+            ]
+            .into_iter()
+            .collect(),
+            [
+                ("relay_a".to_string(), "key1".to_string()),
+                ("relay_b".to_string(), "key2".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_group_relays_by_identity() {
+        let snapshot = identities().snapshot();
+        let relays = [
+            relay("relay_a"),
+            relay("relay_c"),
+            relay("relay_b"),
+            relay("relay_d"),
+        ];
+        let groups: Vec<(String, Vec<String>)> = snapshot
+            .group_relays(&relays)
+            .into_iter()
+            .map(|(identity, relays)| (identity, relays.into_iter().map(|r| r.id).collect()))
+            .collect();
+        assert_eq!(
+            groups,
+            vec![
+                ("key1".to_string(), vec!["relay_a".to_string()]),
+                (
+                    DEFAULT_IDENTITY.to_string(),
+                    vec!["relay_c".to_string(), "relay_d".to_string()]
+                ),
+                ("key2".to_string(), vec!["relay_b".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_identity_is_rejected() {
+        assert_eq!(
+            RelayIdentities::new(
+                HashMap::default(),
+                [("relay_a".to_string(), "key1".to_string())]
+                    .into_iter()
+                    .collect(),
+            )
+            .unwrap_err(),
+            RelayIdentityError::UnknownIdentity("key1".to_string())
+        );
+        assert_eq!(
+            identities()
+                .with_payout_signers(
+                    [("key3".to_string(), payout_signer(3))]
+                        .into_iter()
+                        .collect()
+                )
+                .unwrap_err(),
+            RelayIdentityError::UnknownIdentity("key3".to_string())
+        );
+    }
+
+    #[test]
+    fn test_payout_signer_needs_all_relays() {
+        let identities = identities()
+            .with_payout_signers(
+                [
+                    ("key1".to_string(), payout_signer(1)),
+                    ("key2".to_string(), payout_signer(2)),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .unwrap();
+        let relays = [relay("relay_a"), relay("relay_b")];
+        assert_eq!(identities.payout_signer(&relays), None);
+        assert_eq!(
+            identities.payout_signer(&relays[..1]),
+            Some(payout_signer(1))
+        );
+
+        identities.assign("relay_b", Some("key1")).unwrap();
+        assert_eq!(identities.payout_signer(&relays), Some(payout_signer(1)));
+        // relays on the default identity are paid by the default coinbase
+        identities.assign("relay_b", None).unwrap();
+        assert_eq!(identities.payout_signer(&relays), None);
+    }
+
+    #[tokio::test]
+    async fn test_assign_route() {
+        let registered = Arc::new(Mutex::new(None));
+        let routes = relay_identity_routes(registered.clone());
+        let request = |path: &str| warp::test::request().path(path).reply(&routes);
+
+        let response = request("/debug/relay_identities/assign?relay=relay_b&identity=key1").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        let identities = identities();
+        *registered.lock().unwrap() = Some(identities.clone());
+        let current_slot = identities.snapshot();
+        let response = request("/debug/relay_identities/assign?relay=relay_b&identity=key1").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = request("/debug/relay_identities/assign?relay=relay_a").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = request("/debug/relay_identities/assign?relay=relay_a&identity=key3").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let status: RelayIdentitiesStatus =
+            serde_json::from_slice(request("/debug/relay_identities").await.body()).unwrap();
+        assert_eq!(status.identities, vec!["key1", "key2"]);
+        assert_eq!(
+            status.assignments,
+            [("relay_b".to_string(), "key1".to_string())]
+                .into_iter()
+                .collect()
+        );
+        // slot in progress keeps its identities
+        assert_eq!(current_slot.identity("relay_a"), "key1");
+        assert_eq!(current_slot.identity("relay_b"), "key2");
+        let next_slot = identities.snapshot();
+        assert_eq!(next_slot.identity("relay_a"), DEFAULT_IDENTITY);
+        assert_eq!(next_slot.identity("relay_b"), "key1");
+    }
+}
@@ -0,0 +1,94 @@
+//! Tracking of the relay response latency.
+//! Near the end of the slot a relay that takes longer to answer (simulate) than the time we have left is useless,
+//! so the submission job uses these stats to decide which relays are still worth sending a bid to.
+use crate::{
+    primitives::mev_boost::MevBoostRelayID, telemetry::set_relay_latency_p90, utils::DurationWindow,
+};
+use ahash::HashMap;
+use lazy_static::lazy_static;
+use std::{sync::Mutex, time::Duration};
+
+/// Number of most recent responses per relay used for the percentiles.
+pub const LATENCY_SAMPLES: usize = 200;
+
+lazy_static! {
+    /// Process wide tracker fed by the relay submission tasks.
+    pub static ref RELAY_LATENCY_TRACKER: Mutex<RelayLatencyTracker> =
+        Mutex::new(RelayLatencyTracker::default());
+}
+
+#[derive(Debug, Default)]
+pub struct RelayLatencyTracker {
+    /// Last LATENCY_SAMPLES response latencies.
+    samples: HashMap<MevBoostRelayID, DurationWindow>,
+}
+
+impl RelayLatencyTracker {
+    pub fn record(&mut self, relay: &MevBoostRelayID, latency: Duration) {
+        self.samples
+            .entry(relay.clone())
+            .or_insert_with(|| DurationWindow::new(LATENCY_SAMPLES))
+            .push(latency);
+    }
+
+    /// None if we never got a response from the relay.
+    pub fn percentile(&self, relay: &MevBoostRelayID, percentile: f64) -> Option<Duration> {
+        self.samples.get(relay)?.percentile(percentile)
+    }
+
+    pub fn p90(&self, relay: &MevBoostRelayID) -> Option<Duration> {
+        self.percentile(relay, 0.9)
+    }
+
+    /// Relay latency fits if its p90 is below time_left.
+    /// Relays without samples fit since we have no reason to skip them.
+    pub fn fits(&self, relay: &MevBoostRelayID, time_left: Duration) -> bool {
+        self.p90(relay).map_or(true, |p90| p90 <= time_left)
+    }
+}
+
+/// Records the latency on the global tracker and updates the metrics.
+pub fn record_relay_latency(relay: &MevBoostRelayID, latency: Duration) {
+    let mut tracker = RELAY_LATENCY_TRACKER.lock().unwrap();
+    tracker.record(relay, latency);
+    if let Some(p90) = tracker.p90(relay) {
+        set_relay_latency_p90(relay, p90);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let relay: MevBoostRelayID = "relay".to_string();
+        let mut tracker = RelayLatencyTracker::default();
+        assert_eq!(tracker.p90(&relay), None);
+        assert!(tracker.fits(&relay, Duration::ZERO));
+
+        for ms in (1..=10).rev() {
+            tracker.record(&relay, Duration::from_millis(ms * 100));
+        }
+        assert_eq!(tracker.p90(&relay), Some(Duration::from_millis(900)));
+        assert_eq!(
+            tracker.percentile(&relay, 0.5),
+            Some(Duration::from_millis(500))
+        );
+        assert!(tracker.fits(&relay, Duration::from_millis(900)));
+        assert!(!tracker.fits(&relay, Duration::from_millis(899)));
+    }
+
+    #[test]
+    fn test_old_samples_are_dropped() {
+        let relay: MevBoostRelayID = "relay".to_string();
+        let mut tracker = RelayLatencyTracker::default();
+        for _ in 0..LATENCY_SAMPLES {
+            tracker.record(&relay, Duration::from_millis(800));
+        }
+        for _ in 0..LATENCY_SAMPLES {
+            tracker.record(&relay, Duration::from_millis(50));
+        }
+        assert_eq!(tracker.p90(&relay), Some(Duration::from_millis(50)));
+    }
+}
@@ -1,27 +1,51 @@
+use super::{
+    builder_tag_experiment::{BuilderTagExperiment, BuilderTagMappingEntry},
+    relay_identities::{
+        RelayIdentities, RelayIdentitySnapshot, DEFAULT_IDENTITY, OPTIMISTIC_IDENTITY,
+    },
+    relay_latency::{record_relay_latency, RelayLatencyTracker, RELAY_LATENCY_TRACKER},
+    submission_dedup::{SlotSubmissionFingerprints, SubmissionFingerprint},
+    submission_slo::{
+        record_slot_submission_outcome, NoSubmissionReason, SlotRelayResults, SlotSubmissionOutcome,
+    },
+};
 use crate::{
-    building::builders::{BestBlockCell, BuilderSinkFactory},
+    building::{
+        builders::{BestBlockCell, BestBlockFilter, BuilderSinkFactory},
+        value_curve::{SlotValueCurve, VALUE_CURVES},
+    },
     flashbots::BlocksProcessorClient,
-    live_builder::{bidding::SlotBidder, payload_events::MevBoostSlotData},
+    live_builder::{
+        bidding::SlotBidder, kill_switch::KILL_SWITCH, payload_events::MevBoostSlotData,
+    },
     mev_boost::{
         sign_block_for_relay, BLSBlockSigner, RelayError, SubmitBlockErr, SubmitBlockRequest,
     },
     primitives::mev_boost::{MevBoostRelay, MevBoostRelayID},
     telemetry::{
         add_relay_submit_time, add_subsidy_value, inc_blocks_api_errors, inc_conn_relay_errors,
-        inc_failed_block_simulations, inc_initiated_submissions, inc_other_relay_errors,
-        inc_relay_accepted_submissions, inc_subsidized_blocks, inc_too_many_req_relay_errors,
-        measure_block_e2e_latency,
+        inc_failed_block_simulations, inc_initiated_submissions,
+        inc_kill_switch_suppressed_submissions, inc_other_relay_errors,
+        inc_relay_accepted_submissions, inc_relay_accepted_submissions_by_identity,
+        inc_relay_skipped_due_to_latency, inc_subsidized_blocks, inc_too_many_req_relay_errors,
+        measure_block_e2e_latency, observe_best_block_supersessions,
+    },
+    utils::{
+        error_storage::store_error_event,
+        rate_limited_log::{error_discriminant, rate_limited},
     },
-    utils::error_storage::store_error_event,
     validation_api_client::{ValdationError, ValidationAPIClient},
 };
 use ahash::HashMap;
 use alloy_primitives::{utils::format_ether, U256};
 use reth::primitives::{ChainSpec, SealedBlock};
-use std::{sync::Arc, time::Duration};
-use tokio::time::{sleep, Instant};
+use std::{future::Future, sync::Arc, time::Duration};
+use tokio::{
+    task::JoinHandle,
+    time::{sleep, Instant},
+};
 use tokio_util::sync::CancellationToken;
-use tracing::{debug, error, info_span, trace, warn, Instrument};
+use tracing::{debug, error, info_span, trace, warn, Instrument, Span};
 
 const SIM_ERROR_CATEGORY: &str = "submit_block_simulation";
 const VALIDATION_ERROR_CATEGORY: &str = "validate_block_simulation";
@@ -42,11 +66,34 @@ pub struct SubmissionConfig {
     pub blocks_processor: Option<BlocksProcessorClient>,
     /// Delta relative to slot_time at which we start to submit blocks. Usually negative since we need to start submitting BEFORE the slot time.
     pub slot_delta_to_start_submits: time::Duration,
+    /// Last part of the slot (before slot time) where we only submit right away to the relays whose p90 latency fits in the time left.
+    /// None -> always submit to all relays.
+    pub fast_relay_window: Option<Duration>,
+    /// Blocks using more than this percentage of the gas limit are not submitted (some relays reject them).
+    pub max_gas_used_percent: Option<u64>,
+    /// Relays with an identity get their normal submissions signed by it instead of signer.
+    pub relay_identities: RelayIdentities,
+    /// When set the identity of each slot comes from the experiment schedule and optimistic submissions are disabled
+    /// (the optimistic key would link the tags).
+    pub builder_tag_experiment: Option<BuilderTagExperiment>,
+    /// Blocks from the builders that don't improve enough on the best one of the slot are not submitted.
+    pub best_block_filter: BestBlockFilter,
+    /// Report the blocks the builders sent to the slot sink at the end of each slot (see [`BestBlockCell::report_new_blocks`]).
+    pub slot_block_metrics: bool,
 }
 
 /// run_submit_to_relays_job waits at least MIN_TIME_BETWEEN_BLOCK_CHECK between new block polls to avoid 100% CPU
 const MIN_TIME_BETWEEN_BLOCK_CHECK: Duration = Duration::from_millis(5);
 
+/// Result of a single relay call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelaySubmitOutcome {
+    Accepted,
+    /// Not sent or failed for a reason that might go away (rate limit, connection...), the same submission can be sent again.
+    TransientFailure,
+    Rejected,
+}
+
 /// Values from [`BuiltBlockTrace`]
 struct BuiltBlockInfo {
     pub bid_value: U256,
@@ -57,12 +104,15 @@ struct BuiltBlockInfo {
 ///
 /// How submission works:
 /// 0. We divide relays into optimistic and non-optimistic (defined in config file)
+///    While the kill switch is engaged (and for the rest of the slot) blocks are dropped here (see [`KILL_SWITCH`]).
 /// 1. If we are in dry run mode we validate the payload and skip submission to the relays
-/// 2. We schedule submissions with non-optimistic key for all non-optimistic relays.
+/// 2. We schedule submissions with non-optimistic key (or the relay signing identity) for all non-optimistic relays.
+///    Signing identities are taken at the beginning of the slot (see [`RelayIdentities::snapshot`]).
+///    With the builder tag experiment all the relays use the identity scheduled for the slot (see [`BuilderTagExperiment`]).
 /// 3.1 If "optimistic_enabled" is false or bid_value >= "optimistic_max_bid_value" we schedule submissions with non-optimistic key
 /// 3.2 If "optimistic_prevalidate_optimistic_blocks" is false we schedule submissions with optimistic key
 /// 3.3 If "optimistic_prevalidate_optimistic_blocks" is true we validate block using validation API and then schedule submissions with optimistic key
-/// returns the best bid made
+/// returns the best bid made and the reason to report if no submission was attempted (see [`NoSubmissionReason`])
 #[allow(clippy::too_many_arguments)]
 async fn run_submit_to_relays_job(
     best_bid: BestBlockCell,
@@ -71,8 +121,10 @@ async fn run_submit_to_relays_job(
     config: SubmissionConfig,
     cancel: CancellationToken,
     slot_bidder: Arc<dyn SlotBidder>,
-) -> Option<BuiltBlockInfo> {
+    relay_results: SlotRelayResults,
+) -> (Option<BuiltBlockInfo>, NoSubmissionReason) {
     let mut res = None;
+    let mut no_submission_reason = NoSubmissionReason::NoBlock;
     // first, sleep to slot time - slot_delta_to_start_submits
     {
         let submit_start_time = slot_data.timestamp() + config.slot_delta_to_start_submits;
@@ -82,6 +134,19 @@ async fn run_submit_to_relays_job(
         }
     }
 
+    let relay_identities = match &config.builder_tag_experiment {
+        Some(experiment) => {
+            match experiment.relay_identities_for_slot(&config.relay_identities, slot_data.slot()) {
+                Ok(relay_identities) => relay_identities,
+                Err(err) => {
+                    error!(err = ?err, "Failed to get builder tag identity for slot");
+                    return (None, NoSubmissionReason::SigningFailed);
+                }
+            }
+        }
+        None => config.relay_identities.snapshot(),
+    };
+
     let (normal_relays, optimistic_relays) = {
         let mut normal_relays = Vec::new();
         let mut optimistic_relays = Vec::new();
@@ -95,11 +160,12 @@ async fn run_submit_to_relays_job(
         (normal_relays, optimistic_relays)
     };
 
+    let fingerprints = SlotSubmissionFingerprints::default();
     let mut last_bid_value = U256::from(0);
     let mut last_submit_time = Instant::now();
     'submit: loop {
         if cancel.is_cancelled() {
-            break 'submit res;
+            break 'submit (res, no_submission_reason);
         }
 
         let time_since_submit = last_submit_time.elapsed();
@@ -110,6 +176,29 @@ async fn run_submit_to_relays_job(
 
         let block = if let Some(new_block) = best_bid.take_best_block() {
             if new_block.trace.bid_value > last_bid_value {
+                if new_block.root_hash_skipped {
+                    error!(
+                        builder_name = new_block.builder_name,
+                        "Block without state root rejected before submission"
+                    );
+                    no_submission_reason =
+                        no_submission_reason.max(NoSubmissionReason::BlocksRejected);
+                    continue 'submit;
+                }
+                if let Err(err) = check_gas_used_constraint(
+                    new_block.sealed_block.gas_used,
+                    new_block.sealed_block.gas_limit,
+                    config.max_gas_used_percent,
+                ) {
+                    warn!(
+                        err = ?err,
+                        builder_name = new_block.builder_name,
+                        "Block rejected before submission"
+                    );
+                    no_submission_reason =
+                        no_submission_reason.max(NoSubmissionReason::BlocksRejected);
+                    continue 'submit;
+                }
                 last_bid_value = new_block.trace.bid_value;
                 new_block
             } else {
@@ -119,12 +208,19 @@ async fn run_submit_to_relays_job(
             continue 'submit;
         };
 
+        if KILL_SWITCH.suppress_submission(slot_data.slot()) {
+            inc_kill_switch_suppressed_submissions();
+            no_submission_reason = no_submission_reason.max(NoSubmissionReason::KillSwitch);
+            continue 'submit;
+        }
+
         res = Some(BuiltBlockInfo {
             bid_value: block.trace.bid_value,
             true_bid_value: block.trace.true_bid_value,
         });
 
         let builder_name = block.builder_name.clone();
+        let private_orders = block.trace.has_private_orders();
 
         let bundles = block
             .trace
@@ -132,8 +228,9 @@ async fn run_submit_to_relays_job(
             .iter()
             .filter(|o| !o.order.is_tx())
             .count();
-        let submission_optimistic =
-            config.optimistic_enabled && block.trace.bid_value < config.optimistic_max_bid_value;
+        let submission_optimistic = config.optimistic_enabled
+            && config.builder_tag_experiment.is_none()
+            && block.trace.bid_value < config.optimistic_max_bid_value;
         let best_bid_value = slot_bidder.best_bid_value().unwrap_or_default();
         let submission_span = info_span!(
             "bid",
@@ -155,7 +252,7 @@ async fn run_submit_to_relays_job(
         );
         inc_initiated_submissions(submission_optimistic);
 
-        let (normal_signed_submission, optimistic_signed_submission) = {
+        let (normal_signed_submission, optimistic_signed_submission, identity_signed_submissions) = {
             let normal_signed_submission = match sign_block_for_relay(
                 &config.signer,
                 &block.sealed_block,
@@ -168,6 +265,8 @@ async fn run_submit_to_relays_job(
                 Ok(res) => res,
                 Err(err) => {
                     error!(parent: &submission_span, err = ?err, "Error signing block for relay");
+                    no_submission_reason =
+                        no_submission_reason.max(NoSubmissionReason::SigningFailed);
                     continue 'submit;
                 }
             };
@@ -183,10 +282,42 @@ async fn run_submit_to_relays_job(
                 Ok(res) => res,
                 Err(err) => {
                     error!(parent: &submission_span, err = ?err, "Error signing block for relay");
+                    no_submission_reason =
+                        no_submission_reason.max(NoSubmissionReason::SigningFailed);
                     continue 'submit;
                 }
             };
-            (normal_signed_submission, optimistic_signed_submission)
+            let mut identity_signed_submissions = HashMap::default();
+            for (identity, signer) in relay_identities.signers() {
+                match sign_block_for_relay(
+                    signer,
+                    &block.sealed_block,
+                    &block.txs_blobs_sidecars,
+                    &config.chain_spec,
+                    &slot_data.payload_attributes_event.data,
+                    slot_data.slot_data.pubkey,
+                    block.trace.bid_value,
+                ) {
+                    Ok(res) => {
+                        identity_signed_submissions.insert(identity.to_string(), res);
+                    }
+                    Err(err) => {
+                        error!(parent: &submission_span, err = ?err, identity, "Error signing block for relay");
+                        no_submission_reason =
+                            no_submission_reason.max(NoSubmissionReason::SigningFailed);
+                        continue 'submit;
+                    }
+                }
+            }
+            identity_signed_submissions.insert(
+                DEFAULT_IDENTITY.to_string(),
+                normal_signed_submission.clone(),
+            );
+            (
+                normal_signed_submission,
+                optimistic_signed_submission,
+                identity_signed_submissions,
+            )
         };
 
         if config.dry_run {
@@ -208,10 +339,11 @@ async fn run_submit_to_relays_job(
                 Err(ValdationError::ValidationFailed(err)) => {
                     error!(parent: &submission_span, err = ?err, "Dry run validation failed");
                     inc_failed_block_simulations();
-                    store_error_event(
+                    store_submission_error_event(
                         VALIDATION_ERROR_CATEGORY,
                         &err.to_string(),
                         &normal_signed_submission,
+                        private_orders,
                     );
                 }
             }
@@ -219,19 +351,21 @@ async fn run_submit_to_relays_job(
         }
 
         measure_block_e2e_latency(&block.trace.included_orders);
+        // if any relay gets the bid the slot counts as submitted and this is ignored
+        no_submission_reason = no_submission_reason.max(NoSubmissionReason::RelaysSkipped);
 
-        for relay in &normal_relays {
-            let span = info_span!(parent: &submission_span, "relay_submit", relay = &relay.id, optimistic = false);
-            let relay = relay.clone();
-            let cancel = cancel.clone();
-            let submission = normal_signed_submission.clone();
-            tokio::spawn(
-                async move {
-                    submit_bid_to_the_relay(&relay, cancel.clone(), submission, false).await;
-                }
-                .instrument(span),
-            );
-        }
+        submit_normal_bid_to_relays(
+            &normal_relays,
+            &relay_identities,
+            &identity_signed_submissions,
+            private_orders,
+            slot_data.timestamp(),
+            &config,
+            &cancel,
+            &relay_results,
+            &fingerprints,
+            &submission_span,
+        );
 
         if submission_optimistic {
             let can_submit = if config.optimistic_prevalidate_optimistic_blocks {
@@ -259,10 +393,11 @@ async fn run_submit_to_relays_job(
                     Err(ValdationError::ValidationFailed(err)) => {
                         error!(parent: &submission_span, err = ?err, "Optimistic Payload Validation failed");
                         inc_failed_block_simulations();
-                        store_error_event(
+                        store_submission_error_event(
                             VALIDATION_ERROR_CATEGORY,
                             &err.to_string(),
                             &optimistic_signed_submission,
+                            private_orders,
                         );
                         false
                     }
@@ -272,33 +407,34 @@ async fn run_submit_to_relays_job(
             };
 
             if can_submit {
-                for relay in &optimistic_relays {
-                    let span = info_span!(parent: &submission_span, "relay_submit", relay = &relay.id, optimistic = true);
-                    let relay = relay.clone();
-                    let cancel = cancel.clone();
-                    let submission = optimistic_signed_submission.clone();
-                    tokio::spawn(
-                        async move {
-                            submit_bid_to_the_relay(&relay, cancel.clone(), submission, true).await;
-                        }
-                        .instrument(span),
-                    );
-                }
+                submit_bid_to_relays(
+                    &optimistic_relays,
+                    &optimistic_signed_submission,
+                    true,
+                    OPTIMISTIC_IDENTITY,
+                    private_orders,
+                    slot_data.timestamp(),
+                    &config,
+                    &cancel,
+                    &relay_results,
+                    &fingerprints,
+                    &submission_span,
+                );
             }
         } else {
             // non-optimistic submission to optimistic relays
-            for relay in &optimistic_relays {
-                let span = info_span!(parent: &submission_span, "relay_submit", relay = &relay.id, optimistic = false);
-                let relay = relay.clone();
-                let cancel = cancel.clone();
-                let submission = normal_signed_submission.clone();
-                tokio::spawn(
-                    async move {
-                        submit_bid_to_the_relay(&relay, cancel.clone(), submission, false).await;
-                    }
-                    .instrument(span),
-                );
-            }
+            submit_normal_bid_to_relays(
+                &optimistic_relays,
+                &relay_identities,
+                &identity_signed_submissions,
+                private_orders,
+                slot_data.timestamp(),
+                &config,
+                &cancel,
+                &relay_results,
+                &fingerprints,
+                &submission_span,
+            );
         }
 
         if let Some(blocks_processor) = config.blocks_processor.clone() {
@@ -313,7 +449,11 @@ async fn run_submit_to_relays_job(
                 };
                 if let Err(err) = block_processor_result {
                     inc_blocks_api_errors();
-                    warn!(parent: &submission_span, "Failed to submit block to the blocks api: {}", err);
+                    if let Some(repeated_in_last_minute) =
+                        rate_limited("relay_submit.blocks_api", error_discriminant(&err))
+                    {
+                        warn!(parent: &submission_span, repeated_in_last_minute, "Failed to submit block to the blocks api: {}", err);
+                    }
                 }
             });
         }
@@ -328,15 +468,34 @@ pub async fn run_submit_to_relays_job_and_metrics(
     cancel: CancellationToken,
     slot_bidder: Arc<dyn SlotBidder>,
 ) {
-    let best_bid = run_submit_to_relays_job(
-        best_bid.clone(),
+    let slot_timestamp = slot_data.timestamp();
+    let dry_run = config.dry_run;
+    let slot_block_metrics = config.slot_block_metrics;
+    let relay_results = SlotRelayResults::default();
+    let best_bid_cell = best_bid.clone();
+    let (best_bid, no_submission_reason) = run_submit_to_relays_job(
+        best_bid,
         slot_data,
         relays,
         config,
         cancel,
         slot_bidder,
+        relay_results.clone(),
     )
     .await;
+    let slot_outcome =
+        SlotSubmissionOutcome::new(dry_run, no_submission_reason, relay_results.results());
+    if let SlotSubmissionOutcome::NotSubmitted(reason) = &slot_outcome {
+        debug!(reason = reason.as_str(), "No submission for slot");
+    }
+    record_slot_submission_outcome(slot_timestamp, slot_outcome);
+    if let Some(value_curve) = best_bid_cell.value_curve() {
+        value_curve.finish(&VALUE_CURVES);
+    }
+    observe_best_block_supersessions(best_bid_cell.supersessions());
+    if slot_block_metrics {
+        best_bid_cell.report_new_blocks();
+    }
     if let Some(best_bid) = best_bid {
         if best_bid.bid_value > best_bid.true_bid_value {
             inc_subsidized_blocks(false);
@@ -345,6 +504,251 @@ pub async fn run_submit_to_relays_job_and_metrics(
     }
 }
 
+/// See [`SubmissionConfig::max_gas_used_percent`].
+fn check_gas_used_constraint(
+    gas_used: u64,
+    gas_limit: u64,
+    max_gas_used_percent: Option<u64>,
+) -> eyre::Result<()> {
+    if let Some(max_gas_used_percent) = max_gas_used_percent {
+        if gas_used as u128 * 100 > gas_limit as u128 * max_gas_used_percent as u128 {
+            eyre::bail!(
+                "Gas used {} is above {}% of the gas limit {}",
+                gas_used,
+                max_gas_used_percent,
+                gas_limit
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Relays for a single bid split by their latency, see [`plan_relay_submissions`].
+#[derive(Debug, Default)]
+struct RelaySubmissionPlan {
+    /// Submitted right away.
+    fast: Vec<MevBoostRelay>,
+    /// Relays with their p50 latency.
+    /// Submitted after all the fast relays answered if there is still time for their usual latency before the cutoff.
+    slow: Vec<(MevBoostRelay, Duration)>,
+}
+
+/// Inside the last fast_relay_window before the cutoff only relays whose p90 latency fits in the time left are submitted right away,
+/// sending bids to slower relays at the same time would only compete for bandwidth with the ones that can still make it.
+/// Outside the window (or with no window) all relays are fast.
+fn plan_relay_submissions(
+    relays: &[MevBoostRelay],
+    time_to_cutoff: time::Duration,
+    fast_relay_window: Option<Duration>,
+    latency: &RelayLatencyTracker,
+) -> RelaySubmissionPlan {
+    let time_left: Duration = time_to_cutoff.try_into().unwrap_or(Duration::ZERO);
+    if !fast_relay_window.map_or(false, |window| time_left < window) {
+        return RelaySubmissionPlan {
+            fast: relays.to_vec(),
+            slow: Vec::new(),
+        };
+    }
+    let mut plan = RelaySubmissionPlan::default();
+    for relay in relays {
+        if latency.fits(&relay.id, time_left) {
+            plan.fast.push(relay.clone());
+        } else {
+            let p50 = latency.percentile(&relay.id, 0.5).unwrap_or_default();
+            plan.slow.push((relay.clone(), p50));
+        }
+    }
+    plan
+}
+
+/// Executes the plan, submit does the actual relay call and returns true if the relay accepted the bid.
+/// Slow relays that don't make it before the cutoff are counted as skipped due to latency.
+/// The returned handle finishes when all the submissions are done.
+fn spawn_relay_submissions<Submit, Fut>(
+    plan: RelaySubmissionPlan,
+    cutoff: time::OffsetDateTime,
+    cancel: CancellationToken,
+    relay_results: SlotRelayResults,
+    submit: Submit,
+) -> JoinHandle<()>
+where
+    Submit: Fn(MevBoostRelay) -> Fut + Send + 'static,
+    Fut: Future<Output = bool> + Send + 'static,
+{
+    let spawn_submission = move |relay: MevBoostRelay, relay_results: &SlotRelayResults| {
+        relay_results.add_attempt(&relay.id);
+        let relay_results = relay_results.clone();
+        let relay_id = relay.id.clone();
+        let submission = submit(relay);
+        tokio::spawn(async move {
+            let accepted = submission.await;
+            relay_results.add_result(&relay_id, accepted);
+        })
+    };
+    let fast_submissions: Vec<_> = plan
+        .fast
+        .into_iter()
+        .map(|relay| spawn_submission(relay, &relay_results))
+        .collect();
+
+    tokio::spawn(async move {
+        for submission in fast_submissions {
+            let _ = submission.await;
+        }
+        let mut slow_submissions = Vec::new();
+        for (relay, p50) in plan.slow {
+            let time_left: Duration = (cutoff - time::OffsetDateTime::now_utc())
+                .try_into()
+                .unwrap_or(Duration::ZERO);
+            if cancel.is_cancelled() || p50 > time_left {
+                trace!(
+                    relay = relay.id,
+                    "Relay submission is skipped due to latency"
+                );
+                inc_relay_skipped_due_to_latency(&relay.id);
+                continue;
+            }
+            slow_submissions.push(spawn_submission(relay, &relay_results));
+        }
+        for submission in slow_submissions {
+            let _ = submission.await;
+        }
+    })
+}
+
+/// Non optimistic submission, each relay gets the submission signed by its identity.
+/// identity_signed_submissions must contain all the identities in relay_identities (and DEFAULT_IDENTITY).
+#[allow(clippy::too_many_arguments)]
+fn submit_normal_bid_to_relays(
+    relays: &[MevBoostRelay],
+    relay_identities: &RelayIdentitySnapshot,
+    identity_signed_submissions: &HashMap<String, SubmitBlockRequest>,
+    private_orders: bool,
+    slot_timestamp: time::OffsetDateTime,
+    config: &SubmissionConfig,
+    cancel: &CancellationToken,
+    relay_results: &SlotRelayResults,
+    fingerprints: &SlotSubmissionFingerprints,
+    submission_span: &Span,
+) {
+    for (identity, relays) in relay_identities.group_relays(relays) {
+        submit_bid_to_relays(
+            &relays,
+            &identity_signed_submissions[&identity],
+            false,
+            &identity,
+            private_orders,
+            slot_timestamp,
+            config,
+            cancel,
+            relay_results,
+            fingerprints,
+            submission_span,
+        );
+    }
+}
+
+/// Submits the bid to the relays considering their latency near the slot time (our cutoff), see [`plan_relay_submissions`].
+/// Repeated submissions to a relay are suppressed (see [`crate::live_builder::building::submission_dedup`]).
+/// identity is the signing identity of signed_submit_request (only for metrics/logs).
+/// private_orders: the block has private orders (see [`store_submission_error_event`]).
+/// The returned handle finishes when all the submissions are done.
+#[allow(clippy::too_many_arguments)]
+fn submit_bid_to_relays(
+    relays: &[MevBoostRelay],
+    signed_submit_request: &SubmitBlockRequest,
+    optimistic: bool,
+    identity: &str,
+    private_orders: bool,
+    slot_timestamp: time::OffsetDateTime,
+    config: &SubmissionConfig,
+    cancel: &CancellationToken,
+    relay_results: &SlotRelayResults,
+    fingerprints: &SlotSubmissionFingerprints,
+    submission_span: &Span,
+) -> JoinHandle<()> {
+    let plan = plan_relay_submissions(
+        relays,
+        slot_timestamp - time::OffsetDateTime::now_utc(),
+        config.fast_relay_window,
+        &RELAY_LATENCY_TRACKER.lock().unwrap(),
+    );
+    let submit = {
+        let signed_submit_request = signed_submit_request.clone();
+        let cancel = cancel.clone();
+        let submission_span = submission_span.clone();
+        let identity = identity.to_string();
+        let builder_tag_experiment = config.builder_tag_experiment.clone();
+        let fingerprints = fingerprints.clone();
+        move |relay: MevBoostRelay| {
+            let span = info_span!(parent: &submission_span, "relay_submit", relay = &relay.id, optimistic, identity = identity.as_str());
+            let cancel = cancel.clone();
+            let submission = signed_submit_request.clone();
+            let identity = identity.clone();
+            let builder_tag_experiment = builder_tag_experiment.clone();
+            let fingerprints = fingerprints.clone();
+            async move {
+                let mapping_entry = builder_tag_experiment
+                    .as_ref()
+                    .map(|_| BuilderTagMappingEntry::new(&submission, &relay.id, &identity));
+                let bid_trace = submission.bid_trace();
+                let fingerprint = SubmissionFingerprint {
+                    relay: relay.id.clone(),
+                    block_hash: bid_trace.block_hash,
+                    bid_value: bid_trace.value,
+                };
+                let outcome = fingerprints
+                    .submit_once(fingerprint, || {
+                        submit_bid_to_the_relay(
+                            &relay,
+                            cancel,
+                            submission,
+                            optimistic,
+                            &identity,
+                            private_orders,
+                        )
+                    })
+                    .await;
+                if outcome.is_none() {
+                    trace!("Repeated relay submission suppressed");
+                }
+                let accepted = outcome == Some(RelaySubmitOutcome::Accepted);
+                if let (true, Some(experiment), Some(entry)) =
+                    (accepted, &builder_tag_experiment, &mapping_entry)
+                {
+                    if let Err(err) = experiment.mapping_log.record(entry) {
+                        error!(err = ?err, "Failed to record builder tag mapping");
+                    }
+                }
+                accepted
+            }
+            .instrument(span)
+        }
+    };
+    spawn_relay_submissions(
+        plan,
+        slot_timestamp,
+        cancel.clone(),
+        relay_results.clone(),
+        submit,
+    )
+}
+
+/// The submission has the raw txs of all the orders so for blocks with private orders (see [`crate::primitives::Metadata::private`])
+/// we only store the bid trace.
+fn store_submission_error_event(
+    category: &str,
+    error: &str,
+    submission: &SubmitBlockRequest,
+    private_orders: bool,
+) {
+    if private_orders {
+        store_error_event(category, error, submission.bid_trace());
+    } else {
+        store_error_event(category, error, submission);
+    }
+}
+
 async fn validate_block(
     slot_data: &MevBoostSlotData,
     signed_submit_request: &SubmitBlockRequest,
@@ -372,72 +776,109 @@ async fn submit_bid_to_the_relay(
     cancel: CancellationToken,
     signed_submit_request: SubmitBlockRequest,
     optimistic: bool,
-) {
+    identity: &str,
+    private_orders: bool,
+) -> RelaySubmitOutcome {
     let submit_start = Instant::now();
 
     if let Some(limiter) = &relay.submission_rate_limiter {
         if limiter.check().is_err() {
             trace!("Relay submission is skipped due to rate limit");
-            return;
+            return RelaySubmitOutcome::TransientFailure;
         }
     }
 
     let relay_result = tokio::select! {
         _ = cancel.cancelled() => {
-            return;
+            return RelaySubmitOutcome::TransientFailure;
         },
         res = relay.submit_block(&signed_submit_request) => res
     };
     let submit_time = submit_start.elapsed();
+    record_relay_latency(&relay.id, submit_time);
     match relay_result {
         Ok(()) => {
             trace!("Block submitted to the relay successfully");
             add_relay_submit_time(&relay.id, submit_time);
             inc_relay_accepted_submissions(&relay.id, optimistic);
+            inc_relay_accepted_submissions_by_identity(&relay.id, identity);
+            RelaySubmitOutcome::Accepted
         }
         Err(SubmitBlockErr::PayloadDelivered | SubmitBlockErr::PastSlot) => {
             trace!("Block already delivered by the relay, cancelling");
             cancel.cancel();
+            RelaySubmitOutcome::Rejected
+        }
+        Err(SubmitBlockErr::BidBelowFloor) => {
+            trace!("Block not accepted by the relay, bid below floor");
+            RelaySubmitOutcome::Rejected
         }
-        Err(SubmitBlockErr::BidBelowFloor | SubmitBlockErr::PayloadAttributesNotKnown) => {
-            trace!(err = ?relay_result.unwrap_err(), "Block not accepted by the relay");
+        Err(SubmitBlockErr::PayloadAttributesNotKnown) => {
+            // the relay might get them later
+            trace!("Block not accepted by the relay, payload attributes not known");
+            RelaySubmitOutcome::TransientFailure
         }
         Err(SubmitBlockErr::SimError(err)) => {
             inc_failed_block_simulations();
             error!(err = ?err, "Error block simulation fail, cancelling");
-            store_error_event(SIM_ERROR_CATEGORY, &err.to_string(), &signed_submit_request);
+            store_submission_error_event(
+                SIM_ERROR_CATEGORY,
+                &err.to_string(),
+                &signed_submit_request,
+                private_orders,
+            );
             cancel.cancel();
+            RelaySubmitOutcome::Rejected
         }
         Err(SubmitBlockErr::RelayError(RelayError::TooManyRequests)) => {
             trace!("Too many requests error submitting block to the relay");
             inc_too_many_req_relay_errors(&relay.id);
+            RelaySubmitOutcome::TransientFailure
         }
         Err(SubmitBlockErr::RelayError(RelayError::ConnectionError))
         | Err(SubmitBlockErr::RelayError(RelayError::RequestError(_))) => {
             trace!(err = ?relay_result.unwrap_err(), "Connection error submitting block to the relay");
             inc_conn_relay_errors(&relay.id);
+            RelaySubmitOutcome::TransientFailure
         }
         Err(SubmitBlockErr::BlockKnown) => {
             trace!("Block already known");
+            RelaySubmitOutcome::Rejected
         }
         Err(SubmitBlockErr::RelayError(err)) => {
-            warn!(err = ?err, "Error submitting block to the relay");
+            // fires on every submission while the relay is unhealthy
+            if let Some(repeated_in_last_minute) = rate_limited(
+                "relay_submit.relay_error",
+                format!("{}:{}", relay.id, error_discriminant(&err)),
+            ) {
+                warn!(err = ?err, repeated_in_last_minute, "Error submitting block to the relay");
+            }
             inc_other_relay_errors(&relay.id);
+            RelaySubmitOutcome::TransientFailure
         }
         Err(SubmitBlockErr::RPCConversionError(err)) => {
-            error!(
-                err = ?err,
-                "RPC conversion error (illegal submission?) submitting block to the relay",
-            );
+            if let Some(repeated_in_last_minute) = rate_limited(
+                "relay_submit.rpc_conversion_error",
+                format!("{}:{}", relay.id, error_discriminant(&err)),
+            ) {
+                error!(
+                    err = ?err,
+                    repeated_in_last_minute,
+                    "RPC conversion error (illegal submission?) submitting block to the relay",
+                );
+            }
+            RelaySubmitOutcome::Rejected
         }
         Err(SubmitBlockErr::RPCSerializationError(err)) => {
             error!(
                 err = ?err,
                 "SubmitBlock serialization error submitting block to the relay",
             );
+            RelaySubmitOutcome::Rejected
         }
         Err(SubmitBlockErr::InvalidHeader) => {
             error!("Invalid authorization header submitting block to the relay");
+            RelaySubmitOutcome::Rejected
         }
     }
 }
@@ -470,7 +911,9 @@ impl BuilderSinkFactory for RelaySubmitSinkFactory {
         slot_bidder: Arc<dyn SlotBidder>,
         cancel: CancellationToken,
     ) -> BestBlockCell {
-        let best_bid = BestBlockCell::default();
+        let best_bid = BestBlockCell::default()
+            .with_filter(self.submission_config.best_block_filter)
+            .with_value_curve(SlotValueCurve::new(slot_data.slot(), slot_data.block()));
 
         let relays = slot_data
             .relays
@@ -493,3 +936,528 @@ impl BuilderSinkFactory for RelaySubmitSinkFactory {
         best_bid
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        live_builder::{
+            building::{
+                builder_tag_experiment::{test::schedule, BuilderTagMappingLog},
+                relay_identities::relay_identity_routes,
+            },
+            payload_events::relay_epoch_cache::SlotData,
+        },
+        mev_boost::rpc::TestDataGenerator,
+        telemetry::RELAY_SKIPPED_DUE_TO_LATENCY,
+        test_utils::mock_block,
+    };
+    use alloy_rpc_types_beacon::relay::BidTrace;
+    use ethereum_consensus::crypto::SecretKey;
+    use primitive_types::H384;
+    use reth::primitives::{Block, Header, MAINNET};
+    use reth::rpc::types::beacon::events::PayloadAttributesEvent;
+    use std::{
+        collections::VecDeque,
+        fs::File,
+        io::{BufRead, BufReader},
+        sync::Mutex,
+    };
+    use warp::{http::StatusCode, hyper::body::Bytes, Filter};
+
+    /// Local relay answering the submissions with the scripted statuses (OK after the script ends).
+    struct MockRelay {
+        url: String,
+        /// Bodies of the submissions it got.
+        requests: Arc<Mutex<Vec<Bytes>>>,
+    }
+
+    impl MockRelay {
+        fn spawn(statuses: Vec<StatusCode>) -> Self {
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let statuses = Arc::new(Mutex::new(VecDeque::from(statuses)));
+            let route = warp::post()
+                .and(warp::path!("relay" / "v1" / "builder" / "blocks"))
+                .and(warp::body::bytes())
+                .map({
+                    let requests = requests.clone();
+                    move |body: Bytes| {
+                        requests.lock().unwrap().push(body);
+                        let status = statuses.lock().unwrap().pop_front();
+                        warp::reply::with_status(warp::reply(), status.unwrap_or(StatusCode::OK))
+                    }
+                });
+            let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+            tokio::spawn(server);
+            Self {
+                url: format!("http://{}", addr),
+                requests,
+            }
+        }
+
+        fn relay(&self, id: &str) -> MevBoostRelay {
+            MevBoostRelay::try_from_name_or_url(
+                id, &self.url, 0, false, false, false, None, None, None, None,
+            )
+            .unwrap()
+        }
+
+        fn requests(&self) -> Vec<Bytes> {
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    fn bls_signer(key_byte: u8) -> BLSBlockSigner {
+        let sec = SecretKey::try_from([key_byte; 32].as_slice()).unwrap();
+        BLSBlockSigner::new(sec, Default::default()).unwrap()
+    }
+
+    fn submission_config() -> SubmissionConfig {
+        SubmissionConfig {
+            chain_spec: reth::primitives::MAINNET.clone(),
+            signer: bls_signer(1),
+            dry_run: false,
+            validation_api: ValidationAPIClient::new(&[]).unwrap(),
+            optimistic_enabled: false,
+            optimistic_signer: bls_signer(2),
+            optimistic_max_bid_value: U256::ZERO,
+            optimistic_prevalidate_optimistic_blocks: false,
+            blocks_processor: None,
+            slot_delta_to_start_submits: time::Duration::ZERO,
+            fast_relay_window: None,
+            max_gas_used_percent: None,
+            relay_identities: Default::default(),
+            builder_tag_experiment: None,
+            best_block_filter: Default::default(),
+            slot_block_metrics: false,
+        }
+    }
+
+    fn submit_block_request() -> SubmitBlockRequest {
+        SubmitBlockRequest::Deneb(TestDataGenerator::default().create_deneb_submit_block_request())
+    }
+
+    fn relay(id: &str) -> MevBoostRelay {
+        MevBoostRelay::try_from_name_or_url(
+            id,
+            "http://127.0.0.1:1",
+            0,
+            false,
+            false,
+            false,
+            None,
+            None,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    fn latency_tracker(relays: &[(&str, u64)]) -> RelayLatencyTracker {
+        let mut tracker = RelayLatencyTracker::default();
+        for (id, latency_ms) in relays {
+            for _ in 0..10 {
+                tracker.record(&id.to_string(), Duration::from_millis(*latency_ms));
+            }
+        }
+        tracker
+    }
+
+    fn ids(relays: &[MevBoostRelay]) -> Vec<&str> {
+        relays.iter().map(|relay| relay.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_gas_used_constraint() {
+        let (gas_used, gas_limit) = (29_000_000, 30_000_000);
+        assert!(check_gas_used_constraint(gas_used, gas_limit, None).is_ok());
+        assert!(check_gas_used_constraint(gas_used, gas_limit, Some(99)).is_ok());
+        assert!(check_gas_used_constraint(gas_used, gas_limit, Some(95)).is_err());
+        assert!(check_gas_used_constraint(gas_limit, gas_limit, Some(100)).is_ok());
+    }
+
+    #[test]
+    fn test_plan_outside_window_uses_all_relays() {
+        let relays = [relay("fast_outside"), relay("slow_outside")];
+        let tracker = latency_tracker(&[("fast_outside", 50), ("slow_outside", 800)]);
+        let window = Some(Duration::from_millis(1000));
+
+        let plan = plan_relay_submissions(&relays, time::Duration::seconds(2), window, &tracker);
+        assert_eq!(ids(&plan.fast), vec!["fast_outside", "slow_outside"]);
+        assert!(plan.slow.is_empty());
+
+        let plan =
+            plan_relay_submissions(&relays, time::Duration::milliseconds(200), None, &tracker);
+        assert_eq!(ids(&plan.fast), vec!["fast_outside", "slow_outside"]);
+    }
+
+    #[tokio::test]
+    async fn test_only_fast_relay_attempted_near_deadline() {
+        let relays = [relay("fast"), relay("slow"), relay("unknown")];
+        let tracker = latency_tracker(&[("fast", 50), ("slow", 800)]);
+        let time_to_cutoff = time::Duration::milliseconds(200);
+        let cutoff = time::OffsetDateTime::now_utc() + time_to_cutoff;
+
+        let plan = plan_relay_submissions(
+            &relays,
+            time_to_cutoff,
+            Some(Duration::from_millis(1000)),
+            &tracker,
+        );
+        // relays without stats are not skipped
+        assert_eq!(ids(&plan.fast), vec!["fast", "unknown"]);
+
+        let skipped_before = RELAY_SKIPPED_DUE_TO_LATENCY
+            .with_label_values(&["slow"])
+            .get();
+        let attempted = Arc::new(Mutex::new(Vec::new()));
+        let relay_results = SlotRelayResults::default();
+        spawn_relay_submissions(
+            plan,
+            cutoff,
+            CancellationToken::new(),
+            relay_results.clone(),
+            {
+                let attempted = attempted.clone();
+                move |relay: MevBoostRelay| {
+                    attempted.lock().unwrap().push(relay.id.clone());
+                    async move {
+                        sleep(Duration::from_millis(50)).await;
+                        true
+                    }
+                }
+            },
+        )
+        .await
+        .unwrap();
+
+        let mut attempted = attempted.lock().unwrap().clone();
+        attempted.sort();
+        assert_eq!(attempted, vec!["fast", "unknown"]);
+        assert!(!relay_results.results().contains_key("slow"));
+        assert_eq!(
+            RELAY_SKIPPED_DUE_TO_LATENCY
+                .with_label_values(&["slow"])
+                .get(),
+            skipped_before + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_slow_relay_attempted_after_fast_if_time_allows() {
+        let relays = [relay("fast_with_time"), relay("slow_with_time")];
+        // slow p90 doesn't fit but its p50 does
+        let mut tracker = latency_tracker(&[("fast_with_time", 50), ("slow_with_time", 100)]);
+        for _ in 0..3 {
+            tracker.record(&"slow_with_time".to_string(), Duration::from_millis(5000));
+        }
+        let time_to_cutoff = time::Duration::seconds(2);
+        let cutoff = time::OffsetDateTime::now_utc() + time_to_cutoff;
+
+        let plan = plan_relay_submissions(
+            &relays,
+            time_to_cutoff,
+            Some(Duration::from_secs(3)),
+            &tracker,
+        );
+        assert_eq!(ids(&plan.fast), vec!["fast_with_time"]);
+
+        let attempted = Arc::new(Mutex::new(Vec::new()));
+        spawn_relay_submissions(
+            plan,
+            cutoff,
+            CancellationToken::new(),
+            SlotRelayResults::default(),
+            {
+                let attempted = attempted.clone();
+                move |relay: MevBoostRelay| {
+                    attempted.lock().unwrap().push(relay.id.clone());
+                    async { true }
+                }
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            *attempted.lock().unwrap(),
+            vec!["fast_with_time", "slow_with_time"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_repeated_submission_sent_once() {
+        // first attempt fails transiently
+        let mock_relay = MockRelay::spawn(vec![StatusCode::TOO_MANY_REQUESTS]);
+        let relays = [mock_relay.relay("dedup_submit_relay")];
+        let config = submission_config();
+        let request = submit_block_request();
+        let fingerprints = SlotSubmissionFingerprints::default();
+        let relay_results = SlotRelayResults::default();
+        let submit = || {
+            submit_bid_to_relays(
+                &relays,
+                &request,
+                false,
+                DEFAULT_IDENTITY,
+                false,
+                time::OffsetDateTime::now_utc() + time::Duration::seconds(12),
+                &config,
+                &CancellationToken::new(),
+                &relay_results,
+                &fingerprints,
+                &Span::none(),
+            )
+        };
+
+        submit().await.unwrap();
+        assert_eq!(mock_relay.requests().len(), 1);
+        // transient failure, the repeat goes through and is accepted
+        submit().await.unwrap();
+        assert_eq!(mock_relay.requests().len(), 2);
+        // later repeats never reach the wire
+        submit().await.unwrap();
+        submit().await.unwrap();
+        assert_eq!(mock_relay.requests().len(), 2);
+        assert_eq!(mock_relay.requests()[0], mock_relay.requests()[1]);
+    }
+
+    /// Slot already in its submission window.
+    fn slot_data(slot: u64) -> MevBoostSlotData {
+        let mut data = TestDataGenerator::default().create_payload_attribute_data();
+        data.proposal_slot = slot;
+        data.payload_attributes.timestamp = time::OffsetDateTime::now_utc().unix_timestamp() as u64;
+        MevBoostSlotData {
+            payload_attributes_event: PayloadAttributesEvent {
+                version: "capella".to_string(),
+                data,
+            },
+            suggested_gas_limit: 30_000_000,
+            relays: vec![],
+            slot_data: SlotData {
+                fee_recipient: Default::default(),
+                gas_limit: 30_000_000,
+                pubkey: Default::default(),
+            },
+        }
+    }
+
+    /// (builder_pubkey, signature is valid for it) of the n-th submission the relay got.
+    fn submission_signer(
+        mock_relay: &MockRelay,
+        n: usize,
+        expected: &BLSBlockSigner,
+    ) -> (H384, bool) {
+        let body: serde_json::Value = serde_json::from_slice(&mock_relay.requests()[n]).unwrap();
+        let bid_trace: BidTrace = serde_json::from_value(body["message"].clone()).unwrap();
+        let signature: alloy_primitives::Bytes =
+            serde_json::from_value(body["signature"].clone()).unwrap();
+        // BLS signatures are deterministic
+        let valid = expected.sign_payload(&bid_trace).unwrap() == signature.as_ref();
+        (H384::from_slice(bid_trace.builder_pubkey.as_slice()), valid)
+    }
+
+    async fn wait_for_requests(mock_relay: &MockRelay, count: usize) {
+        for _ in 0..500 {
+            if mock_relay.requests().len() >= count {
+                return;
+            }
+            sleep(Duration::from_millis(10)).await;
+        }
+        panic!("relay got {} submissions", mock_relay.requests().len());
+    }
+
+    #[tokio::test]
+    async fn test_relays_get_bids_signed_by_their_identity() {
+        let relay_a = MockRelay::spawn(Vec::new());
+        let relay_b = MockRelay::spawn(Vec::new());
+        let relays = vec![
+            relay_a.relay("identity_relay_a"),
+            relay_b.relay("identity_relay_b"),
+        ];
+        let (key1, key2) = (bls_signer(11), bls_signer(12));
+        let relay_identities = RelayIdentities::new(
+            [
+                ("key1".to_string(), key1.clone()),
+                ("key2".to_string(), key2.clone()),
+            ]
+            .into_iter()
+            .collect(),
+            [
+                ("identity_relay_a".to_string(), "key1".to_string()),
+                ("identity_relay_b".to_string(), "key2".to_string()),
+            ]
+            .into_iter()
+            .collect(),
+        )
+        .unwrap();
+        let config = SubmissionConfig {
+            relay_identities: relay_identities.clone(),
+            ..submission_config()
+        };
+        let routes = relay_identity_routes(Arc::new(Mutex::new(Some(relay_identities))));
+        let run_slot = |slot: u64, best_bid: BestBlockCell, cancel: CancellationToken| {
+            tokio::spawn(run_submit_to_relays_job(
+                best_bid,
+                slot_data(slot),
+                relays.clone(),
+                config.clone(),
+                cancel,
+                Arc::new(()),
+                SlotRelayResults::default(),
+            ))
+        };
+
+        let best_bid = BestBlockCell::default();
+        let cancel = CancellationToken::new();
+        let job = run_slot(1, best_bid.clone(), cancel.clone());
+        best_bid.compare_and_update(mock_block(1, U256::from(10), "identity_builder"));
+        wait_for_requests(&relay_a, 1).await;
+        wait_for_requests(&relay_b, 1).await;
+        assert_eq!(
+            submission_signer(&relay_a, 0, &key1),
+            (key1.pub_key(), true)
+        );
+        assert_eq!(
+            submission_signer(&relay_b, 0, &key2),
+            (key2.pub_key(), true)
+        );
+        assert_ne!(key1.pub_key(), key2.pub_key());
+
+        let response = warp::test::request()
+            .path("/debug/relay_identities/assign?relay=identity_relay_b&identity=key1")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::OK);
+        // slot in progress keeps its identities
+        best_bid.compare_and_update(mock_block(1, U256::from(20), "identity_builder"));
+        wait_for_requests(&relay_b, 2).await;
+        assert_eq!(
+            submission_signer(&relay_b, 1, &key2),
+            (key2.pub_key(), true)
+        );
+        cancel.cancel();
+        job.await.unwrap();
+
+        let best_bid = BestBlockCell::default();
+        let cancel = CancellationToken::new();
+        let job = run_slot(2, best_bid.clone(), cancel.clone());
+        best_bid.compare_and_update(mock_block(2, U256::from(10), "identity_builder"));
+        wait_for_requests(&relay_a, 3).await;
+        wait_for_requests(&relay_b, 3).await;
+        assert_eq!(
+            submission_signer(&relay_a, 2, &key1),
+            (key1.pub_key(), true)
+        );
+        assert_eq!(
+            submission_signer(&relay_b, 2, &key1),
+            (key1.pub_key(), true)
+        );
+        cancel.cancel();
+        job.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_mapping_log_matches_submissions() {
+        let signers: HashMap<String, BLSBlockSigner> = (1..=3)
+            .map(|i| (format!("key{}", i), bls_signer(i)))
+            .collect();
+        let relay_identities = RelayIdentities::new(
+            signers.clone(),
+            [("tag_accepting_relay".to_string(), "key1".to_string())]
+                .into_iter()
+                .collect(),
+        )
+        .unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let log_path = dir.path().join("builder_tags.jsonl");
+        let experiment = BuilderTagExperiment::new(
+            schedule("seed"),
+            BuilderTagMappingLog::open(&log_path).unwrap(),
+        );
+        let config = SubmissionConfig {
+            relay_identities: relay_identities.clone(),
+            builder_tag_experiment: Some(experiment.clone()),
+            ..submission_config()
+        };
+        let accepting_relay = MockRelay::spawn(Vec::new());
+        let rejecting_relay = MockRelay::spawn(vec![StatusCode::BAD_REQUEST; 100]);
+        let relays = [
+            accepting_relay.relay("tag_accepting_relay"),
+            rejecting_relay.relay("tag_rejecting_relay"),
+        ];
+
+        // same steps the slot submission job does
+        let mut data_gen = TestDataGenerator::default();
+        let mut accepted = Vec::new();
+        for slot in 100..110 {
+            let tag = experiment.schedule.tag_for_slot(slot);
+            let submission_identities = experiment
+                .relay_identities_for_slot(&relay_identities, slot)
+                .unwrap();
+            let block = Block {
+                header: Header {
+                    extra_data: tag.extra_data.clone().into(),
+                    beneficiary: tag.coinbase_signer.address,
+                    ..Default::default()
+                },
+                ..Default::default()
+            }
+            .seal_slow();
+            let mut attrs = data_gen.create_payload_attribute_data();
+            attrs.proposal_slot = slot;
+            for (identity, relays) in submission_identities.group_relays(&relays) {
+                let submission = sign_block_for_relay(
+                    &signers[&identity],
+                    &block,
+                    &[],
+                    &MAINNET,
+                    &attrs,
+                    Default::default(),
+                    U256::from(1),
+                )
+                .unwrap();
+                submit_bid_to_relays(
+                    &relays,
+                    &submission,
+                    false,
+                    &identity,
+                    false,
+                    time::OffsetDateTime::now_utc() + time::Duration::seconds(12),
+                    &config,
+                    &CancellationToken::new(),
+                    &SlotRelayResults::default(),
+                    &SlotSubmissionFingerprints::default(),
+                    &Span::none(),
+                )
+                .await
+                .unwrap();
+                // the rejecting relay gets the same submission
+                accepted.push(submission);
+            }
+        }
+        assert_eq!(rejecting_relay.requests().len(), accepted.len());
+
+        let entries: Vec<BuilderTagMappingEntry> = BufReader::new(File::open(&log_path).unwrap())
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        // only the accepting relay is logged
+        assert_eq!(entries.len(), accepted.len());
+        for (entry, submission) in entries.iter().zip(&accepted) {
+            assert_eq!(entry.relay, "tag_accepting_relay");
+            let bid_trace = submission.bid_trace();
+            assert_eq!(entry.slot, bid_trace.slot);
+            assert_eq!(entry.block_hash, bid_trace.block_hash);
+            assert_eq!(entry.builder_pubkey, bid_trace.builder_pubkey);
+            // relay assignments are ignored, the slot tag decides
+            let tag = experiment.schedule.tag_for_slot(entry.slot);
+            assert_eq!(entry.signing_identity, tag.signing_identity);
+            assert_eq!(entry.extra_data.as_ref(), tag.extra_data.as_slice());
+            assert_eq!(entry.coinbase, tag.coinbase_signer.address);
+            assert_eq!(
+                entry.builder_pubkey.as_slice(),
+                signers[&tag.signing_identity].pub_key().as_bytes()
+            );
+        }
+    }
+}
@@ -0,0 +1,236 @@
+//! Fingerprints of the submissions sent to each relay during a slot.
+//! The same block and bid can reach a relay more than once (several builders/iterations producing the same block,
+//! retries) and some relays rate limit us for repeated submissions. A repeated (relay, block hash, bid value) is
+//! suppressed unless the previous attempt failed transiently. The block hash does not depend on the timestamps of the
+//! submission so near-duplicates differing only in them are caught too.
+//! There is one store per slot (the slot is implicit) bounded to capacity fingerprints, the oldest are evicted first.
+use super::relay_submit::RelaySubmitOutcome;
+use crate::{primitives::mev_boost::MevBoostRelayID, telemetry::inc_relay_suppressed_submissions};
+use ahash::HashMap;
+use alloy_primitives::{B256, U256};
+use std::{
+    collections::VecDeque,
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+pub const MAX_FINGERPRINTS_PER_SLOT: usize = 4096;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SubmissionFingerprint {
+    pub relay: MevBoostRelayID,
+    pub block_hash: B256,
+    pub bid_value: U256,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FingerprintState {
+    InFlight,
+    Done,
+    /// A new attempt is allowed.
+    TransientFailure,
+}
+
+#[derive(Debug, Default)]
+struct Fingerprints {
+    states: HashMap<SubmissionFingerprint, FingerprintState>,
+    /// Insertion order for eviction.
+    order: VecDeque<SubmissionFingerprint>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SlotSubmissionFingerprints {
+    inner: Arc<Mutex<Fingerprints>>,
+    capacity: usize,
+}
+
+impl Default for SlotSubmissionFingerprints {
+    fn default() -> Self {
+        Self::new(MAX_FINGERPRINTS_PER_SLOT)
+    }
+}
+
+impl SlotSubmissionFingerprints {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Default::default(),
+            capacity,
+        }
+    }
+
+    /// true if the submission must be sent, it stays in flight until [`Self::finish`].
+    /// false if it's a repeat (counted as suppressed).
+    pub fn begin(&self, fingerprint: &SubmissionFingerprint) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match inner.states.get_mut(fingerprint) {
+            Some(state @ FingerprintState::TransientFailure) => {
+                *state = FingerprintState::InFlight;
+                return true;
+            }
+            Some(_) => {
+                inc_relay_suppressed_submissions(&fingerprint.relay);
+                return false;
+            }
+            None => {}
+        }
+        if inner.order.len() >= self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.states.remove(&oldest);
+            }
+        }
+        inner
+            .states
+            .insert(fingerprint.clone(), FingerprintState::InFlight);
+        inner.order.push_back(fingerprint.clone());
+        true
+    }
+
+    pub fn finish(&self, fingerprint: &SubmissionFingerprint, outcome: RelaySubmitOutcome) {
+        let state = match outcome {
+            RelaySubmitOutcome::TransientFailure => FingerprintState::TransientFailure,
+            RelaySubmitOutcome::Accepted | RelaySubmitOutcome::Rejected => FingerprintState::Done,
+        };
+        if let Some(current) = self.inner.lock().unwrap().states.get_mut(fingerprint) {
+            *current = state;
+        }
+    }
+
+    /// Runs submit unless fingerprint is a repeat, None if suppressed.
+    pub async fn submit_once<Submit, Fut>(
+        &self,
+        fingerprint: SubmissionFingerprint,
+        submit: Submit,
+    ) -> Option<RelaySubmitOutcome>
+    where
+        Submit: FnOnce() -> Fut,
+        Fut: Future<Output = RelaySubmitOutcome>,
+    {
+        if !self.begin(&fingerprint) {
+            return None;
+        }
+        let outcome = submit().await;
+        self.finish(&fingerprint, outcome);
+        Some(outcome)
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::telemetry::RELAY_SUPPRESSED_SUBMISSIONS;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn fingerprint(relay: &str, block: u8) -> SubmissionFingerprint {
+        SubmissionFingerprint {
+            relay: relay.to_string(),
+            block_hash: B256::repeat_byte(block),
+            bid_value: U256::from(1000),
+        }
+    }
+
+    /// Counts the requests that reach the "wire".
+    async fn submit(
+        fingerprints: &SlotSubmissionFingerprints,
+        fingerprint: SubmissionFingerprint,
+        wire_requests: &AtomicUsize,
+        outcome: RelaySubmitOutcome,
+    ) -> Option<RelaySubmitOutcome> {
+        fingerprints
+            .submit_once(fingerprint, || async {
+                wire_requests.fetch_add(1, Ordering::SeqCst);
+                outcome
+            })
+            .await
+    }
+
+    #[tokio::test]
+    async fn test_repeated_submission_is_suppressed() {
+        let fingerprints = SlotSubmissionFingerprints::default();
+        let wire_requests = AtomicUsize::new(0);
+        let suppressed_before = RELAY_SUPPRESSED_SUBMISSIONS
+            .with_label_values(&["dedup_relay"])
+            .get();
+        let fp = fingerprint("dedup_relay", 1);
+        for outcome in [RelaySubmitOutcome::Accepted, RelaySubmitOutcome::Rejected] {
+            submit(&fingerprints, fp.clone(), &wire_requests, outcome).await;
+        }
+        assert_eq!(wire_requests.load(Ordering::SeqCst), 1);
+        assert_eq!(
+            RELAY_SUPPRESSED_SUBMISSIONS
+                .with_label_values(&["dedup_relay"])
+                .get(),
+            suppressed_before + 1
+        );
+
+        // other relay or other bid are not repeats
+        submit(
+            &fingerprints,
+            fingerprint("dedup_other_relay", 1),
+            &wire_requests,
+            RelaySubmitOutcome::Accepted,
+        )
+        .await;
+        let mut other_bid = fp;
+        other_bid.bid_value += U256::from(1);
+        submit(
+            &fingerprints,
+            other_bid,
+            &wire_requests,
+            RelaySubmitOutcome::Accepted,
+        )
+        .await;
+        assert_eq!(wire_requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_resend_after_transient_failure() {
+        let fingerprints = SlotSubmissionFingerprints::default();
+        let wire_requests = AtomicUsize::new(0);
+        let fp = fingerprint("transient_relay", 1);
+        let first = submit(
+            &fingerprints,
+            fp.clone(),
+            &wire_requests,
+            RelaySubmitOutcome::TransientFailure,
+        )
+        .await;
+        assert_eq!(first, Some(RelaySubmitOutcome::TransientFailure));
+        let second = submit(
+            &fingerprints,
+            fp.clone(),
+            &wire_requests,
+            RelaySubmitOutcome::Accepted,
+        )
+        .await;
+        assert_eq!(second, Some(RelaySubmitOutcome::Accepted));
+        let third = submit(
+            &fingerprints,
+            fp,
+            &wire_requests,
+            RelaySubmitOutcome::Accepted,
+        )
+        .await;
+        assert_eq!(third, None);
+        assert_eq!(wire_requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_bounded() {
+        let fingerprints = SlotSubmissionFingerprints::new(2);
+        for block in 0..3 {
+            assert!(fingerprints.begin(&fingerprint("bounded_relay", block)));
+        }
+        assert_eq!(fingerprints.len(), 2);
+        // the oldest was evicted, the newest are still there
+        assert!(fingerprints.begin(&fingerprint("bounded_relay", 0)));
+        assert!(!fingerprints.begin(&fingerprint("bounded_relay", 2)));
+    }
+}
@@ -0,0 +1,361 @@
+//! Rolling window tracking of the slot submission SLO.
+//! A submitted slot is a success if at least one relay accepted one of our bids.
+//! Slots where we intentionally did not submit are excluded from the ratios and only counted.
+use crate::{
+    primitives::mev_boost::MevBoostRelayID,
+    telemetry::{
+        inc_slots_without_submission, set_relay_submission_slo_ratio, set_submission_slo_ratio,
+    },
+};
+use ahash::{HashMap, HashSet};
+use lazy_static::lazy_static;
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+};
+use time::{Duration, OffsetDateTime};
+
+/// (metric label, window length)
+pub const SLO_WINDOWS: [(&str, Duration); 2] =
+    [("5m", Duration::minutes(5)), ("1h", Duration::hours(1))];
+
+lazy_static! {
+    /// Process wide tracker fed by the relay submission jobs.
+    pub static ref SUBMISSION_SLO_TRACKER: Mutex<SubmissionSloTracker> =
+        Mutex::new(SubmissionSloTracker::default());
+}
+
+/// Number of slot outcomes kept for [`SubmissionSloTracker::recent_slot_outcomes`].
+pub const RECENT_SLOT_OUTCOMES: usize = 64;
+
+/// Reason why we did not submit anything on a slot.
+/// If a slot went through several of these the most advanced one in the submission pipeline is reported
+/// (eg: SigningFailed over BlocksRejected), variants are sorted by how far in the pipeline they are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum NoSubmissionReason {
+    DryRun,
+    /// No block reached the submission job (nothing profitable or the bidder skipped sealing, eg: below the proposer min bid).
+    NoBlock,
+    /// Blocks reached the submission job but all of them failed the pre submission checks (eg: max_gas_used_percent).
+    BlocksRejected,
+    /// The kill switch was engaged (see [`crate::live_builder::kill_switch`]).
+    KillSwitch,
+    /// We failed to sign every block we tried to submit.
+    SigningFailed,
+    /// Signed blocks didn't go to any relay (eg: all relays skipped due to latency).
+    RelaysSkipped,
+}
+
+impl NoSubmissionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NoSubmissionReason::DryRun => "dry_run",
+            NoSubmissionReason::NoBlock => "no_block",
+            NoSubmissionReason::BlocksRejected => "blocks_rejected",
+            NoSubmissionReason::KillSwitch => "kill_switch",
+            NoSubmissionReason::SigningFailed => "signing_failed",
+            NoSubmissionReason::RelaysSkipped => "relays_skipped",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SlotSubmissionOutcome {
+    /// We sent bids to these relays, value is true if the relay accepted at least one of them.
+    Submitted(HashMap<MevBoostRelayID, bool>),
+    NotSubmitted(NoSubmissionReason),
+}
+
+impl SlotSubmissionOutcome {
+    /// Outcome of a slot given what the submission job saw.
+    /// job_reason is the no submission reason the job got to, only used if we didn't send anything to any relay.
+    pub fn new(
+        dry_run: bool,
+        job_reason: NoSubmissionReason,
+        relay_results: HashMap<MevBoostRelayID, bool>,
+    ) -> Self {
+        if dry_run {
+            SlotSubmissionOutcome::NotSubmitted(NoSubmissionReason::DryRun)
+        } else if relay_results.is_empty() {
+            SlotSubmissionOutcome::NotSubmitted(job_reason)
+        } else {
+            SlotSubmissionOutcome::Submitted(relay_results)
+        }
+    }
+}
+
+/// Per slot relay acceptance shared with the spawned relay submission tasks.
+#[derive(Debug, Clone, Default)]
+pub struct SlotRelayResults {
+    results: Arc<Mutex<HashMap<MevBoostRelayID, bool>>>,
+}
+
+impl SlotRelayResults {
+    /// Call before submitting so the relay counts even if the submission never finishes.
+    pub fn add_attempt(&self, relay: &MevBoostRelayID) {
+        self.results
+            .lock()
+            .unwrap()
+            .entry(relay.clone())
+            .or_insert(false);
+    }
+
+    pub fn add_result(&self, relay: &MevBoostRelayID, accepted: bool) {
+        let mut results = self.results.lock().unwrap();
+        let entry = results.entry(relay.clone()).or_insert(false);
+        *entry |= accepted;
+    }
+
+    pub fn results(&self) -> HashMap<MevBoostRelayID, bool> {
+        self.results.lock().unwrap().clone()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SubmissionSloTracker {
+    /// Submitted slots (slot timestamp, relay acceptance), oldest first.
+    /// Only slots within the biggest window are kept.
+    submitted_slots: VecDeque<(OffsetDateTime, HashMap<MevBoostRelayID, bool>)>,
+    /// Last RECENT_SLOT_OUTCOMES outcomes (submitted or not), oldest first.
+    recent_outcomes: VecDeque<(OffsetDateTime, SlotSubmissionOutcome)>,
+}
+
+impl SubmissionSloTracker {
+    pub fn record_slot(&mut self, slot_timestamp: OffsetDateTime, outcome: SlotSubmissionOutcome) {
+        if self.recent_outcomes.len() == RECENT_SLOT_OUTCOMES {
+            self.recent_outcomes.pop_front();
+        }
+        self.recent_outcomes
+            .push_back((slot_timestamp, outcome.clone()));
+        match outcome {
+            SlotSubmissionOutcome::Submitted(relays) => {
+                self.submitted_slots.push_back((slot_timestamp, relays));
+            }
+            SlotSubmissionOutcome::NotSubmitted(reason) => {
+                inc_slots_without_submission(reason.as_str());
+            }
+        }
+        self.prune(slot_timestamp);
+    }
+
+    /// Outcomes of the last slots, oldest first.
+    pub fn recent_slot_outcomes(
+        &self,
+    ) -> impl Iterator<Item = &(OffsetDateTime, SlotSubmissionOutcome)> {
+        self.recent_outcomes.iter()
+    }
+
+    fn prune(&mut self, now: OffsetDateTime) {
+        let max_window = SLO_WINDOWS
+            .iter()
+            .map(|(_, window)| *window)
+            .max()
+            .unwrap_or_default();
+        while let Some((timestamp, _)) = self.submitted_slots.front() {
+            if *timestamp > now - max_window {
+                break;
+            }
+            self.submitted_slots.pop_front();
+        }
+    }
+
+    fn slots_in_window(
+        &self,
+        now: OffsetDateTime,
+        window: Duration,
+    ) -> impl Iterator<Item = &HashMap<MevBoostRelayID, bool>> {
+        self.submitted_slots
+            .iter()
+            .filter(move |(timestamp, _)| *timestamp > now - window && *timestamp <= now)
+            .map(|(_, relays)| relays)
+    }
+
+    /// Ratio of submitted slots in (now - window, now] that had at least one relay accepting our bid.
+    /// None if we didn't submit on any slot in the window.
+    pub fn success_ratio(&self, now: OffsetDateTime, window: Duration) -> Option<f64> {
+        let mut total = 0;
+        let mut ok = 0;
+        for relays in self.slots_in_window(now, window) {
+            total += 1;
+            if relays.values().any(|accepted| *accepted) {
+                ok += 1;
+            }
+        }
+        (total != 0).then(|| ok as f64 / total as f64)
+    }
+
+    /// Same as success_ratio but only considering the slots where we submitted to the given relay.
+    pub fn relay_success_ratio(
+        &self,
+        relay: &MevBoostRelayID,
+        now: OffsetDateTime,
+        window: Duration,
+    ) -> Option<f64> {
+        let mut total = 0;
+        let mut ok = 0;
+        for accepted in self
+            .slots_in_window(now, window)
+            .filter_map(|relays| relays.get(relay))
+        {
+            total += 1;
+            if *accepted {
+                ok += 1;
+            }
+        }
+        (total != 0).then(|| ok as f64 / total as f64)
+    }
+
+    pub fn update_metrics(&self, now: OffsetDateTime) {
+        let relays: HashSet<&MevBoostRelayID> = self
+            .submitted_slots
+            .iter()
+            .flat_map(|(_, relays)| relays.keys())
+            .collect();
+        for (label, window) in SLO_WINDOWS {
+            if let Some(ratio) = self.success_ratio(now, window) {
+                set_submission_slo_ratio(label, ratio);
+            }
+            for relay in &relays {
+                if let Some(ratio) = self.relay_success_ratio(relay, now, window) {
+                    set_relay_submission_slo_ratio(relay, label, ratio);
+                }
+            }
+        }
+    }
+}
+
+/// Records the outcome on the global tracker and refreshes the gauges.
+pub fn record_slot_submission_outcome(
+    slot_timestamp: OffsetDateTime,
+    outcome: SlotSubmissionOutcome,
+) {
+    let mut tracker = SUBMISSION_SLO_TRACKER.lock().unwrap();
+    tracker.record_slot(slot_timestamp, outcome);
+    tracker.update_metrics(slot_timestamp);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn submitted(relays: &[(&str, bool)]) -> SlotSubmissionOutcome {
+        SlotSubmissionOutcome::Submitted(
+            relays
+                .iter()
+                .map(|(relay, accepted)| (relay.to_string(), *accepted))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_window_ratios() {
+        let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let slot = Duration::seconds(12);
+        let mut tracker = SubmissionSloTracker::default();
+
+        // 300 slots = 1h, first 150 fail on every relay, the rest are accepted by relay "a" only
+        for i in 0..300 {
+            let accepted = i >= 150;
+            tracker.record_slot(
+                start + slot * i,
+                submitted(&[("a", accepted), ("b", false)]),
+            );
+        }
+        // excluded from the denominator
+        tracker.record_slot(
+            start + slot * 299,
+            SlotSubmissionOutcome::NotSubmitted(NoSubmissionReason::DryRun),
+        );
+        tracker.record_slot(
+            start + slot * 299,
+            SlotSubmissionOutcome::NotSubmitted(NoSubmissionReason::NoBlock),
+        );
+
+        let now = start + slot * 299;
+        let (_, five_min) = SLO_WINDOWS[0];
+        let (_, one_hour) = SLO_WINDOWS[1];
+        assert_eq!(tracker.success_ratio(now, five_min), Some(1.0));
+        assert_eq!(tracker.success_ratio(now, one_hour), Some(0.5));
+        assert_eq!(
+            tracker.relay_success_ratio(&"a".to_string(), now, one_hour),
+            Some(0.5)
+        );
+        assert_eq!(
+            tracker.relay_success_ratio(&"b".to_string(), now, one_hour),
+            Some(0.0)
+        );
+        assert_eq!(
+            tracker.relay_success_ratio(&"c".to_string(), now, one_hour),
+            None
+        );
+    }
+
+    #[test]
+    fn test_slot_outcome() {
+        let relays: HashMap<MevBoostRelayID, bool> =
+            [("a".to_string(), false)].into_iter().collect();
+        for reason in [
+            NoSubmissionReason::NoBlock,
+            NoSubmissionReason::BlocksRejected,
+            NoSubmissionReason::SigningFailed,
+            NoSubmissionReason::RelaysSkipped,
+        ] {
+            assert_eq!(
+                SlotSubmissionOutcome::new(false, reason, HashMap::default()),
+                SlotSubmissionOutcome::NotSubmitted(reason)
+            );
+            // dry run wins over anything the job saw
+            assert_eq!(
+                SlotSubmissionOutcome::new(true, reason, HashMap::default()),
+                SlotSubmissionOutcome::NotSubmitted(NoSubmissionReason::DryRun)
+            );
+            // if we reached any relay the slot counts as submitted even if the relay rejected us
+            assert_eq!(
+                SlotSubmissionOutcome::new(false, reason, relays.clone()),
+                SlotSubmissionOutcome::Submitted(relays.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn test_recent_slot_outcomes() {
+        let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let slot = Duration::seconds(12);
+        let mut tracker = SubmissionSloTracker::default();
+        for i in 0..RECENT_SLOT_OUTCOMES as i32 + 1 {
+            let outcome = if i % 2 == 0 {
+                SlotSubmissionOutcome::NotSubmitted(NoSubmissionReason::BlocksRejected)
+            } else {
+                submitted(&[("a", true)])
+            };
+            tracker.record_slot(start + slot * i, outcome);
+        }
+        let recent: Vec<_> = tracker.recent_slot_outcomes().collect();
+        assert_eq!(recent.len(), RECENT_SLOT_OUTCOMES);
+        // first slot was dropped
+        assert_eq!(recent[0].0, start + slot);
+        assert_eq!(recent[0].1, submitted(&[("a", true)]));
+        assert_eq!(
+            recent[RECENT_SLOT_OUTCOMES - 1].1,
+            SlotSubmissionOutcome::NotSubmitted(NoSubmissionReason::BlocksRejected)
+        );
+    }
+
+    #[test]
+    fn test_old_slots_are_pruned() {
+        let start = OffsetDateTime::from_unix_timestamp(1_700_000_000).unwrap();
+        let mut tracker = SubmissionSloTracker::default();
+        tracker.record_slot(start, submitted(&[("a", false)]));
+        tracker.record_slot(start + Duration::hours(2), submitted(&[("a", true)]));
+
+        assert_eq!(tracker.submitted_slots.len(), 1);
+        let (_, one_hour) = SLO_WINDOWS[1];
+        assert_eq!(
+            tracker.success_ratio(start + Duration::hours(2), one_hour),
+            Some(1.0)
+        );
+
+        let empty = SubmissionSloTracker::default();
+        assert_eq!(empty.success_ratio(start, one_hour), None);
+    }
+}
@@ -0,0 +1,376 @@
+//! Canary: every interval_mins rebuilds a recent landed block committing its exact txs through the same commit and
+//! finalize code we use to build, and compares the result with the chain. Regressions in execution show up against
+//! known good answers, independently of the orderflow.
+//! CorrectRoot needs the parent to be the tip of our db and a landed block is already our tip (or older) so the state
+//! root is computed on the historical state of the parent (RootHashMode::HistoricalParent) and compared with the landed one.
+//! Runs one at a time on its own single thread pool so it can't take cpu from simulation/building.
+use crate::{
+    building::{BlockBuildingContext, BlockState, PartialBlock, PartialBlockFork},
+    primitives::TransactionSignedEcRecoveredWithBlobs,
+    roothash::RootHashMode,
+    telemetry::inc_canary_runs,
+    utils::{error_storage::store_error_event, ProviderFactoryReopener},
+};
+use alloy_primitives::B256;
+use eyre::eyre;
+use reth::{
+    primitives::ChainSpec,
+    providers::{BlockNumReader, BlockReader, ProviderFactory, ReceiptProvider},
+    tasks::pool::BlockingTaskPool,
+};
+use reth_db::database::Database;
+use revm::primitives::SpecId;
+use serde::{Deserialize, Serialize};
+use std::{fmt::Debug, sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+const CANARY_ERROR_CATEGORY: &str = "canary_mismatch";
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct CanaryConfig {
+    pub interval_mins: u64,
+    /// How many blocks behind our tip is the rebuilt block, keeps us away from reorgs.
+    pub block_depth: u64,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            interval_mins: 10,
+            block_depth: 2,
+        }
+    }
+}
+
+impl CanaryConfig {
+    pub fn interval(&self) -> Duration {
+        Duration::from_secs(self.interval_mins * 60)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HeaderMismatch {
+    pub field: &'static str,
+    pub expected: String,
+    pub got: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ReceiptMismatch {
+    pub tx_index: usize,
+    pub tx_hash: B256,
+    pub expected_success: bool,
+    /// None if we failed to commit the tx.
+    pub got_success: Option<bool>,
+    pub expected_cumulative_gas_used: u64,
+    pub got_cumulative_gas_used: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CanaryReport {
+    pub block_number: u64,
+    pub block_hash: B256,
+    pub header_mismatches: Vec<HeaderMismatch>,
+    /// First tx with a different receipt, None if the canonical receipts are not in our db (eg: pruned).
+    pub first_receipt_mismatch: Option<ReceiptMismatch>,
+    /// (tx index, error) of the txs we failed to commit.
+    pub failed_txs: Vec<(usize, String)>,
+}
+
+impl CanaryReport {
+    pub fn passed(&self) -> bool {
+        self.header_mismatches.is_empty()
+            && self.first_receipt_mismatch.is_none()
+            && self.failed_txs.is_empty()
+    }
+}
+
+/// Re-executes block_number on top of its parent and compares it with the landed one.
+/// spec_id None: the SpecId of the block.
+/// root_hash_task_pool is not used by RootHashMode::HistoricalParent, it's only needed by finalize.
+pub fn rebuild_landed_block<DB: Database + Clone + 'static>(
+    provider_factory: ProviderFactory<DB>,
+    chain_spec: Arc<ChainSpec>,
+    block_number: u64,
+    spec_id: Option<SpecId>,
+    root_hash_task_pool: BlockingTaskPool,
+) -> eyre::Result<CanaryReport> {
+    let block = provider_factory
+        .block_by_number(block_number)?
+        .ok_or_else(|| eyre!("Block {} not found", block_number))?
+        .seal_slow();
+    let parent_number = block_number
+        .checked_sub(1)
+        .ok_or_else(|| eyre!("Can't rebuild the genesis block"))?;
+    let canonical_receipts = provider_factory.receipts_by_block(block_number.into())?;
+    let ctx = BlockBuildingContext::from_landed_block(&block.clone().unseal(), chain_spec, spec_id);
+
+    let state_provider = provider_factory.history_by_block_number(parent_number)?;
+    let mut state = BlockState::new(&state_provider);
+    let mut partial_block = PartialBlock::new(false, None);
+    partial_block.pre_block_call(&ctx, &mut state)?;
+
+    // One by one with commit_tx, txs paying from the coinbase would fail the profit check of commit_order.
+    let mut failed_txs = Vec::new();
+    {
+        let mut fork = PartialBlockFork::new(&mut state);
+        for (index, tx) in block.body.iter().enumerate() {
+            let tx = TransactionSignedEcRecoveredWithBlobs {
+                tx: tx
+                    .clone()
+                    .try_ecrecovered()
+                    .ok_or_else(|| eyre!("Failed to recover tx {}", index))?,
+                blobs_sidecar: Default::default(),
+                metadata: Default::default(),
+            };
+            match fork.commit_tx(
+                &tx,
+                &ctx,
+                partial_block.gas_used,
+                0,
+                partial_block.blob_gas_used,
+            )? {
+                Ok(ok) => {
+                    partial_block.gas_used = ok.cumulative_gas_used;
+                    partial_block.blob_gas_used = ok.cumulative_blob_gas_used;
+                    partial_block.executed_tx.push(ok.tx);
+                    partial_block.receipts.push(ok.receipt);
+                }
+                Err(err) => failed_txs.push((index, err.to_string())),
+            }
+        }
+    }
+
+    let first_receipt_mismatch = canonical_receipts.and_then(|canonical_receipts| {
+        canonical_receipts
+            .into_iter()
+            .enumerate()
+            .find_map(|(tx_index, expected)| {
+                let got = partial_block.receipts.get(tx_index);
+                (got != Some(&expected)).then(|| ReceiptMismatch {
+                    tx_index,
+                    tx_hash: block.body[tx_index].hash(),
+                    expected_success: expected.success,
+                    got_success: got.map(|receipt| receipt.success),
+                    expected_cumulative_gas_used: expected.cumulative_gas_used,
+                    got_cumulative_gas_used: got.map(|receipt| receipt.cumulative_gas_used),
+                })
+            })
+    });
+
+    let rebuilt = partial_block
+        .finalize(
+            state,
+            &ctx,
+            provider_factory,
+            RootHashMode::HistoricalParent,
+            root_hash_task_pool,
+        )?
+        .sealed_block;
+
+    let mut header_mismatches = Vec::new();
+    let mut compare = |field: &'static str, expected: &dyn Debug, got: &dyn Debug| {
+        let (expected, got) = (format!("{:?}", expected), format!("{:?}", got));
+        if expected != got {
+            header_mismatches.push(HeaderMismatch {
+                field,
+                expected,
+                got,
+            });
+        }
+    };
+    let (expected, got) = (&block.header, &rebuilt.header);
+    compare("state_root", &expected.state_root, &got.state_root);
+    compare("gas_used", &expected.gas_used, &got.gas_used);
+    compare("receipts_root", &expected.receipts_root, &got.receipts_root);
+    compare("logs_bloom", &expected.logs_bloom, &got.logs_bloom);
+    compare(
+        "transactions_root",
+        &expected.transactions_root,
+        &got.transactions_root,
+    );
+    compare(
+        "withdrawals_root",
+        &expected.withdrawals_root,
+        &got.withdrawals_root,
+    );
+    compare("blob_gas_used", &expected.blob_gas_used, &got.blob_gas_used);
+
+    Ok(CanaryReport {
+        block_number,
+        block_hash: block.hash(),
+        header_mismatches,
+        first_receipt_mismatch,
+        failed_txs,
+    })
+}
+
+/// Rebuilds our tip - block_depth, reports the result on canary_runs and stores mismatches on the error storage.
+pub fn run_canary<DB: Database + Clone + 'static>(
+    provider_factory: ProviderFactory<DB>,
+    chain_spec: Arc<ChainSpec>,
+    block_depth: u64,
+    root_hash_task_pool: BlockingTaskPool,
+) -> eyre::Result<CanaryReport> {
+    let res = provider_factory
+        .last_block_number()
+        .map_err(eyre::Report::from)
+        .and_then(|last_block| {
+            let block_number = last_block
+                .checked_sub(block_depth)
+                .ok_or_else(|| eyre!("Not enough blocks for the canary"))?;
+            rebuild_landed_block(
+                provider_factory,
+                chain_spec,
+                block_number,
+                None,
+                root_hash_task_pool,
+            )
+        });
+    match &res {
+        Ok(report) if report.passed() => {
+            inc_canary_runs("pass");
+            info!(block = report.block_number, "Canary passed");
+        }
+        Ok(report) => {
+            inc_canary_runs("mismatch");
+            error!(
+                block = report.block_number,
+                ?report,
+                "Canary rebuilt block differs from the landed block"
+            );
+            store_error_event(CANARY_ERROR_CATEGORY, "rebuilt block differs", report);
+        }
+        Err(err) => {
+            inc_canary_runs("error");
+            warn!(?err, "Canary failed to run");
+        }
+    }
+    res
+}
+
+/// Runs the canary every config.interval(), one at a time on a dedicated single thread pool.
+pub fn spawn_canary<DB: Database + Clone + 'static>(
+    provider_factory: ProviderFactoryReopener<DB>,
+    chain_spec: Arc<ChainSpec>,
+    config: CanaryConfig,
+    cancel: CancellationToken,
+) -> eyre::Result<JoinHandle<()>> {
+    let pool = BlockingTaskPool::new(
+        BlockingTaskPool::builder()
+            .num_threads(1)
+            .thread_name(|_| "canary".to_string())
+            .build()?,
+    );
+    Ok(tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(config.interval()) => {}
+            }
+            let provider_factory = provider_factory.provider_factory_unchecked();
+            let chain_spec = chain_spec.clone();
+            let block_depth = config.block_depth;
+            let root_hash_task_pool = pool.clone();
+            if let Err(err) = pool
+                .spawn(move || {
+                    run_canary(
+                        provider_factory,
+                        chain_spec,
+                        block_depth,
+                        root_hash_task_pool,
+                    )
+                })
+                .await
+            {
+                error!(?err, "Canary task panicked");
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::building::testing::test_chain_state::{
+        BlockArgs, NamedAddr, TestChainState, TxArgs,
+    };
+    use reth::{primitives::MAINNET, providers::BlockWriter};
+
+    /// Builds block 1 of the harness chain (with its state root) and lands it on the db.
+    fn land_block(test_chain: &TestChainState) -> eyre::Result<()> {
+        let ctx = test_chain.block_building_context();
+        let state_provider = test_chain.provider_factory().latest()?;
+        let mut state = BlockState::new(&state_provider);
+        let mut partial_block = PartialBlock::new(false, None);
+        partial_block.pre_block_call(ctx, &mut state)?;
+        for args in [
+            TxArgs::new_send_to_coinbase(NamedAddr::User(0), 0, 1_000_000),
+            TxArgs::new_revert(NamedAddr::User(1), 0),
+        ] {
+            let tx = test_chain.sign_tx(args)?;
+            let tx = TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap();
+            let mut fork = PartialBlockFork::new(&mut state);
+            let ok = fork
+                .commit_tx(&tx, ctx, partial_block.gas_used, 0, 0)?
+                .unwrap();
+            partial_block.gas_used = ok.cumulative_gas_used;
+            partial_block.executed_tx.push(ok.tx);
+            partial_block.receipts.push(ok.receipt);
+        }
+        let block = partial_block
+            .finalize(
+                state,
+                ctx,
+                test_chain.provider_factory().clone(),
+                RootHashMode::CorrectRoot,
+                BlockingTaskPool::build()?,
+            )?
+            .sealed_block;
+        let provider = test_chain.provider_factory().provider_rw()?;
+        provider.insert_historical_block(block.try_seal_with_senders().unwrap(), None)?;
+        provider.commit()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_canary() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        land_block(&test_chain)?;
+        let provider_factory = test_chain.provider_factory().clone();
+
+        // harness chain runs on shanghai
+        let report = rebuild_landed_block(
+            provider_factory.clone(),
+            MAINNET.clone(),
+            1,
+            Some(SpecId::SHANGHAI),
+            BlockingTaskPool::build()?,
+        )?;
+        assert!(report.passed(), "{:?}", report);
+        assert_eq!(report.block_number, 1);
+
+        // pre shanghai the coinbase is cold (EIP-3651) so paying it costs more gas
+        let report = rebuild_landed_block(
+            provider_factory,
+            MAINNET.clone(),
+            1,
+            Some(SpecId::MERGE),
+            BlockingTaskPool::build()?,
+        )?;
+        assert!(!report.passed());
+        let fields: Vec<_> = report
+            .header_mismatches
+            .iter()
+            .map(|mismatch| mismatch.field)
+            .collect();
+        assert!(fields.contains(&"gas_used"));
+        assert!(fields.contains(&"receipts_root"));
+        // the coinbase and sender balances differ too
+        assert!(fields.contains(&"state_root"));
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use std::{path::PathBuf, sync::Arc};
 
+use alloy_primitives::{utils::parse_ether, Address};
 use clap::Parser;
 use reth_db::DatabaseEnv;
 use reth_payload_builder::database::CachedReads;
@@ -8,12 +9,20 @@ use tokio_util::sync::CancellationToken;
 
 use crate::{
     building::builders::{BacktestSimulateBlockInput, Block},
-    live_builder::base_config::load_config_toml_and_env,
+    live_builder::base_config::{load_config_toml_and_env, load_effective_config},
     telemetry::spawn_telemetry_server,
     utils::build_info::Version,
 };
 
-use super::{base_config::BaseConfig, building::relay_submit::RelaySubmitSinkFactory, LiveBuilder};
+use super::{
+    base_config::BaseConfig,
+    building::relay_submit::RelaySubmitSinkFactory,
+    config_validation::ConfigValidationReport,
+    devnet::{
+        devnet_accounts, load_base_genesis, write_devnet_genesis, DEFAULT_DEVNET_BALANCE_ETH,
+    },
+    LiveBuilder,
+};
 
 #[derive(Parser, Debug)]
 enum Cli {
@@ -21,6 +30,16 @@ enum Cli {
     Run(RunCmd),
     #[clap(name = "config", about = "Print the current config")]
     Config(RunCmd),
+    #[clap(
+        name = "check-config",
+        about = "Validate the config and print all the errors found"
+    )]
+    CheckConfig(CheckConfigCmd),
+    #[clap(
+        name = "devnet-init",
+        about = "Write a devnet genesis funding the accounts used by the config"
+    )]
+    DevnetInit(DevnetInitCmd),
     #[clap(name = "version", about = "Print version information")]
     Version,
 }
@@ -31,9 +50,43 @@ struct RunCmd {
     config: PathBuf,
 }
 
+#[derive(Parser, Debug)]
+struct CheckConfigCmd {
+    #[clap(help = "Config file path")]
+    config: PathBuf,
+    #[clap(
+        long,
+        help = "Print the config merged with its profile and where each value came from"
+    )]
+    print_effective: bool,
+}
+
+#[derive(Parser, Debug)]
+struct DevnetInitCmd {
+    #[clap(help = "Config file path")]
+    config: PathBuf,
+    #[clap(
+        long,
+        help = "Genesis to patch, by default the genesis of the config chain"
+    )]
+    genesis: Option<PathBuf>,
+    #[clap(long, help = "Where to write the genesis")]
+    out: PathBuf,
+    #[clap(long, default_value_t = DEFAULT_DEVNET_BALANCE_ETH, help = "Balance of every funded account")]
+    balance_eth: u64,
+    #[clap(long, help = "Extra address to fund, can be repeated")]
+    fund: Vec<Address>,
+    #[clap(long, help = "Don't deploy the EIP-4788 beacon roots contract")]
+    no_beacon_roots_contract: bool,
+}
+
 /// Basic stuff needed to call cli::run
 pub trait LiveBuilderConfig: std::fmt::Debug + serde::de::DeserializeOwned {
     fn base_config(&self) -> &BaseConfig;
+    /// Cross-field validation ran before starting the builder.
+    fn validate(&self) -> ConfigValidationReport {
+        self.base_config().validate()
+    }
     /// Version reported by telemetry
     fn version_for_telemetry(&self) -> Version;
     /// Desugared from async to future to keep clippy happy
@@ -67,6 +120,26 @@ pub async fn run<ConfigType: LiveBuilderConfig>(
             println!("{:#?}", config);
             return Ok(());
         }
+        Cli::CheckConfig(cli) => {
+            let effective_config = load_effective_config(cli.config)?;
+            if cli.print_effective {
+                println!("{}", effective_config);
+            }
+            let config: ConfigType = effective_config.deserialize()?;
+            config.validate().into_result()?;
+            println!("Config OK");
+            return Ok(());
+        }
+        Cli::DevnetInit(cli) => {
+            let config: ConfigType = load_config_toml_and_env(cli.config)?;
+            let balance = parse_ether(&cli.balance_eth.to_string())?;
+            let accounts = devnet_accounts(config.base_config(), balance, &cli.fund)?;
+            let genesis = load_base_genesis(config.base_config(), cli.genesis.as_deref())?;
+            let summary =
+                write_devnet_genesis(genesis, &cli.out, &accounts, !cli.no_beacon_roots_contract)?;
+            print!("{}", summary);
+            return Ok(());
+        }
         Cli::Version => {
             print_version_info();
             return Ok(());
@@ -74,6 +147,7 @@ pub async fn run<ConfigType: LiveBuilderConfig>(
     };
 
     let config: ConfigType = load_config_toml_and_env(cli.config)?;
+    config.validate().into_result()?;
     config.base_config().setup_tracing_subsriber()?;
 
     let cancel = CancellationToken::new();
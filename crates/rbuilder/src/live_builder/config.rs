@@ -3,14 +3,18 @@
 use crate::{
     building::{
         builders::{
-            ordering_builder::{OrderingBuilderConfig, OrderingBuildingAlgorithm},
+            ordering_builder::{
+                OrderingBuilderConfig, OrderingBuildingAlgorithm, WeightedOrderingBuilderConfig,
+                WeightedOrderingBuildingAlgorithm,
+            },
             BacktestSimulateBlockInput, BestBlockCell, Block, BlockBuildingAlgorithm,
         },
         Sorting,
     },
-    live_builder::cli::LiveBuilderConfig,
+    live_builder::{cli::LiveBuilderConfig, config_validation::ConfigValidationReport},
     utils::{build_info::rbuilder_version, ProviderFactoryReopener, Signer},
 };
+use ahash::HashMap;
 use alloy_primitives::{Address, B256};
 use eyre::Context;
 use reth::{
@@ -34,6 +38,8 @@ use super::base_config::BaseConfig;
 #[serde(tag = "algo", rename_all = "kebab-case", deny_unknown_fields)]
 pub enum SpecificBuilderConfig {
     OrderingBuilder(OrderingBuilderConfig),
+    /// Several ordering builder configs sharing the iterations by their recent profit.
+    WeightedOrderingBuilder(WeightedOrderingBuilderConfig),
 }
 
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
@@ -57,6 +63,64 @@ impl LiveBuilderConfig for Config {
     fn base_config(&self) -> &BaseConfig {
         &self.base_config
     }
+
+    fn validate(&self) -> ConfigValidationReport {
+        let mut report = self.base_config.validate();
+
+        let mut names: HashMap<&str, usize> = HashMap::default();
+        for (i, builder) in self.builders.iter().enumerate() {
+            if let Some(first) = names.insert(&builder.name, i) {
+                report.add(
+                    format!("builders[{}].name", i),
+                    format!(
+                        "duplicated builder name {:?}, already used by builders[{}]",
+                        builder.name, first
+                    ),
+                );
+            }
+            match &builder.builder {
+                SpecificBuilderConfig::OrderingBuilder(cfg) => {
+                    validate_ordering_builder(&mut report, &format!("builders[{}]", i), cfg);
+                }
+                SpecificBuilderConfig::WeightedOrderingBuilder(cfg) => {
+                    if cfg.candidates.is_empty() {
+                        report.add(format!("builders[{}].candidates", i), "can't be empty");
+                    }
+                    for (j, candidate) in cfg.candidates.iter().enumerate() {
+                        let prefix = format!("builders[{}].candidates[{}]", i, j);
+                        validate_ordering_builder(&mut report, &prefix, candidate);
+                        if candidate.sorting != cfg.candidates[0].sorting {
+                            report.add(
+                                format!("{}.sorting", prefix),
+                                "all the candidates must use the same sorting",
+                            );
+                        }
+                    }
+                    if cfg.min_iteration_share_percent > 100 {
+                        report.add(
+                            format!("builders[{}].min_iteration_share_percent", i),
+                            "must be <= 100",
+                        );
+                    }
+                }
+            }
+        }
+
+        for (field, builder_names) in [
+            ("live_builders", &self.base_config.live_builders),
+            ("backtest_builders", &self.base_config.backtest_builders),
+        ] {
+            for (i, name) in builder_names.iter().enumerate() {
+                if !names.contains_key(name.as_str()) {
+                    report.add(
+                        format!("{}[{}]", field, i),
+                        format!("builder {:?} not found in builders list", name),
+                    );
+                }
+            }
+        }
+        report
+    }
     /// WARN: opens reth db
     async fn create_builder(
         &self,
@@ -88,10 +152,42 @@ impl LiveBuilderConfig for Config {
             SpecificBuilderConfig::OrderingBuilder(config) => {
                 crate::building::builders::ordering_builder::backtest_simulate_block(config, input)
             }
+            SpecificBuilderConfig::WeightedOrderingBuilder(config) => {
+                crate::building::builders::ordering_builder::backtest_simulate_weighted_block(
+                    config, input,
+                )
+            }
         }
     }
 }
 
+/// Checks of a single ordering builder config, prefix is its path on the config file.
+fn validate_ordering_builder(
+    report: &mut ConfigValidationReport,
+    prefix: &str,
+    cfg: &OrderingBuilderConfig,
+) {
+    if cfg.timestamp_offset_s == Some(0) {
+        report.add(
+            format!("{}.timestamp_offset_s", prefix),
+            "must be > 0, block timestamp must be bigger than the parent timestamp",
+        );
+    }
+    if cfg.build_duration_deadline_ms == Some(0) {
+        report.add(
+            format!("{}.build_duration_deadline_ms", prefix),
+            "must be > 0",
+        );
+    }
+    if cfg.max_orders_per_iteration == Some(0) {
+        report.add(
+            format!("{}.max_orders_per_iteration", prefix),
+            "must be > 0",
+        );
+    }
+    report.check(format!("{}.anchor_tx", prefix), cfg.anchor_tx().map(|_| ()));
+}
+
 impl Config {
     fn live_builders(&self) -> eyre::Result<Vec<BuilderConfig>> {
         self.base_config
@@ -122,8 +218,7 @@ impl Default for Config {
                         sorting: Sorting::MevGasPrice,
                         failed_order_retries: 1,
                         drop_failed_orders: true,
-                        coinbase_payment: false,
-                        build_duration_deadline_ms: None,
+                        ..Default::default()
                     }),
                 },
                 BuilderConfig {
@@ -133,8 +228,7 @@ impl Default for Config {
                         sorting: Sorting::MaxProfit,
                         failed_order_retries: 1,
                         drop_failed_orders: true,
-                        coinbase_payment: false,
-                        build_duration_deadline_ms: None,
+                        ..Default::default()
                     }),
                 },
             ],
@@ -216,15 +310,29 @@ fn create_builder(
                 cfg.name,
             ))
         }
+        SpecificBuilderConfig::WeightedOrderingBuilder(weighted_cfg) => {
+            Arc::new(WeightedOrderingBuildingAlgorithm::new(
+                root_hash_task_pool.clone(),
+                sbundle_mergeabe_signers.to_vec(),
+                weighted_cfg,
+                cfg.name,
+            ))
+        }
     }
 }
 
 #[cfg(test)]
 mod test {
-    use crate::live_builder::base_config::load_config_toml_and_env;
+    use crate::live_builder::{
+        base_config::{
+            load_config_toml_and_env, BuilderTagConfig, BuilderTagExperimentConfig, RelayConfig,
+            SigningIdentityConfig,
+        },
+        config_profile::ConfigProfile,
+    };
 
     use super::*;
-    use alloy_primitives::address;
+    use alloy_primitives::{address, Bytes};
     use std::env;
 
     #[test]
@@ -275,4 +383,264 @@ mod test {
 
         load_config_toml_and_env::<Config>(p).expect("Config load");
     }
+
+    /// Builds a report for a config modified by f and checks that it has an error at path.
+    fn assert_validation_error(path: &str, f: impl FnOnce(&mut Config)) {
+        let mut config = Config::default();
+        assert!(
+            !config.validate().has_error_at(path),
+            "default config should be ok at {}",
+            path
+        );
+        f(&mut config);
+        let report = config.validate();
+        assert!(
+            report.has_error_at(path),
+            "{} not found in: {}",
+            path,
+            report
+        );
+    }
+
+    fn ordering_builder_config(config: &mut Config, idx: usize) -> &mut OrderingBuilderConfig {
+        match &mut config.builders[idx].builder {
+            SpecificBuilderConfig::OrderingBuilder(cfg) => cfg,
+            _ => panic!("builders[{}] is not an ordering builder", idx),
+        }
+    }
+
+    #[test]
+    fn test_validate_dry_run_needs_validation_urls() {
+        assert_validation_error("dry_run_validation_url", |config| {
+            config.base_config.dry_run = true;
+        });
+        assert_validation_error("dry_run_validation_url", |config| {
+            config.base_config.optimistic_prevalidate_optimistic_blocks = true;
+        });
+    }
+
+    #[test]
+    fn test_validate_optimistic_needs_optimistic_key() {
+        assert_validation_error("optimistic_relay_secret_key", |config| {
+            config.base_config.optimistic_enabled = true;
+        });
+    }
+
+    #[test]
+    fn test_validate_optimistic_relay_needs_optimistic_enabled() {
+        assert_validation_error("relays[1].optimistic", |config| {
+            config.base_config.relays = vec![
+                relay_config("flashbots", false),
+                relay_config("ultrasound", true),
+            ];
+        });
+    }
+
+    #[test]
+    fn test_validate_duplicated_relay_name() {
+        assert_validation_error("relays[1].name", |config| {
+            config.base_config.relays = vec![
+                relay_config("flashbots", false),
+                relay_config("flashbots", false),
+            ];
+        });
+    }
+
+    #[test]
+    fn test_validate_signing_identities() {
+        let identity = |name: &str| SigningIdentityConfig {
+            name: name.to_string(),
+            relay_secret_key: "5eae315483f028b5cdd5d1090ff0c7618b18737ea9bf3c35047189db22835c48"
+                .into(),
+            payout_secret_key: None,
+        };
+        assert_validation_error("relays[0].signing_identity", |config| {
+            let mut relay = relay_config("flashbots", false);
+            relay.signing_identity = Some("key1".to_string());
+            config.base_config.relays = vec![relay];
+        });
+        assert_validation_error("signing_identities[1].name", |config| {
+            config.base_config.signing_identities = vec![identity("key1"), identity("key1")];
+        });
+        assert_validation_error("signing_identities[0].name", |config| {
+            config.base_config.signing_identities = vec![identity("default")];
+        });
+        assert_validation_error("signing_identities[0].relay_secret_key", |config| {
+            let mut bad_key = identity("key1");
+            bad_key.relay_secret_key = "0x1234".into();
+            config.base_config.signing_identities = vec![bad_key];
+        });
+        assert_validation_error("signing_identities[0].payout_secret_key", |config| {
+            let mut bad_key = identity("key1");
+            bad_key.payout_secret_key = Some("0x1234".into());
+            config.base_config.signing_identities = vec![bad_key];
+        });
+
+        let mut config = Config::default();
+        let mut relay = relay_config("flashbots", false);
+        relay.signing_identity = Some("key1".to_string());
+        config.base_config.relays = vec![relay];
+        config.base_config.signing_identities = vec![identity("key1")];
+        assert!(!config.validate().has_error_at("relays[0].signing_identity"));
+    }
+
+    #[test]
+    fn test_validate_devnet_profile() {
+        assert_validation_error("profile", |config| {
+            config.base_config.profile = Some(ConfigProfile::Devnet);
+        });
+
+        let mut config = Config::default();
+        config.base_config.profile = Some(ConfigProfile::Devnet);
+        config.base_config.chain = "dev".to_string();
+        assert!(!config.validate().has_error_at("profile"));
+        config.base_config.profile = Some(ConfigProfile::Mainnet);
+        config.base_config.chain = "mainnet".to_string();
+        assert!(!config.validate().has_error_at("profile"));
+    }
+
+    #[test]
+    fn test_validate_builder_tag_experiment() {
+        let identity = SigningIdentityConfig {
+            name: "key1".to_string(),
+            relay_secret_key: "5eae315483f028b5cdd5d1090ff0c7618b18737ea9bf3c35047189db22835c48"
+                .into(),
+            payout_secret_key: None,
+        };
+        let builder_tag = |signing_identity: &str| BuilderTagExperimentConfig {
+            seed: "seed".into(),
+            mapping_log_path: "/tmp/rbuilder-builder-tags.jsonl".parse().unwrap(),
+            tags: vec![BuilderTagConfig {
+                extra_data: "tag_a".to_string(),
+                signing_identity: signing_identity.to_string(),
+                coinbase_secret_key:
+                    "0x2f5d2cd5e1fd6b1c2e0a2ef3b2b9c1e8b6e1f3f2b7b1a9a1d9e7c3b5a7f1e2d4".into(),
+            }],
+        };
+        // needs the explicit opt in
+        assert_validation_error("experiments.enabled", |config| {
+            config.base_config.signing_identities = vec![identity.clone()];
+            config.base_config.experiments.builder_tag = Some(builder_tag("key1"));
+        });
+        let mut config = Config::default();
+        config.base_config.experiments.builder_tag = Some(builder_tag("key1"));
+        assert!(config.base_config.builder_tag_schedule().is_err());
+
+        assert_validation_error(
+            "experiments.builder_tag.tags[0].signing_identity",
+            |config| {
+                config.base_config.experiments.enabled = true;
+                config.base_config.experiments.builder_tag = Some(builder_tag("key2"));
+            },
+        );
+
+        let mut config = Config::default();
+        config.base_config.signing_identities = vec![identity];
+        config.base_config.experiments.enabled = true;
+        config.base_config.experiments.builder_tag = Some(builder_tag("key1"));
+        let report = config.validate();
+        assert!(!report.has_error_at("experiments.enabled"));
+        assert!(!report.has_error_at("experiments.builder_tag.tags[0].signing_identity"));
+        assert!(config.base_config.builder_tag_schedule().unwrap().is_some());
+    }
+
+    #[test]
+    fn test_validate_unknown_builder_names() {
+        assert_validation_error("live_builders[2]", |config| {
+            config.base_config.live_builders.push("merging".to_string());
+        });
+        assert_validation_error("backtest_builders[0]", |config| {
+            config.base_config.backtest_builders = vec!["merging".to_string()];
+        });
+    }
+
+    #[test]
+    fn test_validate_duplicated_builder_name() {
+        assert_validation_error("builders[1].name", |config| {
+            config.builders[1].name = config.builders[0].name.clone();
+        });
+    }
+
+    #[test]
+    fn test_validate_telemetry_port_clash() {
+        assert_validation_error("telemetry_port", |config| {
+            config.base_config.telemetry_port = config.base_config.jsonrpc_server_port;
+        });
+    }
+
+    #[test]
+    fn test_validate_zero_timestamp_offset() {
+        assert_validation_error("builders[1].timestamp_offset_s", |config| {
+            ordering_builder_config(config, 1).timestamp_offset_s = Some(0);
+        });
+    }
+
+    #[test]
+    fn test_validate_zero_max_orders_per_iteration() {
+        assert_validation_error("builders[1].max_orders_per_iteration", |config| {
+            ordering_builder_config(config, 1).max_orders_per_iteration = Some(0);
+        });
+    }
+
+    #[test]
+    fn test_validate_invalid_anchor_tx() {
+        assert_validation_error("builders[0].anchor_tx", |config| {
+            ordering_builder_config(config, 0).anchor_tx = Some(Bytes::from_static(&[1, 2, 3]));
+        });
+    }
+
+    #[test]
+    fn test_validate_weighted_builder_sorting() {
+        assert_validation_error("builders[0].candidates[1].sorting", |config| {
+            let candidate = ordering_builder_config(config, 0).clone();
+            let mut other_candidate = candidate.clone();
+            other_candidate.sorting = match candidate.sorting {
+                Sorting::MevGasPrice => Sorting::MaxProfit,
+                Sorting::MaxProfit => Sorting::MevGasPrice,
+            };
+            config.builders[0].builder =
+                SpecificBuilderConfig::WeightedOrderingBuilder(WeightedOrderingBuilderConfig {
+                    candidates: vec![candidate, other_candidate],
+                    min_iteration_share_percent: 10,
+                });
+        });
+    }
+
+    #[test]
+    fn test_validate_reports_all_errors() {
+        let mut config = Config::default();
+        config.base_config.dry_run = true;
+        config.base_config.live_builders.push("merging".to_string());
+        ordering_builder_config(&mut config, 0).build_duration_deadline_ms = Some(0);
+        let report = config.validate();
+        for path in [
+            "dry_run_validation_url",
+            "live_builders[2]",
+            "builders[0].build_duration_deadline_ms",
+        ] {
+            assert!(
+                report.has_error_at(path),
+                "{} not found in: {}",
+                path,
+                report
+            );
+        }
+        assert!(report.into_result().is_err());
+    }
+
+    fn relay_config(name: &str, optimistic: bool) -> RelayConfig {
+        RelayConfig {
+            name: name.to_string(),
+            url: "https://0xac6e77dfe25ecd6110b8e780608cce0dab71fdd5ebea22a16c0205200f2f8e2e3ad3b71d3499c54ad14d6c21b41a37ae@boost-relay.flashbots.net".to_string(),
+            priority: 0,
+            use_ssz_for_submit: false,
+            use_gzip_for_submit: false,
+            optimistic,
+            authorization_header: None,
+            builder_id_header: None,
+            api_token_header: None,
+            interval_between_submissions_ms: None,
+            signing_identity: None,
+        }
+    }
 }
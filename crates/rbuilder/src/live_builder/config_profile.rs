@@ -0,0 +1,264 @@
+//! Named presets selected with `profile = "mainnet" | "holesky" | "devnet"` on the config file.
+//! The config is built by layers: field defaults < profile < config file, so anything set explicitly on the file wins over
+//! the profile. [`EffectiveConfig`] keeps the layer that set each value so the merged config can be inspected
+//! (`check-config --print-effective`).
+use eyre::eyre;
+use serde::{de::DeserializeOwned, Deserialize};
+use std::{collections::BTreeMap, fmt};
+use toml::{Table, Value};
+
+pub const PROFILE_KEY: &str = "profile";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfigProfile {
+    Mainnet,
+    Holesky,
+    /// Local devnets, allows well-known test keys so it's refused on mainnet.
+    Devnet,
+}
+
+const MAINNET_PROFILE: &str = r#"
+chain = "mainnet"
+slot_delta_to_start_submits_ms = -5000
+fast_relay_window_ms = 500
+root_hash_task_pool_threads = 4
+watchdog_timeout_sec = 180
+allow_known_test_keys = false
+log_json = true
+log_level = "info,rbuilder=debug"
+"#;
+
+const HOLESKY_PROFILE: &str = r#"
+chain = "holesky"
+slot_delta_to_start_submits_ms = -5000
+fast_relay_window_ms = 500
+root_hash_task_pool_threads = 2
+watchdog_timeout_sec = 300
+allow_known_test_keys = false
+log_json = true
+log_level = "info,rbuilder=debug"
+"#;
+
+const DEVNET_PROFILE: &str = r#"
+chain = "dev"
+slot_delta_to_start_submits_ms = -2000
+root_hash_task_pool_threads = 1
+watchdog_timeout_sec = 600
+allow_known_test_keys = true
+log_json = false
+log_color = true
+log_level = "info,rbuilder=trace"
+"#;
+
+impl ConfigProfile {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConfigProfile::Mainnet => "mainnet",
+            ConfigProfile::Holesky => "holesky",
+            ConfigProfile::Devnet => "devnet",
+        }
+    }
+
+    /// Values set by the profile, same keys as the config file.
+    pub fn defaults(&self) -> Table {
+        let defaults = match self {
+            ConfigProfile::Mainnet => MAINNET_PROFILE,
+            ConfigProfile::Holesky => HOLESKY_PROFILE,
+            ConfigProfile::Devnet => DEVNET_PROFILE,
+        };
+        toml::from_str(defaults).expect("Invalid profile defaults")
+    }
+}
+
+impl fmt::Display for ConfigProfile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Where a config value came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigLayer {
+    Profile(ConfigProfile),
+    File,
+}
+
+impl fmt::Display for ConfigLayer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigLayer::Profile(profile) => write!(f, "profile {}", profile),
+            ConfigLayer::File => write!(f, "config file"),
+        }
+    }
+}
+
+/// Merged config layers.
+#[derive(Debug, Clone, Default)]
+pub struct EffectiveConfig {
+    pub table: Table,
+    /// Dotted path of every value set -> layer that set it. Anything not here gets its field default.
+    pub provenance: BTreeMap<String, ConfigLayer>,
+}
+
+impl EffectiveConfig {
+    /// The profile key of the file (if any) selects the profile layer.
+    pub fn from_file_table(file: Table) -> eyre::Result<Self> {
+        let mut res = Self::default();
+        if let Some(profile) = file.get(PROFILE_KEY) {
+            let profile = ConfigProfile::deserialize(profile.clone())
+                .map_err(|err| eyre!("Invalid {}: {}", PROFILE_KEY, err))?;
+            res.add_layer(profile.defaults(), ConfigLayer::Profile(profile));
+        }
+        res.add_layer(file, ConfigLayer::File);
+        Ok(res)
+    }
+
+    /// Values of layer override the current ones, tables are merged key by key.
+    pub fn add_layer(&mut self, layer: Table, source: ConfigLayer) {
+        merge_table(&mut self.table, layer, source, "", &mut self.provenance);
+    }
+
+    pub fn source(&self, path: &str) -> Option<ConfigLayer> {
+        self.provenance.get(path).copied()
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, toml::de::Error> {
+        Value::Table(self.table.clone()).try_into()
+    }
+}
+
+fn merge_table(
+    dst: &mut Table,
+    src: Table,
+    source: ConfigLayer,
+    prefix: &str,
+    provenance: &mut BTreeMap<String, ConfigLayer>,
+) {
+    for (key, value) in src {
+        let path = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        match (dst.get_mut(&key), value) {
+            (Some(Value::Table(dst_table)), Value::Table(src_table)) => {
+                merge_table(dst_table, src_table, source, &path, provenance);
+            }
+            (_, value) => {
+                // replaces whatever was there, including what was under it
+                let child_prefix = format!("{}.", path);
+                provenance.retain(|p, _| p != &path && !p.starts_with(&child_prefix));
+                let value = match value {
+                    Value::Table(src_table) => {
+                        let mut table = Table::new();
+                        merge_table(&mut table, src_table, source, &path, provenance);
+                        Value::Table(table)
+                    }
+                    value => {
+                        provenance.insert(path, source);
+                        value
+                    }
+                };
+                dst.insert(key, value);
+            }
+        }
+    }
+}
+
+impl fmt::Display for EffectiveConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let toml = toml::to_string_pretty(&self.table).map_err(|_| fmt::Error)?;
+        writeln!(f, "{}", toml.trim_end())?;
+        writeln!(f)?;
+        writeln!(f, "# Set by (fields not listed use their default):")?;
+        for (path, source) in &self.provenance {
+            writeln!(f, "# {} = {}", path, source)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::live_builder::{cli::LiveBuilderConfig, config::Config};
+
+    fn effective_config(file: &str) -> EffectiveConfig {
+        EffectiveConfig::from_file_table(toml::from_str(file).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_override_precedence() {
+        let effective = effective_config(
+            r#"
+            profile = "devnet"
+            watchdog_timeout_sec = 5
+            builders = []
+
+            [experiments]
+            enabled = true
+            "#,
+        );
+        let devnet = ConfigLayer::Profile(ConfigProfile::Devnet);
+        assert_eq!(effective.source("profile"), Some(ConfigLayer::File));
+        assert_eq!(
+            effective.source("watchdog_timeout_sec"),
+            Some(ConfigLayer::File)
+        );
+        assert_eq!(effective.source("allow_known_test_keys"), Some(devnet));
+        assert_eq!(effective.source("chain"), Some(devnet));
+        assert_eq!(
+            effective.source("experiments.enabled"),
+            Some(ConfigLayer::File)
+        );
+        assert_eq!(effective.source("relays"), None);
+
+        let config: Config = effective.deserialize().unwrap();
+        let base = config.base_config();
+        assert_eq!(base.profile, Some(ConfigProfile::Devnet));
+        assert_eq!(base.watchdog_timeout_sec, 5);
+        assert!(base.allow_known_test_keys);
+        assert_eq!(base.chain, "dev");
+        assert!(base.experiments.enabled);
+        // untouched by both layers
+        assert_eq!(
+            base.simulation_threads,
+            Config::default().base_config.simulation_threads
+        );
+
+        let printed = effective.to_string();
+        assert!(printed.contains("# watchdog_timeout_sec = config file"));
+        assert!(printed.contains("# allow_known_test_keys = profile devnet"));
+
+        // no profile, only the file
+        let effective = effective_config("watchdog_timeout_sec = 5");
+        assert_eq!(
+            effective.provenance.into_iter().collect::<Vec<_>>(),
+            vec![("watchdog_timeout_sec".to_string(), ConfigLayer::File)]
+        );
+    }
+
+    #[test]
+    fn test_layer_replaces_nested_values() {
+        let mut effective = EffectiveConfig::default();
+        effective.add_layer(
+            toml::from_str("[a]\nb = 1\nc = 2").unwrap(),
+            ConfigLayer::Profile(ConfigProfile::Mainnet),
+        );
+        effective.add_layer(toml::from_str("a = 3").unwrap(), ConfigLayer::File);
+        assert_eq!(effective.table["a"], Value::Integer(3));
+        assert_eq!(
+            effective.provenance.into_iter().collect::<Vec<_>>(),
+            vec![("a".to_string(), ConfigLayer::File)]
+        );
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        assert!(
+            EffectiveConfig::from_file_table(toml::from_str(r#"profile = "goerli""#).unwrap())
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,90 @@
+//! Cross-field config validation.
+//! serde only checks each field by itself, here we check the constraints between fields (eg: dry_run needs validation urls)
+//! collecting every problem so the user can fix the whole config in one go.
+use std::fmt;
+
+/// Single config problem. path is the TOML path of the offending field (eg: "builders[1].name").
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError {
+    pub path: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConfigValidationReport {
+    errors: Vec<ConfigError>,
+}
+
+impl ConfigValidationReport {
+    pub fn add(&mut self, path: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(ConfigError {
+            path: path.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Adds the error if check fails.
+    pub fn check(&mut self, path: impl Into<String>, check: eyre::Result<()>) {
+        if let Err(err) = check {
+            self.add(path, err.to_string());
+        }
+    }
+
+    pub fn merge(&mut self, other: ConfigValidationReport) {
+        self.errors.extend(other.errors);
+    }
+
+    pub fn errors(&self) -> &[ConfigError] {
+        &self.errors
+    }
+
+    pub fn has_error_at(&self, path: &str) -> bool {
+        self.errors.iter().any(|err| err.path == path)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    pub fn into_result(self) -> Result<(), ConfigValidationReport> {
+        if self.is_empty() {
+            Ok(())
+        } else {
+            Err(self)
+        }
+    }
+}
+
+impl fmt::Display for ConfigValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid config, {} error(s):", self.errors.len())?;
+        for err in &self.errors {
+            write!(f, "\n  {}: {}", err.path, err.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigValidationReport {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_report_lists_all_errors() {
+        let mut report = ConfigValidationReport::default();
+        assert!(report.clone().into_result().is_ok());
+
+        report.add("dry_run_validation_url", "missing");
+        report.check("relays[0].url", Err(eyre::eyre!("bad url")));
+        report.check("chain", Ok(()));
+
+        assert!(report.has_error_at("relays[0].url"));
+        assert!(!report.has_error_at("chain"));
+        assert_eq!(
+            report.into_result().unwrap_err().to_string(),
+            "Invalid config, 2 error(s):\n  dry_run_validation_url: missing\n  relays[0].url: bad url"
+        );
+    }
+}
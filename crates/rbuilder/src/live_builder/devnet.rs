@@ -0,0 +1,395 @@
+//! Genesis for devnets (see the devnet-init command): funds the accounts the config uses (every signer + extra ones)
+//! and deploys the EIP-4788 beacon roots contract needed by pre_block_call so the builder can run against a fresh chain.
+//! Public chains are refused, their genesis can't be changed and funding known keys there is a mistake.
+use crate::{
+    building::{BEACON_ROOTS_ADDRESS, BEACON_ROOTS_CODE},
+    live_builder::base_config::BaseConfig,
+};
+use alloy_primitives::{utils::format_ether, Address, Bytes, U256};
+use reth::primitives::{Genesis, GenesisAccount};
+use std::{fmt::Write, fs, path::Path};
+
+pub const DEFAULT_DEVNET_BALANCE_ETH: u64 = 1_000;
+
+/// mainnet, goerli, sepolia, holesky
+const PUBLIC_CHAIN_IDS: [u64; 4] = [1, 5, 11_155_111, 17_000];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DevnetAccount {
+    /// What the account is used for (eg: coinbase_secret_key, the config key of the signer).
+    pub name: String,
+    pub address: Address,
+    pub balance: U256,
+}
+
+/// Every signer of the config (builder signer, payout signers of the signing identities, builder tag signers)
+/// + extra_accounts, all funded with balance.
+pub fn devnet_accounts(
+    config: &BaseConfig,
+    balance: U256,
+    extra_accounts: &[Address],
+) -> eyre::Result<Vec<DevnetAccount>> {
+    // fail on a bad builder key, signer_addresses skips the keys it can't parse
+    config.coinbase_signer()?;
+    Ok(accounts_to_fund(
+        config.signer_addresses(),
+        balance,
+        extra_accounts,
+    ))
+}
+
+fn accounts_to_fund(
+    signers: Vec<(String, Address)>,
+    balance: U256,
+    extra_accounts: &[Address],
+) -> Vec<DevnetAccount> {
+    let mut accounts: Vec<_> = signers
+        .into_iter()
+        .map(|(name, address)| DevnetAccount {
+            name,
+            address,
+            balance,
+        })
+        .collect();
+    for (i, address) in extra_accounts.iter().enumerate() {
+        accounts.push(DevnetAccount {
+            name: format!("fund[{}]", i),
+            address: *address,
+            balance,
+        });
+    }
+    accounts
+}
+
+/// Sets the balance of the accounts (code and storage of existing accounts are kept) and, if deploy_beacon_roots_contract,
+/// adds the beacon roots contract unless the genesis already has something at BEACON_ROOTS_ADDRESS.
+pub fn patch_genesis(
+    genesis: &mut Genesis,
+    accounts: &[DevnetAccount],
+    deploy_beacon_roots_contract: bool,
+) -> eyre::Result<()> {
+    let chain_id = genesis.config.chain_id;
+    if PUBLIC_CHAIN_IDS.contains(&chain_id) {
+        eyre::bail!(
+            "Refusing to patch the genesis of public chain id {}",
+            chain_id
+        );
+    }
+    for account in accounts {
+        genesis.alloc.entry(account.address).or_default().balance = account.balance;
+    }
+    if deploy_beacon_roots_contract {
+        genesis
+            .alloc
+            .entry(BEACON_ROOTS_ADDRESS)
+            .or_insert_with(|| GenesisAccount {
+                nonce: Some(1),
+                code: Some(Bytes::from_static(&BEACON_ROOTS_CODE)),
+                ..Default::default()
+            });
+    }
+    Ok(())
+}
+
+/// Genesis json at path, the genesis of the config chain if None.
+pub fn load_base_genesis(config: &BaseConfig, path: Option<&Path>) -> eyre::Result<Genesis> {
+    Ok(match path {
+        Some(path) => serde_json::from_str(&fs::read_to_string(path)?)?,
+        None => config.chain_spec()?.genesis().clone(),
+    })
+}
+
+/// Patches genesis and writes it to out.
+/// Returns a human readable summary of the funded accounts and deployed contracts.
+pub fn write_devnet_genesis(
+    mut genesis: Genesis,
+    out: &Path,
+    accounts: &[DevnetAccount],
+    deploy_beacon_roots_contract: bool,
+) -> eyre::Result<String> {
+    patch_genesis(&mut genesis, accounts, deploy_beacon_roots_contract)?;
+    fs::write(out, serde_json::to_string_pretty(&genesis)?)?;
+
+    let mut summary = format!(
+        "Genesis for chain id {} written to {:?}\n",
+        genesis.config.chain_id, out
+    );
+    for account in accounts {
+        writeln!(
+            summary,
+            "{:?} {} ETH ({})",
+            account.address,
+            format_ether(account.balance),
+            account.name
+        )?;
+    }
+    if deploy_beacon_roots_contract {
+        writeln!(
+            summary,
+            "{:?} EIP-4788 beacon roots contract",
+            BEACON_ROOTS_ADDRESS
+        )?;
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::{
+            builders::finalize_block_execution, sim::simulate_all_orders_with_sim_tree,
+            BlockBuildingContext, BlockState, BuiltBlockTrace, PartialBlock,
+        },
+        primitives::{MempoolTx, Order, TransactionSignedEcRecoveredWithBlobs},
+        roothash::RootHashMode,
+        utils::Signer,
+    };
+    use alloy_primitives::B256;
+    use reth::{
+        primitives::{ChainSpec, TransactionKind, TxEip1559},
+        rpc::types::{
+            beacon::events::{PayloadAttributesData, PayloadAttributesEvent},
+            engine::PayloadAttributes,
+        },
+        tasks::pool::BlockingTaskPool,
+    };
+    use reth_node_core::init::init_genesis;
+    use reth_provider::{
+        test_utils::create_test_provider_factory_with_chain_spec, AccountReader, HeaderProvider,
+        StateProvider,
+    };
+    use std::sync::Arc;
+    use tempfile::TempDir;
+    use time::OffsetDateTime;
+
+    const GWEI: u128 = 1_000_000_000;
+
+    fn devnet_genesis(chain_id: u64) -> Genesis {
+        serde_json::from_value(serde_json::json!({
+            "config": {
+                "chainId": chain_id,
+                "homesteadBlock": 0,
+                "eip150Block": 0,
+                "eip155Block": 0,
+                "eip158Block": 0,
+                "byzantiumBlock": 0,
+                "constantinopleBlock": 0,
+                "petersburgBlock": 0,
+                "istanbulBlock": 0,
+                "berlinBlock": 0,
+                "londonBlock": 0,
+                "terminalTotalDifficulty": 0,
+                "terminalTotalDifficultyPassed": true,
+                "shanghaiTime": 0,
+                "cancunTime": 0
+            },
+            "gasLimit": "0x1c9c380",
+            "difficulty": "0x0",
+            "alloc": {}
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_refuse_public_chains() {
+        let accounts = accounts_to_fund(
+            vec![(
+                "coinbase_secret_key".to_string(),
+                Address::repeat_byte(0x11),
+            )],
+            U256::from(1),
+            &[],
+        );
+        let mut genesis = devnet_genesis(1);
+        assert!(patch_genesis(&mut genesis, &accounts, true).is_err());
+        assert!(genesis.alloc.is_empty());
+    }
+
+    #[test]
+    fn test_devnet_accounts_fund_every_signer() -> eyre::Result<()> {
+        let config: BaseConfig = toml::from_str(
+            r#"
+            coinbase_secret_key = "0x1111111111111111111111111111111111111111111111111111111111111111"
+
+            [[signing_identities]]
+            name = "key1"
+            relay_secret_key = "5eae315483f028b5cdd5d1090ff0c7618b18737ea9bf3c35047189db22835c48"
+            payout_secret_key = "0x2222222222222222222222222222222222222222222222222222222222222222"
+            "#,
+        )?;
+        let extra_account = Address::repeat_byte(0x42);
+        let accounts = devnet_accounts(&config, U256::from(1), &[extra_account])?;
+        let funded: Vec<_> = accounts
+            .iter()
+            .map(|account| (account.name.as_str(), account.address))
+            .collect();
+        assert_eq!(
+            funded,
+            vec![
+                (
+                    "coinbase_secret_key",
+                    Signer::try_from_secret(B256::repeat_byte(0x11))?.address
+                ),
+                (
+                    "signing_identities[0].payout_secret_key",
+                    Signer::try_from_secret(B256::repeat_byte(0x22))?.address
+                ),
+                ("fund[0]", extra_account),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_devnet_genesis_boots() -> eyre::Result<()> {
+        let balance = U256::from(DEFAULT_DEVNET_BALANCE_ETH) * U256::from(10u64.pow(18));
+        let builder_signer = Signer::try_from_secret(B256::repeat_byte(0x11))?;
+        let payout_signer = Signer::try_from_secret(B256::repeat_byte(0x22))?;
+        let extra_account = Address::repeat_byte(0x42);
+        let accounts = accounts_to_fund(
+            vec![
+                ("coinbase_secret_key".to_string(), builder_signer.address),
+                (
+                    "signing_identities[0].payout_secret_key".to_string(),
+                    payout_signer.address,
+                ),
+            ],
+            balance,
+            &[extra_account],
+        );
+        assert_eq!(accounts.len(), 3);
+
+        let mut genesis = devnet_genesis(1337);
+        // already funded accounts keep their code
+        genesis.alloc.insert(
+            extra_account,
+            GenesisAccount {
+                code: Some(Bytes::from_static(&[0x00])),
+                ..Default::default()
+            },
+        );
+        let tempdir = TempDir::with_prefix_in("rbuilder-", "/tmp")?;
+        let out = tempdir.path().join("genesis.json");
+        let summary = write_devnet_genesis(genesis, &out, &accounts, true)?;
+        assert!(summary.contains(&format!("{:?}", extra_account)));
+        let genesis: Genesis = serde_json::from_str(&fs::read_to_string(&out)?)?;
+
+        let chain_spec = Arc::new(ChainSpec::from(genesis));
+        let provider_factory = create_test_provider_factory_with_chain_spec(chain_spec.clone());
+        init_genesis(provider_factory.clone())?;
+        {
+            let state = provider_factory.latest()?;
+            for account in &accounts {
+                assert_eq!(
+                    state.basic_account(account.address)?.map(|a| a.balance),
+                    Some(balance)
+                );
+            }
+            assert_eq!(
+                state
+                    .account_code(extra_account)?
+                    .map(|code| code.original_bytes()),
+                Some(Bytes::from_static(&[0x00]))
+            );
+            assert_eq!(
+                state
+                    .account_code(BEACON_ROOTS_ADDRESS)?
+                    .map(|code| code.original_bytes()),
+                Some(Bytes::from_static(&BEACON_ROOTS_CODE))
+            );
+        }
+
+        // block 1 (Cancun) on top of the genesis: a transfer from a funded signer + the payout tx from the builder signer
+        let parent = provider_factory
+            .sealed_header(0)?
+            .ok_or_else(|| eyre::eyre!("genesis header not found"))?;
+        let timestamp = parent.timestamp + 12;
+        let parent_beacon_block_root = B256::repeat_byte(0x44);
+        let ctx = BlockBuildingContext::from_attributes(
+            PayloadAttributesEvent {
+                version: "deneb".to_string(),
+                data: PayloadAttributesData {
+                    proposal_slot: 1,
+                    parent_block_root: Default::default(),
+                    parent_block_number: parent.number,
+                    parent_block_hash: parent.hash(),
+                    proposer_index: 0,
+                    payload_attributes: PayloadAttributes {
+                        timestamp,
+                        prev_randao: Default::default(),
+                        suggested_fee_recipient: Address::repeat_byte(0x33),
+                        withdrawals: Some(vec![]),
+                        parent_beacon_block_root: Some(parent_beacon_block_root),
+                    },
+                },
+            },
+            &parent,
+            builder_signer,
+            chain_spec,
+            Default::default(),
+            None,
+            vec![],
+            None,
+            OffsetDateTime::now_utc(),
+        );
+        let tx = payout_signer.sign_tx(
+            TxEip1559 {
+                chain_id: 1337,
+                nonce: 0,
+                gas_limit: 21_000,
+                max_fee_per_gas: 10 * GWEI,
+                max_priority_fee_per_gas: 2 * GWEI,
+                to: TransactionKind::Call(Address::repeat_byte(0x55)),
+                value: U256::from(1),
+                access_list: Default::default(),
+                input: Default::default(),
+            }
+            .into(),
+        )?;
+        let order = Order::Tx(MempoolTx::new(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+        ));
+        let (sim_orders, _) =
+            simulate_all_orders_with_sim_tree(provider_factory.clone(), &ctx, &[order], false)?;
+        assert_eq!(sim_orders.len(), 1);
+
+        let state_provider = provider_factory.history_by_block_hash(parent.hash())?;
+        let mut state = BlockState::new(&state_provider);
+        let mut partial_block = PartialBlock::new(false, None);
+        partial_block.pre_block_call(&ctx, &mut state)?;
+        // the beacon roots contract stored the timestamp in its ring buffer
+        assert_eq!(
+            state.storage(BEACON_ROOTS_ADDRESS, U256::from(timestamp % 8191))?,
+            U256::from(timestamp)
+        );
+        partial_block.commit_order(&sim_orders[0], &ctx, &mut state)??;
+        let mut trace = BuiltBlockTrace::new();
+        assert!(finalize_block_execution(
+            &ctx,
+            &mut partial_block,
+            &mut state,
+            &mut trace,
+            Some(21_000),
+            &(),
+            U256::ZERO,
+        )?);
+        let block = partial_block
+            .finalize(
+                state,
+                &ctx,
+                provider_factory,
+                RootHashMode::SkipRootHash,
+                BlockingTaskPool::build()?,
+            )?
+            .sealed_block;
+        assert_eq!(block.header.number, 1);
+        assert_eq!(
+            block.header.parent_beacon_block_root,
+            Some(parent_beacon_block_root)
+        );
+        // tx + payout tx
+        assert_eq!(block.body.len(), 2);
+        Ok(())
+    }
+}
@@ -0,0 +1,332 @@
+//! Kill switch for compliance events: while engaged no bid is submitted to the relays (suppressed bids are counted)
+//! but everything else (order input, simulation, builders) keeps running as usual.
+//! It can be engaged from several sources, it's engaged while any of them is:
+//! - File: its presence is polled every poll_interval_ms, the contents (if any) say who/what engaged it.
+//! - Webhook: polled every poll_interval_ms, answers {"engaged": bool, "by": "..."}. On errors the last answer is kept.
+//! - Rpc: /debug/kill_switch/engage?by=... and /debug/kill_switch/disengage on the telemetry server,
+//!   /debug/kill_switch reports the status.
+//!
+//! Once a slot got a bid suppressed the whole slot stays suppressed, disengaging resumes on the next slot.
+//! The sources are read only from the files/urls given at startup, nothing here depends on parsing the config again.
+use crate::telemetry::set_kill_switch_engaged;
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, TimestampSeconds};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use time::OffsetDateTime;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use warp::{Filter, Rejection, Reply};
+
+lazy_static! {
+    /// Process wide kill switch checked by the relay submission jobs.
+    pub static ref KILL_SWITCH: Arc<KillSwitch> = Arc::new(KillSwitch::default());
+}
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct KillSwitchConfig {
+    /// Engaged while this file exists.
+    pub file: Option<PathBuf>,
+    /// Engaged while this url answers engaged.
+    pub webhook_url: Option<String>,
+    pub poll_interval_ms: u64,
+}
+
+impl Default for KillSwitchConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            webhook_url: None,
+            poll_interval_ms: 1000,
+        }
+    }
+}
+
+impl KillSwitchConfig {
+    pub fn poll_interval(&self) -> Duration {
+        Duration::from_millis(self.poll_interval_ms)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KillSwitchSource {
+    File,
+    Webhook,
+    Rpc,
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KillSwitchEngagement {
+    pub source: KillSwitchSource,
+    /// Who/what engaged it.
+    pub by: String,
+    #[serde_as(as = "TimestampSeconds<i64>")]
+    pub since: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KillSwitchStatus {
+    pub engaged: bool,
+    pub engagements: Vec<KillSwitchEngagement>,
+    /// Last slot with suppressed bids.
+    pub killed_slot: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+struct KillSwitchState {
+    engagements: BTreeMap<KillSwitchSource, KillSwitchEngagement>,
+    killed_slot: Option<u64>,
+}
+
+#[derive(Debug, Default)]
+pub struct KillSwitch {
+    state: Mutex<KillSwitchState>,
+}
+
+impl KillSwitch {
+    /// Engaging an already engaged source keeps the original engagement.
+    pub fn engage(&self, source: KillSwitchSource, by: impl Into<String>) {
+        let mut state = self.state.lock().unwrap();
+        if state.engagements.contains_key(&source) {
+            return;
+        }
+        let by = by.into();
+        warn!(
+            ?source,
+            by, "Kill switch engaged, bids will not be submitted"
+        );
+        state.engagements.insert(
+            source,
+            KillSwitchEngagement {
+                source,
+                by,
+                since: OffsetDateTime::now_utc(),
+            },
+        );
+        set_kill_switch_engaged(true);
+    }
+
+    pub fn disengage(&self, source: KillSwitchSource) {
+        let mut state = self.state.lock().unwrap();
+        if state.engagements.remove(&source).is_none() {
+            return;
+        }
+        info!(?source, "Kill switch source disengaged");
+        set_kill_switch_engaged(!state.engagements.is_empty());
+    }
+
+    pub fn is_engaged(&self) -> bool {
+        !self.state.lock().unwrap().engagements.is_empty()
+    }
+
+    pub fn status(&self) -> KillSwitchStatus {
+        let state = self.state.lock().unwrap();
+        KillSwitchStatus {
+            engaged: !state.engagements.is_empty(),
+            engagements: state.engagements.values().cloned().collect(),
+            killed_slot: state.killed_slot,
+        }
+    }
+
+    /// true if a bid for slot must not be submitted.
+    /// Engaged now or engaged at any point we were asked during this slot.
+    pub fn suppress_submission(&self, slot: u64) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if !state.engagements.is_empty() {
+            state.killed_slot = Some(slot);
+            return true;
+        }
+        state.killed_slot == Some(slot)
+    }
+}
+
+/// Engages KillSwitchSource::File while path exists.
+pub async fn poll_file(kill_switch: &KillSwitch, path: &Path) {
+    match tokio::fs::metadata(path).await {
+        Ok(_) => {
+            let by = tokio::fs::read_to_string(path)
+                .await
+                .ok()
+                .map(|contents| contents.trim().to_string())
+                .filter(|contents| !contents.is_empty())
+                .unwrap_or_else(|| format!("file {}", path.display()));
+            kill_switch.engage(KillSwitchSource::File, by);
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            kill_switch.disengage(KillSwitchSource::File);
+        }
+        Err(err) => {
+            // can't tell, better safe
+            warn!(?err, path = ?path, "Failed to check kill switch file");
+            kill_switch.engage(KillSwitchSource::File, format!("file {}", path.display()));
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct WebhookResponse {
+    engaged: bool,
+    by: Option<String>,
+}
+
+/// Engages/disengages KillSwitchSource::Webhook as url answers, errors leave it as it was.
+pub async fn poll_webhook(kill_switch: &KillSwitch, client: &reqwest::Client, url: &str) {
+    let response = match client.get(url).send().await {
+        Ok(response) => response.error_for_status(),
+        Err(err) => Err(err),
+    };
+    match response {
+        Ok(response) => match response.json::<WebhookResponse>().await {
+            Ok(WebhookResponse { engaged: true, by }) => kill_switch.engage(
+                KillSwitchSource::Webhook,
+                by.unwrap_or_else(|| format!("webhook {}", url)),
+            ),
+            Ok(WebhookResponse { engaged: false, .. }) => {
+                kill_switch.disengage(KillSwitchSource::Webhook)
+            }
+            Err(err) => warn!(?err, url, "Invalid kill switch webhook response"),
+        },
+        Err(err) => warn!(?err, url, "Failed to poll kill switch webhook"),
+    }
+}
+
+pub fn spawn_kill_switch_watcher(
+    kill_switch: Arc<KillSwitch>,
+    config: KillSwitchConfig,
+    cancel: CancellationToken,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = reqwest::Client::builder()
+            .timeout(config.poll_interval())
+            .build()
+            .unwrap_or_default();
+        loop {
+            if let Some(path) = &config.file {
+                poll_file(&kill_switch, path).await;
+            }
+            if let Some(url) = &config.webhook_url {
+                poll_webhook(&kill_switch, &client, url).await;
+            }
+            tokio::select! {
+                _ = cancel.cancelled() => break,
+                _ = tokio::time::sleep(config.poll_interval()) => {}
+            }
+        }
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct EngageQuery {
+    by: Option<String>,
+}
+
+/// /debug/kill_switch, /debug/kill_switch/engage?by=... and /debug/kill_switch/disengage
+pub fn kill_switch_routes(
+    kill_switch: Arc<KillSwitch>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let with_kill_switch = warp::any().map(move || kill_switch.clone());
+    let status_route = warp::path!("debug" / "kill_switch")
+        .and(with_kill_switch.clone())
+        .map(|kill_switch: Arc<KillSwitch>| warp::reply::json(&kill_switch.status()));
+    let engage_route = warp::path!("debug" / "kill_switch" / "engage")
+        .and(warp::query::<EngageQuery>())
+        .and(with_kill_switch.clone())
+        .map(|query: EngageQuery, kill_switch: Arc<KillSwitch>| {
+            kill_switch.engage(
+                KillSwitchSource::Rpc,
+                query.by.unwrap_or_else(|| "rpc".to_string()),
+            );
+            warp::reply::json(&kill_switch.status())
+        });
+    let disengage_route = warp::path!("debug" / "kill_switch" / "disengage")
+        .and(with_kill_switch)
+        .map(|kill_switch: Arc<KillSwitch>| {
+            kill_switch.disengage(KillSwitchSource::Rpc);
+            warp::reply::json(&kill_switch.status())
+        });
+    status_route.or(engage_route).or(disengage_route)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Bids submitted for slot out of attempts.
+    fn submissions(kill_switch: &KillSwitch, slot: u64, attempts: usize) -> usize {
+        (0..attempts)
+            .filter(|_| !kill_switch.suppress_submission(slot))
+            .count()
+    }
+
+    #[tokio::test]
+    async fn test_file_kill_switch() {
+        let kill_switch = KillSwitch::default();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kill");
+
+        poll_file(&kill_switch, &path).await;
+        assert_eq!(submissions(&kill_switch, 1, 3), 3);
+
+        std::fs::write(&path, "compliance ticket 42\n").unwrap();
+        poll_file(&kill_switch, &path).await;
+        assert_eq!(submissions(&kill_switch, 2, 3), 0);
+        let status = kill_switch.status();
+        assert!(status.engaged);
+        assert_eq!(status.engagements[0].source, KillSwitchSource::File);
+        assert_eq!(status.engagements[0].by, "compliance ticket 42");
+        assert_eq!(status.killed_slot, Some(2));
+
+        // still suppressed until the slot ends
+        std::fs::remove_file(&path).unwrap();
+        poll_file(&kill_switch, &path).await;
+        assert!(!kill_switch.is_engaged());
+        assert_eq!(submissions(&kill_switch, 2, 3), 0);
+        assert_eq!(submissions(&kill_switch, 3, 3), 3);
+    }
+
+    #[tokio::test]
+    async fn test_rpc_kill_switch() {
+        let kill_switch = Arc::new(KillSwitch::default());
+        let routes = kill_switch_routes(kill_switch.clone());
+
+        let response = warp::test::request()
+            .path("/debug/kill_switch/engage?by=alice")
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(submissions(&kill_switch, 1, 3), 0);
+
+        let status: serde_json::Value = serde_json::from_slice(
+            warp::test::request()
+                .path("/debug/kill_switch")
+                .reply(&routes)
+                .await
+                .body(),
+        )
+        .unwrap();
+        assert_eq!(status["engaged"], true);
+        assert_eq!(status["engagements"][0]["source"], "rpc");
+        assert_eq!(status["engagements"][0]["by"], "alice");
+
+        // other sources keep it engaged
+        kill_switch.engage(KillSwitchSource::Webhook, "webhook");
+        warp::test::request()
+            .path("/debug/kill_switch/disengage")
+            .reply(&routes)
+            .await;
+        assert_eq!(submissions(&kill_switch, 2, 3), 0);
+        kill_switch.disengage(KillSwitchSource::Webhook);
+        assert_eq!(submissions(&kill_switch, 2, 3), 0);
+        assert_eq!(submissions(&kill_switch, 3, 3), 3);
+        assert!(!kill_switch.status().engaged);
+    }
+}
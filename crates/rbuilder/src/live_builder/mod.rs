@@ -1,8 +1,13 @@
 pub mod base_config;
 pub mod bidding;
 pub mod building;
+pub mod canary;
 pub mod cli;
 pub mod config;
+pub mod config_profile;
+pub mod config_validation;
+pub mod devnet;
+pub mod kill_switch;
 pub mod order_input;
 pub mod payload_events;
 pub mod simulation;
@@ -12,16 +17,33 @@ use crate::{
     beacon_api_client::Client,
     building::{
         builders::{BlockBuildingAlgorithm, BuilderSinkFactory},
-        BlockBuildingContext,
+        pending_accounts::PendingCandidates,
+        token_valuation::TokenValuation,
+        BlockBuildingContext, BuilderIdentity,
     },
     live_builder::{
-        order_input::{start_orderpool_jobs, OrderInputConfig},
-        simulation::OrderSimulationPool,
+        building::{
+            builder_tag_experiment::BuilderTagSchedule,
+            relay_identities::{RelayIdentities, RELAY_IDENTITIES},
+        },
+        canary::{spawn_canary, CanaryConfig},
+        kill_switch::{spawn_kill_switch_watcher, KillSwitchConfig, KILL_SWITCH},
+        order_input::{
+            estimate_gas::GasEstimator, pending_account::PendingAccountService,
+            start_orderpool_jobs, OrderInputConfig, StaticLimits,
+        },
+        simulation::{
+            overload_filter::OverloadFilterConfig, priority_lane::SimPriorityLaneConfig,
+            OrderSimulationPool,
+        },
         watchdog::spawn_watchdog_thread,
     },
     primitives::mev_boost::MevBoostRelay,
     telemetry::inc_active_slots,
-    utils::{error_storage::spawn_error_storage_writer, ProviderFactoryReopener, Signer},
+    utils::{
+        error_storage::spawn_error_storage_writer,
+        rate_limited_log::spawn_rate_limited_log_flusher, ProviderFactoryReopener, Signer,
+    },
 };
 use ahash::HashSet;
 use alloy_primitives::{Address, B256};
@@ -29,7 +51,9 @@ use bidding::BiddingService;
 use building::BlockBuildingPool;
 use eyre::Context;
 use jsonrpsee::RpcModule;
-use payload_events::MevBoostSlotDataGenerator;
+use payload_events::{
+    check_parent_age, skip_slot, MevBoostSlotData, MevBoostSlotDataGenerator, SlotSkipReason,
+};
 use reth::{
     primitives::{ChainSpec, Header},
     providers::{HeaderProvider, ProviderFactory},
@@ -37,7 +61,7 @@ use reth::{
 use reth_db::database::Database;
 use std::{cmp::min, path::PathBuf, sync::Arc, time::Duration};
 use time::OffsetDateTime;
-use tokio::task::spawn_blocking;
+use tokio::{sync::mpsc, task::spawn_blocking};
 use tokio_util::sync::CancellationToken;
 use tracing::{debug, error, info, warn};
 
@@ -58,8 +82,12 @@ pub struct LiveBuilder<DB, BuilderSinkFactoryType: BuilderSinkFactory> {
     pub cls: Vec<Client>,
     pub relays: Vec<MevBoostRelay>,
     pub watchdog_timeout: Duration,
+    /// Slots with older parents are skipped (see [`payload_events::check_parent_age`]).
+    pub max_parent_age: Option<Duration>,
     pub error_storage_path: PathBuf,
     pub simulation_threads: usize,
+    pub simulation_overload_filter: Option<OverloadFilterConfig>,
+    pub simulation_priority_lane: Option<SimPriorityLaneConfig>,
     pub order_input_config: OrderInputConfig,
 
     pub chain_chain_spec: Arc<ChainSpec>,
@@ -68,6 +96,25 @@ pub struct LiveBuilder<DB, BuilderSinkFactoryType: BuilderSinkFactory> {
     pub coinbase_signer: Signer,
     pub extra_data: Vec<u8>,
     pub blocklist: HashSet<Address>,
+    pub builder_identity: BuilderIdentity,
+    pub token_valuation: TokenValuation,
+    /// If set extra_data comes from the tag of each slot instead of extra_data.
+    pub builder_tag_schedule: Option<BuilderTagSchedule>,
+    /// Same ones the relay submission uses, registered for the telemetry server (see [`RELAY_IDENTITIES`]).
+    /// Slots where all the relays use an identity with a payout signer get it as coinbase.
+    pub relay_identities: RelayIdentities,
+    /// If set serves rbuilder_estimateGas on the order input server using the template of the current slot.
+    pub gas_estimator: Option<GasEstimator<DB>>,
+    /// If set serves rbuilder_getPendingAccount on the order input server using the best candidate of the current slot.
+    pub pending_accounts: Option<PendingAccountService<DB>>,
+    /// Builders offer their blocks here, pending_accounts should read the same ones.
+    pub pending_candidates: Arc<PendingCandidates>,
+    /// If set periodically rebuilds a landed block and compares it with the chain.
+    pub canary: Option<CanaryConfig>,
+    /// If set the kill switch file/webhook are polled (the rpc works anyway).
+    pub kill_switch: Option<KillSwitchConfig>,
+    /// If set overrides the sbundle mergeable signers the builders were created with on every build.
+    pub sbundle_mergeabe_signers: Option<Vec<Address>>,
 
     pub global_cancellation: CancellationToken,
 
@@ -112,7 +159,9 @@ where
             .await
             .with_context(|| "Error spawning error storage writer")?;
 
-        let mut inner_jobs_handles = Vec::new();
+        let mut inner_jobs_handles = vec![spawn_rate_limited_log_flusher(
+            self.global_cancellation.clone(),
+        )];
 
         let mut payload_events_channel = {
             let payload_event = MevBoostSlotDataGenerator::new(
@@ -126,11 +175,31 @@ where
             chan
         };
 
+        let order_simulation_pool = {
+            OrderSimulationPool::new(
+                self.provider_factory.clone(),
+                self.simulation_threads,
+                self.global_cancellation.clone(),
+            )
+            .with_overload_filter(self.simulation_overload_filter)
+            .with_priority_lane(self.simulation_priority_lane)
+        };
+
+        let static_limits = Arc::new(StaticLimits::new(self.chain_chain_spec.clone()));
         let orderpool_subscriber = {
+            let mut extra_rpc = self.extra_rpc;
+            if let Some(gas_estimator) = &self.gas_estimator {
+                extra_rpc.merge(gas_estimator.rpc_module()?)?;
+            }
+            if let Some(pending_accounts) = &self.pending_accounts {
+                extra_rpc.merge(pending_accounts.rpc_module()?)?;
+            }
             let (handle, sub) = start_orderpool_jobs(
-                self.order_input_config,
+                self.order_input_config
+                    .with_fast_sim_lane(order_simulation_pool.fast_sim_lane())
+                    .with_static_limits(static_limits.clone()),
                 self.provider_factory.clone(),
-                self.extra_rpc,
+                extra_rpc,
                 self.global_cancellation.clone(),
             )
             .await?;
@@ -138,13 +207,22 @@ where
             sub
         };
 
-        let order_simulation_pool = {
-            OrderSimulationPool::new(
+        if let Some(kill_switch) = self.kill_switch {
+            inner_jobs_handles.push(spawn_kill_switch_watcher(
+                KILL_SWITCH.clone(),
+                kill_switch,
+                self.global_cancellation.clone(),
+            ));
+        }
+
+        if let Some(canary) = self.canary {
+            inner_jobs_handles.push(spawn_canary(
                 self.provider_factory.clone(),
-                self.simulation_threads,
+                self.chain_chain_spec.clone(),
+                canary,
                 self.global_cancellation.clone(),
-            )
-        };
+            )?);
+        }
 
         let mut builder_pool = BlockBuildingPool::new(
             self.provider_factory.clone(),
@@ -153,97 +231,64 @@ where
             self.bidding_service,
             orderpool_subscriber,
             order_simulation_pool,
+            self.sbundle_mergeabe_signers,
+            self.pending_candidates,
         );
 
         let watchdog_sender = spawn_watchdog_thread(self.watchdog_timeout)?;
 
-        while let Some(payload) = payload_events_channel.recv().await {
-            if self.blocklist.contains(&payload.fee_recipient()) {
-                warn!(
-                    slot = payload.slot(),
-                    "Fee recipient is in blocklist: {:?}",
-                    payload.fee_recipient()
-                );
-                continue;
-            }
-            // see if we can get parent header in a reasonable time
-
-            let time_to_slot = payload.timestamp() - OffsetDateTime::now_utc();
-            debug!(
-                slot = payload.slot(),
-                block = payload.block(),
-                ?time_to_slot,
-                "Received payload, time till slot timestamp",
-            );
+        *RELAY_IDENTITIES.lock().unwrap() = Some(self.relay_identities.clone());
 
-            let time_until_slot_end = time_to_slot + SLOT_PROPOSAL_DURATION;
-            if time_until_slot_end.is_negative() {
-                warn!(
-                    slot = payload.slot(),
-                    "Slot already ended, skipping block building"
-                );
-                continue;
-            };
+        let slot_gate = SlotGate {
+            provider_factory: self.provider_factory.clone(),
+            chain_spec: self.chain_chain_spec,
+            coinbase_signer: self.coinbase_signer,
+            extra_data: self.extra_data,
+            blocklist: self.blocklist,
+            builder_tag_schedule: self.builder_tag_schedule,
+            relays: self.relays,
+            relay_identities: self.relay_identities,
+            max_parent_age: self.max_parent_age,
+        };
 
-            let parent_header = {
-                // @Nicer
-                let parent_block = payload.parent_block_hash();
-                let timestamp = payload.timestamp();
-                let provider_factory = self.provider_factory.provider_factory_unchecked();
-                match wait_for_block_header(parent_block, timestamp, &provider_factory).await {
-                    Ok(header) => header,
-                    Err(err) => {
-                        warn!("Failed to get parent header for new slot: {:?}", err);
-                        continue;
-                    }
+        loop {
+            let GatedSlot {
+                payload,
+                mut block_ctx,
+                time_until_slot_end,
+            } = match slot_gate.next_slot(&mut payload_events_channel).await {
+                Ok(Some(slot)) => slot,
+                Ok(None) => break,
+                Err(err) => {
+                    error!(?err, "Failed to check historical block hashes");
+                    // This error is unrecoverable so we restart.
+                    break;
                 }
             };
 
-            {
-                let provider_factory = self.provider_factory.clone();
-                let block = payload.block();
-                match spawn_blocking(move || {
-                    provider_factory.check_consistency_and_reopen_if_needed(block)
-                })
-                .await
-                {
-                    Ok(Ok(_)) => {}
-                    Ok(Err(err)) => {
-                        error!(?err, "Failed to check historical block hashes");
-                        // This error is unrecoverable so we restart.
-                        break;
-                    }
-                    Err(err) => {
-                        error!(?err, "Failed to join historical block hashes task");
-                        continue;
-                    }
-                }
-            }
-
-            debug!(
-                slot = payload.slot(),
-                block = payload.block(),
-                "Got header for slot"
-            );
-
             inc_active_slots();
 
-            let block_ctx = BlockBuildingContext::from_attributes(
-                payload.payload_attributes_event.clone(),
-                &parent_header,
-                self.coinbase_signer.clone(),
-                self.chain_chain_spec.clone(),
-                self.blocklist.clone(),
-                Some(payload.suggested_gas_limit),
-                self.extra_data.clone(),
-                None,
-            );
+            block_ctx.modify_builder_identity(self.builder_identity.clone());
+            block_ctx.modify_token_valuation(self.token_valuation.clone());
+            static_limits.set_slot_timestamp(block_ctx.attributes.timestamp);
+            if let Some(gas_estimator) = &self.gas_estimator {
+                gas_estimator.set_template(
+                    self.provider_factory.provider_factory_unchecked(),
+                    block_ctx.clone(),
+                );
+            }
+            if let Some(pending_accounts) = &self.pending_accounts {
+                pending_accounts.set_template(
+                    self.provider_factory.provider_factory_unchecked(),
+                    &block_ctx,
+                );
+            }
 
             builder_pool.start_block_building(
                 payload,
                 block_ctx,
                 self.global_cancellation.clone(),
-                time_until_slot_end.try_into().unwrap_or_default(),
+                time_until_slot_end,
             );
 
             watchdog_sender.try_send(()).unwrap_or_default();
@@ -261,6 +306,158 @@ where
     }
 }
 
+/// Health gate of [`LiveBuilder::run`]: checks every slot we get from the CL and builds its context.
+/// Slots failing a check are skipped (counted by [`skip_slot`]), the others are returned in order.
+#[derive(Debug)]
+struct SlotGate<DB> {
+    provider_factory: ProviderFactoryReopener<DB>,
+    chain_spec: Arc<ChainSpec>,
+    coinbase_signer: Signer,
+    extra_data: Vec<u8>,
+    blocklist: HashSet<Address>,
+    builder_tag_schedule: Option<BuilderTagSchedule>,
+    relays: Vec<MevBoostRelay>,
+    relay_identities: RelayIdentities,
+    /// See [`check_parent_age`].
+    max_parent_age: Option<Duration>,
+}
+
+/// Slot that passed the [`SlotGate`].
+#[derive(Debug)]
+struct GatedSlot {
+    payload: MevBoostSlotData,
+    block_ctx: BlockBuildingContext,
+    time_until_slot_end: Duration,
+}
+
+impl<DB: Database + Clone + 'static> SlotGate<DB> {
+    /// Waits for the next slot passing all the checks.
+    /// Ok(None) if the payload channel was closed.
+    /// Err if the provider factory is inconsistent even after reopening (unrecoverable).
+    async fn next_slot(
+        &self,
+        payloads: &mut mpsc::UnboundedReceiver<MevBoostSlotData>,
+    ) -> eyre::Result<Option<GatedSlot>> {
+        while let Some(payload) = payloads.recv().await {
+            if let Some(slot) = self.check_slot(payload).await? {
+                return Ok(Some(slot));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Ok(None) if the slot was skipped.
+    async fn check_slot(&self, payload: MevBoostSlotData) -> eyre::Result<Option<GatedSlot>> {
+        let received_at = OffsetDateTime::now_utc();
+        if self.blocklist.contains(&payload.fee_recipient()) {
+            warn!(
+                slot = payload.slot(),
+                "Fee recipient is in blocklist: {:?}",
+                payload.fee_recipient()
+            );
+            skip_slot(SlotSkipReason::FeeRecipientBlocklisted);
+            return Ok(None);
+        }
+        // see if we can get parent header in a reasonable time
+
+        let time_to_slot = payload.timestamp() - OffsetDateTime::now_utc();
+        debug!(
+            slot = payload.slot(),
+            block = payload.block(),
+            ?time_to_slot,
+            "Received payload, time till slot timestamp",
+        );
+
+        let time_until_slot_end = time_to_slot + SLOT_PROPOSAL_DURATION;
+        if time_until_slot_end.is_negative() {
+            warn!(
+                slot = payload.slot(),
+                "Slot already ended, skipping block building"
+            );
+            skip_slot(SlotSkipReason::SlotEnded);
+            return Ok(None);
+        };
+
+        let parent_header = {
+            // @Nicer
+            let parent_block = payload.parent_block_hash();
+            let timestamp = payload.timestamp();
+            let provider_factory = self.provider_factory.provider_factory_unchecked();
+            match wait_for_block_header(parent_block, timestamp, &provider_factory).await {
+                Ok(header) => header,
+                Err(err) => {
+                    warn!("Failed to get parent header for new slot: {:?}", err);
+                    skip_slot(SlotSkipReason::ParentHeaderNotFound);
+                    return Ok(None);
+                }
+            }
+        };
+
+        debug!(
+            slot = payload.slot(),
+            block = payload.block(),
+            "Got header for slot"
+        );
+
+        let (extra_data, coinbase_signer) = match &self.builder_tag_schedule {
+            Some(schedule) => {
+                let tag = schedule.tag_for_slot(payload.slot());
+                (tag.extra_data.clone(), tag.coinbase_signer.clone())
+            }
+            None => (
+                self.extra_data.clone(),
+                self.relay_identities
+                    .payout_signer(&self.relays)
+                    .unwrap_or_else(|| self.coinbase_signer.clone()),
+            ),
+        };
+        let block_ctx = BlockBuildingContext::from_attributes(
+            payload.payload_attributes_event.clone(),
+            &parent_header,
+            coinbase_signer,
+            self.chain_spec.clone(),
+            self.blocklist.clone(),
+            Some(payload.suggested_gas_limit),
+            extra_data,
+            None,
+            received_at,
+        );
+        if let Err(reason) = check_parent_age(&block_ctx, self.max_parent_age) {
+            warn!(
+                slot = payload.slot(),
+                block = payload.block(),
+                parent_age = ?block_ctx.parent_age(),
+                ?reason,
+                "Parent too old, skipping block building"
+            );
+            return Ok(None);
+        }
+
+        {
+            let provider_factory = self.provider_factory.clone();
+            let block = payload.block();
+            match spawn_blocking(move || {
+                provider_factory.check_consistency_and_reopen_if_needed(block)
+            })
+            .await
+            {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => return Err(err),
+                Err(err) => {
+                    error!(?err, "Failed to join historical block hashes task");
+                    return Ok(None);
+                }
+            }
+        }
+
+        Ok(Some(GatedSlot {
+            payload,
+            block_ctx,
+            time_until_slot_end: time_until_slot_end.try_into().unwrap_or_default(),
+        }))
+    }
+}
+
 /// May fail if we wait too much (see [BLOCK_HEADER_DEAD_LINE_DELTA])
 async fn wait_for_block_header<DB: Database>(
     block: B256,
@@ -284,3 +481,125 @@ async fn wait_for_block_header<DB: Database>(
     }
     Err(eyre::eyre!("Block header not found"))
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState},
+        live_builder::payload_events::relay_epoch_cache::SlotData,
+        telemetry::SKIPPED_SLOTS,
+    };
+    use reth::rpc::types::{
+        beacon::events::{PayloadAttributesData, PayloadAttributesEvent},
+        engine::PayloadAttributes,
+    };
+
+    const MAX_PARENT_AGE: Duration = Duration::from_secs(36);
+    const GAS_LIMIT: u64 = 30_000_000;
+
+    /// Slot building on top of the current parent of test_chain.
+    fn slot_payload(test_chain: &TestChainState, slot: u64) -> eyre::Result<MevBoostSlotData> {
+        let fee_recipient = test_chain.named_address(NamedAddr::FeeRecipient)?;
+        let ctx = test_chain.block_building_context();
+        Ok(MevBoostSlotData {
+            payload_attributes_event: PayloadAttributesEvent {
+                version: "deneb".to_string(),
+                data: PayloadAttributesData {
+                    proposal_slot: slot,
+                    parent_block_root: Default::default(),
+                    parent_block_number: ctx.block_env.number.to::<u64>() - 1,
+                    parent_block_hash: test_chain.parent_hash(),
+                    proposer_index: 0,
+                    payload_attributes: PayloadAttributes {
+                        timestamp: ctx.attributes.timestamp,
+                        prev_randao: Default::default(),
+                        suggested_fee_recipient: fee_recipient,
+                        withdrawals: Some(vec![]),
+                        parent_beacon_block_root: Some(B256::ZERO),
+                    },
+                },
+            },
+            suggested_gas_limit: GAS_LIMIT,
+            relays: vec![],
+            slot_data: SlotData {
+                fee_recipient,
+                gas_limit: GAS_LIMIT,
+                pubkey: Default::default(),
+            },
+        })
+    }
+
+    #[tokio::test]
+    async fn test_slot_gate_skips_old_parent() -> eyre::Result<()> {
+        let skipped = || {
+            SKIPPED_SLOTS
+                .with_label_values(&[SlotSkipReason::ParentTooOld.as_str()])
+                .get()
+        };
+        let now = OffsetDateTime::now_utc().unix_timestamp() as u64;
+        // first slot builds on a parent 4 slots older than it
+        let mut test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .timestamp(now + 8)
+                .parent_timestamp(now - 4 * 12),
+        )?;
+        let old_parent = slot_payload(&test_chain, 1)?;
+        // second slot builds on the block of the first one (in the future, parent age 0)
+        test_chain.advance_block()?;
+        let fresh_parent = slot_payload(&test_chain, 2)?;
+
+        let gate = SlotGate {
+            provider_factory: ProviderFactoryReopener::new_from_existing_for_testing(
+                test_chain.provider_factory().clone(),
+            )?,
+            chain_spec: test_chain.provider_factory().chain_spec(),
+            coinbase_signer: test_chain
+                .block_building_context()
+                .builder_signer
+                .clone()
+                .expect("builder signer"),
+            extra_data: vec![],
+            blocklist: Default::default(),
+            builder_tag_schedule: None,
+            relays: vec![],
+            relay_identities: Default::default(),
+            max_parent_age: Some(MAX_PARENT_AGE),
+        };
+        let send_slots = || -> eyre::Result<_> {
+            let (sender, payloads) = mpsc::unbounded_channel();
+            sender.send(old_parent.clone())?;
+            sender.send(fresh_parent.clone())?;
+            Ok(payloads)
+        };
+
+        let mut payloads = send_slots()?;
+        let before = skipped();
+        let slot = gate
+            .next_slot(&mut payloads)
+            .await?
+            .expect("fresh slot passes the gate");
+        assert_eq!(slot.payload, fresh_parent);
+        assert_eq!(slot.block_ctx.attributes.parent, test_chain.parent_hash());
+        assert_eq!(slot.block_ctx.parent_age(), Some(Duration::ZERO));
+        // metrics are global, other tests could skip slots too
+        assert!(skipped() > before);
+        // old slot was dropped, not delayed
+        assert!(gate.next_slot(&mut payloads).await?.is_none());
+
+        // check disabled: both slots are built
+        let gate = SlotGate {
+            max_parent_age: None,
+            ..gate
+        };
+        let mut payloads = send_slots()?;
+        let slot = gate.next_slot(&mut payloads).await?.expect("old slot");
+        assert_eq!(slot.payload, old_parent);
+        assert!(slot.block_ctx.parent_age().expect("live slot") > MAX_PARENT_AGE);
+        let slot = gate.next_slot(&mut payloads).await?.expect("fresh slot");
+        assert_eq!(slot.payload, fresh_parent);
+        assert!(gate.next_slot(&mut payloads).await?.is_none());
+        Ok(())
+    }
+}
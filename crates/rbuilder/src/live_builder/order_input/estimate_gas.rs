@@ -0,0 +1,447 @@
+//! rbuilder_estimateGas: eth_estimateGas like answers for co-located searchers computed on the block template of the
+//! current slot (parent state + pre block call, no orders) instead of the public node.
+//! The template keeps the CachedReads of the slot estimations so they get warm. Each running estimation takes one
+//! for itself and gives it back when done, so concurrent estimations don't overwrite each other's reads.
+//! Estimations run on their own thread pool over a read only snapshot of the parent and can be rate limited so they
+//! can't steal cpu/db time from the builders.
+use crate::building::{BlockBuildingContext, BlockState, PartialBlock};
+use alloy_primitives::{Address, Bytes, U256};
+use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use jsonrpsee::{
+    types::{ErrorObject, ErrorObjectOwned},
+    RpcModule,
+};
+use reth::{providers::ProviderFactory, tasks::pool::BlockingTaskPool};
+use reth_db::database::Database;
+use reth_interfaces::provider::ProviderError;
+use reth_payload_builder::database::CachedReads;
+use revm::primitives::{EVMError, Env, ExecutionResult, InvalidTransaction, TransactTo, TxEnv};
+use serde::{Deserialize, Serialize};
+use std::{
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+};
+use thiserror::Error;
+use tracing::trace;
+
+/// Binary search stops when the gap between the last failed and succeeded gas limits is below this (same as reth).
+pub const ESTIMATE_GAS_TOLERANCE_BPS: u64 = 150;
+
+const RPC_ERROR_CODE: i32 = -32000;
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct EstimateGasConfig {
+    /// Threads used for the estimations, not shared with simulation/building.
+    pub threads: usize,
+    /// None -> no limit.
+    pub max_requests_per_sec: Option<u32>,
+}
+
+/// Call message, like eth_estimateGas. gas is the max gas limit to try (defaults to the block gas limit).
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct EstimateGasRequest {
+    pub from: Address,
+    pub to: Option<Address>,
+    #[serde(default)]
+    pub value: U256,
+    #[serde(default)]
+    pub input: Bytes,
+    pub gas: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateGasResult {
+    /// Gas used executing with the max gas limit.
+    pub gas_used: u64,
+    /// Lowest gas limit found that succeeds (within ESTIMATE_GAS_TOLERANCE_BPS), gas_used if the call failed.
+    pub gas_estimate: u64,
+    pub success: bool,
+    pub revert_data: Option<Bytes>,
+}
+
+#[derive(Error, Debug)]
+pub enum EstimateGasError {
+    #[error("No block template for the current slot")]
+    NoTemplate,
+    #[error("Too many requests")]
+    RateLimited,
+    #[error("Invalid call: {0:?}")]
+    InvalidCall(InvalidTransaction),
+    #[error("Reth error: {0}")]
+    Reth(#[from] ProviderError),
+    #[error("Pre block call error: {0}")]
+    PreBlockCall(eyre::Report),
+    #[error("Evm error: {0}")]
+    Evm(String),
+    #[error("Estimation task failed")]
+    TaskFailed,
+}
+
+#[derive(Debug)]
+struct SlotTemplate<DB> {
+    provider_factory: ProviderFactory<DB>,
+    ctx: BlockBuildingContext,
+    /// Idle caches, at most one per estimation that ran concurrently.
+    cached_reads: Mutex<Vec<CachedReads>>,
+}
+
+/// Cheap to clone, all the clones share the template.
+#[derive(Debug)]
+pub struct GasEstimator<DB> {
+    template: Arc<Mutex<Option<Arc<SlotTemplate<DB>>>>>,
+    pool: BlockingTaskPool,
+    rate_limiter: Option<Arc<DefaultDirectRateLimiter>>,
+}
+
+impl<DB> Clone for GasEstimator<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            template: self.template.clone(),
+            pool: self.pool.clone(),
+            rate_limiter: self.rate_limiter.clone(),
+        }
+    }
+}
+
+impl<DB: Database + Clone + 'static> GasEstimator<DB> {
+    pub fn new(pool: BlockingTaskPool, max_requests_per_sec: Option<u32>) -> Self {
+        let rate_limiter = max_requests_per_sec.map(|max_requests_per_sec| {
+            Arc::new(RateLimiter::direct(Quota::per_second(
+                NonZeroU32::new(max_requests_per_sec).unwrap_or(NonZeroU32::MIN),
+            )))
+        });
+        Self {
+            template: Default::default(),
+            pool,
+            rate_limiter,
+        }
+    }
+
+    pub fn from_config(config: &EstimateGasConfig) -> eyre::Result<Self> {
+        let pool = BlockingTaskPool::new(
+            BlockingTaskPool::builder()
+                .num_threads(config.threads)
+                .build()?,
+        );
+        Ok(Self::new(pool, config.max_requests_per_sec))
+    }
+
+    /// Called on every new slot, the warm reads of the previous slot are dropped.
+    pub fn set_template(&self, provider_factory: ProviderFactory<DB>, ctx: BlockBuildingContext) {
+        *self.template.lock().unwrap() = Some(Arc::new(SlotTemplate {
+            provider_factory,
+            ctx,
+            cached_reads: Default::default(),
+        }));
+    }
+
+    /// Rate limited and executed on the estimations pool.
+    pub async fn estimate_gas(
+        &self,
+        request: EstimateGasRequest,
+    ) -> Result<EstimateGasResult, EstimateGasError> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            if rate_limiter.check().is_err() {
+                return Err(EstimateGasError::RateLimited);
+            }
+        }
+        let template = self
+            .template
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(EstimateGasError::NoTemplate)?;
+        self.pool
+            .spawn(move || estimate_on_template(&template, &request))
+            .await
+            .map_err(|_| EstimateGasError::TaskFailed)?
+    }
+
+    pub fn rpc_module(&self) -> eyre::Result<RpcModule<()>> {
+        let mut module = RpcModule::new(());
+        let estimator = self.clone();
+        module.register_async_method("rbuilder_estimateGas", move |params, _| {
+            let estimator = estimator.clone();
+            async move {
+                let request: EstimateGasRequest = params.one()?;
+                estimator
+                    .estimate_gas(request)
+                    .await
+                    .map_err(|err| -> ErrorObjectOwned {
+                        ErrorObject::owned(RPC_ERROR_CODE, err.to_string(), None::<()>)
+                    })
+            }
+        })?;
+        Ok(module)
+    }
+}
+
+fn estimate_on_template<DB: Database>(
+    template: &SlotTemplate<DB>,
+    request: &EstimateGasRequest,
+) -> Result<EstimateGasResult, EstimateGasError> {
+    let ctx = &template.ctx;
+    let state_provider = template
+        .provider_factory
+        .history_by_block_hash(ctx.attributes.parent)?;
+    let cached_reads = template
+        .cached_reads
+        .lock()
+        .unwrap()
+        .pop()
+        .unwrap_or_default();
+    let mut state = BlockState::new(&state_provider).with_cached_reads(cached_reads);
+    let res = estimate_on_state(ctx, &mut state, request);
+    template
+        .cached_reads
+        .lock()
+        .unwrap()
+        .push(state.into_parts().0);
+    res
+}
+
+fn estimate_on_state(
+    ctx: &BlockBuildingContext,
+    state: &mut BlockState,
+    request: &EstimateGasRequest,
+) -> Result<EstimateGasResult, EstimateGasError> {
+    PartialBlock::new(true, None)
+        .pre_block_call(ctx, state)
+        .map_err(EstimateGasError::PreBlockCall)?;
+
+    let max_gas_limit = request
+        .gas
+        .unwrap_or_else(|| ctx.block_env.gas_limit.to::<u64>());
+    let result =
+        call(ctx, state, request, max_gas_limit)?.map_err(EstimateGasError::InvalidCall)?;
+    let gas_used = result.gas_used();
+    let mut res = EstimateGasResult {
+        gas_used,
+        gas_estimate: gas_used,
+        success: result.is_success(),
+        revert_data: match result {
+            ExecutionResult::Revert { output, .. } => Some(output),
+            _ => None,
+        },
+    };
+    if res.success {
+        // gas_used has the refunds discounted so the limit needed is at least gas_used
+        let (mut failed, mut succeeded) = (gas_used.saturating_sub(1), max_gas_limit);
+        while succeeded - failed > (succeeded * ESTIMATE_GAS_TOLERANCE_BPS / 10_000).max(1) {
+            let mid = failed + (succeeded - failed) / 2;
+            match call(ctx, state, request, mid)? {
+                Ok(result) if result.is_success() => succeeded = mid,
+                _ => failed = mid,
+            }
+        }
+        res.gas_estimate = succeeded;
+    }
+    trace!(?request, ?res, "Gas estimation");
+    Ok(res)
+}
+
+/// Executes request without committing. Like eth_estimateGas gas price and basefee are 0 so from doesn't need to pay for gas.
+fn call(
+    ctx: &BlockBuildingContext,
+    state: &mut BlockState,
+    request: &EstimateGasRequest,
+    gas_limit: u64,
+) -> Result<Result<ExecutionResult, InvalidTransaction>, EstimateGasError> {
+    let mut block = ctx.block_env.clone();
+    block.basefee = U256::ZERO;
+    let env = Env {
+        cfg: ctx.initialized_cfg.cfg_env.clone(),
+        block,
+        tx: TxEnv {
+            caller: request.from,
+            gas_limit,
+            gas_price: U256::ZERO,
+            transact_to: match request.to {
+                Some(to) => TransactTo::Call(to),
+                None => TransactTo::create(),
+            },
+            value: request.value,
+            data: request.input.clone(),
+            nonce: None,
+            ..Default::default()
+        },
+    };
+    let mut db = state.new_db_ref();
+    let mut evm = revm::Evm::builder()
+        .with_spec_id(ctx.spec_id)
+        .with_env(Box::new(env))
+        .with_db(db.as_mut())
+        .build();
+    match evm.transact() {
+        Ok(res) => Ok(Ok(res.result)),
+        Err(EVMError::Transaction(err)) => Ok(Err(err)),
+        Err(EVMError::Database(err)) => Err(err.into()),
+        Err(err) => Err(EstimateGasError::Evm(format!("{:?}", err))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
+        primitives::{MempoolTx, Order, SimulatedOrder, TransactionSignedEcRecoveredWithBlobs},
+    };
+    use reth_db::{test_utils::TempDatabase, DatabaseEnv};
+
+    type TestDB = Arc<TempDatabase<DatabaseEnv>>;
+
+    fn estimator(
+        test_chain: &TestChainState,
+        max_requests_per_sec: Option<u32>,
+    ) -> GasEstimator<TestDB> {
+        let estimator = GasEstimator::from_config(&EstimateGasConfig {
+            threads: 1,
+            max_requests_per_sec,
+        })
+        .unwrap();
+        estimator.set_template(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context().clone(),
+        );
+        estimator
+    }
+
+    #[tokio::test]
+    async fn test_estimate_matches_commit_gas() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let tx = test_chain.sign_tx(TxArgs::new_increment_value(NamedAddr::User(0), 0, 5, 0))?;
+        let request = EstimateGasRequest {
+            from: tx.signer(),
+            to: tx.to(),
+            value: tx.value(),
+            input: tx.input().clone(),
+            gas: None,
+        };
+        let estimator = estimator(&test_chain, None);
+        let estimate = estimator.estimate_gas(request.clone()).await?;
+
+        let state_provider = test_chain.provider_factory().latest()?;
+        let mut state = BlockState::new(&state_provider);
+        let mut partial_block = PartialBlock::new(true, None);
+        partial_block.pre_block_call(test_chain.block_building_context(), &mut state)?;
+        let order = SimulatedOrder {
+            order: Order::Tx(MempoolTx::new(
+                TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+            )),
+            sim_value: Default::default(),
+            prev_order: None,
+            used_state_trace: None,
+        };
+        let committed = partial_block
+            .commit_order(&order, test_chain.block_building_context(), &mut state)?
+            .map_err(|err| eyre::eyre!("Order failed: {}", err))?;
+
+        assert!(estimate.success);
+        assert_eq!(estimate.revert_data, None);
+        assert_eq!(estimate.gas_used, committed.gas_used);
+        // the sstore needs more than its 2300 stipend left, so the min limit can be a bit over gas_used
+        assert!(estimate.gas_estimate >= committed.gas_used);
+        assert!(
+            estimate.gas_estimate - committed.gas_used
+                <= estimate.gas_estimate * ESTIMATE_GAS_TOLERANCE_BPS / 10_000 + 2_300,
+            "estimate: {} commit: {}",
+            estimate.gas_estimate,
+            committed.gas_used
+        );
+
+        // the estimate is enough, gas_used - 1 is not
+        let with_limit = |gas| EstimateGasRequest {
+            gas: Some(gas),
+            ..request.clone()
+        };
+        assert!(
+            estimator
+                .estimate_gas(with_limit(estimate.gas_estimate))
+                .await?
+                .success
+        );
+        assert!(
+            !estimator
+                .estimate_gas(with_limit(committed.gas_used - 1))
+                .await?
+                .success
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_estimations_keep_their_reads() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let estimator = GasEstimator::from_config(&EstimateGasConfig {
+            threads: 2,
+            max_requests_per_sec: None,
+        })?;
+        estimator.set_template(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context().clone(),
+        );
+        let request = |user| -> eyre::Result<EstimateGasRequest> {
+            let tx =
+                test_chain.sign_tx(TxArgs::new_increment_value(NamedAddr::User(user), 0, 5, 0))?;
+            Ok(EstimateGasRequest {
+                from: tx.signer(),
+                to: tx.to(),
+                input: tx.input().clone(),
+                ..Default::default()
+            })
+        };
+        let (first, second) = tokio::join!(
+            estimator.estimate_gas(request(0)?),
+            estimator.estimate_gas(request(1)?)
+        );
+        assert!(first?.success && second?.success);
+
+        let template = estimator.template.lock().unwrap().clone().unwrap();
+        let idle_caches = template.cached_reads.lock().unwrap().len();
+        // every estimation gave its cache back, none was dropped by a concurrent one
+        assert!(
+            (1..=2).contains(&idle_caches),
+            "idle caches: {}",
+            idle_caches
+        );
+        estimator.estimate_gas(request(0)?).await?;
+        assert_eq!(template.cached_reads.lock().unwrap().len(), idle_caches);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_revert_and_limits() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let tx = test_chain.sign_tx(TxArgs::new_revert(NamedAddr::User(0), 0))?;
+        let request = EstimateGasRequest {
+            from: tx.signer(),
+            to: tx.to(),
+            input: tx.input().clone(),
+            ..Default::default()
+        };
+
+        let no_template = GasEstimator::<TestDB>::from_config(&EstimateGasConfig {
+            threads: 1,
+            max_requests_per_sec: None,
+        })?;
+        assert!(matches!(
+            no_template.estimate_gas(request.clone()).await,
+            Err(EstimateGasError::NoTemplate)
+        ));
+
+        let estimator = estimator(&test_chain, Some(1));
+        let estimate = estimator.estimate_gas(request.clone()).await?;
+        assert!(!estimate.success);
+        assert!(estimate.revert_data.is_some());
+        assert_eq!(estimate.gas_estimate, estimate.gas_used);
+        assert!(matches!(
+            estimator.estimate_gas(request).await,
+            Err(EstimateGasError::RateLimited)
+        ));
+        Ok(())
+    }
+}
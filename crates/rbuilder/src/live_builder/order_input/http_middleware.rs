@@ -0,0 +1,294 @@
+//! HTTP layer in front of the order input JSON-RPC server:
+//! - Decompresses gzip/zstd bodies (Content-Encoding). Bodies over max_body_size (compressed or decompressed) are refused.
+//! - Splits JSON-RPC batches and forwards the elements one by one, in order, to the server. Every element is answered on
+//! its own (one bad bundle does not fail the batch) and nonce dependent submissions of the same searcher are processed
+//! in the order they were sent.
+//! Since elements are processed sequentially, eth_sendBundle with feedback waits its sim budget once per element.
+use crate::telemetry::{add_order_input_decompression_time, observe_order_input_batch_size};
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use std::{
+    io::Read,
+    task::{Context, Poll},
+    time::Instant,
+};
+use thiserror::Error;
+use tower::{Layer, Service, ServiceExt};
+use warp::hyper::{
+    self,
+    body::HttpBody,
+    header::{self, HeaderValue},
+    http::request::Parts,
+    Body, Method, Request, Response, StatusCode,
+};
+
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 100;
+
+const INVALID_REQUEST_CODE: i32 = -32600;
+const INTERNAL_ERROR_CODE: i32 = -32603;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+#[derive(Error, Debug)]
+pub enum BodyDecodeError {
+    #[error("Unsupported content encoding: {0}")]
+    UnsupportedEncoding(String),
+    #[error("Body over {0} bytes")]
+    TooLarge(usize),
+    #[error("Failed to decompress body: {0}")]
+    Decompress(#[from] std::io::Error),
+    #[error("Failed to read body: {0}")]
+    Read(#[from] hyper::Error),
+}
+
+impl BodyDecodeError {
+    fn status(&self) -> StatusCode {
+        match self {
+            BodyDecodeError::UnsupportedEncoding(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BodyDecodeError::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyDecodeError::Decompress(_) | BodyDecodeError::Read(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+/// content_encoding None/identity: body as is.
+pub fn decode_body(
+    content_encoding: Option<&str>,
+    body: Vec<u8>,
+    max_size: usize,
+) -> Result<Vec<u8>, BodyDecodeError> {
+    let encoding = match content_encoding.map(str::trim) {
+        None | Some("") | Some("identity") => return Ok(body),
+        Some(encoding) => encoding,
+    };
+    let start = Instant::now();
+    let res = match encoding {
+        "gzip" => read_limited(flate2::read::GzDecoder::new(body.as_slice()), max_size),
+        "zstd" => read_limited(zstd::stream::read::Decoder::new(body.as_slice())?, max_size),
+        encoding => return Err(BodyDecodeError::UnsupportedEncoding(encoding.to_string())),
+    };
+    add_order_input_decompression_time(encoding, start.elapsed());
+    res
+}
+
+/// We never decompress more than max_size + 1 bytes (zip bombs).
+fn read_limited(reader: impl Read, max_size: usize) -> Result<Vec<u8>, BodyDecodeError> {
+    let mut res = Vec::new();
+    reader.take(max_size as u64 + 1).read_to_end(&mut res)?;
+    if res.len() > max_size {
+        return Err(BodyDecodeError::TooLarge(max_size));
+    }
+    Ok(res)
+}
+
+async fn read_body(mut body: Body, max_size: usize) -> Result<Vec<u8>, BodyDecodeError> {
+    let mut res = Vec::new();
+    while let Some(chunk) = body.data().await {
+        res.extend_from_slice(&chunk?);
+        if res.len() > max_size {
+            return Err(BodyDecodeError::TooLarge(max_size));
+        }
+    }
+    Ok(res)
+}
+
+/// None if body is not a JSON array, in that case it goes untouched to the server.
+fn split_batch(body: &[u8]) -> Option<Vec<Value>> {
+    let first = body.iter().find(|b| !b.is_ascii_whitespace())?;
+    if *first != b'[' {
+        return None;
+    }
+    serde_json::from_slice(body).ok()
+}
+
+fn error_object(code: i32, message: impl Into<String>, id: Value) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": code, "message": message.into()},
+        "id": id,
+    })
+}
+
+fn json_response(status: StatusCode, value: &Value) -> Response<Body> {
+    let mut response = Response::new(Body::from(value.to_string()));
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("application/json"),
+    );
+    response
+}
+
+/// Same request with a new body.
+fn request_with_body(parts: &Parts, body: Vec<u8>) -> Request<Body> {
+    let mut request = Request::new(Body::empty());
+    *request.method_mut() = parts.method.clone();
+    *request.uri_mut() = parts.uri.clone();
+    *request.version_mut() = parts.version;
+    *request.headers_mut() = parts.headers.clone();
+    request
+        .headers_mut()
+        .insert(header::CONTENT_LENGTH, HeaderValue::from(body.len()));
+    *request.body_mut() = Body::from(body);
+    request
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderInputHttpLayer {
+    max_body_size: usize,
+    max_batch_size: usize,
+}
+
+impl OrderInputHttpLayer {
+    pub fn new(max_body_size: usize, max_batch_size: usize) -> Self {
+        Self {
+            max_body_size,
+            max_batch_size,
+        }
+    }
+}
+
+impl<S> Layer<S> for OrderInputHttpLayer {
+    type Service = OrderInputHttpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        OrderInputHttpService {
+            inner,
+            max_body_size: self.max_body_size,
+            max_batch_size: self.max_batch_size,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderInputHttpService<S> {
+    inner: S,
+    max_body_size: usize,
+    max_batch_size: usize,
+}
+
+impl<S> Service<Request<Body>> for OrderInputHttpService<S>
+where
+    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Error: Into<BoxError>,
+    S::Future: Send,
+{
+    type Response = Response<Body>;
+    type Error = BoxError;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        // the clone might not be ready, keep the one we polled
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let (max_body_size, max_batch_size) = (self.max_body_size, self.max_batch_size);
+        Box::pin(async move {
+            if request.method() != Method::POST {
+                return inner.call(request).await.map_err(Into::into);
+            }
+            let (mut parts, body) = request.into_parts();
+            let content_encoding = parts.headers.remove(header::CONTENT_ENCODING);
+            let body = match read_body(body, max_body_size).await.and_then(|body| {
+                let content_encoding = content_encoding
+                    .as_ref()
+                    .map(|value| value.to_str().unwrap_or("invalid"));
+                decode_body(content_encoding, body, max_body_size)
+            }) {
+                Ok(body) => body,
+                Err(err) => {
+                    return Ok(json_response(
+                        err.status(),
+                        &error_object(INVALID_REQUEST_CODE, err.to_string(), Value::Null),
+                    ))
+                }
+            };
+
+            let elements = match split_batch(&body) {
+                Some(elements) if !elements.is_empty() => elements,
+                // empty batches are refused by the server
+                _ => {
+                    return inner
+                        .call(request_with_body(&parts, body))
+                        .await
+                        .map_err(Into::into)
+                }
+            };
+            observe_order_input_batch_size(elements.len());
+            if elements.len() > max_batch_size {
+                return Ok(json_response(
+                    StatusCode::OK,
+                    &error_object(
+                        INVALID_REQUEST_CODE,
+                        format!("Batch over {} elements", max_batch_size),
+                        Value::Null,
+                    ),
+                ));
+            }
+
+            let mut responses = Vec::new();
+            for element in elements {
+                let id = element.get("id").cloned().unwrap_or(Value::Null);
+                let request = request_with_body(&parts, element.to_string().into_bytes());
+                let response = inner.ready().await.map_err(Into::into)?.call(request);
+                let body =
+                    hyper::body::to_bytes(response.await.map_err(Into::into)?.into_body()).await?;
+                // notifications are not answered
+                if body.is_empty() {
+                    continue;
+                }
+                responses.push(serde_json::from_slice(&body).unwrap_or_else(|_| {
+                    error_object(INTERNAL_ERROR_CODE, "Invalid server response", id)
+                }));
+            }
+            if responses.is_empty() {
+                return Ok(Response::new(Body::empty()));
+            }
+            Ok(json_response(StatusCode::OK, &Value::Array(responses)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_decode_body() {
+        let body = br#"{"jsonrpc": "2.0"}"#.to_vec();
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(&body).unwrap();
+        let gzip = gzip.finish().unwrap();
+        assert_eq!(decode_body(Some("gzip"), gzip, 1024).unwrap(), body);
+
+        let zstd = zstd::encode_all(body.as_slice(), 0).unwrap();
+        assert_eq!(decode_body(Some("zstd"), zstd.clone(), 1024).unwrap(), body);
+        assert!(matches!(
+            decode_body(Some("zstd"), zstd, body.len() - 1),
+            Err(BodyDecodeError::TooLarge(_))
+        ));
+
+        assert_eq!(decode_body(None, body.clone(), 1024).unwrap(), body);
+        assert!(matches!(
+            decode_body(Some("br"), body, 1024),
+            Err(BodyDecodeError::UnsupportedEncoding(_))
+        ));
+    }
+
+    #[test]
+    fn test_split_batch() {
+        assert_eq!(split_batch(br#"{"id": 1}"#), None);
+        assert_eq!(
+            split_batch(b" \n[{\"id\": 1}, {\"id\": 2}]").map(|elements| elements.len()),
+            Some(2)
+        );
+        // invalid json goes to the server that answers the parse error
+        assert_eq!(split_batch(b"[{"), None);
+    }
+}
@@ -1,27 +1,42 @@
 //! order_input handles receiving new orders from the ipc mempool subscription and json rpc server
 //!
 pub mod clean_orderpool;
+pub mod estimate_gas;
+pub mod http_middleware;
 pub mod order_replacement_manager;
 pub mod order_sink;
 pub mod orderpool;
+pub mod pending_account;
 pub mod replaceable_order_sink;
 pub mod rpc_server;
 pub mod txpool_fetcher;
 
 use self::{
+    http_middleware::{DEFAULT_MAX_BATCH_SIZE, DEFAULT_MAX_BODY_SIZE},
     orderpool::{OrderPool, OrderPoolSubscriptionId},
     replaceable_order_sink::ReplaceableOrderSink,
 };
 use crate::{
-    primitives::{serialize::CancelShareBundle, BundleReplacementKey, Order},
+    live_builder::simulation::fast_lane::FastSimLane,
+    primitives::{
+        serialize::CancelShareBundle, static_limits::check_order_static_limits,
+        BundleReplacementKey, Order,
+    },
+    telemetry::inc_order_input_static_limit_rejections,
     utils::ProviderFactoryReopener,
 };
+use alloy_primitives::Address;
 use jsonrpsee::RpcModule;
+use reth::primitives::ChainSpec;
 use reth_db::database::Database;
 use std::{
+    collections::HashSet,
     net::Ipv4Addr,
     path::PathBuf,
-    sync::{Arc, Mutex},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
     time::Duration,
 };
 use tokio::{sync::mpsc, task::JoinHandle};
@@ -90,10 +105,23 @@ pub struct OrderInputConfig {
     serve_max_connections: u32,
     results_channel_timeout: Duration,
     input_channel_buffer_size: usize,
+    /// Max time eth_sendBundle waits for the fast simulation when the caller asks for feedback.
+    bundle_feedback_sim_budget: Duration,
+    /// If None eth_sendBundle feedback never includes simulation results.
+    fast_sim_lane: Option<FastSimLane>,
+    /// Max request body, compressed and decompressed (see [`http_middleware`]).
+    max_request_body_size: usize,
+    /// Max elements of a JSON-RPC batch.
+    max_batch_size: usize,
+    /// If Some orders over the static limits of the chain are dropped (see [`crate::primitives::static_limits`]).
+    static_limits: Option<Arc<StaticLimits>>,
+    /// See [`mark_private_signers_orders`].
+    private_order_signers: HashSet<Address>,
 }
 pub const DEFAULT_SERVE_MAX_CONNECTIONS: u32 = 4096;
 pub const DEFAULT_RESULTS_CHANNEL_TIMEOUT: Duration = Duration::from_millis(50);
 pub const DEFAULT_INPUT_CHANNEL_BUFFER_SIZE: usize = 10_000;
+pub const DEFAULT_BUNDLE_FEEDBACK_SIM_BUDGET: Duration = Duration::from_millis(50);
 impl OrderInputConfig {
     #[allow(clippy::too_many_arguments)]
     pub fn new(
@@ -115,6 +143,12 @@ impl OrderInputConfig {
             serve_max_connections,
             results_channel_timeout,
             input_channel_buffer_size,
+            bundle_feedback_sim_budget: DEFAULT_BUNDLE_FEEDBACK_SIM_BUDGET,
+            fast_sim_lane: None,
+            max_request_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            static_limits: None,
+            private_order_signers: HashSet::default(),
         }
     }
     pub fn from_config(config: &BaseConfig) -> Self {
@@ -127,8 +161,92 @@ impl OrderInputConfig {
             serve_max_connections: 4096,
             results_channel_timeout: Duration::from_millis(50),
             input_channel_buffer_size: 10_000,
+            bundle_feedback_sim_budget: Duration::from_millis(config.bundle_feedback_sim_budget_ms),
+            fast_sim_lane: None,
+            max_request_body_size: config.jsonrpc_max_request_body_size,
+            max_batch_size: config.jsonrpc_max_batch_size,
+            static_limits: None,
+            private_order_signers: config.private_order_signers.iter().copied().collect(),
         }
     }
+
+    /// Lets eth_sendBundle include fast simulation results in the feedback.
+    pub fn with_fast_sim_lane(self, fast_sim_lane: FastSimLane) -> Self {
+        Self {
+            fast_sim_lane: Some(fast_sim_lane),
+            ..self
+        }
+    }
+
+    /// Drops the orders over static_limits.
+    pub fn with_static_limits(self, static_limits: Arc<StaticLimits>) -> Self {
+        Self {
+            static_limits: Some(static_limits),
+            ..self
+        }
+    }
+
+    pub fn check_static_limits(&self, order: &Order) -> Result<(), String> {
+        check_static_limits(self.static_limits.as_deref(), order)
+    }
+}
+
+/// Chain and slot the ingested orders are checked against, the live builder moves it to every new slot.
+#[derive(Debug)]
+pub struct StaticLimits {
+    chain_spec: Arc<ChainSpec>,
+    /// 0 until the first slot (no fork limits, only the tx size).
+    slot_timestamp: AtomicU64,
+}
+
+impl StaticLimits {
+    pub fn new(chain_spec: Arc<ChainSpec>) -> Self {
+        Self {
+            chain_spec,
+            slot_timestamp: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_slot_timestamp(&self, slot_timestamp: u64) {
+        self.slot_timestamp.store(slot_timestamp, Ordering::Relaxed);
+    }
+
+    pub fn slot_timestamp(&self) -> u64 {
+        self.slot_timestamp.load(Ordering::Relaxed)
+    }
+}
+
+/// Err (the rejection reason) if order is over the static limits, never if static_limits is None.
+/// The forks are the ones active on the current slot, an order for a later block after an activation that slips
+/// through is caught when committing it.
+pub fn check_static_limits(
+    static_limits: Option<&StaticLimits>,
+    order: &Order,
+) -> Result<(), String> {
+    let Some(static_limits) = static_limits else {
+        return Ok(());
+    };
+    check_order_static_limits(
+        order,
+        &static_limits.chain_spec,
+        static_limits.slot_timestamp(),
+    )
+    .map_err(|(tx_hash, err)| {
+        inc_order_input_static_limit_rejections(err.kind());
+        format!("tx {:?}: {}", tx_hash, err)
+    })
+}
+
+/// Orders from private_order_signers are private no matter what the request says (see [`crate::primitives::Metadata::private`]).
+/// The signer is the bundle signing address or, for mempool txs, the tx signer.
+pub fn mark_private_signers_orders(order: &mut Order, private_order_signers: &HashSet<Address>) {
+    let signer = match order {
+        Order::Tx(tx) => Some(tx.tx_with_blobs.signer()),
+        Order::Bundle(_) | Order::ShareBundle(_) => order.signer(),
+    };
+    if signer.map_or(false, |signer| private_order_signers.contains(&signer)) {
+        order.set_private();
+    }
 }
 
 /// Commands we can get from RPC
@@ -226,6 +344,24 @@ pub async fn start_orderpool_jobs<DB: Database + Clone + 'static>(
                 })
             }
 
+            for command in &mut new_commands {
+                if let ReplaceableOrderPoolCommand::Order(order) = command {
+                    mark_private_signers_orders(order, &config.private_order_signers);
+                }
+            }
+
+            new_commands.retain(|o| match o {
+                ReplaceableOrderPoolCommand::Order(o) => match config.check_static_limits(o) {
+                    Ok(()) => true,
+                    Err(reason) => {
+                        trace!(order=?o.id(), reason, "Ignoring order over static limits");
+                        false
+                    }
+                },
+                ReplaceableOrderPoolCommand::CancelShareBundle(_)
+                | ReplaceableOrderPoolCommand::CancelBundle(_) => true,
+            });
+
             if config.ignore_blobs {
                 new_commands.retain(|o| {
                     let has_blobs = match o {
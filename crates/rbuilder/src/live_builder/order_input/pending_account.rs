@@ -0,0 +1,260 @@
+//! rbuilder_getPendingAccount: nonce and balance an account will have in the block we are building so searchers can
+//! chain txs on top of it.
+//! Answered from the best block candidate of the current slot (see [`PendingCandidates`]) if it changed the account,
+//! otherwise from the template of the slot. The template (parent state + pre block call) never changes accounts so its
+//! answer is the parent state.
+//! The answer says where it comes from and which candidate iteration it reflects, a newer iteration means it's stale.
+use crate::building::{
+    pending_accounts::{PendingAccount, PendingCandidates},
+    BlockBuildingContext,
+};
+use alloy_primitives::{Address, B256, U256};
+use jsonrpsee::{
+    types::{ErrorObject, ErrorObjectOwned},
+    RpcModule,
+};
+use reth::providers::{AccountReader, ProviderFactory};
+use reth_db::database::Database;
+use reth_interfaces::provider::ProviderError;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use thiserror::Error;
+
+const RPC_ERROR_CODE: i32 = -32000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PendingAccountSource {
+    Candidate,
+    Template,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingAccountResult {
+    pub nonce: u64,
+    pub balance: U256,
+    /// The candidate changed the account.
+    pub touched: bool,
+    pub source: PendingAccountSource,
+    pub block_number: u64,
+    pub parent_hash: B256,
+    /// Candidate iteration of the answer, None if there was no candidate yet.
+    pub iteration: Option<u64>,
+    pub builder_name: Option<String>,
+}
+
+#[derive(Error, Debug)]
+pub enum PendingAccountError {
+    #[error("No block template for the current slot")]
+    NoTemplate,
+    #[error("Reth error: {0}")]
+    Reth(#[from] ProviderError),
+    #[error("Lookup task failed")]
+    TaskFailed,
+}
+
+#[derive(Debug)]
+struct SlotTemplate<DB> {
+    provider_factory: ProviderFactory<DB>,
+    parent_hash: B256,
+    block_number: u64,
+}
+
+/// Cheap to clone, all the clones share the template.
+#[derive(Debug)]
+pub struct PendingAccountService<DB> {
+    candidates: Arc<PendingCandidates>,
+    template: Arc<Mutex<Option<Arc<SlotTemplate<DB>>>>>,
+}
+
+impl<DB> Clone for PendingAccountService<DB> {
+    fn clone(&self) -> Self {
+        Self {
+            candidates: self.candidates.clone(),
+            template: self.template.clone(),
+        }
+    }
+}
+
+impl<DB: Database + Clone + 'static> PendingAccountService<DB> {
+    /// candidates gets enabled.
+    pub fn new(candidates: Arc<PendingCandidates>) -> Self {
+        candidates.enable();
+        Self {
+            candidates,
+            template: Default::default(),
+        }
+    }
+
+    /// Called on every new slot.
+    pub fn set_template(&self, provider_factory: ProviderFactory<DB>, ctx: &BlockBuildingContext) {
+        *self.template.lock().unwrap() = Some(Arc::new(SlotTemplate {
+            provider_factory,
+            parent_hash: ctx.attributes.parent,
+            block_number: ctx.block(),
+        }));
+    }
+
+    pub async fn pending_account(
+        &self,
+        address: Address,
+    ) -> Result<PendingAccountResult, PendingAccountError> {
+        let template = self
+            .template
+            .lock()
+            .unwrap()
+            .clone()
+            .ok_or(PendingAccountError::NoTemplate)?;
+        let candidate = self.candidates.best(template.parent_hash);
+        let iteration = candidate.as_ref().map(|candidate| candidate.iteration);
+        let builder_name = candidate
+            .as_ref()
+            .map(|candidate| candidate.builder_name.clone());
+        let (account, touched, source) =
+            match candidate.and_then(|candidate| candidate.account(&address)) {
+                Some(account) => (account, true, PendingAccountSource::Candidate),
+                None => {
+                    let template = template.clone();
+                    let account =
+                        tokio::task::spawn_blocking(move || template_account(&template, address))
+                            .await
+                            .map_err(|_| PendingAccountError::TaskFailed)??;
+                    (account, false, PendingAccountSource::Template)
+                }
+            };
+        Ok(PendingAccountResult {
+            nonce: account.nonce,
+            balance: account.balance,
+            touched,
+            source,
+            block_number: template.block_number,
+            parent_hash: template.parent_hash,
+            iteration,
+            builder_name,
+        })
+    }
+
+    pub fn rpc_module(&self) -> eyre::Result<RpcModule<()>> {
+        let mut module = RpcModule::new(());
+        let service = self.clone();
+        module.register_async_method("rbuilder_getPendingAccount", move |params, _| {
+            let service = service.clone();
+            async move {
+                let address: Address = params.one()?;
+                service
+                    .pending_account(address)
+                    .await
+                    .map_err(|err| -> ErrorObjectOwned {
+                        ErrorObject::owned(RPC_ERROR_CODE, err.to_string(), None::<()>)
+                    })
+            }
+        })?;
+        Ok(module)
+    }
+}
+
+fn template_account<DB: Database>(
+    template: &SlotTemplate<DB>,
+    address: Address,
+) -> Result<PendingAccount, PendingAccountError> {
+    let state_provider = template
+        .provider_factory
+        .history_by_block_hash(template.parent_hash)?;
+    Ok(state_provider
+        .basic_account(address)?
+        .map(|account| PendingAccount {
+            nonce: account.nonce,
+            balance: account.balance,
+        })
+        .unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::{
+            builders::ordering_builder::{OrderingBuilderConfig, OrderingBuilderContext},
+            sim::simulate_all_orders_with_sim_tree,
+            testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
+            BlockOrders, CoinbaseMode, Sorting,
+        },
+        primitives::{MempoolTx, Order, TransactionSignedEcRecoveredWithBlobs},
+        test_utils::StaticBidder,
+    };
+    use reth::tasks::pool::BlockingTaskPool;
+
+    #[tokio::test]
+    async fn test_pending_nonce_after_candidate() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let candidates = Arc::new(PendingCandidates::default());
+        let service = PendingAccountService::new(candidates.clone());
+        service.set_template(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+        );
+        let user = test_chain.named_address(NamedAddr::User(0))?;
+
+        let before = service.pending_account(user).await?;
+        assert_eq!(before.source, PendingAccountSource::Template);
+        assert_eq!(before.iteration, None);
+        assert!(!before.touched);
+
+        let tx = test_chain.sign_tx(
+            TxArgs::new_send_to_coinbase(NamedAddr::User(0), before.nonce, 1_000_000)
+                .max_fee_per_gas(10_000_000_000),
+        )?;
+        let order = Order::Tx(MempoolTx::new(
+            TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap(),
+        ));
+        let (sim_orders, _) = simulate_all_orders_with_sim_tree(
+            test_chain.provider_factory().clone(),
+            test_chain.block_building_context(),
+            &[order],
+            false,
+        )?;
+        let mut block_orders = BlockOrders::new(Sorting::MaxProfit, vec![], &[]);
+        for order in sim_orders {
+            block_orders.add_order(order);
+        }
+        let config: OrderingBuilderConfig = serde_json::from_value(serde_json::json!({
+            "discard_txs": true,
+            "sorting": "max-profit",
+            "failed_order_retries": 1,
+            "drop_failed_orders": true,
+        }))?;
+        OrderingBuilderContext::new(
+            test_chain.provider_factory().clone(),
+            Arc::new(StaticBidder::full_value()),
+            BlockingTaskPool::build()?,
+            "pending_account_builder".to_string(),
+            test_chain.block_building_context().clone(),
+            config,
+        )
+        .with_skip_root_hash()
+        .with_pending_candidates(candidates)
+        .build_block(block_orders, CoinbaseMode::SuggestedFeeRecipient)?
+        .expect("block");
+
+        let after = service.pending_account(user).await?;
+        assert_eq!(after.source, PendingAccountSource::Candidate);
+        assert!(after.touched);
+        assert_eq!(after.nonce, before.nonce + 1);
+        assert!(after.balance < before.balance);
+        assert_eq!(after.iteration, Some(1));
+        assert_eq!(
+            after.builder_name.as_deref(),
+            Some("pending_account_builder")
+        );
+        assert_eq!(after.block_number, 1);
+
+        // untouched accounts still come from the template
+        let other = service
+            .pending_account(test_chain.named_address(NamedAddr::User(1))?)
+            .await?;
+        assert_eq!(other.source, PendingAccountSource::Template);
+        assert_eq!(other.iteration, Some(1));
+        Ok(())
+    }
+}
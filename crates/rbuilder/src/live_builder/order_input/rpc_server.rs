@@ -1,14 +1,29 @@
-use super::{OrderInputConfig, ReplaceableOrderPoolCommand};
-use crate::primitives::{
-    serialize::{RawBundle, RawShareBundle, RawShareBundleDecodeResult, RawTx, TxEncoding},
-    Bundle, BundleReplacementKey, MempoolTx, Order,
+use super::{
+    check_static_limits, http_middleware::OrderInputHttpLayer, OrderInputConfig,
+    ReplaceableOrderPoolCommand, StaticLimits,
+};
+use crate::{
+    live_builder::simulation::fast_lane::{FastSimLane, FastSimResult},
+    primitives::{
+        serialize::{RawBundle, RawShareBundle, RawShareBundleDecodeResult, RawTx, TxEncoding},
+        Bundle, BundleReplacementKey, MempoolTx, Order,
+    },
+    telemetry,
+    utils::rate_limited_log::{error_discriminant, rate_limited},
+};
+use alloy_primitives::{Address, U256};
+use jsonrpsee::{
+    server::Server,
+    types::{error::INVALID_PARAMS_CODE, ErrorObjectOwned},
+    RpcModule,
 };
-use alloy_primitives::Address;
-use jsonrpsee::{server::Server, RpcModule};
 use reth::primitives::Bytes;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
+    fmt::Display,
     net::{SocketAddr, SocketAddrV4},
+    panic::{self, AssertUnwindSafe},
+    sync::Arc,
     time::{Duration, Instant},
 };
 use tokio::{
@@ -16,6 +31,7 @@ use tokio::{
     task::JoinHandle,
 };
 use tokio_util::sync::CancellationToken;
+use tower::ServiceBuilder;
 use tracing::{info, trace, warn};
 use uuid::Uuid;
 
@@ -27,44 +43,56 @@ pub async fn start_server_accepting_bundles(
     global_cancel: CancellationToken,
 ) -> eyre::Result<JoinHandle<()>> {
     let addr = SocketAddr::V4(SocketAddrV4::new(config.server_ip, config.server_port));
-    let timeout = config.results_channel_timeout;
 
     let server = Server::builder()
         .max_connections(config.serve_max_connections)
+        .max_request_body_size(config.max_request_body_size as u32)
+        .set_middleware(ServiceBuilder::new().layer(OrderInputHttpLayer::new(
+            config.max_request_body_size,
+            config.max_batch_size,
+        )))
         .http_only()
         .build(addr)
         .await?;
 
+    let mut module = order_input_rpc_module(&config, results)?;
+    module.merge(extra_rpc)?;
+    let handle = server.start(module);
+
+    Ok(tokio::spawn(async move {
+        info!("RPC server job: started");
+        tokio::select! {
+            _ = global_cancel.cancelled() => {},
+            _ = handle.stopped() => {
+                info!("RPC Server stopped");
+                global_cancel.cancel();
+            },
+        }
+
+        info!("RPC server job: finished");
+    }))
+}
+
+fn order_input_rpc_module(
+    config: &OrderInputConfig,
+    results: mpsc::Sender<ReplaceableOrderPoolCommand>,
+) -> eyre::Result<RpcModule<()>> {
+    let timeout = config.results_channel_timeout;
     let mut module = RpcModule::new(());
 
     let results_clone = results.clone();
+    let feedback_sim_budget = config.bundle_feedback_sim_budget;
+    let fast_sim_lane = config.fast_sim_lane.clone();
+    let static_limits = config.static_limits.clone();
     module.register_async_method("eth_sendBundle", move |params, _| {
-        let results = results_clone.clone();
-        async move {
-            let start = Instant::now();
-            let raw_bundle: RawBundle = match params.one() {
-                Ok(raw_bundle) => raw_bundle,
-                Err(err) => {
-                    warn!(?err, "Failed to parse raw bundle");
-                    // @Metric
-                    return;
-                }
-            };
-
-            let bundle: Bundle = match raw_bundle.decode(TxEncoding::WithBlobData) {
-                Ok(bundle) => bundle,
-                Err(err) => {
-                    warn!(?err, "Failed to parse bundle");
-                    // @Metric
-                    return;
-                }
-            };
-            let order = Order::Bundle(bundle);
-            let parse_duration = start.elapsed();
-            let target_block = order.target_block().unwrap_or_default();
-            trace!(order = ?order.id(), parse_duration_mus = parse_duration.as_micros(), target_block, "Received bundle");
-            send_order(order, &results, timeout).await;
-        }
+        handle_eth_send_bundle(
+            results_clone.clone(),
+            timeout,
+            feedback_sim_budget,
+            fast_sim_lane.clone(),
+            static_limits.clone(),
+            params,
+        )
     })?;
 
     let results_clone = results.clone();
@@ -85,17 +113,27 @@ pub async fn start_server_accepting_bundles(
             let raw_tx: Bytes = match params.one() {
                 Ok(raw_tx) => raw_tx,
                 Err(err) => {
-                    warn!(?err, "Failed to parse transaction");
+                    if let Some(repeated_in_last_minute) =
+                        rate_limited("rpc_server.parse_transaction", error_discriminant(&err))
+                    {
+                        warn!(?err, repeated_in_last_minute, "Failed to parse transaction");
+                    }
                     // @Metric
                     return;
                 }
             };
             let raw_tx_order = RawTx { tx: raw_tx };
 
-            let tx: MempoolTx = match raw_tx_order.decode(TxEncoding::WithBlobData) {
+            let tx: MempoolTx = match decode_catching_panics("eth_sendRawTransaction", || {
+                raw_tx_order.decode(TxEncoding::WithBlobData)
+            }) {
                 Ok(tx) => tx,
                 Err(err) => {
-                    warn!(?err, "Failed to verify transaction");
+                    if let Some(repeated_in_last_minute) =
+                        rate_limited("rpc_server.verify_transaction", error_discriminant(&err))
+                    {
+                        warn!(?err, repeated_in_last_minute, "Failed to verify transaction");
+                    }
                     // @Metric
                     return;
                 }
@@ -107,21 +145,158 @@ pub async fn start_server_accepting_bundles(
         }
     })?;
 
-    module.merge(extra_rpc)?;
-    let handle = server.start(module);
+    Ok(module)
+}
 
-    Ok(tokio::spawn(async move {
-        info!("RPC server job: started");
-        tokio::select! {
-            _ = global_cancel.cancelled() => {},
-            _ = handle.stopped() => {
-                info!("RPC Server stopped");
-                global_cancel.cancel();
-            },
+/// Optional second param of eth_sendBundle.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SendBundleOptions {
+    /// If false (default) eth_sendBundle returns null as always (invalid bundles get an invalid params error).
+    pub feedback: bool,
+}
+
+/// eth_sendBundle response when the caller asks for feedback.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum BundleFeedback {
+    /// Bundle failed validation and was dropped.
+    Rejected { reason: String },
+    /// Bundle accepted and simulated alone on top of the parent block within the budget
+    /// (see [`crate::live_builder::simulation::fast_lane`]).
+    #[serde(rename_all = "camelCase")]
+    Simulated {
+        gas_used: u64,
+        coinbase_profit: U256,
+        reverted: bool,
+        error: Option<String>,
+    },
+    /// Bundle accepted, no simulation results within the budget.
+    Queued,
+}
+
+impl From<FastSimResult> for BundleFeedback {
+    fn from(result: FastSimResult) -> Self {
+        BundleFeedback::Simulated {
+            gas_used: result.gas_used,
+            coinbase_profit: result.coinbase_profit,
+            reverted: result.reverted,
+            error: result.error,
         }
+    }
+}
 
-        info!("RPC server job: finished");
-    }))
+/// Parses [bundle, options?]. Bundle errors are returned apart so we can still honor options.feedback.
+fn parse_send_bundle_params(
+    params: &jsonrpsee::types::Params<'_>,
+) -> Result<(Result<RawBundle, String>, SendBundleOptions), ErrorObjectOwned> {
+    let values: Vec<serde_json::Value> = params.parse()?;
+    let options = match values.get(1) {
+        Some(options) => SendBundleOptions::deserialize(options)
+            .map_err(|err| invalid_params(err.to_string()))?,
+        None => SendBundleOptions::default(),
+    };
+    let raw_bundle = match values.first() {
+        Some(raw_bundle) => RawBundle::deserialize(raw_bundle).map_err(|err| err.to_string()),
+        None => return Err(invalid_params("missing bundle")),
+    };
+    Ok((raw_bundle, options))
+}
+
+/// Last resort against decoder bugs: a panic while decoding an external order becomes an error (counted on
+/// order_input_decode_panics) instead of killing the request task.
+fn decode_catching_panics<T, E: Display>(
+    method: &str,
+    decode: impl FnOnce() -> Result<T, E>,
+) -> Result<T, String> {
+    match panic::catch_unwind(AssertUnwindSafe(decode)) {
+        Ok(res) => res.map_err(|err| err.to_string()),
+        Err(_) => {
+            telemetry::inc_order_input_decode_panics(method);
+            Err("Decoding panicked".to_string())
+        }
+    }
+}
+
+fn invalid_params(message: impl Into<String>) -> ErrorObjectOwned {
+    ErrorObjectOwned::owned(INVALID_PARAMS_CODE, message.into(), None::<()>)
+}
+
+/// Invalid bundle: Rejected feedback if the caller asked for it, null (as always) otherwise.
+fn reject_bundle(
+    options: &SendBundleOptions,
+    reason: String,
+) -> Result<Option<BundleFeedback>, ErrorObjectOwned> {
+    Ok(options
+        .feedback
+        .then_some(BundleFeedback::Rejected { reason }))
+}
+
+/// Returns None (serialized as null) unless the caller asked for feedback, clients that don't set feedback see the
+/// old responses. Callers that need to know which elements of a batch were taken ask for feedback (see [`reject_bundle`]).
+async fn handle_eth_send_bundle(
+    results: mpsc::Sender<ReplaceableOrderPoolCommand>,
+    timeout: Duration,
+    feedback_sim_budget: Duration,
+    fast_sim_lane: Option<FastSimLane>,
+    static_limits: Option<Arc<StaticLimits>>,
+    params: jsonrpsee::types::Params<'static>,
+) -> Result<Option<BundleFeedback>, ErrorObjectOwned> {
+    let start = Instant::now();
+    let (raw_bundle, options) = match parse_send_bundle_params(&params) {
+        Ok(res) => res,
+        Err(err) => {
+            if let Some(repeated_in_last_minute) =
+                rate_limited("rpc_server.parse_raw_bundle", error_discriminant(&err))
+            {
+                warn!(?err, repeated_in_last_minute, "Failed to parse raw bundle");
+            }
+            // @Metric
+            return Err(err);
+        }
+    };
+    let bundle: Result<Bundle, String> = raw_bundle.and_then(|raw_bundle| {
+        decode_catching_panics("eth_sendBundle", || {
+            raw_bundle.decode(TxEncoding::WithBlobData)
+        })
+    });
+    let bundle = match bundle {
+        Ok(bundle) => bundle,
+        Err(reason) => {
+            if let Some(repeated_in_last_minute) =
+                rate_limited("rpc_server.parse_bundle", error_discriminant(&reason))
+            {
+                warn!(?reason, repeated_in_last_minute, "Failed to parse bundle");
+            }
+            // @Metric
+            return reject_bundle(&options, reason);
+        }
+    };
+    let order = Order::Bundle(bundle);
+    if let Err(reason) = check_static_limits(static_limits.as_deref(), &order) {
+        if let Some(repeated_in_last_minute) = rate_limited(
+            "rpc_server.bundle_static_limits",
+            error_discriminant(&reason),
+        ) {
+            warn!(order = ?order.id(), reason, repeated_in_last_minute, "Bundle over static limits");
+        }
+        return reject_bundle(&options, reason);
+    }
+    let parse_duration = start.elapsed();
+    let target_block = order.target_block().unwrap_or_default();
+    trace!(order = ?order.id(), parse_duration_mus = parse_duration.as_micros(), target_block, "Received bundle");
+    if !options.feedback {
+        send_order(order, &results, timeout).await;
+        return Ok(None);
+    }
+    send_order(order.clone(), &results, timeout).await;
+    let sim_result = match fast_sim_lane {
+        Some(fast_sim_lane) => fast_sim_lane.simulate(order, feedback_sim_budget).await,
+        None => None,
+    };
+    Ok(Some(
+        sim_result.map_or(BundleFeedback::Queued, BundleFeedback::from),
+    ))
 }
 
 async fn handle_mev_send_bundle(
@@ -133,15 +308,32 @@ async fn handle_mev_send_bundle(
     let raw_bundle: RawShareBundle = match params.one() {
         Ok(raw_bundle) => raw_bundle,
         Err(err) => {
-            warn!(?err, "Failed to parse raw share bundle");
+            if let Some(repeated_in_last_minute) = rate_limited(
+                "rpc_server.parse_raw_share_bundle",
+                error_discriminant(&err),
+            ) {
+                warn!(
+                    ?err,
+                    repeated_in_last_minute, "Failed to parse raw share bundle"
+                );
+            }
             // @Metric
             return;
         }
     };
-    let decode_res = match raw_bundle.decode(TxEncoding::WithBlobData) {
+    let decode_res = match decode_catching_panics("mev_sendBundle", || {
+        raw_bundle.decode(TxEncoding::WithBlobData)
+    }) {
         Ok(res) => res,
         Err(err) => {
-            warn!(?err, "Failed to verify share bundle");
+            if let Some(repeated_in_last_minute) =
+                rate_limited("rpc_server.verify_share_bundle", error_discriminant(&err))
+            {
+                warn!(
+                    ?err,
+                    repeated_in_last_minute, "Failed to verify share bundle"
+                );
+            }
             // @Metric
             return;
         }
@@ -220,3 +412,286 @@ async fn handle_cancel_bundle(
     )
     .await;
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::live_builder::order_input::http_middleware::{
+        DEFAULT_MAX_BATCH_SIZE, DEFAULT_MAX_BODY_SIZE,
+    };
+    use crate::utils::Signer;
+    use jsonrpsee::types::Params;
+    use reth::primitives::{TransactionKind, TxEip1559, MAINNET};
+    use revm::primitives::MAX_INITCODE_SIZE;
+    use serde_json::json;
+    use std::{net::Ipv4Addr, path::PathBuf};
+    use time::OffsetDateTime;
+
+    const RAW_TX: &str = "0x02f86b0180843b9aca00852ecc889a0082520894c87037874aed04e51c29f582394217a0a2b89d808080c080a0a463985c616dd8ee17d7ef9112af4e6e06a27b071525b42182fe7b0b5c8b4925a00af5ca177ffef2ff28449292505d41be578bebb77110dfc09361d2fb56998260";
+    const BUDGET: Duration = Duration::from_millis(50);
+
+    fn params(tx: &str, options: &str) -> Params<'static> {
+        let json = format!(
+            r#"[{{"blockNumber": "0x1136F1F", "txs": ["{}"], "revertingTxHashes": []}}{}]"#,
+            tx, options
+        );
+        Params::new(Some(Box::leak(json.into_boxed_str())))
+    }
+
+    fn sim_result() -> FastSimResult {
+        FastSimResult {
+            gas_used: 21_000,
+            coinbase_profit: U256::from(1_000),
+            reverted: false,
+            error: None,
+        }
+    }
+
+    /// Lane replying every request with sim_result() after delay.
+    fn fast_sim_lane(delay: Duration) -> FastSimLane {
+        let (lane, requests) = FastSimLane::new(1);
+        std::thread::spawn(move || {
+            while let Ok(request) = requests.recv() {
+                std::thread::sleep(delay);
+                let _ = request.reply.send(sim_result());
+            }
+        });
+        lane
+    }
+
+    async fn send_bundle(
+        fast_sim_lane: Option<FastSimLane>,
+        params: Params<'static>,
+    ) -> (serde_json::Value, usize) {
+        send_bundle_with_static_limits(fast_sim_lane, None, params).await
+    }
+
+    async fn send_bundle_with_static_limits(
+        fast_sim_lane: Option<FastSimLane>,
+        static_limits: Option<Arc<StaticLimits>>,
+        params: Params<'static>,
+    ) -> (serde_json::Value, usize) {
+        let (sender, mut receiver) = mpsc::channel(10);
+        let response = match handle_eth_send_bundle(
+            sender,
+            Duration::from_millis(50),
+            BUDGET,
+            fast_sim_lane,
+            static_limits,
+            params,
+        )
+        .await
+        {
+            Ok(feedback) => serde_json::to_value(feedback).unwrap(),
+            Err(err) => json!({"error": {"code": err.code(), "message": err.message()}}),
+        };
+        let mut orders = 0;
+        while let Ok(ReplaceableOrderPoolCommand::Order(_)) = receiver.try_recv() {
+            orders += 1;
+        }
+        (response, orders)
+    }
+
+    #[tokio::test]
+    async fn test_feedback_within_budget() {
+        let lane = fast_sim_lane(Duration::ZERO);
+        let (response, orders) =
+            send_bundle(Some(lane), params(RAW_TX, r#", {"feedback": true}"#)).await;
+        assert_eq!(orders, 1);
+        assert_eq!(
+            response,
+            serde_json::json!({
+                "status": "simulated",
+                "gasUsed": 21000,
+                "coinbaseProfit": "0x3e8",
+                "reverted": false,
+                "error": null,
+            })
+        );
+
+        let (response, orders) =
+            send_bundle(None, params("0x1234", r#", {"feedback": true}"#)).await;
+        assert_eq!(orders, 0);
+        assert_eq!(response["status"], "rejected");
+        assert!(response["reason"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_feedback_exceeding_budget() {
+        let lane = fast_sim_lane(BUDGET * 4);
+        let (response, orders) =
+            send_bundle(Some(lane), params(RAW_TX, r#", {"feedback": true}"#)).await;
+        assert_eq!(orders, 1);
+        assert_eq!(response, serde_json::json!({"status": "queued"}));
+
+        // no sim lane, same answer
+        let (response, _) = send_bundle(None, params(RAW_TX, r#", {"feedback": true}"#)).await;
+        assert_eq!(response, serde_json::json!({"status": "queued"}));
+    }
+
+    #[tokio::test]
+    async fn test_no_feedback_response() {
+        let lane = fast_sim_lane(Duration::ZERO);
+        for options in ["", r#", {"feedback": false}"#, ", {}"] {
+            let (response, orders) = send_bundle(Some(lane.clone()), params(RAW_TX, options)).await;
+            assert_eq!(orders, 1);
+            assert_eq!(response, serde_json::Value::Null);
+        }
+        // invalid bundles look the same
+        let (response, orders) = send_bundle(Some(lane), params("0x1234", "")).await;
+        assert_eq!(orders, 0);
+        assert_eq!(response, serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn test_feedback_rejects_static_limits() {
+        let tx = Signer::random()
+            .sign_tx(
+                TxEip1559 {
+                    chain_id: 1,
+                    gas_limit: 10_000_000,
+                    to: TransactionKind::Create,
+                    input: vec![0; MAX_INITCODE_SIZE + 1].into(),
+                    ..Default::default()
+                }
+                .into(),
+            )
+            .unwrap();
+        let raw_tx = alloy_primitives::hex::encode_prefixed(tx.envelope_encoded());
+        let feedback = r#", {"feedback": true}"#;
+
+        let static_limits = Arc::new(StaticLimits::new(MAINNET.clone()));
+        static_limits.set_slot_timestamp(OffsetDateTime::now_utc().unix_timestamp() as u64);
+        let (response, orders) =
+            send_bundle_with_static_limits(None, Some(static_limits), params(&raw_tx, feedback))
+                .await;
+        assert_eq!(orders, 0);
+        assert_eq!(response["status"], "rejected");
+        assert!(response["reason"]
+            .as_str()
+            .unwrap()
+            .contains("Initcode size"));
+
+        // limits off
+        let (response, orders) = send_bundle(None, params(&raw_tx, feedback)).await;
+        assert_eq!(orders, 1);
+        assert_eq!(response, json!({"status": "queued"}));
+    }
+
+    #[tokio::test]
+    async fn test_rejection_names_bad_tx_index() {
+        let json = format!(
+            r#"[{{"blockNumber": "0x1136F1F", "txs": ["{}", "0x02f8"], "revertingTxHashes": []}}, {{"feedback": true}}]"#,
+            RAW_TX
+        );
+        let (response, orders) =
+            send_bundle(None, Params::new(Some(Box::leak(json.into_boxed_str())))).await;
+        assert_eq!(orders, 0);
+        assert_eq!(response["status"], "rejected");
+        assert!(response["reason"].as_str().unwrap().contains("idx: 1"));
+    }
+
+    #[test]
+    fn test_decode_panics_become_errors() {
+        let panics = || {
+            telemetry::ORDER_INPUT_DECODE_PANICS
+                .with_label_values(&["test"])
+                .get()
+        };
+        let before = panics();
+        let res: Result<(), String> =
+            decode_catching_panics("test", || -> Result<(), String> { panic!("decoder bug") });
+        assert!(res.is_err());
+        assert_eq!(panics(), before + 1);
+        assert_eq!(
+            decode_catching_panics("test", || Err::<(), _>("bad rlp")),
+            Err("bad rlp".to_string())
+        );
+        assert_eq!(panics(), before + 1);
+    }
+
+    #[tokio::test]
+    async fn test_compressed_batch() {
+        let (sender, mut receiver) = mpsc::channel(10);
+        let config = OrderInputConfig::new(
+            false,
+            false,
+            PathBuf::new(),
+            0,
+            Ipv4Addr::LOCALHOST,
+            16,
+            Duration::from_millis(50),
+            10,
+        );
+        let server = Server::builder()
+            .set_middleware(ServiceBuilder::new().layer(OrderInputHttpLayer::new(
+                DEFAULT_MAX_BODY_SIZE,
+                DEFAULT_MAX_BATCH_SIZE,
+            )))
+            .http_only()
+            .build("127.0.0.1:0")
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        let handle = server.start(order_input_rpc_module(&config, sender).unwrap());
+
+        let send_bundle = |id: u64, tx: &str, feedback: bool| {
+            json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "eth_sendBundle",
+                "params": [
+                    {"blockNumber": "0x1136F1F", "txs": [tx], "revertingTxHashes": []},
+                    {"feedback": feedback},
+                ],
+            })
+        };
+        let post_batch = |feedback: bool| async move {
+            let batch = json!([
+                send_bundle(1, RAW_TX, feedback),
+                send_bundle(2, "0x1234", feedback),
+                send_bundle(3, RAW_TX, feedback)
+            ]);
+            let body = zstd::encode_all(batch.to_string().as_bytes(), 0).unwrap();
+            let response = reqwest::Client::new()
+                .post(format!("http://{}", addr))
+                .header("content-type", "application/json")
+                .header("content-encoding", "zstd")
+                .body(body)
+                .send()
+                .await
+                .unwrap();
+            assert!(response.status().is_success());
+            let response: serde_json::Value =
+                serde_json::from_slice(&response.bytes().await.unwrap()).unwrap();
+            let responses = response.as_array().unwrap().clone();
+            assert_eq!(
+                responses
+                    .iter()
+                    .map(|r| r["id"].clone())
+                    .collect::<Vec<_>>(),
+                vec![json!(1), json!(2), json!(3)]
+            );
+            responses
+        };
+
+        let responses = post_batch(true).await;
+        assert_eq!(responses[0]["result"], json!({"status": "queued"}));
+        assert_eq!(responses[1]["result"]["status"], "rejected");
+        assert!(responses[1]["result"]["reason"].is_string());
+        assert_eq!(responses[2]["result"], json!({"status": "queued"}));
+
+        // without feedback every element gets null as before
+        let responses = post_batch(false).await;
+        for response in &responses {
+            assert_eq!(response["result"], serde_json::Value::Null);
+        }
+
+        let mut orders = 0;
+        while let Ok(ReplaceableOrderPoolCommand::Order(_)) = receiver.try_recv() {
+            orders += 1;
+        }
+        assert_eq!(orders, 4);
+        handle.stop().unwrap();
+    }
+}
@@ -121,6 +121,9 @@ async fn get_tx_with_blobs(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::live_builder::order_input::http_middleware::{
+        DEFAULT_MAX_BATCH_SIZE, DEFAULT_MAX_BODY_SIZE,
+    };
     use alloy_consensus::{SidecarBuilder, SimpleCoder};
     use alloy_network::{EthereumWallet, TransactionBuilder};
     use alloy_node_bindings::Anvil;
@@ -141,6 +144,12 @@ mod test {
             serve_max_connections: 4096,
             server_ip: Ipv4Addr::new(127, 0, 0, 1),
             server_port: 0,
+            bundle_feedback_sim_budget: Duration::from_millis(50),
+            fast_sim_lane: None,
+            max_request_body_size: DEFAULT_MAX_BODY_SIZE,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
+            static_limits: None,
+            private_order_signers: Default::default(),
         }
     }
 
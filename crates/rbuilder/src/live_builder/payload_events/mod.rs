@@ -7,11 +7,13 @@ pub mod relay_epoch_cache;
 
 use crate::{
     beacon_api_client::Client,
+    building::BlockBuildingContext,
     live_builder::payload_events::{
         payload_source::PayloadSourceMuxer,
         relay_epoch_cache::{RelaysForSlotData, SlotData},
     },
     primitives::mev_boost::{MevBoostRelay, MevBoostRelayID},
+    telemetry::inc_skipped_slots,
 };
 use ahash::HashSet;
 use alloy_primitives::{utils::format_ether, Address, B256, U256};
@@ -177,6 +179,48 @@ fn check_slot_data_for_blocklist(
     Ok(())
 }
 
+/// Why LiveBuilder didn't build a slot (skipped_slots metric).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlotSkipReason {
+    FeeRecipientBlocklisted,
+    SlotEnded,
+    ParentHeaderNotFound,
+    /// See [`check_parent_age`].
+    ParentTooOld,
+}
+
+impl SlotSkipReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SlotSkipReason::FeeRecipientBlocklisted => "fee_recipient_blocklisted",
+            SlotSkipReason::SlotEnded => "slot_ended",
+            SlotSkipReason::ParentHeaderNotFound => "parent_header_not_found",
+            SlotSkipReason::ParentTooOld => "parent_too_old",
+        }
+    }
+}
+
+/// Counts the skipped slot.
+pub fn skip_slot(reason: SlotSkipReason) -> SlotSkipReason {
+    inc_skipped_slots(reason.as_str());
+    reason
+}
+
+/// Refuses slots whose parent was older than max_parent_age when we got the attributes (CL hiccups make us build on
+/// heads several slots old and the relays reject those blocks as not building on the canonical head).
+/// None max_parent_age or contexts without parent age (ParentSource::Historical) always pass.
+pub fn check_parent_age(
+    ctx: &BlockBuildingContext,
+    max_parent_age: Option<Duration>,
+) -> Result<(), SlotSkipReason> {
+    match (ctx.parent_age(), max_parent_age) {
+        (Some(parent_age), Some(max_parent_age)) if parent_age > max_parent_age => {
+            Err(skip_slot(SlotSkipReason::ParentTooOld))
+        }
+        _ => Ok(()),
+    }
+}
+
 fn report_slot_withdrawals_to_fee_recipients(data: &MevBoostSlotData) {
     let withdrawals = if let Some(withdrawals) = &data
         .payload_attributes_event
@@ -212,3 +256,71 @@ fn report_slot_withdrawals_to_fee_recipients(data: &MevBoostSlotData) {
         );
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::{
+            testing::test_chain_state::{BlockArgs, TestChainState},
+            ParentSource,
+        },
+        telemetry::SKIPPED_SLOTS,
+    };
+
+    const SLOT_TIMESTAMP: u64 = 1_000_000;
+    const MAX_PARENT_AGE: Duration = Duration::from_secs(36);
+
+    #[test]
+    fn test_old_parent_skips_slot() -> eyre::Result<()> {
+        let skipped = || {
+            SKIPPED_SLOTS
+                .with_label_values(&[SlotSkipReason::ParentTooOld.as_str()])
+                .get()
+        };
+        // attributes arrive 4s after the parent of the previous slot
+        let test_chain =
+            TestChainState::new(BlockArgs::default().number(1).timestamp(SLOT_TIMESTAMP))?;
+        let ctx = test_chain.block_building_context();
+        assert_eq!(ctx.parent_age(), Some(Duration::from_secs(4)));
+        assert_eq!(check_parent_age(ctx, Some(MAX_PARENT_AGE)), Ok(()));
+
+        // parent 5 slots before the one we build on
+        let test_chain = TestChainState::new(
+            BlockArgs::default()
+                .number(1)
+                .timestamp(SLOT_TIMESTAMP)
+                .parent_timestamp(SLOT_TIMESTAMP - 5 * 12),
+        )?;
+        let ctx = test_chain.block_building_context();
+        assert_eq!(ctx.parent_age(), Some(Duration::from_secs(52)));
+        let before = skipped();
+        assert_eq!(
+            check_parent_age(ctx, Some(MAX_PARENT_AGE)),
+            Err(SlotSkipReason::ParentTooOld)
+        );
+        // metrics are global, other tests could skip slots too
+        assert!(skipped() > before);
+
+        // disabled or longer slots
+        assert_eq!(check_parent_age(ctx, None), Ok(()));
+        assert_eq!(check_parent_age(ctx, Some(Duration::from_secs(60))), Ok(()));
+
+        let mut historical = ctx.clone();
+        historical.parent_source = ParentSource::Historical;
+        assert_eq!(historical.parent_age(), None);
+        assert_eq!(check_parent_age(&historical, Some(MAX_PARENT_AGE)), Ok(()));
+
+        // out of range parent timestamps don't panic the slot loop
+        for parent_timestamp in [u64::MAX, i64::MAX as u64] {
+            let mut garbage = ctx.clone();
+            garbage.parent_source = ParentSource::PayloadAttributes {
+                parent_timestamp,
+                received_at: ctx.timestamp(),
+            };
+            assert_eq!(garbage.parent_age(), None);
+            assert_eq!(check_parent_age(&garbage, Some(MAX_PARENT_AGE)), Ok(()));
+        }
+        Ok(())
+    }
+}
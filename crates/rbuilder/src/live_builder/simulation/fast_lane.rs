@@ -0,0 +1,142 @@
+//! Bounded priority lane on the simulation workers used to give eth_sendBundle callers quick feedback about their bundle.
+//! Workers always take fast lane requests before the normal sim queue. The request is simulated on top of the parent
+//! block (no parent orders) of the context the worker is processing so it's only a hint of what the builder will see.
+//! If the lane is full or the result does not arrive within the budget the caller gets nothing and the order just goes
+//! through the normal flow.
+use crate::{
+    building::{
+        sim::OrderSimResult, simulate_order, BlockBuildingContext, BlockState, BundleErr,
+        CriticalCommitOrderError, OrderErr,
+    },
+    primitives::Order,
+};
+use alloy_primitives::U256;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// Max pending fast lane requests, after that we stop taking new ones.
+pub const FAST_SIM_LANE_CAPACITY: usize = 64;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FastSimResult {
+    /// Includes gas consumed by reverting txs.
+    pub gas_used: u64,
+    pub coinbase_profit: U256,
+    /// A tx not allowed to revert reverted.
+    pub reverted: bool,
+    /// Why the order could not be included.
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct FastSimRequest {
+    pub order: Order,
+    pub reply: oneshot::Sender<FastSimResult>,
+}
+
+/// Sending side of the lane, cheap to clone.
+#[derive(Debug, Clone)]
+pub struct FastSimLane {
+    requests: flume::Sender<FastSimRequest>,
+}
+
+impl FastSimLane {
+    pub fn new(capacity: usize) -> (Self, flume::Receiver<FastSimRequest>) {
+        let (requests, receiver) = flume::bounded(capacity);
+        (Self { requests }, receiver)
+    }
+
+    /// None if the lane is full or the simulation does not finish within budget.
+    pub async fn simulate(&self, order: Order, budget: Duration) -> Option<FastSimResult> {
+        let (reply, result) = oneshot::channel();
+        self.requests
+            .try_send(FastSimRequest { order, reply })
+            .ok()?;
+        tokio::time::timeout(budget, result).await.ok()?.ok()
+    }
+}
+
+pub fn simulate_fast_request(
+    order: Order,
+    ctx: &BlockBuildingContext,
+    state: &mut BlockState,
+) -> Result<FastSimResult, CriticalCommitOrderError> {
+    let sim_result = simulate_order(Vec::new(), order, ctx, state)?;
+    let gas_used = sim_result.gas_used;
+    Ok(match sim_result.result {
+        OrderSimResult::Success(simulated_order, _) => FastSimResult {
+            gas_used,
+            coinbase_profit: simulated_order.sim_value.coinbase_profit,
+            reverted: false,
+            error: None,
+        },
+        OrderSimResult::Failed(err) => FastSimResult {
+            gas_used,
+            coinbase_profit: U256::ZERO,
+            reverted: matches!(err, OrderErr::Bundle(BundleErr::TransactionReverted(_))),
+            error: Some(err.to_string()),
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        building::testing::test_chain_state::{BlockArgs, NamedAddr, TestChainState, TxArgs},
+        primitives::{Bundle, TransactionSignedEcRecoveredWithBlobs},
+    };
+
+    fn bundle(test_chain: &TestChainState, txs: Vec<TxArgs>) -> eyre::Result<Order> {
+        let txs = txs
+            .into_iter()
+            .map(|args| {
+                Ok(
+                    TransactionSignedEcRecoveredWithBlobs::new_no_blobs(test_chain.sign_tx(args)?)
+                        .unwrap(),
+                )
+            })
+            .collect::<eyre::Result<Vec<_>>>()?;
+        let mut bundle = Bundle {
+            block: test_chain.block_building_context().block_env.number.to(),
+            min_timestamp: None,
+            max_timestamp: None,
+            txs,
+            reverting_tx_hashes: vec![],
+            hash: Default::default(),
+            uuid: Default::default(),
+            replacement_data: None,
+            signer: None,
+            max_block_position: None,
+            metadata: Default::default(),
+        };
+        bundle.hash_slow();
+        Ok(Order::Bundle(bundle))
+    }
+
+    #[test]
+    fn test_simulate_fast_request() -> eyre::Result<()> {
+        let test_chain = TestChainState::new(BlockArgs::default().number(1))?;
+        let state_provider = test_chain.provider_factory().latest()?;
+
+        let payment = 100_000;
+        let order = bundle(
+            &test_chain,
+            vec![TxArgs::new_send_to_coinbase(NamedAddr::User(0), 0, payment)],
+        )?;
+        let mut state = BlockState::new(&state_provider);
+        let result = simulate_fast_request(order, test_chain.block_building_context(), &mut state)?;
+        assert_eq!(result.error, None);
+        assert!(!result.reverted);
+        assert!(result.gas_used > 0);
+        assert!(result.coinbase_profit >= U256::from(payment));
+
+        let order = bundle(&test_chain, vec![TxArgs::new_revert(NamedAddr::User(0), 0)])?;
+        let mut state = BlockState::new(&state_provider);
+        let result = simulate_fast_request(order, test_chain.block_building_context(), &mut state)?;
+        assert!(result.reverted);
+        assert!(result.error.is_some());
+        assert!(result.gas_used > 0);
+        Ok(())
+    }
+}
@@ -1,8 +1,11 @@
+pub mod fast_lane;
+pub mod overload_filter;
+pub mod priority_lane;
 pub mod sim_worker;
 
 use crate::{
     building::{
-        sim::{SimTree, SimulatedResult, SimulationRequest},
+        sim::{SimTree, SimulatedResult},
         BlockBuildingContext,
     },
     live_builder::order_input::orderpool::OrdersForBlock,
@@ -10,8 +13,13 @@ use crate::{
     utils::{gen_uid, ProviderFactoryReopener},
 };
 use ahash::{HashMap, HashSet};
-use alloy_primitives::utils::format_ether;
+use alloy_primitives::{utils::format_ether, B256};
+use fast_lane::{FastSimLane, FastSimRequest, FAST_SIM_LANE_CAPACITY};
+use overload_filter::{OverloadFilter, OverloadFilterConfig, OverloadFilterDecision};
+use priority_lane::{sim_lanes, SimLanesReceiver, SimLanesSender, SimPriorityLaneConfig};
+use reth::providers::ProviderFactory;
 use reth_db::database::Database;
+use reth_provider::StateProvider;
 use std::{
     fmt,
     sync::{Arc, Mutex},
@@ -35,18 +43,23 @@ pub struct OrderSimulationPool<DB> {
     running_tasks: Arc<Mutex<Vec<JoinHandle<()>>>>,
     current_contexts: Arc<Mutex<CurrentSimulationContexts>>,
     worker_threads: Vec<std::thread::JoinHandle<()>>,
+    overload_filter: Option<OverloadFilterConfig>,
+    priority_lane: Option<SimPriorityLaneConfig>,
+    fast_sim_lane: FastSimLane,
 }
 
 #[derive(Debug, Clone)]
 pub struct SimulationContext {
     pub block_ctx: BlockBuildingContext,
-    pub requests: flume::Receiver<SimulationRequest>,
+    pub requests: SimLanesReceiver,
     pub results: mpsc::Sender<SimulatedResult>,
 }
 
 #[derive(Debug)]
 pub struct CurrentSimulationContexts {
     pub contexts: HashMap<BlockContextId, SimulationContext>,
+    /// Served by the workers before the requests of the contexts (see [`fast_lane`]).
+    pub fast_sim_requests: flume::Receiver<FastSimRequest>,
 }
 
 #[derive(Default)]
@@ -97,7 +110,7 @@ struct SimulationJob<DB> {
     /// Input orders to be simulated
     new_order_sub: mpsc::UnboundedReceiver<OrderPoolCommand>,
     /// Here we send requests to the simulator pool
-    sim_req_sender: flume::Sender<SimulationRequest>,
+    sim_req_sender: SimLanesSender,
     /// Here we receive the results we asked to sim_req_sender
     sim_results_receiver: mpsc::Receiver<SimulatedResult>,
     /// Output of the simulations
@@ -110,6 +123,12 @@ struct SimulationJob<DB> {
     /// Orders we got via new_order_sub and are still being processed (they could be inside the SimTree or in the sim queue)
     /// and were not cancelled.
     in_flight_orders: HashSet<OrderId>,
+
+    /// Sheds heavy orders when the sim queue is too long.
+    overload_filter: Option<OverloadFilter>,
+    /// Used for the overload_filter code size lookups on the parent state.
+    provider_factory: ProviderFactory<DB>,
+    parent: B256,
 }
 
 #[derive(Clone, Debug)]
@@ -269,6 +288,23 @@ impl<DB: Database + Clone + Send + 'static> SimulationJob<DB> {
     fn process_new_order(&mut self, order: Order) -> bool {
         self.orders_received.accumulate(&order);
         let order_id = order.id();
+        if let Some(overload_filter) = &mut self.overload_filter {
+            let provider_factory = &self.provider_factory;
+            let parent = self.parent;
+            let decision =
+                overload_filter.check_order(&order, self.sim_req_sender.len(), |address| {
+                    Ok(provider_factory
+                        .history_by_block_hash(parent)?
+                        .account_code(address)?
+                        .map(|code| code.len())
+                        .unwrap_or_default())
+                });
+            if decision == OverloadFilterDecision::Shed {
+                trace!(?order_id, "Order shed by the overload filter");
+                // not in in_flight_orders so a cancellation will be forwarded, same as when the sim channel is full
+                return true;
+            }
+        }
         if let Err(err) = self.sim_tree.push_orders(vec![order]) {
             error!(?err, "Failed to push order into the sim tree");
             // @Metric
@@ -304,13 +340,18 @@ impl<DB: Database + Clone + Send + 'static> OrderSimulationPool<DB> {
         num_workers: usize,
         global_cancellation: CancellationToken,
     ) -> Self {
+        let (fast_sim_lane, fast_sim_requests) = FastSimLane::new(FAST_SIM_LANE_CAPACITY);
         let mut result = Self {
             provider_factory,
             running_tasks: Arc::new(Mutex::new(Vec::new())),
             current_contexts: Arc::new(Mutex::new(CurrentSimulationContexts {
                 contexts: HashMap::default(),
+                fast_sim_requests,
             })),
             worker_threads: Vec::new(),
+            overload_filter: None,
+            priority_lane: None,
+            fast_sim_lane,
         };
         for i in 0..num_workers {
             let ctx = Arc::clone(&result.current_contexts);
@@ -327,6 +368,27 @@ impl<DB: Database + Clone + Send + 'static> OrderSimulationPool<DB> {
         result
     }
 
+    /// Enables the overload filter (see [`OverloadFilter`]) on all the future simulation jobs.
+    pub fn with_overload_filter(self, overload_filter: Option<OverloadFilterConfig>) -> Self {
+        Self {
+            overload_filter,
+            ..self
+        }
+    }
+
+    /// Enables the urgent lane (see [`priority_lane`]) on all the future simulation jobs.
+    pub fn with_priority_lane(self, priority_lane: Option<SimPriorityLaneConfig>) -> Self {
+        Self {
+            priority_lane,
+            ..self
+        }
+    }
+
+    /// Lane to simulate single orders with priority over the normal flow.
+    pub fn fast_sim_lane(&self) -> FastSimLane {
+        self.fast_sim_lane.clone()
+    }
+
     pub fn spawn_simulation_job(
         &self,
         ctx: BlockBuildingContext,
@@ -338,15 +400,19 @@ impl<DB: Database + Clone + Send + 'static> OrderSimulationPool<DB> {
         let provider = self.provider_factory.provider_factory_unchecked();
 
         let current_contexts = Arc::clone(&self.current_contexts);
+        let overload_filter = self.overload_filter.clone().map(OverloadFilter::new);
+        let priority_lane = self.priority_lane.clone();
         let block_context: BlockContextId = gen_uid();
         let span = info_span!("sim_ctx", block = ctx.block_env.number.to::<u64>(), parent = ?ctx.attributes.parent);
 
         let handle = tokio::spawn(
             async move {
                 debug!("Starting simulation job for parent block");
-                let sim_tree = SimTree::new(provider, ctx.attributes.parent);
+                let parent = ctx.attributes.parent;
+                let sim_tree = SimTree::new(provider.clone(), parent);
                 let new_order_sub = input.new_order_sub;
-                let (sim_req_sender, sim_req_receiver) = flume::unbounded();
+                let (sim_req_sender, sim_req_receiver) =
+                    sim_lanes(ctx.block_env.number.to(), ctx.timestamp(), priority_lane.as_ref());
                 let (sim_results_sender, sim_results_receiver) = mpsc::channel(1024);
                 {
                     let mut contexts = current_contexts.lock().unwrap();
@@ -367,6 +433,9 @@ impl<DB: Database + Clone + Send + 'static> OrderSimulationPool<DB> {
                     orders_received: OrderCounter::default(),
                     orders_simulated_ok: OrderCounter::default(),
                     in_flight_orders: Default::default(),
+                    overload_filter,
+                    provider_factory: provider,
+                    parent,
                 };
 
                 simulation_job.run().await;
@@ -379,6 +448,7 @@ impl<DB: Database + Clone + Send + 'static> OrderSimulationPool<DB> {
                 info!(
                     ?simulation_job.orders_received,
                     ?simulation_job.orders_simulated_ok,
+                    overload_filter = ?simulation_job.overload_filter.as_ref().map(|filter| filter.stats()),
                     "Stopping simulation job "
                 );
             }
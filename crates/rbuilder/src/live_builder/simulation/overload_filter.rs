@@ -0,0 +1,272 @@
+//! Load shedding for the simulation job.
+//! When simulation can't keep up (backpressure: too many requests waiting for a sim worker) we drop orders that are unlikely
+//! to pay for the simulation time they need: a tx with a huge gas limit calling a big contract.
+//! Orders signed by trusted signers are never dropped.
+//! Every decision is counted so we can check what the heuristic costs us.
+use crate::{primitives::Order, telemetry::inc_overload_filter_decisions};
+use ahash::{HashMap, HashSet};
+use alloy_primitives::Address;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct OverloadFilterConfig {
+    /// Number of pending simulation requests from which we consider the simulation overloaded.
+    pub sim_queue_len: usize,
+    /// Only txs with a bigger gas limit are candidates to be shed.
+    pub max_gas_limit: u64,
+    /// Only txs calling contracts with a bigger code size (bytes) are candidates to be shed.
+    pub max_code_size: usize,
+    /// Bundles/sbundles from these signers are never shed.
+    #[serde(default)]
+    pub trusted_signers: Vec<Address>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverloadFilterDecision {
+    /// No overload signal, the filter did not look at the order.
+    NotOverloaded,
+    Trusted,
+    Accepted,
+    Shed,
+}
+
+impl OverloadFilterDecision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OverloadFilterDecision::NotOverloaded => "not_overloaded",
+            OverloadFilterDecision::Trusted => "trusted",
+            OverloadFilterDecision::Accepted => "accepted",
+            OverloadFilterDecision::Shed => "shed",
+        }
+    }
+}
+
+/// Decisions taken during a slot.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OverloadFilterStats {
+    pub not_overloaded: usize,
+    pub trusted: usize,
+    pub accepted: usize,
+    pub shed: usize,
+}
+
+impl OverloadFilterStats {
+    fn add(&mut self, decision: OverloadFilterDecision) {
+        let counter = match decision {
+            OverloadFilterDecision::NotOverloaded => &mut self.not_overloaded,
+            OverloadFilterDecision::Trusted => &mut self.trusted,
+            OverloadFilterDecision::Accepted => &mut self.accepted,
+            OverloadFilterDecision::Shed => &mut self.shed,
+        };
+        *counter += 1;
+    }
+}
+
+/// One per slot (simulation job) since the code sizes are cached for the slot parent state.
+#[derive(Debug)]
+pub struct OverloadFilter {
+    config: OverloadFilterConfig,
+    trusted_signers: HashSet<Address>,
+    /// Code size cache, None -> lookup failed (we never shed on unknown code).
+    code_sizes: HashMap<Address, Option<usize>>,
+    stats: OverloadFilterStats,
+}
+
+impl OverloadFilter {
+    pub fn new(config: OverloadFilterConfig) -> Self {
+        Self {
+            trusted_signers: config.trusted_signers.iter().copied().collect(),
+            config,
+            code_sizes: HashMap::default(),
+            stats: OverloadFilterStats::default(),
+        }
+    }
+
+    pub fn is_overloaded(&self, pending_sim_requests: usize) -> bool {
+        pending_sim_requests >= self.config.sim_queue_len
+    }
+
+    /// code_size is used on cache misses.
+    pub fn check_order(
+        &mut self,
+        order: &Order,
+        pending_sim_requests: usize,
+        mut code_size: impl FnMut(Address) -> eyre::Result<usize>,
+    ) -> OverloadFilterDecision {
+        let decision = if !self.is_overloaded(pending_sim_requests) {
+            OverloadFilterDecision::NotOverloaded
+        } else if order
+            .signer()
+            .is_some_and(|signer| self.trusted_signers.contains(&signer))
+        {
+            OverloadFilterDecision::Trusted
+        } else {
+            let heavy = order.list_txs().iter().any(|(tx, _)| {
+                if tx.tx.gas_limit() <= self.config.max_gas_limit {
+                    return false;
+                }
+                let Some(to) = tx.tx.to() else {
+                    return false;
+                };
+                let size = *self
+                    .code_sizes
+                    .entry(to)
+                    .or_insert_with(|| code_size(to).ok());
+                size.is_some_and(|size| size > self.config.max_code_size)
+            });
+            if heavy {
+                OverloadFilterDecision::Shed
+            } else {
+                OverloadFilterDecision::Accepted
+            }
+        };
+        self.stats.add(decision);
+        inc_overload_filter_decisions(decision.as_str());
+        decision
+    }
+
+    pub fn stats(&self) -> &OverloadFilterStats {
+        &self.stats
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::primitives::{Bundle, MempoolTx, Metadata, TransactionSignedEcRecoveredWithBlobs};
+    use reth::primitives::{
+        Transaction, TransactionKind, TransactionSigned, TransactionSignedEcRecovered, TxLegacy,
+    };
+    use uuid::Uuid;
+
+    const HEAVY_CONTRACT: Address = Address::repeat_byte(0x01);
+    const LIGHT_CONTRACT: Address = Address::repeat_byte(0x02);
+    const TRUSTED_SIGNER: Address = Address::repeat_byte(0x03);
+    const QUEUE_LEN: usize = 100;
+
+    fn config() -> OverloadFilterConfig {
+        OverloadFilterConfig {
+            sim_queue_len: QUEUE_LEN,
+            max_gas_limit: 1_000_000,
+            max_code_size: 10_000,
+            trusted_signers: vec![TRUSTED_SIGNER],
+        }
+    }
+
+    fn code_size(address: Address) -> eyre::Result<usize> {
+        Ok(if address == HEAVY_CONTRACT {
+            20_000
+        } else {
+            100
+        })
+    }
+
+    fn tx(to: Address, gas_limit: u64) -> TransactionSignedEcRecoveredWithBlobs {
+        let tx = TransactionSignedEcRecovered::from_signed_transaction(
+            TransactionSigned {
+                transaction: Transaction::Legacy(TxLegacy {
+                    gas_limit,
+                    to: TransactionKind::Call(to),
+                    ..TxLegacy::default()
+                }),
+                ..Default::default()
+            },
+            Address::repeat_byte(0x10),
+        );
+        TransactionSignedEcRecoveredWithBlobs::new_no_blobs(tx).unwrap()
+    }
+
+    fn tx_order(to: Address, gas_limit: u64) -> Order {
+        Order::Tx(MempoolTx::new(tx(to, gas_limit)))
+    }
+
+    fn bundle_order(to: Address, gas_limit: u64, signer: Address) -> Order {
+        Order::Bundle(Bundle {
+            block: 1,
+            min_timestamp: None,
+            max_timestamp: None,
+            txs: vec![tx(to, gas_limit)],
+            reverting_tx_hashes: vec![],
+            hash: Default::default(),
+            uuid: Uuid::new_v4(),
+            replacement_data: None,
+            signer: Some(signer),
+            max_block_position: None,
+            metadata: Metadata::default(),
+        })
+    }
+
+    #[test]
+    fn test_only_active_under_overload() {
+        let mut filter = OverloadFilter::new(config());
+        let heavy_order = tx_order(HEAVY_CONTRACT, 5_000_000);
+        assert_eq!(
+            filter.check_order(&heavy_order, QUEUE_LEN - 1, code_size),
+            OverloadFilterDecision::NotOverloaded
+        );
+        assert_eq!(
+            filter.check_order(&heavy_order, QUEUE_LEN, code_size),
+            OverloadFilterDecision::Shed
+        );
+        // needs both a big gas limit and a big contract
+        assert_eq!(
+            filter.check_order(&tx_order(HEAVY_CONTRACT, 100_000), QUEUE_LEN, code_size),
+            OverloadFilterDecision::Accepted
+        );
+        assert_eq!(
+            filter.check_order(&tx_order(LIGHT_CONTRACT, 5_000_000), QUEUE_LEN, code_size),
+            OverloadFilterDecision::Accepted
+        );
+        assert_eq!(
+            filter.stats(),
+            &OverloadFilterStats {
+                not_overloaded: 1,
+                trusted: 0,
+                accepted: 2,
+                shed: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_trusted_signers_are_never_shed() {
+        let mut filter = OverloadFilter::new(config());
+        let trusted = bundle_order(HEAVY_CONTRACT, 5_000_000, TRUSTED_SIGNER);
+        let untrusted = bundle_order(HEAVY_CONTRACT, 5_000_000, Address::repeat_byte(0x04));
+        for pending in [0, QUEUE_LEN, 10 * QUEUE_LEN] {
+            assert_ne!(
+                filter.check_order(&trusted, pending, code_size),
+                OverloadFilterDecision::Shed
+            );
+        }
+        assert_eq!(
+            filter.check_order(&trusted, QUEUE_LEN, code_size),
+            OverloadFilterDecision::Trusted
+        );
+        assert_eq!(
+            filter.check_order(&untrusted, QUEUE_LEN, code_size),
+            OverloadFilterDecision::Shed
+        );
+    }
+
+    #[test]
+    fn test_code_size_is_cached() {
+        let mut filter = OverloadFilter::new(config());
+        let mut lookups = 0;
+        for _ in 0..3 {
+            filter.check_order(&tx_order(HEAVY_CONTRACT, 5_000_000), QUEUE_LEN, |address| {
+                lookups += 1;
+                code_size(address)
+            });
+        }
+        assert_eq!(lookups, 1);
+        // failed lookups don't shed
+        assert_eq!(
+            filter.check_order(&tx_order(LIGHT_CONTRACT, 5_000_000), QUEUE_LEN, |_| {
+                Err(eyre::eyre!("db error"))
+            }),
+            OverloadFilterDecision::Accepted
+        );
+    }
+}
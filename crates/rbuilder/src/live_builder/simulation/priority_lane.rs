@@ -0,0 +1,407 @@
+//! Deadline aware scheduling of the simulation requests of a slot.
+//! An order that can only land on the block we are building is worthless if simulated after the slot so, once the
+//! slot is in its last urgent_window_ms, it goes to the urgent lane. Orders that can still land on later blocks (mempool
+//! txs, sbundles with a wider block range) and all the orders arriving earlier in the slot (there is time for them)
+//! go to the normal lane.
+//! Workers take urgent requests first but the normal lane earns credit with every urgent request taken and takes a turn
+//! (if it has requests) when it can pay for it, so it always gets at least normal_min_share_percent of the simulations.
+//! Disabled (no config) every request goes to the normal lane, same as a single fifo queue.
+use crate::{
+    building::sim::SimulationRequest, primitives::Order, telemetry::add_sim_queue_latency,
+};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use time::OffsetDateTime;
+
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SimPriorityLaneConfig {
+    /// Min share of the simulations that go to the normal lane while it has requests, (0, 100).
+    pub normal_min_share_percent: u64,
+    /// Single block orders are urgent only when they arrive less than this before the slot timestamp.
+    pub urgent_window_ms: u64,
+}
+
+impl Default for SimPriorityLaneConfig {
+    fn default() -> Self {
+        Self {
+            normal_min_share_percent: 20,
+            urgent_window_ms: 4000,
+        }
+    }
+}
+
+impl SimPriorityLaneConfig {
+    /// normal_min_share_percent clamped to (0, 100].
+    pub fn normal_share_percent(&self) -> u64 {
+        self.normal_min_share_percent.clamp(1, 100)
+    }
+
+    pub fn urgent_window(&self) -> Duration {
+        Duration::from_millis(self.urgent_window_ms)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SimUrgency {
+    /// Last block it can land on is the one being built.
+    Urgent,
+    Normal,
+}
+
+impl SimUrgency {
+    pub fn new(order: &Order, block: u64) -> Self {
+        let last_block = match order {
+            Order::Bundle(bundle) => bundle.block,
+            Order::ShareBundle(bundle) => bundle.max_block,
+            Order::Tx(_) => return SimUrgency::Normal,
+        };
+        if last_block <= block {
+            SimUrgency::Urgent
+        } else {
+            SimUrgency::Normal
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SimUrgency::Urgent => "urgent",
+            SimUrgency::Normal => "normal",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct QueuedSimRequest {
+    pub request: SimulationRequest,
+    pub urgency: SimUrgency,
+    pub queued_at: Instant,
+}
+
+/// Job side of the lanes of a slot.
+#[derive(Debug)]
+pub struct SimLanesSender {
+    urgent: flume::Sender<QueuedSimRequest>,
+    normal: flume::Sender<QueuedSimRequest>,
+    /// Block being built.
+    block: u64,
+    slot_timestamp: OffsetDateTime,
+    /// None: disabled.
+    urgent_window: Option<Duration>,
+}
+
+/// Worker side of the lanes of a slot, cheap to clone.
+#[derive(Debug, Clone)]
+pub struct SimLanesReceiver {
+    pub urgent: flume::Receiver<QueuedSimRequest>,
+    pub normal: flume::Receiver<QueuedSimRequest>,
+    /// See [`SimPriorityLaneConfig::normal_share_percent`], 100 when disabled.
+    normal_share_percent: u64,
+}
+
+/// Unbounded lanes for the requests of block (proposed at slot_timestamp).
+pub fn sim_lanes(
+    block: u64,
+    slot_timestamp: OffsetDateTime,
+    config: Option<&SimPriorityLaneConfig>,
+) -> (SimLanesSender, SimLanesReceiver) {
+    let (urgent_sender, urgent) = flume::unbounded();
+    let (normal_sender, normal) = flume::unbounded();
+    (
+        SimLanesSender {
+            urgent: urgent_sender,
+            normal: normal_sender,
+            block,
+            slot_timestamp,
+            urgent_window: config.map(|c| c.urgent_window()),
+        },
+        SimLanesReceiver {
+            urgent,
+            normal,
+            normal_share_percent: config.map(|c| c.normal_share_percent()).unwrap_or(100),
+        },
+    )
+}
+
+impl SimLanesSender {
+    pub fn try_send(
+        &self,
+        request: SimulationRequest,
+    ) -> Result<(), flume::TrySendError<SimulationRequest>> {
+        let urgency = if self.in_urgent_window() {
+            SimUrgency::new(&request.order, self.block)
+        } else {
+            SimUrgency::Normal
+        };
+        let lane = match urgency {
+            SimUrgency::Urgent => &self.urgent,
+            SimUrgency::Normal => &self.normal,
+        };
+        lane.try_send(QueuedSimRequest {
+            request,
+            urgency,
+            queued_at: Instant::now(),
+        })
+        .map_err(|err| match err {
+            flume::TrySendError::Full(queued) => flume::TrySendError::Full(queued.request),
+            flume::TrySendError::Disconnected(queued) => {
+                flume::TrySendError::Disconnected(queued.request)
+            }
+        })
+    }
+
+    fn in_urgent_window(&self) -> bool {
+        match self.urgent_window {
+            Some(urgent_window) => self.slot_timestamp - OffsetDateTime::now_utc() < urgent_window,
+            None => false,
+        }
+    }
+
+    /// Requests waiting for a worker on both lanes.
+    pub fn len(&self) -> usize {
+        self.urgent.len() + self.normal.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Per worker state of the lane selection.
+/// Every urgent request taken on an urgent turn gives normal_share_percent credit to the normal lane, a normal turn
+/// costs 100 - normal_share_percent (eg: 20% -> a normal turn after 4 urgent requests, 60% -> 3 normal turns every
+/// 2 urgent requests).
+#[derive(Debug, Default)]
+pub struct LaneScheduler {
+    normal_credit: u64,
+}
+
+impl LaneScheduler {
+    /// Next request without blocking, None if both lanes are empty.
+    /// Records the time the request waited on its lane.
+    pub fn try_recv(&mut self, lanes: &SimLanesReceiver) -> Option<QueuedSimRequest> {
+        let normal_turn = self.normal_turn(lanes);
+        let queued = if normal_turn {
+            lanes.normal.try_recv().or_else(|_| lanes.urgent.try_recv())
+        } else {
+            lanes.urgent.try_recv().or_else(|_| lanes.normal.try_recv())
+        }
+        .ok()?;
+        self.taken(lanes, &queued);
+        Some(queued)
+    }
+
+    fn normal_turn_cost(lanes: &SimLanesReceiver) -> u64 {
+        100 - lanes.normal_share_percent
+    }
+
+    fn normal_turn(&self, lanes: &SimLanesReceiver) -> bool {
+        self.normal_credit >= Self::normal_turn_cost(lanes)
+    }
+
+    /// Call for requests taken from the lanes by other means (eg: blocking wait).
+    pub fn taken(&mut self, lanes: &SimLanesReceiver, queued: &QueuedSimRequest) {
+        match queued.urgency {
+            // an urgent request on a normal turn means the normal lane was empty, it does not earn more credit
+            SimUrgency::Urgent => {
+                if !self.normal_turn(lanes) {
+                    self.normal_credit += lanes.normal_share_percent;
+                }
+            }
+            SimUrgency::Normal => {
+                self.normal_credit = self
+                    .normal_credit
+                    .saturating_sub(Self::normal_turn_cost(lanes))
+            }
+        }
+        add_sim_queue_latency(queued.urgency.as_str(), queued.queued_at.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::primitives::{Bundle, ShareBundle, ShareBundleInner};
+
+    const BLOCK: u64 = 10;
+
+    /// Slot timestamp 1s from now.
+    fn in_urgent_window() -> OffsetDateTime {
+        OffsetDateTime::now_utc() + Duration::from_secs(1)
+    }
+
+    fn bundle(block: u64) -> Order {
+        Order::Bundle(Bundle {
+            block,
+            min_timestamp: None,
+            max_timestamp: None,
+            txs: Vec::new(),
+            reverting_tx_hashes: Vec::new(),
+            hash: Default::default(),
+            uuid: uuid::Uuid::new_v4(),
+            replacement_data: None,
+            signer: None,
+            max_block_position: None,
+            metadata: Default::default(),
+        })
+    }
+
+    fn share_bundle(block: u64, max_block: u64) -> Order {
+        Order::ShareBundle(ShareBundle {
+            hash: rand::random(),
+            block,
+            max_block,
+            inner_bundle: ShareBundleInner {
+                body: Vec::new(),
+                refund: Vec::new(),
+                refund_config: Vec::new(),
+                can_skip: false,
+                original_order_id: None,
+            },
+            signer: None,
+            replacement_data: None,
+            original_orders: Vec::new(),
+            metadata: Default::default(),
+        })
+    }
+
+    fn request(order: Order) -> SimulationRequest {
+        SimulationRequest {
+            id: rand::random(),
+            order,
+            parents: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_urgency() {
+        assert_eq!(SimUrgency::new(&bundle(BLOCK), BLOCK), SimUrgency::Urgent);
+        assert_eq!(
+            SimUrgency::new(&share_bundle(BLOCK, BLOCK), BLOCK),
+            SimUrgency::Urgent
+        );
+        assert_eq!(
+            SimUrgency::new(&share_bundle(BLOCK, BLOCK + 5), BLOCK),
+            SimUrgency::Normal
+        );
+    }
+
+    #[test]
+    fn test_single_block_order_goes_first() {
+        let config = SimPriorityLaneConfig {
+            normal_min_share_percent: 20,
+            ..Default::default()
+        };
+        let (sender, lanes) = sim_lanes(BLOCK, in_urgent_window(), Some(&config));
+        for _ in 0..3 {
+            sender
+                .try_send(request(share_bundle(BLOCK, BLOCK + 5)))
+                .unwrap();
+        }
+        let urgent = bundle(BLOCK);
+        sender.try_send(request(urgent.clone())).unwrap();
+        assert_eq!(sender.len(), 4);
+
+        let mut scheduler = LaneScheduler::default();
+        assert_eq!(
+            scheduler.try_recv(&lanes).unwrap().request.order.id(),
+            urgent.id()
+        );
+        for _ in 0..3 {
+            assert_eq!(
+                scheduler.try_recv(&lanes).unwrap().urgency,
+                SimUrgency::Normal
+            );
+        }
+        assert!(scheduler.try_recv(&lanes).is_none());
+
+        // disabled, arrival order
+        let (sender, lanes) = sim_lanes(BLOCK, in_urgent_window(), None);
+        let normal = share_bundle(BLOCK, BLOCK + 5);
+        sender.try_send(request(normal.clone())).unwrap();
+        sender.try_send(request(urgent)).unwrap();
+        assert_eq!(
+            LaneScheduler::default()
+                .try_recv(&lanes)
+                .unwrap()
+                .request
+                .order
+                .id(),
+            normal.id()
+        );
+    }
+
+    #[test]
+    fn test_normal_lane_progresses() {
+        for (normal_min_share_percent, expected_normal) in [(20, 20), (50, 50), (60, 60), (99, 99)]
+        {
+            let config = SimPriorityLaneConfig {
+                normal_min_share_percent,
+                ..Default::default()
+            };
+            let (sender, lanes) = sim_lanes(BLOCK, in_urgent_window(), Some(&config));
+            for _ in 0..100 {
+                sender.try_send(request(bundle(BLOCK))).unwrap();
+                sender
+                    .try_send(request(share_bundle(BLOCK, BLOCK + 5)))
+                    .unwrap();
+            }
+            let mut scheduler = LaneScheduler::default();
+            let normal = (0..100)
+                .filter(|_| scheduler.try_recv(&lanes).unwrap().urgency == SimUrgency::Normal)
+                .count();
+            assert_eq!(normal, expected_normal, "{normal_min_share_percent}%");
+        }
+    }
+
+    #[test]
+    fn test_normal_lane_empty_earns_no_credit() {
+        let config = SimPriorityLaneConfig::default();
+        let (sender, lanes) = sim_lanes(BLOCK, in_urgent_window(), Some(&config));
+        let mut scheduler = LaneScheduler::default();
+        for _ in 0..20 {
+            sender.try_send(request(bundle(BLOCK))).unwrap();
+            scheduler.try_recv(&lanes).unwrap();
+        }
+        // the normal lane gets one turn, not a burst for all the urgent requests taken while it was empty
+        for _ in 0..10 {
+            sender.try_send(request(bundle(BLOCK))).unwrap();
+            sender
+                .try_send(request(share_bundle(BLOCK, BLOCK + 5)))
+                .unwrap();
+        }
+        let urgencies: Vec<_> = (0..6)
+            .map(|_| scheduler.try_recv(&lanes).unwrap().urgency)
+            .collect();
+        assert_eq!(
+            urgencies,
+            [
+                SimUrgency::Normal,
+                SimUrgency::Urgent,
+                SimUrgency::Urgent,
+                SimUrgency::Urgent,
+                SimUrgency::Urgent,
+                SimUrgency::Normal
+            ]
+        );
+    }
+
+    #[test]
+    fn test_urgent_only_in_urgent_window() {
+        let config = SimPriorityLaneConfig::default();
+        let early = OffsetDateTime::now_utc() + config.urgent_window() + Duration::from_secs(4);
+        let (sender, lanes) = sim_lanes(BLOCK, early, Some(&config));
+        sender.try_send(request(bundle(BLOCK))).unwrap();
+        assert_eq!(
+            LaneScheduler::default().try_recv(&lanes).unwrap().urgency,
+            SimUrgency::Normal
+        );
+
+        let (sender, lanes) = sim_lanes(BLOCK, in_urgent_window(), Some(&config));
+        sender.try_send(request(bundle(BLOCK))).unwrap();
+        assert_eq!(
+            LaneScheduler::default().try_recv(&lanes).unwrap().urgency,
+            SimUrgency::Urgent
+        );
+    }
+}
@@ -1,13 +1,19 @@
 use crate::{
     building::{
+        pre_block_call,
         sim::{NonceKey, OrderSimResult, SimulatedResult},
         simulate_order, BlockState,
     },
-    live_builder::simulation::CurrentSimulationContexts,
+    live_builder::simulation::{
+        fast_lane::{simulate_fast_request, FastSimRequest},
+        priority_lane::{LaneScheduler, QueuedSimRequest},
+        BlockContextId, CurrentSimulationContexts,
+    },
     telemetry,
     telemetry::add_sim_thread_utilisation_timings,
     utils::ProviderFactoryReopener,
 };
+use ahash::HashMap;
 use reth_db::database::Database;
 use reth_payload_builder::database::CachedReads;
 use std::{
@@ -18,20 +24,42 @@ use std::{
 use tokio_util::sync::CancellationToken;
 use tracing::error;
 
+/// A context whose state can't be opened (eg: its parent was reorged or pruned) is not picked again before this so we
+/// don't spin on it until it's replaced.
+const FAILED_CONTEXT_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+enum SimTask {
+    /// Sent via [`crate::live_builder::simulation::fast_lane::FastSimLane`].
+    Fast(FastSimRequest),
+    /// Sent via [`crate::live_builder::simulation::priority_lane::SimLanesSender`].
+    Normal(QueuedSimRequest),
+}
+
 pub fn run_sim_worker<DB: Database + Clone + Send + 'static>(
     worker_id: usize,
     ctx: Arc<Mutex<CurrentSimulationContexts>>,
     provider_factory: ProviderFactoryReopener<DB>,
     global_cancellation: CancellationToken,
 ) {
+    let fast_sim_requests = ctx.lock().unwrap().fast_sim_requests.clone();
+    // context -> last time we failed to open its state
+    let mut failed_contexts: HashMap<BlockContextId, Instant> = HashMap::default();
     loop {
         if global_cancellation.is_cancelled() {
             return;
         }
-        let current_sim_context = loop {
+        let (context_id, current_sim_context) = loop {
             let next_ctx = {
                 let ctxs = ctx.lock().unwrap();
-                ctxs.contexts.iter().next().map(|(_, c)| c.clone())
+                failed_contexts.retain(|id, _| ctxs.contexts.contains_key(id));
+                ctxs.contexts
+                    .iter()
+                    .find(|(id, _)| {
+                        failed_contexts.get(*id).map_or(true, |failed_at| {
+                            failed_at.elapsed() >= FAILED_CONTEXT_RETRY_DELAY
+                        })
+                    })
+                    .map(|(id, c)| (*id, c.clone()))
             };
             // @Perf chose random context so its more fair when we have 2 instead of 1
             if let Some(ctx) = next_ctx {
@@ -48,67 +76,116 @@ pub fn run_sim_worker<DB: Database + Clone + Send + 'static>(
             Ok(provider_factory) => provider_factory,
             Err(err) => {
                 error!(?err, "Error while reopening provider factory");
+                failed_contexts.insert(context_id, Instant::now());
                 continue;
             }
         };
 
-        let mut cached_reads = CachedReads::default();
+        let state_provider = match provider_factory
+            .history_by_block_hash(current_sim_context.block_ctx.attributes.parent)
+        {
+            Ok(state_provider) => state_provider,
+            Err(err) => {
+                error!(?err, "Error while getting state for block");
+                // @Metric
+                failed_contexts.insert(context_id, Instant::now());
+                continue;
+            }
+        };
+        // One state for all the simulations of the context (each one is rolled back), it starts with the block
+        // pre block call as the block does.
+        let mut block_state =
+            BlockState::new(&state_provider).with_cached_reads(CachedReads::default());
+        if let Err(err) = pre_block_call(&current_sim_context.block_ctx, &mut block_state) {
+            error!(?err, "Error while applying pre block call");
+            // @Metric
+            failed_contexts.insert(context_id, Instant::now());
+            continue;
+        }
+        failed_contexts.remove(&context_id);
         let mut last_sim_finished = Instant::now();
-        while let Ok(task) = current_sim_context.requests.recv() {
+        let mut lane_scheduler = LaneScheduler::default();
+        let lanes = &current_sim_context.requests;
+        loop {
+            // fast lane always goes first, then the urgent/normal lanes (see [`LaneScheduler`])
+            let task = if let Ok(request) = fast_sim_requests.try_recv() {
+                SimTask::Fast(request)
+            } else if let Some(queued) = lane_scheduler.try_recv(lanes) {
+                SimTask::Normal(queued)
+            } else {
+                let task = match flume::Selector::new()
+                    .recv(&fast_sim_requests, |res| res.map(SimTask::Fast))
+                    .recv(&lanes.urgent, |res| res.map(SimTask::Normal))
+                    .recv(&lanes.normal, |res| res.map(SimTask::Normal))
+                    .wait()
+                {
+                    Ok(task) => task,
+                    Err(_) => break,
+                };
+                if let SimTask::Normal(queued) = &task {
+                    lane_scheduler.taken(lanes, queued);
+                }
+                task
+            };
             let sim_thread_wait_time = last_sim_finished.elapsed();
             let sim_start = Instant::now();
-
-            let state_provider = match provider_factory
-                .history_by_block_hash(current_sim_context.block_ctx.attributes.parent)
-            {
-                Ok(state_provider) => state_provider,
-                Err(err) => {
-                    error!(?err, "Error while getting state for block");
-                    // break here so we can try to get new context
-                    // @Metric
-                    break;
+            match task {
+                SimTask::Fast(request) => {
+                    match simulate_fast_request(
+                        request.order,
+                        &current_sim_context.block_ctx,
+                        &mut block_state,
+                    ) {
+                        Ok(result) => {
+                            // caller may not be waiting anymore
+                            let _ = request.reply.send(result);
+                        }
+                        Err(err) => {
+                            error!(?err, "Critical error while simulating fast lane order");
+                            break;
+                        }
+                    }
                 }
-            };
-            let start_time = Instant::now();
-            let mut block_state = BlockState::new(&state_provider).with_cached_reads(cached_reads);
-            let sim_result = simulate_order(
-                task.parents.clone(),
-                task.order,
-                &current_sim_context.block_ctx,
-                &mut block_state,
-            );
-            match sim_result {
-                Ok(sim_result) => {
-                    let sim_ok = match sim_result.result {
-                        OrderSimResult::Success(simulated_order, nonces_after) => {
-                            let result = SimulatedResult {
-                                id: task.id,
-                                simulated_order,
-                                previous_orders: task.parents,
-                                nonces_after: nonces_after
-                                    .into_iter()
-                                    .map(|(address, nonce)| NonceKey { address, nonce })
-                                    .collect(),
-                                simulation_time: start_time.elapsed(),
+                SimTask::Normal(QueuedSimRequest { request: task, .. }) => {
+                    let sim_result = simulate_order(
+                        task.parents.clone(),
+                        task.order,
+                        &current_sim_context.block_ctx,
+                        &mut block_state,
+                    );
+                    match sim_result {
+                        Ok(sim_result) => {
+                            let sim_ok = match sim_result.result {
+                                OrderSimResult::Success(simulated_order, nonces_after) => {
+                                    let result = SimulatedResult {
+                                        id: task.id,
+                                        simulated_order,
+                                        previous_orders: task.parents,
+                                        nonces_after: nonces_after
+                                            .into_iter()
+                                            .map(|(address, nonce)| NonceKey { address, nonce })
+                                            .collect(),
+                                        simulation_time: sim_start.elapsed(),
+                                    };
+                                    current_sim_context
+                                        .results
+                                        .try_send(result)
+                                        .unwrap_or_default();
+                                    true
+                                }
+                                OrderSimResult::Failed(_) => false,
                             };
-                            current_sim_context
-                                .results
-                                .try_send(result)
-                                .unwrap_or_default();
-                            true
+                            telemetry::inc_simulated_orders(sim_ok);
+                            telemetry::inc_simulation_gas_used(sim_result.gas_used);
                         }
-                        OrderSimResult::Failed(_) => false,
-                    };
-                    telemetry::inc_simulated_orders(sim_ok);
-                    telemetry::inc_simulation_gas_used(sim_result.gas_used);
-                }
-                Err(err) => {
-                    error!(?err, "Critical error while simulating order");
-                    // @Metric
-                    break;
+                        Err(err) => {
+                            error!(?err, "Critical error while simulating order");
+                            // @Metric
+                            break;
+                        }
+                    }
                 }
             }
-            (cached_reads, _) = block_state.into_parts();
 
             last_sim_finished = Instant::now();
             let sim_thread_work_time = sim_start.elapsed();
@@ -618,6 +618,35 @@ impl SubmitBlockRequest {
             SubmitBlockRequest::Deneb(req) => req.0.message.clone(),
         }
     }
+
+    /// Coinbase of the block.
+    pub fn fee_recipient(&self) -> Address {
+        match self {
+            SubmitBlockRequest::Capella(req) => req.0.execution_payload.payload_inner.fee_recipient,
+            SubmitBlockRequest::Deneb(req) => {
+                req.0
+                    .execution_payload
+                    .payload_inner
+                    .payload_inner
+                    .fee_recipient
+            }
+        }
+    }
+
+    pub fn extra_data(&self) -> Bytes {
+        match self {
+            SubmitBlockRequest::Capella(req) => {
+                req.0.execution_payload.payload_inner.extra_data.clone()
+            }
+            SubmitBlockRequest::Deneb(req) => req
+                .0
+                .execution_payload
+                .payload_inner
+                .payload_inner
+                .extra_data
+                .clone(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -4,6 +4,7 @@ pub mod fmt;
 pub mod mev_boost;
 pub mod order_builder;
 pub mod serialize;
+pub mod static_limits;
 mod test_data_generator;
 
 use crate::building::evm_inspector::UsedStateTrace;
@@ -20,6 +21,7 @@ use reth::primitives::{
 use revm::primitives::U256;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use static_limits::MAX_RAW_TX_SIZE;
 use std::{cmp::Ordering, collections::HashMap, fmt::Display, str::FromStr, sync::Arc};
 pub use test_data_generator::TestDataGenerator;
 use thiserror::Error;
@@ -29,12 +31,18 @@ use uuid::Uuid;
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Metadata {
     pub received_at_timestamp: time::OffsetDateTime,
+    /// The sender requires we never persist the raw txs of this order.
+    /// Execution uses the real txs but Debug of the txs only shows their hashes and artifacts must be written
+    /// with [`serialize::RawOrder::encode_redacted`].
+    /// Set on the order and on all its txs (see [`Order::set_private`]).
+    pub private: bool,
 }
 
 impl Metadata {
     pub fn with_current_received_at() -> Self {
         Self {
             received_at_timestamp: time::OffsetDateTime::now_utc(),
+            private: false,
         }
     }
 }
@@ -115,14 +123,31 @@ pub struct Bundle {
     /// Bundle signer is redundant with self.signer.
     pub replacement_data: Option<BundleReplacementData>,
     pub signer: Option<Address>,
+    /// If Some the bundle can only be included if its first tx lands at a block position <= max_block_position (0 = top of block).
+    /// Every tx already in the block counts (there is no special section for any tx), the anchor tx takes position 0.
+    /// The payout tx goes after all the orders so it never takes a position, its gas is reserved before the fill so
+    /// position constrained bundles only get the gas left like any other order.
+    pub max_block_position: Option<usize>,
 
     #[derivative(PartialEq = "ignore", Hash = "ignore")]
     pub metadata: Metadata,
 }
 
 impl Bundle {
-    pub fn can_execute_with_block_base_fee(&self, block_base_fee: u128) -> bool {
-        can_execute_with_block_base_fee(self.list_txs(), block_base_fee)
+    /// See [`Order::set_private`].
+    pub fn set_private(&mut self) {
+        self.metadata.private = true;
+        for tx in &mut self.txs {
+            tx.metadata.private = true;
+        }
+    }
+
+    pub fn can_execute_with_block_base_fee(
+        &self,
+        block_base_fee: u128,
+        blob_base_fee: Option<u128>,
+    ) -> bool {
+        can_execute_with_block_base_fee(self.list_txs(), block_base_fee, blob_base_fee)
     }
 
     /// BundledTxInfo for all the child txs.
@@ -278,6 +303,15 @@ pub struct ShareBundleInner {
 }
 
 impl ShareBundleInner {
+    fn set_private(&mut self) {
+        for body in &mut self.body {
+            match body {
+                ShareBundleBody::Tx(sbundle_tx) => sbundle_tx.tx.metadata.private = true,
+                ShareBundleBody::Bundle(inner) => inner.set_private(),
+            }
+        }
+    }
+
     fn list_txs(&self) -> Vec<(&TransactionSignedEcRecoveredWithBlobs, bool)> {
         self.body
             .iter()
@@ -361,8 +395,21 @@ pub struct ShareBundle {
 }
 
 impl ShareBundle {
-    pub fn can_execute_with_block_base_fee(&self, block_base_fee: u128) -> bool {
-        can_execute_with_block_base_fee(self.list_txs(), block_base_fee)
+    /// See [`Order::set_private`].
+    pub fn set_private(&mut self) {
+        self.metadata.private = true;
+        self.inner_bundle.set_private();
+        for order in &mut self.original_orders {
+            order.set_private();
+        }
+    }
+
+    pub fn can_execute_with_block_base_fee(
+        &self,
+        block_base_fee: u128,
+        blob_base_fee: Option<u128>,
+    ) -> bool {
+        can_execute_with_block_base_fee(self.list_txs(), block_base_fee, blob_base_fee)
     }
 
     pub fn list_txs(&self) -> Vec<(&TransactionSignedEcRecoveredWithBlobs, bool)> {
@@ -398,7 +445,7 @@ impl ShareBundle {
 /// First idea to handle blobs might change.
 /// Don't like the fact that blobs_sidecar exists no matter if TransactionSignedEcRecovered contains a non blob tx.
 #[derive(Derivative)]
-#[derivative(Debug, Clone, PartialEq, Eq)]
+#[derivative(Clone, PartialEq, Eq)]
 pub struct TransactionSignedEcRecoveredWithBlobs {
     pub tx: TransactionSignedEcRecovered,
     /// Will have a non empty BlobTransactionSidecar if TransactionSignedEcRecovered is 4844
@@ -408,6 +455,24 @@ pub struct TransactionSignedEcRecoveredWithBlobs {
     pub metadata: Metadata,
 }
 
+/// Txs of private orders (see [`Metadata::private`]) only show their hash so logs never get their contents.
+impl std::fmt::Debug for TransactionSignedEcRecoveredWithBlobs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.metadata.private {
+            return f
+                .debug_struct("TransactionSignedEcRecoveredWithBlobs")
+                .field("hash", &self.hash())
+                .field("metadata", &self.metadata)
+                .finish_non_exhaustive();
+        }
+        f.debug_struct("TransactionSignedEcRecoveredWithBlobs")
+            .field("tx", &self.tx)
+            .field("blobs_sidecar", &self.blobs_sidecar)
+            .field("metadata", &self.metadata)
+            .finish()
+    }
+}
+
 impl AsRef<TransactionSignedEcRecovered> for TransactionSignedEcRecoveredWithBlobs {
     fn as_ref(&self) -> &TransactionSignedEcRecovered {
         &self.tx
@@ -426,8 +491,26 @@ pub enum RawTxWithBlobsConvertError {
     FailedToDecodeTransaction(alloy_rlp::Error),
     #[error("Invalid transaction signature")]
     InvalidTransactionSignature,
-    #[error("Invalid transaction signature")]
+    #[error("Unexpected error")]
     UnexpectedError,
+    #[error("Raw transaction size {size} over {max} bytes")]
+    RawTxTooLarge { size: usize, max: usize },
+}
+
+/// Checked before decoding so malformed/huge inputs never reach the decoder.
+fn check_raw_tx_size(raw_tx: &[u8]) -> Result<(), RawTxWithBlobsConvertError> {
+    if raw_tx.is_empty() {
+        return Err(RawTxWithBlobsConvertError::FailedToDecodeTransaction(
+            alloy_rlp::Error::InputTooShort,
+        ));
+    }
+    if raw_tx.len() > MAX_RAW_TX_SIZE {
+        return Err(RawTxWithBlobsConvertError::RawTxTooLarge {
+            size: raw_tx.len(),
+            max: MAX_RAW_TX_SIZE,
+        });
+    }
+    Ok(())
 }
 
 impl TransactionSignedEcRecoveredWithBlobs {
@@ -461,6 +544,7 @@ impl TransactionSignedEcRecoveredWithBlobs {
     pub fn decode_enveloped_with_real_blobs(
         raw_tx: Bytes,
     ) -> Result<TransactionSignedEcRecoveredWithBlobs, RawTxWithBlobsConvertError> {
+        check_raw_tx_size(&raw_tx)?;
         let raw_tx = &mut raw_tx.as_ref();
         let pooled_tx: PooledTransactionsElement =
             PooledTransactionsElement::decode_enveloped(raw_tx)
@@ -507,6 +591,7 @@ impl TransactionSignedEcRecoveredWithBlobs {
     pub fn decode_enveloped_with_fake_blobs(
         raw_tx: Bytes,
     ) -> Result<TransactionSignedEcRecoveredWithBlobs, RawTxWithBlobsConvertError> {
+        check_raw_tx_size(&raw_tx)?;
         let decoded = TransactionSigned::decode_enveloped(&mut raw_tx.as_ref())
             .map_err(RawTxWithBlobsConvertError::FailedToDecodeTransaction)?;
         let tx = decoded
@@ -591,12 +676,32 @@ pub enum OrderReplacementKey {
 }
 
 impl Order {
+    /// See [`Bundle::max_block_position`]. Only bundles can have a position constraint.
+    pub fn max_block_position(&self) -> Option<usize> {
+        match self {
+            Order::Bundle(bundle) => bundle.max_block_position,
+            Order::Tx(_) => None,
+            Order::ShareBundle(_) => None,
+        }
+    }
+
     /// Partial execution is valid as long as some tx is left.
-    pub fn can_execute_with_block_base_fee(&self, block_base_fee: u128) -> bool {
+    /// blob_base_fee: None before Cancun.
+    pub fn can_execute_with_block_base_fee(
+        &self,
+        block_base_fee: u128,
+        blob_base_fee: Option<u128>,
+    ) -> bool {
         match self {
-            Order::Bundle(bundle) => bundle.can_execute_with_block_base_fee(block_base_fee),
-            Order::Tx(tx) => tx.tx_with_blobs.tx.max_fee_per_gas() >= block_base_fee,
-            Order::ShareBundle(bundle) => bundle.can_execute_with_block_base_fee(block_base_fee),
+            Order::Bundle(bundle) => {
+                bundle.can_execute_with_block_base_fee(block_base_fee, blob_base_fee)
+            }
+            Order::Tx(tx) => {
+                tx_can_pay_block_fees(&tx.tx_with_blobs.tx, block_base_fee, blob_base_fee)
+            }
+            Order::ShareBundle(bundle) => {
+                bundle.can_execute_with_block_base_fee(block_base_fee, blob_base_fee)
+            }
         }
     }
 
@@ -700,6 +805,15 @@ impl Order {
         }
     }
 
+    /// Marks the order and all its txs as private (see [`Metadata::private`]).
+    pub fn set_private(&mut self) {
+        match self {
+            Order::Bundle(bundle) => bundle.set_private(),
+            Order::Tx(tx) => tx.tx_with_blobs.metadata.private = true,
+            Order::ShareBundle(bundle) => bundle.set_private(),
+        }
+    }
+
     pub fn metadata(&self) -> &Metadata {
         match self {
             Order::Bundle(bundle) => &bundle.metadata,
@@ -716,10 +830,14 @@ pub struct SimValue {
     pub gas_used: u64,
     #[serde(default)]
     pub blob_gas_used: u64,
-    /// This is computed as coinbase_profit/gas_used so it includes not only gas tip but also payments made directly to coinbase
+    /// This is computed as (coinbase_profit + token_profit)/gas_used so it includes not only gas tip but also payments made directly to coinbase
     pub mev_gas_price: U256,
     /// Kickbacks paid during simulation as (receiver, amount)
     pub paid_kickbacks: Vec<(Address, U256)>,
+    /// ETH equivalent of the tokens paid to the coinbase (see [`crate::building::token_valuation`]).
+    /// Counts for sorting but it's not part of coinbase_profit so it's never bid.
+    #[serde(default)]
+    pub token_profit: U256,
 }
 
 impl SimValue {
@@ -729,18 +847,35 @@ impl SimValue {
         blob_gas_used: u64,
         paid_kickbacks: Vec<(Address, U256)>,
     ) -> Self {
-        let mev_gas_price = if gas_used != 0 {
-            coinbase_profit / U256::from(gas_used)
-        } else {
-            U256::ZERO
-        };
-        Self {
+        let mut res = Self {
             coinbase_profit,
             gas_used,
             blob_gas_used,
-            mev_gas_price,
+            mev_gas_price: U256::ZERO,
             paid_kickbacks,
-        }
+            token_profit: U256::ZERO,
+        };
+        res.update_mev_gas_price();
+        res
+    }
+
+    pub fn with_token_profit(mut self, token_profit: U256) -> Self {
+        self.token_profit = token_profit;
+        self.update_mev_gas_price();
+        self
+    }
+
+    /// Profit used to score the order: coinbase_profit + token_profit.
+    pub fn score_profit(&self) -> U256 {
+        self.coinbase_profit.saturating_add(self.token_profit)
+    }
+
+    fn update_mev_gas_price(&mut self) {
+        self.mev_gas_price = if self.gas_used != 0 {
+            self.score_profit() / U256::from(self.gas_used)
+        } else {
+            U256::ZERO
+        };
     }
 }
 
@@ -863,14 +998,30 @@ fn bundle_nonces<'a>(
     res
 }
 
+/// max_fee_per_gas covers the basefee and, for blob txs, max_fee_per_blob_gas covers the blob basefee.
+fn tx_can_pay_block_fees(
+    tx: &TransactionSigned,
+    block_base_fee: u128,
+    blob_base_fee: Option<u128>,
+) -> bool {
+    tx.max_fee_per_gas() >= block_base_fee
+        && match (tx.max_fee_per_blob_gas(), blob_base_fee) {
+            (Some(max_fee_per_blob_gas), Some(blob_base_fee)) => {
+                max_fee_per_blob_gas >= blob_base_fee
+            }
+            _ => true,
+        }
+}
+
 /// Checks that at least one tx can execute and that all mandatory txs can.
 fn can_execute_with_block_base_fee<Transaction: AsRef<TransactionSigned>>(
     list_txs: Vec<(Transaction, bool)>,
     block_base_fee: u128,
+    blob_base_fee: Option<u128>,
 ) -> bool {
     let mut executable_tx_count = 0u32;
     for (tx, opt) in list_txs.iter().map(|(tx, opt)| (tx.as_ref(), opt)) {
-        if tx.max_fee_per_gas() >= block_base_fee {
+        if tx_can_pay_block_fees(tx, block_base_fee, blob_base_fee) {
             executable_tx_count += 1;
         } else if !opt {
             return false;
@@ -883,7 +1034,7 @@ fn can_execute_with_block_base_fee<Transaction: AsRef<TransactionSigned>>(
 mod tests {
     use super::*;
     use alloy_primitives::fixed_bytes;
-    use reth::primitives::{Transaction, TransactionSigned, TxLegacy};
+    use reth::primitives::{Transaction, TransactionSigned, TxEip4844, TxLegacy};
     use uuid::uuid;
 
     #[test]
@@ -902,7 +1053,51 @@ mod tests {
         );
         assert!(can_execute_with_block_base_fee(
             vec![(tx, true)],
-            needed_base_gas
+            needed_base_gas,
+            None
+        ));
+    }
+
+    #[test]
+    fn can_execute_blob_tx_only_over_blob_base_fee() {
+        let blob_base_fee: u128 = 1000;
+        let blob_tx = |max_fee_per_blob_gas| {
+            TransactionSignedEcRecovered::from_signed_transaction(
+                TransactionSigned {
+                    transaction: Transaction::Eip4844(TxEip4844 {
+                        max_fee_per_gas: 1,
+                        max_fee_per_blob_gas,
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+                Address::default(),
+            )
+        };
+        assert!(can_execute_with_block_base_fee(
+            vec![(blob_tx(blob_base_fee), false)],
+            1,
+            Some(blob_base_fee)
+        ));
+        assert!(!can_execute_with_block_base_fee(
+            vec![(blob_tx(blob_base_fee - 1), false)],
+            1,
+            Some(blob_base_fee)
+        ));
+        // optional blob tx can be dropped
+        assert!(can_execute_with_block_base_fee(
+            vec![
+                (blob_tx(blob_base_fee - 1), true),
+                (blob_tx(blob_base_fee), false)
+            ],
+            1,
+            Some(blob_base_fee)
+        ));
+        // pre Cancun
+        assert!(can_execute_with_block_base_fee(
+            vec![(blob_tx(0), false)],
+            1,
+            None
         ));
     }
 
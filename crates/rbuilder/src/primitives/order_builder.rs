@@ -96,6 +96,15 @@ impl OrderBuilder {
         }
     }
 
+    pub fn set_bundle_max_block_position(&mut self, max_block_position: Option<usize>) {
+        match self {
+            OrderBuilder::Bundle(builder) => {
+                builder.set_bundle_max_block_position(max_block_position);
+            }
+            _ => panic!("Only Bundle can have max block position"),
+        }
+    }
+
     // nested bundle methods
     pub fn start_inner_bundle(&mut self, can_skip: bool) {
         match self {
@@ -150,6 +159,7 @@ pub struct BundleBuilder {
     min_timestamp: Option<u64>,
     max_timestamp: Option<u64>,
     replacement_data: Option<BundleReplacementData>,
+    max_block_position: Option<usize>,
 }
 
 impl BundleBuilder {
@@ -160,6 +170,7 @@ impl BundleBuilder {
             min_timestamp: None,
             max_timestamp: None,
             replacement_data: None,
+            max_block_position: None,
         }
     }
 
@@ -172,6 +183,10 @@ impl BundleBuilder {
         self.replacement_data = Some(data);
     }
 
+    fn set_bundle_max_block_position(&mut self, max_block_position: Option<usize>) {
+        self.max_block_position = max_block_position;
+    }
+
     fn build(self) -> Bundle {
         let mut reverting_tx_hashes = Vec::new();
         let mut txs = Vec::new();
@@ -191,6 +206,7 @@ impl BundleBuilder {
             uuid: Default::default(),
             replacement_data: self.replacement_data,
             signer: None,
+            max_block_position: self.max_block_position,
             metadata: Default::default(),
         };
         bundle.hash_slow();
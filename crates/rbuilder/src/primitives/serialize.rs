@@ -58,11 +58,20 @@ pub struct RawBundle {
     /// See [`BundleReplacementData`] sequence_number
     #[serde(skip_serializing_if = "Option::is_none")]
     pub replacement_nonce: Option<u64>,
+    /// See [`super::Metadata::private`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
+    /// See [`Bundle::max_block_position`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_block_position: Option<usize>,
+    /// Same as max_block_position = 0
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub top_of_block: bool,
 }
 
 #[derive(Error, Debug)]
 pub enum RawBundleConvertError {
-    #[error("Failed to decode transaction, idx: {0}, error: {0}")]
+    #[error("Failed to decode transaction, idx: {0}, error: {1}")]
     FailedToDecodeTransaction(usize, RawTxWithBlobsConvertError),
     #[error("Incorrect replacement data")]
     IncorrectReplacementData,
@@ -94,6 +103,11 @@ impl RawBundle {
             sorted_reverting_hashes
         };
 
+        let max_block_position = match (self.top_of_block, self.max_block_position) {
+            (true, _) => Some(0),
+            (false, max_block_position) => max_block_position,
+        };
+
         let mut bundle = Bundle {
             block: self.block_number.to(),
             txs,
@@ -104,8 +118,12 @@ impl RawBundle {
             min_timestamp: self.min_timestamp,
             max_timestamp: self.max_timestamp,
             signer: self.signing_address,
+            max_block_position,
             metadata: Default::default(),
         };
+        if self.private {
+            bundle.set_private();
+        }
         bundle.hash_slow();
         Ok(bundle)
     }
@@ -143,6 +161,7 @@ impl RawBundle {
         let signing_address = value
             .signer
             .or(value.replacement_data.map(|r| r.key.key().signer));
+        let private = value.metadata.private;
         Self {
             block_number: U64::from(value.block),
             txs: value
@@ -156,10 +175,31 @@ impl RawBundle {
             min_timestamp: value.min_timestamp,
             max_timestamp: value.max_timestamp,
             replacement_nonce,
+            private,
+            max_block_position: value.max_block_position,
+            top_of_block: false,
+        }
+    }
+
+    /// Same as encode_no_blobs but txs of private bundles (see [`super::Metadata::private`]) are replaced by their hashes.
+    /// The result can't be decoded back.
+    pub fn encode_redacted(value: Bundle) -> Self {
+        if !value.metadata.private {
+            return Self::encode_no_blobs(value);
+        }
+        let txs = value.txs.iter().map(redact_tx).collect();
+        Self {
+            txs,
+            ..Self::encode_no_blobs(value)
         }
     }
 }
 
+/// Used instead of the tx for private orders.
+fn redact_tx(tx: &TransactionSignedEcRecoveredWithBlobs) -> Bytes {
+    Bytes::copy_from_slice(tx.hash().as_slice())
+}
+
 /// Struct to de/serialize json Bundles from bundles APIs and from/db.
 /// Does not assume a particular format on txs.
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -178,6 +218,17 @@ impl RawTx {
             tx: value.tx_with_blobs.envelope_encoded_no_blobs(),
         }
     }
+
+    /// Same as encode_no_blobs but private txs (see [`super::Metadata::private`]) are replaced by their hashes.
+    /// The result can't be decoded back.
+    pub fn encode_redacted(value: MempoolTx) -> Self {
+        if !value.tx_with_blobs.metadata.private {
+            return Self::encode_no_blobs(value);
+        }
+        Self {
+            tx: redact_tx(&value.tx_with_blobs),
+        }
+    }
 }
 
 /// Struct to de/serialize json Bundles from bundles APIs and from/db.
@@ -234,11 +285,14 @@ pub struct RawShareBundleMetadatada {
     /// Used for cancelling. When true the only thing we care about is signer,replacement_nonce and RawShareBundle::replacement_uuid
     #[serde(default)]
     pub cancelled: bool,
+    /// See [`super::Metadata::private`]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub private: bool,
 }
 
 #[derive(Error, Debug)]
 pub enum RawShareBundleConvertError {
-    #[error("Failed to decode transaction, idx: {0}, error: {0}")]
+    #[error("Failed to decode transaction, idx: {0}, error: {1}")]
     FailedToDecodeTransaction(usize, RawTxWithBlobsConvertError),
     #[error("Bundle too deep")]
     BundleTooDeep,
@@ -330,6 +384,9 @@ impl RawShareBundle {
             original_orders: Vec::new(),
             metadata: Default::default(),
         };
+        if self.metadata.as_ref().map_or(false, |m| m.private) {
+            bundle.set_private();
+        }
 
         bundle.hash_slow();
 
@@ -338,15 +395,28 @@ impl RawShareBundle {
 
     /// See [TransactionSignedEcRecoveredWithBlobs::envelope_encoded_no_blobs]
     pub fn encode_no_blobs(value: ShareBundle) -> Self {
+        Self::encode(value, false)
+    }
+
+    /// Same as encode_no_blobs but txs of private sbundles (see [`super::Metadata::private`]) are replaced by their hashes.
+    /// The result can't be decoded back.
+    pub fn encode_redacted(value: ShareBundle) -> Self {
+        let redact = value.metadata.private;
+        Self::encode(value, redact)
+    }
+
+    fn encode(value: ShareBundle, redact: bool) -> Self {
         let inclusion = RawShareBundleInclusion {
             block: U64::from(value.block),
             max_block: (value.block != value.max_block).then_some(U64::from(value.max_block)),
         };
-        let mut result = inner_bundle_to_raw_bundle_no_blobs(inclusion, value.inner_bundle);
-        result.metadata = value.signer.map(|signer| RawShareBundleMetadatada {
-            signer: Some(signer),
+        let mut result = inner_bundle_to_raw_bundle_no_blobs(inclusion, value.inner_bundle, redact);
+        let private = value.metadata.private;
+        result.metadata = (value.signer.is_some() || private).then_some(RawShareBundleMetadatada {
+            signer: value.signer,
             replacement_nonce: None,
             cancelled: false,
+            private,
         });
         result
     }
@@ -414,12 +484,17 @@ fn extract_inner_bundle(
     let (refund, refund_config) = raw
         .validity
         .map(|v| {
-            if v.refund.iter().map(|r| r.percent).sum::<usize>() > 100 {
+            // percents come from the request, a plain sum could overflow
+            let total_percent = |percents: &mut dyn Iterator<Item = usize>| {
+                percents.try_fold(0usize, |total, percent| total.checked_add(percent))
+            };
+            if total_percent(&mut v.refund.iter().map(|r| r.percent)).map_or(true, |t| t > 100) {
                 return Err(RawShareBundleConvertError::TotalRefundTooBig);
             }
 
             if !v.refund_config.is_empty()
-                && v.refund_config.iter().map(|r| r.percent).sum::<usize>() > 100
+                && total_percent(&mut v.refund_config.iter().map(|r| r.percent))
+                    .map_or(true, |t| t > 100)
             {
                 return Err(RawShareBundleConvertError::RefundConfigIncorrect);
             }
@@ -441,10 +516,11 @@ fn extract_inner_bundle(
     ))
 }
 
-/// Txs serialized without blobs data (canonical format)
+/// Txs serialized without blobs data (canonical format), if redact only their hashes.
 fn inner_bundle_to_raw_bundle_no_blobs(
     inclusion: RawShareBundleInclusion,
     inner: ShareBundleInner,
+    redact: bool,
 ) -> RawShareBundle {
     let body = inner
         .body
@@ -457,10 +533,15 @@ fn inner_bundle_to_raw_bundle_no_blobs(
                 bundle: Some(Box::new(inner_bundle_to_raw_bundle_no_blobs(
                     inclusion.clone(),
                     inner,
+                    redact,
                 ))),
             },
             ShareBundleBody::Tx(sbundle_tx) => RawShareBundleBody {
-                tx: Some(sbundle_tx.tx.envelope_encoded_no_blobs()),
+                tx: Some(if redact {
+                    redact_tx(&sbundle_tx.tx)
+                } else {
+                    sbundle_tx.tx.envelope_encoded_no_blobs()
+                }),
                 can_revert: sbundle_tx.revert_behavior.can_revert(),
                 revert_mode: None,
                 bundle: None,
@@ -528,6 +609,20 @@ impl RawOrder {
     }
 }
 
+impl RawOrder {
+    /// What every artifact (traces, block dumps, error events, etc) must use to serialize orders.
+    /// Txs of private orders (see [`super::Metadata::private`]) are replaced by their hashes.
+    pub fn encode_redacted(value: Order) -> Self {
+        match value {
+            Order::Bundle(bundle) => Self::Bundle(RawBundle::encode_redacted(bundle)),
+            Order::Tx(tx) => Self::Tx(RawTx::encode_redacted(tx)),
+            Order::ShareBundle(bundle) => {
+                Self::ShareBundle(RawShareBundle::encode_redacted(bundle))
+            }
+        }
+    }
+}
+
 impl From<Order> for RawOrder {
     fn from(value: Order) -> Self {
         match value {
@@ -543,6 +638,7 @@ impl From<Order> for RawOrder {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::primitives::static_limits::MAX_RAW_TX_SIZE;
     use alloy_primitives::{address, fixed_bytes, U256};
     use ethers::utils::keccak256;
     use uuid::uuid;
@@ -604,6 +700,155 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_private_bundle_encoding_hides_txs() {
+        let raw_tx = "0x02f86b0180843b9aca00852ecc889a0082520894c87037874aed04e51c29f582394217a0a2b89d808080c080a0a463985c616dd8ee17d7ef9112af4e6e06a27b071525b42182fe7b0b5c8b4925a00af5ca177ffef2ff28449292505d41be578bebb77110dfc09361d2fb56998260";
+        let bundle_json = format!(
+            r#"
+        {{
+            "blockNumber": "0x1136F1F",
+            "txs": ["{}"],
+            "revertingTxHashes": [],
+            "private": true
+        }}"#,
+            raw_tx
+        );
+
+        let bundle_request: RawBundle =
+            serde_json::from_str(&bundle_json).expect("failed to decode bundle");
+        let bundle = bundle_request
+            .clone()
+            .decode(TxEncoding::WithBlobData)
+            .expect("failed to convert bundle request to bundle");
+        assert!(bundle.metadata.private);
+        assert!(bundle.txs[0].metadata.private);
+        // execution still has the real tx
+        assert_eq!(
+            bundle.txs[0].envelope_encoded_no_blobs().to_string(),
+            raw_tx
+        );
+
+        // plain encoding is lossless
+        let roundtrip = RawBundle::encode_no_blobs(bundle.clone());
+        assert_eq!(roundtrip, bundle_request);
+        assert!(
+            roundtrip
+                .decode(TxEncoding::WithBlobData)
+                .unwrap()
+                .metadata
+                .private
+        );
+
+        let redacted = RawBundle::encode_redacted(bundle.clone());
+        assert!(redacted.private);
+        assert_eq!(
+            redacted.txs,
+            vec![Bytes::from(bundle.txs[0].hash().to_vec())]
+        );
+
+        let calldata = raw_tx.trim_start_matches("0x");
+        let artifacts = [
+            serde_json::to_string(&redacted).unwrap(),
+            serde_json::to_string(&RawOrder::encode_redacted(Order::Bundle(bundle.clone())))
+                .unwrap(),
+            format!("{:?}", redacted),
+            format!("{:?}", bundle),
+        ];
+        for artifact in artifacts {
+            assert!(!artifact.contains(calldata), "{}", artifact);
+        }
+    }
+
+    #[test]
+    fn test_private_share_bundle_and_tx_encoding_hides_txs() {
+        let raw_tx = "0x02f86b0180843b9aca00852ecc889a0082520894c87037874aed04e51c29f582394217a0a2b89d808080c080a0a463985c616dd8ee17d7ef9112af4e6e06a27b071525b42182fe7b0b5c8b4925a00af5ca177ffef2ff28449292505d41be578bebb77110dfc09361d2fb56998260";
+        let calldata = raw_tx.trim_start_matches("0x");
+        let sbundle_json = format!(
+            r#"
+        {{
+            "version": "v0.1",
+            "inclusion": {{ "block": "0x1" }},
+            "body": [{{ "bundle": {{
+                "version": "v0.1",
+                "inclusion": {{ "block": "0x1" }},
+                "body": [{{ "tx": "{}", "canRevert": false }}]
+            }} }}],
+            "metadata": {{ "private": true }}
+        }}"#,
+            raw_tx
+        );
+        let sbundle_request: RawShareBundle = serde_json::from_str(&sbundle_json).unwrap();
+        let sbundle = sbundle_request
+            .decode_new_bundle(TxEncoding::WithBlobData)
+            .unwrap();
+        assert!(sbundle.metadata.private);
+        assert!(sbundle.list_txs().iter().all(|(tx, _)| tx.metadata.private));
+
+        let roundtrip = RawShareBundle::encode_no_blobs(sbundle.clone());
+        assert!(serde_json::to_string(&roundtrip)
+            .unwrap()
+            .contains(calldata));
+        assert!(
+            roundtrip
+                .decode_new_bundle(TxEncoding::WithBlobData)
+                .unwrap()
+                .metadata
+                .private
+        );
+
+        let mut tx = RawTx {
+            tx: raw_tx.parse().unwrap(),
+        }
+        .decode(TxEncoding::WithBlobData)
+        .map(Order::Tx)
+        .unwrap();
+        tx.set_private();
+
+        let sbundle = Order::ShareBundle(sbundle);
+        for order in [sbundle, tx] {
+            let artifacts = [
+                serde_json::to_string(&RawOrder::encode_redacted(order.clone())).unwrap(),
+                format!("{:?}", order),
+            ];
+            for artifact in artifacts {
+                assert!(!artifact.contains(calldata), "{}", artifact);
+            }
+        }
+    }
+
+    #[test]
+    fn test_bundle_block_position_constraint() {
+        let raw_tx = "0x02f86b0180843b9aca00852ecc889a0082520894c87037874aed04e51c29f582394217a0a2b89d808080c080a0a463985c616dd8ee17d7ef9112af4e6e06a27b071525b42182fe7b0b5c8b4925a00af5ca177ffef2ff28449292505d41be578bebb77110dfc09361d2fb56998260";
+        let decode = |extra: &str| {
+            let bundle_json = format!(
+                r#"{{"blockNumber": "0x1136F1F", "txs": ["{}"], "revertingTxHashes": []{}}}"#,
+                raw_tx, extra
+            );
+            let bundle_request: RawBundle =
+                serde_json::from_str(&bundle_json).expect("failed to decode bundle");
+            bundle_request
+                .decode(TxEncoding::WithBlobData)
+                .expect("failed to convert bundle request to bundle")
+        };
+
+        assert_eq!(decode("").max_block_position, None);
+        assert_eq!(
+            decode(r#", "maxBlockPosition": 3"#).max_block_position,
+            Some(3)
+        );
+        assert_eq!(
+            decode(r#", "topOfBlock": true"#).max_block_position,
+            Some(0)
+        );
+        assert_eq!(
+            decode(r#", "topOfBlock": true, "maxBlockPosition": 3"#).max_block_position,
+            Some(0)
+        );
+
+        let encoded = RawBundle::encode_no_blobs(decode(r#", "maxBlockPosition": 3"#));
+        assert_eq!(encoded.max_block_position, Some(3));
+    }
+
     #[test]
     fn test_correct_bundle_uuid_multiple_reverting_hashes() {
         // reverting tx hashes ordering should not matter
@@ -883,4 +1128,109 @@ mod tests {
             serde_json::from_str(raw_tx_json).expect("failed to decode raw order with tx");
         assert!(matches!(raw_order, RawOrder::Tx(_)));
     }
+
+    const VALID_RAW_TX: &str = "0x02f86b0180843b9aca00852ecc889a0082520894c87037874aed04e51c29f582394217a0a2b89d808080c080a0a463985c616dd8ee17d7ef9112af4e6e06a27b071525b42182fe7b0b5c8b4925a00af5ca177ffef2ff28449292505d41be578bebb77110dfc09361d2fb56998260";
+
+    /// Malformed raw txs must fail with an error on both encodings, never panic.
+    #[test]
+    fn test_malformed_raw_tx_corpus() {
+        let valid: Bytes = VALID_RAW_TX.parse().unwrap();
+        let corpus: Vec<(&str, Vec<u8>)> = vec![
+            ("empty", vec![]),
+            ("type byte only", vec![0x02]),
+            ("truncated header", valid[..3].to_vec()),
+            ("truncated body", valid[..valid.len() / 2].to_vec()),
+            ("truncated signature", valid[..valid.len() - 1].to_vec()),
+            ("unknown tx type", [&[0x05u8][..], &valid[1..]].concat()),
+            ("reserved tx type", [&[0x7fu8][..], &valid[1..]].concat()),
+            (
+                "huge string length prefix",
+                vec![0xbf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (
+                "huge list length prefix",
+                vec![0x02, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (
+                "huge legacy list length prefix",
+                vec![0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            ),
+            (
+                "list length over input",
+                [&[0x02u8, 0xf9, 0xff, 0xff][..], &valid[3..]].concat(),
+            ),
+            ("oversized", vec![0x02; MAX_RAW_TX_SIZE + 1]),
+        ];
+        for (name, raw_tx) in &corpus {
+            for encoding in [TxEncoding::NoBlobData, TxEncoding::WithBlobData] {
+                let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    encoding.decode(Bytes::from(raw_tx.clone()))
+                }));
+                assert!(
+                    matches!(res, Ok(Err(_))),
+                    "{} should fail without panicking",
+                    name
+                );
+            }
+        }
+        assert!(matches!(
+            TxEncoding::WithBlobData.decode(Bytes::from(vec![0x02; MAX_RAW_TX_SIZE + 1])),
+            Err(RawTxWithBlobsConvertError::RawTxTooLarge { .. })
+        ));
+
+        // valid input unaffected
+        for encoding in [TxEncoding::NoBlobData, TxEncoding::WithBlobData] {
+            assert!(encoding.decode(valid.clone()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_malformed_bundle_errors_name_the_tx() {
+        let bundle: RawBundle = serde_json::from_value(serde_json::json!({
+            "blockNumber": "0x1",
+            "txs": [VALID_RAW_TX, VALID_RAW_TX, "0x02f8"],
+            "revertingTxHashes": []
+        }))
+        .unwrap();
+        let err = bundle.decode(TxEncoding::WithBlobData).unwrap_err();
+        assert!(matches!(
+            err,
+            RawBundleConvertError::FailedToDecodeTransaction(2, _)
+        ));
+        assert!(err.to_string().contains("idx: 2"));
+
+        let share_bundle = |body: serde_json::Value, refund: serde_json::Value| -> RawShareBundle {
+            serde_json::from_value(serde_json::json!({
+                "version": "v0.1",
+                "inclusion": {"block": "0x1"},
+                "body": body,
+                "validity": {"refund": refund}
+            }))
+            .unwrap()
+        };
+        let err = share_bundle(
+            serde_json::json!([{"tx": VALID_RAW_TX}, {"tx": "0x"}]),
+            serde_json::json!([]),
+        )
+        .decode(TxEncoding::WithBlobData)
+        .err()
+        .unwrap();
+        assert!(matches!(
+            err,
+            RawShareBundleConvertError::FailedToDecodeTransaction(1, _)
+        ));
+
+        // percents adding over usize::MAX
+        let err = share_bundle(
+            serde_json::json!([{"tx": VALID_RAW_TX}]),
+            serde_json::json!([
+                {"bodyIdx": 0, "percent": usize::MAX},
+                {"bodyIdx": 0, "percent": 1}
+            ]),
+        )
+        .decode(TxEncoding::WithBlobData)
+        .err()
+        .unwrap();
+        assert!(matches!(err, RawShareBundleConvertError::TotalRefundTooBig));
+    }
 }
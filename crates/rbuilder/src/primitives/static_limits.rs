@@ -0,0 +1,231 @@
+//! Limits we can check on a tx without executing it. Checked at ingestion (orders breaking them are dropped, see
+//! [`check_order_static_limits`]) and the consensus ones again right before committing (so orders from other sources
+//! never reach the EVM only to fail with a generic error, see [`check_tx_static_limits`]).
+//! - Initcode size (EIP-3860), from Shanghai.
+//! - Blobs per tx (EIP-4844, a tx can't have more blobs than a block), from Cancun.
+//! - Tx size (same as the geth txpool, blobs not included), always. Txpool policy, not consensus: ingestion only.
+use super::{Order, TransactionSignedEcRecoveredWithBlobs};
+use alloy_primitives::B256;
+use alloy_rlp::Encodable;
+use ethereum_consensus::deneb::polynomial_commitments::BYTES_PER_COMMITMENT;
+use reth::primitives::{
+    constants::eip4844::{DATA_GAS_PER_BLOB, MAX_DATA_GAS_PER_BLOCK},
+    kzg::{BYTES_PER_BLOB, BYTES_PER_PROOF},
+    ChainSpec,
+};
+use revm::primitives::MAX_INITCODE_SIZE;
+use thiserror::Error;
+
+pub const MAX_BLOBS_PER_TX: usize = (MAX_DATA_GAS_PER_BLOCK / DATA_GAS_PER_BLOB) as usize;
+pub const MAX_TX_SIZE: usize = 128 * 1024;
+/// Raw txs (network encoding, blobs included) bigger than this are rejected before decoding.
+/// MAX_TX_SIZE + the sidecar of MAX_BLOBS_PER_TX + some room for the rlp headers.
+pub const MAX_RAW_TX_SIZE: usize = MAX_TX_SIZE
+    + MAX_BLOBS_PER_TX * (BYTES_PER_BLOB + BYTES_PER_COMMITMENT + BYTES_PER_PROOF)
+    + 1024;
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum StaticLimitError {
+    #[error("Initcode size {size} over {max} bytes")]
+    InitcodeTooLarge { size: usize, max: usize },
+    #[error("{count} blobs, max {max} per tx")]
+    TooManyBlobs { count: usize, max: usize },
+    #[error("Tx size {size} over {max} bytes")]
+    TxTooLarge { size: usize, max: usize },
+}
+
+impl StaticLimitError {
+    /// For metrics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StaticLimitError::InitcodeTooLarge { .. } => "initcode_too_large",
+            StaticLimitError::TooManyBlobs { .. } => "too_many_blobs",
+            StaticLimitError::TxTooLarge { .. } => "tx_too_large",
+        }
+    }
+}
+
+/// Consensus limits only (no tx size).
+/// timestamp: of the block the tx would go in, decides the active forks.
+pub fn check_tx_static_limits(
+    tx_with_blobs: &TransactionSignedEcRecoveredWithBlobs,
+    chain_spec: &ChainSpec,
+    timestamp: u64,
+) -> Result<(), StaticLimitError> {
+    let tx = &tx_with_blobs.tx;
+    if tx.to().is_none() && chain_spec.is_shanghai_active_at_timestamp(timestamp) {
+        let size = tx.input().len();
+        if size > MAX_INITCODE_SIZE {
+            return Err(StaticLimitError::InitcodeTooLarge {
+                size,
+                max: MAX_INITCODE_SIZE,
+            });
+        }
+    }
+    if chain_spec.is_cancun_active_at_timestamp(timestamp) {
+        let count = tx
+            .blob_versioned_hashes()
+            .map_or(0, |hashes| hashes.len())
+            .max(tx_with_blobs.blobs_sidecar.blobs.len());
+        if count > MAX_BLOBS_PER_TX {
+            return Err(StaticLimitError::TooManyBlobs {
+                count,
+                max: MAX_BLOBS_PER_TX,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub fn check_tx_size(
+    tx_with_blobs: &TransactionSignedEcRecoveredWithBlobs,
+) -> Result<(), StaticLimitError> {
+    // rlp length, a couple of bytes over the envelope for typed txs
+    let size = tx_with_blobs.tx.length();
+    if size > MAX_TX_SIZE {
+        return Err(StaticLimitError::TxTooLarge {
+            size,
+            max: MAX_TX_SIZE,
+        });
+    }
+    Ok(())
+}
+
+/// First tx of the order over a limit (all of them, tx size included).
+pub fn check_order_static_limits(
+    order: &Order,
+    chain_spec: &ChainSpec,
+    timestamp: u64,
+) -> Result<(), (B256, StaticLimitError)> {
+    for (tx, _) in order.list_txs() {
+        check_tx_static_limits(tx, chain_spec, timestamp)
+            .and_then(|()| check_tx_size(tx))
+            .map_err(|err| (tx.hash(), err))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::primitives::MempoolTx;
+    use alloy_primitives::{Address, Bytes};
+    use reth::primitives::{
+        BlobTransactionSidecar, ForkCondition, Hardfork, Transaction, TransactionKind,
+        TransactionSigned, TransactionSignedEcRecovered, TxEip4844, TxLegacy, MAINNET,
+    };
+    use std::sync::Arc;
+
+    fn fork_timestamp(fork: Hardfork) -> u64 {
+        match MAINNET.fork(fork) {
+            ForkCondition::Timestamp(timestamp) => timestamp,
+            condition => panic!("unexpected fork condition {:?}", condition),
+        }
+    }
+
+    fn tx(transaction: Transaction) -> TransactionSignedEcRecoveredWithBlobs {
+        TransactionSignedEcRecoveredWithBlobs {
+            tx: TransactionSignedEcRecovered::from_signed_transaction(
+                TransactionSigned {
+                    transaction,
+                    ..Default::default()
+                },
+                Address::ZERO,
+            ),
+            blobs_sidecar: Arc::new(BlobTransactionSidecar::default()),
+            metadata: Default::default(),
+        }
+    }
+
+    fn legacy(to: TransactionKind, input_size: usize) -> TransactionSignedEcRecoveredWithBlobs {
+        tx(Transaction::Legacy(TxLegacy {
+            to,
+            input: Bytes::from(vec![1u8; input_size]),
+            ..Default::default()
+        }))
+    }
+
+    #[test]
+    fn test_initcode_size() {
+        let shanghai = fork_timestamp(Hardfork::Shanghai);
+        let max_create = legacy(TransactionKind::Create, MAX_INITCODE_SIZE);
+        let create = legacy(TransactionKind::Create, MAX_INITCODE_SIZE + 1);
+        let call = legacy(TransactionKind::Call(Address::ZERO), MAX_INITCODE_SIZE + 1);
+
+        assert_eq!(
+            check_tx_static_limits(&create, &MAINNET, shanghai - 1),
+            Ok(())
+        );
+        assert_eq!(
+            check_tx_static_limits(&create, &MAINNET, shanghai),
+            Err(StaticLimitError::InitcodeTooLarge {
+                size: MAX_INITCODE_SIZE + 1,
+                max: MAX_INITCODE_SIZE
+            })
+        );
+        assert_eq!(
+            check_tx_static_limits(&max_create, &MAINNET, shanghai),
+            Ok(())
+        );
+        // only creations have initcode
+        assert_eq!(check_tx_static_limits(&call, &MAINNET, shanghai), Ok(()));
+    }
+
+    #[test]
+    fn test_blobs_per_tx() {
+        let cancun = fork_timestamp(Hardfork::Cancun);
+        let blob_tx = |count| {
+            tx(Transaction::Eip4844(TxEip4844 {
+                blob_versioned_hashes: vec![B256::ZERO; count],
+                ..Default::default()
+            }))
+        };
+
+        assert_eq!(
+            check_tx_static_limits(&blob_tx(MAX_BLOBS_PER_TX + 1), &MAINNET, cancun - 1),
+            Ok(())
+        );
+        assert_eq!(
+            check_tx_static_limits(&blob_tx(MAX_BLOBS_PER_TX + 1), &MAINNET, cancun),
+            Err(StaticLimitError::TooManyBlobs {
+                count: MAX_BLOBS_PER_TX + 1,
+                max: MAX_BLOBS_PER_TX
+            })
+        );
+        assert_eq!(
+            check_tx_static_limits(&blob_tx(MAX_BLOBS_PER_TX), &MAINNET, cancun),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_tx_size() {
+        let cancun = fork_timestamp(Hardfork::Cancun);
+        let call = |input_size| legacy(TransactionKind::Call(Address::ZERO), input_size);
+        assert_eq!(check_tx_size(&call(MAX_TX_SIZE - 1024)), Ok(()));
+        assert!(matches!(
+            check_tx_size(&call(MAX_TX_SIZE)),
+            Err(StaticLimitError::TxTooLarge { .. })
+        ));
+        // not a consensus limit, only checked on the orders we ingest
+        assert_eq!(
+            check_tx_static_limits(&call(MAX_TX_SIZE), &MAINNET, cancun),
+            Ok(())
+        );
+        for timestamp in [0, cancun] {
+            let order = Order::Tx(MempoolTx::new(call(MAX_TX_SIZE)));
+            let (_, err) = check_order_static_limits(&order, &MAINNET, timestamp).unwrap_err();
+            assert_eq!(err.kind(), "tx_too_large");
+        }
+    }
+
+    #[test]
+    fn test_order_reports_first_tx_over_limit() {
+        let shanghai = fork_timestamp(Hardfork::Shanghai);
+        let create = legacy(TransactionKind::Create, MAX_INITCODE_SIZE + 1);
+        let order = Order::Tx(MempoolTx::new(create.clone()));
+        let (tx_hash, err) = check_order_static_limits(&order, &MAINNET, shanghai).unwrap_err();
+        assert_eq!(tx_hash, create.hash());
+        assert_eq!(err.kind(), "initcode_too_large");
+    }
+}
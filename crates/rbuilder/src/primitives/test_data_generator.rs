@@ -59,6 +59,7 @@ impl TestDataGenerator {
             uuid: Uuid::default(),
             replacement_data: replacement_data.clone(),
             signer: replacement_data.map(|r| r.key.key().signer),
+            max_block_position: None,
             metadata: Default::default(),
         };
         res.hash_slow();
@@ -123,6 +124,7 @@ impl TestDataGenerator {
             uuid: Uuid::default(),
             replacement_data: replacement_data.clone(),
             signer: replacement_data.map(|r| r.key.key().signer),
+            max_block_position: None,
             metadata: Default::default(),
         };
         bundle.hash_slow();
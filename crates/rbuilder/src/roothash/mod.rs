@@ -1,6 +1,8 @@
 use alloy_primitives::B256;
 use reth::{
-    providers::{providers::ConsistentDbView, BundleStateWithReceipts, ProviderFactory},
+    providers::{
+        providers::ConsistentDbView, BundleStateWithReceipts, ProviderFactory, StateRootProvider,
+    },
     tasks::pool::BlockingTaskPool,
 };
 use reth_db::database::Database;
@@ -14,6 +16,9 @@ pub enum RootHashMode {
     /// Makes correct root hash calculation on the incorrect parent state.
     /// It can be used for benchmarks.
     IgnoreParentHash,
+    /// Makes correct root hash calculation on the historical state of the parent, the parent doesn't need to be our tip.
+    /// It's single threaded and has to revert the state from our tip so it's only for checks on landed blocks.
+    HistoricalParent,
     /// Don't calculate root hash.
     /// It can be used for backtest.
     SkipRootHash,
@@ -31,6 +36,12 @@ pub fn calculate_state_root<DB: Database + Clone + 'static>(
         RootHashMode::CorrectRoot => ConsistentDbView::new(provider_factory, Some(parent_hash)),
         RootHashMode::IgnoreParentHash => ConsistentDbView::new_with_latest_tip(provider_factory)
             .map_err(AsyncStateRootError::Provider)?,
+        RootHashMode::HistoricalParent => {
+            return provider_factory
+                .history_by_block_hash(parent_hash)
+                .and_then(|state_provider| state_provider.state_root(bundle))
+                .map_err(AsyncStateRootError::Provider);
+        }
         RootHashMode::SkipRootHash => {
             return Ok(B256::ZERO);
         }
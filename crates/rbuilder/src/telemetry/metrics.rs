@@ -6,14 +6,20 @@
 //! When metric server is spawned is serves prometheus metrics at: /debug/metrics/prometheus
 
 use crate::{
-    building::ExecutionResult, primitives::mev_boost::MevBoostRelayID, utils::build_info::Version,
+    building::{
+        provider_io::{ProviderIoStats, ProviderMethodStats},
+        value_curve::ValueCurveSummary,
+        ExecutionResult,
+    },
+    primitives::mev_boost::MevBoostRelayID,
+    utils::build_info::Version,
 };
 use alloy_primitives::{utils::Unit, U256};
 use bigdecimal::num_traits::Pow;
 use lazy_static::lazy_static;
 use prometheus::{
-    Counter, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec, Opts,
-    Registry,
+    Counter, GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts, Registry,
 };
 use std::time::Duration;
 use time::OffsetDateTime;
@@ -45,6 +51,24 @@ lazy_static! {
         &["builder_name"]
     )
     .unwrap();
+    pub static ref BUILD_CPU_TIME: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "build_cpu_time",
+            "CPU time of the building thread during the block fill (ms)"
+        )
+        .buckets(exponential_buckets_range(0.01, 3000.0, 100)),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref TIME_TO_FIRST_COMMIT: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "time_to_first_commit",
+            "Time from block building start to the first successful order commit (ms)"
+        )
+        .buckets(exponential_buckets_range(0.01, 3000.0, 100)),
+        &["builder_name"]
+    )
+    .unwrap();
     pub static ref BLOCK_BUILT_TXS: HistogramVec = HistogramVec::new(
         HistogramOpts::new("block_built_txs", "Transactions in the built block")
             .buckets(linear_buckets_range(1.0, 1000.0, 100)),
@@ -116,6 +140,11 @@ lazy_static! {
         IntCounter::new("simulation_gas_used", "Simulation gas used").unwrap();
     pub static ref ACTIVE_SLOTS: IntCounter =
         IntCounter::new("active_slots", "Slots when builder was active").unwrap();
+    pub static ref SKIPPED_SLOTS: IntCounterVec = IntCounterVec::new(
+        Opts::new("skipped_slots", "Slots we got attributes for but didn't build"),
+        &["reason"],
+    )
+    .unwrap();
     pub static ref INITIATED_SUBMISSIONS: IntCounterVec = IntCounterVec::new(
         Opts::new(
             "initiated_submissions",
@@ -130,6 +159,40 @@ lazy_static! {
         &["relay"],
     )
     .unwrap();
+    pub static ref RELAY_LATENCY_P90: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "relay_latency_p90",
+            "p90 of the recent relay response times (ms)"
+        ),
+        &["relay"],
+    )
+    .unwrap();
+    pub static ref RELAY_SUPPRESSED_SUBMISSIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "relay_suppressed_submissions",
+            "Repeated submissions (same block and bid) not sent again to the relay"
+        ),
+        &["relay"],
+    )
+    .unwrap();
+    pub static ref KILL_SWITCH_ENGAGED: IntGauge = IntGauge::new(
+        "kill_switch_engaged",
+        "1 if the kill switch is engaged (no bids are submitted)"
+    )
+    .unwrap();
+    pub static ref KILL_SWITCH_SUPPRESSED_SUBMISSIONS: IntCounter = IntCounter::new(
+        "kill_switch_suppressed_submissions",
+        "Bids not submitted because of the kill switch"
+    )
+    .unwrap();
+    pub static ref RELAY_SKIPPED_DUE_TO_LATENCY: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "relay_skipped_due_to_latency",
+            "Bids not sent to the relay because its latency did not fit before the slot cutoff"
+        ),
+        &["relay"],
+    )
+    .unwrap();
     pub static ref VERSION: IntGaugeVec = IntGaugeVec::new(
         Opts::new("version", "Version of the builder"),
         &["git", "git_ref", "build_time_utc"]
@@ -143,6 +206,164 @@ lazy_static! {
         &["relay", "optimistic"]
     )
     .unwrap();
+    pub static ref RELAY_ACCEPTED_SUBMISSIONS_BY_IDENTITY: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "relay_accepted_submissions_by_identity",
+            "Number of accepted submissions by signing identity"
+        ),
+        &["relay", "identity"]
+    )
+    .unwrap();
+    /// Not the profit, the bidder decides how much of it we bid.
+    pub static ref BLOCK_BID_VALUE: GaugeVec = GaugeVec::new(
+        Opts::new("block_bid_value", "Bid value of the last block built (ETH)"),
+        &["builder_name"]
+    )
+    .unwrap();
+    /// See [`crate::building::profit_divergence`].
+    pub static ref LAST_BLOCK_ORDER_PROFIT_DIVERGENCE: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "last_block_order_profit_divergence",
+            "Realized - simulated profit of the orders of the last block built, mean signed error and p95 absolute error (ETH)"
+        ),
+        &["builder_name", "stat"]
+    )
+    .unwrap();
+    /// Summary of the value curve of the last slot (see [`crate::building::value_curve`]).
+    pub static ref VALUE_CURVE_VALUE: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "value_curve_value",
+            "Best bid value of the last slot at 4s, 8s and 11s after the payload attributes (ETH)"
+        ),
+        &["at"]
+    )
+    .unwrap();
+    pub static ref VALUE_CURVE_LAST_IMPROVEMENT: IntGauge = IntGauge::new(
+        "value_curve_last_improvement_ms",
+        "Time of the last best bid improvement of the last slot after the payload attributes (ms)"
+    )
+    .unwrap();
+    pub static ref SUBMISSION_SLO_RATIO: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "submission_slo_ratio",
+            "Ratio of submitted slots where at least one relay accepted our bid"
+        ),
+        &["window"]
+    )
+    .unwrap();
+    pub static ref COMMIT_SUCCESS_RATIO: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "commit_success_ratio",
+            "Ratio of successful order commits over commit attempts on the last block filled by the builder"
+        ),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref RELAY_SUBMISSION_SLO_RATIO: GaugeVec = GaugeVec::new(
+        Opts::new(
+            "relay_submission_slo_ratio",
+            "Ratio of submitted slots where the relay accepted our bid"
+        ),
+        &["relay", "window"]
+    )
+    .unwrap();
+    pub static ref SLOTS_WITHOUT_SUBMISSION: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "slots_without_submission",
+            "Slots where we intentionally did not submit to the relays"
+        ),
+        &["reason"]
+    )
+    .unwrap();
+    pub static ref OVERLOAD_FILTER_DECISIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "overload_filter_decisions_total",
+            "Decisions of the simulation overload filter by decision"
+        ),
+        &["decision"]
+    )
+    .unwrap();
+    pub static ref BUILD_TIME_PERCENTILE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "block_build_time_percentile_ms",
+            "Percentiles of the recent block build times (fill + finalize) of the builder"
+        ),
+        &["builder_name", "percentile"]
+    )
+    .unwrap();
+    pub static ref ORDERS_CONSIDERED: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "orders_considered",
+            "Orders considered on the last block filled by the builder"
+        ),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref ORDERS_AVAILABLE: IntGaugeVec = IntGaugeVec::new(
+        Opts::new(
+            "orders_available",
+            "Orders that could be considered on the last block filled by the builder"
+        ),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref BUILD_ATTEMPTS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "build_attempts_total",
+            "Number of times a builder started building for a slot, regardless of the outcome"
+        ),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref CACHED_READS_DIVERGENCES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "cached_reads_divergences_total",
+            "Sampled cached reads that did not match the state provider"
+        ),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref HEADER_BASEFEE_MISMATCHES: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "header_basefee_mismatches_total",
+            "Finalized headers whose basefee does not follow from the parent header"
+        ),
+        &["builder_name"]
+    )
+    .unwrap();
+    pub static ref STATE_PROVIDER_READS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "state_provider_reads_total",
+            "Lookups that reached the state provider while building blocks (path: execution or batched before execution)"
+        ),
+        &["builder_name", "path"]
+    )
+    .unwrap();
+    pub static ref PROVIDER_IO_CALLS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "provider_io_calls_total",
+            "State provider calls while building blocks (method: history_by_block_hash, account, storage, bytecode, block_hash)"
+        ),
+        &["builder_name", "method"]
+    )
+    .unwrap();
+    pub static ref PROVIDER_IO_TIME: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "provider_io_time_mus",
+            "Time spent on state provider calls while building blocks (mus)"
+        ),
+        &["builder_name", "method"]
+    )
+    .unwrap();
+    pub static ref SIM_QUEUE_LATENCY: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "sim_queue_latency",
+            "Time the simulation requests waited for a sim worker by lane (ms)"
+        )
+        .buckets(exponential_buckets_range(0.01, 10000.0, 60)),
+        &["lane"]
+    )
+    .unwrap();
     pub static ref SIMULATION_THREAD_WORK_TIME: IntCounterVec = IntCounterVec::new(
         Opts::new(
             "simulation_thread_work_time",
@@ -191,6 +412,75 @@ lazy_static! {
         &["kind"],
     ).unwrap();
 
+    pub static ref BEST_BLOCK_SUPERSESSIONS: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "best_block_supersessions",
+            "Blocks that superseded the first best block of the slot"
+        )
+        .buckets(exponential_buckets_range(1.0, 1000.0, 30)),
+    )
+    .unwrap();
+    pub static ref BEST_BLOCK_SUPERSESSIONS_CAPPED: IntCounter = IntCounter::new(
+        "best_block_supersessions_capped_total",
+        "Better blocks dropped because the slot reached max_best_block_supersessions"
+    )
+    .unwrap();
+    pub static ref SLOT_SINK_BLOCKS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "slot_sink_blocks",
+            "Blocks the builders sent to the slot sink by outcome (see slot_block_metrics)"
+        ),
+        &["outcome"],
+    )
+    .unwrap();
+    pub static ref SINK_BLOCKS_PER_SLOT: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "sink_blocks_per_slot",
+            "Blocks the builders sent to the slot sink on a slot (see slot_block_metrics)"
+        )
+        .buckets(exponential_buckets_range(1.0, 10000.0, 40)),
+    )
+    .unwrap();
+    pub static ref ORDER_INPUT_BATCH_SIZE: Histogram = Histogram::with_opts(
+        HistogramOpts::new(
+            "order_input_batch_size",
+            "Elements of the JSON-RPC batches received by the order input server"
+        )
+        .buckets(exponential_buckets_range(1.0, 1000.0, 30)),
+    )
+    .unwrap();
+    pub static ref ORDER_INPUT_DECOMPRESSION_TIME: HistogramVec = HistogramVec::new(
+        HistogramOpts::new(
+            "order_input_decompression_time",
+            "Decompression time of the order input request bodies (ms)"
+        )
+        .buckets(exponential_buckets_range(0.01, 1000.0, 50)),
+        &["encoding"]
+    )
+    .unwrap();
+    /// Orders dropped by the order input for being over a static limit, kind: see StaticLimitError::kind.
+    pub static ref ORDER_INPUT_STATIC_LIMIT_REJECTIONS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "order_input_static_limit_rejections",
+            "Orders over the static tx limits dropped by the order input"
+        ),
+        &["kind"],
+    ).unwrap();
+    /// Order decodings of the rpc that panicked (turned into an error response), method: rpc method.
+    pub static ref ORDER_INPUT_DECODE_PANICS: IntCounterVec = IntCounterVec::new(
+        Opts::new(
+            "order_input_decode_panics",
+            "Order decodings that panicked on the order input rpc"
+        ),
+        &["method"],
+    ).unwrap();
+
+    /// Rebuilds of landed blocks by the canary, result: pass, mismatch or error.
+    pub static ref CANARY_RUNS: IntCounterVec = IntCounterVec::new(
+        Opts::new("canary_runs_total", "Canary rebuilds of landed blocks"),
+        &["result"],
+    ).unwrap();
+
     /// We decide this at the end of the submission to relays
     /// We expect to see values around .001
     /// We only count subsidized blocks.
@@ -307,6 +597,41 @@ pub fn add_built_block_metrics(
         );
 }
 
+pub fn set_block_bid_value(builder_name: &str, bid_value: U256) {
+    BLOCK_BID_VALUE
+        .with_label_values(&[builder_name])
+        .set(wei_to_eth(bid_value));
+}
+
+/// Errors in wei, they replace the ones of the previous block of the builder.
+pub fn set_last_block_order_profit_divergence(
+    builder_name: &str,
+    mean_signed_error: f64,
+    p95_abs_error: f64,
+) {
+    let eth = 10_f64.pow(Unit::ETHER.get());
+    LAST_BLOCK_ORDER_PROFIT_DIVERGENCE
+        .with_label_values(&[builder_name, "mean_signed_error"])
+        .set(mean_signed_error / eth);
+    LAST_BLOCK_ORDER_PROFIT_DIVERGENCE
+        .with_label_values(&[builder_name, "p95_abs_error"])
+        .set(p95_abs_error / eth);
+}
+
+/// Missing values (no block yet) are set to 0.
+pub fn set_value_curve_summary(summary: &ValueCurveSummary) {
+    for (at, value) in [
+        ("4s", summary.value_at_4s),
+        ("8s", summary.value_at_8s),
+        ("11s", summary.value_at_11s),
+    ] {
+        VALUE_CURVE_VALUE
+            .with_label_values(&[at])
+            .set(wei_to_eth(value.unwrap_or_default()));
+    }
+    VALUE_CURVE_LAST_IMPROVEMENT.set(summary.last_improvement_ms.unwrap_or_default() as i64);
+}
+
 pub fn add_block_validation_time(duration: Duration) {
     BLOCK_VALIDATION_TIME
         .with_label_values(&[])
@@ -317,6 +642,10 @@ pub fn inc_active_slots() {
     ACTIVE_SLOTS.inc();
 }
 
+pub fn inc_skipped_slots(reason: &str) {
+    SKIPPED_SLOTS.with_label_values(&[reason]).inc();
+}
+
 pub fn inc_initiated_submissions(optimistic: bool) {
     INITIATED_SUBMISSIONS
         .with_label_values(&[&optimistic.to_string()])
@@ -329,12 +658,147 @@ pub fn add_relay_submit_time(relay: &MevBoostRelayID, duration: Duration) {
         .observe(duration.as_millis() as f64);
 }
 
+pub fn set_relay_latency_p90(relay: &MevBoostRelayID, latency: Duration) {
+    RELAY_LATENCY_P90
+        .with_label_values(&[relay.as_str()])
+        .set(latency.as_millis() as i64);
+}
+
+pub fn inc_relay_suppressed_submissions(relay: &MevBoostRelayID) {
+    RELAY_SUPPRESSED_SUBMISSIONS
+        .with_label_values(&[relay.as_str()])
+        .inc();
+}
+
+pub fn set_kill_switch_engaged(engaged: bool) {
+    KILL_SWITCH_ENGAGED.set(engaged as i64);
+}
+
+pub fn inc_kill_switch_suppressed_submissions() {
+    KILL_SWITCH_SUPPRESSED_SUBMISSIONS.inc();
+}
+
+pub fn inc_relay_skipped_due_to_latency(relay: &MevBoostRelayID) {
+    RELAY_SKIPPED_DUE_TO_LATENCY
+        .with_label_values(&[relay.as_str()])
+        .inc();
+}
+
 pub fn inc_relay_accepted_submissions(relay: &MevBoostRelayID, optimistic: bool) {
     RELAY_ACCEPTED_SUBMISSIONS
         .with_label_values(&[relay.as_str(), &optimistic.to_string()])
         .inc();
 }
 
+pub fn inc_relay_accepted_submissions_by_identity(relay: &MevBoostRelayID, identity: &str) {
+    RELAY_ACCEPTED_SUBMISSIONS_BY_IDENTITY
+        .with_label_values(&[relay.as_str(), identity])
+        .inc();
+}
+
+pub fn add_build_cpu_time(builder_name: &str, duration: Duration) {
+    BUILD_CPU_TIME
+        .with_label_values(&[builder_name])
+        .observe(duration.as_secs_f64() * 1000.0);
+}
+
+pub fn add_time_to_first_commit(builder_name: &str, duration: Duration) {
+    TIME_TO_FIRST_COMMIT
+        .with_label_values(&[builder_name])
+        .observe(duration.as_secs_f64() * 1000.0);
+}
+
+pub fn set_commit_success_ratio(builder_name: &str, ratio: f64) {
+    COMMIT_SUCCESS_RATIO
+        .with_label_values(&[builder_name])
+        .set(ratio);
+}
+
+pub fn inc_overload_filter_decisions(decision: &str) {
+    OVERLOAD_FILTER_DECISIONS
+        .with_label_values(&[decision])
+        .inc();
+}
+
+pub fn set_build_time_percentiles(builder_name: &str, p50: Duration, p95: Duration, p99: Duration) {
+    for (percentile, value) in [("p50", p50), ("p95", p95), ("p99", p99)] {
+        BUILD_TIME_PERCENTILE
+            .with_label_values(&[builder_name, percentile])
+            .set(value.as_millis() as i64);
+    }
+}
+
+pub fn set_orders_considered(builder_name: &str, considered: usize, available: usize) {
+    ORDERS_CONSIDERED
+        .with_label_values(&[builder_name])
+        .set(considered as i64);
+    ORDERS_AVAILABLE
+        .with_label_values(&[builder_name])
+        .set(available as i64);
+}
+
+pub fn set_submission_slo_ratio(window: &str, ratio: f64) {
+    SUBMISSION_SLO_RATIO.with_label_values(&[window]).set(ratio);
+}
+
+pub fn set_relay_submission_slo_ratio(relay: &MevBoostRelayID, window: &str, ratio: f64) {
+    RELAY_SUBMISSION_SLO_RATIO
+        .with_label_values(&[relay.as_str(), window])
+        .set(ratio);
+}
+
+pub fn inc_slots_without_submission(reason: &str) {
+    SLOTS_WITHOUT_SUBMISSION.with_label_values(&[reason]).inc();
+}
+
+pub fn inc_build_attempts(builder_name: &str) {
+    BUILD_ATTEMPTS.with_label_values(&[builder_name]).inc();
+}
+
+pub fn add_cached_reads_divergences(builder_name: &str, divergences: usize) {
+    CACHED_READS_DIVERGENCES
+        .with_label_values(&[builder_name])
+        .inc_by(divergences as u64);
+}
+
+pub fn inc_header_basefee_mismatches(builder_name: &str) {
+    HEADER_BASEFEE_MISMATCHES
+        .with_label_values(&[builder_name])
+        .inc();
+}
+
+pub fn add_state_provider_reads(builder_name: &str, execution_reads: usize, batched_reads: usize) {
+    STATE_PROVIDER_READS
+        .with_label_values(&[builder_name, "execution"])
+        .inc_by(execution_reads as u64);
+    STATE_PROVIDER_READS
+        .with_label_values(&[builder_name, "batched"])
+        .inc_by(batched_reads as u64);
+}
+
+pub fn add_provider_io(
+    builder_name: &str,
+    state_provider_open_time: Duration,
+    provider_io: &ProviderIoStats,
+) {
+    let calls = std::iter::once((
+        "history_by_block_hash",
+        ProviderMethodStats {
+            calls: 1,
+            time: state_provider_open_time,
+        },
+    ))
+    .chain(provider_io.methods());
+    for (method, stats) in calls {
+        PROVIDER_IO_CALLS
+            .with_label_values(&[builder_name, method])
+            .inc_by(stats.calls);
+        PROVIDER_IO_TIME
+            .with_label_values(&[builder_name, method])
+            .inc_by(stats.time.as_micros() as u64);
+    }
+}
+
 pub fn add_txfetcher_time_to_query(duration: Duration) {
     TXFETCHER_TRANSACTION_QUERY_TIME
         .with_label_values(&[])
@@ -343,6 +807,12 @@ pub fn add_txfetcher_time_to_query(duration: Duration) {
     TXFETCHER_TRANSACTION_COUNTER.inc();
 }
 
+pub fn add_sim_queue_latency(lane: &str, latency: Duration) {
+    SIM_QUEUE_LATENCY
+        .with_label_values(&[lane])
+        .observe(latency.as_secs_f64() * 1000.0);
+}
+
 pub fn add_sim_thread_utilisation_timings(
     work_time: Duration,
     wait_time: Duration,
@@ -395,8 +865,13 @@ pub fn inc_subsidized_blocks(landed: bool) {
         .inc();
 }
 
+/// Approximation good enough for metrics.
+fn wei_to_eth(value: U256) -> f64 {
+    2.0_f64.powf(value.approx_log2()) / 10_f64.pow(Unit::ETHER.get())
+}
+
 pub fn add_subsidy_value(value: U256, landed: bool) {
-    let value_float = 2.0_f64.powf(value.approx_log2()) / 10_f64.pow(Unit::ETHER.get());
+    let value_float = wei_to_eth(value);
     SUBSIDY_VALUE
         .with_label_values(&[subsidized_label(landed)])
         .observe(value_float);
@@ -405,6 +880,48 @@ pub fn add_subsidy_value(value: U256, landed: bool) {
     }
 }
 
+pub fn observe_best_block_supersessions(supersessions: usize) {
+    BEST_BLOCK_SUPERSESSIONS.observe(supersessions as f64);
+}
+
+pub fn inc_best_block_supersessions_capped() {
+    BEST_BLOCK_SUPERSESSIONS_CAPPED.inc();
+}
+
+pub fn add_slot_sink_blocks(outcome: &str, blocks: usize) {
+    SLOT_SINK_BLOCKS
+        .with_label_values(&[outcome])
+        .inc_by(blocks as u64);
+}
+
+pub fn observe_sink_blocks_per_slot(blocks: usize) {
+    SINK_BLOCKS_PER_SLOT.observe(blocks as f64);
+}
+
+pub fn observe_order_input_batch_size(size: usize) {
+    ORDER_INPUT_BATCH_SIZE.observe(size as f64);
+}
+
+pub fn add_order_input_decompression_time(encoding: &str, duration: Duration) {
+    ORDER_INPUT_DECOMPRESSION_TIME
+        .with_label_values(&[encoding])
+        .observe(duration.as_secs_f64() * 1000.0);
+}
+
+pub fn inc_order_input_static_limit_rejections(kind: &str) {
+    ORDER_INPUT_STATIC_LIMIT_REJECTIONS
+        .with_label_values(&[kind])
+        .inc();
+}
+
+pub fn inc_order_input_decode_panics(method: &str) {
+    ORDER_INPUT_DECODE_PANICS.with_label_values(&[method]).inc();
+}
+
+pub fn inc_canary_runs(result: &str) {
+    CANARY_RUNS.with_label_values(&[result]).inc();
+}
+
 pub(super) fn register_custom_metrics() {
     REGISTRY
         .register(Box::new(BLOCK_FILL_TIME.clone()))
@@ -412,6 +929,10 @@ pub(super) fn register_custom_metrics() {
     REGISTRY
         .register(Box::new(BLOCK_FINALIZE_TIME.clone()))
         .unwrap();
+    REGISTRY
+        .register(Box::new(TIME_TO_FIRST_COMMIT.clone()))
+        .unwrap();
+    REGISTRY.register(Box::new(BUILD_CPU_TIME.clone())).unwrap();
     REGISTRY
         .register(Box::new(BLOCK_VALIDATION_TIME.clone()))
         .unwrap();
@@ -449,16 +970,90 @@ pub(super) fn register_custom_metrics() {
         .register(Box::new(SIMULATION_GAS_USED.clone()))
         .unwrap();
     REGISTRY.register(Box::new(ACTIVE_SLOTS.clone())).unwrap();
+    REGISTRY.register(Box::new(SKIPPED_SLOTS.clone())).unwrap();
     REGISTRY
         .register(Box::new(INITIATED_SUBMISSIONS.clone()))
         .unwrap();
     REGISTRY
         .register(Box::new(RELAY_SUBMIT_TIME.clone()))
         .unwrap();
+    REGISTRY
+        .register(Box::new(RELAY_LATENCY_P90.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(RELAY_SKIPPED_DUE_TO_LATENCY.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(RELAY_SUPPRESSED_SUBMISSIONS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(KILL_SWITCH_ENGAGED.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(KILL_SWITCH_SUPPRESSED_SUBMISSIONS.clone()))
+        .unwrap();
     REGISTRY.register(Box::new(VERSION.clone())).unwrap();
     REGISTRY
         .register(Box::new(RELAY_ACCEPTED_SUBMISSIONS.clone()))
         .unwrap();
+    REGISTRY
+        .register(Box::new(RELAY_ACCEPTED_SUBMISSIONS_BY_IDENTITY.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(SUBMISSION_SLO_RATIO.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(BLOCK_BID_VALUE.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(LAST_BLOCK_ORDER_PROFIT_DIVERGENCE.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(VALUE_CURVE_VALUE.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(VALUE_CURVE_LAST_IMPROVEMENT.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(RELAY_SUBMISSION_SLO_RATIO.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(COMMIT_SUCCESS_RATIO.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(SLOTS_WITHOUT_SUBMISSION.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(OVERLOAD_FILTER_DECISIONS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(BUILD_TIME_PERCENTILE.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(ORDERS_CONSIDERED.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(ORDERS_AVAILABLE.clone()))
+        .unwrap();
+    REGISTRY.register(Box::new(BUILD_ATTEMPTS.clone())).unwrap();
+    REGISTRY
+        .register(Box::new(CACHED_READS_DIVERGENCES.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(HEADER_BASEFEE_MISMATCHES.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(STATE_PROVIDER_READS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(PROVIDER_IO_CALLS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(PROVIDER_IO_TIME.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(SIM_QUEUE_LATENCY.clone()))
+        .unwrap();
     REGISTRY
         .register(Box::new(SIMULATION_THREAD_WORK_TIME.clone()))
         .unwrap();
@@ -472,6 +1067,31 @@ pub(super) fn register_custom_metrics() {
         .register(Box::new(SUBSIDIZED_BLOCK_COUNT.clone()))
         .unwrap();
     REGISTRY.register(Box::new(SUBSIDY_VALUE.clone())).unwrap();
+    REGISTRY.register(Box::new(CANARY_RUNS.clone())).unwrap();
+    REGISTRY
+        .register(Box::new(BEST_BLOCK_SUPERSESSIONS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(BEST_BLOCK_SUPERSESSIONS_CAPPED.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(SLOT_SINK_BLOCKS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(SINK_BLOCKS_PER_SLOT.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(ORDER_INPUT_BATCH_SIZE.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(ORDER_INPUT_DECOMPRESSION_TIME.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(ORDER_INPUT_STATIC_LIMIT_REJECTIONS.clone()))
+        .unwrap();
+    REGISTRY
+        .register(Box::new(ORDER_INPUT_DECODE_PANICS.clone()))
+        .unwrap();
     REGISTRY
         .register(Box::new(TOTAL_LANDED_SUBSIDIES_SUM.clone()))
         .unwrap();
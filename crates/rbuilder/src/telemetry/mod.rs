@@ -15,7 +15,14 @@ pub mod metrics;
 pub use dynamic_logs::*;
 pub use metrics::*;
 
-use crate::utils::build_info::Version;
+use crate::{
+    building::{profit_divergence::PROFIT_DIVERGENCES, value_curve::VALUE_CURVES},
+    live_builder::{
+        building::relay_identities::{relay_identity_routes, RELAY_IDENTITIES},
+        kill_switch::{kill_switch_routes, KILL_SWITCH},
+    },
+    utils::build_info::Version,
+};
 
 async fn metrics_handler() -> Result<impl Reply, Rejection> {
     Ok(gather_prometheus_metrics())
@@ -67,7 +74,21 @@ pub async fn spawn_telemetry_server(addr: SocketAddr, version: Version) -> eyre:
         .and_then(set_rust_log_handle);
     let log_reset_route = warp::path!("debug" / "log" / "reset").and_then(reset_log_handle);
 
-    let route = metrics_route.or(log_set_route).or(log_reset_route);
+    // value curves of the last slots, see crate::building::value_curve
+    let value_curve_route =
+        warp::path!("debug" / "value_curve").map(|| warp::reply::json(&VALUE_CURVES.curves()));
+
+    // last simulated vs realized order profits of each builder, see crate::building::profit_divergence
+    let profit_divergence_route = warp::path!("debug" / "profit_divergence")
+        .map(|| warp::reply::json(&PROFIT_DIVERGENCES.reports()));
+
+    let route = metrics_route
+        .or(log_set_route)
+        .or(log_reset_route)
+        .or(value_curve_route)
+        .or(profit_divergence_route)
+        .or(kill_switch_routes(KILL_SWITCH.clone()))
+        .or(relay_identity_routes(RELAY_IDENTITIES.clone()));
 
     tokio::spawn(warp::serve(route).run(addr));
 
@@ -0,0 +1,265 @@
+//! Throwaway [`SlotBidder`]s and [`BlockBuildingSink`]s to test [`crate::building::builders::BlockBuildingAlgorithm`]s
+//! (ours or external ones embedding the library) without a relay/bidding setup.
+//! Outside this crate it needs the test-utils feature.
+use crate::{
+    building::{
+        builders::{Block, BlockBuildingSink},
+        BuiltBlockTrace,
+    },
+    live_builder::bidding::{SealInstruction, SlotBidder},
+};
+use alloy_primitives::U256;
+use reth::primitives::{Header, SealedBlock};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+/// Empty block (only the number is set) bidding bid_value, to feed sinks.
+pub fn mock_block(block_number: u64, bid_value: U256, builder_name: &str) -> Block {
+    let mut trace = BuiltBlockTrace::new();
+    trace.bid_value = bid_value;
+    trace.true_bid_value = bid_value;
+    Block {
+        trace,
+        sealed_block: SealedBlock {
+            header: Header {
+                number: block_number,
+                ..Default::default()
+            }
+            .seal_slow(),
+            ..Default::default()
+        },
+        txs_blobs_sidecars: Vec::new(),
+        builder_name: builder_name.to_string(),
+        coinbase_payment_tx_hash: None,
+        root_hash_skipped: false,
+    }
+}
+
+/// Bids fraction (clamped to [0, 1]) of the true block value.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StaticBidder {
+    pub fraction: f64,
+}
+
+impl StaticBidder {
+    const FRACTION_PRECISION: u64 = 1_000_000;
+
+    pub fn new(fraction: f64) -> Self {
+        Self { fraction }
+    }
+
+    /// Bids all the true value, like the dummy bidder of the live builder.
+    pub fn full_value() -> Self {
+        Self::new(1.0)
+    }
+
+    pub fn bid_value(&self, unsealed_block_profit: U256) -> U256 {
+        let ppm = (self.fraction.clamp(0.0, 1.0) * Self::FRACTION_PRECISION as f64) as u64;
+        unsealed_block_profit * U256::from(ppm) / U256::from(Self::FRACTION_PRECISION)
+    }
+}
+
+impl SlotBidder for StaticBidder {
+    fn is_pay_to_coinbase_allowed(&self) -> bool {
+        true
+    }
+
+    fn seal_instruction(
+        &self,
+        unsealed_block_profit: U256,
+        _slot_timestamp: time::OffsetDateTime,
+    ) -> SealInstruction {
+        SealInstruction::Value(self.bid_value(unsealed_block_profit))
+    }
+
+    fn best_bid_value(&self) -> Option<U256> {
+        None
+    }
+}
+
+/// Skips every block.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AlwaysDeclineBidder;
+
+impl SlotBidder for AlwaysDeclineBidder {
+    fn is_pay_to_coinbase_allowed(&self) -> bool {
+        true
+    }
+
+    fn seal_instruction(
+        &self,
+        _unsealed_block_profit: U256,
+        _slot_timestamp: time::OffsetDateTime,
+    ) -> SealInstruction {
+        SealInstruction::Skip
+    }
+
+    fn best_bid_value(&self) -> Option<U256> {
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedBlock {
+    pub block: Block,
+    pub received_at: Instant,
+}
+
+/// Keeps every block it gets, clones share the blocks.
+#[derive(Debug, Clone, Default)]
+pub struct RecordingSink {
+    blocks: Arc<Mutex<Vec<RecordedBlock>>>,
+}
+
+impl RecordingSink {
+    /// In arrival order.
+    pub fn blocks(&self) -> Vec<RecordedBlock> {
+        self.blocks.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.blocks.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn best_bid(&self) -> Option<U256> {
+        self.blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|recorded| recorded.block.trace.bid_value)
+            .max()
+    }
+
+    /// Block has no slot, slots are identified by the number of the block built on them.
+    pub fn blocks_for_slot(&self, block_number: u64) -> Vec<RecordedBlock> {
+        self.blocks
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|recorded| recorded.block.sealed_block.number == block_number)
+            .cloned()
+            .collect()
+    }
+}
+
+impl BlockBuildingSink for RecordingSink {
+    fn new_block(&self, block: Block) {
+        self.blocks.lock().unwrap().push(RecordedBlock {
+            block,
+            received_at: Instant::now(),
+        });
+    }
+}
+
+/// Records the first fail_after blocks (see [`FailingSink::recorded`]) and drops the next ones counting them as
+/// failed, like a sink whose relay connection died.
+#[derive(Debug, Clone)]
+pub struct FailingSink {
+    pub fail_after: usize,
+    failed: Arc<AtomicUsize>,
+    recorded: RecordingSink,
+}
+
+impl FailingSink {
+    pub fn new(fail_after: usize) -> Self {
+        Self {
+            fail_after,
+            failed: Default::default(),
+            recorded: Default::default(),
+        }
+    }
+
+    /// Blocks taken before failing.
+    pub fn recorded(&self) -> &RecordingSink {
+        &self.recorded
+    }
+
+    /// Blocks dropped after fail_after.
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Calls to new_block, including the failed ones.
+    pub fn delivered(&self) -> usize {
+        self.recorded.len() + self.failed()
+    }
+}
+
+impl BlockBuildingSink for FailingSink {
+    fn new_block(&self, block: Block) {
+        // the lock of the recording sink serializes the check with the push
+        let mut blocks = self.recorded.blocks.lock().unwrap();
+        if blocks.len() >= self.fail_after {
+            self.failed.fetch_add(1, Ordering::Relaxed);
+            return;
+        }
+        blocks.push(RecordedBlock {
+            block,
+            received_at: Instant::now(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn block(number: u64, bid_value: u64) -> Block {
+        mock_block(number, U256::from(bid_value), "test")
+    }
+
+    #[test]
+    fn test_bidders() {
+        let slot_timestamp = time::OffsetDateTime::now_utc();
+        let profit = U256::from(1_000);
+        assert_eq!(
+            StaticBidder::new(0.9).seal_instruction(profit, slot_timestamp),
+            SealInstruction::Value(U256::from(900))
+        );
+        assert_eq!(
+            StaticBidder::new(2.0).seal_instruction(profit, slot_timestamp),
+            SealInstruction::Value(profit)
+        );
+        assert_eq!(
+            StaticBidder::full_value().seal_instruction(profit, slot_timestamp),
+            SealInstruction::Value(profit)
+        );
+        assert_eq!(
+            AlwaysDeclineBidder.seal_instruction(profit, slot_timestamp),
+            SealInstruction::Skip
+        );
+    }
+
+    #[test]
+    fn test_recording_sink() {
+        let sink = RecordingSink::default();
+        assert_eq!(sink.best_bid(), None);
+        sink.clone().new_block(block(1, 10));
+        sink.new_block(block(2, 30));
+        sink.new_block(block(2, 20));
+        assert_eq!(sink.len(), 3);
+        assert_eq!(sink.best_bid(), Some(U256::from(30)));
+        assert_eq!(sink.blocks_for_slot(2).len(), 2);
+        assert!(sink.blocks_for_slot(3).is_empty());
+    }
+
+    #[test]
+    fn test_failing_sink() {
+        let sink = FailingSink::new(1);
+        sink.new_block(block(1, 10));
+        sink.clone().new_block(block(1, 20));
+        assert_eq!(sink.delivered(), 2);
+        assert_eq!(sink.failed(), 1);
+        assert_eq!(sink.recorded().len(), 1);
+        assert_eq!(sink.recorded().best_bid(), Some(U256::from(10)));
+    }
+}
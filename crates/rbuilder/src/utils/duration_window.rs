@@ -0,0 +1,81 @@
+//! Bounded window of the most recent durations to get latency percentiles.
+use std::{collections::VecDeque, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct DurationWindow {
+    capacity: usize,
+    /// Oldest first.
+    samples: VecDeque<Duration>,
+}
+
+impl DurationWindow {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            samples: VecDeque::with_capacity(capacity),
+        }
+    }
+
+    /// Adds a sample dropping the oldest one if the window is full.
+    pub fn push(&mut self, duration: Duration) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(duration);
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Nearest rank percentile (percentile in [0,1]), None if we have no samples.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        if self.samples.is_empty() {
+            return None;
+        }
+        let mut sorted: Vec<_> = self.samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let index = ((sorted.len() as f64 * percentile).ceil() as usize).clamp(1, sorted.len()) - 1;
+        Some(sorted[index])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_percentiles() {
+        let mut window = DurationWindow::new(100);
+        assert_eq!(window.percentile(0.5), None);
+        // 100..1 ms, insertion order must not matter
+        for ms in (1..=100).rev() {
+            window.push(Duration::from_millis(ms));
+        }
+        assert_eq!(window.percentile(0.5), Some(Duration::from_millis(50)));
+        assert_eq!(window.percentile(0.95), Some(Duration::from_millis(95)));
+        assert_eq!(window.percentile(0.99), Some(Duration::from_millis(99)));
+        assert_eq!(window.percentile(1.0), Some(Duration::from_millis(100)));
+        assert_eq!(window.percentile(0.0), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn test_old_samples_are_dropped() {
+        let mut window = DurationWindow::new(10);
+        for _ in 0..10 {
+            window.push(Duration::from_secs(1));
+        }
+        for _ in 0..10 {
+            window.push(Duration::from_millis(1));
+        }
+        assert_eq!(window.len(), 10);
+        assert_eq!(window.percentile(1.0), Some(Duration::from_millis(1)));
+    }
+}
@@ -1,10 +1,12 @@
 //! a2r prefix = alloy to reth conversion
 pub mod build_info;
 pub mod constants;
+mod duration_window;
 pub mod error_storage;
 pub mod fmt;
 mod noncer;
 mod provider_factory_reopen;
+pub mod rate_limited_log;
 pub mod reconnect;
 mod test_data_generator;
 mod tx_signer;
@@ -20,6 +22,7 @@ use reth::primitives::{
 use revm::primitives::CfgEnvWithHandlerCfg;
 use std::cmp::{max, min};
 
+pub use duration_window::DurationWindow;
 pub use noncer::{NonceCache, NonceCacheRef};
 pub use provider_factory_reopen::{
     check_provider_factory_health, is_provider_factory_health_error, ProviderFactoryReopener,
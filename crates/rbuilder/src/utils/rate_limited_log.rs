@@ -0,0 +1,326 @@
+//! Rate limiting for log sites that can fire hundreds of times per minute with the same error (eg: "Error filling orders"
+//! on every build iteration of every slot).
+//! Occurrences are counted per key (site + error discriminant, the error without the numbers so distinct errors are
+//! never merged).
+//! The first threshold occurrences of a window are logged, the rest suppressed. The first occurrence logged after the
+//! window ends carries how many were suppressed ("repeated N times in the last minute"), if the error stops the
+//! flusher (see [`spawn_rate_limited_log_flusher`]) logs the summary once the window expires.
+use ahash::HashMap;
+use lazy_static::lazy_static;
+use std::{
+    fmt::Debug,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::warn;
+
+pub const RATE_LIMITED_LOG_WINDOW: Duration = Duration::from_secs(60);
+/// Occurrences per key and window that are logged.
+pub const RATE_LIMITED_LOG_THRESHOLD: u64 = 1;
+/// Keys over this are not tracked (always logged) so a bad discriminant can't grow the map forever.
+const MAX_KEYS: usize = 1024;
+/// Discriminants are truncated to this.
+const MAX_DISCRIMINANT_LEN: usize = 128;
+/// How often the flusher looks for expired windows.
+const FLUSH_PERIOD: Duration = Duration::from_secs(5);
+
+lazy_static! {
+    static ref RATE_LIMITED_LOG: RateLimitedLog =
+        RateLimitedLog::new(RATE_LIMITED_LOG_WINDOW, RATE_LIMITED_LOG_THRESHOLD);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct LogKey {
+    site: &'static str,
+    discriminant: String,
+}
+
+#[derive(Debug)]
+struct KeyState {
+    window_start: Instant,
+    /// Occurrences on the current window.
+    count: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogDecision {
+    /// suppressed: occurrences we didn't log on the previous window, > 0 means the log should carry the summary.
+    Log {
+        suppressed: u64,
+    },
+    Suppress,
+}
+
+/// Summary of an expired window with suppressed occurrences.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuppressedSummary {
+    pub site: &'static str,
+    pub discriminant: String,
+    pub suppressed: u64,
+}
+
+#[derive(Debug)]
+pub struct RateLimitedLog {
+    window: Duration,
+    threshold: u64,
+    keys: Mutex<HashMap<LogKey, KeyState>>,
+}
+
+impl RateLimitedLog {
+    pub fn new(window: Duration, threshold: u64) -> Self {
+        Self {
+            window,
+            threshold,
+            keys: Mutex::new(HashMap::default()),
+        }
+    }
+
+    /// Counts an occurrence of (site, discriminant) at now.
+    pub fn check(&self, site: &'static str, discriminant: String, now: Instant) -> LogDecision {
+        let mut keys = self.keys.lock().unwrap();
+        let key = LogKey { site, discriminant };
+        let state = match keys.get_mut(&key) {
+            Some(state) => state,
+            None => {
+                if keys.len() < MAX_KEYS {
+                    keys.insert(
+                        key,
+                        KeyState {
+                            window_start: now,
+                            count: 1,
+                        },
+                    );
+                }
+                return LogDecision::Log { suppressed: 0 };
+            }
+        };
+        if now.saturating_duration_since(state.window_start) >= self.window {
+            let suppressed = state.count.saturating_sub(self.threshold);
+            state.window_start = now;
+            state.count = 1;
+            return LogDecision::Log { suppressed };
+        }
+        state.count += 1;
+        if state.count <= self.threshold {
+            LogDecision::Log { suppressed: 0 }
+        } else {
+            LogDecision::Suppress
+        }
+    }
+
+    /// Forgets the keys whose window expired at now, returns the summaries of the ones with suppressed occurrences.
+    /// The next occurrence of a forgotten key starts a new window.
+    pub fn flush_expired(&self, now: Instant) -> Vec<SuppressedSummary> {
+        let mut summaries = Vec::new();
+        self.keys.lock().unwrap().retain(|key, state| {
+            if now.saturating_duration_since(state.window_start) < self.window {
+                return true;
+            }
+            let suppressed = state.count.saturating_sub(self.threshold);
+            if suppressed > 0 {
+                summaries.push(SuppressedSummary {
+                    site: key.site,
+                    discriminant: key.discriminant.clone(),
+                    suppressed,
+                });
+            }
+            false
+        });
+        summaries
+    }
+}
+
+/// Some(suppressed) if the occurrence of (site, discriminant) should be logged, suppressed is the count for the
+/// "repeated N times in the last minute" summary (log it as a field when > 0).
+/// Shared by all the sites of the process, windows span slots.
+pub fn rate_limited(site: &'static str, discriminant: String) -> Option<u64> {
+    match RATE_LIMITED_LOG.check(site, discriminant, Instant::now()) {
+        LogDecision::Log { suppressed } => Some(suppressed),
+        LogDecision::Suppress => None,
+    }
+}
+
+/// Logs the summaries of the expired windows every FLUSH_PERIOD so they are not lost when the error stops.
+pub fn spawn_rate_limited_log_flusher(global_cancellation: CancellationToken) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLUSH_PERIOD);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = global_cancellation.cancelled() => return,
+            }
+            for summary in RATE_LIMITED_LOG.flush_expired(Instant::now()) {
+                warn!(
+                    site = summary.site,
+                    discriminant = summary.discriminant,
+                    repeated_in_last_minute = summary.suppressed,
+                    "Rate limited log stopped repeating"
+                );
+            }
+        }
+    })
+}
+
+/// Error discriminant for the key: the Debug of the error without numbers (blocks, nonces) and 0x hex values (hashes,
+/// addresses) so they don't split the key but the variant and its fields do (eg: "BlockHashNotFound()" for
+/// ProviderError::BlockHashNotFound(hash), "ErrorObject { code: -, message: \"nonce too low\" }" for rpc errors).
+/// Truncated to MAX_DISCRIMINANT_LEN chars.
+pub fn error_discriminant(err: &impl Debug) -> String {
+    let debug = format!("{:?}", err);
+    let mut discriminant = String::new();
+    let mut chars = debug.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '0' && chars.peek() == Some(&'x') {
+            chars.next();
+            while chars.next_if(|c| c.is_ascii_hexdigit()).is_some() {}
+        } else if !c.is_ascii_digit() {
+            discriminant.push(c);
+        }
+        if discriminant.len() >= MAX_DISCRIMINANT_LEN {
+            break;
+        }
+    }
+    discriminant
+}
+
+/// error_discriminant of the root cause, the outer contexts are usually the same for every error of a site.
+pub fn report_discriminant(err: &eyre::Report) -> String {
+    error_discriminant(&err.root_cause())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Debug)]
+    #[allow(dead_code)]
+    enum TestError {
+        NonceTooLow(u64),
+        Other { reason: String },
+        ErrorObject { code: i64, message: String },
+    }
+
+    #[test]
+    fn test_suppression_and_summary() {
+        let log = RateLimitedLog::new(Duration::from_secs(60), 3);
+        let start = Instant::now();
+        let key = || error_discriminant(&TestError::NonceTooLow(1));
+        for i in 0..3 {
+            assert_eq!(
+                log.check("site", key(), start + Duration::from_secs(i)),
+                LogDecision::Log { suppressed: 0 }
+            );
+        }
+        for i in 3..10 {
+            assert_eq!(
+                log.check("site", key(), start + Duration::from_secs(i)),
+                LogDecision::Suppress
+            );
+        }
+        // other errors and sites have their own budget
+        assert_eq!(
+            log.check(
+                "site",
+                error_discriminant(&TestError::Other {
+                    reason: "x".to_string()
+                }),
+                start
+            ),
+            LogDecision::Log { suppressed: 0 }
+        );
+        assert_eq!(
+            log.check("other_site", key(), start),
+            LogDecision::Log { suppressed: 0 }
+        );
+
+        // next window: logged with the summary of the 7 suppressed ones
+        let next_window = start + Duration::from_secs(60);
+        assert_eq!(
+            log.check("site", key(), next_window),
+            LogDecision::Log { suppressed: 7 }
+        );
+        assert_eq!(
+            log.check("site", key(), next_window),
+            LogDecision::Log { suppressed: 0 }
+        );
+        // quiet window, nothing to summarize
+        assert_eq!(
+            log.check("site", key(), next_window + Duration::from_secs(120)),
+            LogDecision::Log { suppressed: 0 }
+        );
+    }
+
+    #[test]
+    fn test_error_discriminant() {
+        assert_eq!(
+            error_discriminant(&TestError::NonceTooLow(1)),
+            "NonceTooLow()"
+        );
+        assert_eq!(
+            error_discriminant(&TestError::NonceTooLow(1)),
+            error_discriminant(&TestError::NonceTooLow(2))
+        );
+        assert_eq!(
+            report_discriminant(&eyre::eyre!("Nonce for 0x12 is 3 expected 5")),
+            error_discriminant(&"Nonce for 0x34 is 4 expected 6")
+        );
+        assert_ne!(
+            report_discriminant(&eyre::eyre!("Profit too low")),
+            report_discriminant(&eyre::eyre!("Gas limit reached"))
+        );
+        // struct variants are split by their fields
+        let error_object = |code, message: &str| TestError::ErrorObject {
+            code,
+            message: message.to_string(),
+        };
+        assert_ne!(
+            error_discriminant(&error_object(-32000, "nonce too low")),
+            error_discriminant(&error_object(-32000, "insufficient funds"))
+        );
+        assert_ne!(
+            error_discriminant(&TestError::Other {
+                reason: "x".to_string()
+            }),
+            error_discriminant(&TestError::Other {
+                reason: "y".to_string()
+            })
+        );
+        assert_eq!(
+            error_discriminant(&error_object(-32000, "nonce too low for 0xab12")),
+            error_discriminant(&error_object(-32001, "nonce too low for 0xcd34"))
+        );
+        assert!(error_discriminant(&"x".repeat(1000)).len() <= MAX_DISCRIMINANT_LEN);
+    }
+
+    #[test]
+    fn test_flush_expired() {
+        let log = RateLimitedLog::new(Duration::from_secs(60), 1);
+        let start = Instant::now();
+        for _ in 0..5 {
+            log.check("site", "a".to_string(), start);
+        }
+        log.check("site", "b".to_string(), start);
+        assert!(log
+            .flush_expired(start + Duration::from_secs(59))
+            .is_empty());
+        // "b" was never suppressed: forgotten without summary
+        assert_eq!(
+            log.flush_expired(start + Duration::from_secs(60)),
+            vec![SuppressedSummary {
+                site: "site",
+                discriminant: "a".to_string(),
+                suppressed: 4,
+            }]
+        );
+        assert!(log
+            .flush_expired(start + Duration::from_secs(120))
+            .is_empty());
+        // flushed: the next occurrence carries no summary
+        assert_eq!(
+            log.check("site", "a".to_string(), start + Duration::from_secs(61)),
+            LogDecision::Log { suppressed: 0 }
+        );
+    }
+}